@@ -0,0 +1,30 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate cranelift_codegen;
+#[macro_use]
+extern crate target_lexicon;
+use cranelift_codegen::random_ir::{is_legalizable, random_function, Entropy};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_codegen::{isa, CodegenError, Context};
+use std::str::FromStr;
+
+fuzz_target!(|data: &[u8]| {
+    let mut flag_builder = settings::builder();
+    flag_builder.enable("enable_verifier").unwrap();
+    let isa = isa::lookup(triple!("x86_64"))
+        .unwrap()
+        .finish(settings::Flags::new(flag_builder));
+
+    let func = random_function(&*isa, &mut Entropy::new(data));
+    if !is_legalizable(&func, &*isa) {
+        return;
+    }
+
+    // Running into a limit is fine, but the verifier must accept the code at every step.
+    let mut context = Context::for_function(func.clone());
+    match context.compile(&*isa) {
+        Ok(_) | Err(CodegenError::ImplLimitExceeded) | Err(CodegenError::Unsupported(_)) => {}
+        Err(err) => panic!("{}\n{}", err, func.display(&*isa)),
+    }
+});
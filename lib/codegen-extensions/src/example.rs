@@ -0,0 +1,92 @@
+//! An example extension, registered with the `example` feature.
+//!
+//! It adds a safepoint poll encoded on x86, and a three-way addition
+//! legalized into two `iadd` instructions, so the tests of `cranelift-codegen`
+//! can check that the instructions, legalizations and encodings of extensions
+//! make it into the generated code.
+
+use meta::base;
+use meta::cdsl::ast::var;
+use meta::cdsl::encodings::EncodingBuilder;
+use meta::cdsl::instructions::{
+    InstructionBuilder as Inst, InstructionGroup, InstructionGroupBuilder,
+};
+use meta::cdsl::isa::TargetIsa;
+use meta::cdsl::operands::Operand;
+use meta::cdsl::recipes::EncodingRecipeBuilder;
+use meta::cdsl::typevar::{Interval, TypeSetBuilder, TypeVar};
+use meta::extension::Extension;
+
+pub struct Example;
+
+impl Extension for Example {
+    fn define_instructions(&self, shared_defs: &base::Definitions) -> InstructionGroup {
+        let mut ig = InstructionGroupBuilder::new(
+            "example",
+            "Example extension instructions",
+            &shared_defs.format_registry,
+        );
+
+        ig.push(
+            Inst::new("safepoint_poll", "Poll for a pending safepoint.").other_side_effects(true),
+        );
+
+        let int = &TypeVar::new(
+            "Int",
+            "A scalar integer type",
+            TypeSetBuilder::new().ints(Interval::All).finish(),
+        );
+        let x = &Operand::new("x", int);
+        let y = &Operand::new("y", int);
+        let z = &Operand::new("z", int);
+        let a = &Operand::new("a", int);
+        ig.push(
+            Inst::new("iadd3", "Wrapping addition of three integers.")
+                .operands_in(vec![x, y, z])
+                .operands_out(vec![a]),
+        );
+
+        ig.finish()
+    }
+
+    fn define_legalizations(
+        &self,
+        shared_defs: &mut base::Definitions,
+        instructions: &InstructionGroup,
+    ) {
+        let iadd = shared_defs.instructions.by_name("iadd");
+        let iadd3 = instructions.by_name("iadd3");
+        let (a, t, x, y, z) = (var("a"), var("t"), var("x"), var("y"), var("z"));
+
+        let expand = shared_defs.transform_groups.by_name("expand");
+        shared_defs.transform_groups.get_mut(expand).legalize(
+            def!(a = iadd3(x, y, z)),
+            vec![def!(t = iadd(x, y)), def!(a = iadd(t, z))],
+        );
+    }
+
+    fn extend_isa(
+        &self,
+        isa: &mut TargetIsa,
+        shared_defs: &base::Definitions,
+        instructions: &InstructionGroup,
+    ) {
+        if isa.name != "x86" {
+            return;
+        }
+
+        // The poll is a `pause` instruction.
+        let formats = &shared_defs.format_registry;
+        let recipe = isa.add_recipe(
+            EncodingRecipeBuilder::new("example_poll", formats.by_name("NullAry"), 2)
+                .emit("sink.put1(0xf3);\nsink.put1(0x90);")
+                .build(formats),
+        );
+        let safepoint_poll = instructions.by_name("safepoint_poll");
+        for mode in &["I32", "I64"] {
+            let encoding =
+                EncodingBuilder::new(safepoint_poll, recipe, 0).build(&isa.recipes, formats);
+            isa.cpu_mode_mut(mode).add_encodings(vec![encoding]);
+        }
+    }
+}
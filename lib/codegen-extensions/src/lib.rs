@@ -0,0 +1,29 @@
+//! Instruction set extensions built into `cranelift-codegen`.
+//!
+//! The build script of `cranelift-codegen` calls `extensions()` and passes the
+//! result to `cranelift_codegen_meta::generate_with_extensions`. This crate
+//! registers no extension, so a project embedding Cranelift adds its own
+//! instructions by replacing it with its own version through the `[patch]`
+//! section of its `Cargo.toml`.
+//!
+//! The extensions are only generated by the Rust meta crate, so
+//! `cranelift-codegen` must be built with its `rust-meta` feature when this
+//! crate registers any.
+
+#![deny(missing_docs, trivial_numeric_casts, unused_extern_crates)]
+
+#[cfg_attr(feature = "example", macro_use)]
+extern crate cranelift_codegen_meta as meta;
+
+#[cfg(feature = "example")]
+mod example;
+
+use meta::extension::Extension;
+
+/// Get the extensions to add to the base instruction set, in order.
+pub fn extensions() -> Vec<Box<dyn Extension>> {
+    let mut extensions: Vec<Box<dyn Extension>> = Vec::new();
+    #[cfg(feature = "example")]
+    extensions.push(Box::new(example::Example));
+    extensions
+}
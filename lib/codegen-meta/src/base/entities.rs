@@ -0,0 +1,57 @@
+//! The `entities` module predefines all the Cranelift entity reference operand
+//! kinds. There are corresponding definitions in the `ir::entities`
+//! Rust module.
+
+use cdsl::operands::OperandKind;
+
+pub struct EntityRefs {
+    /// A reference to an extended basic block in the same function.
+    /// This is primarliy used in control flow instructions.
+    pub ebb: OperandKind,
+
+    /// A reference to a stack slot declared in the function preamble.
+    pub stack_slot: OperandKind,
+
+    /// A reference to a global value.
+    pub global_value: OperandKind,
+
+    /// A reference to a function sugnature declared in the function preamble.
+    /// This is used to provide the call signature in a call_indirect
+    /// instruction.
+    pub sig_ref: OperandKind,
+
+    /// A reference to an external function declared in the function preamble.
+    /// This is used to provide the callee and signature in a call instruction.
+    pub func_ref: OperandKind,
+
+    /// A reference to a jump table declared in the function preamble.
+    pub jump_table: OperandKind,
+
+    /// A reference to a heap declared in the function preamble.
+    pub heap: OperandKind,
+}
+
+impl Default for EntityRefs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EntityRefs {
+    pub fn new() -> Self {
+        Self {
+            ebb: OperandKind::new_entity_ref(
+                "ebb",
+                "An extended basic block in the same function.",
+            )
+            .default_member("destination"),
+            stack_slot: OperandKind::new_entity_ref("stack_slot", "A stack slot."),
+            global_value: OperandKind::new_entity_ref("global_value", "A global value."),
+            sig_ref: OperandKind::new_entity_ref("sig_ref", "A function signature."),
+            func_ref: OperandKind::new_entity_ref("func_ref", "An external function."),
+            jump_table: OperandKind::new_entity_ref("jump_table", "A jump table.")
+                .default_member("table"),
+            heap: OperandKind::new_entity_ref("heap", "A heap."),
+        }
+    }
+}
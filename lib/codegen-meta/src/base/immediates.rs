@@ -0,0 +1,136 @@
+//! The `immediates` module predefines all the Cranelift immediate operand
+//! kinds.
+
+use cdsl::operands::OperandKind;
+
+pub struct Immediates {
+    /// A 64-bit immediate integer operand.
+    ///
+    /// This type of immediate integer can interact with SSA values with any
+    /// `IntType` type.
+    pub imm64: OperandKind,
+
+    /// An unsigned 8-bit immediate integer operand.
+    ///
+    /// This small operand is used to indicate lane indexes in SIMD vectors and
+    /// immediate bit counts on shift instructions.
+    pub uimm8: OperandKind,
+
+    /// An unsigned 32-bit immediate integer operand.
+    pub uimm32: OperandKind,
+
+    /// A 32-bit immediate signed offset.
+    ///
+    /// This is used to represent an immediate address offset in load/store
+    /// instructions.
+    pub offset32: OperandKind,
+
+    /// A 32-bit immediate floating point operand.
+    ///
+    /// IEEE 754-2008 binary32 interchange format.
+    pub ieee32: OperandKind,
+
+    /// A 64-bit immediate floating point operand.
+    ///
+    /// IEEE 754-2008 binary64 interchange format.
+    pub ieee64: OperandKind,
+
+    /// An immediate boolean operand.
+    ///
+    /// This type of immediate boolean can interact with SSA values with any
+    /// `BoolType` type.
+    pub boolean: OperandKind,
+
+    /// A condition code for comparing integer values.
+    ///
+    /// This enumerated operand kind is used for the `icmp` instruction and
+    /// corresponds to the `condcodes::IntCC` Rust type.
+    pub intcc: OperandKind,
+
+    /// A condition code for comparing floating point values.
+    ///
+    /// This enumerated operand kind is used for the `fcmp` instruction and
+    /// corresponds to the `condcodes::FloatCC` Rust type.
+    pub floatcc: OperandKind,
+
+    /// Flags for memory operations like `load` and `store`.
+    pub memflags: OperandKind,
+
+    /// A register unit in the current target ISA.
+    pub regunit: OperandKind,
+
+    /// A trap code indicating the reason for trapping.
+    ///
+    /// The Rust enum type also has a `User(u16)` variant for user-provided
+    /// trap codes.
+    pub trapcode: OperandKind,
+}
+
+impl Default for Immediates {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Immediates {
+    pub fn new() -> Self {
+        Self {
+            imm64: OperandKind::new_imm("imm64", "A 64-bit immediate integer."),
+            uimm8: OperandKind::new_imm("uimm8", "An 8-bit immediate unsigned integer."),
+            uimm32: OperandKind::new_imm("uimm32", "A 32-bit immediate unsigned integer."),
+            offset32: OperandKind::new_imm("offset32", "A 32-bit immediate signed offset.")
+                .default_member("offset"),
+            ieee32: OperandKind::new_imm("ieee32", "A 32-bit immediate floating point number."),
+            ieee64: OperandKind::new_imm("ieee64", "A 64-bit immediate floating point number."),
+            boolean: OperandKind::new_imm("bool", "An immediate boolean.").rust_type("bool"),
+            intcc: OperandKind::new_imm("intcc", "An integer comparison condition code.")
+                .default_member("cond")
+                .rust_type("ir::condcodes::IntCC")
+                .enumerators(vec![
+                    ("eq", "Equal"),
+                    ("ne", "NotEqual"),
+                    ("sge", "SignedGreaterThanOrEqual"),
+                    ("sgt", "SignedGreaterThan"),
+                    ("sle", "SignedLessThanOrEqual"),
+                    ("slt", "SignedLessThan"),
+                    ("uge", "UnsignedGreaterThanOrEqual"),
+                    ("ugt", "UnsignedGreaterThan"),
+                    ("ule", "UnsignedLessThanOrEqual"),
+                    ("ult", "UnsignedLessThan"),
+                ]),
+            floatcc: OperandKind::new_imm("floatcc", "A floating point comparison condition code.")
+                .default_member("cond")
+                .rust_type("ir::condcodes::FloatCC")
+                .enumerators(vec![
+                    ("ord", "Ordered"),
+                    ("uno", "Unordered"),
+                    ("eq", "Equal"),
+                    ("ne", "NotEqual"),
+                    ("one", "OrderedNotEqual"),
+                    ("ueq", "UnorderedOrEqual"),
+                    ("lt", "LessThan"),
+                    ("le", "LessThanOrEqual"),
+                    ("gt", "GreaterThan"),
+                    ("ge", "GreaterThanOrEqual"),
+                    ("ult", "UnorderedOrLessThan"),
+                    ("ule", "UnorderedOrLessThanOrEqual"),
+                    ("ugt", "UnorderedOrGreaterThan"),
+                    ("uge", "UnorderedOrGreaterThanOrEqual"),
+                ]),
+            memflags: OperandKind::new_imm("memflags", "Memory operation flags")
+                .default_member("flags")
+                .rust_type("ir::MemFlags"),
+            regunit: OperandKind::new_imm("regunit", "A register unit in the target ISA")
+                .rust_type("isa::RegUnit"),
+            trapcode: OperandKind::new_imm("trapcode", "A trap reason code.")
+                .default_member("code")
+                .rust_type("ir::TrapCode")
+                .enumerators(vec![
+                    ("stk_ovf", "StackOverflow"),
+                    ("heap_oob", "HeapOutOfBounds"),
+                    ("int_ovf", "IntegerOverflow"),
+                    ("int_divz", "IntegerDivisionByZero"),
+                ]),
+        }
+    }
+}
@@ -0,0 +1,921 @@
+//! Patterns for legalizing the `base` instruction set.
+//!
+//! The base Cretonne instruction set is 'fat', and many instructions don't
+//! have legal representations in a given target ISA. This module defines
+//! legalization patterns that describe how base instructions can be
+//! transformed to other base instructions that are legal.
+
+use cdsl::ast::{var, Literal};
+use cdsl::instructions::InstructionGroup;
+use cdsl::types::ValueType;
+use cdsl::xform::{XFormGroupBuilder, XFormGroups};
+
+use base::immediates::Immediates;
+use base::types::{Float, Int};
+
+pub fn define(insts: &InstructionGroup, imm: &Immediates) -> XFormGroups {
+    let mut narrow = XFormGroupBuilder::new(
+        "narrow",
+        r#"
+        Legalize instructions by narrowing.
+
+        The transformations in the 'narrow' group work by expressing
+        instructions in terms of smaller types. Operations on vector types are
+        expressed in terms of vector types with fewer lanes, and integer
+        operations are expressed in terms of smaller integer types.
+
+        Instructions that can't be narrowed directly fall back to the normal
+        expansions, which are then narrowed in turn.
+        "#,
+    );
+
+    let mut widen = XFormGroupBuilder::new(
+        "widen",
+        r#"
+        Legalize instructions by widening.
+
+        The transformations in the 'widen' group work by expressing
+        instructions in terms of larger types.
+        "#,
+    );
+
+    let mut expand = XFormGroupBuilder::new(
+        "expand",
+        r#"
+        Legalize instructions by expansion.
+
+        Rewrite instructions in terms of other instructions, generally
+        operating on the same types as the original instructions.
+        "#,
+    );
+
+    // Instructions used in the patterns.
+    let atomic_add = insts.by_name("atomic_add");
+    let atomic_sub = insts.by_name("atomic_sub");
+    let band = insts.by_name("band");
+    let band_imm = insts.by_name("band_imm");
+    let band_not = insts.by_name("band_not");
+    let bint = insts.by_name("bint");
+    let bnot = insts.by_name("bnot");
+    let bor = insts.by_name("bor");
+    let bor_imm = insts.by_name("bor_imm");
+    let bor_not = insts.by_name("bor_not");
+    let br_table = insts.by_name("br_table");
+    let clz = insts.by_name("clz");
+    let copy = insts.by_name("copy");
+    let ctz = insts.by_name("ctz");
+    let bxor = insts.by_name("bxor");
+    let bxor_imm = insts.by_name("bxor_imm");
+    let bxor_not = insts.by_name("bxor_not");
+    let f32const = insts.by_name("f32const");
+    let f64const = insts.by_name("f64const");
+    let fadd = insts.by_name("fadd");
+    let fabs = insts.by_name("fabs");
+    let fcopysign = insts.by_name("fcopysign");
+    let fcvt_to_sint = insts.by_name("fcvt_to_sint");
+    let fcvt_to_uint = insts.by_name("fcvt_to_uint");
+    let fdiv = insts.by_name("fdiv");
+    let fmul = insts.by_name("fmul");
+    let fneg = insts.by_name("fneg");
+    let fpromote = insts.by_name("fpromote");
+    let fsub = insts.by_name("fsub");
+    let iadd = insts.by_name("iadd");
+    let iadd_carry = insts.by_name("iadd_carry");
+    let iadd_cin = insts.by_name("iadd_cin");
+    let iadd_cout = insts.by_name("iadd_cout");
+    let iadd_imm = insts.by_name("iadd_imm");
+    let icmp = insts.by_name("icmp");
+    let icmp_imm = insts.by_name("icmp_imm");
+    let iconcat = insts.by_name("iconcat");
+    let iconst = insts.by_name("iconst");
+    let ifcmp = insts.by_name("ifcmp");
+    let ifcmp_imm = insts.by_name("ifcmp_imm");
+    let imul = insts.by_name("imul");
+    let imul_imm = insts.by_name("imul_imm");
+    let ireduce = insts.by_name("ireduce");
+    let irsub_imm = insts.by_name("irsub_imm");
+    let ishl = insts.by_name("ishl");
+    let ishl_imm = insts.by_name("ishl_imm");
+    let isplit = insts.by_name("isplit");
+    let istore8 = insts.by_name("istore8");
+    let istore16 = insts.by_name("istore16");
+    let istore32 = insts.by_name("istore32");
+    let isub = insts.by_name("isub");
+    let isub_bin = insts.by_name("isub_bin");
+    let isub_borrow = insts.by_name("isub_borrow");
+    let isub_bout = insts.by_name("isub_bout");
+    let load = insts.by_name("load");
+    let popcnt = insts.by_name("popcnt");
+    let rotl = insts.by_name("rotl");
+    let rotl_imm = insts.by_name("rotl_imm");
+    let rotr = insts.by_name("rotr");
+    let rotr_imm = insts.by_name("rotr_imm");
+    let sdiv = insts.by_name("sdiv");
+    let sdiv_imm = insts.by_name("sdiv_imm");
+    let select = insts.by_name("select");
+    let sextend = insts.by_name("sextend");
+    let sload8 = insts.by_name("sload8");
+    let sload16 = insts.by_name("sload16");
+    let sload32 = insts.by_name("sload32");
+    let smulhi = insts.by_name("smulhi");
+    let sshr = insts.by_name("sshr");
+    let sshr_imm = insts.by_name("sshr_imm");
+    let srem = insts.by_name("srem");
+    let srem_imm = insts.by_name("srem_imm");
+    let store = insts.by_name("store");
+    let trapif = insts.by_name("trapif");
+    let trapnz = insts.by_name("trapnz");
+    let trapz = insts.by_name("trapz");
+    let udiv = insts.by_name("udiv");
+    let udiv_imm = insts.by_name("udiv_imm");
+    let uextend = insts.by_name("uextend");
+    let uload8 = insts.by_name("uload8");
+    let uload16 = insts.by_name("uload16");
+    let uload32 = insts.by_name("uload32");
+    let umulhi = insts.by_name("umulhi");
+    let urem = insts.by_name("urem");
+    let urem_imm = insts.by_name("urem_imm");
+    let ushr = insts.by_name("ushr");
+    let ushr_imm = insts.by_name("ushr_imm");
+    let vconcat = insts.by_name("vconcat");
+    let vselect = insts.by_name("vselect");
+    let vsplit = insts.by_name("vsplit");
+
+    // Custom expansions for memory objects.
+    expand.custom_legalize(insts.by_name("global_value"), "expand_global_value");
+    expand.custom_legalize(insts.by_name("heap_addr"), "expand_heap_addr");
+
+    // Custom expansions for calls.
+    expand.custom_legalize(insts.by_name("call"), "expand_call");
+
+    // Custom expansions that need to change the CFG.
+    // TODO: Add sufficient XForm syntax that we don't need to hand-code these.
+    expand.custom_legalize(trapz, "expand_cond_trap");
+    expand.custom_legalize(trapnz, "expand_cond_trap");
+    expand.custom_legalize(br_table, "expand_br_table");
+    expand.custom_legalize(select, "expand_select");
+
+    // Custom expansions for floating point constants.
+    // These expansions require bit-casting or creating constant pool entries.
+    expand.custom_legalize(f32const, "expand_fconst");
+    expand.custom_legalize(f64const, "expand_fconst");
+
+    // Custom expansions for stack memory accesses.
+    expand.custom_legalize(insts.by_name("stack_load"), "expand_stack_load");
+    expand.custom_legalize(insts.by_name("stack_store"), "expand_stack_store");
+
+    // Custom narrowing of constants and memory accesses, which need to compute
+    // the immediate or the offset of the high half.
+    narrow.custom_legalize(iconst, "narrow_iconst");
+    narrow.custom_legalize(load, "narrow_load");
+    narrow.custom_legalize(store, "narrow_store");
+
+    // Branches on a split integer only need to know if any of the halves is
+    // zero.
+    narrow.custom_legalize(insts.by_name("brz"), "narrow_brz");
+    narrow.custom_legalize(insts.by_name("brnz"), "narrow_brz");
+
+    // Bit casts that can't be done in registers go through memory.
+    expand.custom_legalize(insts.by_name("bitcast"), "expand_bitcast");
+
+    // Custom expansions of atomic read-modify-write operations into a loop
+    // around a compare-and-swap, for targets that don't have the operations
+    // natively.
+    expand.custom_legalize(atomic_add, "expand_atomic_rmw");
+    expand.custom_legalize(insts.by_name("atomic_and"), "expand_atomic_rmw");
+    expand.custom_legalize(insts.by_name("atomic_or"), "expand_atomic_rmw");
+    expand.custom_legalize(insts.by_name("atomic_xor"), "expand_atomic_rmw");
+    expand.custom_legalize(insts.by_name("atomic_xchg"), "expand_atomic_rmw");
+
+    // List of types.
+    let f32 = ValueType::from(Float::F32);
+    let f64 = ValueType::from(Float::F64);
+    let i8 = ValueType::from(Int::I8);
+    let i16 = ValueType::from(Int::I16);
+    let i32 = ValueType::from(Int::I32);
+    let i64 = ValueType::from(Int::I64);
+
+    // List of variables to reuse in patterns.
+    let x = var("x");
+    let y = var("y");
+    let a = var("a");
+    let a1 = var("a1");
+    let a2 = var("a2");
+    let b = var("b");
+    let b1 = var("b1");
+    let b2 = var("b2");
+    let b3 = var("b3");
+    let b_in = var("b_in");
+    let b_int = var("b_int");
+    let c = var("c");
+    let c1 = var("c1");
+    let c2 = var("c2");
+    let c3 = var("c3");
+    let c4 = var("c4");
+    let c5 = var("c5");
+    let c_in = var("c_in");
+    let c_int = var("c_int");
+    let d = var("d");
+    let d1 = var("d1");
+    let d2 = var("d2");
+    let e1 = var("e1");
+    let e2 = var("e2");
+    let f1 = var("f1");
+    let f2 = var("f2");
+    let g1 = var("g1");
+    let g2 = var("g2");
+    let h1 = var("h1");
+    let h2 = var("h2");
+    let k1 = var("k1");
+    let k2 = var("k2");
+    let z = var("z");
+    let xl = var("xl");
+    let xh = var("xh");
+    let yl = var("yl");
+    let yh = var("yh");
+    let al = var("al");
+    let ah = var("ah");
+    let cc = var("cc");
+    let ptr = var("ptr");
+    let flags = var("flags");
+    let offset = var("off");
+    let cl = var("cl");
+    let ch = var("ch");
+
+    // Split vector operations into two halves. A vector with two lanes is
+    // split into two scalars. These patterns come first so that integer
+    // vectors are split into fewer lanes rather than into narrower lanes by
+    // `isplit`.
+    for &binop in &[
+        iadd, isub, imul, band, bor, bxor, band_not, fadd, fsub, fmul, fdiv,
+    ] {
+        narrow.legalize(
+            def!(a = binop(x, y)),
+            vec![
+                def!((xl, xh) = vsplit(x)),
+                def!((yl, yh) = vsplit(y)),
+                def!(al = binop(xl, yl)),
+                def!(ah = binop(xh, yh)),
+                def!(a = vconcat(al, ah)),
+            ],
+        );
+    }
+
+    narrow.legalize(
+        def!(a = vselect(c, x, y)),
+        vec![
+            def!((cl, ch) = vsplit(c)),
+            def!((xl, xh) = vsplit(x)),
+            def!((yl, yh) = vsplit(y)),
+            def!(al = vselect(cl, xl, yl)),
+            def!(ah = vselect(ch, xh, yh)),
+            def!(a = vconcat(al, ah)),
+        ],
+    );
+
+    narrow.legalize(
+        def!(a = iadd(x, y)),
+        vec![
+            def!((xl, xh) = isplit(x)),
+            def!((yl, yh) = isplit(y)),
+            def!((al, c) = iadd_cout(xl, yl)),
+            def!(ah = iadd_cin(xh, yh, c)),
+            def!(a = iconcat(al, ah)),
+        ],
+    );
+
+    narrow.legalize(
+        def!(a = isub(x, y)),
+        vec![
+            def!((xl, xh) = isplit(x)),
+            def!((yl, yh) = isplit(y)),
+            def!((al, b) = isub_bout(xl, yl)),
+            def!(ah = isub_bin(xh, yh, b)),
+            def!(a = iconcat(al, ah)),
+        ],
+    );
+
+    for &bitop in &[band, bor, bxor] {
+        narrow.legalize(
+            def!(a = bitop(x, y)),
+            vec![
+                def!((xl, xh) = isplit(x)),
+                def!((yl, yh) = isplit(y)),
+                def!(al = bitop(xl, yl)),
+                def!(ah = bitop(xh, yh)),
+                def!(a = iconcat(al, ah)),
+            ],
+        );
+    }
+
+    narrow.legalize(
+        def!(a = select(c, x, y)),
+        vec![
+            def!((xl, xh) = isplit(x)),
+            def!((yl, yh) = isplit(y)),
+            def!(al = select(c, xl, yl)),
+            def!(ah = select(c, xh, yh)),
+            def!(a = iconcat(al, ah)),
+        ],
+    );
+
+    narrow.legalize(
+        def!(a = bnot(x)),
+        vec![
+            def!((xl, xh) = isplit(x)),
+            def!(al = bnot(xl)),
+            def!(ah = bnot(xh)),
+            def!(a = iconcat(al, ah)),
+        ],
+    );
+
+    // The low half of the product only depends on the low halves, and the
+    // cross products only contribute to the high half.
+    narrow.legalize(
+        def!(a = imul(x, y)),
+        vec![
+            def!((xl, xh) = isplit(x)),
+            def!((yl, yh) = isplit(y)),
+            def!(al = imul(xl, yl)),
+            def!(c = umulhi(xl, yl)),
+            def!(c1 = imul(xl, yh)),
+            def!(c2 = imul(xh, yl)),
+            def!(d = iadd(c1, c2)),
+            def!(ah = iadd(c, d)),
+            def!(a = iconcat(al, ah)),
+        ],
+    );
+
+    // The high half of the full product accumulates the partial products of
+    // the halves, one 32-bit column at a time.
+    let imm64_0 = Literal::constant(&imm.imm64, 0);
+    narrow.legalize(
+        def!(a = umulhi(x, y)),
+        vec![
+            def!((xl, xh) = isplit(x)),
+            def!((yl, yh) = isplit(y)),
+            def!(z = iconst.i32(imm64_0)),
+            def!(c = umulhi(xl, yl)),
+            def!(d1 = imul(xh, yl)),
+            def!(d2 = umulhi(xh, yl)),
+            def!((e1, c1) = iadd_cout(d1, c)),
+            def!(e2 = iadd_cin(d2, z, c1)),
+            def!(f1 = imul(xl, yh)),
+            def!(f2 = umulhi(xl, yh)),
+            def!((g1, c2) = iadd_cout(f1, e1)),
+            def!(g2 = iadd_cin(f2, z, c2)),
+            def!(h1 = imul(xh, yh)),
+            def!(h2 = umulhi(xh, yh)),
+            def!((k1, c3) = iadd_cout(h1, e2)),
+            def!(k2 = iadd_cin(h2, z, c3)),
+            def!((al, c4) = iadd_cout(k1, g2)),
+            def!(ah = iadd_cin(k2, z, c4)),
+            def!(a = iconcat(al, ah)),
+        ],
+    );
+
+    // The signed high half subtracts the other operand for each negative
+    // operand.
+    let imm64_31 = Literal::constant(&imm.imm64, 31);
+    narrow.legalize(
+        def!(a = smulhi(x, y)),
+        vec![
+            def!(b = umulhi(x, y)),
+            def!((xl, xh) = isplit(x)),
+            def!((yl, yh) = isplit(y)),
+            def!(c1 = sshr_imm(xh, imm64_31)),
+            def!(d1 = iconcat(c1, c1)),
+            def!(e1 = band(d1, y)),
+            def!(c2 = sshr_imm(yh, imm64_31)),
+            def!(d2 = iconcat(c2, c2)),
+            def!(e2 = band(d2, x)),
+            def!(f1 = isub(b, e1)),
+            def!(a = isub(f1, e2)),
+        ],
+    );
+
+    narrow.legalize(
+        def!(a = copy(x)),
+        vec![
+            def!((xl, xh) = isplit(x)),
+            def!(al = copy(xl)),
+            def!(ah = copy(xh)),
+            def!(a = iconcat(al, ah)),
+        ],
+    );
+
+    // Equality compares both halves.
+    let intcc_eq = Literal::enumerator_for(&imm.intcc, "eq");
+    narrow.legalize(
+        def!(b = icmp(intcc_eq, x, y)),
+        vec![
+            def!((xl, xh) = isplit(x)),
+            def!((yl, yh) = isplit(y)),
+            def!(b1 = icmp(intcc_eq, xl, yl)),
+            def!(b2 = icmp(intcc_eq, xh, yh)),
+            def!(b = band(b1, b2)),
+        ],
+    );
+
+    let intcc_ne = Literal::enumerator_for(&imm.intcc, "ne");
+    narrow.legalize(
+        def!(b = icmp(intcc_ne, x, y)),
+        vec![
+            def!((xl, xh) = isplit(x)),
+            def!((yl, yh) = isplit(y)),
+            def!(b1 = icmp(intcc_ne, xl, yl)),
+            def!(b2 = icmp(intcc_ne, xh, yh)),
+            def!(b = bor(b1, b2)),
+        ],
+    );
+
+    // Ordered comparisons are decided by the high halves, unless they are
+    // equal. The low halves are always compared as unsigned numbers.
+    for &(cond, cond_hi, cond_lo) in &[
+        ("slt", "slt", "ult"),
+        ("sge", "sgt", "uge"),
+        ("sgt", "sgt", "ugt"),
+        ("sle", "slt", "ule"),
+        ("ult", "ult", "ult"),
+        ("uge", "ugt", "uge"),
+        ("ugt", "ugt", "ugt"),
+        ("ule", "ult", "ule"),
+    ] {
+        let cond = Literal::enumerator_for(&imm.intcc, cond);
+        let cond_hi = Literal::enumerator_for(&imm.intcc, cond_hi);
+        let cond_lo = Literal::enumerator_for(&imm.intcc, cond_lo);
+        narrow.legalize(
+            def!(b = icmp(cond, x, y)),
+            vec![
+                def!((xl, xh) = isplit(x)),
+                def!((yl, yh) = isplit(y)),
+                def!(b1 = icmp(cond_hi, xh, yh)),
+                def!(b2 = icmp(intcc_eq, xh, yh)),
+                def!(b3 = icmp(cond_lo, xl, yl)),
+                def!(c = band(b2, b3)),
+                def!(b = bor(b1, c)),
+            ],
+        );
+    }
+
+    // Bit counts. The count of the high half is 32 exactly when the low half
+    // needs to be counted too.
+    let imm64_5 = Literal::constant(&imm.imm64, 5);
+    for &(count, first, second) in &[(clz, xh, xl), (ctz, xl, xh)] {
+        narrow.legalize(
+            def!(a = count.i64(x)),
+            vec![
+                def!((xl, xh) = isplit(x)),
+                def!(c1 = count(first)),
+                def!(c2 = count(second)),
+                def!(c3 = ushr_imm(c1, imm64_5)),
+                def!(c4 = irsub_imm(c3, imm64_0)),
+                def!(c5 = band(c2, c4)),
+                def!(al = iadd(c1, c5)),
+                def!(ah = iconst.i32(imm64_0)),
+                def!(a = iconcat(al, ah)),
+            ],
+        );
+    }
+
+    narrow.legalize(
+        def!(a = popcnt.i64(x)),
+        vec![
+            def!((xl, xh) = isplit(x)),
+            def!(c1 = popcnt(xl)),
+            def!(c2 = popcnt(xh)),
+            def!(al = iadd(c1, c2)),
+            def!(ah = iconst.i32(imm64_0)),
+            def!(a = iconcat(al, ah)),
+        ],
+    );
+
+    // Extensions to 64 bits compute the high half from the extended low half.
+    for &int_ty in &[&i8, &i16] {
+        narrow.legalize(
+            def!(a = uextend.i64.int_ty(x)),
+            vec![
+                def!(al = uextend.i32(x)),
+                def!(ah = iconst.i32(imm64_0)),
+                def!(a = iconcat(al, ah)),
+            ],
+        );
+        narrow.legalize(
+            def!(a = sextend.i64.int_ty(x)),
+            vec![
+                def!(al = sextend.i32(x)),
+                def!(ah = sshr_imm(al, imm64_31)),
+                def!(a = iconcat(al, ah)),
+            ],
+        );
+    }
+
+    narrow.legalize(
+        def!(a = uextend.i64.i32(x)),
+        vec![def!(ah = iconst.i32(imm64_0)), def!(a = iconcat(x, ah))],
+    );
+
+    narrow.legalize(
+        def!(a = sextend.i64.i32(x)),
+        vec![def!(ah = sshr_imm(x, imm64_31)), def!(a = iconcat(x, ah))],
+    );
+
+    narrow.legalize(
+        def!(a = bint.i64(x)),
+        vec![
+            def!(al = bint.i32(x)),
+            def!(ah = iconst.i32(imm64_0)),
+            def!(a = iconcat(al, ah)),
+        ],
+    );
+
+    // Narrow loads and stores only access the low half in memory.
+    for &(load_inst, ext) in &[
+        (uload8, uextend),
+        (uload16, uextend),
+        (sload8, sextend),
+        (sload16, sextend),
+    ] {
+        narrow.legalize(
+            def!(a = load_inst.i64(flags, ptr, offset)),
+            vec![
+                def!(b = load_inst.i32(flags, ptr, offset)),
+                def!(a = ext.i64(b)),
+            ],
+        );
+    }
+
+    for &store_inst in &[istore8, istore16] {
+        narrow.legalize(
+            def!(store_inst.i64(flags, x, ptr, offset)),
+            vec![
+                def!((xl, xh) = isplit(x)),
+                def!(store_inst(flags, xl, ptr, offset)),
+            ],
+        );
+    }
+
+    // There is no 64-bit conversion from f32, but it is exact to go through
+    // f64.
+    for &cvt in &[fcvt_to_sint, fcvt_to_uint] {
+        narrow.legalize(
+            def!(a = cvt.i64.f32(x)),
+            vec![def!(b = fpromote.f64(x)), def!(a = cvt(b))],
+        );
+    }
+
+    for &int_ty in &[&i8, &i16] {
+        widen.legalize(
+            def!(a = iconst.int_ty(b)),
+            vec![def!(c = iconst.i32(b)), def!(a = ireduce.int_ty(c))],
+        );
+    }
+
+    widen.legalize(
+        def!(store.i8(flags, a, ptr, offset)),
+        vec![
+            def!(b = uextend.i32(a)),
+            def!(istore8(flags, b, ptr, offset)),
+        ],
+    );
+
+    widen.legalize(
+        def!(store.i16(flags, a, ptr, offset)),
+        vec![
+            def!(b = uextend.i32(a)),
+            def!(istore16(flags, b, ptr, offset)),
+        ],
+    );
+
+    widen.legalize(
+        def!(a = load.i8(flags, ptr, offset)),
+        vec![
+            def!(b = uload8.i32(flags, ptr, offset)),
+            def!(a = ireduce(b)),
+        ],
+    );
+
+    widen.legalize(
+        def!(a = load.i16(flags, ptr, offset)),
+        vec![
+            def!(b = uload16.i32(flags, ptr, offset)),
+            def!(a = ireduce(b)),
+        ],
+    );
+
+    for &binop in &[iadd, isub, imul, udiv, band, bor, bxor] {
+        for &int_ty in &[&i8, &i16] {
+            widen.legalize(
+                def!(a = binop.int_ty(x, y)),
+                vec![
+                    def!(b = uextend.i32(x)),
+                    def!(c = uextend.i32(y)),
+                    def!(d = binop(b, c)),
+                    def!(a = ireduce(d)),
+                ],
+            );
+        }
+    }
+
+    for &binop in &[sdiv] {
+        for &int_ty in &[&i8, &i16] {
+            widen.legalize(
+                def!(a = binop.int_ty(x, y)),
+                vec![
+                    def!(b = sextend.i32(x)),
+                    def!(c = sextend.i32(y)),
+                    def!(d = binop(b, c)),
+                    def!(a = ireduce(d)),
+                ],
+            );
+        }
+    }
+
+    for &unop in &[bnot] {
+        for &int_ty in &[&i8, &i16] {
+            widen.legalize(
+                def!(a = unop.int_ty(x)),
+                vec![
+                    def!(b = sextend.i32(x)),
+                    def!(d = unop(b)),
+                    def!(a = ireduce(d)),
+                ],
+            );
+        }
+    }
+
+    for &binop in &[
+        iadd_imm, imul_imm, udiv_imm, urem_imm, band_imm, bor_imm, bxor_imm,
+    ] {
+        for &int_ty in &[&i8, &i16] {
+            widen.legalize(
+                def!(a = binop.int_ty(x, y)),
+                vec![
+                    def!(b = uextend.i32(x)),
+                    def!(c = binop(b, y)),
+                    def!(a = ireduce(c)),
+                ],
+            );
+        }
+    }
+
+    for &binop in &[sdiv_imm, srem_imm] {
+        for &int_ty in &[&i8, &i16] {
+            widen.legalize(
+                def!(a = binop.int_ty(x, y)),
+                vec![
+                    def!(b = sextend.i32(x)),
+                    def!(c = binop(b, y)),
+                    def!(a = ireduce(c)),
+                ],
+            );
+        }
+    }
+
+    for &int_ty in &[&i8, &i16] {
+        widen.legalize(
+            def!(a = ireduce.int_ty.i64(x)),
+            vec![def!((b, xh) = isplit(x)), def!(a = ireduce(b))],
+        );
+    }
+
+    for &int_ty in &[&i8, &i16] {
+        widen.legalize(
+            def!(br_table.int_ty(x, y)),
+            vec![def!(b = uextend.i32(x)), def!(br_table(b, y))],
+        );
+    }
+
+    // Expand integer operations with carry for RISC architectures that don't
+    // have the flags.
+    let intcc_ult = Literal::enumerator_for(&imm.intcc, "ult");
+    expand.legalize(
+        def!((a, c) = iadd_cout(x, y)),
+        vec![def!(a = iadd(x, y)), def!(c = icmp(intcc_ult, a, x))],
+    );
+
+    let intcc_ugt = Literal::enumerator_for(&imm.intcc, "ugt");
+    expand.legalize(
+        def!((a, b) = isub_bout(x, y)),
+        vec![def!(a = isub(x, y)), def!(b = icmp(intcc_ugt, a, x))],
+    );
+
+    expand.legalize(
+        def!(a = iadd_cin(x, y, c)),
+        vec![
+            def!(a1 = iadd(x, y)),
+            def!(c_int = bint(c)),
+            def!(a = iadd(a1, c_int)),
+        ],
+    );
+
+    expand.legalize(
+        def!(a = isub_bin(x, y, b)),
+        vec![
+            def!(a1 = isub(x, y)),
+            def!(b_int = bint(b)),
+            def!(a = isub(a1, b_int)),
+        ],
+    );
+
+    expand.legalize(
+        def!((a, c) = iadd_carry(x, y, c_in)),
+        vec![
+            def!((a1, c1) = iadd_cout(x, y)),
+            def!(c_int = bint(c_in)),
+            def!((a, c2) = iadd_cout(a1, c_int)),
+            def!(c = bor(c1, c2)),
+        ],
+    );
+
+    expand.legalize(
+        def!((a, b) = isub_borrow(x, y, b_in)),
+        vec![
+            def!((a1, b1) = isub_bout(x, y)),
+            def!(b_int = bint(b_in)),
+            def!((a, b2) = isub_bout(a1, b_int)),
+            def!(b = bor(b1, b2)),
+        ],
+    );
+
+    // Expansions for immediate operands that are out of range.
+    for &(inst_imm, inst) in &[
+        (iadd_imm, iadd),
+        (imul_imm, imul),
+        (sdiv_imm, sdiv),
+        (udiv_imm, udiv),
+        (srem_imm, srem),
+        (urem_imm, urem),
+        (band_imm, band),
+        (bor_imm, bor),
+        (bxor_imm, bxor),
+        (ifcmp_imm, ifcmp),
+    ] {
+        expand.legalize(
+            def!(a = inst_imm(x, y)),
+            vec![def!(a1 = iconst(y)), def!(a = inst(x, a1))],
+        );
+    }
+
+    expand.legalize(
+        def!(a = irsub_imm(y, x)),
+        vec![def!(a1 = iconst(x)), def!(a = isub(a1, y))],
+    );
+
+    // Rotates and shifts.
+    for &(inst_imm, inst) in &[
+        (rotl_imm, rotl),
+        (rotr_imm, rotr),
+        (ishl_imm, ishl),
+        (sshr_imm, sshr),
+        (ushr_imm, ushr),
+    ] {
+        expand.legalize(
+            def!(a = inst_imm(x, y)),
+            vec![def!(a1 = iconst.i32(y)), def!(a = inst(x, a1))],
+        );
+    }
+
+    // Rotates are composed from shifts, whose amounts are taken modulo the
+    // number of bits.
+    for &(rot, shl, shr) in &[(rotl, ishl, ushr), (rotr, ushr, ishl)] {
+        expand.legalize(
+            def!(a = rot(x, y)),
+            vec![
+                def!(a1 = shl(x, y)),
+                def!(b = irsub_imm(y, imm64_0)),
+                def!(a2 = shr(x, b)),
+                def!(a = bor(a1, a2)),
+            ],
+        );
+    }
+
+    // Reducing a 64-bit integer on a 32-bit target only needs its low half. The
+    // split produces new values, so the result is a copy of the low half.
+    expand.legalize(
+        def!(a = ireduce.i32.i64(x)),
+        vec![def!((xl, xh) = isplit(x)), def!(a = copy(xl))],
+    );
+
+    expand.legalize(
+        def!(a = icmp_imm(cc, x, y)),
+        vec![def!(a1 = iconst(y)), def!(a = icmp(cc, x, a1))],
+    );
+
+    // Expansions for *_not variants of bitwise ops.
+    for &(inst_not, inst) in &[(band_not, band), (bor_not, bor), (bxor_not, bxor)] {
+        expand.legalize(
+            def!(a = inst_not(x, y)),
+            vec![def!(a1 = bnot(y)), def!(a = inst(x, a1))],
+        );
+    }
+
+    // The 32-bit loads and stores of i64 values are monomorphic, so they go
+    // through the expansions on 32-bit targets.
+    expand.legalize(
+        def!(a = uload32(flags, ptr, offset)),
+        vec![
+            def!(b = load.i32(flags, ptr, offset)),
+            def!(a = uextend.i64(b)),
+        ],
+    );
+
+    expand.legalize(
+        def!(a = sload32(flags, ptr, offset)),
+        vec![
+            def!(b = load.i32(flags, ptr, offset)),
+            def!(a = sextend.i64(b)),
+        ],
+    );
+
+    expand.legalize(
+        def!(istore32(flags, x, ptr, offset)),
+        vec![
+            def!((xl, xh) = isplit(x)),
+            def!(store(flags, xl, ptr, offset)),
+        ],
+    );
+
+    // Expand bnot using xor.
+    let minus_one = Literal::constant(&imm.imm64, -1);
+    expand.legalize(
+        def!(a = bnot(x)),
+        vec![def!(y = iconst(minus_one)), def!(a = bxor(x, y))],
+    );
+
+    // Floating-point sign manipulations.
+    for &(ty, const_inst, ref minus_zero) in &[
+        (&f32, f32const, Literal::bits(&imm.ieee32, 0x8000_0000)),
+        (
+            &f64,
+            f64const,
+            Literal::bits(&imm.ieee64, 0x8000_0000_0000_0000),
+        ),
+    ] {
+        expand.legalize(
+            def!(a = fabs.ty(x)),
+            vec![def!(b = const_inst(minus_zero)), def!(a = band_not(x, b))],
+        );
+        expand.legalize(
+            def!(a = fneg.ty(x)),
+            vec![def!(b = const_inst(minus_zero)), def!(a = bxor(x, b))],
+        );
+        expand.legalize(
+            def!(a = fcopysign.ty(x, y)),
+            vec![
+                def!(b = const_inst(minus_zero)),
+                def!(a1 = band_not(x, b)),
+                def!(a2 = band(y, b)),
+                def!(a = bor(a1, a2)),
+            ],
+        );
+    }
+
+    // Subtract atomically by adding the negated operand.
+    expand.legalize(
+        def!(a = atomic_sub(flags, x, ptr, offset)),
+        vec![
+            def!(y = irsub_imm(x, imm64_0)),
+            def!(a = atomic_add(flags, y, ptr, offset)),
+        ],
+    );
+
+    let mut groups = XFormGroups::new();
+
+    let expand_id = expand.finish_and_add_to(&mut groups);
+    narrow.chain(expand_id).finish_and_add_to(&mut groups);
+    widen.finish_and_add_to(&mut groups);
+
+    // Expansions using CPU flags.
+    let mut expand_flags = XFormGroupBuilder::new(
+        "expand_flags",
+        r#"
+        Instruction expansions for architectures with flags.
+
+        Expand some instructions using CPU flags, then fall back to the normal
+        expansions. Not all architectures support CPU flags, so these patterns
+        are kept separate.
+        "#,
+    )
+    .chain(expand_id);
+
+    expand_flags.legalize(
+        def!(trapnz(x, c)),
+        vec![
+            def!(a = ifcmp_imm(x, imm64_0)),
+            def!(trapif(intcc_ne, a, c)),
+        ],
+    );
+
+    expand_flags.legalize(
+        def!(trapz(x, c)),
+        vec![
+            def!(a = ifcmp_imm(x, imm64_0)),
+            def!(trapif(intcc_eq, a, c)),
+        ],
+    );
+
+    expand_flags.finish_and_add_to(&mut groups);
+
+    groups
+}
@@ -1,3 +1,56 @@
 //! Definitions for the base Cretonne language.
 
+pub mod entities;
+pub mod formats;
+pub mod immediates;
+pub mod instructions;
+pub mod legalize;
+pub mod semantics;
+pub mod settings;
 pub mod types;
+
+use cdsl::formats::FormatRegistry;
+use cdsl::instructions::InstructionGroup;
+use cdsl::isa::TargetIsa;
+use cdsl::xform::XFormGroups;
+
+/// The definitions shared by all the target ISAs.
+pub struct Definitions {
+    pub imm: immediates::Immediates,
+    pub entities: entities::EntityRefs,
+    pub format_registry: FormatRegistry,
+    pub instructions: InstructionGroup,
+    /// The legalization transformation groups, including the groups added
+    /// by the target ISAs.
+    pub transform_groups: XFormGroups,
+    /// The instructions added by out-of-tree extensions, numbered after the
+    /// instructions of the target ISAs.
+    pub extension_instructions: Vec<InstructionGroup>,
+}
+
+impl Definitions {
+    /// Get all the instruction groups in opcode order: The base instructions,
+    /// followed by the ones of the `isas` and the ones of the extensions.
+    pub fn instruction_groups<'a>(&'a self, isas: &'a [TargetIsa]) -> Vec<&'a InstructionGroup> {
+        let mut groups = vec![&self.instructions];
+        groups.extend(isas.iter().map(|isa| &isa.instructions));
+        groups.extend(self.extension_instructions.iter());
+        groups
+    }
+}
+
+pub fn define() -> Definitions {
+    let imm = immediates::Immediates::new();
+    let entities = entities::EntityRefs::new();
+    let format_registry = formats::define(&imm, &entities);
+    let instructions = instructions::define(&format_registry, &imm, &entities);
+    let transform_groups = legalize::define(&instructions, &imm);
+    Definitions {
+        imm,
+        entities,
+        format_registry,
+        instructions,
+        transform_groups,
+        extension_instructions: Vec::new(),
+    }
+}
@@ -0,0 +1,569 @@
+//! Semantics of the base instruction set.
+//!
+//! The semantics of an instruction are described by transformations into
+//! primitive instructions, or into other instructions with known semantics,
+//! see the `semantics` module.
+
+use cdsl::ast::{var, Literal, Var};
+use cdsl::instructions::InstructionGroup;
+use cdsl::type_inference::Constraint;
+use cdsl::typevar::{Interval, TypeSetBuilder};
+
+use base::immediates::Immediates;
+use semantics::InstructionSemantics;
+
+pub fn define(
+    insts: &InstructionGroup,
+    immediates: &Immediates,
+    primitives: &InstructionGroup,
+    macros: &InstructionGroup,
+    semantics: &mut InstructionSemantics,
+) {
+    let band = insts.by_name("band");
+    let band_imm = insts.by_name("band_imm");
+    let band_not = insts.by_name("band_not");
+    let bextend = insts.by_name("bextend");
+    let bint = insts.by_name("bint");
+    let bnot = insts.by_name("bnot");
+    let bor = insts.by_name("bor");
+    let bor_imm = insts.by_name("bor_imm");
+    let bor_not = insts.by_name("bor_not");
+    let bxor = insts.by_name("bxor");
+    let bxor_imm = insts.by_name("bxor_imm");
+    let bxor_not = insts.by_name("bxor_not");
+    let iadd = insts.by_name("iadd");
+    let iadd_carry = insts.by_name("iadd_carry");
+    let iadd_cin = insts.by_name("iadd_cin");
+    let iadd_cout = insts.by_name("iadd_cout");
+    let iadd_imm = insts.by_name("iadd_imm");
+    let icmp = insts.by_name("icmp");
+    let icmp_imm = insts.by_name("icmp_imm");
+    let iconcat = insts.by_name("iconcat");
+    let iconst = insts.by_name("iconst");
+    let imul = insts.by_name("imul");
+    let imul_imm = insts.by_name("imul_imm");
+    let ireduce = insts.by_name("ireduce");
+    let irsub_imm = insts.by_name("irsub_imm");
+    let isplit = insts.by_name("isplit");
+    let isub = insts.by_name("isub");
+    let isub_bin = insts.by_name("isub_bin");
+    let isub_borrow = insts.by_name("isub_borrow");
+    let isub_bout = insts.by_name("isub_bout");
+    let select = insts.by_name("select");
+    let sextend = insts.by_name("sextend");
+    let uextend = insts.by_name("uextend");
+    let vconcat = insts.by_name("vconcat");
+    let vsplit = insts.by_name("vsplit");
+
+    let prim_to_bv = primitives.by_name("prim_to_bv");
+    let prim_from_bv = primitives.by_name("prim_from_bv");
+    let bv_from_imm64 = primitives.by_name("bv_from_imm64");
+    let bvadd = primitives.by_name("bvadd");
+    let bvand = primitives.by_name("bvand");
+    let bvconcat = primitives.by_name("bvconcat");
+    let bvite = primitives.by_name("bvite");
+    let bvmul = primitives.by_name("bvmul");
+    let bvne = primitives.by_name("bvne");
+    let bvnot = primitives.by_name("bvnot");
+    let bvor = primitives.by_name("bvor");
+    let bvreduce = primitives.by_name("bvreduce");
+    let bvsignext = primitives.by_name("bvsignext");
+    let bvsplit = primitives.by_name("bvsplit");
+    let bvsub = primitives.by_name("bvsub");
+    let bvult = primitives.by_name("bvult");
+    let bvxor = primitives.by_name("bvxor");
+    let bvzeroext = primitives.by_name("bvzeroext");
+
+    let bool2bv = macros.by_name("bool2bv");
+
+    let a = var("a");
+    let b = var("b");
+    let c = var("c");
+    let b_in = var("b_in");
+    let b_out = var("b_out");
+    let c_in = var("c_in");
+    let c_out = var("c_out");
+    let cc = var("cc");
+    let imm = var("imm");
+    let x = var("x");
+    let y = var("y");
+    let alo = var("alo");
+    let ahi = var("ahi");
+    let blo = var("blo");
+    let bhi = var("bhi");
+    let lo = var("lo");
+    let hi = var("hi");
+    let xlo = var("xlo");
+    let xhi = var("xhi");
+    let ylo = var("ylo");
+    let yhi = var("yhi");
+    let ba = var("ba");
+    let bc = var("bc");
+    let bc_out = var("bc_out");
+    let bc_out1 = var("bc_out1");
+    let bc_out2 = var("bc_out2");
+    let bva = var("bva");
+    let bva_wide = var("bva_wide");
+    let bvb_in = var("bvb_in");
+    let bvb_out = var("bvb_out");
+    let bvc = var("bvc");
+    let bvc_in = var("bvc_in");
+    let bvc_out = var("bvc_out");
+    let bvc_out1 = var("bvc_out1");
+    let bvc_out2 = var("bvc_out2");
+    let bvhi = var("bvhi");
+    let bvlo = var("bvlo");
+    let bvs = var("bvs");
+    let bvt = var("bvt");
+    let bvx = var("bvx");
+    let bvy = var("bvy");
+    let bvz = var("bvz");
+
+    let scalar_ts = TypeSetBuilder::new()
+        .ints(Interval::All)
+        .floats(Interval::All)
+        .bools(Interval::All)
+        .finish();
+    let scalar = |v: Var| vec![Constraint::in_typeset(v.type_var(), scalar_ts.clone())];
+    let b1_ts = TypeSetBuilder::new().bools(1..=1).finish();
+    let zero = Literal::constant(&immediates.imm64, 0);
+
+    semantics.add(
+        def!((lo, hi) = vsplit(x)),
+        vec![
+            def!(bvx = prim_to_bv(x)),
+            def!((bvlo, bvhi) = bvsplit(bvx)),
+            def!(lo = prim_from_bv(bvlo)),
+            def!(hi = prim_from_bv(bvhi)),
+        ],
+        vec![],
+    );
+
+    semantics.add(
+        def!(x = vconcat(lo, hi)),
+        vec![
+            def!(bvlo = prim_to_bv(lo)),
+            def!(bvhi = prim_to_bv(hi)),
+            def!(bvx = bvconcat(bvlo, bvhi)),
+            def!(x = prim_from_bv(bvx)),
+        ],
+        vec![],
+    );
+
+    semantics.add(
+        def!(a = iadd(x, y)),
+        vec![
+            def!(bvx = prim_to_bv(x)),
+            def!(bvy = prim_to_bv(y)),
+            def!(bva = bvadd(bvx, bvy)),
+            def!(a = prim_from_bv(bva)),
+        ],
+        scalar(x),
+    );
+    semantics.add(
+        def!(a = iadd(x, y)),
+        vec![
+            def!((xlo, xhi) = vsplit(x)),
+            def!((ylo, yhi) = vsplit(y)),
+            def!(alo = iadd(xlo, ylo)),
+            def!(ahi = iadd(xhi, yhi)),
+            def!(a = vconcat(alo, ahi)),
+        ],
+        vec![],
+    );
+
+    semantics.add(
+        def!(a = isub(x, y)),
+        vec![
+            def!(bvx = prim_to_bv(x)),
+            def!(bvy = prim_to_bv(y)),
+            def!(bva = bvsub(bvx, bvy)),
+            def!(a = prim_from_bv(bva)),
+        ],
+        scalar(x),
+    );
+    semantics.add(
+        def!(a = isub(x, y)),
+        vec![
+            def!((xlo, xhi) = vsplit(x)),
+            def!((ylo, yhi) = vsplit(y)),
+            def!(alo = isub(xlo, ylo)),
+            def!(ahi = isub(xhi, yhi)),
+            def!(a = vconcat(alo, ahi)),
+        ],
+        vec![],
+    );
+
+    semantics.add(
+        def!(a = imul(x, y)),
+        vec![
+            def!(bvx = prim_to_bv(x)),
+            def!(bvy = prim_to_bv(y)),
+            def!(bva = bvmul(bvx, bvy)),
+            def!(a = prim_from_bv(bva)),
+        ],
+        scalar(x),
+    );
+    semantics.add(
+        def!(a = imul(x, y)),
+        vec![
+            def!((xlo, xhi) = vsplit(x)),
+            def!((ylo, yhi) = vsplit(y)),
+            def!(alo = imul(xlo, ylo)),
+            def!(ahi = imul(xhi, yhi)),
+            def!(a = vconcat(alo, ahi)),
+        ],
+        vec![],
+    );
+
+    // Integer arithmetic with carry and/or borrow.
+
+    semantics.add(
+        def!(a = iadd_cin(x, y, c_in)),
+        vec![
+            def!(bvx = prim_to_bv(x)),
+            def!(bvy = prim_to_bv(y)),
+            def!(bvc_in = prim_to_bv(c_in)),
+            def!(bvs = bvzeroext(bvc_in)),
+            def!(bvt = bvadd(bvx, bvy)),
+            def!(bva = bvadd(bvt, bvs)),
+            def!(a = prim_from_bv(bva)),
+        ],
+        vec![],
+    );
+
+    semantics.add(
+        def!((a, c_out) = iadd_cout(x, y)),
+        vec![
+            def!(bvx = prim_to_bv(x)),
+            def!(bvy = prim_to_bv(y)),
+            def!(bva = bvadd(bvx, bvy)),
+            def!(bc_out = bvult(bva, bvx)),
+            def!(bvc_out = bool2bv(bc_out)),
+            def!(a = prim_from_bv(bva)),
+            def!(c_out = prim_from_bv(bvc_out)),
+        ],
+        vec![],
+    );
+
+    semantics.add(
+        def!((a, c_out) = iadd_carry(x, y, c_in)),
+        vec![
+            def!(bvx = prim_to_bv(x)),
+            def!(bvy = prim_to_bv(y)),
+            def!(bvc_in = prim_to_bv(c_in)),
+            def!(bvs = bvzeroext(bvc_in)),
+            def!(bvt = bvadd(bvx, bvy)),
+            def!(bva = bvadd(bvt, bvs)),
+            // There is a carry out if either addition wraps around.
+            def!(bc_out1 = bvult(bvt, bvx)),
+            def!(bc_out2 = bvult(bva, bvt)),
+            def!(bvc_out1 = bool2bv(bc_out1)),
+            def!(bvc_out2 = bool2bv(bc_out2)),
+            def!(bvc_out = bvor(bvc_out1, bvc_out2)),
+            def!(a = prim_from_bv(bva)),
+            def!(c_out = prim_from_bv(bvc_out)),
+        ],
+        vec![],
+    );
+
+    semantics.add(
+        def!(a = isub_bin(x, y, b_in)),
+        vec![
+            def!(bvx = prim_to_bv(x)),
+            def!(bvy = prim_to_bv(y)),
+            def!(bvb_in = prim_to_bv(b_in)),
+            def!(bvs = bvzeroext(bvb_in)),
+            def!(bvt = bvsub(bvx, bvy)),
+            def!(bva = bvsub(bvt, bvs)),
+            def!(a = prim_from_bv(bva)),
+        ],
+        vec![],
+    );
+
+    semantics.add(
+        def!((a, b_out) = isub_bout(x, y)),
+        vec![
+            def!(bvx = prim_to_bv(x)),
+            def!(bvy = prim_to_bv(y)),
+            def!(bva = bvsub(bvx, bvy)),
+            def!(bc_out = bvult(bvx, bvy)),
+            def!(bvb_out = bool2bv(bc_out)),
+            def!(a = prim_from_bv(bva)),
+            def!(b_out = prim_from_bv(bvb_out)),
+        ],
+        vec![],
+    );
+
+    semantics.add(
+        def!((a, b_out) = isub_borrow(x, y, b_in)),
+        vec![
+            def!(bvx = prim_to_bv(x)),
+            def!(bvy = prim_to_bv(y)),
+            def!(bvb_in = prim_to_bv(b_in)),
+            def!(bvs = bvzeroext(bvb_in)),
+            def!(bvt = bvsub(bvx, bvy)),
+            def!(bva = bvsub(bvt, bvs)),
+            // There is a borrow out if either subtraction wraps around.
+            def!(bc_out1 = bvult(bvx, bvy)),
+            def!(bc_out2 = bvult(bvt, bvs)),
+            def!(bvc_out1 = bool2bv(bc_out1)),
+            def!(bvc_out2 = bool2bv(bc_out2)),
+            def!(bvb_out = bvor(bvc_out1, bvc_out2)),
+            def!(a = prim_from_bv(bva)),
+            def!(b_out = prim_from_bv(bvb_out)),
+        ],
+        vec![],
+    );
+
+    // Operations with an immediate operand.
+
+    // The materialization of vector constants isn't specified.
+    semantics.add(
+        def!(a = iconst(imm)),
+        vec![def!(bva = bv_from_imm64(imm)), def!(a = prim_from_bv(bva))],
+        scalar(a),
+    );
+
+    for &(inst, bvop) in &[
+        (iadd_imm, bvadd),
+        (imul_imm, bvmul),
+        (band_imm, bvand),
+        (bor_imm, bvor),
+        (bxor_imm, bvxor),
+    ] {
+        semantics.add(
+            def!(a = inst(x, imm)),
+            vec![
+                def!(bvx = prim_to_bv(x)),
+                def!(bvy = bv_from_imm64(imm)),
+                def!(bva = bvop(bvx, bvy)),
+                def!(a = prim_from_bv(bva)),
+            ],
+            vec![],
+        );
+    }
+
+    semantics.add(
+        def!(a = irsub_imm(x, imm)),
+        vec![
+            def!(bvx = prim_to_bv(x)),
+            def!(bvy = bv_from_imm64(imm)),
+            def!(bva = bvsub(bvy, bvx)),
+            def!(a = prim_from_bv(bva)),
+        ],
+        vec![],
+    );
+
+    // Bitwise operations don't depend on the lanes of vectors.
+
+    for &(inst, bvop) in &[(band, bvand), (bor, bvor), (bxor, bvxor)] {
+        semantics.add(
+            def!(a = inst(x, y)),
+            vec![
+                def!(bvx = prim_to_bv(x)),
+                def!(bvy = prim_to_bv(y)),
+                def!(bva = bvop(bvx, bvy)),
+                def!(a = prim_from_bv(bva)),
+            ],
+            vec![],
+        );
+    }
+
+    for &(inst, bvop) in &[(band_not, bvand), (bor_not, bvor), (bxor_not, bvxor)] {
+        semantics.add(
+            def!(a = inst(x, y)),
+            vec![
+                def!(bvx = prim_to_bv(x)),
+                def!(bvy = prim_to_bv(y)),
+                def!(bvt = bvnot(bvy)),
+                def!(bva = bvop(bvx, bvt)),
+                def!(a = prim_from_bv(bva)),
+            ],
+            vec![],
+        );
+    }
+
+    semantics.add(
+        def!(a = bnot(x)),
+        vec![
+            def!(bvx = prim_to_bv(x)),
+            def!(bva = bvnot(bvx)),
+            def!(a = prim_from_bv(bva)),
+        ],
+        vec![],
+    );
+
+    semantics.add(
+        def!(a = select(c, x, y)),
+        vec![
+            def!(bvc = prim_to_bv(c)),
+            def!(bvz = bv_from_imm64(zero)),
+            def!(bc = bvne(bvc, bvz)),
+            def!(bvx = prim_to_bv(x)),
+            def!(bvy = prim_to_bv(y)),
+            def!(bva = bvite(bc, bvx, bvy)),
+            def!(a = prim_from_bv(bva)),
+        ],
+        vec![],
+    );
+
+    // Conversions.
+
+    semantics.add(
+        def!(a = bint(x)),
+        vec![
+            def!(bvx = prim_to_bv(x)),
+            def!(bva = bvzeroext(bvx)),
+            def!(a = prim_from_bv(bva)),
+        ],
+        vec![Constraint::in_typeset(x.type_var(), b1_ts)],
+    );
+
+    for &(inst, bvop) in &[
+        (uextend, bvzeroext),
+        (sextend, bvsignext),
+        (ireduce, bvreduce),
+    ] {
+        semantics.add(
+            def!(a = inst(x)),
+            vec![
+                def!(bvx = prim_to_bv(x)),
+                def!(bva = bvop(bvx)),
+                def!(a = prim_from_bv(bva)),
+            ],
+            scalar(x),
+        );
+        semantics.add(
+            def!(a = inst(x)),
+            vec![
+                def!((xlo, xhi) = vsplit(x)),
+                def!(alo = inst(xlo)),
+                def!(ahi = inst(xhi)),
+                def!(a = vconcat(alo, ahi)),
+            ],
+            vec![],
+        );
+    }
+
+    semantics.add(
+        def!(a = bextend(x)),
+        vec![
+            def!(bvx = prim_to_bv(x)),
+            def!(bvy = bvsignext(bvx)),
+            def!(a = prim_from_bv(bvy)),
+        ],
+        scalar(x),
+    );
+    semantics.add(
+        def!(a = bextend(x)),
+        vec![
+            def!((xlo, xhi) = vsplit(x)),
+            def!(alo = bextend(xlo)),
+            def!(ahi = bextend(xhi)),
+            def!(a = vconcat(alo, ahi)),
+        ],
+        vec![],
+    );
+
+    // Vector comparisons are done lane by lane, scalar comparisons map to a
+    // bitvector comparison for each condition code.
+    semantics.add(
+        def!(a = icmp(cc, x, y)),
+        vec![
+            def!((xlo, xhi) = vsplit(x)),
+            def!((ylo, yhi) = vsplit(y)),
+            def!(alo = icmp(cc, xlo, ylo)),
+            def!(ahi = icmp(cc, xhi, yhi)),
+            def!(b = vconcat(alo, ahi)),
+            def!(a = bextend(b)),
+        ],
+        vec![],
+    );
+    let comparisons = [
+        ("eq", "bveq"),
+        ("ne", "bvne"),
+        ("sge", "bvsge"),
+        ("sgt", "bvsgt"),
+        ("sle", "bvsle"),
+        ("slt", "bvslt"),
+        ("uge", "bvuge"),
+        ("ugt", "bvugt"),
+        ("ule", "bvule"),
+        ("ult", "bvult"),
+    ];
+    for &(cond, bvcmp) in &comparisons {
+        let cond = Literal::enumerator_for(&immediates.intcc, cond);
+        let bvcmp = primitives.by_name(bvcmp);
+        semantics.add(
+            def!(a = icmp_imm(cond, x, imm)),
+            vec![
+                def!(bvx = prim_to_bv(x)),
+                def!(bvy = bv_from_imm64(imm)),
+                def!(ba = bvcmp(bvx, bvy)),
+                def!(bva = bool2bv(ba)),
+                def!(bva_wide = bvzeroext(bva)),
+                def!(a = prim_from_bv(bva_wide)),
+            ],
+            vec![],
+        );
+        semantics.add(
+            def!(a = icmp(cond, x, y)),
+            vec![
+                def!(bvx = prim_to_bv(x)),
+                def!(bvy = prim_to_bv(y)),
+                def!(ba = bvcmp(bvx, bvy)),
+                def!(bva = bool2bv(ba)),
+                def!(bva_wide = bvzeroext(bva)),
+                def!(a = prim_from_bv(bva_wide)),
+            ],
+            scalar(x),
+        );
+    }
+
+    // Legalization helper instructions.
+
+    semantics.add(
+        def!((xlo, xhi) = isplit(x)),
+        vec![
+            def!(bvx = prim_to_bv(x)),
+            def!((bvlo, bvhi) = bvsplit(bvx)),
+            def!(xlo = prim_from_bv(bvlo)),
+            def!(xhi = prim_from_bv(bvhi)),
+        ],
+        scalar(x),
+    );
+    semantics.add(
+        def!((xlo, xhi) = isplit(x)),
+        vec![
+            def!((a, b) = vsplit(x)),
+            def!((alo, ahi) = isplit(a)),
+            def!((blo, bhi) = isplit(b)),
+            def!(xlo = vconcat(alo, blo)),
+            def!(xhi = vconcat(ahi, bhi)),
+        ],
+        vec![],
+    );
+
+    semantics.add(
+        def!(x = iconcat(xlo, xhi)),
+        vec![
+            def!(bvlo = prim_to_bv(xlo)),
+            def!(bvhi = prim_to_bv(xhi)),
+            def!(bvx = bvconcat(bvlo, bvhi)),
+            def!(x = prim_from_bv(bvx)),
+        ],
+        scalar(x),
+    );
+    semantics.add(
+        def!(x = iconcat(xlo, xhi)),
+        vec![
+            def!((alo, ahi) = vsplit(xlo)),
+            def!((blo, bhi) = vsplit(xhi)),
+            def!(a = iconcat(alo, blo)),
+            def!(b = iconcat(ahi, bhi)),
+            def!(x = vconcat(a, b)),
+        ],
+        vec![],
+    );
+}
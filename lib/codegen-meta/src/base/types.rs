@@ -13,7 +13,7 @@ use std::fmt;
 // in the high 4 bits, giving a range of 2-256 lanes.
 static LANE_BASE: u8 = 0x70;
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub enum Bool {
     /// 1-bit bool.
     B1 = 1,
@@ -46,6 +46,12 @@ pub struct BoolIterator {
     index: usize,
 }
 
+impl Default for BoolIterator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl BoolIterator {
     pub fn new() -> Self {
         Self { index: 0 }
@@ -68,7 +74,7 @@ impl Iterator for BoolIterator {
     }
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub enum Int {
     /// 8-bit int.
     I8 = 8,
@@ -98,6 +104,12 @@ pub struct IntIterator {
     index: usize,
 }
 
+impl Default for IntIterator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl IntIterator {
     pub fn new() -> Self {
         Self { index: 0 }
@@ -119,7 +131,7 @@ impl Iterator for IntIterator {
     }
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub enum Float {
     F32 = 32,
     F64 = 64,
@@ -142,6 +154,12 @@ pub struct FloatIterator {
     index: usize,
 }
 
+impl Default for FloatIterator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl FloatIterator {
     pub fn new() -> Self {
         Self { index: 0 }
@@ -161,7 +179,7 @@ impl Iterator for FloatIterator {
     }
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 /// A type representing CPU flags.
 ///
 /// Flags can't be stored in memory.
@@ -200,6 +218,12 @@ pub struct FlagIterator {
     index: usize,
 }
 
+impl Default for FlagIterator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl FlagIterator {
     pub fn new() -> Self {
         Self { index: 0 }
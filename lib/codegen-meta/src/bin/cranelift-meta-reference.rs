@@ -0,0 +1,17 @@
+//! Write the JSON and Markdown references of the Cranelift meta definitions.
+//!
+//! Usage: `cranelift-meta-reference [OUT_DIR]`, where `OUT_DIR` defaults to
+//! the current directory.
+
+extern crate cranelift_codegen_meta as meta;
+
+use std::env;
+use std::process;
+
+fn main() {
+    let out_dir = env::args().nth(1).unwrap_or_else(|| ".".to_string());
+    if let Err(err) = meta::generate_reference(&out_dir) {
+        eprintln!("Error: {}", err);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,404 @@
+//! Abstract syntax trees for instruction patterns.
+//!
+//! Patterns are lists of definitions, like `a = iadd(x, y)`, where an
+//! instruction is applied to variables and literals. They are most easily
+//! written with the `def!` macro:
+//!
+//! ```ignore
+//! let a = var("a");
+//! let x = var("x");
+//! let c = var("c");
+//! def!(a = iadd(x, c));
+//! def!((a, c) = iadd_cout(x, y));
+//! def!(c = iconst.i32(imm));
+//! ```
+
+use std::fmt;
+
+use cdsl::formats::FormatRegistry;
+use cdsl::instructions::{BoundInstruction, Instruction, InstructionPredicate};
+use cdsl::operands::{OperandKind, OperandKindFields};
+use cdsl::types::ValueType;
+use cdsl::typevar::{TypeSetBuilder, TypeVar};
+use cdsl::xform::SymbolTable;
+
+/// A free variable in a pattern.
+///
+/// Variables are identified by their name. Each transformation has its own
+/// symbol table describing how the variables are used in its patterns, see
+/// `xform::Symbol`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Var {
+    pub name: &'static str,
+}
+
+impl Var {
+    /// Get the `typeof_<name>` type variable standing for the type of this
+    /// variable in the constraints of a transformation.
+    pub fn type_var(self) -> TypeVar {
+        TypeVar::new(
+            format!("typeof_{}", self.name),
+            format!("Type of the pattern variable `{}`", self.name),
+            TypeSetBuilder::new().finish(),
+        )
+    }
+}
+
+impl fmt::Display for Var {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// Create a new pattern variable named `name`.
+pub fn var(name: &'static str) -> Var {
+    Var { name }
+}
+
+/// A literal value of an immediate operand.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Literal {
+    /// An integer immediate operand, like `imm64(5)`.
+    Int { kind: OperandKind, value: i64 },
+    /// A bitwise value of an immediate operand, used to create bitwise exact
+    /// floating point constants like `ieee32.bits(0x80000000)`.
+    Bits { kind: OperandKind, bits: u64 },
+    /// A value of an enumerated immediate operand, like `intcc.ult`.
+    Enumerator {
+        kind: OperandKind,
+        value: &'static str,
+    },
+}
+
+impl Literal {
+    /// Create an integer literal of the immediate kind `kind`.
+    pub fn constant(kind: &OperandKind, value: i64) -> Self {
+        match kind.fields {
+            OperandKindFields::ImmValue => {}
+            _ => panic!("{} is not an immediate operand kind", kind),
+        }
+        Literal::Int {
+            kind: kind.clone(),
+            value,
+        }
+    }
+
+    /// Create a literal of the immediate kind `kind` from its bitwise
+    /// representation.
+    pub fn bits(kind: &OperandKind, bits: u64) -> Self {
+        match kind.fields {
+            OperandKindFields::ImmValue => {}
+            _ => panic!("{} is not an immediate operand kind", kind),
+        }
+        Literal::Bits {
+            kind: kind.clone(),
+            bits,
+        }
+    }
+
+    /// Create a literal for the enumerator `value` of the enumerated
+    /// immediate kind `kind`.
+    pub fn enumerator_for(kind: &OperandKind, value: &'static str) -> Self {
+        // Make sure the enumerator exists.
+        kind.rust_enumerator(value);
+        Literal::Enumerator {
+            kind: kind.clone(),
+            value,
+        }
+    }
+
+    pub fn kind(&self) -> &OperandKind {
+        match *self {
+            Literal::Int { ref kind, .. }
+            | Literal::Bits { ref kind, .. }
+            | Literal::Enumerator { ref kind, .. } => kind,
+        }
+    }
+}
+
+/// Literals are displayed as the Rust expression computing their value.
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Literal::Int { value, .. } => write!(f, "{}", value),
+            Literal::Bits { ref kind, bits } => {
+                write!(f, "{}::with_bits({:#x})", kind.rust_type, bits)
+            }
+            Literal::Enumerator { ref kind, value } => {
+                write!(f, "{}", kind.rust_enumerator(value))
+            }
+        }
+    }
+}
+
+/// An argument of an instruction application.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Var(Var),
+    Literal(Literal),
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Expr::Var(ref var) => write!(f, "{}", var),
+            Expr::Literal(ref literal) => write!(f, "{}", literal),
+        }
+    }
+}
+
+impl From<Var> for Expr {
+    fn from(var: Var) -> Self {
+        Expr::Var(var)
+    }
+}
+
+impl From<Literal> for Expr {
+    fn from(literal: Literal) -> Self {
+        Expr::Literal(literal)
+    }
+}
+
+/// Apply an instruction to arguments.
+///
+/// The instruction may be bound to concrete types, like `iadd.i32(x, y)`.
+#[derive(Clone)]
+pub struct Apply {
+    pub inst: Instruction,
+    pub value_types: Vec<ValueType>,
+    pub args: Vec<Expr>,
+}
+
+impl Apply {
+    pub fn new<I: Into<BoundInstruction>>(target: I, args: Vec<Expr>) -> Self {
+        let BoundInstruction { inst, value_types } = target.into();
+        let value_types = value_types
+            .into_iter()
+            .map(|vt| vt.expect("`any` can only be used in encodings"))
+            .collect();
+        assert_eq!(
+            inst.operands_in.len(),
+            args.len(),
+            "wrong number of arguments to {}",
+            inst.name
+        );
+
+        // Check that the kinds of the literal arguments match the operands.
+        for &opnum in &inst.imm_opnums {
+            if let Expr::Literal(ref literal) = args[opnum] {
+                let kind = &inst.operands_in[opnum].kind;
+                assert!(
+                    literal.kind() == kind,
+                    "Passing literal {} to field of wrong kind {}.",
+                    literal,
+                    kind
+                );
+            }
+        }
+
+        for &opnum in &inst.value_opnums {
+            match args[opnum] {
+                Expr::Var(_) => {}
+                _ => panic!("Value arg must be `Var` in {}", inst.name),
+            }
+        }
+
+        Self {
+            inst,
+            value_types,
+            args,
+        }
+    }
+
+    /// Get the name of the applied instruction, including any bound types.
+    pub fn instname(&self) -> String {
+        let mut name = self.inst.name.to_string();
+        for value_type in &self.value_types {
+            name.push_str(&format!(".{}", value_type));
+        }
+        name
+    }
+
+    /// Get the variables used as value arguments.
+    pub fn value_args(&self) -> Vec<Var> {
+        self.inst
+            .value_opnums
+            .iter()
+            .map(|&opnum| match self.args[opnum] {
+                Expr::Var(var) => var,
+                _ => unreachable!("value arguments are variables"),
+            })
+            .collect()
+    }
+
+    /// Get a Rust builder method call for instantiating this instruction
+    /// application.
+    ///
+    /// The variables defined by the instruction, `defs`, are used to
+    /// construct a result type if necessary.
+    pub fn rust_builder(&self, defs: &[Var], symbols: &SymbolTable) -> String {
+        let mut args = self
+            .args
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        // Do we need to pass an explicit type argument?
+        if let Some(ref poly) = self.inst.polymorphic_info {
+            if !poly.use_typevar_operand {
+                args = format!("{}, {}", symbols.get(defs[0]).rust_type(), args);
+            }
+        }
+
+        format!("{}({})", self.inst.snake_name(), args)
+    }
+
+    /// Construct an instruction predicate that verifies the immediate
+    /// operands of this instruction.
+    ///
+    /// Immediate operands in a source pattern can be either free variables
+    /// or literals. Only the literals are checked.
+    pub fn inst_predicate(&self, format_registry: &FormatRegistry) -> Option<InstructionPredicate> {
+        let iform = format_registry.get(self.inst.format);
+
+        let mut parts = Vec::new();
+        for (field, &opnum) in iform.imm_fields.iter().zip(&self.inst.imm_opnums) {
+            if let Expr::Literal(ref literal) = self.args[opnum] {
+                parts.push(Some(InstructionPredicate::is_equal(
+                    iform,
+                    field.member,
+                    literal.to_string(),
+                )));
+            }
+        }
+
+        // Add checks for any bound secondary type variables. We can't check
+        // the controlling type variable this way since it may not appear as
+        // the type of an operand.
+        if let Some(ref poly) = self.inst.polymorphic_info {
+            for (value_type, type_var) in self.value_types.iter().skip(1).zip(&poly.other_typevars)
+            {
+                parts.push(Some(InstructionPredicate::typevar_check(
+                    &self.inst,
+                    type_var,
+                    value_type.clone(),
+                )));
+            }
+        }
+
+        InstructionPredicate::combine(parts)
+    }
+
+    /// Same as `inst_predicate()`, but also check the controlling type
+    /// variable.
+    pub fn inst_predicate_with_ctrl_typevar(
+        &self,
+        format_registry: &FormatRegistry,
+    ) -> Option<InstructionPredicate> {
+        let pred = self.inst_predicate(format_registry);
+
+        let (value_type, poly) = match (self.value_types.first(), &self.inst.polymorphic_info) {
+            (Some(value_type), &Some(ref poly)) => (value_type.clone(), poly),
+            _ => return pred,
+        };
+
+        // Prefer to look at the types of input operands.
+        let type_check = if poly.use_typevar_operand {
+            InstructionPredicate::typevar_check(&self.inst, &poly.ctrl_typevar, value_type)
+        } else {
+            InstructionPredicate::CtrlTypeCheck(value_type)
+        };
+        InstructionPredicate::combine(vec![pred, Some(type_check)])
+    }
+}
+
+impl fmt::Display for Apply {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let args = self
+            .args
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(f, "{}({})", self.instname(), args)
+    }
+}
+
+/// A definition associates a list of variables with the values produced by
+/// an instruction application.
+///
+/// Instructions that don't produce any values can be used in a `Def` without
+/// any variables.
+#[derive(Clone)]
+pub struct Def {
+    pub defs: Vec<Var>,
+    pub expr: Apply,
+}
+
+impl Def {
+    pub fn new(defs: Vec<Var>, expr: Apply) -> Self {
+        Self { defs, expr }
+    }
+}
+
+impl fmt::Display for Def {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.defs.len() == 1 {
+            write!(f, "{} << {}", self.defs[0], self.expr)
+        } else {
+            let defs = self
+                .defs
+                .iter()
+                .map(|var| var.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            write!(f, "({}) << {}", defs, self.expr)
+        }
+    }
+}
+
+/// Apply an instruction, optionally bound to types, to a list of arguments.
+///
+/// The arguments are variables or literals: `apply!(iadd(x, y))`,
+/// `apply!(iconst.i32(imm))`, or `apply!(uextend.i64.i32(x))`.
+#[macro_export]
+macro_rules! apply {
+    ($inst:ident($($arg:expr),*)) => {
+        $crate::cdsl::ast::Apply::new(
+            $inst,
+            vec![$($crate::cdsl::ast::Expr::from($arg.clone())),*],
+        )
+    };
+    ($inst:ident.$ty:ident($($arg:expr),*)) => {
+        $crate::cdsl::ast::Apply::new(
+            $crate::cdsl::instructions::Bindable::bind($inst, $ty.clone()),
+            vec![$($crate::cdsl::ast::Expr::from($arg.clone())),*],
+        )
+    };
+    ($inst:ident.$ty:ident.$ty2:ident($($arg:expr),*)) => {
+        $crate::cdsl::ast::Apply::new(
+            $crate::cdsl::instructions::Bindable::bind(
+                &$crate::cdsl::instructions::Bindable::bind($inst, $ty.clone()),
+                $ty2.clone(),
+            ),
+            vec![$($crate::cdsl::ast::Expr::from($arg.clone())),*],
+        )
+    };
+}
+
+/// Define variables using an instruction application: `def!(a = iadd(x, y))`,
+/// `def!((a, c) = iadd_cout(x, y))`, or `def!(store(flags, x, ptr, off))`
+/// for instructions without results.
+#[macro_export]
+macro_rules! def {
+    (($($def:ident),*) = $($apply:tt)*) => {
+        $crate::cdsl::ast::Def::new(vec![$($def),*], apply!($($apply)*))
+    };
+    ($def:ident = $($apply:tt)*) => {
+        $crate::cdsl::ast::Def::new(vec![$def], apply!($($apply)*))
+    };
+    ($($apply:tt)*) => {
+        $crate::cdsl::ast::Def::new(vec![], apply!($($apply)*))
+    };
+}
@@ -0,0 +1,139 @@
+//! CPU modes of a target ISA.
+
+use cdsl::encodings::Encoding;
+use cdsl::types::ValueType;
+use cdsl::xform::XFormGroupIndex;
+
+/// A CPU mode determines which instruction encodings are active.
+///
+/// All instruction encodings are associated with exactly one `CpuMode`, and
+/// all CPU modes are associated with exactly one `TargetIsa`.
+pub struct CpuMode {
+    pub name: &'static str,
+    /// The legalization action for controlling type variables that don't
+    /// have an explicitly configured action.
+    default_legalize: Option<XFormGroupIndex>,
+    /// The legalization action for monomorphic instructions.
+    monomorphic_legalize: Option<XFormGroupIndex>,
+    /// The legalization actions per controlling type variable, in the order
+    /// they were configured.
+    typed_legalize: Vec<(ValueType, XFormGroupIndex)>,
+    encodings: Vec<Encoding>,
+}
+
+impl CpuMode {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            default_legalize: None,
+            monomorphic_legalize: None,
+            typed_legalize: Vec::new(),
+            encodings: Vec::new(),
+        }
+    }
+
+    pub fn set_encodings(&mut self, encodings: Vec<Encoding>) {
+        assert!(
+            self.encodings.is_empty(),
+            "encodings already set for {}",
+            self.name
+        );
+        self.encodings = encodings;
+    }
+
+    /// Add encodings after the ones already set for this CPU mode.
+    pub fn add_encodings(&mut self, encodings: Vec<Encoding>) {
+        self.encodings.extend(encodings);
+    }
+
+    pub fn encodings(&self) -> &[Encoding] {
+        &self.encodings
+    }
+
+    pub fn encodings_mut(&mut self) -> &mut [Encoding] {
+        &mut self.encodings
+    }
+
+    /// Configure the legalization action to take for controlling type
+    /// variables that don't have an explicitly configured action.
+    pub fn legalize_default(&mut self, group: XFormGroupIndex) {
+        assert!(
+            self.default_legalize.is_none(),
+            "default legalization action already set for {}",
+            self.name
+        );
+        self.default_legalize = Some(group);
+    }
+
+    /// Configure the legalization action to take for monomorphic instructions
+    /// which don't have a controlling type variable.
+    ///
+    /// See also `legalize_type()` for polymorphic instructions.
+    pub fn legalize_monomorphic(&mut self, group: XFormGroupIndex) {
+        assert!(
+            self.monomorphic_legalize.is_none(),
+            "monomorphic legalization action already set for {}",
+            self.name
+        );
+        self.monomorphic_legalize = Some(group);
+    }
+
+    /// Configure the legalization action for instructions with the
+    /// controlling type variable `value_type`, instead of using the default
+    /// action.
+    pub fn legalize_type<T: Into<ValueType>>(&mut self, value_type: T, group: XFormGroupIndex) {
+        let value_type = value_type.into();
+        assert!(
+            self.typed_legalize
+                .iter()
+                .all(|&(ref ty, _)| *ty != value_type),
+            "legalization action for {} already set for {}",
+            value_type,
+            self.name
+        );
+        self.typed_legalize.push((value_type, group));
+    }
+
+    /// Get the legalization action for controlling type variables that don't
+    /// have an explicitly configured action.
+    pub fn get_default_legalize(&self) -> XFormGroupIndex {
+        self.default_legalize
+            .unwrap_or_else(|| panic!("CPU mode {} needs a default legalize action", self.name))
+    }
+
+    /// Get the legalization action to use for the controlling type variable
+    /// `value_type`, or for monomorphic instructions if it is `None`.
+    pub fn get_legalize_action(&self, value_type: Option<&ValueType>) -> XFormGroupIndex {
+        let action = match value_type {
+            None => self.monomorphic_legalize,
+            Some(value_type) => self
+                .typed_legalize
+                .iter()
+                .find(|&&(ref ty, _)| ty == value_type)
+                .map(|&(_, group)| group),
+        };
+        action.unwrap_or_else(|| self.get_default_legalize())
+    }
+
+    /// Get the controlling type variables with an explicitly configured
+    /// legalization action, where `None` stands for monomorphic
+    /// instructions.
+    pub fn legalized_types(&self) -> Vec<Option<&ValueType>> {
+        self.monomorphic_legalize
+            .iter()
+            .map(|_| None)
+            .chain(self.typed_legalize.iter().map(|&(ref ty, _)| Some(ty)))
+            .collect()
+    }
+
+    /// Get all the legalization actions used by this CPU mode: The default
+    /// action first, followed by the monomorphic and the per type actions.
+    pub fn legalize_actions(&self) -> Vec<XFormGroupIndex> {
+        self.default_legalize
+            .iter()
+            .chain(self.monomorphic_legalize.iter())
+            .chain(self.typed_legalize.iter().map(|&(_, ref group)| group))
+            .cloned()
+            .collect()
+    }
+}
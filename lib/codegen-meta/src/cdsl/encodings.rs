@@ -0,0 +1,154 @@
+//! Encodings of concrete instructions.
+
+use cdsl::ast::Apply;
+use cdsl::formats::FormatRegistry;
+use cdsl::instructions::{BoundInstruction, Instruction, InstructionPredicate, ValueTypeOrAny};
+use cdsl::recipes::{EncodingRecipeNumber, RecipeGroup};
+use cdsl::types::ValueType;
+
+/// The concrete instruction of an encoding.
+///
+/// It can be in three different forms:
+///
+/// 1. A naked opcode: `trap` for non-polymorphic instructions.
+/// 2. With bound type variables: `iadd.i32` for polymorphic instructions.
+/// 3. With operands providing constraints: `icmp.i32(intcc.eq, x, y)`.
+///
+/// If the instruction is polymorphic, all type variables must be provided.
+pub enum InstSpec {
+    Inst(Instruction),
+    Bound(BoundInstruction),
+    Apply(Apply),
+}
+
+impl<'a> From<&'a Instruction> for InstSpec {
+    fn from(inst: &'a Instruction) -> Self {
+        InstSpec::Inst(inst.clone())
+    }
+}
+
+impl From<BoundInstruction> for InstSpec {
+    fn from(inst: BoundInstruction) -> Self {
+        InstSpec::Bound(inst)
+    }
+}
+
+impl From<Apply> for InstSpec {
+    fn from(apply: Apply) -> Self {
+        InstSpec::Apply(apply)
+    }
+}
+
+/// Encoding for a concrete instruction.
+///
+/// An `Encoding` ties an instruction opcode with concrete type variables
+/// together with an encoding recipe and encoding bits.
+pub struct Encoding {
+    pub inst: Instruction,
+    /// The value of the controlling type variable, or `None` for
+    /// monomorphic instructions.
+    pub bound_type: Option<ValueType>,
+    pub recipe: EncodingRecipeNumber,
+    /// Additional encoding bits to be interpreted by the recipe.
+    pub encbits: u16,
+    /// Instruction predicate, in addition to the one of the recipe.
+    pub inst_predicate: Option<InstructionPredicate>,
+    /// Number of the ISA predicate in the ISA's setting group.
+    pub isa_predicate: Option<u8>,
+}
+
+pub struct EncodingBuilder {
+    inst: InstSpec,
+    recipe: EncodingRecipeNumber,
+    encbits: u16,
+    inst_predicate: Option<InstructionPredicate>,
+    isa_predicate: Option<u8>,
+}
+
+impl EncodingBuilder {
+    pub fn new<I: Into<InstSpec>>(inst: I, recipe: EncodingRecipeNumber, encbits: u16) -> Self {
+        Self {
+            inst: inst.into(),
+            recipe,
+            encbits,
+            inst_predicate: None,
+            isa_predicate: None,
+        }
+    }
+
+    pub fn inst_predicate(mut self, inst_predicate: InstructionPredicate) -> Self {
+        assert!(self.inst_predicate.is_none());
+        self.inst_predicate = Some(inst_predicate);
+        self
+    }
+
+    pub fn isa_predicate(mut self, isa_predicate: u8) -> Self {
+        assert!(self.isa_predicate.is_none());
+        self.isa_predicate = Some(isa_predicate);
+        self
+    }
+
+    pub fn build(self, recipes: &RecipeGroup, format_registry: &FormatRegistry) -> Encoding {
+        let mut inst_predicate = self.inst_predicate;
+
+        let (inst, bound_type) = match self.inst {
+            InstSpec::Apply(apply) => {
+                // Check for possible instruction predicates in the operands.
+                inst_predicate = InstructionPredicate::combine(vec![
+                    inst_predicate,
+                    apply.inst_predicate(format_registry),
+                ]);
+                (apply.inst.clone(), apply.value_types.first().cloned())
+            }
+            InstSpec::Inst(inst) => {
+                let bound = BoundInstruction::from(&inst);
+                (inst, bound.ctrl_type())
+            }
+            InstSpec::Bound(bound) => {
+                let bound_type = bound.ctrl_type();
+
+                // Add secondary type variables to the instruction predicate.
+                let poly = bound.inst.polymorphic_info.as_ref().unwrap();
+                for (value_type, type_var) in
+                    bound.value_types.iter().skip(1).zip(&poly.other_typevars)
+                {
+                    if let ValueTypeOrAny::ValueType(ref value_type) = *value_type {
+                        let type_check = InstructionPredicate::typevar_check(
+                            &bound.inst,
+                            type_var,
+                            value_type.clone(),
+                        );
+                        inst_predicate =
+                            InstructionPredicate::combine(vec![inst_predicate, Some(type_check)]);
+                    }
+                }
+                (bound.inst, bound_type)
+            }
+        };
+
+        let recipe = recipes.get(self.recipe);
+        assert!(
+            inst.format == recipe.format,
+            "format of {} must match recipe {}",
+            inst.name,
+            recipe.name
+        );
+        if inst.is_branch {
+            assert!(
+                recipe.branch_range.is_some(),
+                "recipe {} for {} must have a branch_range",
+                recipe.name,
+                inst.name
+            );
+        }
+
+        Encoding {
+            inst,
+            bound_type,
+            recipe: self.recipe,
+            encbits: self.encbits,
+            inst_predicate,
+            isa_predicate: self.isa_predicate,
+        }
+    }
+}
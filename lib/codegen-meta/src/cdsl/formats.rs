@@ -0,0 +1,321 @@
+//! Classes for describing instruction formats.
+
+use std::fmt;
+use std::slice;
+
+use cdsl::operands::{Operand, OperandKind, OperandKindFields};
+
+/// An immediate field in an instruction format.
+///
+/// This corresponds to a single member of a variant of the `InstructionData`
+/// data type.
+#[derive(Debug)]
+pub struct FormatField {
+    /// Immediate operand kind.
+    pub kind: OperandKind,
+    /// Member name in the `InstructionData` variant.
+    pub member: &'static str,
+}
+
+/// Every instruction opcode has a corresponding instruction format which
+/// determines the number of operands and their kinds. Instruction formats are
+/// identified structurally, i.e., the format of an instruction is derived from
+/// the kinds of operands used in its declaration.
+///
+/// The instruction format stores two separate lists of operands: Immediates
+/// and values. Immediate operands (including entity references) are
+/// represented as explicit members in the `InstructionData` variants. The
+/// value operands are stored differently, depending on how many there are.
+/// Beyond a certain point, instruction formats switch to an external value
+/// list for storing value arguments. Value lists can hold an arbitrary number
+/// of values.
+///
+/// All instruction formats must be predefined in the `base::formats` module.
+#[derive(Debug)]
+pub struct InstructionFormat {
+    /// Instruction format name in CamelCase. This is used as a Rust variant
+    /// name in both the `InstructionData` and `InstructionFormat` enums.
+    pub name: &'static str,
+    /// The number of value operands stored in the format, not counting the
+    /// value list.
+    pub num_value_operands: usize,
+    /// Does this format use a value list for storing value operands?
+    pub has_value_list: bool,
+    /// Operand fields for the immediate operands. All other instruction
+    /// operands are values or variable argument lists. They are all handled
+    /// specially.
+    pub imm_fields: Vec<FormatField>,
+    /// Index of the value input operand that is used to infer the controlling
+    /// type variable. The index is relative to the values only, ignoring
+    /// immediate operands.
+    pub typevar_operand: Option<usize>,
+}
+
+impl InstructionFormat {
+    /// Get the immediate field with the member name `member`.
+    pub fn imm_by_name(&self, member: &str) -> &FormatField {
+        self.imm_fields
+            .iter()
+            .find(|field| field.member == member)
+            .unwrap_or_else(|| panic!("{} is not a {} member", member, self.name))
+    }
+
+    /// The signature used to identify this format structurally.
+    fn signature(&self) -> (Vec<&str>, usize, bool) {
+        (
+            self.imm_fields.iter().map(|f| f.kind.name).collect(),
+            self.num_value_operands,
+            self.has_value_list,
+        )
+    }
+}
+
+impl fmt::Display for InstructionFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let args = self
+            .imm_fields
+            .iter()
+            .map(|field| format!("{}: {}", field.member, field.kind))
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(
+            f,
+            "{}(imms=({}), vals={})",
+            self.name, args, self.num_value_operands
+        )
+    }
+}
+
+pub struct InstructionFormatBuilder {
+    name: &'static str,
+    num_value_operands: usize,
+    has_value_list: bool,
+    imm_fields: Vec<FormatField>,
+    typevar_operand: Option<usize>,
+}
+
+impl InstructionFormatBuilder {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            num_value_operands: 0,
+            has_value_list: false,
+            imm_fields: Vec::new(),
+            typevar_operand: None,
+        }
+    }
+
+    /// Add a value operand.
+    pub fn value(mut self) -> Self {
+        self.num_value_operands += 1;
+        self
+    }
+
+    /// Store the value operands in a value list.
+    pub fn varargs(mut self) -> Self {
+        self.has_value_list = true;
+        self
+    }
+
+    /// Add an immediate operand of kind `kind`, using the default member name
+    /// of the kind.
+    pub fn imm(self, kind: &OperandKind) -> Self {
+        let member = kind
+            .default_member
+            .unwrap_or_else(|| panic!("{} has no default member name", kind.name));
+        self.imm_with_name(member, kind)
+    }
+
+    /// Add an immediate operand of kind `kind` with the member name `member`.
+    pub fn imm_with_name(mut self, member: &'static str, kind: &OperandKind) -> Self {
+        match kind.fields {
+            OperandKindFields::TypeVar(_) | OperandKindFields::VariableArgs => {
+                panic!("{} is not an immediate operand kind", kind.name)
+            }
+            _ => {}
+        }
+        self.imm_fields.push(FormatField {
+            kind: kind.clone(),
+            member,
+        });
+        self
+    }
+
+    /// Use the value operand number `operand` to infer the controlling type
+    /// variable. By default, this is `0`, the first `value` operand.
+    pub fn typevar_operand(mut self, operand: usize) -> Self {
+        assert!(self.typevar_operand.is_none());
+        assert!(
+            self.has_value_list || operand < self.num_value_operands,
+            "typevar_operand must indicate a 'value' operand"
+        );
+        self.typevar_operand = Some(operand);
+        self
+    }
+
+    pub fn build(self) -> InstructionFormat {
+        let typevar_operand = if self.typevar_operand.is_some() {
+            self.typevar_operand
+        } else if self.has_value_list || self.num_value_operands > 0 {
+            // Default to the first 'value' operand, if there is one.
+            Some(0)
+        } else {
+            None
+        };
+
+        InstructionFormat {
+            name: self.name,
+            num_value_operands: self.num_value_operands,
+            has_value_list: self.has_value_list,
+            imm_fields: self.imm_fields,
+            typevar_operand,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct InstructionFormatIndex(usize);
+
+impl InstructionFormatIndex {
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// The set of all the instruction formats, in definition order.
+pub struct FormatRegistry {
+    formats: Vec<InstructionFormat>,
+}
+
+impl Default for FormatRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FormatRegistry {
+    pub fn new() -> Self {
+        Self {
+            formats: Vec::new(),
+        }
+    }
+
+    /// Add a new instruction format to the registry. Each format must have a
+    /// unique signature.
+    pub fn insert(&mut self, builder: InstructionFormatBuilder) -> InstructionFormatIndex {
+        let format = builder.build();
+        let sig = format.signature();
+        if let Some(existing) = self.formats.iter().find(|f| f.signature() == sig) {
+            panic!(
+                "Format '{}' has the same signature as existing format '{}'",
+                format.name, existing.name
+            );
+        }
+        self.formats.push(format);
+        InstructionFormatIndex(self.formats.len() - 1)
+    }
+
+    pub fn get(&self, index: InstructionFormatIndex) -> &InstructionFormat {
+        &self.formats[index.0]
+    }
+
+    pub fn by_name(&self, name: &str) -> InstructionFormatIndex {
+        let index = self
+            .formats
+            .iter()
+            .position(|f| f.name == name)
+            .unwrap_or_else(|| panic!("No instruction format named {}", name));
+        InstructionFormatIndex(index)
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, InstructionFormat> {
+        self.formats.iter()
+    }
+
+    /// Find an existing instruction format that matches the given list of
+    /// instruction inputs.
+    pub fn lookup(&self, operands_in: &[Operand]) -> InstructionFormatIndex {
+        let imm_kinds: Vec<&str> = operands_in
+            .iter()
+            .filter(|op| op.is_immediate())
+            .map(|op| op.kind.name)
+            .collect();
+        let num_values = operands_in.iter().filter(|op| op.is_value()).count();
+        let has_varargs = operands_in.iter().any(|op| op.is_varargs());
+
+        let sig = (imm_kinds, num_values, has_varargs);
+        if let Some(index) = self.formats.iter().position(|f| f.signature() == sig) {
+            return InstructionFormatIndex(index);
+        }
+
+        // Try another value list format as an alternative.
+        let sig = (sig.0, 0, true);
+        if let Some(index) = self.formats.iter().position(|f| f.signature() == sig) {
+            return InstructionFormatIndex(index);
+        }
+
+        panic!(
+            "No instruction format matches imms={:?}, vals={}, varargs={}",
+            sig.0, num_values, has_varargs
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cdsl::typevar::{Interval, TypeSetBuilder, TypeVar};
+
+    fn imm64() -> OperandKind {
+        OperandKind::new_imm("imm64", "A 64-bit immediate integer.")
+    }
+
+    #[test]
+    fn format_display() {
+        let format = InstructionFormatBuilder::new("BinaryImm")
+            .value()
+            .imm(&imm64())
+            .build();
+        assert_eq!(format.to_string(), "BinaryImm(imms=(imm: imm64), vals=1)");
+        assert_eq!(format.typevar_operand, Some(0));
+
+        let format = InstructionFormatBuilder::new("NullAry").build();
+        assert_eq!(format.to_string(), "NullAry(imms=(), vals=0)");
+        assert_eq!(format.typevar_operand, None);
+    }
+
+    #[test]
+    fn lookup_by_operands() {
+        let mut registry = FormatRegistry::new();
+        let unary = registry.insert(InstructionFormatBuilder::new("Unary").value());
+        let binary_imm = registry.insert(
+            InstructionFormatBuilder::new("BinaryImm")
+                .value()
+                .imm(&imm64()),
+        );
+        let multi_ary = registry.insert(InstructionFormatBuilder::new("MultiAry").varargs());
+
+        let int = &TypeVar::new(
+            "Int",
+            "An integer type",
+            TypeSetBuilder::new().ints(Interval::All).finish(),
+        );
+        let x = Operand::new("x", int);
+        let y = Operand::new("y", int);
+        let imm = Operand::new("imm", imm64());
+
+        assert_eq!(registry.lookup(slice::from_ref(&y)), unary);
+        assert_eq!(registry.lookup(&[x.clone(), imm]), binary_imm);
+        // Falls back to the value list format.
+        assert_eq!(registry.lookup(&[x, y]), multi_ary);
+        assert_eq!(registry.by_name("Unary"), unary);
+    }
+
+    #[test]
+    #[should_panic(expected = "same signature")]
+    fn duplicate_signature() {
+        let mut registry = FormatRegistry::new();
+        registry.insert(InstructionFormatBuilder::new("Unary").value());
+        registry.insert(InstructionFormatBuilder::new("Unary2").value());
+    }
+}
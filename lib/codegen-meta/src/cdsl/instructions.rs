@@ -0,0 +1,976 @@
+//! Classes for defining instructions.
+
+use std::fmt;
+use std::rc::Rc;
+use std::slice;
+
+use cdsl::camel_case;
+use cdsl::formats::{FormatRegistry, InstructionFormat, InstructionFormatIndex};
+use cdsl::operands::Operand;
+use cdsl::type_inference::Constraint;
+use cdsl::types::ValueType;
+use cdsl::typevar::TypeVar;
+
+/// Every instruction must belong to exactly one instruction group. A given
+/// target architecture can support instructions from multiple groups, and it
+/// does not necessarily support all instructions in a group.
+pub struct InstructionGroup {
+    pub name: &'static str,
+    pub doc: &'static str,
+    instructions: Vec<Instruction>,
+}
+
+impl InstructionGroup {
+    pub fn iter(&self) -> slice::Iter<'_, Instruction> {
+        self.instructions.iter()
+    }
+
+    pub fn by_name(&self, name: &str) -> &Instruction {
+        self.instructions
+            .iter()
+            .find(|inst| inst.name == name)
+            .unwrap_or_else(|| panic!("unexisting instruction with name {}", name))
+    }
+}
+
+pub struct InstructionGroupBuilder<'format_reg> {
+    name: &'static str,
+    doc: &'static str,
+    format_registry: &'format_reg FormatRegistry,
+    instructions: Vec<Instruction>,
+}
+
+impl<'format_reg> InstructionGroupBuilder<'format_reg> {
+    pub fn new(
+        name: &'static str,
+        doc: &'static str,
+        format_registry: &'format_reg FormatRegistry,
+    ) -> Self {
+        Self {
+            name,
+            doc,
+            format_registry,
+            instructions: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, builder: InstructionBuilder) {
+        let inst = builder.build(self.format_registry);
+        assert!(
+            self.instructions.iter().all(|i| i.name != inst.name),
+            "duplicate instruction {}",
+            inst.name
+        );
+        self.instructions.push(inst);
+    }
+
+    pub fn finish(self) -> InstructionGroup {
+        InstructionGroup {
+            name: self.name,
+            doc: self.doc,
+            instructions: self.instructions,
+        }
+    }
+}
+
+/// Information about the controlling type variable of a polymorphic
+/// instruction.
+pub struct PolymorphicInfo {
+    /// Is the controlling type variable inferred from the typevar operand of
+    /// the instruction format, rather than from the first result?
+    pub use_typevar_operand: bool,
+    pub ctrl_typevar: TypeVar,
+    /// Other free type variables used by the instruction operands.
+    pub other_typevars: Vec<TypeVar>,
+}
+
+/// A shared reference to an instruction definition, so that patterns and
+/// encodings can refer to the instructions of a group.
+pub type Instruction = Rc<InstructionContent>;
+
+pub struct InstructionContent {
+    /// Instruction mnemonic, also becomes opcode name.
+    pub name: &'static str,
+    pub camel_name: String,
+    /// Documentation string.
+    pub doc: &'static str,
+    /// Input operands. This can be a mix of SSA value operands and other
+    /// operand kinds.
+    pub operands_in: Vec<Operand>,
+    /// Output operands. The output operands must be SSA values or
+    /// `variable_args`.
+    pub operands_out: Vec<Operand>,
+    /// The instruction format matching the input operands.
+    pub format: InstructionFormatIndex,
+    /// Indexes into `operands_in` for value operands.
+    pub value_opnums: Vec<usize>,
+    /// Indexes into `operands_out` for value results. Other results are
+    /// `variable_args`.
+    pub value_results: Vec<usize>,
+    /// Indexes into `operands_in` for non-value operands.
+    pub imm_opnums: Vec<usize>,
+    /// Type variable information, if this instruction is polymorphic.
+    pub polymorphic_info: Option<PolymorphicInfo>,
+    /// Instruction-specific type constraints, used by type inference.
+    pub constraints: Vec<Constraint>,
+
+    /// True for instructions that terminate the EBB.
+    pub is_terminator: bool,
+    /// True for all branch or jump instructions.
+    pub is_branch: bool,
+    /// Is this a call instruction?
+    pub is_call: bool,
+    /// Is this a return instruction?
+    pub is_return: bool,
+    /// Can this instruction read from memory?
+    pub can_load: bool,
+    /// Can this instruction write to memory?
+    pub can_store: bool,
+    /// Can this instruction cause a trap?
+    pub can_trap: bool,
+    /// Does this instruction have other side effects besides can_*
+    pub other_side_effects: bool,
+    /// Does this instruction write to CPU flags?
+    pub writes_cpu_flags: bool,
+}
+
+impl InstructionContent {
+    /// Get the snake_case name of this instruction.
+    ///
+    /// Keywords in Rust are altered by appending a '_'.
+    pub fn snake_name(&self) -> &str {
+        if self.name == "return" {
+            "return_"
+        } else {
+            self.name
+        }
+    }
+
+    /// Get the first line of the doc comment.
+    pub fn blurb(&self) -> &str {
+        for line in self.doc.split('\n') {
+            let stripped = line.trim();
+            if !stripped.is_empty() {
+                return stripped;
+            }
+        }
+        ""
+    }
+
+    pub fn is_polymorphic(&self) -> bool {
+        self.polymorphic_info.is_some()
+    }
+
+    /// Get a list of all type variables in the instruction.
+    pub fn all_typevars(&self) -> Vec<&TypeVar> {
+        match self.polymorphic_info {
+            Some(ref poly) => {
+                let mut typevars = vec![&poly.ctrl_typevar];
+                typevars.extend(poly.other_typevars.iter());
+                typevars
+            }
+            None => Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for InstructionContent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.operands_out.is_empty() {
+            let outs = self
+                .operands_out
+                .iter()
+                .map(|op| op.name)
+                .collect::<Vec<_>>()
+                .join(", ");
+            write!(f, "{} = ", outs)?;
+        }
+        let ins = self
+            .operands_in
+            .iter()
+            .map(|op| op.name)
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(f, "{} {}", self.name, ins)
+    }
+}
+
+/// A value type bound to a type variable of an instruction, or `any` to
+/// leave a secondary type variable unconstrained, like `ishl.i32.any` in the
+/// Python DSL.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValueTypeOrAny {
+    ValueType(ValueType),
+    Any,
+}
+
+impl ValueTypeOrAny {
+    /// Get the bound value type, panicking with `msg` for an `any` wildcard.
+    pub fn expect(self, msg: &str) -> ValueType {
+        match self {
+            ValueTypeOrAny::ValueType(vt) => vt,
+            ValueTypeOrAny::Any => panic!("{}", msg),
+        }
+    }
+}
+
+impl fmt::Display for ValueTypeOrAny {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ValueTypeOrAny::ValueType(ref vt) => write!(f, "{}", vt),
+            ValueTypeOrAny::Any => write!(f, "any"),
+        }
+    }
+}
+
+/// A polymorphic instruction bound to concrete types.
+///
+/// The `value_types` bind the type variables of the instruction in the order
+/// of `all_typevars()`: The controlling type variable first, followed by the
+/// other free type variables. Trailing type variables may be left unbound.
+#[derive(Clone)]
+pub struct BoundInstruction {
+    pub inst: Instruction,
+    pub value_types: Vec<ValueTypeOrAny>,
+}
+
+impl BoundInstruction {
+    /// Check that all the type variables of the instruction are bound, and
+    /// get the controlling type, if any.
+    pub fn ctrl_type(&self) -> Option<ValueType> {
+        assert_eq!(
+            self.value_types.len(),
+            self.inst.all_typevars().len(),
+            "{} is not fully bound",
+            self
+        );
+        self.value_types.first().map(|vt| {
+            vt.clone()
+                .expect("the controlling type variable can't be bound to any")
+        })
+    }
+}
+
+impl fmt::Display for BoundInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.inst.name)?;
+        for value_type in &self.value_types {
+            write!(f, ".{}", value_type)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> From<&'a Instruction> for BoundInstruction {
+    fn from(inst: &'a Instruction) -> Self {
+        BoundInstruction {
+            inst: inst.clone(),
+            value_types: Vec::new(),
+        }
+    }
+}
+
+/// Binding the type variables of an instruction to concrete types, like
+/// `iadd.i32` in the Python DSL.
+pub trait Bindable {
+    /// Bind the next unbound type variable to `value_type`.
+    fn bind<T: Into<ValueType>>(&self, value_type: T) -> BoundInstruction {
+        self.bind_type(ValueTypeOrAny::ValueType(value_type.into()))
+    }
+
+    /// Leave the next unbound type variable unconstrained. This is only
+    /// allowed for secondary type variables, and only in encodings.
+    fn bind_any(&self) -> BoundInstruction {
+        self.bind_type(ValueTypeOrAny::Any)
+    }
+
+    fn bind_type(&self, value_type: ValueTypeOrAny) -> BoundInstruction;
+}
+
+impl Bindable for Instruction {
+    fn bind_type(&self, value_type: ValueTypeOrAny) -> BoundInstruction {
+        assert!(self.is_polymorphic(), "{} is not polymorphic", self.name);
+        BoundInstruction::from(self).bind_type(value_type)
+    }
+}
+
+impl Bindable for BoundInstruction {
+    fn bind_type(&self, value_type: ValueTypeOrAny) -> BoundInstruction {
+        let mut value_types = self.value_types.clone();
+        value_types.push(value_type);
+        assert!(
+            value_types.len() <= self.inst.all_typevars().len(),
+            "too many types bound to {}",
+            self.inst.name
+        );
+        BoundInstruction {
+            inst: self.inst.clone(),
+            value_types,
+        }
+    }
+}
+
+/// An instruction predicate, evaluated in the context of an instruction and
+/// its containing function.
+#[derive(Clone, Debug, PartialEq)]
+pub enum InstructionPredicate {
+    /// Call the boolean predicate function `function` in the
+    /// `predicates` module with the field `member` of the instruction format
+    /// `format` as its first argument, followed by `args`.
+    Field {
+        format: &'static str,
+        member: &'static str,
+        function: &'static str,
+        args: Vec<String>,
+    },
+    /// The value operand with index `value_arg` has the type `value_type`.
+    TypeCheck {
+        value_arg: usize,
+        value_type: ValueType,
+    },
+    /// The controlling type variable of the instruction is `value_type`.
+    CtrlTypeCheck(ValueType),
+    /// All of the parts are true.
+    And(Vec<InstructionPredicate>),
+    /// Any of the parts is true.
+    Or(Vec<InstructionPredicate>),
+}
+
+impl InstructionPredicate {
+    fn field<S: Into<String>>(
+        format: &InstructionFormat,
+        member: &'static str,
+        function: &'static str,
+        args: Vec<S>,
+    ) -> Self {
+        if member == "args" {
+            assert!(
+                format.has_value_list,
+                "{} doesn't have a value list",
+                format.name
+            );
+        } else {
+            format.imm_by_name(member);
+        }
+        InstructionPredicate::Field {
+            format: format.name,
+            member,
+            function,
+            args: args.into_iter().map(|arg| arg.into()).collect(),
+        }
+    }
+
+    /// A predicate checking that the immediate field `member` is equal to the
+    /// Rust expression `value`.
+    pub fn is_equal<S: Into<String>>(
+        format: &InstructionFormat,
+        member: &'static str,
+        value: S,
+    ) -> Self {
+        Self::field(format, member, "is_equal", vec![value])
+    }
+
+    /// A predicate checking that the immediate field `member` is representable
+    /// as a `width`-bit two's complement integer whose `scale` low bits are
+    /// zero.
+    pub fn is_signed_int(
+        format: &InstructionFormat,
+        member: &'static str,
+        width: usize,
+        scale: usize,
+    ) -> Self {
+        assert!(width <= 64 && scale < width);
+        let args = vec![width.to_string(), scale.to_string()];
+        Self::field(format, member, "is_signed_int", args)
+    }
+
+    /// A predicate checking that the immediate field `member` is representable
+    /// as a `width`-bit unsigned integer whose `scale` low bits are zero.
+    pub fn is_unsigned_int(
+        format: &InstructionFormat,
+        member: &'static str,
+        width: usize,
+        scale: usize,
+    ) -> Self {
+        assert!(width <= 64 && scale < width);
+        let args = vec![width.to_string(), scale.to_string()];
+        Self::field(format, member, "is_unsigned_int", args)
+    }
+
+    /// A predicate checking that the `Ieee32` field `member` is zero.
+    pub fn is_zero_32_bit_float(format: &InstructionFormat, member: &'static str) -> Self {
+        Self::field::<String>(format, member, "is_zero_32_bit_float", vec![])
+    }
+
+    /// A predicate checking that the `Ieee64` field `member` is zero.
+    pub fn is_zero_64_bit_float(format: &InstructionFormat, member: &'static str) -> Self {
+        Self::field::<String>(format, member, "is_zero_64_bit_float", vec![])
+    }
+
+    /// A predicate checking that the function referenced by the field
+    /// `member` is colocated.
+    pub fn is_colocated_func(format: &InstructionFormat, member: &'static str) -> Self {
+        Self::field(format, member, "is_colocated_func", vec!["func"])
+    }
+
+    /// A predicate checking that the global value referenced by the field
+    /// `member` is colocated.
+    pub fn is_colocated_data(format: &InstructionFormat, member: &'static str) -> Self {
+        Self::field(format, member, "is_colocated_data", vec!["func"])
+    }
+
+    /// A predicate checking that the value list of an instruction with the
+    /// format `format` has `length` values.
+    pub fn length_equals(format: &InstructionFormat, length: usize) -> Self {
+        let args = vec![length.to_string(), "func".to_string()];
+        Self::field(format, "args", "has_length_of", args)
+    }
+
+    /// A predicate checking the type of the first value operand of `inst`
+    /// whose type is the free type variable `type_var`.
+    pub fn typevar_check(
+        inst: &InstructionContent,
+        type_var: &TypeVar,
+        value_type: ValueType,
+    ) -> Self {
+        let value_arg = inst
+            .value_opnums
+            .iter()
+            .position(|&opnum| inst.operands_in[opnum].type_var() == Some(type_var))
+            .unwrap_or_else(|| panic!("{} is not the type of a {} operand", type_var, inst.name));
+        InstructionPredicate::TypeCheck {
+            value_arg,
+            value_type,
+        }
+    }
+
+    /// Combine a sequence of optional predicates into a predicate that is true
+    /// when all of them are true, or `None` if there are none.
+    pub fn combine(parts: Vec<Option<InstructionPredicate>>) -> Option<Self> {
+        let mut parts: Vec<InstructionPredicate> = parts.into_iter().flatten().collect();
+        match parts.len() {
+            0 => None,
+            1 => parts.pop(),
+            _ => Some(InstructionPredicate::And(parts)),
+        }
+    }
+
+    /// Get the name of the instruction format this predicate must be
+    /// evaluated in, or `None` if it only checks types and applies to any
+    /// instruction.
+    pub fn format_name(&self) -> Option<&'static str> {
+        match *self {
+            InstructionPredicate::Field { format, .. } => Some(format),
+            InstructionPredicate::TypeCheck { .. } | InstructionPredicate::CtrlTypeCheck(_) => None,
+            InstructionPredicate::And(ref parts) | InstructionPredicate::Or(ref parts) => {
+                let mut formats = parts.iter().filter_map(|p| p.format_name());
+                let format = formats.next();
+                assert!(
+                    formats.all(|f| Some(f) == format),
+                    "incompatible predicate parts in {}",
+                    self
+                );
+                format
+            }
+        }
+    }
+
+    /// Does this predicate check the type of any values?
+    pub fn has_type_check(&self) -> bool {
+        match *self {
+            InstructionPredicate::Field { .. } => false,
+            InstructionPredicate::TypeCheck { .. } | InstructionPredicate::CtrlTypeCheck(_) => true,
+            InstructionPredicate::And(ref parts) | InstructionPredicate::Or(ref parts) => {
+                parts.iter().any(|p| p.has_type_check())
+            }
+        }
+    }
+
+    /// Get the sorted names to use when destructuring the instruction format
+    /// fields needed by this predicate.
+    pub fn field_names(&self) -> Vec<&'static str> {
+        fn collect(pred: &InstructionPredicate, names: &mut Vec<&'static str>) {
+            match *pred {
+                InstructionPredicate::Field { member, .. } => {
+                    names.push(if member == "args" { "ref args" } else { member })
+                }
+                InstructionPredicate::TypeCheck { .. } | InstructionPredicate::CtrlTypeCheck(_) => {
+                }
+                InstructionPredicate::And(ref parts) | InstructionPredicate::Or(ref parts) => {
+                    for part in parts {
+                        collect(part, names);
+                    }
+                }
+            }
+        }
+        let mut names = Vec::new();
+        collect(self, &mut names);
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Get a Rust expression evaluating this predicate. The expression may
+    /// refer to the `func`, `inst` and `args` variables.
+    ///
+    /// The surrounding precedence determines whether parentheses are needed:
+    ///
+    /// 0. An `if` statement.
+    /// 1. An `||` expression.
+    /// 2. An `&&` expression.
+    pub fn rust_predicate(&self, precedence: u8) -> String {
+        match *self {
+            InstructionPredicate::Field {
+                member,
+                function,
+                ref args,
+                ..
+            } => {
+                let mut all_args = vec![member.to_string()];
+                all_args.extend(args.iter().cloned());
+                format!("predicates::{}({})", function, all_args.join(", "))
+            }
+            InstructionPredicate::TypeCheck {
+                value_arg,
+                ref value_type,
+            } => format!(
+                "func.dfg.value_type(args[{}]) == {}",
+                value_arg,
+                value_type.rust_name()
+            ),
+            InstructionPredicate::CtrlTypeCheck(ref value_type) => {
+                format!("func.dfg.ctrl_typevar(inst) == {}", value_type.rust_name())
+            }
+            InstructionPredicate::And(ref parts) => {
+                let s = parts
+                    .iter()
+                    .map(|p| p.rust_predicate(2))
+                    .collect::<Vec<_>>()
+                    .join(" && ");
+                if precedence > 2 {
+                    format!("({})", s)
+                } else {
+                    s
+                }
+            }
+            InstructionPredicate::Or(ref parts) => {
+                let s = parts
+                    .iter()
+                    .map(|p| p.rust_predicate(1))
+                    .collect::<Vec<_>>()
+                    .join(" || ");
+                if precedence > 1 {
+                    format!("({})", s)
+                } else {
+                    s
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for InstructionPredicate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InstructionPredicate::Field {
+                member,
+                function,
+                ref args,
+                ..
+            } => {
+                let mut all_args = vec![member.to_string()];
+                all_args.extend(args.iter().cloned());
+                write!(f, "{}({})", function, all_args.join(", "))
+            }
+            InstructionPredicate::TypeCheck {
+                value_arg,
+                ref value_type,
+            } => write!(f, "args[{}]:{}", value_arg, value_type),
+            InstructionPredicate::CtrlTypeCheck(ref value_type) => {
+                write!(f, "ctrl_typevar:{}", value_type)
+            }
+            InstructionPredicate::And(ref parts) | InstructionPredicate::Or(ref parts) => {
+                let name = match *self {
+                    InstructionPredicate::And(_) => "And",
+                    _ => "Or",
+                };
+                let parts = parts
+                    .iter()
+                    .map(|p| p.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(f, "{}({})", name, parts)
+            }
+        }
+    }
+}
+
+pub struct InstructionBuilder {
+    name: &'static str,
+    doc: &'static str,
+    operands_in: Vec<Operand>,
+    operands_out: Vec<Operand>,
+    constraints: Vec<Constraint>,
+
+    is_terminator: bool,
+    is_branch: bool,
+    is_call: bool,
+    is_return: bool,
+    can_load: bool,
+    can_store: bool,
+    can_trap: bool,
+    other_side_effects: bool,
+}
+
+#[cfg_attr(feature = "cargo-clippy", allow(wrong_self_convention))]
+impl InstructionBuilder {
+    pub fn new(name: &'static str, doc: &'static str) -> Self {
+        Self {
+            name,
+            doc,
+            operands_in: Vec::new(),
+            operands_out: Vec::new(),
+            constraints: Vec::new(),
+
+            is_terminator: false,
+            is_branch: false,
+            is_call: false,
+            is_return: false,
+            can_load: false,
+            can_store: false,
+            can_trap: false,
+            other_side_effects: false,
+        }
+    }
+
+    pub fn operands_in(mut self, operands: Vec<&Operand>) -> Self {
+        assert!(self.operands_in.is_empty());
+        self.operands_in = operands.into_iter().cloned().collect();
+        self
+    }
+
+    pub fn operands_out(mut self, operands: Vec<&Operand>) -> Self {
+        assert!(self.operands_out.is_empty());
+        self.operands_out = operands.into_iter().cloned().collect();
+        self
+    }
+
+    pub fn constraints(mut self, constraints: Vec<Constraint>) -> Self {
+        assert!(self.constraints.is_empty());
+        self.constraints = constraints;
+        self
+    }
+
+    pub fn is_terminator(mut self, val: bool) -> Self {
+        self.is_terminator = val;
+        self
+    }
+
+    pub fn is_branch(mut self, val: bool) -> Self {
+        self.is_branch = val;
+        self
+    }
+
+    pub fn is_call(mut self, val: bool) -> Self {
+        self.is_call = val;
+        self
+    }
+
+    pub fn is_return(mut self, val: bool) -> Self {
+        self.is_return = val;
+        self
+    }
+
+    pub fn can_load(mut self, val: bool) -> Self {
+        self.can_load = val;
+        self
+    }
+
+    pub fn can_store(mut self, val: bool) -> Self {
+        self.can_store = val;
+        self
+    }
+
+    pub fn can_trap(mut self, val: bool) -> Self {
+        self.can_trap = val;
+        self
+    }
+
+    pub fn other_side_effects(mut self, val: bool) -> Self {
+        self.other_side_effects = val;
+        self
+    }
+
+    fn build(self, format_registry: &FormatRegistry) -> Instruction {
+        let format_index = format_registry.lookup(&self.operands_in);
+
+        let value_opnums: Vec<usize> = self
+            .operands_in
+            .iter()
+            .enumerate()
+            .filter(|(_, op)| op.is_value())
+            .map(|(i, _)| i)
+            .collect();
+        let value_results: Vec<usize> = self
+            .operands_out
+            .iter()
+            .enumerate()
+            .filter(|(_, op)| op.is_value())
+            .map(|(i, _)| i)
+            .collect();
+        let imm_opnums: Vec<usize> = self
+            .operands_in
+            .iter()
+            .enumerate()
+            .filter(|(_, op)| op.is_immediate())
+            .map(|(i, _)| i)
+            .collect();
+
+        let typevar_operand = format_registry.get(format_index).typevar_operand;
+        let polymorphic_info = verify_polymorphic(
+            &self.operands_in,
+            &self.operands_out,
+            typevar_operand,
+            &value_opnums,
+        )
+        .unwrap_or_else(|err| panic!("{}: {}", self.name, err));
+
+        // Infer the 'writes_cpu_flags' field value.
+        let writes_cpu_flags = self.operands_out.iter().any(|op| op.is_cpu_flags());
+
+        Rc::new(InstructionContent {
+            name: self.name,
+            camel_name: camel_case(self.name),
+            doc: self.doc,
+            operands_in: self.operands_in,
+            operands_out: self.operands_out,
+            format: format_index,
+            value_opnums,
+            value_results,
+            imm_opnums,
+            polymorphic_info,
+            constraints: self.constraints,
+            is_terminator: self.is_terminator,
+            is_branch: self.is_branch,
+            is_call: self.is_call,
+            is_return: self.is_return,
+            can_load: self.can_load,
+            can_store: self.can_store,
+            can_trap: self.can_trap,
+            other_side_effects: self.other_side_effects,
+            writes_cpu_flags,
+        })
+    }
+}
+
+/// Check if an instruction is polymorphic, and verify its use of type
+/// variables.
+fn verify_polymorphic(
+    operands_in: &[Operand],
+    operands_out: &[Operand],
+    typevar_operand: Option<usize>,
+    value_opnums: &[usize],
+) -> Result<Option<PolymorphicInfo>, String> {
+    let is_polymorphic = operands_in
+        .iter()
+        .chain(operands_out.iter())
+        .filter_map(|op| op.type_var())
+        .any(|tv| tv.free_typevar().is_some());
+    if !is_polymorphic {
+        return Ok(None);
+    }
+
+    // Prefer to use the typevar_operand to infer the controlling typevar.
+    let mut typevar_error = None;
+    if let Some(tv_op) = typevar_operand {
+        if tv_op < value_opnums.len() {
+            let tv = operands_in[value_opnums[tv_op]].type_var().unwrap();
+            if tv.free_typevar().as_ref() == Some(tv) || tv.singleton_type().is_some() {
+                match verify_ctrl_typevar(tv, operands_in, operands_out, value_opnums) {
+                    Ok(other_typevars) => {
+                        return Ok(Some(PolymorphicInfo {
+                            use_typevar_operand: true,
+                            ctrl_typevar: tv.clone(),
+                            other_typevars,
+                        }))
+                    }
+                    Err(err) => typevar_error = Some(err),
+                }
+            }
+        }
+    }
+
+    // The typevar_operand argument doesn't work. Can we infer from the first
+    // result instead?
+    if operands_out.is_empty() {
+        return Err(typevar_error
+            .unwrap_or_else(|| "typevar_operand must be a free type variable".to_string()));
+    }
+    let tv = operands_out[0]
+        .type_var()
+        .ok_or_else(|| "first result must be a value".to_string())?;
+    if tv.free_typevar().as_ref() != Some(tv) {
+        return Err("first result must be a free type variable".to_string());
+    }
+    let other_typevars = verify_ctrl_typevar(tv, operands_in, operands_out, value_opnums)?;
+    Ok(Some(PolymorphicInfo {
+        use_typevar_operand: false,
+        ctrl_typevar: tv.clone(),
+        other_typevars,
+    }))
+}
+
+/// Verify that the use of TypeVars is consistent with `ctrl_typevar` as the
+/// controlling type variable.
+///
+/// All polymorphic inputs must either be derived from `ctrl_typevar` or be
+/// independent free type variables only used once.
+///
+/// All polymorphic results must be derived from `ctrl_typevar`.
+///
+/// Return the list of other type variables used, or an error.
+fn verify_ctrl_typevar(
+    ctrl_typevar: &TypeVar,
+    operands_in: &[Operand],
+    operands_out: &[Operand],
+    value_opnums: &[usize],
+) -> Result<Vec<TypeVar>, String> {
+    let mut other_typevars = Vec::new();
+
+    // Check value inputs.
+    for &opnum in value_opnums {
+        let typ = operands_in[opnum].type_var().unwrap();
+        let tv = match typ.free_typevar() {
+            // Non-polymorphic or derived form ctrl_typevar is OK.
+            None => continue,
+            Some(ref tv) if tv == ctrl_typevar => continue,
+            Some(tv) => tv,
+        };
+
+        // No other derived typevars allowed.
+        if *typ != tv {
+            return Err(format!(
+                "{}: type variable {} must be derived from {}",
+                operands_in[opnum],
+                typ.name(),
+                ctrl_typevar
+            ));
+        }
+
+        // Other free type variables can only be used once each.
+        if other_typevars.contains(&tv) {
+            return Err(format!(
+                "type variable {} can't be used more than once",
+                tv.name()
+            ));
+        }
+
+        other_typevars.push(tv);
+    }
+
+    // Check outputs.
+    for result in operands_out {
+        let tv = match result.type_var() {
+            Some(typ) => typ.free_typevar(),
+            None => continue,
+        };
+        // Non-polymorphic or derived from ctrl_typevar is OK.
+        if tv.is_none() || tv.as_ref() == Some(ctrl_typevar) {
+            continue;
+        }
+        return Err("type variable in output not derived from ctrl_typevar".to_string());
+    }
+
+    Ok(other_typevars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cdsl::formats::InstructionFormatBuilder;
+    use cdsl::typevar::{Interval, TypeSetBuilder};
+
+    fn format_registry() -> FormatRegistry {
+        let mut registry = FormatRegistry::new();
+        registry.insert(InstructionFormatBuilder::new("Unary").value());
+        registry.insert(InstructionFormatBuilder::new("Binary").value().value());
+        registry.insert(InstructionFormatBuilder::new("NullAry"));
+        registry
+    }
+
+    fn int_typevar(name: &'static str) -> TypeVar {
+        TypeVar::new(
+            name,
+            "A scalar or vector integer type",
+            TypeSetBuilder::new()
+                .ints(Interval::All)
+                .simd_lanes(Interval::All)
+                .finish(),
+        )
+    }
+
+    #[test]
+    fn infers_ctrl_typevar_from_operand() {
+        let registry = format_registry();
+        let int = &int_typevar("Int");
+        let x = &Operand::new("x", int);
+        let y = &Operand::new("y", int);
+        let a = &Operand::new("a", int);
+        let c = &Operand::new("c", &int.as_bool());
+
+        let mut ig = InstructionGroupBuilder::new("test", "Test instructions", &registry);
+        ig.push(
+            InstructionBuilder::new("iadd", "Wrapping integer addition.")
+                .operands_in(vec![x, y])
+                .operands_out(vec![a]),
+        );
+        ig.push(
+            InstructionBuilder::new("is_zero", "Test for zero.")
+                .operands_in(vec![x])
+                .operands_out(vec![c]),
+        );
+        ig.push(InstructionBuilder::new("nop", "Do nothing.").other_side_effects(true));
+        let ig = ig.finish();
+
+        let iadd = ig.by_name("iadd");
+        assert_eq!(iadd.to_string(), "a = iadd x, y");
+        assert_eq!(iadd.camel_name, "Iadd");
+        assert_eq!(iadd.value_opnums, vec![0, 1]);
+        let poly = iadd.polymorphic_info.as_ref().unwrap();
+        assert!(poly.use_typevar_operand);
+        assert!(poly.ctrl_typevar == *int);
+        assert!(poly.other_typevars.is_empty());
+
+        assert!(ig.by_name("is_zero").is_polymorphic());
+        let nop = ig.by_name("nop");
+        assert!(!nop.is_polymorphic());
+        assert!(nop.other_side_effects);
+        assert_eq!(nop.blurb(), "Do nothing.");
+    }
+
+    #[test]
+    fn infers_ctrl_typevar_from_result() {
+        let registry = format_registry();
+        let int = &int_typevar("Int");
+        let a = &Operand::new("a", int);
+
+        let mut ig = InstructionGroupBuilder::new("test", "Test instructions", &registry);
+        ig.push(InstructionBuilder::new("make", "Make a value.").operands_out(vec![a]));
+        let ig = ig.finish();
+
+        let poly = ig.by_name("make").polymorphic_info.as_ref().unwrap();
+        assert!(!poly.use_typevar_operand);
+        assert!(poly.ctrl_typevar == *int);
+    }
+
+    #[test]
+    #[should_panic(expected = "type variable in output not derived from ctrl_typevar")]
+    fn rejects_unrelated_result_typevar() {
+        let registry = format_registry();
+        let a = &Operand::new("a", &int_typevar("Int"));
+        let b = &Operand::new("b", &int_typevar("IntTo"));
+
+        let mut ig = InstructionGroupBuilder::new("test", "Test instructions", &registry);
+        ig.push(InstructionBuilder::new("make_two", "Make two values.").operands_out(vec![a, b]));
+    }
+}
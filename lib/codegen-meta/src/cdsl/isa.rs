@@ -0,0 +1,111 @@
+//! Defining instruction set architectures.
+
+use cdsl::cpu_modes::CpuMode;
+use cdsl::instructions::{InstructionGroup, InstructionPredicate};
+use cdsl::recipes::{EncodingRecipe, EncodingRecipeNumber, RecipeGroup};
+use cdsl::registers::IsaRegs;
+use cdsl::settings::SettingGroup;
+use cdsl::xform::XFormGroupIndex;
+
+/// A target instruction set architecture.
+///
+/// The `TargetIsa` struct collects everything known about a target ISA.
+pub struct TargetIsa {
+    /// Short mnemonic name for the ISA.
+    pub name: &'static str,
+    pub settings: SettingGroup,
+    pub regs: IsaRegs,
+    /// The ISA-specific instructions, in addition to the base instruction
+    /// set.
+    pub instructions: InstructionGroup,
+    /// The encoding recipes used by the CPU modes, numbered in order of first
+    /// use.
+    pub recipes: RecipeGroup,
+    pub cpu_modes: Vec<CpuMode>,
+}
+
+impl TargetIsa {
+    pub fn new(
+        name: &'static str,
+        settings: SettingGroup,
+        regs: IsaRegs,
+        instructions: InstructionGroup,
+        recipes: RecipeGroup,
+        mut cpu_modes: Vec<CpuMode>,
+    ) -> Self {
+        // Number the recipes in use in the order they are first used, and
+        // drop the unused ones.
+        let mut order = Vec::new();
+        for cpu_mode in &cpu_modes {
+            for encoding in cpu_mode.encodings() {
+                if !order.contains(&encoding.recipe) {
+                    order.push(encoding.recipe);
+                }
+            }
+        }
+        let (recipes, new_numbers) = recipes.reorder(&order);
+        for cpu_mode in &mut cpu_modes {
+            for encoding in cpu_mode.encodings_mut() {
+                encoding.recipe = new_numbers[encoding.recipe.index()].unwrap();
+            }
+        }
+
+        Self {
+            name,
+            settings,
+            regs,
+            instructions,
+            recipes,
+            cpu_modes,
+        }
+    }
+
+    /// Get the CPU mode named `name`.
+    pub fn cpu_mode_mut(&mut self, name: &str) -> &mut CpuMode {
+        let isa_name = self.name;
+        self.cpu_modes
+            .iter_mut()
+            .find(|cpu_mode| cpu_mode.name == name)
+            .unwrap_or_else(|| panic!("unknown CPU mode {} for {}", name, isa_name))
+    }
+
+    /// Add an encoding recipe after the ones used by the CPU modes, so the
+    /// numbers of the existing recipes don't change.
+    pub fn add_recipe(&mut self, recipe: EncodingRecipe) -> EncodingRecipeNumber {
+        self.recipes.push(recipe)
+    }
+
+    /// Get the unique instruction predicates of the encodings of this ISA, in
+    /// the order they are first used. The position of a predicate in this list
+    /// is its number in the encoding tables.
+    pub fn inst_predicates(&self) -> Vec<&InstructionPredicate> {
+        let mut predicates = Vec::new();
+        for cpu_mode in &self.cpu_modes {
+            for encoding in cpu_mode.encodings() {
+                if let Some(ref pred) = encoding.inst_predicate {
+                    if !predicates.contains(&pred) {
+                        predicates.push(pred);
+                    }
+                }
+            }
+        }
+        predicates
+    }
+
+    /// Get the legalization actions used by the CPU modes of this ISA.
+    ///
+    /// Each target ISA has its own list of legalization actions; the position
+    /// of an action in this list is the legalize code that appears in the
+    /// encoding tables.
+    pub fn legalize_codes(&self) -> Vec<XFormGroupIndex> {
+        let mut codes = Vec::new();
+        for cpu_mode in &self.cpu_modes {
+            for group in cpu_mode.legalize_actions() {
+                if !codes.contains(&group) {
+                    codes.push(group);
+                }
+            }
+        }
+        codes
+    }
+}
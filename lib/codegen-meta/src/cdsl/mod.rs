@@ -3,18 +3,37 @@
 //! This module defines the classes that are used to define Cretonne
 //! instructions and other entitties.
 
+#[macro_use]
+pub mod settings;
+#[macro_use]
+pub mod ast;
+pub mod cpu_modes;
+pub mod encodings;
+pub mod formats;
+pub mod instructions;
+pub mod isa;
+pub mod operands;
+pub mod recipes;
+pub mod registers;
+pub mod type_inference;
 pub mod types;
+pub mod typevar;
+pub mod xform;
 
 /// Convert the string `s` to CamelCase.
-fn _camel_case(s: &str) -> String {
+///
+/// Underscores are only removed when they are followed by a lowercase letter,
+/// so `system_v` becomes `SystemV`.
+pub fn camel_case(s: &str) -> String {
     let mut output_chars = String::with_capacity(s.len());
 
+    let mut chars = s.chars().peekable();
     let mut capitalize = true;
-    for curr_char in s.chars() {
-        if curr_char == '_' {
+    while let Some(curr_char) = chars.next() {
+        if curr_char == '_' && chars.peek().map_or(false, |c| c.is_ascii_lowercase()) {
             capitalize = true;
         } else {
-            if capitalize {
+            if capitalize && curr_char.is_ascii_lowercase() {
                 output_chars.extend(curr_char.to_uppercase());
             } else {
                 output_chars.push(curr_char);
@@ -32,7 +51,7 @@ fn _is_power_of_two(x: u8) -> bool {
 }
 
 /// Compute the next power of two that is greater than `x`.
-fn _next_power_of_two(x: u8) -> u8 {
+pub fn next_power_of_two(x: usize) -> usize {
     let mut s = 1;
     let mut res = x;
     while res & (res + 1) != 0 {
@@ -45,14 +64,17 @@ fn _next_power_of_two(x: u8) -> u8 {
 
 #[cfg(test)]
 mod tests {
-    use super::_camel_case as camel_case;
     use super::_is_power_of_two as is_power_of_two;
-    use super::_next_power_of_two as next_power_of_two;
+    use super::camel_case;
+    use super::next_power_of_two;
 
     #[test]
     fn camel_case_works() {
         assert_eq!(camel_case("x"), "X");
         assert_eq!(camel_case("camel_case"), "CamelCase");
+        assert_eq!(camel_case("system_v"), "SystemV");
+        assert_eq!(camel_case("i32_x4"), "I32X4");
+        assert_eq!(camel_case("x_1"), "X_1");
     }
 
     #[test]
@@ -0,0 +1,267 @@
+//! Classes for describing instruction operands.
+
+use std::fmt;
+
+use cdsl::camel_case;
+use cdsl::typevar::TypeVar;
+
+/// The kind of an operand.
+///
+/// Each instruction has an opcode and a number of operands. The opcode
+/// determines the instruction format, and the format determines the number of
+/// operands and the kind of each operand.
+///
+/// An instance of `OperandKind` corresponds to a kind of operand. Each operand
+/// kind has a corresponding type in the Rust representation of an
+/// instruction.
+#[derive(Clone, Debug)]
+pub struct OperandKind {
+    pub name: &'static str,
+    doc: Option<&'static str>,
+    /// The default member name of this kind in the `InstructionData` data
+    /// structure.
+    pub default_member: Option<&'static str>,
+    /// The Rust type used to represent operands of this kind.
+    pub rust_type: String,
+    pub fields: OperandKindFields,
+}
+
+/// The variants of operand kinds.
+#[derive(Clone, Debug)]
+pub enum OperandKindFields {
+    /// An SSA value defined by another instruction, with the type variable
+    /// constraining its type.
+    TypeVar(TypeVar),
+    /// A variable-sized list of value operands.
+    VariableArgs,
+    /// An immediate operand.
+    ImmValue,
+    /// An enumerated immediate operand, with its values mapped to the Rust
+    /// enumerator names.
+    ImmEnum(Vec<(&'static str, &'static str)>),
+    /// A reference to another entity in the function.
+    EntityRef,
+}
+
+impl OperandKind {
+    /// An SSA value operand whose type is constrained by `type_var`.
+    ///
+    /// This kind of operand can represent any SSA value type, but the
+    /// instruction format may restrict the valid value types for a given
+    /// operand.
+    pub fn new_value(type_var: TypeVar) -> Self {
+        Self {
+            name: "value",
+            doc: None,
+            default_member: None,
+            rust_type: "ir::Value".to_string(),
+            fields: OperandKindFields::TypeVar(type_var),
+        }
+    }
+
+    /// A variable size list of `value` operands.
+    ///
+    /// Use this to represent arguments passed to a function call, arguments
+    /// passed to an extended basic block, or a variable number of results
+    /// returned from an instruction.
+    pub fn new_variable_args() -> Self {
+        Self {
+            name: "variable_args",
+            doc: Some(
+                r#"
+                A variable size list of `value` operands.
+
+                Use this to represent arguments passed to a function call, arguments
+                passed to an extended basic block, or a variable number of results
+                returned from an instruction.
+                "#,
+            ),
+            default_member: None,
+            rust_type: "&[Value]".to_string(),
+            fields: OperandKindFields::VariableArgs,
+        }
+    }
+
+    /// The kind of an immediate instruction operand. Its default member name
+    /// in `InstructionData` is `imm`.
+    pub fn new_imm(name: &'static str, doc: &'static str) -> Self {
+        Self {
+            name,
+            doc: Some(doc),
+            default_member: Some("imm"),
+            rust_type: format!("ir::immediates::{}", camel_case(name)),
+            fields: OperandKindFields::ImmValue,
+        }
+    }
+
+    /// The kind of an entity reference instruction operand. Its default
+    /// member name in `InstructionData` is the kind name.
+    pub fn new_entity_ref(name: &'static str, doc: &'static str) -> Self {
+        Self {
+            name,
+            doc: Some(doc),
+            default_member: Some(name),
+            rust_type: format!("ir::{}", camel_case(name)),
+            fields: OperandKindFields::EntityRef,
+        }
+    }
+
+    /// Set the default member name of this kind.
+    pub fn default_member(mut self, member: &'static str) -> Self {
+        self.default_member = Some(member);
+        self
+    }
+
+    /// Set the Rust type representing this kind.
+    pub fn rust_type(mut self, rust_type: &'static str) -> Self {
+        self.rust_type = rust_type.to_string();
+        self
+    }
+
+    /// Turn an immediate kind into an enumerated immediate kind, where
+    /// `values` maps the enumerator names to the Rust enumerators.
+    pub fn enumerators(mut self, values: Vec<(&'static str, &'static str)>) -> Self {
+        match self.fields {
+            OperandKindFields::ImmValue => {}
+            _ => panic!("{} is not an immediate operand kind", self.name),
+        }
+        self.fields = OperandKindFields::ImmEnum(values);
+        self
+    }
+
+    pub fn doc(&self) -> Option<&str> {
+        self.doc
+    }
+
+    /// Get the qualified Rust name of the enumerator value `value`.
+    pub fn rust_enumerator(&self, value: &str) -> String {
+        match self.fields {
+            OperandKindFields::ImmEnum(ref values) => {
+                let &(_, rust_name) = values
+                    .iter()
+                    .find(|&&(name, _)| name == value)
+                    .unwrap_or_else(|| panic!("No such {} enumerator: {}", self.name, value));
+                format!("{}::{}", self.rust_type, rust_name)
+            }
+            _ => panic!("{} is not an enumerated operand kind", self.name),
+        }
+    }
+}
+
+/// Operand kinds are identified by name.
+impl PartialEq for OperandKind {
+    fn eq(&self, other: &OperandKind) -> bool {
+        self.name == other.name
+    }
+}
+
+impl fmt::Display for OperandKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl<'a> From<&'a TypeVar> for OperandKind {
+    fn from(type_var: &'a TypeVar) -> Self {
+        OperandKind::new_value(type_var.clone())
+    }
+}
+
+impl<'a> From<&'a OperandKind> for OperandKind {
+    fn from(kind: &'a OperandKind) -> Self {
+        kind.clone()
+    }
+}
+
+/// An instruction operand can be an *immediate*, an *SSA value*, or an
+/// *entity reference*. The kind of the operand is one of:
+///
+/// 1. A value operand, whose type is constrained by a `TypeVar`. The
+///    instruction is polymorphic over the possible concrete types that the
+///    type variable can assume, or a single type for singleton type
+///    variables.
+///
+/// 2. An immediate operand, whose value is encoded in the instruction itself
+///    rather than being passed as an SSA value.
+///
+/// 3. An entity reference operand, which references another entity in the
+///    function, typically something declared in the function preamble.
+///
+/// 4. A variable size list of value operands.
+#[derive(Clone, Debug)]
+pub struct Operand {
+    pub name: &'static str,
+    doc: Option<&'static str>,
+    pub kind: OperandKind,
+}
+
+impl Operand {
+    pub fn new<K: Into<OperandKind>>(name: &'static str, kind: K) -> Self {
+        Self {
+            name,
+            doc: None,
+            kind: kind.into(),
+        }
+    }
+
+    pub fn with_doc(mut self, doc: &'static str) -> Self {
+        self.doc = Some(doc);
+        self
+    }
+
+    /// Get the documentation of this operand, falling back to the
+    /// documentation of its type variable or kind.
+    pub fn doc(&self) -> Option<&str> {
+        if let Some(doc) = self.doc {
+            return Some(doc);
+        }
+        match self.kind.fields {
+            OperandKindFields::TypeVar(ref tv) => Some(tv.doc()),
+            _ => self.kind.doc(),
+        }
+    }
+
+    /// Get the type variable of a value operand.
+    pub fn type_var(&self) -> Option<&TypeVar> {
+        match self.kind.fields {
+            OperandKindFields::TypeVar(ref tv) => Some(tv),
+            _ => None,
+        }
+    }
+
+    /// Is this an SSA value operand?
+    pub fn is_value(&self) -> bool {
+        self.type_var().is_some()
+    }
+
+    /// Is this a variable size list of value operands?
+    pub fn is_varargs(&self) -> bool {
+        match self.kind.fields {
+            OperandKindFields::VariableArgs => true,
+            _ => false,
+        }
+    }
+
+    /// Is this an immediate operand?
+    ///
+    /// Note that this includes both immediate operands *and* entity
+    /// references. It is any operand that doesn't represent a value
+    /// dependency.
+    pub fn is_immediate(&self) -> bool {
+        !self.is_value() && !self.is_varargs()
+    }
+
+    /// Is this a CPU flags operand?
+    pub fn is_cpu_flags(&self) -> bool {
+        match self.type_var() {
+            Some(tv) => tv.name() == "iflags" || tv.name() == "fflags",
+            None => false,
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "`{}`", self.name)
+    }
+}
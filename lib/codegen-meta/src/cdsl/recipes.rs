@@ -0,0 +1,368 @@
+//! Encoding recipes.
+//!
+//! A recipe describes how to encode the instructions of a given format, and
+//! which register allocation constraints apply to their operands.
+
+use std::slice;
+
+use cdsl::formats::{FormatRegistry, InstructionFormatIndex};
+use cdsl::instructions::InstructionPredicate;
+use cdsl::registers::RegClass;
+
+/// A specific register in a register class.
+///
+/// A register is identified by the top-level register class it belongs to and
+/// its first register unit.
+///
+/// Specific registers are used to describe constraints on instructions where
+/// some operands must use a fixed register.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Register {
+    /// Index of the register class.
+    pub regclass: usize,
+    /// Register unit, counted from the first unit of all banks.
+    pub unit: u8,
+}
+
+impl Register {
+    pub fn new(regclass: &RegClass, unit: u8) -> Self {
+        Self {
+            regclass: regclass.index,
+            unit,
+        }
+    }
+}
+
+/// An operand that must be in a stack slot.
+///
+/// A `Stack` object can be used to indicate an operand constraint for a value
+/// operand that must live in a stack slot.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stack {
+    /// Index of the register class of the value.
+    pub regclass: usize,
+}
+
+impl Stack {
+    pub fn new(regclass: &RegClass) -> Self {
+        Self {
+            regclass: regclass.index,
+        }
+    }
+
+    /// Get the StackBaseMask to use for this operand.
+    ///
+    /// This is a mask of base registers that can be supported by this operand.
+    pub fn stack_base_mask(self) -> &'static str {
+        // TODO: Make this configurable instead of just using the SP.
+        "StackBaseMask(1)"
+    }
+}
+
+/// The exact range that can be encoded in a branch instruction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BranchRange {
+    /// Offset in bytes from the address of the branch instruction to the
+    /// origin used for computing the branch displacement.
+    pub origin: u64,
+    /// Number of bits in the signed byte displacement.
+    pub bits: u64,
+}
+
+/// A register allocation constraint for a value operand or a result of an
+/// encoding recipe.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OperandConstraint {
+    /// The operand must be in a register of the class with this index.
+    RegClass(usize),
+    /// The operand must be in a specific register.
+    FixedReg(Register),
+    /// This result is tied to the value operand with the given index, so
+    /// they must use the same register.
+    TiedInput(usize),
+    /// The operand must be in a stack slot.
+    Stack(Stack),
+}
+
+impl<'a> From<&'a RegClass> for OperandConstraint {
+    fn from(regclass: &'a RegClass) -> Self {
+        OperandConstraint::RegClass(regclass.index)
+    }
+}
+
+impl From<Register> for OperandConstraint {
+    fn from(reg: Register) -> Self {
+        OperandConstraint::FixedReg(reg)
+    }
+}
+
+impl From<Stack> for OperandConstraint {
+    fn from(stack: Stack) -> Self {
+        OperandConstraint::Stack(stack)
+    }
+}
+
+/// A recipe for encoding instructions with a given format.
+///
+/// Many different instructions can be encoded by the same recipe, but they
+/// must all have the same instruction format.
+///
+/// For ISAs that use CPU flags in `iflags` and `fflags` value types, the
+/// `clobbers_flags` field is used to indicate instruction encodings that
+/// clobber the CPU flags, so they can't be used where a flag value is live.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EncodingRecipe {
+    /// Short mnemonic name for this recipe.
+    pub name: String,
+    /// All encoded instructions must have this instruction format.
+    pub format: InstructionFormatIndex,
+    /// Number of bytes in the binary encoded instruction.
+    pub base_size: u64,
+    /// Register constraints for the value operands.
+    pub operands_in: Vec<OperandConstraint>,
+    /// Register constraints for the results.
+    pub operands_out: Vec<OperandConstraint>,
+    /// The range that can be encoded, for recipes that can encode branches.
+    pub branch_range: Option<BranchRange>,
+    pub clobbers_flags: bool,
+    pub inst_predicate: Option<InstructionPredicate>,
+    /// Number of the ISA predicate in the ISA's setting group.
+    pub isa_predicate: Option<u8>,
+    /// Rust code for binary emission, or `None` to call a `recipe_<name>`
+    /// function in the target ISA's binemit module.
+    pub emit: Option<String>,
+}
+
+impl EncodingRecipe {
+    /// Get the map from value operands to their tied result, if any.
+    pub fn tied_inputs(&self) -> Vec<Option<usize>> {
+        let mut tied = vec![None; self.operands_in.len()];
+        for (out_num, constraint) in self.operands_out.iter().enumerate() {
+            if let OperandConstraint::TiedInput(in_num) = *constraint {
+                tied[in_num] = Some(out_num);
+            }
+        }
+        tied
+    }
+
+    /// Get the fixed registers used by `constraints`.
+    pub fn fixed_registers(constraints: &[OperandConstraint]) -> Vec<Register> {
+        constraints
+            .iter()
+            .filter_map(|c| match *c {
+                OperandConstraint::FixedReg(reg) => Some(reg),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Index of an encoding recipe in its `RecipeGroup`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EncodingRecipeNumber(usize);
+
+impl EncodingRecipeNumber {
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// The encoding recipes of a target ISA.
+pub struct RecipeGroup {
+    recipes: Vec<EncodingRecipe>,
+}
+
+impl Default for RecipeGroup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RecipeGroup {
+    pub fn new() -> Self {
+        Self {
+            recipes: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, recipe: EncodingRecipe) -> EncodingRecipeNumber {
+        assert!(
+            self.recipes.iter().all(|r| r.name != recipe.name),
+            "duplicate recipe {}",
+            recipe.name
+        );
+        self.recipes.push(recipe);
+        EncodingRecipeNumber(self.recipes.len() - 1)
+    }
+
+    pub fn get(&self, number: EncodingRecipeNumber) -> &EncodingRecipe {
+        &self.recipes[number.0]
+    }
+
+    pub fn by_name(&self, name: &str) -> EncodingRecipeNumber {
+        self.recipes
+            .iter()
+            .position(|r| r.name == name)
+            .map(EncodingRecipeNumber)
+            .unwrap_or_else(|| panic!("unexisting recipe with name {}", name))
+    }
+
+    pub fn len(&self) -> usize {
+        self.recipes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.recipes.is_empty()
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, EncodingRecipe> {
+        self.recipes.iter()
+    }
+
+    /// Keep only the recipes in `order`, renumbered in that order.
+    ///
+    /// Return the new group, and the new number of each of the original
+    /// recipes, if it was kept.
+    pub fn reorder(
+        self,
+        order: &[EncodingRecipeNumber],
+    ) -> (Self, Vec<Option<EncodingRecipeNumber>>) {
+        let mut new_numbers = vec![None; self.recipes.len()];
+        let mut recipes: Vec<Option<EncodingRecipe>> = self.recipes.into_iter().map(Some).collect();
+        let recipes = order
+            .iter()
+            .enumerate()
+            .map(|(new_index, number)| {
+                new_numbers[number.0] = Some(EncodingRecipeNumber(new_index));
+                recipes[number.0]
+                    .take()
+                    .expect("recipes can only appear once in the new order")
+            })
+            .collect();
+        (Self { recipes }, new_numbers)
+    }
+}
+
+#[derive(Clone)]
+pub struct EncodingRecipeBuilder {
+    pub name: String,
+    format: InstructionFormatIndex,
+    pub base_size: u64,
+    pub operands_in: Vec<OperandConstraint>,
+    pub operands_out: Vec<OperandConstraint>,
+    pub branch_range: Option<BranchRange>,
+    clobbers_flags: bool,
+    inst_predicate: Option<InstructionPredicate>,
+    isa_predicate: Option<u8>,
+    pub emit: Option<String>,
+}
+
+impl EncodingRecipeBuilder {
+    pub fn new<S: Into<String>>(name: S, format: InstructionFormatIndex, base_size: u64) -> Self {
+        Self {
+            name: name.into(),
+            format,
+            base_size,
+            operands_in: Vec::new(),
+            operands_out: Vec::new(),
+            branch_range: None,
+            clobbers_flags: true,
+            inst_predicate: None,
+            isa_predicate: None,
+            emit: None,
+        }
+    }
+
+    pub fn operands_in(mut self, constraints: Vec<OperandConstraint>) -> Self {
+        assert!(self.operands_in.is_empty());
+        self.operands_in = constraints;
+        self
+    }
+
+    pub fn operands_out(mut self, constraints: Vec<OperandConstraint>) -> Self {
+        assert!(self.operands_out.is_empty());
+        self.operands_out = constraints;
+        self
+    }
+
+    /// The `(origin, bits)` range for recipes that can encode branch
+    /// instructions.
+    pub fn branch_range(mut self, range: (u64, u64)) -> Self {
+        assert!(self.branch_range.is_none());
+        self.branch_range = Some(BranchRange {
+            origin: range.0,
+            bits: range.1,
+        });
+        self
+    }
+
+    pub fn clobbers_flags(mut self, flag: bool) -> Self {
+        self.clobbers_flags = flag;
+        self
+    }
+
+    pub fn inst_predicate(mut self, inst_predicate: InstructionPredicate) -> Self {
+        assert!(self.inst_predicate.is_none());
+        self.inst_predicate = Some(inst_predicate);
+        self
+    }
+
+    pub fn isa_predicate(mut self, isa_predicate: u8) -> Self {
+        assert!(self.isa_predicate.is_none());
+        self.isa_predicate = Some(isa_predicate);
+        self
+    }
+
+    pub fn emit<S: Into<String>>(mut self, code: S) -> Self {
+        assert!(self.emit.is_none());
+        self.emit = Some(code.into());
+        self
+    }
+
+    pub fn build(self, format_registry: &FormatRegistry) -> EncodingRecipe {
+        let format = format_registry.get(self.format);
+        if !format.has_value_list {
+            assert_eq!(
+                self.operands_in.len(),
+                format.num_value_operands,
+                "wrong number of input constraints for recipe {}",
+                self.name
+            );
+        }
+        for constraint in &self.operands_in {
+            if let OperandConstraint::TiedInput(_) = *constraint {
+                panic!("input of recipe {} can't be tied", self.name);
+            }
+        }
+        for constraint in &self.operands_out {
+            if let OperandConstraint::TiedInput(n) = *constraint {
+                assert!(
+                    n < self.operands_in.len(),
+                    "recipe {} ties a result to a missing input",
+                    self.name
+                );
+            }
+        }
+        if let Some(ref pred) = self.inst_predicate {
+            assert_eq!(
+                pred.format_name(),
+                Some(format.name),
+                "predicate of recipe {} doesn't match its format",
+                self.name
+            );
+        }
+
+        EncodingRecipe {
+            name: self.name,
+            format: self.format,
+            base_size: self.base_size,
+            operands_in: self.operands_in,
+            operands_out: self.operands_out,
+            branch_range: self.branch_range,
+            clobbers_flags: self.clobbers_flags,
+            inst_predicate: self.inst_predicate,
+            isa_predicate: self.isa_predicate,
+            emit: self.emit,
+        }
+    }
+}
@@ -0,0 +1,638 @@
+//! Register set definitions.
+//!
+//! Each ISA defines a separate register set that is used by the register
+//! allocator and the final binary encoding of machine code.
+//!
+//! The CPU registers are first divided into disjoint register banks,
+//! represented by a `RegBank` instance. Registers in different register banks
+//! never interfere with each other. A typical CPU will have a general purpose
+//! and a floating point register bank.
+//!
+//! A register bank consists of a number of *register units* which are the
+//! smallest indivisible units of allocation and interference. A register unit
+//! doesn't necessarily correspond to a particular number of bits in a
+//! register, it is more like a placeholder that can be used to determine of a
+//! register is taken or not.
+//!
+//! The register allocator works with *register classes* which can allocate
+//! one or more register units at a time. A register class allocates more than
+//! one register unit at a time when its registers are composed of smaller
+//! allocatable units. For example, the ARM double precision floating point
+//! registers are composed of two single precision registers.
+
+use cdsl::next_power_of_two;
+
+/// Maximum number of top-level register classes with pressure tracking.
+const MAX_TRACKED_TOPRCS: usize = 4;
+
+/// Maximum number of register classes in an ISA, limited by the width of the
+/// generated subclass masks.
+const MAX_REGCLASSES: usize = 32;
+
+/// Number of 32-bit words in the generated register unit masks.
+const MASK_LEN: usize = 3;
+
+/// Reference to a register bank in an `IsaRegsBuilder` or `IsaRegs`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RegBankIndex(usize);
+
+impl RegBankIndex {
+    /// Get the position of this bank in `IsaRegs::banks`.
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// Reference to a register class in an `IsaRegsBuilder`.
+///
+/// Once the builder is finished, register classes are renumbered in their
+/// final order, and `RegClass::index` holds the index used by the generated
+/// code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RegClassIndex(usize);
+
+/// A register bank belonging to an ISA.
+///
+/// A register bank controls a set of *register units* disjoint from all the
+/// other register banks in the ISA. The register units are numbered
+/// uniquely within the target ISA, and the units in a register bank form a
+/// contiguous sequence starting from a sufficiently aligned point that their
+/// low bits can be used directly when encoding machine code instructions.
+///
+/// Register units can be given generated names like `r0`, `r1`, ..., or a
+/// tuple of special register unit names can be provided.
+pub struct RegBank {
+    pub name: &'static str,
+    /// Documentation string.
+    pub doc: &'static str,
+    /// First register unit in this bank, aligned for the number of units.
+    pub first_unit: u8,
+    /// Number of register units in this bank.
+    pub units: u8,
+    /// Special names for the first register units in the bank.
+    pub names: Vec<&'static str>,
+    /// Prefix used for generated unit names.
+    pub prefix: &'static str,
+    /// Enable tracking of register pressure for this bank.
+    pub pressure_tracking: bool,
+    /// Top-level register classes in this bank, by final index.
+    pub toprcs: Vec<usize>,
+}
+
+impl RegBank {
+    /// Get the name of the register unit `unit` in this bank, counted from
+    /// the start of the bank.
+    pub fn unit_name(&self, unit: u8) -> String {
+        match self.names.get(unit as usize) {
+            Some(name) => name.to_string(),
+            None => format!("{}{}", self.prefix, unit),
+        }
+    }
+
+    /// Get a register unit in this bank by name, counted from the start of
+    /// the bank.
+    pub fn unit_by_name(&self, name: &str) -> u8 {
+        (0..self.units)
+            .find(|&unit| self.unit_name(unit) == name)
+            .unwrap_or_else(|| panic!("No register unit named {} in {}", name, self.name))
+    }
+}
+
+/// Builder for a `RegBank`.
+pub struct RegBankBuilder {
+    name: &'static str,
+    doc: &'static str,
+    units: u8,
+    names: Vec<&'static str>,
+    prefix: &'static str,
+    pressure_tracking: bool,
+}
+
+impl RegBankBuilder {
+    pub fn new(name: &'static str, doc: &'static str) -> Self {
+        Self {
+            name,
+            doc,
+            units: 0,
+            names: Vec::new(),
+            prefix: "r",
+            pressure_tracking: true,
+        }
+    }
+
+    /// Set the number of register units in the bank.
+    pub fn units(mut self, units: u8) -> Self {
+        self.units = units;
+        self
+    }
+
+    /// Give special names to the first register units in the bank.
+    pub fn names(mut self, names: Vec<&'static str>) -> Self {
+        assert!(
+            names.len() <= self.units as usize,
+            "Too many names for {}",
+            self.name
+        );
+        self.names = names;
+        self
+    }
+
+    /// Set the prefix used for generated unit names.
+    pub fn prefix(mut self, prefix: &'static str) -> Self {
+        self.prefix = prefix;
+        self
+    }
+
+    /// Enable or disable register pressure tracking for this bank.
+    pub fn track_pressure(mut self, track: bool) -> Self {
+        self.pressure_tracking = track;
+        self
+    }
+}
+
+/// A register class.
+///
+/// A register class is a subset of register units in a `RegBank` along with a
+/// strategy for allocating registers.
+///
+/// The *width* parameter determines how many register units are allocated at
+/// a time. Usually it that is one, but for example the ARM D registers are
+/// allocated two units at a time. When multiple units are allocated, it is
+/// always a contiguous set of unit numbers.
+pub struct RegClass {
+    pub name: &'static str,
+    /// Final index of this class in the ISA.
+    pub index: usize,
+    /// Number of register units allocated at a time.
+    pub width: u8,
+    pub bank: RegBankIndex,
+    /// Final index of the top-level class containing this class.
+    pub toprc: usize,
+    /// Final indices of the subclasses of this class, not including itself.
+    pub subclasses: Vec<usize>,
+    /// Bit-mask of allocatable units, one bit per allocation, relative to the
+    /// start of the bank.
+    bitmask: u64,
+}
+
+impl RegClass {
+    /// Get the first register unit allocated by this class, relative to the
+    /// start of the bank.
+    pub fn start(&self) -> u8 {
+        self.bitmask.trailing_zeros() as u8
+    }
+
+    /// Is this a top-level register class?
+    pub fn is_toprc(&self) -> bool {
+        self.toprc == self.index
+    }
+
+    /// Compute a bit-mask of the register units allocated by this register
+    /// class, split into 32-bit words.
+    pub fn mask(&self, first_unit: u8) -> Vec<u32> {
+        let bitmask = u128::from(self.bitmask) << first_unit;
+        (0..MASK_LEN)
+            .map(|i| (bitmask >> (i * 32)) as u32)
+            .collect()
+    }
+
+    /// Compute a bit-mask of subclasses, including self.
+    pub fn subclass_mask(&self) -> u32 {
+        self.subclasses
+            .iter()
+            .fold(1 << self.index, |m, rc| m | 1 << rc)
+    }
+}
+
+/// How the register units of a `RegClassBuilder` are chosen.
+enum RegClassProto {
+    /// A top-level class covering `count` allocations of its bank, or the
+    /// whole bank when `count` is zero.
+    TopLevel {
+        bank: RegBankIndex,
+        count: u8,
+        width: u8,
+    },
+    /// A contiguous slice of allocations `start..stop` of the parent class.
+    Slice {
+        parent: RegClassIndex,
+        start: u8,
+        stop: u8,
+    },
+    /// The parent class without the named registers.
+    Without {
+        parent: RegClassIndex,
+        regs: Vec<&'static str>,
+    },
+}
+
+/// Builder for a `RegClass`.
+pub struct RegClassBuilder {
+    name: &'static str,
+    proto: RegClassProto,
+}
+
+impl RegClassBuilder {
+    /// Create a top-level register class covering all of `bank`.
+    pub fn new_toplevel(name: &'static str, bank: RegBankIndex) -> Self {
+        Self {
+            name,
+            proto: RegClassProto::TopLevel {
+                bank,
+                count: 0,
+                width: 1,
+            },
+        }
+    }
+
+    /// Create a sub-class of `parent` made of its allocations
+    /// `start..stop`. The indexes refer to allocations in the parent register
+    /// class, not register units.
+    pub fn subclass_of(name: &'static str, parent: RegClassIndex, start: u8, stop: u8) -> Self {
+        assert!(start < stop, "Empty slice of register class");
+        Self {
+            name,
+            proto: RegClassProto::Slice {
+                parent,
+                start,
+                stop,
+            },
+        }
+    }
+
+    /// Create a sub-class of `parent` with the registers named `regs`
+    /// removed.
+    pub fn without(name: &'static str, parent: RegClassIndex, regs: &[&'static str]) -> Self {
+        Self {
+            name,
+            proto: RegClassProto::Without {
+                parent,
+                regs: regs.to_vec(),
+            },
+        }
+    }
+
+    /// Set the number of allocations of a top-level class.
+    pub fn count(mut self, count: u8) -> Self {
+        match self.proto {
+            RegClassProto::TopLevel {
+                count: ref mut c, ..
+            } => *c = count,
+            _ => panic!("Only top-level register classes have a count"),
+        }
+        self
+    }
+
+    /// Set the number of register units allocated at a time by a top-level
+    /// class.
+    pub fn width(mut self, width: u8) -> Self {
+        match self.proto {
+            RegClassProto::TopLevel {
+                width: ref mut w, ..
+            } => *w = width,
+            _ => panic!("Only top-level register classes have a width"),
+        }
+        self
+    }
+}
+
+/// A register class while the ISA registers are being built.
+struct ProtoRegClass {
+    name: &'static str,
+    width: u8,
+    bank: RegBankIndex,
+    bitmask: u64,
+}
+
+/// Builder for the register banks and classes of an ISA.
+pub struct IsaRegsBuilder {
+    banks: Vec<RegBank>,
+    classes: Vec<ProtoRegClass>,
+}
+
+impl Default for IsaRegsBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IsaRegsBuilder {
+    pub fn new() -> Self {
+        Self {
+            banks: Vec::new(),
+            classes: Vec::new(),
+        }
+    }
+
+    /// Add a register bank. Its units are numbered after those of the
+    /// previously added banks, aligned to the size of the bank.
+    pub fn add_bank(&mut self, builder: RegBankBuilder) -> RegBankIndex {
+        let first_unit = match self.banks.last() {
+            None => 0,
+            Some(last) => {
+                let u = last.first_unit as usize + last.units as usize;
+                let align = if builder.units.is_power_of_two() {
+                    builder.units as usize
+                } else {
+                    next_power_of_two(builder.units as usize)
+                };
+                (u + align - 1) & !(align - 1)
+            }
+        };
+        assert!(
+            first_unit + builder.units as usize <= MASK_LEN * 32,
+            "Too many register units in {}",
+            builder.name
+        );
+        self.banks.push(RegBank {
+            name: builder.name,
+            doc: builder.doc,
+            first_unit: first_unit as u8,
+            units: builder.units,
+            names: builder.names,
+            prefix: builder.prefix,
+            pressure_tracking: builder.pressure_tracking,
+            toprcs: Vec::new(),
+        });
+        RegBankIndex(self.banks.len() - 1)
+    }
+
+    /// Add a register class.
+    pub fn add_class(&mut self, builder: RegClassBuilder) -> RegClassIndex {
+        let (width, bank, bitmask) = match builder.proto {
+            RegClassProto::TopLevel { bank, count, width } => {
+                let units = self.banks[bank.0].units;
+                let count = if count == 0 { units / width } else { count };
+                assert!(
+                    count * width <= units,
+                    "Register class {} is too large for its bank",
+                    builder.name
+                );
+                let bitmask = (0..count).fold(0u64, |m, a| m | 1 << (a * width));
+                (width, bank, bitmask)
+            }
+            RegClassProto::Slice {
+                parent,
+                start,
+                stop,
+            } => {
+                let parent = &self.classes[parent.0];
+                let w = parent.width;
+                let first = parent.bitmask.trailing_zeros() as u8 + start * w;
+                let bitmask = (0..stop - start).fold(0u64, |m, a| m | 1 << (first + a * w));
+                assert_eq!(
+                    bitmask & parent.bitmask,
+                    bitmask,
+                    "Slice {} is not contained in its parent",
+                    builder.name
+                );
+                (w, parent.bank, bitmask)
+            }
+            RegClassProto::Without { parent, regs } => {
+                let parent = &self.classes[parent.0];
+                let bank = &self.banks[parent.bank.0];
+                let fmask = (1u64 << parent.width) - 1;
+                let bitmask = regs.iter().fold(parent.bitmask, |m, reg| {
+                    m & !(fmask << bank.unit_by_name(reg))
+                });
+                (parent.width, parent.bank, bitmask)
+            }
+        };
+        self.classes.push(ProtoRegClass {
+            name: builder.name,
+            width,
+            bank,
+            bitmask,
+        });
+        RegClassIndex(self.classes.len() - 1)
+    }
+
+    /// Compute subclasses and top-level classes, number the register classes,
+    /// and verify that the set of register classes in each bank satisfies:
+    ///
+    /// 1. Closed under intersection: The intersection of any two register
+    ///    classes in the set is either empty or identical to a member of the
+    ///    set.
+    /// 2. There are no identical classes under different names.
+    /// 3. Classes are sorted topologically such that all subclasses have a
+    ///    higher index that the superclass.
+    ///
+    /// Top-level classes with pressure tracking are numbered first, followed
+    /// by the other top-level classes, and then all the remaining classes,
+    /// bank by bank.
+    pub fn finish(self) -> IsaRegs {
+        let mut banks = self.banks;
+        let classes = self.classes;
+
+        // Classes of each bank in definition order, and the builder index of
+        // the top-level class containing each class.
+        let mut bank_classes: Vec<Vec<usize>> = vec![Vec::new(); banks.len()];
+        for (i, rc) in classes.iter().enumerate() {
+            bank_classes[rc.bank.0].push(i);
+        }
+        let mut toprc: Vec<usize> = (0..classes.len()).collect();
+        let mut subclasses: Vec<Vec<usize>> = vec![Vec::new(); classes.len()];
+        let mut bank_toprcs: Vec<Vec<usize>> = vec![Vec::new(); banks.len()];
+
+        for (bank, members) in bank_classes.iter().enumerate() {
+            // Check for duplicates.
+            for (idx, &i1) in members.iter().enumerate() {
+                let rc1 = &classes[i1];
+                for &i2 in &members[0..idx] {
+                    let rc2 = &classes[i2];
+                    assert!(
+                        rc1.width != rc2.width || rc1.bitmask != rc2.bitmask,
+                        "{} and {} are identical register classes",
+                        rc1.name,
+                        rc2.name
+                    );
+                }
+            }
+
+            // Check intersections and topological order.
+            for (idx, &i1) in members.iter().enumerate() {
+                let rc1 = &classes[i1];
+                for &i2 in &members[0..idx] {
+                    let rc2 = &classes[i2];
+                    if rc1.width != rc2.width {
+                        continue;
+                    }
+                    let intersection = rc1.bitmask & rc2.bitmask;
+                    if intersection == 0 {
+                        continue;
+                    }
+                    let irc = members
+                        .iter()
+                        .cloned()
+                        .find(|&i| {
+                            classes[i].width == rc1.width && classes[i].bitmask == intersection
+                        })
+                        .unwrap_or_else(|| {
+                            panic!("intersection of {} and {} missing", rc1.name, rc2.name)
+                        });
+                    assert!(
+                        irc != i2,
+                        "Bad topological order: {}/{}",
+                        rc1.name,
+                        rc2.name
+                    );
+                    if irc == i1 {
+                        // The intersection of rc1 and rc2 is rc1, so it must
+                        // be a sub-class.
+                        subclasses[i2].push(i1);
+                        toprc[i1] = toprc[i2];
+                    }
+                }
+                if toprc[i1] == i1 {
+                    bank_toprcs[bank].push(i1);
+                }
+            }
+        }
+
+        // Assign the final indices.
+        let mut order = Vec::with_capacity(classes.len());
+        for (bank, toprcs) in banks.iter().zip(bank_toprcs.iter()) {
+            if bank.pressure_tracking {
+                order.extend(toprcs.iter().cloned());
+            }
+        }
+        assert!(
+            order.len() <= MAX_TRACKED_TOPRCS,
+            "Too many top-level register classes with pressure tracking"
+        );
+        for (bank, toprcs) in banks.iter().zip(bank_toprcs.iter()) {
+            if !bank.pressure_tracking {
+                order.extend(toprcs.iter().cloned());
+            }
+        }
+        for members in &bank_classes {
+            order.extend(members.iter().cloned().filter(|&i| toprc[i] != i));
+        }
+        assert!(order.len() <= MAX_REGCLASSES, "Too many register classes");
+
+        let mut index = vec![0; classes.len()];
+        for (n, &i) in order.iter().enumerate() {
+            index[i] = n;
+        }
+        for (bank, toprcs) in banks.iter_mut().zip(bank_toprcs.iter()) {
+            bank.toprcs = toprcs.iter().map(|&i| index[i]).collect();
+        }
+
+        let mut classes: Vec<Option<ProtoRegClass>> = classes.into_iter().map(Some).collect();
+        let classes = order
+            .iter()
+            .map(|&i| {
+                let rc = classes[i].take().unwrap();
+                RegClass {
+                    name: rc.name,
+                    index: index[i],
+                    width: rc.width,
+                    bank: rc.bank,
+                    toprc: index[toprc[i]],
+                    subclasses: subclasses[i].iter().map(|&s| index[s]).collect(),
+                    bitmask: rc.bitmask,
+                }
+            })
+            .collect();
+
+        IsaRegs { banks, classes }
+    }
+}
+
+/// The register banks and classes of an ISA.
+pub struct IsaRegs {
+    pub banks: Vec<RegBank>,
+    /// Register classes, ordered by their final index.
+    pub classes: Vec<RegClass>,
+}
+
+impl IsaRegs {
+    pub fn bank(&self, index: RegBankIndex) -> &RegBank {
+        &self.banks[index.0]
+    }
+
+    /// Look up a register class by name.
+    pub fn class_by_name(&self, name: &str) -> &RegClass {
+        self.classes
+            .iter()
+            .find(|rc| rc.name == name)
+            .unwrap_or_else(|| panic!("register class {} not found", name))
+    }
+
+    /// Get the register unit of the register `name` of the class `regclass`,
+    /// counted from the first unit of all banks.
+    pub fn regunit_by_name(&self, regclass: &RegClass, name: &str) -> u8 {
+        let bank = self.bank(regclass.bank);
+        bank.first_unit + bank.unit_by_name(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn x86_like() -> IsaRegs {
+        let mut regs = IsaRegsBuilder::new();
+        let int_regs = regs.add_bank(
+            RegBankBuilder::new("IntRegs", "General purpose registers")
+                .units(16)
+                .names(vec!["rax", "rcx", "rdx", "rbx", "rsp", "rbp", "rsi", "rdi"]),
+        );
+        let flag_regs = regs.add_bank(
+            RegBankBuilder::new("FlagRegs", "Flag registers")
+                .units(1)
+                .names(vec!["rflags"])
+                .track_pressure(false),
+        );
+        let gpr = regs.add_class(RegClassBuilder::new_toplevel("GPR", int_regs));
+        regs.add_class(RegClassBuilder::without(
+            "GPR_DEREF_SAFE",
+            gpr,
+            &["rsp", "r12"],
+        ));
+        regs.add_class(RegClassBuilder::subclass_of("ABCD", gpr, 0, 4));
+        regs.add_class(RegClassBuilder::new_toplevel("FLAG", flag_regs));
+        regs.finish()
+    }
+
+    #[test]
+    fn banks_are_aligned() {
+        let mut regs = IsaRegsBuilder::new();
+        regs.add_bank(RegBankBuilder::new("A", "").units(3));
+        let b = regs.add_bank(RegBankBuilder::new("B", "").units(6));
+        let c = regs.add_bank(RegBankBuilder::new("C", "").units(1));
+        let regs = regs.finish();
+        assert_eq!(regs.bank(b).first_unit, 8);
+        assert_eq!(regs.bank(c).first_unit, 14);
+    }
+
+    #[test]
+    fn classes_are_numbered_toprcs_first() {
+        let regs = x86_like();
+        let names: Vec<_> = regs.classes.iter().map(|rc| rc.name).collect();
+        assert_eq!(names, vec!["GPR", "FLAG", "GPR_DEREF_SAFE", "ABCD"]);
+        let abcd = regs.class_by_name("ABCD");
+        assert_eq!(abcd.toprc, 0);
+        assert_eq!(abcd.mask(0), vec![0xf, 0, 0]);
+        assert_eq!(regs.class_by_name("GPR").subclass_mask(), 0b1101);
+        assert_eq!(regs.class_by_name("GPR_DEREF_SAFE").subclass_mask(), 0b1100);
+        assert_eq!(regs.class_by_name("FLAG").mask(16), vec![0x10000, 0, 0]);
+        assert_eq!(
+            regs.regunit_by_name(regs.class_by_name("FLAG"), "rflags"),
+            16
+        );
+    }
+
+    #[test]
+    fn wide_classes() {
+        let mut regs = IsaRegsBuilder::new();
+        let float_regs = regs.add_bank(RegBankBuilder::new("FloatRegs", "").units(64).prefix("s"));
+        regs.add_class(RegClassBuilder::new_toplevel("S", float_regs).count(32));
+        regs.add_class(RegClassBuilder::new_toplevel("D", float_regs).width(2));
+        let regs = regs.finish();
+        let d = regs.class_by_name("D");
+        assert!(d.is_toprc());
+        assert_eq!(d.mask(0), vec![0x55555555, 0x55555555, 0]);
+        assert_eq!(regs.class_by_name("S").mask(0), vec![0xffffffff, 0, 0]);
+    }
+}
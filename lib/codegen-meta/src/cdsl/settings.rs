@@ -0,0 +1,660 @@
+//! Classes for describing settings and groups of settings.
+
+/// A boolean setting, which also is a numbered predicate of its group.
+pub struct BoolSetting {
+    /// The default value of this setting.
+    pub default: bool,
+    /// Offset of the bit containing this setting, in its byte.
+    pub bit_offset: u8,
+    /// Number of this setting as a predicate in its group.
+    pub predicate_number: u8,
+}
+
+/// The kind-specific part of a setting.
+pub enum SpecificSetting {
+    /// A setting with a boolean on/off value.
+    Bool(BoolSetting),
+    /// A setting with an enumerated set of possible values. The default value
+    /// is always the first enumerator.
+    Enum(Vec<&'static str>),
+    /// A setting with an integral value in the range 0--255, and its default
+    /// value.
+    Num(u8),
+}
+
+/// A named setting variable that can be configured externally to Cranelift.
+pub struct Setting {
+    pub name: &'static str,
+    pub comment: &'static str,
+    pub specific: SpecificSetting,
+    /// Offset of byte in settings vector containing this setting.
+    pub byte_offset: u8,
+}
+
+impl Setting {
+    /// Get the default value of this setting, as a byte that can be bitwise
+    /// or'ed with the other settings sharing the same byte.
+    pub fn default_byte(&self) -> u8 {
+        match self.specific {
+            SpecificSetting::Bool(BoolSetting {
+                default,
+                bit_offset,
+                ..
+            }) => {
+                if default {
+                    1 << bit_offset
+                } else {
+                    0
+                }
+            }
+            SpecificSetting::Enum(_) => 0,
+            SpecificSetting::Num(default) => default,
+        }
+    }
+
+    /// Get the setting byte value that corresponds to enabling or disabling a
+    /// boolean setting.
+    fn byte_for_value(&self, v: bool) -> u8 {
+        match self.specific {
+            SpecificSetting::Bool(BoolSetting { bit_offset, .. }) => {
+                if v {
+                    1 << bit_offset
+                } else {
+                    0
+                }
+            }
+            _ => panic!("byte_for_value shouldn't be used for non-boolean settings."),
+        }
+    }
+
+    /// Get a mask of bits in our byte that are relevant to this setting.
+    fn byte_mask(&self) -> u8 {
+        match self.specific {
+            SpecificSetting::Bool(BoolSetting { bit_offset, .. }) => 1 << bit_offset,
+            _ => 0xff,
+        }
+    }
+}
+
+/// Index of a boolean setting in the builder of its setting group.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BoolSettingIndex(usize);
+
+/// Index of a preset in the builder of its setting group.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PresetIndex(usize);
+
+/// An item that can appear in the definition of a preset.
+#[derive(Clone, Copy)]
+pub enum PresetType {
+    /// A boolean setting to enable.
+    BoolSetting(BoolSettingIndex),
+    /// Another preset, which is immediately expanded.
+    OtherPreset(PresetIndex),
+}
+
+impl From<BoolSettingIndex> for PresetType {
+    fn from(index: BoolSettingIndex) -> Self {
+        PresetType::BoolSetting(index)
+    }
+}
+
+impl From<PresetIndex> for PresetType {
+    fn from(index: PresetIndex) -> Self {
+        PresetType::OtherPreset(index)
+    }
+}
+
+/// A collection of setting values that are applied at once.
+///
+/// A `Preset` represents a shorthand notation for applying a number of
+/// settings at once. Enabling the `nehalem` preset is equivalent to enabling
+/// all of the settings listed in its definition.
+pub struct Preset {
+    pub name: &'static str,
+    /// Indices into `SettingGroup::settings` of the boolean settings to enable.
+    values: Vec<usize>,
+}
+
+impl Preset {
+    /// Get the names of the boolean settings enabled by this preset.
+    pub fn setting_names<'a>(&self, group: &'a SettingGroup) -> Vec<&'a str> {
+        self.values
+            .iter()
+            .map(|&index| group.settings[index].name)
+            .collect()
+    }
+
+    /// Compute a list of (mask, byte) pairs that incorporate all values in
+    /// this preset.
+    ///
+    /// The list will have an entry for each setting byte in the settings
+    /// group.
+    pub fn layout(&self, group: &SettingGroup) -> Vec<(u8, u8)> {
+        let mut layout = vec![(0, 0); group.settings_size as usize];
+        // Apply setting values in order.
+        for &setting_index in &self.values {
+            let setting = &group.settings[setting_index];
+            let s_mask = setting.byte_mask();
+            let s_val = setting.byte_for_value(true);
+            assert!((s_val & !s_mask) == 0);
+            let entry = &mut layout[setting.byte_offset as usize];
+            // Accumulated mask of modified bits.
+            entry.0 |= s_mask;
+            // Overwrite the relevant bits with the new value.
+            entry.1 = (entry.1 & !s_mask) | s_val;
+        }
+        layout
+    }
+}
+
+/// A boolean predicate computed from the boolean settings of a group, or of
+/// its parent group.
+#[derive(Clone, PartialEq)]
+pub enum PredicateNode {
+    /// A boolean setting of the group owning the predicate.
+    OwnedBool(BoolSettingIndex),
+    /// A boolean setting of the parent group, described by the group's name
+    /// and the setting's name.
+    SharedBool(&'static str, &'static str),
+    /// Computed predicate that is true if all parts are true.
+    And(Box<PredicateNode>, Box<PredicateNode>),
+    /// Computed predicate that is true if any parts are true.
+    Or(Box<PredicateNode>, Box<PredicateNode>),
+    /// Computed predicate that is true if its single part is false.
+    Not(Box<PredicateNode>),
+}
+
+impl From<BoolSettingIndex> for PredicateNode {
+    fn from(index: BoolSettingIndex) -> Self {
+        PredicateNode::OwnedBool(index)
+    }
+}
+
+impl PredicateNode {
+    /// Return a Rust expression computing the value of this predicate.
+    ///
+    /// The emitted code assumes that the setting groups exist as local
+    /// variables. The surrounding precedence determines whether parentheses
+    /// are needed:
+    ///
+    /// 0. An `if` statement.
+    /// 1. An `||` expression.
+    /// 2. An `&&` expression.
+    /// 3. A `!` expression.
+    pub fn render(&self, group: &SettingGroup, precedence: u8) -> String {
+        match self {
+            PredicateNode::OwnedBool(index) => {
+                let setting = &group.settings[group.bool_settings[index.0]];
+                format!("{}.{}()", group.name, setting.name)
+            }
+            PredicateNode::SharedBool(group_name, name) => format!("{}.{}()", group_name, name),
+            PredicateNode::And(lhs, rhs) => {
+                let s = format!("{} && {}", lhs.render(group, 2), rhs.render(group, 2));
+                if precedence > 2 {
+                    format!("({})", s)
+                } else {
+                    s
+                }
+            }
+            PredicateNode::Or(lhs, rhs) => {
+                let s = format!("{} || {}", lhs.render(group, 1), rhs.render(group, 1));
+                if precedence > 1 {
+                    format!("({})", s)
+                } else {
+                    s
+                }
+            }
+            PredicateNode::Not(node) => format!("!{}", node.render(group, 3)),
+        }
+    }
+
+    /// Describe this predicate in the notation of the Python DSL, as used in
+    /// the comments of the generated encoding tables.
+    pub fn describe(&self, group: &SettingGroup) -> String {
+        match self {
+            PredicateNode::OwnedBool(index) => {
+                let setting = &group.settings[group.bool_settings[index.0]];
+                format!("{}.{}", group.name, setting.name)
+            }
+            PredicateNode::SharedBool(group_name, name) => format!("{}.{}", group_name, name),
+            PredicateNode::And(..) => format!("And({})", self.describe_conjuncts(group)),
+            PredicateNode::Or(lhs, rhs) => {
+                format!("Or({}, {})", lhs.describe(group), rhs.describe(group))
+            }
+            PredicateNode::Not(node) => format!("Not({})", node.describe(group)),
+        }
+    }
+
+    /// Describe the parts of a chain of `And` nodes, separated by commas.
+    fn describe_conjuncts(&self, group: &SettingGroup) -> String {
+        match self {
+            PredicateNode::And(lhs, rhs) => format!(
+                "{}, {}",
+                lhs.describe_conjuncts(group),
+                rhs.describe_conjuncts(group)
+            ),
+            _ => self.describe(group),
+        }
+    }
+}
+
+/// A numbered predicate of a setting group.
+///
+/// Named predicates get a getter in the generated `Flags` struct; anonymous
+/// ones are only accessible by number, e.g. from the encoding tables.
+pub struct Predicate {
+    pub name: Option<&'static str>,
+    pub node: PredicateNode,
+    pub number: u8,
+}
+
+/// A group of settings.
+///
+/// The byte vector representing a settings group contains the following
+/// entries in order:
+///
+/// 1. Byte-sized settings like `NumSetting` and `EnumSetting`.
+/// 2. `BoolSetting` settings.
+/// 3. Precomputed named predicates.
+/// 4. Other numbered predicates, including anonymous predicates and parent
+///    predicates that need to be accessible by number.
+pub struct SettingGroup {
+    /// Short mnemonic name for setting group.
+    pub name: &'static str,
+    pub settings: Vec<Setting>,
+    /// Offset of the first byte containing boolean settings and predicates.
+    pub bool_start_byte_offset: u8,
+    /// Length of the byte vector prefix that contains the settings. All
+    /// bytes after that are computed, not configured.
+    pub settings_size: u8,
+    pub presets: Vec<Preset>,
+    pub predicates: Vec<Predicate>,
+    /// Number of boolean settings, i.e. of predicates that are not computed.
+    pub num_bool_settings: u8,
+    /// Indices into `settings` of the boolean settings, in builder order.
+    bool_settings: Vec<usize>,
+}
+
+impl SettingGroup {
+    /// Compute the number of bytes required to hold all settings and
+    /// precomputed predicates.
+    ///
+    /// This is the size of the byte-sized settings plus all the numbered
+    /// predicate bits rounded up to a whole number of bytes.
+    pub fn byte_size(&self) -> u8 {
+        let num_predicates = self.num_bool_settings as usize + self.predicates.len();
+        self.bool_start_byte_offset + ((num_predicates + 7) / 8) as u8
+    }
+
+    /// Get the boolean setting called `name` as a predicate that can be used
+    /// in another setting group having this one as parent.
+    pub fn get_bool(&self, name: &str) -> PredicateNode {
+        let setting = self
+            .settings
+            .iter()
+            .find(|s| s.name == name)
+            .unwrap_or_else(|| panic!("unknown setting {}.{}", self.name, name));
+        match setting.specific {
+            SpecificSetting::Bool(_) => PredicateNode::SharedBool(self.name, setting.name),
+            _ => panic!("setting {}.{} isn't a boolean", self.name, name),
+        }
+    }
+
+    /// Get the number of the named predicate or boolean setting `name`.
+    pub fn predicate_by_name(&self, name: &str) -> u8 {
+        if let Some(p) = self.predicates.iter().find(|p| p.name == Some(name)) {
+            return p.number;
+        }
+        match self.settings.iter().find(|s| s.name == name) {
+            Some(Setting {
+                specific: SpecificSetting::Bool(ref b),
+                ..
+            }) => b.predicate_number,
+            _ => panic!("unknown predicate {}.{}", self.name, name),
+        }
+    }
+
+    /// Describe the predicate with the number `number` in the notation of the
+    /// Python DSL.
+    ///
+    /// Named predicates are always described as a conjunction, even if they
+    /// have a single part.
+    pub fn describe_predicate(&self, number: u8) -> String {
+        if number < self.num_bool_settings {
+            let index = self.bool_settings[number as usize];
+            return format!("{}.{}", self.name, self.settings[index].name);
+        }
+        let predicate = &self.predicates[(number - self.num_bool_settings) as usize];
+        match (predicate.name, &predicate.node) {
+            (Some(_), PredicateNode::And(..)) | (None, _) => predicate.node.describe(self),
+            (Some(_), node) => format!("And({})", node.describe(self)),
+        }
+    }
+
+    /// Find the number of the predicate equal to `node`, if it is numbered in
+    /// this group.
+    pub fn predicate_number(&self, node: &PredicateNode) -> Option<u8> {
+        if let PredicateNode::OwnedBool(index) = node {
+            return match self.settings[self.bool_settings[index.0]].specific {
+                SpecificSetting::Bool(ref b) => Some(b.predicate_number),
+                _ => None,
+            };
+        }
+        self.predicates
+            .iter()
+            .find(|p| p.node == *node)
+            .map(|p| p.number)
+    }
+}
+
+enum ProtoSpecificSetting {
+    Bool(bool),
+    Enum(Vec<&'static str>),
+    Num(u8),
+}
+
+struct ProtoSetting {
+    name: &'static str,
+    comment: &'static str,
+    specific: ProtoSpecificSetting,
+}
+
+/// Builder for a `SettingGroup`.
+///
+/// Settings, presets and predicates are laid out in the order they are added
+/// when calling `finish`.
+pub struct SettingGroupBuilder {
+    name: &'static str,
+    settings: Vec<ProtoSetting>,
+    presets: Vec<(&'static str, Vec<PresetType>)>,
+    predicates: Vec<(Option<&'static str>, PredicateNode)>,
+    num_bool_settings: usize,
+}
+
+impl SettingGroupBuilder {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            settings: Vec::new(),
+            presets: Vec::new(),
+            predicates: Vec::new(),
+            num_bool_settings: 0,
+        }
+    }
+
+    fn add_setting(
+        &mut self,
+        name: &'static str,
+        comment: &'static str,
+        specific: ProtoSpecificSetting,
+    ) {
+        assert!(
+            self.settings.iter().all(|s| s.name != name),
+            "duplicate setting {}.{}",
+            self.name,
+            name
+        );
+        self.settings.push(ProtoSetting {
+            name,
+            comment,
+            specific,
+        })
+    }
+
+    /// Add a named setting with a boolean on/off value.
+    pub fn add_bool(
+        &mut self,
+        name: &'static str,
+        comment: &'static str,
+        default: bool,
+    ) -> BoolSettingIndex {
+        assert!(
+            self.predicates.is_empty(),
+            "predicates must be added after the boolean settings"
+        );
+        self.add_setting(name, comment, ProtoSpecificSetting::Bool(default));
+        self.num_bool_settings += 1;
+        BoolSettingIndex(self.num_bool_settings - 1)
+    }
+
+    /// Add a named setting with an enumerated set of possible values. The
+    /// default value is always the first enumerator.
+    pub fn add_enum(
+        &mut self,
+        name: &'static str,
+        comment: &'static str,
+        values: Vec<&'static str>,
+    ) {
+        assert!(
+            !values.is_empty(),
+            "enum setting must have at least one value"
+        );
+        self.add_setting(name, comment, ProtoSpecificSetting::Enum(values));
+    }
+
+    /// Add a named setting with an integral value in the range 0--255.
+    pub fn add_num(&mut self, name: &'static str, comment: &'static str, default: u8) {
+        self.add_setting(name, comment, ProtoSpecificSetting::Num(default));
+    }
+
+    /// Add a named predicate computed from settings in this group or its
+    /// parent.
+    pub fn add_predicate(&mut self, name: &'static str, node: PredicateNode) {
+        self.predicates.push((Some(name), node));
+    }
+
+    /// Make sure that `node` has an assigned number, and will be included in
+    /// this group's bit vector, without generating a getter for it.
+    ///
+    /// This is used for ISA predicates that are only referenced by number.
+    pub fn add_anonymous_predicate(&mut self, node: PredicateNode) {
+        self.predicates.push((None, node));
+    }
+
+    /// Add a preset enabling all of the boolean settings in `args`, where any
+    /// other preset is expanded into its own settings.
+    pub fn add_preset(&mut self, name: &'static str, args: Vec<PresetType>) -> PresetIndex {
+        self.presets.push((name, args));
+        PresetIndex(self.presets.len() - 1)
+    }
+
+    /// Compute the layout of the byte vector used to represent this settings
+    /// group, and return the finished group.
+    pub fn finish(self) -> SettingGroup {
+        let mut group = SettingGroup {
+            name: self.name,
+            settings: Vec::new(),
+            bool_start_byte_offset: 0,
+            settings_size: 0,
+            presets: Vec::new(),
+            predicates: Vec::new(),
+            num_bool_settings: self.num_bool_settings as u8,
+            bool_settings: Vec::new(),
+        };
+
+        // Assign the non-boolean settings first.
+        let mut byte_offset = 0;
+        for s in &self.settings {
+            match s.specific {
+                ProtoSpecificSetting::Bool(_) => {}
+                _ => byte_offset += 1,
+            }
+        }
+
+        // Then the boolean settings.
+        group.bool_start_byte_offset = byte_offset;
+        let mut non_bool_offset = 0;
+        let mut predicate_number = 0;
+        for s in self.settings {
+            let (specific, byte_offset) = match s.specific {
+                ProtoSpecificSetting::Bool(default) => {
+                    let number = predicate_number;
+                    predicate_number += 1;
+                    group.bool_settings.push(group.settings.len());
+                    (
+                        SpecificSetting::Bool(BoolSetting {
+                            default,
+                            bit_offset: number % 8,
+                            predicate_number: number,
+                        }),
+                        group.bool_start_byte_offset + number / 8,
+                    )
+                }
+                ProtoSpecificSetting::Enum(values) => {
+                    non_bool_offset += 1;
+                    (SpecificSetting::Enum(values), non_bool_offset - 1)
+                }
+                ProtoSpecificSetting::Num(default) => {
+                    non_bool_offset += 1;
+                    (SpecificSetting::Num(default), non_bool_offset - 1)
+                }
+            };
+            group.settings.push(Setting {
+                name: s.name,
+                comment: s.comment,
+                specific,
+                byte_offset,
+            });
+        }
+
+        // This is the end of the settings. Round up to a whole number of bytes.
+        group.settings_size = group.byte_size();
+
+        // Now assign numbers to all our predicates, named ones first.
+        let (named, anonymous): (Vec<_>, Vec<_>) = self
+            .predicates
+            .into_iter()
+            .partition(|(name, _)| name.is_some());
+        for (name, node) in named.into_iter().chain(anonymous) {
+            if name.is_none() && group.predicate_number(&node).is_some() {
+                continue;
+            }
+            let number = group.num_bool_settings + group.predicates.len() as u8;
+            group.predicates.push(Predicate { name, node, number });
+        }
+
+        // Expand the presets into lists of boolean settings.
+        let mut expanded_presets: Vec<Vec<usize>> = Vec::new();
+        for (name, args) in self.presets {
+            let mut values = Vec::new();
+            for arg in args {
+                match arg {
+                    PresetType::BoolSetting(index) => values.push(group.bool_settings[index.0]),
+                    PresetType::OtherPreset(index) => {
+                        values.extend(expanded_presets[index.0].iter().cloned())
+                    }
+                }
+            }
+            expanded_presets.push(values.clone());
+            group.presets.push(Preset { name, values });
+        }
+
+        group
+    }
+}
+
+/// Build a `PredicateNode` from a conjunction of (possibly negated) boolean
+/// settings or predicates, e.g. `predicate!(has_sse42 && !is_pic)`.
+macro_rules! predicate {
+    ($a:ident && $($b:tt)*) => {
+        $crate::cdsl::settings::PredicateNode::And(
+            Box::new(predicate!($a)),
+            Box::new(predicate!($($b)*)),
+        )
+    };
+    (!$a:ident && $($b:tt)*) => {
+        $crate::cdsl::settings::PredicateNode::And(
+            Box::new(predicate!(!$a)),
+            Box::new(predicate!($($b)*)),
+        )
+    };
+    (!$a:ident) => {
+        $crate::cdsl::settings::PredicateNode::Not(Box::new(predicate!($a)))
+    };
+    ($a:ident) => {
+        $crate::cdsl::settings::PredicateNode::from($a.clone())
+    };
+}
+
+/// Build the list of arguments of a preset from a sequence of boolean
+/// settings and other presets, e.g. `preset!(nehalem && has_bmi1)`.
+macro_rules! preset {
+    () => {
+        vec![]
+    };
+    ($($x:ident)&&*) => {
+        vec![$($crate::cdsl::settings::PresetType::from($x)),*]
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout_puts_bools_after_bytes() {
+        let mut builder = SettingGroupBuilder::new("test");
+        let a = builder.add_bool("a", "A bool.", true);
+        builder.add_num("n", "A number.", 12);
+        let b = builder.add_bool("b", "Another bool.", false);
+        builder.add_enum("e", "An enum.", vec!["x", "y"]);
+        builder.add_predicate("a_and_b", predicate!(a && !b));
+        let group = builder.finish();
+
+        assert_eq!(group.bool_start_byte_offset, 2);
+        assert_eq!(group.settings_size, 3);
+        assert_eq!(group.byte_size(), 3);
+        let offsets: Vec<u8> = group.settings.iter().map(|s| s.byte_offset).collect();
+        assert_eq!(offsets, vec![2, 0, 2, 1]);
+        let defaults: Vec<u8> = group.settings.iter().map(|s| s.default_byte()).collect();
+        assert_eq!(defaults, vec![1, 12, 0, 0]);
+        assert_eq!(group.predicates[0].number, 2);
+        assert_eq!(
+            group.predicates[0].node.render(&group, 0),
+            "test.a() && !test.b()"
+        );
+    }
+
+    #[test]
+    fn presets_are_expanded() {
+        let mut builder = SettingGroupBuilder::new("test");
+        let a = builder.add_bool("a", "", false);
+        let b = builder.add_bool("b", "", false);
+        let c = builder.add_bool("c", "", false);
+        let first = builder.add_preset("first", preset!(a && b));
+        builder.add_preset("second", preset!(first && c));
+        let group = builder.finish();
+
+        assert_eq!(group.presets[0].layout(&group), vec![(0b011, 0b011)]);
+        assert_eq!(group.presets[1].layout(&group), vec![(0b111, 0b111)]);
+    }
+
+    #[test]
+    fn anonymous_predicates_are_deduplicated() {
+        let shared = {
+            let mut builder = SettingGroupBuilder::new("shared");
+            builder.add_bool("is_pic", "", false);
+            builder.finish()
+        };
+        let mut builder = SettingGroupBuilder::new("isa");
+        let has_x = builder.add_bool("has_x", "", false);
+        let is_pic = shared.get_bool("is_pic");
+        builder.add_predicate("use_x", predicate!(has_x));
+        builder.add_anonymous_predicate(predicate!(has_x));
+        builder.add_anonymous_predicate(predicate!(!is_pic));
+        builder.add_anonymous_predicate(predicate!(!is_pic));
+        let group = builder.finish();
+
+        assert_eq!(group.predicates.len(), 2);
+        assert_eq!(group.predicate_number(&predicate!(!is_pic)), Some(2));
+        assert_eq!(
+            group.predicates[1].node.render(&group, 0),
+            "!shared.is_pic()"
+        );
+        assert_eq!(group.predicate_by_name("use_x"), 1);
+        assert_eq!(group.describe_predicate(0), "isa.has_x");
+        assert_eq!(group.describe_predicate(1), "And(isa.has_x)");
+        assert_eq!(group.describe_predicate(2), "Not(shared.is_pic)");
+    }
+}
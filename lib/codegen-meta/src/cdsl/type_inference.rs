@@ -0,0 +1,1051 @@
+//! Type inference.
+//!
+//! Type inference works on a `TypeEnvironment` which records the equivalence
+//! classes of type variables, and the constraints between them that could not
+//! be expressed by unification alone.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use cdsl::ast::Def;
+use cdsl::typevar::{DerivedFunc, NumSet, TypeSet, TypeVar};
+use cdsl::xform::{Rtl, SymbolTable};
+
+/// A type constraint that must be checked at runtime, because it can't be
+/// expressed by the equivalence of type variables.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Constraint {
+    /// Two derived type variables must have the same runtime type.
+    TypesEqual(TypeVar, TypeVar),
+    /// A type variable must belong to some typeset.
+    InTypeset(TypeVar, TypeSet),
+    /// The first type variable must be wider than or equal to the second one
+    /// at runtime. This requires that:
+    ///     1) They have the same number of lanes
+    ///     2) In a lane the first one has at least as many bits as the second.
+    WiderOrEq(TypeVar, TypeVar),
+    /// Two type variables must have the same total width, e.g. `i32x2` has
+    /// the same width as `i64`, `i16x4`, `f32x2`, `f64` etc.
+    SameWidth(TypeVar, TypeVar),
+}
+
+impl Constraint {
+    /// Create a `TypesEqual` constraint. The arguments are ordered
+    /// canonically, so the same constraint is created regardless of the order
+    /// they are passed in.
+    pub fn types_equal(tv1: TypeVar, tv2: TypeVar) -> Self {
+        if repr(&tv2) < repr(&tv1) {
+            Constraint::TypesEqual(tv2, tv1)
+        } else {
+            Constraint::TypesEqual(tv1, tv2)
+        }
+    }
+
+    /// Create an `InTypeset` constraint. The type variable must be the free
+    /// type variable of some `Var`.
+    pub fn in_typeset(tv: TypeVar, ts: TypeSet) -> Self {
+        assert!(!tv.is_derived() && tv.name().starts_with("typeof_"));
+        Constraint::InTypeset(tv, ts)
+    }
+
+    /// Get the type variables contained in this constraint.
+    pub fn typevars(&self) -> Vec<&TypeVar> {
+        match *self {
+            Constraint::TypesEqual(ref tv1, ref tv2)
+            | Constraint::WiderOrEq(ref tv1, ref tv2)
+            | Constraint::SameWidth(ref tv1, ref tv2) => vec![tv1, tv2],
+            Constraint::InTypeset(ref tv, _) => vec![tv],
+        }
+    }
+
+    /// Rebuild this constraint with each type variable replaced by `f(tv)`.
+    fn map<F: Fn(&TypeVar) -> TypeVar>(&self, f: F) -> Constraint {
+        match *self {
+            Constraint::TypesEqual(ref tv1, ref tv2) => Constraint::types_equal(f(tv1), f(tv2)),
+            Constraint::InTypeset(ref tv, ref ts) => Constraint::InTypeset(f(tv), ts.clone()),
+            Constraint::WiderOrEq(ref tv1, ref tv2) => Constraint::WiderOrEq(f(tv1), f(tv2)),
+            Constraint::SameWidth(ref tv1, ref tv2) => Constraint::SameWidth(f(tv1), f(tv2)),
+        }
+    }
+
+    /// Translate any type variables in the constraint according to the
+    /// substitution `map`.
+    pub fn translate_with_map(&self, map: &HashMap<TypeVar, TypeVar>) -> Constraint {
+        self.map(|tv| subst(tv, map))
+    }
+
+    /// Translate any type variables in the constraint to their canonical
+    /// representatives in the type environment `env`.
+    pub fn translate_with_env(&self, env: &TypeEnvironment) -> Constraint {
+        self.map(|tv| env.get_equivalent(tv))
+    }
+
+    /// Return true iff all type variables in the constraint are singletons.
+    pub fn is_concrete(&self) -> bool {
+        self.typevars()
+            .iter()
+            .all(|tv| tv.singleton_type().is_some())
+    }
+
+    /// Return true if this constraint is statically decidable.
+    pub fn is_trivial(&self) -> bool {
+        match *self {
+            Constraint::TypesEqual(ref tv1, ref tv2) => tv1 == tv2 || self.is_concrete(),
+            Constraint::InTypeset(ref tv, ref ts) => {
+                let mut tv_ts = tv.get_typeset();
+
+                // Trivially true.
+                if tv_ts.is_subset(ts) {
+                    return true;
+                }
+
+                // Trivially false.
+                tv_ts.intersect_with(ts);
+                if tv_ts.size() == 0 {
+                    return true;
+                }
+
+                self.is_concrete()
+            }
+            Constraint::WiderOrEq(ref tv1, ref tv2) => {
+                // Trivially true.
+                if tv1 == tv2 {
+                    return true;
+                }
+
+                let ts1 = tv1.get_typeset();
+                let ts2 = tv2.get_typeset();
+
+                let set_wider_or_equal = |s1: &NumSet, s2: &NumSet| {
+                    !s1.is_empty() && !s2.is_empty() && s1.iter().min() >= s2.iter().max()
+                };
+
+                // Trivially true.
+                if set_wider_or_equal(&ts1.ints, &ts2.ints)
+                    && set_wider_or_equal(&ts1.floats, &ts2.floats)
+                    && set_wider_or_equal(&ts1.bools, &ts2.bools)
+                {
+                    return true;
+                }
+
+                let set_narrower = |s1: &NumSet, s2: &NumSet| {
+                    !s1.is_empty() && !s2.is_empty() && s1.iter().min() < s2.iter().max()
+                };
+
+                // Trivially false.
+                if set_narrower(&ts1.ints, &ts2.ints)
+                    && set_narrower(&ts1.floats, &ts2.floats)
+                    && set_narrower(&ts1.bools, &ts2.bools)
+                {
+                    return true;
+                }
+
+                // Trivially false.
+                if ts1.lanes.intersection(&ts2.lanes).next().is_none() {
+                    return true;
+                }
+
+                self.is_concrete()
+            }
+            Constraint::SameWidth(ref tv1, ref tv2) => {
+                // Trivially true.
+                if tv1 == tv2 {
+                    return true;
+                }
+
+                // Trivially false.
+                let widths1 = tv1.get_typeset().widths();
+                let widths2 = tv2.get_typeset().widths();
+                if widths1.intersection(&widths2).next().is_none() {
+                    return true;
+                }
+
+                self.is_concrete()
+            }
+        }
+    }
+
+    /// Evaluate this constraint. Should only be called when the constraint
+    /// has been translated to concrete types.
+    pub fn eval(&self) -> bool {
+        assert!(self.is_concrete());
+        match *self {
+            Constraint::TypesEqual(ref tv1, ref tv2) => {
+                tv1.singleton_type() == tv2.singleton_type()
+            }
+            Constraint::InTypeset(ref tv, ref ts) => tv.get_typeset().is_subset(ts),
+            Constraint::WiderOrEq(ref tv1, ref tv2) => {
+                let typ1 = tv1.singleton_type().unwrap();
+                let typ2 = tv2.singleton_type().unwrap();
+                typ1.wider_or_equal(&typ2)
+            }
+            Constraint::SameWidth(ref tv1, ref tv2) => {
+                let typ1 = tv1.singleton_type().unwrap();
+                let typ2 = tv2.singleton_type().unwrap();
+                typ1.width() == typ2.width()
+            }
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match *self {
+            Constraint::TypesEqual(..) => "TypesEqual",
+            Constraint::InTypeset(..) => "InTypeset",
+            Constraint::WiderOrEq(..) => "WiderOrEq",
+            Constraint::SameWidth(..) => "SameWidth",
+        }
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Constraint::InTypeset(ref tv, ref ts) => write!(f, "InTypeset({}, {})", tv, ts),
+            Constraint::TypesEqual(ref tv1, ref tv2)
+            | Constraint::WiderOrEq(ref tv1, ref tv2)
+            | Constraint::SameWidth(ref tv1, ref tv2) => {
+                write!(f, "{}({}, {})", self.name(), tv1, tv2)
+            }
+        }
+    }
+}
+
+/// A detailed description of a type variable, used to order the arguments of
+/// symmetric constraints.
+fn repr(tv: &TypeVar) -> String {
+    match tv.base() {
+        Some(base) => format!(
+            "TypeVar({}, base={}, derived_func={})",
+            tv.name(),
+            base.type_var,
+            base.derived_func.name()
+        ),
+        None => format!("TypeVar({}, {})", tv.name(), tv.get_typeset()),
+    }
+}
+
+/// The rank of a type variable is a partial ordering based on its origin.
+///
+/// Type variables directly associated with a `Var` get their rank from the
+/// `Var`. Internally generated non-derived type variables get the lowest
+/// rank. Derived type variables get their rank from their free type variable.
+/// Singletons have the highest rank.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TypeEnvRank {
+    Internal,
+    Temp,
+    Output,
+    Intermediate,
+    Input,
+    Singleton,
+}
+
+/// The book keeping necessary for type inference.
+pub struct TypeEnvironment {
+    /// The type variables of all the registered variables, in registration
+    /// order.
+    vars: Vec<TypeVar>,
+    /// The (optional) ranks of type variables.
+    ranks: HashMap<TypeVar, TypeEnvRank>,
+    /// The equivalence relations between type variables.
+    type_map: HashMap<TypeVar, TypeVar>,
+    /// The accumulated constraints.
+    pub constraints: Vec<Constraint>,
+    /// Counter used to get fresh ids.
+    idx: usize,
+}
+
+impl Default for TypeEnvironment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TypeEnvironment {
+    pub fn new() -> Self {
+        Self {
+            vars: Vec::new(),
+            ranks: HashMap::new(),
+            type_map: HashMap::new(),
+            constraints: Vec::new(),
+            idx: 0,
+        }
+    }
+
+    /// Get the type variables of all the registered variables.
+    pub fn vars(&self) -> &[TypeVar] {
+        &self.vars
+    }
+
+    /// Lookup the canonical representative of the type variable `tv`.
+    pub fn get_equivalent(&self, tv: &TypeVar) -> TypeVar {
+        let mut tv = tv;
+        while let Some(next) = self.type_map.get(tv) {
+            tv = next;
+        }
+        match tv.base() {
+            Some(base) => self
+                .get_equivalent(&base.type_var)
+                .derived(base.derived_func),
+            None => tv.clone(),
+        }
+    }
+
+    /// Record that the free `tv1` is part of the same equivalence class as
+    /// `tv2`. The canonical representative of the merged class is `tv2`'s
+    /// canonical representative.
+    pub fn record_equivalent(&mut self, tv1: TypeVar, tv2: TypeVar) {
+        assert!(!tv1.is_derived());
+        assert!(self.get_equivalent(&tv1) == tv1);
+
+        // Make sure we don't create cycles.
+        if let Some(base) = tv2.base() {
+            assert!(self.get_equivalent(&base.type_var) != tv1);
+        }
+
+        self.type_map.insert(tv1, tv2);
+    }
+
+    /// Add a new constraint.
+    pub fn add_constraint(&mut self, constraint: Constraint) {
+        if self.constraints.contains(&constraint) {
+            return;
+        }
+
+        // InTypeset constraints can be expressed by constraining the typeset
+        // of a variable. No need to add them to self.constraints.
+        if let Constraint::InTypeset(ref tv, ref ts) = constraint {
+            self.get_equivalent(tv).constrain_types_by_ts(ts.clone());
+            return;
+        }
+
+        self.constraints.push(constraint);
+    }
+
+    /// Get a fresh id, used to name fresh type variables.
+    pub fn get_uid(&mut self) -> String {
+        let uid = self.idx.to_string();
+        self.idx += 1;
+        uid
+    }
+
+    /// Get the rank of `tv` in the partial order.
+    pub fn rank(&self, tv: &TypeVar) -> TypeEnvRank {
+        let default_rank = if tv.singleton_type().is_some() {
+            TypeEnvRank::Singleton
+        } else {
+            TypeEnvRank::Internal
+        };
+
+        let tv = if tv.is_derived() {
+            tv.free_typevar()
+        } else {
+            Some(tv.clone())
+        };
+
+        tv.and_then(|tv| self.ranks.get(&tv).cloned())
+            .unwrap_or(default_rank)
+    }
+
+    /// Register the type variable `tv` of a variable, with a rank computed
+    /// from the variable's position in the transformation. The rank is used
+    /// to impose a partial order on type variables.
+    pub fn register(&mut self, tv: &TypeVar, rank: TypeEnvRank) {
+        if !self.vars.contains(tv) {
+            self.vars.push(tv.clone());
+        }
+        self.ranks.insert(tv.clone(), rank);
+    }
+
+    /// Get the free type variables in the current type environment, sorted
+    /// by name.
+    pub fn free_typevars(&self) -> Vec<TypeVar> {
+        let mut tvs: Vec<TypeVar> = Vec::new();
+        for tv in self.type_map.keys().chain(self.vars.iter()) {
+            // Singleton type variables don't have a free type variable.
+            if let Some(free) = self.get_equivalent(tv).free_typevar() {
+                if !tvs.contains(&free) {
+                    tvs.push(free);
+                }
+            }
+        }
+        tvs.sort_by(|a, b| a.name().cmp(b.name()));
+        tvs
+    }
+
+    /// Normalize by collapsing any roots that don't correspond to a concrete
+    /// type variable AND have a single type variable derived from them or
+    /// equivalent to them.
+    ///
+    /// E.g. if we have a root of the tree that looks like:
+    ///
+    /// ```text
+    ///   typeof_a   typeof_b
+    ///          \  /
+    ///       typeof_x
+    ///           |
+    ///         half_width(1)
+    ///           |
+    ///           1
+    /// ```
+    ///
+    /// we want to collapse the linear path between 1 and typeof_x. The
+    /// resulting graph is:
+    ///
+    /// ```text
+    ///   typeof_a   typeof_b
+    ///          \  /
+    ///       typeof_x
+    /// ```
+    pub fn normalize(&mut self) {
+        let mut children: HashMap<TypeVar, HashSet<TypeVar>> = HashMap::new();
+        for tv in self.type_map.values() {
+            if !tv.is_derived() {
+                continue;
+            }
+            if let Some(free) = tv.free_typevar() {
+                children.entry(free).or_default().insert(tv.clone());
+            }
+        }
+        for (a, b) in &self.type_map {
+            children.entry(b.clone()).or_default().insert(a.clone());
+        }
+
+        for mut root in self.free_typevars() {
+            while !self.vars.contains(&root) {
+                let child = match children.get(&root) {
+                    Some(set) if set.len() == 1 => set.iter().next().unwrap().clone(),
+                    _ => break,
+                };
+                if let Some(parent) = self.type_map.remove(&child) {
+                    assert!(parent == root);
+                }
+                root = child;
+            }
+        }
+    }
+
+    /// Extract a clean type environment from self, that only mentions type
+    /// variables associated with real variables.
+    pub fn extract(&self) -> TypeEnvironment {
+        let mut type_map = HashMap::new();
+        for tv in &self.vars {
+            let equivalent = self.get_equivalent(tv);
+            if *tv != equivalent {
+                type_map.insert(tv.clone(), equivalent);
+            }
+        }
+
+        let mut constraints: Vec<Constraint> = Vec::new();
+        for constraint in &self.constraints {
+            let constraint = constraint.translate_with_env(self);
+            if constraint.is_trivial() || constraints.contains(&constraint) {
+                continue;
+            }
+
+            // Sanity: translated constraints should refer to only real vars.
+            for tv in constraint.typevars() {
+                if let Some(free) = tv.free_typevar() {
+                    assert!(self.vars.contains(&free));
+                }
+            }
+
+            constraints.push(constraint);
+        }
+
+        // Sanity: translated typemap should refer to only real vars.
+        for (k, v) in &type_map {
+            assert!(self.vars.contains(k));
+            if let Some(free) = v.free_typevar() {
+                assert!(self.vars.contains(&free));
+            }
+        }
+
+        TypeEnvironment {
+            vars: self.vars.clone(),
+            ranks: self.ranks.clone(),
+            type_map,
+            constraints,
+            idx: 0,
+        }
+    }
+
+    /// Get all the possible concrete typings permitted by this type
+    /// environment. Each typing maps the type variable of every registered
+    /// variable to a singleton type variable.
+    pub fn concrete_typings(&self) -> Vec<HashMap<TypeVar, TypeVar>> {
+        let free_tvs = self.free_typevars();
+        let all_types: Vec<Vec<TypeVar>> = free_tvs
+            .iter()
+            .map(|tv| {
+                tv.get_typeset()
+                    .concrete_types()
+                    .into_iter()
+                    .map(TypeVar::new_singleton)
+                    .collect()
+            })
+            .collect();
+
+        let mut typings = Vec::new();
+        let mut indices = vec![0; free_tvs.len()];
+        if all_types.iter().any(|types| types.is_empty()) {
+            return typings;
+        }
+
+        loop {
+            // Build type substitutions for all free vars.
+            let map: HashMap<TypeVar, TypeVar> = free_tvs
+                .iter()
+                .zip(indices.iter().zip(all_types.iter()))
+                .map(|(tv, (&i, types))| (tv.clone(), types[i].clone()))
+                .collect();
+
+            // Check if constraints are satisfied for this typing.
+            if self
+                .constraints
+                .iter()
+                .all(|constraint| constraint.translate_with_map(&map).eval())
+            {
+                typings.push(
+                    self.vars
+                        .iter()
+                        .map(|tv| (tv.clone(), subst(&self.get_equivalent(tv), &map)))
+                        .collect(),
+                );
+            }
+
+            // Advance to the next combination of concrete types.
+            let mut pos = indices.len();
+            loop {
+                if pos == 0 {
+                    return typings;
+                }
+                pos -= 1;
+                indices[pos] += 1;
+                if indices[pos] < all_types[pos].len() {
+                    break;
+                }
+                indices[pos] = 0;
+            }
+        }
+    }
+
+    /// Return true iff this type environment permits the (possibly partial)
+    /// concrete typing `concrete_typing`, mapping variable type variables to
+    /// singleton type variables.
+    pub fn permits(&self, concrete_typing: &HashMap<TypeVar, TypeVar>) -> bool {
+        // Each variable has a concrete type, that is a subset of its inferred
+        // typeset.
+        for (tv, typ) in concrete_typing {
+            assert!(typ.singleton_type().is_some());
+            if !typ
+                .get_typeset()
+                .is_subset(&self.get_equivalent(tv).get_typeset())
+            {
+                return false;
+            }
+        }
+
+        let map: HashMap<TypeVar, TypeVar> = concrete_typing
+            .iter()
+            .map(|(tv, typ)| (self.get_equivalent(tv), typ.clone()))
+            .collect();
+
+        // Constraints involving only vars in the concrete typing must be
+        // satisfied. Other constraints are ignored.
+        self.constraints.iter().all(|constraint| {
+            let constraint = constraint.translate_with_map(&map);
+            !constraint.is_concrete() || constraint.eval()
+        })
+    }
+
+    /// Unify `tv1` and `tv2` in this type environment.
+    pub fn unify(&mut self, tv1: &TypeVar, tv2: &TypeVar) -> Result<(), String> {
+        let tv1 = normalize_tv(&self.get_equivalent(tv1));
+        let tv2 = normalize_tv(&self.get_equivalent(tv2));
+
+        // Already unified.
+        if tv1 == tv2 {
+            return Ok(());
+        }
+
+        if self.rank(&tv2) < self.rank(&tv1) {
+            return self.unify(&tv2, &tv1);
+        }
+
+        constrain_fixpoint(&tv1, &tv2);
+
+        if tv1.get_typeset().size() == 0 || tv2.get_typeset().size() == 0 {
+            return Err(format!(
+                "Error: empty type created when unifying {} and {}",
+                tv1, tv2
+            ));
+        }
+
+        let base = match tv1.base() {
+            // Free -> Derived(Free).
+            None => {
+                self.record_equivalent(tv1, tv2);
+                return Ok(());
+            }
+            Some(base) => base.clone(),
+        };
+
+        if let Some(inverse) = base.derived_func.inverse() {
+            return self.unify(&base.type_var, &normalize_tv(&tv2.derived(inverse)));
+        }
+
+        self.add_constraint(Constraint::types_equal(tv1, tv2));
+        Ok(())
+    }
+
+    /// Return a representation of self as a graph in dot format.
+    ///
+    /// - Nodes correspond to type variables.
+    /// - Dotted edges correspond to equivalences between type variables.
+    /// - Solid edges correspond to derivation relations between type
+    ///   variables.
+    /// - Dashed edges correspond to constraints.
+    pub fn to_dot(&self) -> String {
+        let mut nodes: Vec<TypeVar> = Vec::new();
+        let mut edges: Vec<(
+            TypeVar,
+            TypeVar,
+            &'static str,
+            &'static str,
+            Option<&'static str>,
+        )> = Vec::new();
+
+        fn add_nodes(
+            nodes: &mut Vec<TypeVar>,
+            edges: &mut Vec<(
+                TypeVar,
+                TypeVar,
+                &'static str,
+                &'static str,
+                Option<&'static str>,
+            )>,
+            tv: &TypeVar,
+        ) {
+            let mut tv = tv.clone();
+            if !nodes.contains(&tv) {
+                nodes.push(tv.clone());
+            }
+            while let Some(base) = tv.base().cloned() {
+                if !nodes.contains(&base.type_var) {
+                    nodes.push(base.type_var.clone());
+                }
+                let edge = (
+                    tv.clone(),
+                    base.type_var.clone(),
+                    "solid",
+                    "forward",
+                    Some(base.derived_func.name()),
+                );
+                if !edges.contains(&edge) {
+                    edges.push(edge);
+                }
+                tv = base.type_var;
+            }
+        }
+
+        // Add all registered type variables, as some of them may be singleton
+        // nodes not appearing in the graph.
+        for tv in &self.vars {
+            add_nodes(&mut nodes, &mut edges, tv);
+        }
+
+        for (tv1, tv2) in &self.type_map {
+            // Add all intermediate type variables appearing in edges.
+            add_nodes(&mut nodes, &mut edges, tv1);
+            add_nodes(&mut nodes, &mut edges, tv2);
+            edges.push((tv1.clone(), tv2.clone(), "dotted", "forward", None));
+        }
+
+        for constraint in &self.constraints {
+            let (tv1, tv2, dir, label) = match *constraint {
+                Constraint::TypesEqual(ref tv1, ref tv2) => (tv1, tv2, "none", "equal"),
+                Constraint::WiderOrEq(ref tv1, ref tv2) => (tv1, tv2, "forward", ">="),
+                Constraint::SameWidth(ref tv1, ref tv2) => (tv1, tv2, "none", "same_width"),
+                Constraint::InTypeset(..) => panic!("Can't display constraint {}", constraint),
+            };
+            add_nodes(&mut nodes, &mut edges, tv1);
+            add_nodes(&mut nodes, &mut edges, tv2);
+            edges.push((tv1.clone(), tv2.clone(), "dashed", dir, Some(label)));
+        }
+
+        let mut result = "digraph {\n".to_string();
+        for node in &nodes {
+            result += &format!("\"{}\"", node);
+            if !self.type_map.contains_key(node) && !node.is_derived() {
+                result += &format!("[xlabel=\"{}\"]", self.get_equivalent(node).get_typeset());
+            }
+            result += ";\n";
+        }
+
+        for (tv1, tv2, style, dir, label) in edges {
+            result += &format!("\"{}\"->\"{}\"[style={},dir={}", tv1, tv2, style, dir);
+            if let Some(label) = label {
+                result += &format!(",label=\"{}\"", label);
+            }
+            result += "];\n";
+        }
+        result += "}";
+        result
+    }
+}
+
+impl fmt::Display for TypeEnvironment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_dot())
+    }
+}
+
+/// Perform substitution on the type variable `tv` using the map `map`.
+pub fn subst(tv: &TypeVar, map: &HashMap<TypeVar, TypeVar>) -> TypeVar {
+    if let Some(result) = map.get(tv) {
+        return result.clone();
+    }
+    match tv.base() {
+        Some(base) => subst(&base.type_var, map).derived(base.derived_func),
+        None => tv.clone(),
+    }
+}
+
+/// Normalize a (potentially derived) type variable using the following
+/// rules:
+///
+/// - vector and width derived functions commute:
+///   {HALF,DOUBLE}VECTOR({HALF,DOUBLE}WIDTH(base)) ->
+///   {HALF,DOUBLE}WIDTH({HALF,DOUBLE}VECTOR(base))
+///
+/// - half/double pairs collapse:
+///   {HALF,DOUBLE}WIDTH({DOUBLE,HALF}WIDTH(base)) -> base
+///   {HALF,DOUBLE}VECTOR({DOUBLE,HALF}VECTOR(base)) -> base
+fn normalize_tv(tv: &TypeVar) -> TypeVar {
+    let base = match tv.base() {
+        Some(base) => base,
+        None => return tv.clone(),
+    };
+    let df = base.derived_func;
+
+    if let Some(base_base) = base.type_var.base() {
+        let base_df = base_base.derived_func;
+        let is_vector = |func| func == DerivedFunc::HalfVector || func == DerivedFunc::DoubleVector;
+        let is_width = |func| func == DerivedFunc::HalfWidth || func == DerivedFunc::DoubleWidth;
+
+        // Reordering: {HALFWIDTH, DOUBLEWIDTH} commute with {HALFVECTOR,
+        // DOUBLEVECTOR}. Arbitrarily pick WIDTH < VECTOR.
+        if is_vector(df) && is_width(base_df) {
+            return normalize_tv(&base_base.type_var.derived(df).derived(base_df));
+        }
+
+        // Cancelling: HALFWIDTH, DOUBLEWIDTH and HALFVECTOR, DOUBLEVECTOR
+        // cancel each other. Note: This doesn't hide any over/underflows,
+        // since we 1) assert the safety of each type variable in the chain
+        // upon its creation, and 2) the base typeset is only allowed to
+        // shrink.
+        if df.inverse() == Some(base_df) {
+            return normalize_tv(&base_base.type_var);
+        }
+    }
+
+    normalize_tv(&base.type_var).derived(df)
+}
+
+/// Given type variables `tv1` and `tv2` (which could be derived from one
+/// another) constrain their typesets to be the same. When one is derived from
+/// the other, repeat the constrain process until fixpoint.
+fn constrain_fixpoint(tv1: &TypeVar, tv2: &TypeVar) {
+    // Constrain tv2's typeset as long as tv1's typeset is changing.
+    loop {
+        let old_tv1_ts = tv1.get_typeset();
+        tv2.constrain_types(tv1);
+        if tv1.get_typeset() == old_tv1_ts {
+            break;
+        }
+    }
+
+    let old_tv2_ts = tv2.get_typeset();
+    tv1.constrain_types(tv2);
+    assert!(old_tv2_ts == tv2.get_typeset());
+}
+
+/// Perform type inference on a single definition `def` of a pattern whose
+/// variables are described by `symbols`, updating the environment `env`.
+fn ti_def(def: &Def, symbols: &SymbolTable, env: &mut TypeEnvironment) -> Result<(), String> {
+    let inst = &def.expr.inst;
+
+    // Map each free type variable in the signature of the instruction to a
+    // fresh copy of itself, or to the explicitly bound type.
+    let free_formal_tvs = inst.all_typevars();
+    let mut map = HashMap::new();
+    for &tv in &free_formal_tvs {
+        let fresh = tv.get_fresh_copy(env.get_uid());
+        map.insert(tv.clone(), fresh);
+    }
+    for (tv, value_type) in free_formal_tvs.iter().zip(&def.expr.value_types) {
+        map.insert((*tv).clone(), TypeVar::new_singleton(value_type.clone()));
+    }
+
+    // Get fresh copies for each type variable in the signature, both free and
+    // derived.
+    let mut formal_tvs = inst
+        .value_results
+        .iter()
+        .map(|&i| &inst.operands_out[i])
+        .chain(inst.value_opnums.iter().map(|&i| &inst.operands_in[i]))
+        .map(|op| subst(op.type_var().unwrap(), &map))
+        .collect::<Vec<_>>();
+
+    // Get the type variables of the actual variables, registering them with
+    // the rank of their variable.
+    let mut actual_tvs = inst
+        .value_results
+        .iter()
+        .map(|&i| def.defs[i])
+        .chain(def.expr.value_args())
+        .map(|var| {
+            let symbol = symbols.get(var);
+            env.register(symbol.type_var(), symbol.rank());
+            symbol.type_var().clone()
+        })
+        .collect::<Vec<_>>();
+
+    // Make sure we unify the controlling type variable first.
+    if let Some(ref poly) = inst.polymorphic_info {
+        let ctrl_tv = &map[&poly.ctrl_typevar];
+        let idx = formal_tvs.iter().position(|tv| tv == ctrl_tv).unwrap();
+        let formal = formal_tvs.remove(idx);
+        formal_tvs.insert(0, formal);
+        let actual = actual_tvs.remove(idx);
+        actual_tvs.insert(0, actual);
+    }
+
+    for (actual_tv, formal_tv) in actual_tvs.iter().zip(&formal_tvs) {
+        env.unify(actual_tv, formal_tv)
+            .map_err(|err| format!("fail ti on {} <: {}: {}", actual_tv, formal_tv, err))?;
+    }
+
+    // Add any instruction specific constraints.
+    for constraint in &inst.constraints {
+        env.add_constraint(constraint.translate_with_map(&map));
+    }
+    Ok(())
+}
+
+/// Perform type inference on the pattern `rtl`, updating the environment
+/// `env`.
+pub fn ti_rtl(rtl: &Rtl, symbols: &SymbolTable, env: &mut TypeEnvironment) -> Result<(), String> {
+    for (i, def) in rtl.rtl.iter().enumerate() {
+        ti_def(def, symbols, env).map_err(|err| format!("On line {}: {}", i, err))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base;
+    use base::types as base_types;
+    use cdsl::ast::var;
+    use cdsl::typevar::{Interval, TypeSetBuilder};
+
+    fn ints(name: &str) -> TypeVar {
+        TypeVar::new(
+            name,
+            "",
+            TypeSetBuilder::new()
+                .ints(Interval::All)
+                .simd_lanes(Interval::All)
+                .finish(),
+        )
+    }
+
+    #[test]
+    fn normalize_derived() {
+        let x = TypeVar::new(
+            "x",
+            "",
+            TypeSetBuilder::new()
+                .ints(16..=32)
+                .simd_lanes(2..=128)
+                .finish(),
+        );
+        assert_eq!(normalize_tv(&x.half_width().double_width()), x);
+        assert_eq!(
+            normalize_tv(&x.double_vector().half_vector().as_bool()),
+            x.as_bool()
+        );
+        // Width functions are moved before vector functions.
+        assert_eq!(
+            normalize_tv(&x.half_width().double_vector()),
+            x.double_vector().half_width()
+        );
+    }
+
+    #[test]
+    fn unify_empty_type() {
+        let x = TypeVar::new(
+            "typeof_v2",
+            "",
+            TypeSetBuilder::new()
+                .ints(Interval::All)
+                .simd_lanes(2..=256)
+                .finish(),
+        );
+        let mut env = TypeEnvironment::new();
+        assert_eq!(
+            env.unify(&x, &x.half_vector()),
+            Err(
+                "Error: empty type created when unifying `typeof_v2` and `half_vector(typeof_v2)`"
+                    .to_string()
+            )
+        );
+
+        let i16 = TypeVar::new_singleton(base_types::Int::I16.into());
+        let i32 = TypeVar::new_singleton(base_types::Int::I32.into());
+        assert_eq!(
+            env.unify(&i16, &i32),
+            Err("Error: empty type created when unifying `i16` and `i32`".to_string())
+        );
+    }
+
+    #[test]
+    fn ti_rtl_empty_type() {
+        let shared = base::define();
+        let vconcat = shared.instructions.by_name("vconcat");
+        let vsplit = shared.instructions.by_name("vsplit");
+        let (v0, v1, v2, v3) = (var("v0"), var("v1"), var("v2"), var("v3"));
+
+        let rtl = Rtl::new(vec![
+            def!((v0, v1) = vsplit(v2)),
+            def!(v3 = vconcat(v0, v2)),
+        ]);
+        let symbols = SymbolTable::new_inputs(&rtl);
+        let mut env = TypeEnvironment::new();
+        assert_eq!(
+            ti_rtl(&rtl, &symbols, &mut env),
+            Err(
+                "On line 1: fail ti on `typeof_v2` <: `1`: Error: empty type created when \
+                 unifying `typeof_v2` and `half_vector(typeof_v2)`"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn unify_free_and_derived() {
+        let a = TypeVar::new(
+            "a",
+            "",
+            TypeSetBuilder::new()
+                .ints(16..=64)
+                .simd_lanes(Interval::All)
+                .finish(),
+        );
+        let b = ints("b");
+        let mut env = TypeEnvironment::new();
+        env.unify(&a.half_width(), &b).unwrap();
+
+        // half_width is a bijection, so `a` is now known to be
+        // `double_width(b)`.
+        assert_eq!(env.get_equivalent(&a), b.double_width());
+        assert_eq!(
+            env.get_equivalent(&a.half_width()),
+            b.double_width().half_width()
+        );
+        assert_eq!(
+            a.get_typeset().ints.into_iter().collect::<Vec<_>>(),
+            vec![16, 32, 64]
+        );
+        assert_eq!(
+            b.get_typeset().ints.into_iter().collect::<Vec<_>>(),
+            vec![8, 16, 32]
+        );
+        assert!(env.constraints.is_empty());
+    }
+
+    #[test]
+    fn unify_by_rank() {
+        let a = ints("typeof_a");
+        let b = ints("typeof_b");
+        let c = ints("typeof_c");
+        let mut env = TypeEnvironment::new();
+        env.register(&a, TypeEnvRank::Input);
+        env.register(&b, TypeEnvRank::Temp);
+        env.register(&c, TypeEnvRank::Output);
+
+        // The higher ranked type variable becomes the representative.
+        env.unify(&a, &b).unwrap();
+        assert_eq!(env.get_equivalent(&b), a);
+        assert_eq!(env.get_equivalent(&a), a);
+
+        // as_bool isn't a bijection, so a constraint is recorded instead.
+        env.unify(&a.as_bool(), &c.as_bool()).unwrap();
+        assert_eq!(
+            env.constraints,
+            vec![Constraint::types_equal(a.as_bool(), c.as_bool())]
+        );
+        assert_eq!(env.free_typevars(), vec![a.clone(), c.clone()]);
+
+        let env = env.extract();
+        assert_eq!(env.get_equivalent(&b), a);
+        assert_eq!(env.constraints.len(), 1);
+    }
+
+    #[test]
+    fn extend_reduce_enumeration() {
+        // The number of possible typings of `uextend` is 9 * (4 + 3 + 2 + 1).
+        let v0 = ints("typeof_v0");
+        let v1 = ints("typeof_v1");
+        let mut env = TypeEnvironment::new();
+        env.register(&v0, TypeEnvRank::Input);
+        env.register(&v1, TypeEnvRank::Output);
+        env.add_constraint(Constraint::WiderOrEq(v1.clone(), v0.clone()));
+
+        let typings = env.concrete_typings();
+        assert_eq!(typings.len(), 90);
+        for typing in &typings {
+            let typ0 = typing[&v0].singleton_type().unwrap();
+            let typ1 = typing[&v1].singleton_type().unwrap();
+            assert!(typ1.wider_or_equal(&typ0));
+            assert!(env.permits(typing));
+        }
+
+        let mut typing = HashMap::new();
+        typing.insert(
+            v0.clone(),
+            TypeVar::new_singleton(base_types::Int::I64.into()),
+        );
+        typing.insert(
+            v1.clone(),
+            TypeVar::new_singleton(base_types::Int::I8.into()),
+        );
+        assert!(!env.permits(&typing));
+    }
+
+    #[test]
+    fn trivial_constraints() {
+        let i8 = TypeVar::new_singleton(base_types::Int::I8.into());
+        let i32 = TypeVar::new_singleton(base_types::Int::I32.into());
+        let f32 = TypeVar::new_singleton(base_types::Float::F32.into());
+        let x = ints("x");
+
+        assert!(Constraint::WiderOrEq(x.clone(), x.clone()).is_trivial());
+        assert!(Constraint::WiderOrEq(i32.clone(), i8.clone()).is_trivial());
+        assert!(Constraint::WiderOrEq(i32.clone(), i8.clone()).eval());
+        assert!(!Constraint::WiderOrEq(i8.clone(), i32.clone()).eval());
+        assert!(!Constraint::WiderOrEq(x.clone(), i8.clone()).is_trivial());
+        assert!(Constraint::SameWidth(i32.clone(), f32.clone()).eval());
+        assert!(!Constraint::SameWidth(x.clone(), i32.clone()).is_trivial());
+        assert_eq!(
+            Constraint::types_equal(x.as_bool(), i8.clone()),
+            Constraint::types_equal(i8.clone(), x.as_bool())
+        );
+        assert_eq!(
+            Constraint::WiderOrEq(x, i8).to_string(),
+            "WiderOrEq(`x`, `i8`)"
+        );
+    }
+}
@@ -3,6 +3,8 @@
 // Temporary disabled: Unused at the moment.
 // use std::collections::HashMap;
 
+use std::fmt;
+
 use base::types as base_types;
 
 static _RUST_NAME_PREFIX: &'static str = "ir::types::";
@@ -13,6 +15,7 @@ static _RUST_NAME_PREFIX: &'static str = "ir::types::";
 ///
 /// All SSA values have a type that is described by an instance of `ValueType`
 /// or one of its subclasses.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum ValueType {
     BV(BVType),
     Lane(LaneType),
@@ -85,14 +88,14 @@ impl ValueType {
     }
 
     /// Return the name of this type for other Rust source files.
-    pub fn _rust_name(&self) -> String {
+    pub fn rust_name(&self) -> String {
         format!("{}{}", _RUST_NAME_PREFIX, self.name().to_uppercase())
     }
 
     /// Return true iff:
     ///     1. self and other have equal number of lanes
     ///     2. each lane in self has at least as many bits as a lane in other
-    pub fn _wider_or_equal(&self, rhs: &ValueType) -> bool {
+    pub fn wider_or_equal(&self, rhs: &ValueType) -> bool {
         (self.lane_count() == rhs.lane_count()) && (self.lane_bits() >= rhs.lane_bits())
     }
 
@@ -130,8 +133,44 @@ impl From<VectorType> for ValueType {
     }
 }
 
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Create a ValueType from a given bool variant.
+impl From<base_types::Bool> for ValueType {
+    fn from(b: base_types::Bool) -> Self {
+        ValueType::Lane(b.into())
+    }
+}
+
+/// Create a ValueType from a given float variant.
+impl From<base_types::Float> for ValueType {
+    fn from(f: base_types::Float) -> Self {
+        ValueType::Lane(f.into())
+    }
+}
+
+/// Create a ValueType from a given int variant.
+impl From<base_types::Int> for ValueType {
+    fn from(i: base_types::Int) -> Self {
+        ValueType::Lane(i.into())
+    }
+}
+
+/// Create a ValueType from a given flag variant.
+impl From<base_types::Flag> for ValueType {
+    fn from(f: base_types::Flag) -> Self {
+        ValueType::Special(SpecialType {
+            tag: SpecialTypeTag::Flag(f),
+        })
+    }
+}
+
 /// A concrete scalar type that can appear as a vector lane too.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct LaneType {
     bits: u64,
     tag: LaneTypeTag,
@@ -183,6 +222,72 @@ impl LaneType {
             LaneTypeTag::IntType(i) => i.number(),
         }
     }
+
+    /// Is this an integer lane type?
+    pub fn is_int(&self) -> bool {
+        match self.tag {
+            LaneTypeTag::IntType(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Is this a floating point lane type?
+    pub fn is_float(&self) -> bool {
+        match self.tag {
+            LaneTypeTag::FloatType(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Is this a boolean lane type?
+    pub fn is_bool(&self) -> bool {
+        match self.tag {
+            LaneTypeTag::BoolType(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Get the integer lane type with `bits` bits.
+    pub fn int_from_bits(bits: u16) -> LaneType {
+        LaneType::from(match bits {
+            8 => base_types::Int::I8,
+            16 => base_types::Int::I16,
+            32 => base_types::Int::I32,
+            64 => base_types::Int::I64,
+            _ => panic!("no i{} type", bits),
+        })
+    }
+
+    /// Get the floating point lane type with `bits` bits.
+    pub fn float_from_bits(bits: u16) -> LaneType {
+        LaneType::from(match bits {
+            32 => base_types::Float::F32,
+            64 => base_types::Float::F64,
+            _ => panic!("no f{} type", bits),
+        })
+    }
+
+    /// Get the boolean lane type with `bits` bits.
+    pub fn bool_from_bits(bits: u16) -> LaneType {
+        LaneType::from(match bits {
+            1 => base_types::Bool::B1,
+            8 => base_types::Bool::B8,
+            16 => base_types::Bool::B16,
+            32 => base_types::Bool::B32,
+            64 => base_types::Bool::B64,
+            _ => panic!("no b{} type", bits),
+        })
+    }
+
+    /// Get a type with this lane type and `lanes` lanes. A single lane is the
+    /// lane type itself.
+    pub fn by(self, lanes: u16) -> ValueType {
+        if lanes == 1 {
+            ValueType::Lane(self)
+        } else {
+            ValueType::Vector(VectorType::new(self, lanes.into()))
+        }
+    }
 }
 
 /// Create a LaneType from a given bool variant.
@@ -213,7 +318,7 @@ impl From<base_types::Int> for LaneType {
 }
 
 /// Tags used to specify the kinds of elements in a lane type.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum LaneTypeTag {
     BoolType(base_types::Bool),
     FloatType(base_types::Float),
@@ -257,6 +362,7 @@ impl Iterator for LaneTypeIterator {
 ///
 /// A vector type has a lane type which is an instance of `LaneType`,
 /// and a positive number of lanes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct VectorType {
     base: LaneType,
     lanes: u64,
@@ -278,7 +384,7 @@ impl VectorType {
 
     /// Get the name of this type.
     pub fn name(&self) -> String {
-        format!("{}X{}", self.base.name(), self.lanes,)
+        format!("{}x{}", self.base.name(), self.lanes)
     }
 
     /// Find the unique number associated with this type.
@@ -298,16 +404,22 @@ impl VectorType {
     pub fn lane_count(&self) -> u64 {
         self.lanes
     }
+
+    /// Get the lane type of this vector type.
+    pub fn lane_type(&self) -> LaneType {
+        self.base
+    }
 }
 
 /// A flat bitvector type. Used for semantics description only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct BVType {
     bits: u64,
 }
 
 impl BVType {
     /// Initialize a new bitvector type with `n` bits.
-    pub fn _new(bits: u64) -> Self {
+    pub fn new(bits: u64) -> Self {
         Self { bits }
     }
 
@@ -330,6 +442,7 @@ impl BVType {
 /// A concrete scalar type that is neither a vector nor a lane type.
 ///
 /// Special types cannot be used to form vectors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct SpecialType {
     tag: SpecialTypeTag,
 }
@@ -363,6 +476,7 @@ impl SpecialType {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum SpecialTypeTag {
     Flag(base_types::Flag),
 }
@@ -0,0 +1,1329 @@
+//! Type variables for Parametric polymorphism.
+//!
+//! Cranelift instructions and instruction transformations can be specified to
+//! be polymorphic by using type variables.
+
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::hash;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+
+use cdsl::types::{BVType, LaneType, SpecialType, ValueType};
+
+const MAX_LANES: u16 = 256;
+const MAX_BITS: u16 = 64;
+const MAX_BITVEC: u16 = MAX_BITS * MAX_LANES;
+
+/// A set of numbers, such as lane counts or bit widths.
+pub type NumSet = BTreeSet<u16>;
+
+macro_rules! num_set {
+    ($($expr:expr),*) => {
+        vec![$($expr),*].into_iter().collect::<NumSet>()
+    };
+}
+
+/// Type variables can be used in place of concrete types when defining
+/// instructions. This makes the instructions *polymorphic*.
+///
+/// A type variable is restricted to vary over a subset of the value types.
+/// This subset is specified by a set of flags that control the permitted base
+/// types and whether the type variable can assume scalar or vector types, or
+/// both.
+#[derive(Clone, Debug)]
+pub struct TypeVar {
+    content: Rc<TypeVarContent>,
+}
+
+#[derive(Debug)]
+struct TypeVarContent {
+    /// Short name of type variable used in instruction descriptions.
+    name: String,
+    /// Documentation string.
+    doc: String,
+    /// Type set associated to the type variable. Derived type variables
+    /// compute their type set from their base instead. The type set can only
+    /// shrink during type inference, see `constrain_types`.
+    type_set: Option<RefCell<TypeSet>>,
+    base: Option<TypeVarParent>,
+}
+
+/// The type variable a derived type variable is computed from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypeVarParent {
+    pub type_var: TypeVar,
+    pub derived_func: DerivedFunc,
+}
+
+impl TypeVar {
+    pub fn new<S: Into<String>>(name: S, doc: S, type_set: TypeSet) -> Self {
+        Self {
+            content: Rc::new(TypeVarContent {
+                name: name.into(),
+                doc: doc.into(),
+                type_set: Some(RefCell::new(type_set)),
+                base: None,
+            }),
+        }
+    }
+
+    /// Create an anonymous type variable from a type set.
+    pub fn from_typeset(type_set: TypeSet) -> Self {
+        TypeVar::new("", "", type_set)
+    }
+
+    /// Create a type variable that can only assume a single type.
+    pub fn new_singleton(value_type: ValueType) -> Self {
+        let (name, doc) = (value_type.name(), value_type.doc());
+        let mut builder = TypeSetBuilder::new();
+
+        let (scalar_type, num_lanes) = match value_type {
+            ValueType::Special(special_type) => {
+                return TypeVar::new(name, doc, builder.specials(vec![special_type]).finish());
+            }
+            ValueType::BV(bitvec_type) => {
+                let bits = bitvec_type.lane_bits() as u16;
+                return TypeVar::new(name, doc, builder.bitvecs(bits..=bits).finish());
+            }
+            ValueType::Lane(lane_type) => (lane_type, 1),
+            ValueType::Vector(vec_type) => (vec_type.lane_type(), vec_type.lane_count() as u16),
+        };
+
+        builder = builder.simd_lanes(num_lanes..=num_lanes);
+
+        let bits = scalar_type.lane_bits() as u16;
+        let builder = if scalar_type.is_int() {
+            builder.ints(bits..=bits)
+        } else if scalar_type.is_float() {
+            builder.floats(bits..=bits)
+        } else {
+            builder.bools(bits..=bits)
+        };
+        TypeVar::new(name, doc, builder.finish())
+    }
+
+    /// Create a type variable that is a function of another.
+    pub fn derived(&self, derived_func: DerivedFunc) -> TypeVar {
+        // Safety checks to avoid over/underflows.
+        let ts = self.get_typeset();
+        assert!(ts.specials.is_empty(), "Can't derive from special types");
+
+        match derived_func {
+            DerivedFunc::HalfWidth => {
+                assert!(
+                    ts.ints.is_empty() || *ts.ints.iter().min().unwrap() > 8,
+                    "Can't halve all integer types"
+                );
+                assert!(
+                    ts.floats.is_empty() || *ts.floats.iter().min().unwrap() > 32,
+                    "Can't halve all float types"
+                );
+                assert!(
+                    ts.bools.is_empty() || *ts.bools.iter().min().unwrap() > 8,
+                    "Can't halve all boolean types"
+                );
+            }
+            DerivedFunc::DoubleWidth => {
+                assert!(
+                    ts.ints.is_empty() || *ts.ints.iter().max().unwrap() < MAX_BITS,
+                    "Can't double all integer types."
+                );
+                assert!(
+                    ts.floats.is_empty() || *ts.floats.iter().max().unwrap() < MAX_BITS,
+                    "Can't double all float types."
+                );
+                assert!(
+                    ts.bools.is_empty() || *ts.bools.iter().max().unwrap() < MAX_BITS,
+                    "Can't double all bool types."
+                );
+            }
+            DerivedFunc::HalfVector => {
+                assert!(
+                    *ts.lanes.iter().min().unwrap() > 1,
+                    "Can't halve a scalar type"
+                );
+            }
+            DerivedFunc::DoubleVector => {
+                assert!(
+                    *ts.lanes.iter().max().unwrap() < MAX_LANES,
+                    "Can't double 256 lanes."
+                );
+            }
+            DerivedFunc::LaneOf | DerivedFunc::AsBool | DerivedFunc::ToBitVec => {}
+        }
+
+        TypeVar {
+            content: Rc::new(TypeVarContent {
+                name: format!("{}({})", derived_func.name(), self.name()),
+                doc: String::new(),
+                type_set: None,
+                base: Some(TypeVarParent {
+                    type_var: self.clone(),
+                    derived_func,
+                }),
+            }),
+        }
+    }
+
+    /// Return a derived type variable that is the scalar lane type of this
+    /// type variable.
+    ///
+    /// When this type variable assumes a scalar type, the derived type will be
+    /// the same scalar type.
+    pub fn lane_of(&self) -> TypeVar {
+        self.derived(DerivedFunc::LaneOf)
+    }
+
+    /// Return a derived type variable that has the same vector geometry as
+    /// this type variable, but with boolean lanes. Scalar types map to `b1`.
+    pub fn as_bool(&self) -> TypeVar {
+        self.derived(DerivedFunc::AsBool)
+    }
+
+    /// Return a derived type variable that has the same number of vector lanes
+    /// as this one, but the lanes are half the width.
+    pub fn half_width(&self) -> TypeVar {
+        self.derived(DerivedFunc::HalfWidth)
+    }
+
+    /// Return a derived type variable that has the same number of vector lanes
+    /// as this one, but the lanes are double the width.
+    pub fn double_width(&self) -> TypeVar {
+        self.derived(DerivedFunc::DoubleWidth)
+    }
+
+    /// Return a derived type variable that has half the number of vector lanes
+    /// as this one, with the same lane type.
+    pub fn half_vector(&self) -> TypeVar {
+        self.derived(DerivedFunc::HalfVector)
+    }
+
+    /// Return a derived type variable that has twice the number of vector
+    /// lanes as this one, with the same lane type.
+    pub fn double_vector(&self) -> TypeVar {
+        self.derived(DerivedFunc::DoubleVector)
+    }
+
+    /// Return a derived type variable that represent a flat bitvector with
+    /// the same size as self.
+    pub fn to_bitvec(&self) -> TypeVar {
+        self.derived(DerivedFunc::ToBitVec)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.content.name
+    }
+
+    pub fn doc(&self) -> &str {
+        &self.content.doc
+    }
+
+    /// Get the type variable and function this one is derived from, if any.
+    pub fn base(&self) -> Option<&TypeVarParent> {
+        self.content.base.as_ref()
+    }
+
+    pub fn is_derived(&self) -> bool {
+        self.content.base.is_some()
+    }
+
+    /// Returns the typeset for this type variable. If the type variable is
+    /// derived, computes it recursively from the derived function and the
+    /// base's typeset.
+    pub fn get_typeset(&self) -> TypeSet {
+        match self.content.base {
+            Some(ref base) => base.type_var.get_typeset().image(base.derived_func),
+            None => self.content.type_set.as_ref().unwrap().borrow().clone(),
+        }
+    }
+
+    /// Constrain the range of types this variable can assume to a subset of
+    /// those in the typeset `ts`.
+    pub fn constrain_types_by_ts(&self, ts: TypeSet) {
+        match self.content.base {
+            Some(ref base) => base
+                .type_var
+                .constrain_types_by_ts(ts.preimage(base.derived_func)),
+            None => self
+                .content
+                .type_set
+                .as_ref()
+                .unwrap()
+                .borrow_mut()
+                .intersect_with(&ts),
+        }
+    }
+
+    /// Constrain the range of types this variable can assume to a subset of
+    /// those `other` can assume.
+    pub fn constrain_types(&self, other: &TypeVar) {
+        if Rc::ptr_eq(&self.content, &other.content) {
+            return;
+        }
+        self.constrain_types_by_ts(other.get_typeset());
+    }
+
+    /// Get a fresh copy of self, named `name`. Can only be called on free
+    /// type variables.
+    pub fn get_fresh_copy<S: Into<String>>(&self, name: S) -> TypeVar {
+        assert!(!self.is_derived());
+        TypeVar::new(name.into(), String::new(), self.get_typeset())
+    }
+
+    /// If the associated typeset has a single type return it. Otherwise
+    /// return None.
+    pub fn singleton_type(&self) -> Option<ValueType> {
+        let ts = self.get_typeset();
+        if ts.size() != 1 {
+            return None;
+        }
+        Some(ts.get_singleton())
+    }
+
+    /// Get the free type variable controlling this one.
+    pub fn free_typevar(&self) -> Option<TypeVar> {
+        match self.content.base {
+            Some(ref base) => base.type_var.free_typevar(),
+            None if self.singleton_type().is_some() => {
+                // A singleton type variable is not a proper free variable.
+                None
+            }
+            None => Some(self.clone()),
+        }
+    }
+
+    /// Get a Rust expression that computes the type of this type variable.
+    pub fn rust_expr(&self) -> String {
+        // This also covers derived type variables with a known base, like the
+        // halves of an `iconcat.i64`.
+        if let Some(value_type) = self.singleton_type() {
+            return value_type.rust_name();
+        }
+        match self.content.base {
+            Some(ref base) => {
+                // `ir::Type` names the `lane_of` function `lane_type()`.
+                let func = match base.derived_func {
+                    DerivedFunc::LaneOf => "lane_type",
+                    func => func.name(),
+                };
+                format!("{}.{}()", base.type_var.rust_expr(), func)
+            }
+            None => self.name().to_string(),
+        }
+    }
+}
+
+/// Free type variables are equal if they are the same object. Derived type
+/// variables are equal if they apply the same function to equal bases.
+impl PartialEq for TypeVar {
+    fn eq(&self, other: &TypeVar) -> bool {
+        match (&self.content.base, &other.content.base) {
+            (&Some(ref base1), &Some(ref base2)) => base1 == base2,
+            _ => Rc::ptr_eq(&self.content, &other.content),
+        }
+    }
+}
+
+impl Eq for TypeVar {}
+
+impl hash::Hash for TypeVar {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        match self.content.base {
+            Some(ref base) => {
+                base.derived_func.hash(state);
+                base.type_var.hash(state);
+            }
+            None => (&*self.content as *const TypeVarContent).hash(state),
+        }
+    }
+}
+
+impl fmt::Display for TypeVar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "`{}`", self.name())
+    }
+}
+
+/// Supported functions for derived type variables.
+///
+/// The names here must match the method names on `ir::types::Type`, and their
+/// camel case must match `enum OperandConstraint` in `instructions.rs`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DerivedFunc {
+    LaneOf,
+    AsBool,
+    HalfWidth,
+    DoubleWidth,
+    HalfVector,
+    DoubleVector,
+    ToBitVec,
+}
+
+impl DerivedFunc {
+    pub fn name(self) -> &'static str {
+        match self {
+            DerivedFunc::LaneOf => "lane_of",
+            DerivedFunc::AsBool => "as_bool",
+            DerivedFunc::HalfWidth => "half_width",
+            DerivedFunc::DoubleWidth => "double_width",
+            DerivedFunc::HalfVector => "half_vector",
+            DerivedFunc::DoubleVector => "double_vector",
+            DerivedFunc::ToBitVec => "to_bitvec",
+        }
+    }
+
+    /// Is this function a bijection, i.e. can a type variable be recovered
+    /// from its image?
+    pub fn is_bijection(self) -> bool {
+        self.inverse().is_some()
+    }
+
+    /// Get the inverse of a bijective function.
+    pub fn inverse(self) -> Option<DerivedFunc> {
+        match self {
+            DerivedFunc::HalfWidth => Some(DerivedFunc::DoubleWidth),
+            DerivedFunc::DoubleWidth => Some(DerivedFunc::HalfWidth),
+            DerivedFunc::HalfVector => Some(DerivedFunc::DoubleVector),
+            DerivedFunc::DoubleVector => Some(DerivedFunc::HalfVector),
+            DerivedFunc::LaneOf | DerivedFunc::AsBool | DerivedFunc::ToBitVec => None,
+        }
+    }
+}
+
+/// An inclusive range of permitted bit widths or lane counts.
+#[derive(Clone, Debug)]
+pub enum Interval {
+    /// An empty interval.
+    None,
+    /// The full range of the permitted values.
+    All,
+    /// An explicit `(min, max)` inclusive range.
+    Range(u16, u16),
+}
+
+impl From<RangeInclusive<u16>> for Interval {
+    fn from(range: RangeInclusive<u16>) -> Self {
+        Interval::Range(*range.start(), *range.end())
+    }
+}
+
+impl Interval {
+    /// Convert this interval to the set of powers of two it contains, where
+    /// `All` means `full_range`.
+    fn to_set(&self, full_range: (u16, u16)) -> NumSet {
+        let (lo, hi) = match *self {
+            Interval::None => return NumSet::new(),
+            Interval::All => full_range,
+            Interval::Range(lo, hi) => {
+                assert!(lo.is_power_of_two());
+                assert!(hi.is_power_of_two());
+                assert!(lo <= hi);
+                assert!(lo >= full_range.0);
+                assert!(hi <= full_range.1);
+                (lo, hi)
+            }
+        };
+        let mut set = NumSet::new();
+        let mut x = lo;
+        while x <= hi {
+            set.insert(x);
+            if x == hi {
+                break;
+            }
+            x *= 2;
+        }
+        set
+    }
+}
+
+/// True iff `bits` is a legal bit width for a bool type.
+/// bits == 1 || bits \in { 8, 16, .. MAX_BITS }
+fn legal_bool(bits: u16) -> bool {
+    bits == 1 || (bits >= 8 && bits <= MAX_BITS && bits.is_power_of_two())
+}
+
+/// A set of types.
+///
+/// We don't allow arbitrary subsets of types, but use a parametrized approach
+/// instead.
+///
+/// Parametrized type sets are specified in terms of ranges:
+///
+/// - The permitted range of vector lanes, where 1 indicates a scalar type.
+/// - The permitted range of integer types.
+/// - The permitted range of floating point types, and
+/// - The permitted range of boolean types.
+///
+/// The ranges are inclusive from smallest bit-width to largest bit-width.
+///
+/// Finally, a type set can contain special types (derived from `SpecialType`)
+/// which can't appear as lane types.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TypeSet {
+    pub lanes: NumSet,
+    pub ints: NumSet,
+    pub floats: NumSet,
+    pub bools: NumSet,
+    pub bitvecs: NumSet,
+    pub specials: Vec<SpecialType>,
+}
+
+impl TypeSet {
+    fn new(
+        lanes: NumSet,
+        ints: NumSet,
+        floats: NumSet,
+        bools: NumSet,
+        bitvecs: NumSet,
+        mut specials: Vec<SpecialType>,
+    ) -> Self {
+        specials.sort_by_key(|s| s.name());
+        specials.dedup();
+        Self {
+            lanes,
+            ints,
+            floats,
+            bools,
+            bitvecs,
+            specials,
+        }
+    }
+
+    /// Return the number of concrete types represented by this typeset.
+    pub fn size(&self) -> usize {
+        self.lanes.len()
+            * (self.ints.len() + self.floats.len() + self.bools.len() + self.bitvecs.len())
+            + self.specials.len()
+    }
+
+    /// Return the image of self across the derived function `func`.
+    pub fn image(&self, func: DerivedFunc) -> TypeSet {
+        match func {
+            DerivedFunc::LaneOf => self.lane_of(),
+            DerivedFunc::AsBool => self.as_bool(),
+            DerivedFunc::HalfWidth => self.half_width(),
+            DerivedFunc::DoubleWidth => self.double_width(),
+            DerivedFunc::HalfVector => self.half_vector(),
+            DerivedFunc::DoubleVector => self.double_vector(),
+            DerivedFunc::ToBitVec => self.to_bitvec(),
+        }
+    }
+
+    /// Return a TypeSet describing the image of self across lane_of.
+    fn lane_of(&self) -> TypeSet {
+        let mut copy = self.clone();
+        copy.lanes = num_set![1];
+        copy.bitvecs = NumSet::new();
+        copy
+    }
+
+    /// Return a TypeSet describing the image of self across as_bool.
+    fn as_bool(&self) -> TypeSet {
+        let mut copy = self.clone();
+        copy.ints = NumSet::new();
+        copy.floats = NumSet::new();
+        copy.bitvecs = NumSet::new();
+        if self.lanes.iter().any(|&l| l != 1) {
+            copy.bools = &(&self.ints | &self.floats) | &self.bools;
+        }
+        if self.lanes.contains(&1) {
+            copy.bools.insert(1);
+        }
+        copy
+    }
+
+    /// Return a TypeSet describing the image of self across half_width.
+    fn half_width(&self) -> TypeSet {
+        let mut copy = self.clone();
+        copy.ints = self
+            .ints
+            .iter()
+            .filter(|&&x| x > 8)
+            .map(|&x| x / 2)
+            .collect();
+        copy.floats = self
+            .floats
+            .iter()
+            .filter(|&&x| x > 32)
+            .map(|&x| x / 2)
+            .collect();
+        copy.bools = self
+            .bools
+            .iter()
+            .filter(|&&x| x > 8)
+            .map(|&x| x / 2)
+            .collect();
+        copy.bitvecs = self
+            .bitvecs
+            .iter()
+            .filter(|&&x| x > 1)
+            .map(|&x| x / 2)
+            .collect();
+        copy.specials = Vec::new();
+        copy
+    }
+
+    /// Return a TypeSet describing the image of self across double_width.
+    fn double_width(&self) -> TypeSet {
+        let mut copy = self.clone();
+        copy.ints = self
+            .ints
+            .iter()
+            .filter(|&&x| x < MAX_BITS)
+            .map(|&x| x * 2)
+            .collect();
+        copy.floats = self
+            .floats
+            .iter()
+            .filter(|&&x| x < MAX_BITS)
+            .map(|&x| x * 2)
+            .collect();
+        copy.bools = self
+            .bools
+            .iter()
+            .filter(|&&x| x < MAX_BITS)
+            .map(|&x| x * 2)
+            .filter(|&x| legal_bool(x))
+            .collect();
+        copy.bitvecs = self
+            .bitvecs
+            .iter()
+            .filter(|&&x| x < MAX_BITVEC)
+            .map(|&x| x * 2)
+            .collect();
+        copy.specials = Vec::new();
+        copy
+    }
+
+    /// Return a TypeSet describing the image of self across half_vector.
+    fn half_vector(&self) -> TypeSet {
+        let mut copy = self.clone();
+        copy.bitvecs = NumSet::new();
+        copy.lanes = self
+            .lanes
+            .iter()
+            .filter(|&&x| x > 1)
+            .map(|&x| x / 2)
+            .collect();
+        copy.specials = Vec::new();
+        copy
+    }
+
+    /// Return a TypeSet describing the image of self across double_vector.
+    fn double_vector(&self) -> TypeSet {
+        let mut copy = self.clone();
+        copy.bitvecs = NumSet::new();
+        copy.lanes = self
+            .lanes
+            .iter()
+            .filter(|&&x| x < MAX_LANES)
+            .map(|&x| x * 2)
+            .collect();
+        copy.specials = Vec::new();
+        copy
+    }
+
+    /// Return a TypeSet describing the image of self across to_bitvec.
+    fn to_bitvec(&self) -> TypeSet {
+        assert!(self.bitvecs.is_empty());
+        let all_scalars = &(&self.ints | &self.floats) | &self.bools;
+
+        let mut copy = self.clone();
+        copy.lanes = num_set![1];
+        copy.ints = NumSet::new();
+        copy.bools = NumSet::new();
+        copy.floats = NumSet::new();
+        copy.bitvecs = all_scalars
+            .iter()
+            .flat_map(|&lane_width| self.lanes.iter().map(move |&nlanes| lane_width * nlanes))
+            .collect();
+        copy.specials = Vec::new();
+        copy
+    }
+
+    /// Return all the concrete types represented by this typeset.
+    pub fn concrete_types(&self) -> Vec<ValueType> {
+        let mut ret = Vec::new();
+        for &num_lanes in &self.lanes {
+            for &bits in &self.ints {
+                ret.push(LaneType::int_from_bits(bits).by(num_lanes));
+            }
+            for &bits in &self.floats {
+                ret.push(LaneType::float_from_bits(bits).by(num_lanes));
+            }
+            for &bits in &self.bools {
+                ret.push(LaneType::bool_from_bits(bits).by(num_lanes));
+            }
+            for &bits in &self.bitvecs {
+                assert_eq!(num_lanes, 1);
+                ret.push(ValueType::BV(BVType::new(bits.into())));
+            }
+        }
+        for &special in &self.specials {
+            ret.push(ValueType::Special(special));
+        }
+        ret
+    }
+
+    /// Return the singleton type represented by self. Can only call on
+    /// typesets containing 1 type.
+    pub fn get_singleton(&self) -> ValueType {
+        let mut types = self.concrete_types();
+        assert_eq!(types.len(), 1);
+        types.remove(0)
+    }
+
+    /// Intersect self with the `other` type set.
+    pub fn intersect_with(&mut self, other: &TypeSet) {
+        self.lanes = &self.lanes & &other.lanes;
+        self.ints = &self.ints & &other.ints;
+        self.floats = &self.floats & &other.floats;
+        self.bools = &self.bools & &other.bools;
+        self.bitvecs = &self.bitvecs & &other.bitvecs;
+        self.specials.retain(|s| other.specials.contains(s));
+    }
+
+    /// Return true iff self is a subset of `other`.
+    pub fn is_subset(&self, other: &TypeSet) -> bool {
+        self.lanes.is_subset(&other.lanes)
+            && self.ints.is_subset(&other.ints)
+            && self.floats.is_subset(&other.floats)
+            && self.bools.is_subset(&other.bools)
+            && self.bitvecs.is_subset(&other.bitvecs)
+            && self.specials.iter().all(|s| other.specials.contains(s))
+    }
+
+    /// Return the inverse image of self across the derived function `func`.
+    pub fn preimage(&self, func: DerivedFunc) -> TypeSet {
+        // The inverse of the empty set is always empty.
+        if self.size() == 0 {
+            return self.clone();
+        }
+
+        match func {
+            DerivedFunc::LaneOf => {
+                let mut copy = self.clone();
+                copy.bitvecs = NumSet::new();
+                copy.lanes = Interval::All.to_set((1, MAX_LANES));
+                copy
+            }
+            DerivedFunc::AsBool => {
+                let mut copy = self.clone();
+                copy.bitvecs = NumSet::new();
+                if !self.bools.contains(&1) {
+                    copy.ints = self.bools.iter().filter(|&&x| x != 1).cloned().collect();
+                    copy.floats = &self.bools & &num_set![32, 64];
+                    // If b1 is not in our typeset, than lanes=1 cannot be in the
+                    // pre-image, as as_bool() of scalars is always b1.
+                    copy.lanes = self.lanes.iter().filter(|&&x| x != 1).cloned().collect();
+                } else {
+                    copy.ints = Interval::All.to_set((8, MAX_BITS));
+                    copy.floats = num_set![32, 64];
+                }
+                copy
+            }
+            DerivedFunc::HalfWidth => self.double_width(),
+            DerivedFunc::DoubleWidth => self.half_width(),
+            DerivedFunc::HalfVector => self.double_vector(),
+            DerivedFunc::DoubleVector => self.half_vector(),
+            DerivedFunc::ToBitVec => {
+                let all = TypeSetBuilder::new()
+                    .ints(Interval::All)
+                    .floats(Interval::All)
+                    .bools(Interval::All)
+                    .simd_lanes(Interval::All)
+                    .finish();
+
+                // See which combinations have a size that appears in
+                // self.bitvecs.
+                let mut copy = TypeSetBuilder::new().finish();
+                for &lanes in &all.lanes {
+                    let mut has_lanes = false;
+                    for &bits in &all.ints {
+                        if self.bitvecs.contains(&(bits * lanes)) {
+                            copy.ints.insert(bits);
+                            has_lanes = true;
+                        }
+                    }
+                    for &bits in &all.floats {
+                        if self.bitvecs.contains(&(bits * lanes)) {
+                            copy.floats.insert(bits);
+                            has_lanes = true;
+                        }
+                    }
+                    for &bits in &all.bools {
+                        if self.bitvecs.contains(&(bits * lanes)) {
+                            copy.bools.insert(bits);
+                            has_lanes = true;
+                        }
+                    }
+                    if has_lanes {
+                        copy.lanes.insert(lanes);
+                    }
+                }
+                copy
+            }
+        }
+    }
+
+    /// Return the set of the widths of all possible types in self.
+    pub fn widths(&self) -> BTreeSet<u32> {
+        let scalar_widths = &(&(&self.ints | &self.floats) | &self.bools) | &self.bitvecs;
+        scalar_widths
+            .iter()
+            .flat_map(|&width| {
+                self.lanes
+                    .iter()
+                    .map(move |&lanes| u32::from(width) * u32::from(lanes))
+            })
+            .collect()
+    }
+}
+
+/// Format a set of numbers the way the Python meta language does, e.g.
+/// `{8, 16, 32}`.
+fn fmt_num_set(set: &NumSet) -> String {
+    let items: Vec<String> = set.iter().map(|x| x.to_string()).collect();
+    format!("{{{}}}", items.join(", "))
+}
+
+impl fmt::Display for TypeSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TypeSet(lanes={}", fmt_num_set(&self.lanes))?;
+        if !self.ints.is_empty() {
+            write!(f, ", ints={}", fmt_num_set(&self.ints))?;
+        }
+        if !self.floats.is_empty() {
+            write!(f, ", floats={}", fmt_num_set(&self.floats))?;
+        }
+        if !self.bools.is_empty() {
+            write!(f, ", bools={}", fmt_num_set(&self.bools))?;
+        }
+        if !self.bitvecs.is_empty() {
+            write!(f, ", bitvecs={}", fmt_num_set(&self.bitvecs))?;
+        }
+        if !self.specials.is_empty() {
+            let specials: Vec<String> = self
+                .specials
+                .iter()
+                .map(|s| format!("SpecialType({})", s.name()))
+                .collect();
+            write!(f, ", specials=[{{{}}}]", specials.join(", "))?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Builder for a `TypeSet`.
+///
+/// By default a type set contains scalar types only, and no base types.
+/// Passing `Interval::All` for a range selects all the available widths:
+///
+/// ```ignore
+/// // i8 through i32.
+/// TypeSetBuilder::new().ints(8..=32).finish();
+/// // All scalar and vector integer types.
+/// TypeSetBuilder::new().ints(Interval::All).simd_lanes(Interval::All).finish();
+/// ```
+pub struct TypeSetBuilder {
+    ints: Interval,
+    floats: Interval,
+    bools: Interval,
+    bitvecs: Interval,
+    includes_scalars: bool,
+    simd_lanes: Interval,
+    specials: Vec<SpecialType>,
+}
+
+impl Default for TypeSetBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TypeSetBuilder {
+    pub fn new() -> Self {
+        Self {
+            ints: Interval::None,
+            floats: Interval::None,
+            bools: Interval::None,
+            bitvecs: Interval::None,
+            includes_scalars: true,
+            simd_lanes: Interval::None,
+            specials: Vec::new(),
+        }
+    }
+
+    /// Permitted range of scalar integer widths.
+    pub fn ints<T: Into<Interval>>(mut self, interval: T) -> Self {
+        self.ints = interval.into();
+        self
+    }
+
+    /// Permitted range of scalar floating point widths.
+    pub fn floats<T: Into<Interval>>(mut self, interval: T) -> Self {
+        self.floats = interval.into();
+        self
+    }
+
+    /// Permitted range of scalar boolean widths.
+    pub fn bools<T: Into<Interval>>(mut self, interval: T) -> Self {
+        self.bools = interval.into();
+        self
+    }
+
+    /// Permitted range of bitvector widths.
+    pub fn bitvecs<T: Into<Interval>>(mut self, interval: T) -> Self {
+        self.bitvecs = interval.into();
+        self
+    }
+
+    /// Allow the type set to contain scalar types.
+    pub fn includes_scalars(mut self, includes_scalars: bool) -> Self {
+        self.includes_scalars = includes_scalars;
+        self
+    }
+
+    /// Permitted range of vector lane counts.
+    pub fn simd_lanes<T: Into<Interval>>(mut self, interval: T) -> Self {
+        self.simd_lanes = interval.into();
+        self
+    }
+
+    /// Special types to appear in the set.
+    pub fn specials(mut self, specials: Vec<SpecialType>) -> Self {
+        self.specials = specials;
+        self
+    }
+
+    pub fn finish(self) -> TypeSet {
+        let min_lanes = if self.includes_scalars { 1 } else { 2 };
+        let lanes = match self.simd_lanes {
+            Interval::None => num_set![1],
+            ref lanes => lanes.to_set((min_lanes, MAX_LANES)),
+        };
+        let bools = self
+            .bools
+            .to_set((1, MAX_BITS))
+            .into_iter()
+            .filter(|&x| legal_bool(x))
+            .collect();
+        TypeSet::new(
+            lanes,
+            self.ints.to_set((8, MAX_BITS)),
+            self.floats.to_set((32, 64)),
+            bools,
+            self.bitvecs.to_set((1, MAX_BITVEC)),
+            self.specials,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base::types as base_types;
+
+    #[test]
+    fn typeset_display() {
+        let ts = TypeSetBuilder::new().ints(8..=32).finish();
+        assert_eq!(ts.to_string(), "TypeSet(lanes={1}, ints={8, 16, 32})");
+        let ts = TypeSetBuilder::new().bools(Interval::All).finish();
+        assert_eq!(
+            ts.to_string(),
+            "TypeSet(lanes={1}, bools={1, 8, 16, 32, 64})"
+        );
+        let ts = TypeSetBuilder::new()
+            .ints(Interval::All)
+            .simd_lanes(Interval::All)
+            .finish();
+        assert_eq!(
+            ts.to_string(),
+            "TypeSet(lanes={1, 2, 4, 8, 16, 32, 64, 128, 256}, ints={8, 16, 32, 64})"
+        );
+    }
+
+    #[test]
+    fn singleton_typevars() {
+        let i32x4 = LaneType::from(base_types::Int::I32).by(4);
+        let tv = TypeVar::new_singleton(i32x4.clone());
+        assert_eq!(tv.name(), "i32x4");
+        assert_eq!(tv.singleton_type(), Some(i32x4));
+        assert_eq!(tv.free_typevar(), None);
+        assert_eq!(tv.rust_expr(), "ir::types::I32X4");
+
+        let iflags = TypeVar::new_singleton(base_types::Flag::IFlags.into());
+        assert_eq!(iflags.get_typeset().size(), 1);
+        assert_eq!(iflags.free_typevar(), None);
+    }
+
+    #[test]
+    fn derived_typevars() {
+        let x = TypeVar::new(
+            "x",
+            "",
+            TypeSetBuilder::new()
+                .ints(16..=32)
+                .simd_lanes(Interval::All)
+                .finish(),
+        );
+        let half = x.half_width();
+        assert_eq!(half.name(), "half_width(x)");
+        assert!(half.is_derived());
+        assert_eq!(half, x.half_width());
+        assert_eq!(half.free_typevar(), Some(x.clone()));
+        assert_eq!(half.rust_expr(), "x.half_width()");
+        assert_eq!(half.get_typeset().ints, num_set![8, 16]);
+        assert_eq!(x.as_bool().get_typeset().bools, num_set![1, 16, 32]);
+        assert_eq!(x.lane_of().get_typeset().lanes, num_set![1]);
+    }
+
+    #[test]
+    fn forward_images() {
+        let a = TypeSetBuilder::new()
+            .simd_lanes(2..=8)
+            .ints(8..=8)
+            .floats(32..=32)
+            .finish();
+        let b = TypeSetBuilder::new()
+            .simd_lanes(1..=8)
+            .ints(8..=8)
+            .floats(32..=32)
+            .finish();
+        assert_eq!(
+            a.lane_of(),
+            TypeSetBuilder::new().ints(8..=8).floats(32..=32).finish()
+        );
+
+        // Test case with disjoint intervals.
+        let mut c = TypeSetBuilder::new().simd_lanes(2..=8).finish();
+        c.bools = num_set![8, 32];
+        assert_eq!(a.as_bool(), c);
+
+        // For as_bool check b1 is present when 1 \in lanes.
+        let mut d = TypeSetBuilder::new().simd_lanes(1..=8).finish();
+        d.bools = num_set![1, 8, 32];
+        assert_eq!(b.as_bool(), d);
+
+        let lanes = |lo: u16, hi: u16| TypeSetBuilder::new().simd_lanes(lo..=hi).finish();
+        assert_eq!(lanes(1, 32).half_vector(), lanes(1, 16));
+        assert_eq!(lanes(1, 32).double_vector(), lanes(2, 64));
+        assert_eq!(lanes(128, 256).double_vector(), lanes(256, 256));
+
+        let ints = |lo: u16, hi: u16| TypeSetBuilder::new().ints(lo..=hi).finish();
+        assert_eq!(ints(8, 32).half_width(), ints(8, 16));
+        assert_eq!(ints(8, 32).double_width(), ints(16, 64));
+        assert_eq!(ints(32, 64).double_width(), ints(64, 64));
+
+        // Should produce an empty ts.
+        let floats = |lo: u16, hi: u16| TypeSetBuilder::new().floats(lo..=hi).finish();
+        let empty = TypeSetBuilder::new().finish();
+        assert_eq!(floats(32, 32).half_width(), empty);
+        assert_eq!(floats(32, 64).half_width(), floats(32, 32));
+        assert_eq!(floats(32, 32).double_width(), floats(64, 64));
+        assert_eq!(floats(32, 64).double_width(), floats(64, 64));
+
+        // Bools have trickier behavior around b1 (since b2, b4 don't exist).
+        let bools = |lo: u16, hi: u16| TypeSetBuilder::new().bools(lo..=hi).finish();
+        assert_eq!(bools(1, 8).half_width(), empty);
+
+        let mut t = TypeSetBuilder::new().finish();
+        t.bools = num_set![8, 16];
+        assert_eq!(bools(1, 32).half_width(), t);
+
+        // double_width() of bools={1, 8, 16} must not include 2 or 8.
+        t.bools = num_set![16, 32];
+        assert_eq!(bools(1, 16).double_width(), t);
+        assert_eq!(bools(32, 64).double_width(), bools(64, 64));
+    }
+
+    #[test]
+    fn get_singleton() {
+        let int = |bits: u16| TypeSetBuilder::new().ints(bits..=bits).finish();
+        assert_eq!(int(16).get_singleton(), base_types::Int::I16.into());
+        assert_eq!(
+            TypeSetBuilder::new()
+                .floats(64..=64)
+                .finish()
+                .get_singleton(),
+            base_types::Float::F64.into()
+        );
+        assert_eq!(
+            TypeSetBuilder::new().bools(1..=1).finish().get_singleton(),
+            base_types::Bool::B1.into()
+        );
+        assert_eq!(
+            TypeSetBuilder::new()
+                .simd_lanes(4..=4)
+                .ints(32..=32)
+                .finish()
+                .get_singleton(),
+            LaneType::from(base_types::Int::I32).by(4)
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn get_singleton_of_non_singleton() {
+        TypeSetBuilder::new()
+            .simd_lanes(1..=2)
+            .floats(32..=32)
+            .finish()
+            .get_singleton();
+    }
+
+    #[test]
+    fn preimage() {
+        let t = TypeSetBuilder::new().ints(8..=8).floats(32..=32).finish();
+        let empty = TypeSetBuilder::new().finish();
+
+        // LaneOf.
+        assert_eq!(
+            t.preimage(DerivedFunc::LaneOf),
+            TypeSetBuilder::new()
+                .simd_lanes(Interval::All)
+                .ints(8..=8)
+                .floats(32..=32)
+                .finish()
+        );
+        // Inverse of empty set is still empty across LaneOf.
+        assert_eq!(empty.preimage(DerivedFunc::LaneOf), empty);
+
+        // AsBool.
+        let t = TypeSetBuilder::new()
+            .simd_lanes(1..=4)
+            .bools(1..=64)
+            .finish();
+        assert_eq!(
+            t.preimage(DerivedFunc::AsBool),
+            TypeSetBuilder::new()
+                .simd_lanes(1..=4)
+                .ints(Interval::All)
+                .bools(Interval::All)
+                .floats(Interval::All)
+                .finish()
+        );
+
+        // Half/Double Vector.
+        let t = TypeSetBuilder::new().ints(8..=8).finish();
+        let t1 = TypeSetBuilder::new()
+            .simd_lanes(256..=256)
+            .ints(8..=8)
+            .finish();
+        assert_eq!(t.preimage(DerivedFunc::DoubleVector).size(), 0);
+        assert_eq!(t1.preimage(DerivedFunc::HalfVector).size(), 0);
+
+        let t = TypeSetBuilder::new()
+            .simd_lanes(1..=16)
+            .ints(8..=16)
+            .floats(32..=32)
+            .finish();
+        let t1 = TypeSetBuilder::new()
+            .simd_lanes(64..=256)
+            .bools(1..=32)
+            .finish();
+        assert_eq!(
+            t.preimage(DerivedFunc::DoubleVector),
+            TypeSetBuilder::new()
+                .simd_lanes(1..=8)
+                .ints(8..=16)
+                .floats(32..=32)
+                .finish()
+        );
+        assert_eq!(
+            t1.preimage(DerivedFunc::HalfVector),
+            TypeSetBuilder::new()
+                .simd_lanes(128..=256)
+                .bools(1..=32)
+                .finish()
+        );
+
+        // Half/Double Width.
+        let t = TypeSetBuilder::new()
+            .ints(8..=8)
+            .floats(32..=32)
+            .bools(1..=8)
+            .finish();
+        let t1 = TypeSetBuilder::new()
+            .ints(64..=64)
+            .floats(64..=64)
+            .bools(64..=64)
+            .finish();
+        assert_eq!(t.preimage(DerivedFunc::DoubleWidth).size(), 0);
+        assert_eq!(t1.preimage(DerivedFunc::HalfWidth).size(), 0);
+
+        let t = TypeSetBuilder::new()
+            .simd_lanes(1..=16)
+            .ints(8..=16)
+            .floats(32..=64)
+            .finish();
+        let t1 = TypeSetBuilder::new()
+            .simd_lanes(64..=256)
+            .bools(1..=64)
+            .finish();
+        assert_eq!(
+            t.preimage(DerivedFunc::DoubleWidth),
+            TypeSetBuilder::new()
+                .simd_lanes(1..=16)
+                .ints(8..=8)
+                .floats(32..=32)
+                .finish()
+        );
+        assert_eq!(
+            t1.preimage(DerivedFunc::HalfWidth),
+            TypeSetBuilder::new()
+                .simd_lanes(64..=256)
+                .bools(16..=64)
+                .finish()
+        );
+    }
+
+    #[test]
+    fn derived_functions() {
+        let x2 = TypeVar::new(
+            "x2",
+            "i16 and up",
+            TypeSetBuilder::new().ints(16..=64).finish(),
+        );
+        assert_eq!(x2.half_width().to_string(), "`half_width(x2)`");
+        assert_eq!(
+            x2.half_width().double_width().rust_expr(),
+            "x2.half_width().double_width()"
+        );
+
+        let x3 = TypeVar::new(
+            "x3",
+            "up to i32",
+            TypeSetBuilder::new().ints(8..=32).finish(),
+        );
+        assert_eq!(x3.double_width().to_string(), "`double_width(x3)`");
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't double all integer types.")]
+    fn double_width_of_all_ints() {
+        let x = TypeVar::new(
+            "x",
+            "all ints",
+            TypeSetBuilder::new().ints(Interval::All).finish(),
+        );
+        x.double_width();
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't halve all integer types")]
+    fn half_width_of_all_ints() {
+        let x = TypeVar::new(
+            "x",
+            "all ints",
+            TypeSetBuilder::new().ints(Interval::All).finish(),
+        );
+        x.half_width();
+    }
+
+    #[test]
+    fn constrain_derived_typevar() {
+        let x = TypeVar::new(
+            "x",
+            "",
+            TypeSetBuilder::new()
+                .ints(Interval::All)
+                .simd_lanes(2..=256)
+                .finish(),
+        );
+        let y = TypeVar::new(
+            "y",
+            "",
+            TypeSetBuilder::new()
+                .ints(32..=32)
+                .simd_lanes(4..=4)
+                .finish(),
+        );
+
+        // Constraining a derived type variable constrains its base.
+        x.half_vector().constrain_types(&y);
+        assert_eq!(x.get_typeset().lanes, num_set![8]);
+        assert_eq!(x.get_typeset().ints, num_set![32]);
+
+        let copy = x.get_fresh_copy("x1");
+        assert!(copy != x);
+        assert_eq!(copy.get_typeset(), x.get_typeset());
+    }
+
+    #[test]
+    fn stress_constrain_types() {
+        // Get all 43 possible derived vars of length up to 2.
+        let funcs = [
+            DerivedFunc::LaneOf,
+            DerivedFunc::AsBool,
+            DerivedFunc::HalfVector,
+            DerivedFunc::DoubleVector,
+            DerivedFunc::HalfWidth,
+            DerivedFunc::DoubleWidth,
+        ];
+        let mut chains = vec![vec![]];
+        for &f in &funcs {
+            chains.push(vec![f]);
+        }
+        for &f in &funcs {
+            for &g in &funcs {
+                chains.push(vec![f, g]);
+            }
+        }
+        assert_eq!(chains.len(), 43);
+
+        let full_ts = TypeSetBuilder::new()
+            .simd_lanes(Interval::All)
+            .floats(Interval::All)
+            .ints(Interval::All)
+            .bools(Interval::All)
+            .finish();
+
+        // For each pair of derived variables.
+        for i1 in &chains {
+            for i2 in &chains {
+                // Compute the derived sets for each starting with a full
+                // typeset.
+                let ts1 = i1.iter().fold(full_ts.clone(), |ts, &f| ts.image(f));
+                let ts2 = i2.iter().fold(full_ts.clone(), |ts, &f| ts.image(f));
+
+                // Compute intersection.
+                let mut intersect = ts1.clone();
+                intersect.intersect_with(&ts2);
+
+                // Propagate intersections backward.
+                let ts1_src = i1
+                    .iter()
+                    .rev()
+                    .fold(intersect.clone(), |ts, &f| ts.preimage(f));
+                let ts2_src = i2
+                    .iter()
+                    .rev()
+                    .fold(intersect.clone(), |ts, &f| ts.preimage(f));
+
+                // If the intersection or its propagated forms are empty, then
+                // these two variables can never overlap. For example
+                // x.double_vector and x.lane_of.
+                if intersect.size() == 0 || ts1_src.size() == 0 || ts2_src.size() == 0 {
+                    continue;
+                }
+
+                // Should be safe to create derived tvs from ts1_src and
+                // ts2_src.
+                let tv1 = i1
+                    .iter()
+                    .fold(TypeVar::from_typeset(ts1_src), |tv, &f| tv.derived(f));
+                let tv2 = i2
+                    .iter()
+                    .fold(TypeVar::from_typeset(ts2_src), |tv, &f| tv.derived(f));
+
+                // In the absence of AS_BOOL image(preimage(f)) == f so the
+                // typesets of tv1 and tv2 should be exactly intersection.
+                assert!(tv1.get_typeset() == intersect || i1.iter().any(|f| !f.is_bijection()));
+                assert!(tv2.get_typeset() == intersect || i2.iter().any(|f| !f.is_bijection()));
+            }
+        }
+    }
+}
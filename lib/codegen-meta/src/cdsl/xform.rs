@@ -0,0 +1,706 @@
+//! Instruction transformations.
+//!
+//! An instruction transformation (`XForm`) rewrites instructions matching a
+//! source pattern into a destination pattern. Legalization transformations
+//! are collected into `XFormGroup`s which are used to legalize instructions
+//! that can't be encoded directly by a target ISA.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::slice;
+
+use cdsl::ast::{Def, Expr, Var};
+use cdsl::instructions::Instruction;
+use cdsl::type_inference::{ti_rtl, Constraint, TypeEnvRank, TypeEnvironment};
+use cdsl::types::ValueType;
+use cdsl::typevar::{Interval, TypeSetBuilder, TypeVar};
+
+/// Register Transfer Language list.
+///
+/// An RTL object contains a list of register assignments in the form of `Def`
+/// objects. It can represent both a source pattern to be matched, or a
+/// destination pattern to be inserted.
+#[derive(Clone)]
+pub struct Rtl {
+    pub rtl: Vec<Def>,
+}
+
+impl Rtl {
+    pub fn new(rtl: Vec<Def>) -> Self {
+        Self { rtl }
+    }
+}
+
+impl fmt::Display for Rtl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let lines = self
+            .rtl
+            .iter()
+            .map(|def| def.to_string())
+            .collect::<Vec<_>>();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+/// The pattern of a transformation a variable is defined in.
+#[derive(Clone, Copy, PartialEq)]
+enum Context {
+    Src,
+    Dst,
+}
+
+/// The information a transformation keeps about one of its variables.
+///
+/// Variables are classified by where they are defined:
+///
+/// Input values
+///     Uses in the source pattern with no preceding def. These may appear as
+///     inputs in the destination pattern too, but no new inputs can be
+///     introduced.
+/// Output values
+///     Variables that are defined in both the source and destination
+///     pattern. These values may have uses outside the source pattern, and
+///     the destination pattern must compute the same value.
+/// Intermediate values
+///     Values that are defined in the source pattern, but not in the
+///     destination pattern. These may have uses outside the source pattern,
+///     so the defining instruction can't be deleted immediately.
+/// Temporary values
+///     Values that are defined only in the destination pattern.
+pub struct Symbol {
+    pub name: &'static str,
+    /// Index of the `Def` defining this variable in the source pattern.
+    pub src_def: Option<usize>,
+    /// Index of the `Def` defining this variable in the destination pattern.
+    pub dst_def: Option<usize>,
+    /// Type variable representing the type of this variable. Type inference
+    /// may change it to another type variable.
+    type_var: TypeVar,
+    /// The original `typeof_<name>` type variable created for this variable.
+    original_typevar: TypeVar,
+}
+
+impl Symbol {
+    fn new(name: &'static str) -> Self {
+        let type_var = TypeVar::new(
+            format!("typeof_{}", name),
+            format!("Type of the pattern variable `{}`", name),
+            TypeSetBuilder::new()
+                .ints(Interval::All)
+                .floats(Interval::All)
+                .bools(Interval::All)
+                .simd_lanes(Interval::All)
+                .bitvecs(Interval::All)
+                .specials(
+                    ValueType::all_special_types()
+                        .filter_map(|value_type| match value_type {
+                            ValueType::Special(special) => Some(special),
+                            _ => None,
+                        })
+                        .collect(),
+                )
+                .finish(),
+        );
+        Self {
+            name,
+            src_def: None,
+            dst_def: None,
+            original_typevar: type_var.clone(),
+            type_var,
+        }
+    }
+
+    fn get_def(&self, context: Context) -> Option<usize> {
+        match context {
+            Context::Src => self.src_def,
+            Context::Dst => self.dst_def,
+        }
+    }
+
+    fn set_def(&mut self, context: Context, def: usize) {
+        match context {
+            Context::Src => self.src_def = Some(def),
+            Context::Dst => self.dst_def = Some(def),
+        }
+    }
+
+    /// Is this an input value to the source pattern?
+    pub fn is_input(&self) -> bool {
+        self.src_def.is_none() && self.dst_def.is_none()
+    }
+
+    /// Is this an output value, defined in both the source and destination
+    /// patterns?
+    pub fn is_output(&self) -> bool {
+        self.src_def.is_some() && self.dst_def.is_some()
+    }
+
+    /// Is this an intermediate value, defined only in the source pattern?
+    pub fn is_intermediate(&self) -> bool {
+        self.src_def.is_some() && self.dst_def.is_none()
+    }
+
+    /// Is this a temporary value, defined only in the destination pattern?
+    pub fn is_temp(&self) -> bool {
+        self.src_def.is_none() && self.dst_def.is_some()
+    }
+
+    /// Get the rank of this variable's type variable in type inference.
+    pub fn rank(&self) -> TypeEnvRank {
+        if self.is_input() {
+            TypeEnvRank::Input
+        } else if self.is_intermediate() {
+            TypeEnvRank::Intermediate
+        } else if self.is_output() {
+            TypeEnvRank::Output
+        } else {
+            TypeEnvRank::Temp
+        }
+    }
+
+    pub fn type_var(&self) -> &TypeVar {
+        &self.type_var
+    }
+
+    /// Check if this variable has a free type variable.
+    ///
+    /// If not, the type of this variable is computed from the type of another
+    /// variable.
+    pub fn has_free_typevar(&self) -> bool {
+        !self.type_var.is_derived() && self.type_var == self.original_typevar
+    }
+
+    /// Get a Rust expression that computes the type of this variable.
+    ///
+    /// It is assumed that local variables exist corresponding to the free
+    /// type variables.
+    pub fn rust_type(&self) -> String {
+        self.type_var.rust_expr()
+    }
+}
+
+/// The variables of a transformation, in order of appearance.
+pub struct SymbolTable {
+    symbols: Vec<Symbol>,
+    /// Indexes of the variables that were first seen as inputs.
+    inputs: Vec<usize>,
+}
+
+impl SymbolTable {
+    /// Collect the variables used in the `src` and `dst` patterns, and
+    /// verify that they are used consistently.
+    pub fn new(src: &Rtl, dst: &Rtl) -> Self {
+        let mut table = Self {
+            symbols: Vec::new(),
+            inputs: Vec::new(),
+        };
+
+        table.add_rtl(src, Context::Src);
+        let num_src_inputs = table.inputs.len();
+        table.add_rtl(dst, Context::Dst);
+
+        // Check for inconsistently used inputs.
+        for &index in &table.inputs {
+            let symbol = &table.symbols[index];
+            assert!(
+                symbol.is_input(),
+                "'{}' used as both input and def",
+                symbol.name
+            );
+        }
+
+        // Check for spurious inputs in dst.
+        let inputs = table
+            .inputs
+            .iter()
+            .map(|&index| table.symbols[index].name)
+            .collect::<Vec<_>>();
+        assert!(
+            inputs.len() == num_src_inputs,
+            "extra inputs in dst RTL: {:?}",
+            &inputs[num_src_inputs..]
+        );
+
+        table
+    }
+
+    /// Create a table of fresh input variables for all the variables in
+    /// `rtl`.
+    ///
+    /// This is used to perform type inference on a pattern in isolation,
+    /// without the variable ranks of a transformation.
+    pub fn new_inputs(rtl: &Rtl) -> Self {
+        let mut table = Self {
+            symbols: Vec::new(),
+            inputs: Vec::new(),
+        };
+        for def in &rtl.rtl {
+            let vars = def
+                .defs
+                .iter()
+                .cloned()
+                .chain(def.expr.args.iter().filter_map(|arg| match *arg {
+                    Expr::Var(var) => Some(var),
+                    Expr::Literal(_) => None,
+                }));
+            for var in vars {
+                if table.position(var.name).is_none() {
+                    table.symbols.push(Symbol::new(var.name));
+                }
+            }
+        }
+        table
+    }
+
+    fn add_rtl(&mut self, rtl: &Rtl, context: Context) {
+        for (index, def) in rtl.rtl.iter().enumerate() {
+            for var in &def.defs {
+                let symbol = match self.position(var.name) {
+                    Some(pos) => {
+                        let symbol = &mut self.symbols[pos];
+                        assert!(
+                            symbol.get_def(context).is_none(),
+                            "'{}' multiply defined",
+                            var
+                        );
+                        symbol
+                    }
+                    None => {
+                        self.symbols.push(Symbol::new(var.name));
+                        self.symbols.last_mut().unwrap()
+                    }
+                };
+                symbol.set_def(context, index);
+            }
+
+            for arg in &def.expr.args {
+                let var = match *arg {
+                    Expr::Var(var) => var,
+                    Expr::Literal(_) => continue,
+                };
+                match self.position(var.name) {
+                    Some(pos) => {
+                        // The variable must be used consistently as a def or
+                        // input.
+                        let symbol = &self.symbols[pos];
+                        assert!(
+                            symbol.is_input() || symbol.get_def(context).is_some(),
+                            "'{}' used as both input and def",
+                            var
+                        );
+                    }
+                    None => {
+                        // First time use of variable.
+                        self.inputs.push(self.symbols.len());
+                        self.symbols.push(Symbol::new(var.name));
+                    }
+                }
+            }
+        }
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.symbols.iter().position(|s| s.name == name)
+    }
+
+    /// Get the symbol of the variable `var`.
+    pub fn get(&self, var: Var) -> &Symbol {
+        self.lookup(var.name)
+            .unwrap_or_else(|| panic!("unknown variable {}", var))
+    }
+
+    /// Get the symbol named `name`, if any.
+    pub fn lookup(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.iter().find(|s| s.name == name)
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, Symbol> {
+        self.symbols.iter()
+    }
+}
+
+/// An instruction transformation consists of a source and destination
+/// pattern.
+///
+/// Patterns are expressed in *register transfer language* as lists of `Def`
+/// nodes. A pattern may optionally have a list of type constraints, that
+/// additionally limit the set of cases when it applies.
+///
+/// A legalization pattern must have a source pattern containing only a single
+/// instruction.
+pub struct XForm {
+    pub src: Rtl,
+    pub dst: Rtl,
+    pub symbols: SymbolTable,
+    /// The type environment inferred for the variables.
+    pub ti: TypeEnvironment,
+    /// The explicit type constraints of this transformation.
+    pub constraints: Vec<Constraint>,
+}
+
+impl XForm {
+    /// Create a new transformation and infer the types of its variables.
+    ///
+    /// The type variables in `constraints` named `typeof_<name>` refer to the
+    /// type of the variable `<name>`.
+    pub fn new(src: Rtl, dst: Rtl, constraints: Vec<Constraint>) -> Self {
+        let mut symbols = SymbolTable::new(&src, &dst);
+
+        // Perform type inference and cleanup.
+        let mut raw_ti = TypeEnvironment::new();
+        ti_rtl(&src, &symbols, &mut raw_ti).unwrap_or_else(|err| panic!("In src pattern: {}", err));
+        ti_rtl(&dst, &symbols, &mut raw_ti).unwrap_or_else(|err| panic!("In dst pattern: {}", err));
+        raw_ti.normalize();
+        let mut ti = raw_ti.extract();
+
+        let mut inner_constraints = Vec::new();
+        for constraint in constraints {
+            let map: HashMap<TypeVar, TypeVar> = constraint
+                .typevars()
+                .iter()
+                .filter_map(|tv| tv.free_typevar())
+                .filter(|tv| tv.name().starts_with("typeof_"))
+                .map(|tv| {
+                    let symbol = symbols
+                        .lookup(&tv.name()["typeof_".len()..])
+                        .unwrap_or_else(|| panic!("no variable for {}", tv));
+                    (tv, symbol.type_var.clone())
+                })
+                .collect();
+            let inner_constraint = constraint.translate_with_map(&map);
+            inner_constraints.push(inner_constraint.clone());
+            ti.add_constraint(inner_constraint);
+        }
+
+        // Sanity: The set of inferred free type variables should be a subset
+        // of the type variables corresponding to variables appearing in src.
+        for tv in ti.free_typevars() {
+            assert!(
+                symbols
+                    .iter()
+                    .any(|s| !s.is_temp() && s.original_typevar == tv),
+                "Some free vars don't appear in src - {}",
+                tv
+            );
+        }
+
+        // Update the type variables of each variable to their inferred
+        // values.
+        for symbol in &mut symbols.symbols {
+            symbol.type_var = ti.get_equivalent(&symbol.type_var);
+        }
+
+        Self {
+            src,
+            dst,
+            symbols,
+            ti,
+            constraints: inner_constraints,
+        }
+    }
+
+    /// Verify that this is a valid legalization transformation.
+    ///
+    /// - The source pattern must describe a single instruction.
+    /// - All values defined in the source pattern must be defined in the
+    ///   destination pattern.
+    pub fn verify_legalize(&self) {
+        assert!(
+            self.src.rtl.len() == 1,
+            "Legalize needs single instruction."
+        );
+        for &var in &self.src.rtl[0].defs {
+            assert!(
+                self.symbols.get(var).is_output(),
+                "{} not defined in dest pattern",
+                var
+            );
+        }
+    }
+}
+
+/// A group of related transformations.
+pub struct XFormGroup {
+    pub name: &'static str,
+    pub doc: &'static str,
+    /// The target ISA whose instructions are allowed, or `None` for groups
+    /// shared by all ISAs.
+    pub isa: Option<&'static str>,
+    /// A next level group to try if this one doesn't match.
+    pub chain: Option<XFormGroupIndex>,
+    pub xforms: Vec<XForm>,
+    /// Custom legalization functions, in the order they were added.
+    pub custom: Vec<(Instruction, &'static str)>,
+}
+
+impl XFormGroup {
+    /// Get the Rust name of the function implementing this transform.
+    pub fn rust_name(&self) -> String {
+        match self.isa {
+            // This is a function in the same module as the LEGALIZE_ACTIONS
+            // table referring to it.
+            Some(_) => self.name.to_string(),
+            None => format!("::legalizer::{}", self.name),
+        }
+    }
+
+    /// Add a legalization pattern to this group, after its existing ones.
+    ///
+    /// This lets extensions legalize their instructions through the groups
+    /// already used by the CPU modes.
+    pub fn legalize(&mut self, src: Def, dst: Vec<Def>) {
+        let xform = XForm::new(Rtl::new(vec![src]), Rtl::new(dst), Vec::new());
+        xform.verify_legalize();
+        self.xforms.push(xform);
+    }
+}
+
+impl fmt::Display for XFormGroup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.isa {
+            Some(isa) => write!(f, "{}.{}", isa, self.name),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+pub struct XFormGroupBuilder {
+    name: &'static str,
+    doc: &'static str,
+    isa: Option<&'static str>,
+    chain: Option<XFormGroupIndex>,
+    xforms: Vec<XForm>,
+    custom: Vec<(Instruction, &'static str)>,
+}
+
+impl XFormGroupBuilder {
+    pub fn new(name: &'static str, doc: &'static str) -> Self {
+        Self {
+            name,
+            doc,
+            isa: None,
+            chain: None,
+            xforms: Vec::new(),
+            custom: Vec::new(),
+        }
+    }
+
+    /// Restrict this group to the target ISA `isa`, allowing its
+    /// instructions to be used.
+    pub fn isa(mut self, isa: &'static str) -> Self {
+        self.isa = Some(isa);
+        self
+    }
+
+    /// Set the group to try if this one doesn't match.
+    pub fn chain(mut self, chain: XFormGroupIndex) -> Self {
+        self.chain = Some(chain);
+        self
+    }
+
+    /// Add a legalization pattern to this group, rewriting the single
+    /// instruction `src` into the `dst` instructions.
+    pub fn legalize(&mut self, src: Def, dst: Vec<Def>) {
+        let xform = XForm::new(Rtl::new(vec![src]), Rtl::new(dst), Vec::new());
+        xform.verify_legalize();
+        self.xforms.push(xform);
+    }
+
+    /// Add a custom legalization action for `inst`.
+    ///
+    /// The `funcname` parameter is the fully qualified name of a Rust
+    /// function which takes the same arguments as the `isa::Legalize`
+    /// actions. The custom function will be called to legalize `inst`. It
+    /// returns false if it left `inst` unchanged, and then the chained group is
+    /// tried.
+    pub fn custom_legalize(&mut self, inst: &Instruction, funcname: &'static str) {
+        assert!(
+            self.custom.iter().all(|&(ref i, _)| i.name != inst.name),
+            "Duplicate custom_legalize"
+        );
+        self.custom.push((inst.clone(), funcname));
+    }
+
+    pub fn finish_and_add_to(self, groups: &mut XFormGroups) -> XFormGroupIndex {
+        groups.add(XFormGroup {
+            name: self.name,
+            doc: self.doc,
+            isa: self.isa,
+            chain: self.chain,
+            xforms: self.xforms,
+            custom: self.custom,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct XFormGroupIndex(usize);
+
+/// The registry of all the transformation groups, shared and ISA-specific.
+pub struct XFormGroups {
+    groups: Vec<XFormGroup>,
+}
+
+impl Default for XFormGroups {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl XFormGroups {
+    pub fn new() -> Self {
+        Self { groups: Vec::new() }
+    }
+
+    fn add(&mut self, group: XFormGroup) -> XFormGroupIndex {
+        assert!(
+            self.groups.iter().all(|g| g.name != group.name),
+            "duplicate transformation group {}",
+            group.name
+        );
+        self.groups.push(group);
+        XFormGroupIndex(self.groups.len() - 1)
+    }
+
+    pub fn get(&self, index: XFormGroupIndex) -> &XFormGroup {
+        &self.groups[index.0]
+    }
+
+    pub fn get_mut(&mut self, index: XFormGroupIndex) -> &mut XFormGroup {
+        &mut self.groups[index.0]
+    }
+
+    pub fn by_name(&self, name: &str) -> XFormGroupIndex {
+        let pos = self
+            .groups
+            .iter()
+            .position(|g| g.name == name)
+            .unwrap_or_else(|| panic!("unknown transformation group {}", name));
+        XFormGroupIndex(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base;
+    use base::types::Bool;
+    use cdsl::ast::{var, Literal};
+
+    #[test]
+    fn macro_pattern() {
+        let shared = base::define();
+        let iadd = shared.instructions.by_name("iadd");
+        let iadd_imm = shared.instructions.by_name("iadd_imm");
+        let iconst = shared.instructions.by_name("iconst");
+        let (a, c, x, y) = (var("a"), var("c"), var("x"), var("y"));
+
+        let xform = XForm::new(
+            Rtl::new(vec![def!(a = iadd_imm(x, y))]),
+            Rtl::new(vec![def!(c = iconst(y)), def!(a = iadd(x, c))]),
+            Vec::new(),
+        );
+        xform.verify_legalize();
+
+        let a = xform.symbols.get(a);
+        assert!(a.is_output());
+        assert!(xform.symbols.get(c).is_temp());
+        assert!(xform.symbols.get(x).is_input());
+        assert!(xform.symbols.get(y).is_input());
+
+        // The type of `a` is the same as the type of `x`.
+        assert!(xform.symbols.get(x).has_free_typevar());
+        assert!(!a.has_free_typevar());
+        assert_eq!(a.rust_type(), "typeof_x");
+    }
+
+    #[test]
+    #[should_panic(expected = "'a' used as both input and def")]
+    fn def_input() {
+        // Src pattern has a def which is an input in dst.
+        let shared = base::define();
+        let iadd_imm = shared.instructions.by_name("iadd_imm");
+        let one = Literal::constant(&shared.imm.imm64, 1);
+        let (a, x, y) = (var("a"), var("x"), var("y"));
+        XForm::new(
+            Rtl::new(vec![def!(a = iadd_imm(x, one))]),
+            Rtl::new(vec![def!(y = iadd_imm(a, one))]),
+            Vec::new(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "'a' used as both input and def")]
+    fn input_def() {
+        // Converse of the above.
+        let shared = base::define();
+        let iadd_imm = shared.instructions.by_name("iadd_imm");
+        let one = Literal::constant(&shared.imm.imm64, 1);
+        let (a, x, y) = (var("a"), var("x"), var("y"));
+        XForm::new(
+            Rtl::new(vec![def!(y = iadd_imm(a, one))]),
+            Rtl::new(vec![def!(a = iadd_imm(x, one))]),
+            Vec::new(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "extra inputs in dst")]
+    fn extra_input() {
+        let shared = base::define();
+        let iadd = shared.instructions.by_name("iadd");
+        let iadd_imm = shared.instructions.by_name("iadd_imm");
+        let one = Literal::constant(&shared.imm.imm64, 1);
+        let (a, x, y) = (var("a"), var("x"), var("y"));
+        XForm::new(
+            Rtl::new(vec![def!(a = iadd_imm(x, one))]),
+            Rtl::new(vec![def!(a = iadd(x, y))]),
+            Vec::new(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "'a' multiply defined")]
+    fn double_def() {
+        let shared = base::define();
+        let iadd = shared.instructions.by_name("iadd");
+        let iadd_imm = shared.instructions.by_name("iadd_imm");
+        let one = Literal::constant(&shared.imm.imm64, 1);
+        let (a, x, y) = (var("a"), var("x"), var("y"));
+        XForm::new(
+            Rtl::new(vec![def!(a = iadd_imm(x, one)), def!(a = iadd(x, y))]),
+            Rtl::new(vec![def!(a = iadd(x, y))]),
+            Vec::new(),
+        );
+    }
+
+    #[test]
+    fn iadd_cout() {
+        let shared = base::define();
+        let iadd = shared.instructions.by_name("iadd");
+        let iadd_cout = shared.instructions.by_name("iadd_cout");
+        let icmp = shared.instructions.by_name("icmp");
+        let ult = Literal::enumerator_for(&shared.imm.intcc, "ult");
+        let (v0, v1, v2, v3) = (var("v0"), var("v1"), var("v2"), var("v3"));
+
+        let xform = XForm::new(
+            Rtl::new(vec![def!((v0, v1) = iadd_cout(v2, v3))]),
+            Rtl::new(vec![def!(v0 = iadd(v2, v3)), def!(v1 = icmp(ult, v0, v2))]),
+            Vec::new(),
+        );
+
+        let itype = TypeSetBuilder::new()
+            .ints(Interval::All)
+            .simd_lanes(1..=1)
+            .finish();
+        let v2 = xform.symbols.get(v2).type_var();
+        assert_eq!(v2.get_typeset(), itype);
+        assert_eq!(xform.symbols.get(v0).type_var(), v2);
+        assert_eq!(xform.symbols.get(v3).type_var(), v2);
+        assert_eq!(
+            xform.symbols.get(v1).type_var().singleton_type(),
+            Some(Bool::B1.into())
+        );
+        assert!(xform.constraints.is_empty());
+    }
+}
@@ -0,0 +1,69 @@
+//! Generate constant hash tables.
+//!
+//! The `constant_hash` module can generate constant pre-populated hash tables.
+//! We don't attempt perfect hashing, but simply generate an open addressed
+//! quadratically probed hash table.
+
+use cdsl::next_power_of_two;
+
+/// Compute a primitive hash of a string.
+///
+/// This must match `simple_hash` in `lib/codegen/src/constant_hash.rs`.
+pub fn simple_hash(s: &str) -> usize {
+    let mut h: u32 = 5381;
+    for c in s.chars() {
+        h = (h ^ c as u32).wrapping_add(h.rotate_right(6));
+    }
+    h as usize
+}
+
+/// Compute an open addressed, quadratically probed hash table containing
+/// `items`. The returned table is a list containing the elements of the
+/// iterable `items` and `None` in unused slots.
+pub fn generate_table<'a, T, I: Iterator<Item = &'a T>, H: Fn(&T) -> usize>(
+    items: I,
+    num_items: usize,
+    hash_function: H,
+) -> Vec<Option<&'a T>> {
+    // Table size must be a power of two. Aim for >20% unused slots.
+    let size = next_power_of_two((1.20 * num_items as f64) as usize);
+    let mut table = vec![None; size];
+
+    for i in items {
+        let mut h = hash_function(i) % size;
+        let mut s = 0;
+        while table[h].is_some() {
+            s += 1;
+            h = (h + s) % size;
+        }
+        table[h] = Some(i);
+    }
+
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_table, simple_hash};
+
+    #[test]
+    fn basic() {
+        assert_eq!(simple_hash("Hello"), 0x2fa70c01);
+        assert_eq!(simple_hash("world"), 0x5b0c31d5);
+    }
+
+    #[test]
+    fn test_generate_table() {
+        let v = ["Hello".to_string(), "world".to_string()];
+        let table = generate_table(v.iter(), v.len(), |s| simple_hash(s));
+        assert_eq!(
+            table,
+            vec![
+                None,
+                Some(&"Hello".to_string()),
+                Some(&"world".to_string()),
+                None
+            ]
+        );
+    }
+}
@@ -0,0 +1,288 @@
+//! Differential check between the Python and the Rust meta generators.
+//!
+//! While the port from the Python meta language is in progress, both
+//! pipelines are run into separate directories, and every file generated by
+//! the Python scripts is compared with its `new_` prefixed counterpart
+//! generated by this crate.
+//!
+//! Lines are normalized before being compared: trailing whitespace and blank
+//! lines are ignored. Files that only differ in comments are reported as
+//! cosmetic differences; any other difference, such as a type getting a
+//! different number, is a semantic difference.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use error;
+
+/// Prefix of the files generated by this crate during the check.
+const RUST_PREFIX: &str = "new_";
+
+/// The result of comparing a file generated by Python with its Rust
+/// counterpart.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    /// Both files are the same, after normalization.
+    Same,
+    /// Only the comments differ.
+    Cosmetic,
+    /// The generated code differs.
+    Semantic,
+    /// The Rust generator doesn't emit this file.
+    Missing,
+}
+
+/// Comparison report for a single generated file.
+#[derive(Debug)]
+pub struct FileDiff {
+    /// Name of the file generated by the Python scripts.
+    pub file_name: String,
+    pub outcome: Outcome,
+    /// The differing lines, prefixed with `-` for the Python version and `+`
+    /// for the Rust version.
+    pub diff: Vec<String>,
+}
+
+impl FileDiff {
+    /// Does this difference make the check fail?
+    pub fn is_failure(&self) -> bool {
+        match self.outcome {
+            Outcome::Same | Outcome::Cosmetic => false,
+            Outcome::Semantic | Outcome::Missing => true,
+        }
+    }
+}
+
+impl fmt::Display for FileDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = match self.outcome {
+            Outcome::Same => "same",
+            Outcome::Cosmetic => "comments differ",
+            Outcome::Semantic => "code differs",
+            Outcome::Missing => "not generated by the Rust meta crate",
+        };
+        writeln!(
+            f,
+            "{} vs {}{}: {}",
+            self.file_name, RUST_PREFIX, self.file_name, description
+        )?;
+        for line in &self.diff {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Normalize the contents of a generated file into the list of lines to be
+/// compared.
+fn normalize(contents: &str) -> Vec<&str> {
+    contents
+        .lines()
+        .map(|line| line.trim_right())
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+fn is_comment(line: &str) -> bool {
+    line.trim_left().starts_with("//")
+}
+
+/// Compute a line diff between `old` and `new`, using the longest common
+/// subsequence of the lines that are not part of a common prefix or suffix.
+fn diff_lines(old: &[&str], new: &[&str]) -> Vec<String> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let (old, new) = (&old[prefix..], &new[prefix..]);
+    let suffix = old
+        .iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old, new) = (&old[..old.len() - suffix], &new[..new.len() - suffix]);
+
+    // lcs[i][j] is the length of the longest common subsequence of old[i..]
+    // and new[j..].
+    let mut lcs = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            diff.push(format!("-{}", old[i]));
+            i += 1;
+        } else {
+            diff.push(format!("+{}", new[j]));
+            j += 1;
+        }
+    }
+    diff
+}
+
+/// Compare the contents of a file generated by Python with the contents of
+/// its Rust counterpart.
+pub fn compare_contents(file_name: &str, python: &str, rust: &str) -> FileDiff {
+    let diff = diff_lines(&normalize(python), &normalize(rust));
+    let outcome = if diff.is_empty() {
+        Outcome::Same
+    } else if diff.iter().all(|line| is_comment(&line[1..])) {
+        Outcome::Cosmetic
+    } else {
+        Outcome::Semantic
+    };
+    FileDiff {
+        file_name: file_name.to_string(),
+        outcome,
+        diff,
+    }
+}
+
+/// Compare every file of `python_dir` with its `new_` prefixed counterpart in
+/// `rust_dir`.
+///
+/// The reports are sorted by file name.
+pub fn compare_dirs(python_dir: &Path, rust_dir: &Path) -> Result<Vec<FileDiff>, error::Error> {
+    let mut file_names = Vec::new();
+    for entry in fs::read_dir(python_dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            file_names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    file_names.sort();
+
+    let mut reports = Vec::new();
+    for file_name in file_names {
+        let rust_path = rust_dir.join(format!("{}{}", RUST_PREFIX, file_name));
+        if !rust_path.is_file() {
+            reports.push(FileDiff {
+                file_name,
+                outcome: Outcome::Missing,
+                diff: Vec::new(),
+            });
+            continue;
+        }
+        let python = fs::read_to_string(python_dir.join(&file_name))?;
+        let rust = fs::read_to_string(rust_path)?;
+        reports.push(compare_contents(&file_name, &python, &rust));
+    }
+    Ok(reports)
+}
+
+/// Find a Python interpreter in the path.
+fn identify_python() -> Option<&'static str> {
+    ["python", "python3", "python2.7"]
+        .iter()
+        .cloned()
+        .find(|python| {
+            process::Command::new(python)
+                .arg("--version")
+                .output()
+                .is_ok()
+        })
+}
+
+/// Run the Python meta language scripts in `python_meta_dir` (usually
+/// `lib/codegen/meta`), generating files into `out_dir`.
+pub fn run_python(python_meta_dir: &Path, out_dir: &Path) -> Result<(), error::Error> {
+    let python = identify_python()
+        .ok_or_else(|| error::Error::with_msg("no Python interpreter was found"))?;
+    let output = process::Command::new(python)
+        .arg("-B")
+        .arg(python_meta_dir.join("build.py"))
+        .arg("--out-dir")
+        .arg(out_dir)
+        .output()?;
+    if !output.status.success() {
+        return Err(error::Error::with_msg(format!(
+            "the Python meta language scripts failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+    Ok(())
+}
+
+/// Run both the Python and the Rust generators into subdirectories of
+/// `work_dir`, and compare their outputs.
+pub fn check(python_meta_dir: &Path, work_dir: &Path) -> Result<Vec<FileDiff>, error::Error> {
+    let python_dir = work_dir.join("python");
+    let rust_dir = work_dir.join("rust");
+    fs::create_dir_all(&python_dir)?;
+    fs::create_dir_all(&rust_dir)?;
+
+    run_python(python_meta_dir, &python_dir)?;
+    ::generate(RUST_PREFIX, rust_dir.to_str().unwrap())?;
+
+    compare_dirs(&python_dir, &rust_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::path::PathBuf;
+
+    #[test]
+    fn normalized_comparison() {
+        let python = "const A: u8 = 1;\n\n// Comment.  \nconst B: u8 = 2;\n";
+
+        let same = compare_contents(
+            "a.rs",
+            python,
+            "const A: u8 = 1;\n// Comment.\nconst B: u8 = 2;",
+        );
+        assert_eq!(same.outcome, Outcome::Same);
+        assert!(same.diff.is_empty());
+
+        let cosmetic = compare_contents("a.rs", python, "const A: u8 = 1;\nconst B: u8 = 2;\n");
+        assert_eq!(cosmetic.outcome, Outcome::Cosmetic);
+        assert_eq!(cosmetic.diff, vec!["-// Comment."]);
+
+        let renumbered = compare_contents(
+            "a.rs",
+            python,
+            "const A: u8 = 2;\n// Comment.\nconst B: u8 = 2;\n",
+        );
+        assert_eq!(renumbered.outcome, Outcome::Semantic);
+        assert_eq!(
+            renumbered.diff,
+            vec!["-const A: u8 = 1;", "+const A: u8 = 2;"]
+        );
+        assert!(renumbered.is_failure());
+    }
+
+    #[test]
+    fn python_and_rust_generators_agree() {
+        // This check needs a Python interpreter. It fails rather than being skipped without one,
+        // so that a passing test run always means the generators were compared.
+        let python_meta_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../codegen/meta");
+        let work_dir = env::temp_dir().join(format!("cranelift-meta-diff-{}", process::id()));
+        let reports = check(&python_meta_dir, &work_dir)
+            .unwrap_or_else(|err| panic!("can't run the differential check: {}", err));
+        fs::remove_dir_all(&work_dir).unwrap();
+
+        let failures: Vec<String> = reports
+            .iter()
+            .filter(|report| report.outcome != Outcome::Same)
+            .map(|report| report.to_string())
+            .collect();
+        assert!(
+            reports.iter().all(|report| !report.is_failure()),
+            "the Python and Rust generators disagree:\n{}",
+            failures.join("\n")
+        );
+    }
+}
@@ -0,0 +1,223 @@
+//! Extending the meta definitions from out-of-tree crates.
+//!
+//! A crate embedding Cranelift can add its own instructions, such as a
+//! safepoint poll for its virtual machine, without forking the base
+//! instruction set. It implements the `Extension` trait with the DSL of the
+//! `cdsl` module, and returns it from its own version of the
+//! `cranelift-codegen-extensions` crate, which the build script of
+//! `cranelift-codegen` passes to `generate_with_extensions`.
+//!
+//! The instructions of the extensions are numbered after the base and the
+//! ISA-specific instructions, so the opcodes of the base instruction set
+//! don't depend on the extensions in use.
+
+use base;
+use cdsl::instructions::InstructionGroup;
+use cdsl::isa::TargetIsa;
+
+/// An out-of-tree extension of the instruction set.
+pub trait Extension {
+    /// Define the instructions of this extension, using the formats,
+    /// immediates and entities of the base definitions.
+    fn define_instructions(&self, shared_defs: &base::Definitions) -> InstructionGroup;
+
+    /// Define the legalizations of the instructions of this extension, by
+    /// adding transformation groups to `shared_defs` or patterns to its
+    /// existing groups.
+    fn define_legalizations(
+        &self,
+        _shared_defs: &mut base::Definitions,
+        _instructions: &InstructionGroup,
+    ) {
+    }
+
+    /// Add encodings of the instructions of this extension to the CPU modes
+    /// of `isa`, along with the recipes they need.
+    fn extend_isa(
+        &self,
+        _isa: &mut TargetIsa,
+        _shared_defs: &base::Definitions,
+        _instructions: &InstructionGroup,
+    ) {
+    }
+}
+
+/// Add the definitions of `extensions` to the base definitions and to the
+/// target ISAs, in order.
+pub fn apply(
+    extensions: &[&dyn Extension],
+    shared_defs: &mut base::Definitions,
+    isas: &mut [TargetIsa],
+) {
+    for extension in extensions {
+        let instructions = extension.define_instructions(shared_defs);
+        for inst in instructions.iter() {
+            assert!(
+                shared_defs
+                    .instruction_groups(isas)
+                    .iter()
+                    .all(|group| group.iter().all(|other| other.name != inst.name)),
+                "instruction {} of extension {} is already defined",
+                inst.name,
+                instructions.name
+            );
+        }
+
+        extension.define_legalizations(shared_defs, &instructions);
+        for isa in isas.iter_mut() {
+            extension.extend_isa(isa, shared_defs, &instructions);
+        }
+        shared_defs.extension_instructions.push(instructions);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base::settings;
+    use cdsl::ast::var;
+    use cdsl::encodings::EncodingBuilder;
+    use cdsl::instructions::{InstructionBuilder as Inst, InstructionGroupBuilder};
+    use cdsl::operands::Operand;
+    use cdsl::recipes::EncodingRecipeBuilder;
+    use cdsl::typevar::{Interval, TypeSetBuilder, TypeVar};
+    use isa;
+
+    /// A virtual machine with a safepoint poll and a three-way addition.
+    struct VmExtension;
+
+    impl Extension for VmExtension {
+        fn define_instructions(&self, shared_defs: &base::Definitions) -> InstructionGroup {
+            let mut ig = InstructionGroupBuilder::new(
+                "vm",
+                "Virtual machine instructions",
+                &shared_defs.format_registry,
+            );
+
+            ig.push(
+                Inst::new("safepoint_poll", "Poll for a pending safepoint.")
+                    .other_side_effects(true),
+            );
+
+            let int = &TypeVar::new(
+                "Int",
+                "A scalar integer type",
+                TypeSetBuilder::new().ints(Interval::All).finish(),
+            );
+            let x = &Operand::new("x", int);
+            let y = &Operand::new("y", int);
+            let z = &Operand::new("z", int);
+            let a = &Operand::new("a", int);
+            ig.push(
+                Inst::new("iadd3", "Wrapping addition of three integers.")
+                    .operands_in(vec![x, y, z])
+                    .operands_out(vec![a]),
+            );
+
+            ig.finish()
+        }
+
+        fn define_legalizations(
+            &self,
+            shared_defs: &mut base::Definitions,
+            instructions: &InstructionGroup,
+        ) {
+            let iadd = shared_defs.instructions.by_name("iadd");
+            let iadd3 = instructions.by_name("iadd3");
+            let (a, t, x, y, z) = (var("a"), var("t"), var("x"), var("y"), var("z"));
+
+            let expand = shared_defs.transform_groups.by_name("expand");
+            shared_defs.transform_groups.get_mut(expand).legalize(
+                def!(a = iadd3(x, y, z)),
+                vec![def!(t = iadd(x, y)), def!(a = iadd(t, z))],
+            );
+        }
+
+        fn extend_isa(
+            &self,
+            isa: &mut TargetIsa,
+            shared_defs: &base::Definitions,
+            instructions: &InstructionGroup,
+        ) {
+            if isa.name != "x86" {
+                return;
+            }
+            let formats = &shared_defs.format_registry;
+            let recipe = isa.add_recipe(
+                EncodingRecipeBuilder::new("vm_poll", formats.by_name("NullAry"), 1)
+                    .emit("sink.put1(0x90);")
+                    .build(formats),
+            );
+            let safepoint_poll = instructions.by_name("safepoint_poll");
+            let encoding =
+                EncodingBuilder::new(safepoint_poll, recipe, 0).build(&isa.recipes, formats);
+            isa.cpu_mode_mut("I64").add_encodings(vec![encoding]);
+        }
+    }
+
+    fn instruction_names(shared_defs: &base::Definitions, isas: &[TargetIsa]) -> Vec<&'static str> {
+        shared_defs
+            .instruction_groups(isas)
+            .iter()
+            .flat_map(|group| group.iter().map(|inst| inst.name))
+            .collect()
+    }
+
+    fn recipe_names(isa: &TargetIsa) -> Vec<String> {
+        isa.recipes
+            .iter()
+            .map(|recipe| recipe.name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn extension_opcodes_come_last() {
+        let shared_settings = settings::generate();
+        let mut shared_defs = base::define();
+        let mut isas = isa::define_all(&shared_settings, &mut shared_defs);
+        let names = instruction_names(&shared_defs, &isas);
+        let recipes = recipe_names(&isas[1]);
+
+        apply(&[&VmExtension], &mut shared_defs, &mut isas);
+
+        let extended_names = instruction_names(&shared_defs, &isas);
+        assert_eq!(&extended_names[..names.len()], &names[..]);
+        assert_eq!(&extended_names[names.len()..], &["safepoint_poll", "iadd3"]);
+
+        // The existing recipes keep their numbers.
+        let x86 = &isas[1];
+        let extended_recipes = recipe_names(x86);
+        assert_eq!(&extended_recipes[..recipes.len()], &recipes[..]);
+        assert_eq!(&extended_recipes[recipes.len()..], &["vm_poll".to_string()]);
+        let encoding = x86.cpu_modes[0].encodings().last().unwrap();
+        assert_eq!(encoding.inst.name, "safepoint_poll");
+
+        let expand = shared_defs.transform_groups.by_name("expand");
+        let xform = shared_defs
+            .transform_groups
+            .get(expand)
+            .xforms
+            .last()
+            .unwrap();
+        assert_eq!(xform.src.rtl[0].expr.inst.name, "iadd3");
+    }
+
+    #[test]
+    #[should_panic(expected = "instruction iadd of extension dup is already defined")]
+    fn duplicate_instruction() {
+        struct Duplicate;
+        impl Extension for Duplicate {
+            fn define_instructions(&self, shared_defs: &base::Definitions) -> InstructionGroup {
+                let mut ig =
+                    InstructionGroupBuilder::new("dup", "Duplicate", &shared_defs.format_registry);
+                ig.push(Inst::new("iadd", "Not the base iadd."));
+                ig.finish()
+            }
+        }
+
+        let shared_settings = settings::generate();
+        let mut shared_defs = base::define();
+        let mut isas = isa::define_all(&shared_settings, &mut shared_defs);
+        apply(&[&Duplicate], &mut shared_defs, &mut isas);
+    }
+}
@@ -0,0 +1,214 @@
+//! Generate binary emission code for each ISA.
+
+use cdsl::formats::FormatRegistry;
+use cdsl::isa::TargetIsa;
+use cdsl::recipes::{EncodingRecipe, OperandConstraint};
+use error;
+use srcgen::Formatter;
+
+/// Generate code to handle a single recipe.
+///
+/// - Unpack the instruction data, knowing the format.
+/// - Determine register locations for operands with register constraints.
+/// - Determine stack slot locations for operands with stack constraints.
+/// - Call hand-written code for the actual emission.
+fn gen_recipe(formats: &FormatRegistry, recipe: &EncodingRecipe, fmt: &mut Formatter) {
+    let iform = formats.get(recipe.format);
+    let nvops = iform.num_value_operands;
+    let want_args = recipe.operands_in.iter().any(|c| match *c {
+        OperandConstraint::RegClass(_) | OperandConstraint::Stack(_) => true,
+        _ => false,
+    });
+    assert!(!want_args || nvops > 0 || iform.has_value_list);
+    let want_outs = recipe.operands_out.iter().any(|c| match *c {
+        OperandConstraint::RegClass(_) | OperandConstraint::Stack(_) => true,
+        _ => false,
+    });
+
+    // Regmove instructions get special treatment.
+    let is_regmove = ["RegMove", "RegSpill", "RegFill"].contains(&iform.name);
+
+    // First unpack the instruction.
+    fmt.line(&format!("if let InstructionData::{} {{", iform.name));
+    fmt.indent(|fmt| {
+        fmt.line("opcode,");
+        for field in &iform.imm_fields {
+            fmt.line(&format!("{},", field.member));
+        }
+        if want_args {
+            if iform.has_value_list || nvops > 1 {
+                fmt.line("ref args,");
+            } else {
+                fmt.line("arg,");
+            }
+        }
+        fmt.line("..");
+        fmt.outdented_line("} = func.dfg[inst] {");
+
+        // Pass recipe arguments in this order: inputs, imm_fields, outputs.
+        let mut args = String::new();
+
+        // Normalize to an `args` array.
+        if want_args && !is_regmove {
+            if iform.has_value_list {
+                fmt.line("let args = args.as_slice(&func.dfg.value_lists);");
+            } else if nvops == 1 {
+                fmt.line("let args = [arg];");
+            }
+            args += &unwrap_values(&recipe.operands_in, "in", "args", fmt);
+        }
+
+        for field in &iform.imm_fields {
+            args += &format!(", {}", field.member);
+        }
+
+        // Unwrap interesting output arguments.
+        if want_outs {
+            if recipe.operands_out.len() == 1 {
+                fmt.line("let results = [func.dfg.first_result(inst)];")
+            } else {
+                fmt.line("let results = func.dfg.inst_results(inst);");
+            }
+            args += &unwrap_values(&recipe.operands_out, "out", "results", fmt);
+        }
+
+        // Special handling for regmove instructions. Update the register
+        // diversion tracker.
+        match iform.name {
+            "RegMove" => fmt.line("divert.regmove(arg, src, dst);"),
+            "RegSpill" => fmt.line("divert.regspill(arg, src, dst);"),
+            "RegFill" => fmt.line("divert.regfill(arg, src, dst);"),
+            _ => {}
+        }
+
+        // Call hand-written code. If the recipe contains a code snippet, use
+        // that. Otherwise call a recipe function in the target ISA's binemit
+        // module.
+        match recipe.emit {
+            Some(ref emit) => {
+                fmt.multi_line(emit);
+                fmt.line("return;");
+            }
+            None => fmt.line(&format!(
+                "return recipe_{}(func, inst, sink, bits{});",
+                recipe.name.to_lowercase(),
+                args
+            )),
+        }
+    });
+    fmt.line("}");
+}
+
+/// Emit code that unwraps values living in registers or stack slots.
+///
+/// `prefix` is used for the generated local variables, and `values` is the
+/// name of the slice containing the values to be unwrapped.
+///
+/// Return a comma separated list of the generated variables.
+fn unwrap_values(
+    constraints: &[OperandConstraint],
+    prefix: &str,
+    values: &str,
+    fmt: &mut Formatter,
+) -> String {
+    let mut varlist = String::new();
+    for (i, constraint) in constraints.iter().enumerate() {
+        match *constraint {
+            OperandConstraint::RegClass(_) => {
+                let v = format!("{}_reg{}", prefix, i);
+                varlist += &format!(", {}", v);
+                fmt.line(&format!(
+                    "let {} = divert.reg({}[{}], &func.locations);",
+                    v, values, i
+                ));
+            }
+            OperandConstraint::Stack(stack) => {
+                let v = format!("{}_stk{}", prefix, i);
+                varlist += &format!(", {}", v);
+                fmt.line(&format!("let {} = StackRef::masked(", v));
+                fmt.indent(|fmt| {
+                    fmt.line(&format!(
+                        "divert.stack({}[{}], &func.locations),",
+                        values, i
+                    ));
+                    fmt.line(&format!("{},", stack.stack_base_mask()));
+                    fmt.line("&func.stack_slots,");
+                });
+                fmt.line(").unwrap();");
+            }
+            _ => {}
+        }
+    }
+    varlist
+}
+
+/// Generate Binary emission code for `isa`.
+fn gen_isa(formats: &FormatRegistry, isa: &TargetIsa, fmt: &mut Formatter) {
+    fmt.doc_comment(&format!(
+        "Emit binary machine code for `inst` for the {} ISA.",
+        isa.name
+    ));
+
+    if isa.recipes.is_empty() {
+        // No encoding recipes: Emit a stub.
+        fmt.line("pub fn emit_inst<CS: CodeSink + ?Sized>(");
+        fmt.indent(|fmt| {
+            fmt.line("func: &Function,");
+            fmt.line("inst: Inst,");
+            fmt.line("_divert: &mut RegDiversions,");
+            fmt.line("_sink: &mut CS,");
+        });
+        fmt.line(") {");
+        fmt.indent(|fmt| fmt.line("bad_encoding(func, inst)"));
+        fmt.line("}");
+        return;
+    }
+
+    fmt.line("#[allow(unused_variables, unreachable_code)]");
+    fmt.line("pub fn emit_inst<CS: CodeSink + ?Sized>(");
+    fmt.indent(|fmt| {
+        fmt.line("func: &Function,");
+        fmt.line("inst: Inst,");
+        fmt.line("divert: &mut RegDiversions,");
+        fmt.line("sink: &mut CS,");
+    });
+    fmt.line(") {");
+    fmt.indent(|fmt| {
+        fmt.line("let encoding = func.encodings[inst];");
+        fmt.line("let bits = encoding.bits();");
+        fmt.line("match func.encodings[inst].recipe() {");
+        fmt.indent(|fmt| {
+            for (i, recipe) in isa.recipes.iter().enumerate() {
+                fmt.comment(&format!("Recipe {}", recipe.name));
+                fmt.line(&format!("{} => {{", i));
+                fmt.indent(|fmt| gen_recipe(formats, recipe, fmt));
+                fmt.line("}");
+            }
+            fmt.line("_ => {},");
+        });
+        fmt.line("}");
+
+        // Allow for un-encoded ghost instructions.
+        // Verifier checks the details.
+        fmt.line("if encoding.is_legal() {");
+        fmt.indent(|fmt| fmt.line("bad_encoding(func, inst);"));
+        fmt.line("}");
+    });
+    fmt.line("}");
+}
+
+/// Generate the `<filename_prefix>-<isa>.rs` binary emission code for each
+/// ISA.
+pub fn generate(
+    formats: &FormatRegistry,
+    isas: &[TargetIsa],
+    filename_prefix: &str,
+    out_dir: &str,
+) -> Result<(), error::Error> {
+    for isa in isas {
+        let mut fmt = Formatter::new();
+        gen_isa(formats, isa, &mut fmt);
+        fmt.update_file(&format!("{}-{}.rs", filename_prefix, isa.name), out_dir)?;
+    }
+    Ok(())
+}
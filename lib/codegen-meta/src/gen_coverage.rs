@@ -0,0 +1,445 @@
+//! Generate instruction selection coverage reports.
+//!
+//! This generates a `coverage-<isa>.md` file for each ISA, telling for every
+//! CPU mode which base instructions and controlling types have an encoding,
+//! which ones are legalized into supported instructions, and which ones are
+//! unsupported and would make the compiler fail with a bad encoding.
+//!
+//! The analysis follows the legalization actions of the CPU modes like the
+//! generated legalizer does. It doesn't know about the instruction predicates
+//! of the encodings and of the legalization patterns, nor about the library
+//! calls which the legalizer uses as a last resort.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use base;
+use cdsl::ast::{Def, Expr};
+use cdsl::formats::FormatRegistry;
+use cdsl::instructions::Instruction;
+use cdsl::isa::TargetIsa;
+use cdsl::type_inference::subst;
+use cdsl::types::ValueType;
+use cdsl::typevar::TypeVar;
+use cdsl::xform::{XForm, XFormGroupIndex, XFormGroups};
+use error;
+use srcgen::Formatter;
+
+/// Instructions which are removed by the value splitting of the legalizer,
+/// instead of being encoded.
+const VALUE_SPLITTING: [&str; 4] = ["isplit", "iconcat", "vsplit", "vconcat"];
+
+/// How an instruction with a given controlling type is handled by a CPU mode.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Support {
+    /// The instruction has an encoding.
+    Encoded,
+    /// The instruction is rewritten by the legalization group with the given
+    /// name, into instructions which are all supported.
+    Legalized(&'static str),
+    /// The instruction is removed by the value splitting of the legalizer.
+    Split,
+    /// The instruction can't be handled, for the given reason.
+    Unsupported(String),
+}
+
+impl Support {
+    pub fn is_supported(&self) -> bool {
+        if let Support::Unsupported(_) = *self {
+            return false;
+        }
+        true
+    }
+}
+
+/// The support of one instruction with one controlling type.
+pub struct Entry {
+    pub inst: &'static str,
+    /// The controlling type, or `None` for monomorphic instructions.
+    pub ctrl_type: Option<ValueType>,
+    pub support: Support,
+}
+
+/// The coverage of the base instructions by one CPU mode of an ISA.
+pub struct CpuModeCoverage {
+    pub isa: &'static str,
+    pub cpu_mode: &'static str,
+    pub entries: Vec<Entry>,
+}
+
+impl CpuModeCoverage {
+    /// Get the support of an instruction with its controlling type, named
+    /// like `iadd.i32`, or like `jump` for monomorphic instructions.
+    pub fn get(&self, name: &str) -> Option<&Support> {
+        self.entries
+            .iter()
+            .find(|entry| describe(entry.inst, entry.ctrl_type.as_ref()) == name)
+            .map(|entry| &entry.support)
+    }
+
+    /// Count the entries which are encoded, legalized and unsupported.
+    pub fn counts(&self) -> (usize, usize, usize) {
+        let mut counts = (0, 0, 0);
+        for entry in &self.entries {
+            match entry.support {
+                Support::Encoded => counts.0 += 1,
+                Support::Legalized(_) | Support::Split => counts.1 += 1,
+                Support::Unsupported(_) => counts.2 += 1,
+            }
+        }
+        counts
+    }
+}
+
+/// Get the controlling type of `def`, under the concrete `typing` of the
+/// transformation `xform`.
+fn def_ctrl_type(
+    def: &Def,
+    xform: &XForm,
+    typing: &HashMap<TypeVar, TypeVar>,
+    format_registry: &FormatRegistry,
+) -> Option<ValueType> {
+    let inst = &def.expr.inst;
+    let poly = inst.polymorphic_info.as_ref()?;
+    let var = if poly.use_typevar_operand {
+        let operand = format_registry.get(inst.format).typevar_operand.unwrap();
+        match def.expr.args[inst.value_opnums[operand]] {
+            Expr::Var(var) => var,
+            Expr::Literal(_) => panic!("literal value operand in {}", def),
+        }
+    } else {
+        def.defs[0]
+    };
+    subst(xform.symbols.get(var).type_var(), typing).singleton_type()
+}
+
+/// A concrete instance of a legalization pattern: the controlling type of
+/// its source instruction, and the instructions it is rewritten into along
+/// with their controlling types.
+struct Instance<'a> {
+    ctrl_type: Option<ValueType>,
+    results: Vec<(&'a Instruction, Option<ValueType>)>,
+}
+
+/// The coverage analysis of the CPU modes of one ISA.
+struct Analysis<'a> {
+    isa: &'a TargetIsa,
+    groups: &'a XFormGroups,
+    format_registry: &'a FormatRegistry,
+    /// The concrete instances of the transformations, indexed by group and
+    /// position in the group.
+    instances: HashMap<(XFormGroupIndex, usize), Vec<Instance<'a>>>,
+    /// The support computed so far, indexed by CPU mode, instruction name
+    /// and controlling type.
+    known: HashMap<(usize, &'static str, Option<ValueType>), Support>,
+    /// The queries being computed, to break cycles between legalizations.
+    pending: HashSet<(usize, &'static str, Option<ValueType>)>,
+}
+
+impl<'a> Analysis<'a> {
+    fn new(isa: &'a TargetIsa, shared_defs: &'a base::Definitions) -> Self {
+        Self {
+            isa,
+            groups: &shared_defs.transform_groups,
+            format_registry: &shared_defs.format_registry,
+            instances: HashMap::new(),
+            known: HashMap::new(),
+            pending: HashSet::new(),
+        }
+    }
+
+    /// Get the concrete instances of the transformation at `position` in
+    /// `group`.
+    fn instances(&mut self, group: XFormGroupIndex, position: usize) -> &[Instance<'a>] {
+        let groups = self.groups;
+        let format_registry = self.format_registry;
+        self.instances.entry((group, position)).or_insert_with(|| {
+            let xform = &groups.get(group).xforms[position];
+            xform
+                .ti
+                .concrete_typings()
+                .iter()
+                .map(|typing| Instance {
+                    ctrl_type: def_ctrl_type(&xform.src.rtl[0], xform, typing, format_registry),
+                    results: xform
+                        .dst
+                        .rtl
+                        .iter()
+                        .map(|def| {
+                            let ctrl_type = def_ctrl_type(def, xform, typing, format_registry);
+                            (&def.expr.inst, ctrl_type)
+                        })
+                        .collect(),
+                })
+                .collect()
+        })
+    }
+
+    /// Find how the CPU mode `cpu_mode` handles `inst` with the controlling
+    /// type `ctrl_type`.
+    fn support(
+        &mut self,
+        cpu_mode: usize,
+        inst: &'a Instruction,
+        ctrl_type: Option<ValueType>,
+    ) -> Support {
+        let key = (cpu_mode, inst.name, ctrl_type.clone());
+        if let Some(support) = self.known.get(&key) {
+            return support.clone();
+        }
+        // Assume that the legalizations depending on this one succeed. Cycles
+        // are resolved by the other legalizations on their path.
+        if !self.pending.insert(key.clone()) {
+            return Support::Legalized("cycle");
+        }
+        let support = self.compute_support(cpu_mode, inst, ctrl_type);
+        self.pending.remove(&key);
+        self.known.insert(key, support.clone());
+        support
+    }
+
+    fn compute_support(
+        &mut self,
+        cpu_mode: usize,
+        inst: &'a Instruction,
+        ctrl_type: Option<ValueType>,
+    ) -> Support {
+        let mode = &self.isa.cpu_modes[cpu_mode];
+        if mode
+            .encodings()
+            .iter()
+            .any(|enc| enc.inst.name == inst.name && enc.bound_type == ctrl_type)
+        {
+            return Support::Encoded;
+        }
+        if VALUE_SPLITTING.contains(&inst.name) {
+            return Support::Split;
+        }
+
+        // Follow the chain of legalization groups. The first transformation
+        // matching the instruction and its type is applied.
+        let mut next = Some(mode.get_legalize_action(ctrl_type.as_ref()));
+        while let Some(group_index) = next {
+            let group = self.groups.get(group_index);
+            for position in 0..group.xforms.len() {
+                if group.xforms[position].src.rtl[0].expr.inst.name != inst.name {
+                    continue;
+                }
+                let results = self
+                    .instances(group_index, position)
+                    .iter()
+                    .filter(|instance| instance.ctrl_type == ctrl_type)
+                    .flat_map(|instance| instance.results.iter().cloned())
+                    .collect::<Vec<_>>();
+                if results.is_empty() {
+                    continue;
+                }
+                for (result, result_type) in results {
+                    if !self
+                        .support(cpu_mode, result, result_type.clone())
+                        .is_supported()
+                    {
+                        return Support::Unsupported(format!(
+                            "legalized by {} into unsupported {}",
+                            group.name,
+                            describe(result.name, result_type.as_ref())
+                        ));
+                    }
+                }
+                return Support::Legalized(group.name);
+            }
+            if group.custom.iter().any(|custom| custom.0.name == inst.name) {
+                return Support::Legalized(group.name);
+            }
+            next = group.chain;
+        }
+
+        Support::Unsupported("no encoding or legalization".to_string())
+    }
+}
+
+/// Describe an instruction with its controlling type, like `iadd.i32`.
+fn describe(inst: &str, ctrl_type: Option<&ValueType>) -> String {
+    match ctrl_type {
+        Some(ty) => format!("{}.{}", inst, ty),
+        None => inst.to_string(),
+    }
+}
+
+/// Analyze how every CPU mode of `isa` handles the base instructions, for
+/// all of their controlling types.
+pub fn analyze(isa: &TargetIsa, shared_defs: &base::Definitions) -> Vec<CpuModeCoverage> {
+    let mut analysis = Analysis::new(isa, shared_defs);
+    (0..isa.cpu_modes.len())
+        .map(|cpu_mode| {
+            let mut entries = Vec::new();
+            for inst in shared_defs.instructions.iter() {
+                let ctrl_types = match inst.polymorphic_info {
+                    Some(ref poly) => poly
+                        .ctrl_typevar
+                        .get_typeset()
+                        .concrete_types()
+                        .into_iter()
+                        .map(Some)
+                        .collect(),
+                    None => vec![None],
+                };
+                for ctrl_type in ctrl_types {
+                    let support = analysis.support(cpu_mode, inst, ctrl_type.clone());
+                    entries.push(Entry {
+                        inst: inst.name,
+                        ctrl_type,
+                        support,
+                    });
+                }
+            }
+            CpuModeCoverage {
+                isa: isa.name,
+                cpu_mode: isa.cpu_modes[cpu_mode].name,
+                entries,
+            }
+        })
+        .collect()
+}
+
+fn gen_cpu_mode(coverage: &CpuModeCoverage, fmt: &mut Formatter) {
+    let (encoded, legalized, unsupported) = coverage.counts();
+    fmt.line(&format!("## CPU mode `{}`", coverage.cpu_mode));
+    fmt.line("");
+    fmt.line(&format!(
+        "{} encoded, {} legalized, {} unsupported.",
+        encoded, legalized, unsupported
+    ));
+    fmt.line("");
+
+    // Group the controlling types by instruction, in the encoded, legalized
+    // and unsupported columns.
+    let mut by_inst: BTreeMap<&str, [Vec<String>; 3]> = BTreeMap::new();
+    for entry in &coverage.entries {
+        let types = by_inst.entry(entry.inst).or_default();
+        let name = match entry.ctrl_type {
+            Some(ref ty) => ty.to_string(),
+            None => "-".to_string(),
+        };
+        match entry.support {
+            Support::Encoded => types[0].push(name),
+            Support::Legalized(group) => types[1].push(format!("{} ({})", name, group)),
+            Support::Split => types[1].push(format!("{} (split)", name)),
+            Support::Unsupported(_) => types[2].push(name),
+        }
+    }
+
+    fmt.line("| Instruction | Encoded | Legalized | Unsupported |");
+    fmt.line("|-------------|---------|-----------|-------------|");
+    for (inst, types) in &by_inst {
+        fmt.line(&format!(
+            "| `{}` | {} | {} | {} |",
+            inst,
+            types[0].join(", "),
+            types[1].join(", "),
+            types[2].join(", ")
+        ));
+    }
+    fmt.line("");
+
+    fmt.line("### Unsupported instructions");
+    fmt.line("");
+    for entry in &coverage.entries {
+        if let Support::Unsupported(ref reason) = entry.support {
+            fmt.line(&format!(
+                "- `{}`: {}",
+                describe(entry.inst, entry.ctrl_type.as_ref()),
+                reason
+            ));
+        }
+    }
+    fmt.line("");
+}
+
+/// Generate the coverage reports of all the ISAs, named
+/// `<prefix>-<isa>.md`.
+pub fn generate(
+    shared_defs: &base::Definitions,
+    isas: &[TargetIsa],
+    prefix: &str,
+    out_dir: &str,
+) -> Result<(), error::Error> {
+    for isa in isas {
+        let mut fmt = Formatter::new();
+        fmt.line(&format!("# Instruction selection coverage of {}", isa.name));
+        fmt.line("");
+        for coverage in analyze(isa, shared_defs) {
+            gen_cpu_mode(&coverage, &mut fmt);
+        }
+        fmt.update_file(&format!("{}-{}.md", prefix, isa.name), out_dir)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base::settings;
+    use isa;
+
+    fn analyze_all() -> Vec<CpuModeCoverage> {
+        let shared_settings = settings::generate();
+        let mut shared_defs = base::define();
+        let isas = isa::define_all(&shared_settings, &mut shared_defs);
+        isas.iter()
+            .flat_map(|isa| analyze(isa, &shared_defs))
+            .collect()
+    }
+
+    fn cpu_mode<'a>(coverage: &'a [CpuModeCoverage], name: &str) -> &'a CpuModeCoverage {
+        coverage.iter().find(|mode| mode.cpu_mode == name).unwrap()
+    }
+
+    #[test]
+    fn riscv_coverage() {
+        let coverage = analyze_all();
+        let rv32 = cpu_mode(&coverage, "RV32");
+        assert_eq!(rv32.get("iadd.i32"), Some(&Support::Encoded));
+        assert_eq!(
+            rv32.get("iadd_cout.i32"),
+            Some(&Support::Legalized("expand"))
+        );
+        // The carry of the narrowed addition needs `bint`, which RISC-V
+        // doesn't encode.
+        assert_eq!(
+            rv32.get("iadd.i64"),
+            Some(&Support::Unsupported(
+                "legalized by narrow into unsupported iadd_cin.i32".to_string()
+            ))
+        );
+        assert_eq!(rv32.get("isplit.i64"), Some(&Support::Split));
+        assert_eq!(rv32.get("fadd.f32"), Some(&Support::Encoded));
+
+        let rv64 = cpu_mode(&coverage, "RV64");
+        assert_eq!(rv64.get("iadd.i64"), Some(&Support::Encoded));
+        assert_eq!(rv64.get("jump"), Some(&Support::Encoded));
+    }
+
+    #[test]
+    fn x86_coverage() {
+        let coverage = analyze_all();
+        let i64_mode = cpu_mode(&coverage, "I64");
+        assert_eq!(i64_mode.get("iadd.i64"), Some(&Support::Encoded));
+        assert_eq!(
+            i64_mode.get("udiv.i64"),
+            Some(&Support::Legalized("x86_expand"))
+        );
+        assert_eq!(i64_mode.get("iadd.i8"), Some(&Support::Legalized("widen")));
+
+        let i32_mode = cpu_mode(&coverage, "I32");
+        assert_eq!(
+            i32_mode.get("iadd.i64"),
+            Some(&Support::Legalized("narrow"))
+        );
+        assert!(!i32_mode.get("udiv.i64").unwrap().is_supported());
+
+        // Every base instruction is either supported or has a reason.
+        let (encoded, legalized, unsupported) = i64_mode.counts();
+        assert_eq!(encoded + legalized + unsupported, i64_mode.entries.len());
+        assert!(encoded > 0 && legalized > 0);
+    }
+}
@@ -0,0 +1,992 @@
+//! Generate sources for instruction encoding.
+//!
+//! The tables and functions generated here support the `TargetISA::encode()`
+//! function which determines if a given instruction is legal, and if so, its
+//! `Encoding` data which consists of a *recipe* and some *encoding* bits.
+//!
+//! The `encode` function doesn't actually generate the binary machine bits.
+//! Each recipe has a corresponding hand-written function to do that after
+//! registers are allocated.
+//!
+//! This is the information available to us:
+//!
+//! - The instruction to be encoded as an `InstructionData` reference.
+//! - The controlling type variable.
+//! - The data-flow graph giving us access to the types of all values involved.
+//!   This is needed for testing any secondary type variables.
+//! - A `PredicateView` reference for the ISA-specific settings for evaluating
+//!   ISA predicates.
+//! - The currently active CPU mode is determined by the ISA.
+//!
+//! ## Level 1 table lookup
+//!
+//! The CPU mode provides the first table. The key is the instruction's
+//! controlling type variable. If the instruction is not polymorphic, use
+//! `VOID` for the type variable. The table values are level 2 tables.
+//!
+//! ## Level 2 table lookup
+//!
+//! The level 2 table is keyed by the instruction's opcode. The table values
+//! are *encoding lists*.
+//!
+//! The two-level table lookup allows the level 2 tables to be much smaller
+//! with good locality. Code in any given function usually only uses a few
+//! different types, so many of the level 2 tables will be cold.
+//!
+//! ## Encoding lists
+//!
+//! An encoding list is a non-empty sequence of list entries. Each entry has
+//! one of these forms:
+//!
+//! 1. Recipe + bits. Use this encoding if the recipe predicate is satisfied.
+//! 2. Recipe + bits, final entry. Use this encoding if the recipe predicate is
+//!    satisfied. Otherwise, stop with the default legalization code.
+//! 3. Stop with legalization code.
+//! 4. Predicate + skip count. Test predicate and skip N entries if it is
+//!    false.
+//! 5. Predicate + stop. Test predicate and stop with the default legalization
+//!    code if it is false.
+//!
+//! The instruction predicate is also used to distinguish between polymorphic
+//! instructions with different types for secondary type variables.
+
+use std::collections::{BTreeMap, HashMap};
+
+use base;
+use cdsl::encodings::Encoding;
+use cdsl::instructions::{Instruction, InstructionPredicate};
+use cdsl::isa::TargetIsa;
+use cdsl::recipes::{EncodingRecipe, OperandConstraint, Register};
+use cdsl::types::ValueType;
+use cdsl::xform::XFormGroupIndex;
+use constant_hash::generate_table;
+use error;
+use srcgen::Formatter;
+use unique_table::UniqueSeqTable;
+
+/// Doc comments keyed by offsets into a generated table.
+type DocTable = BTreeMap<usize, Vec<String>>;
+
+/// Emit code for matching an instruction predicate against an
+/// `InstructionData` reference called `inst`.
+///
+/// The generated code is an `if let` pattern match that falls through if the
+/// instruction has an unexpected format. This should lead to a panic.
+fn emit_instp(instp: &InstructionPredicate, has_func: bool, fmt: &mut Formatter) {
+    let format_name = match instp.format_name() {
+        Some(format_name) => format_name,
+        None => {
+            // Deal with pure type check predicates which apply to any
+            // instruction.
+            fmt.line("let args = inst.arguments(&func.dfg.value_lists);");
+            fmt.line(&instp.rust_predicate(0));
+            return;
+        }
+    };
+
+    fmt.line(&format!(
+        "if let ir::InstructionData::{} {{ {}, .. }} = *inst {{",
+        format_name,
+        instp.field_names().join(", ")
+    ));
+    fmt.indent(|fmt| {
+        if instp.has_type_check() {
+            // We could implement this if we need to.
+            assert!(has_func, "Recipe predicates can't check type variables.");
+            fmt.line("let args = inst.arguments(&func.dfg.value_lists);");
+        } else if has_func {
+            // Silence dead argument warning.
+            fmt.line("let _ = func;");
+        }
+        fmt.line(&format!("return {};", instp.rust_predicate(0)));
+    });
+    fmt.line("}");
+    fmt.line("unreachable!();");
+}
+
+/// Emit private functions for matching instruction predicates as well as a
+/// static `INST_PREDICATES` array indexed by predicate number.
+fn emit_inst_predicates(instps: &[&InstructionPredicate], fmt: &mut Formatter) {
+    for (number, instp) in instps.iter().enumerate() {
+        fmt.line(&format!(
+            "fn inst_predicate_{}(func: &ir::Function, inst: &ir::InstructionData)-> bool {{",
+            number
+        ));
+        fmt.indent(|fmt| emit_instp(instp, true, fmt));
+        fmt.line("}");
+    }
+
+    // Generate the static table.
+    fmt.line(&format!(
+        "pub static INST_PREDICATES: [InstPredicate; {}] = [",
+        instps.len()
+    ));
+    fmt.indent(|fmt| {
+        for number in 0..instps.len() {
+            fmt.line(&format!("inst_predicate_{},", number));
+        }
+    });
+    fmt.line("];");
+}
+
+/// The ISA predicate and instruction predicate of a recipe.
+type RecipePredicate<'a> = (Option<u8>, Option<&'a InstructionPredicate>);
+
+/// Emit private functions for checking recipe predicates as well as a static
+/// `RECIPE_PREDICATES` array indexed by recipe number.
+///
+/// A recipe predicate is a combination of an ISA predicate and an instruction
+/// predicate. Many recipes have identical predicates.
+fn emit_recipe_predicates(isa: &TargetIsa, fmt: &mut Formatter) {
+    // Table for uniquing recipe predicates, mapping each predicate to its
+    // generated function name.
+    let mut pnames: Vec<(RecipePredicate, String)> = Vec::new();
+
+    // Generate unique recipe predicates.
+    for recipe in isa.recipes.iter() {
+        let pred = (recipe.isa_predicate, recipe.inst_predicate.as_ref());
+        if pred == (None, None) || pnames.iter().any(|&(ref p, _)| *p == pred) {
+            continue;
+        }
+        let name = format!("recipe_predicate_{}", recipe.name.to_lowercase());
+        let (isap, instp) = pred;
+
+        // Generate the predicate function.
+        fmt.line(&format!(
+            "fn {}({}: ::settings::PredicateView, {}: &ir::InstructionData) -> bool {{",
+            name,
+            if isap.is_some() { "isap" } else { "_" },
+            if instp.is_some() { "inst" } else { "_" }
+        ));
+        fmt.indent(|fmt| {
+            if let Some(isap) = isap {
+                fmt.line(&format!("if !isap.test({}) {{", isap));
+                fmt.indent(|fmt| fmt.line("return false;"));
+                fmt.line("}");
+            }
+            match instp {
+                Some(instp) => emit_instp(instp, false, fmt),
+                None => fmt.line("true"),
+            }
+        });
+        fmt.line("}");
+
+        pnames.push((pred, name));
+    }
+
+    // Generate the static table.
+    fmt.line(&format!(
+        "pub static RECIPE_PREDICATES: [RecipePredicate; {}] = [",
+        isa.recipes.len()
+    ));
+    fmt.indent(|fmt| {
+        for recipe in isa.recipes.iter() {
+            let pred = (recipe.isa_predicate, recipe.inst_predicate.as_ref());
+            match pnames.iter().find(|&&(ref p, _)| *p == pred) {
+                Some(&(_, ref name)) => fmt.line(&format!("Some({}),", name)),
+                None => fmt.line("None,"),
+            }
+        }
+    });
+    fmt.line("];");
+}
+
+// The u16 values in an encoding list entry are interpreted as follows:
+//
+// NR = len(all_recipes)
+//
+// entry < 2*NR
+//     Try Encoding(entry/2, next_entry) if the recipe predicate is satisfied.
+//     If bit 0 is set, stop with the default legalization code.
+//     If bit 0 is clear, keep going down the list.
+// entry < PRED_START
+//     Stop with legalization code `entry - 2*NR`.
+//
+// Remaining entries are interpreted as (skip, pred) pairs, where:
+//
+//     skip = (entry - PRED_START) >> PRED_BITS
+//     pred = (entry - PRED_START) & PRED_MASK
+//
+// If the predicate is satisfied, keep going. Otherwise skip over the next
+// `skip` entries. If skip == 0, stop with the default legalization code.
+//
+// The `pred` predicate number is interpreted as an instruction predicate if it
+// is in range, otherwise an ISA predicate.
+
+/// Encoding lists are represented as u16 arrays.
+const CODE_BITS: usize = 16;
+
+/// Beginning of the predicate code words.
+const PRED_START: usize = 0x1000;
+
+/// Number of bits used to hold a predicate number (instruction + ISA
+/// predicates).
+const PRED_BITS: usize = 12;
+
+/// Mask for extracting the predicate number.
+const PRED_MASK: usize = (1 << PRED_BITS) - 1;
+
+/// A predicate guarding a node of the encoder tree.
+#[derive(Clone, Copy, PartialEq)]
+enum EncPredicate<'a> {
+    /// The number of an ISA predicate in the ISA's settings.
+    Isa(u8),
+    Inst(&'a InstructionPredicate),
+}
+
+/// Encoder for the list format above.
+struct Encoder<'a> {
+    isa: &'a TargetIsa,
+    instps: &'a [&'a InstructionPredicate],
+    /// u16 encoding list words.
+    words: Vec<u16>,
+    /// Documentation comments: Index into `words` + comment.
+    docs: Vec<(usize, String)>,
+}
+
+impl<'a> Encoder<'a> {
+    fn new(isa: &'a TargetIsa, instps: &'a [&'a InstructionPredicate]) -> Self {
+        Self {
+            isa,
+            instps,
+            words: Vec::new(),
+            docs: Vec::new(),
+        }
+    }
+
+    /// Add a recipe+bits entry to the list.
+    fn recipe(&mut self, encoding: &Encoding, is_final: bool) {
+        let offset = self.words.len();
+        let recipe = self.isa.recipes.get(encoding.recipe);
+        let mut code = 2 * encoding.recipe.index();
+        let mut doc = format!("--> [{}#{:02x}]", recipe.name, encoding.encbits);
+        if is_final {
+            code += 1;
+            doc.push_str(" and stop");
+        }
+
+        assert!(code < PRED_START);
+        self.words.push(code as u16);
+        self.words.push(encoding.encbits);
+        self.docs.push((offset, doc));
+    }
+
+    /// Add a predicate entry.
+    fn pred(&mut self, pred: EncPredicate, skip: usize) {
+        let (number, pred_doc) = match pred {
+            EncPredicate::Inst(instp) => (
+                self.instps.iter().position(|p| *p == instp).unwrap(),
+                instp.to_string(),
+            ),
+            // ISA predicates follow the instruction predicates.
+            EncPredicate::Isa(isap) => (
+                self.instps.len() + isap as usize,
+                self.isa.settings.describe_predicate(isap),
+            ),
+        };
+        assert!(number <= PRED_MASK);
+        let code = PRED_START + (number | (skip << PRED_BITS));
+        assert!(code < (1 << CODE_BITS));
+
+        let doc = if skip == 0 {
+            "stop".to_string()
+        } else {
+            format!("skip {}", skip)
+        };
+        self.docs
+            .push((self.words.len(), format!("{} unless {}", doc, pred_doc)));
+        self.words.push(code as u16);
+    }
+}
+
+/// A node in the encoder tree for an instruction.
+///
+/// This tree is used to simplify the predicates guarding recipe+bits entries.
+enum EncNode<'a> {
+    /// A predicate asserted on its child nodes. A `None` predicate is always
+    /// satisfied.
+    Pred(Option<EncPredicate<'a>>, Vec<EncNode<'a>>),
+    /// A single `Encoding`, without its predicates (they are represented in
+    /// the tree by parent nodes).
+    Leaf(&'a Encoding),
+}
+
+impl<'a> EncNode<'a> {
+    /// Get the number of list entries needed to encode this tree.
+    fn size(&self) -> usize {
+        match *self {
+            EncNode::Pred(ref pred, ref children) => {
+                let pred_size = if pred.is_some() { 1 } else { 0 };
+                pred_size + children.iter().map(|c| c.size()).sum::<usize>()
+            }
+            // Recipe + bits.
+            EncNode::Leaf(_) => 2,
+        }
+    }
+
+    /// Get the predicate guarding this tree, or `None` for always.
+    fn predicate(&self) -> Option<EncPredicate<'a>> {
+        match *self {
+            EncNode::Pred(pred, _) => pred,
+            EncNode::Leaf(_) => None,
+        }
+    }
+
+    /// Encode this tree.
+    fn encode(&self, encoder: &mut Encoder, is_final: bool) {
+        match *self {
+            EncNode::Pred(pred, ref children) => {
+                if let Some(pred) = pred {
+                    let skip = if is_final { 0 } else { self.size() - 1 };
+                    encoder.pred(pred, skip);
+                }
+                for (idx, node) in children.iter().enumerate() {
+                    node.encode(encoder, is_final && idx == children.len() - 1);
+                }
+            }
+            EncNode::Leaf(encoding) => encoder.recipe(encoding, is_final),
+        }
+    }
+
+    /// Transform this encoder tree into something simpler, by combining
+    /// child nodes that have identical predicates.
+    fn optimize(self) -> Self {
+        let (pred, children) = match self {
+            EncNode::Pred(pred, children) => (pred, children),
+            leaf => return leaf,
+        };
+
+        let mut cnodes: Vec<EncNode> = Vec::new();
+        let mut group_start = 0;
+        for node in children.into_iter().map(|c| c.optimize()) {
+            let node_pred = node.predicate();
+            // Merge with the group of preceding nodes having the same
+            // predicate, if any.
+            if node_pred.is_some()
+                && group_start < cnodes.len()
+                && cnodes[group_start].predicate() == node_pred
+            {
+                if let (&mut EncNode::Pred(_, ref mut n0), EncNode::Pred(_, n)) =
+                    (&mut cnodes[group_start], node)
+                {
+                    n0.extend(n);
+                }
+                continue;
+            }
+            group_start = cnodes.len();
+            cnodes.push(node);
+        }
+
+        // Finally strip a redundant grouping node.
+        if pred.is_none() && cnodes.len() == 1 {
+            cnodes.pop().unwrap()
+        } else {
+            EncNode::Pred(pred, cnodes)
+        }
+    }
+}
+
+/// List of encodings for a given type + opcode pair.
+///
+/// An encoding list contains a sequence of predicates and encoding recipes,
+/// all encoded as u16 values.
+struct EncList<'a> {
+    inst: &'a Instruction,
+    encodings: Vec<&'a Encoding>,
+    /// Offset of the encoded list in the `ENCLISTS` table.
+    offset: usize,
+}
+
+impl<'a> EncList<'a> {
+    fn name(&self, ty: Option<&ValueType>, cpu_mode_name: &str) -> String {
+        match ty {
+            Some(ty) => format!("{}.{} ({})", self.inst.name, ty, cpu_mode_name),
+            None => format!("{} ({})", self.inst.name, cpu_mode_name),
+        }
+    }
+
+    /// Generate an optimized encoder tree for this list. The tree represents
+    /// all of the encodings with parent nodes for the predicates that need
+    /// checking.
+    fn encoder_tree(&self) -> EncNode<'a> {
+        let forest = self
+            .encodings
+            .iter()
+            .map(|&encoding| {
+                let mut node = EncNode::Leaf(encoding);
+                if let Some(ref instp) = encoding.inst_predicate {
+                    node = EncNode::Pred(Some(EncPredicate::Inst(instp)), vec![node]);
+                }
+                if let Some(isap) = encoding.isa_predicate {
+                    node = EncNode::Pred(Some(EncPredicate::Isa(isap)), vec![node]);
+                }
+                node
+            })
+            .collect();
+        EncNode::Pred(None, forest).optimize()
+    }
+
+    /// Encode this list as a sequence of u16 numbers into `seq_table`, and
+    /// record the offset.
+    ///
+    /// Adds comment lines to `doc_table` keyed by `seq_table` offsets.
+    fn encode(
+        &mut self,
+        name: &str,
+        encoder: &mut Encoder,
+        seq_table: &mut UniqueSeqTable<u16>,
+        doc_table: &mut DocTable,
+    ) {
+        self.encoder_tree().encode(encoder, true);
+
+        self.offset = seq_table.add(&encoder.words);
+
+        // Add doc comments.
+        doc_table
+            .entry(self.offset)
+            .or_default()
+            .push(format!("{:06x}: {}", self.offset, name));
+        for &(pos, ref doc) in &encoder.docs {
+            doc_table
+                .entry(self.offset + pos)
+                .or_default()
+                .push(doc.clone());
+        }
+        doc_table
+            .entry(self.offset + encoder.words.len())
+            .or_default()
+            .insert(0, format!("end of: {}", name));
+    }
+}
+
+/// Level 2 table mapping instruction opcodes to `EncList` objects.
+///
+/// A level 2 table can be completely empty if it only holds a custom
+/// legalization action for `ty`.
+struct Level2Table<'a> {
+    /// Controlling type variable of all entries, or `None`.
+    ty: Option<&'a ValueType>,
+    /// Default legalize action for `ty`.
+    legalize: XFormGroupIndex,
+    lists: Vec<EncList<'a>>,
+    hash_table_offset: usize,
+    hash_table_len: usize,
+}
+
+impl<'a> Level2Table<'a> {
+    fn list_mut(&mut self, inst: &'a Instruction) -> &mut EncList<'a> {
+        match self.lists.iter().position(|l| l.inst.name == inst.name) {
+            Some(pos) => &mut self.lists[pos],
+            None => {
+                self.lists.push(EncList {
+                    inst,
+                    encodings: Vec::new(),
+                    offset: 0,
+                });
+                self.lists.last_mut().unwrap()
+            }
+        }
+    }
+
+    /// Compute the hash table mapping opcode -> enclist.
+    ///
+    /// Append the hash table to `level2_hashtables` and record the offset.
+    fn layout_hashtable(
+        &mut self,
+        opcode_numbers: &HashMap<&str, usize>,
+        level2_hashtables: &mut Vec<Option<(String, usize)>>,
+        level2_doc: &mut DocTable,
+    ) {
+        let hash_table = generate_table(self.lists.iter(), self.lists.len(), |enclist| {
+            opcode_numbers[enclist.inst.name]
+        });
+
+        self.hash_table_offset = level2_hashtables.len();
+        self.hash_table_len = hash_table.len();
+
+        level2_doc
+            .entry(self.hash_table_offset)
+            .or_default()
+            .push(format!(
+                "{:06x}: {}, {} entries",
+                self.hash_table_offset,
+                self.ty.map_or("None".to_string(), |ty| ty.to_string()),
+                self.hash_table_len
+            ));
+        level2_hashtables.extend(
+            hash_table.into_iter().map(|entry| {
+                entry.map(|enclist| (enclist.inst.camel_name.clone(), enclist.offset))
+            }),
+        );
+    }
+}
+
+/// Level 1 table mapping types to `Level2Table` objects.
+struct Level1Table<'a> {
+    tables: Vec<Level2Table<'a>>,
+}
+
+impl<'a> Level1Table<'a> {
+    fn table_mut(
+        &mut self,
+        isa: &'a TargetIsa,
+        cpu_mode: usize,
+        ty: Option<&'a ValueType>,
+    ) -> &mut Level2Table<'a> {
+        match self.tables.iter().position(|t| t.ty == ty) {
+            Some(pos) => &mut self.tables[pos],
+            None => {
+                self.tables.push(Level2Table {
+                    ty,
+                    legalize: isa.cpu_modes[cpu_mode].get_legalize_action(ty),
+                    lists: Vec::new(),
+                    hash_table_offset: 0,
+                    hash_table_len: 0,
+                });
+                self.tables.last_mut().unwrap()
+            }
+        }
+    }
+
+    /// Get the non-empty level 2 tables.
+    fn l2tables(&mut self) -> impl Iterator<Item = &mut Level2Table<'a>> {
+        self.tables.iter_mut().filter(|l2| !l2.lists.is_empty())
+    }
+}
+
+/// Generate the level 1 table for the CPU mode `cpu_mode` of `isa`.
+fn make_tables(isa: &TargetIsa, cpu_mode: usize) -> Level1Table {
+    let mut table = Level1Table { tables: Vec::new() };
+    let mode = &isa.cpu_modes[cpu_mode];
+    for encoding in mode.encodings() {
+        table
+            .table_mut(isa, cpu_mode, encoding.bound_type.as_ref())
+            .list_mut(&encoding.inst)
+            .encodings
+            .push(encoding);
+    }
+
+    // Ensure there are level 1 table entries for all types with a custom
+    // legalize action.
+    for ty in mode.legalized_types() {
+        table.table_mut(isa, cpu_mode, ty);
+    }
+
+    table
+}
+
+fn emit_enclists(seq_table: &UniqueSeqTable<u16>, doc_table: &DocTable, fmt: &mut Formatter) {
+    fmt.line(&format!(
+        "pub static ENCLISTS: [u16; {}] = [",
+        seq_table.len()
+    ));
+    fmt.indent(|fmt| {
+        let mut line = String::new();
+        for (idx, entry) in seq_table.iter().enumerate() {
+            if let Some(docs) = doc_table.get(&idx) {
+                if !line.is_empty() {
+                    fmt.line(&line);
+                    line.clear();
+                }
+                for doc in docs {
+                    fmt.comment(doc);
+                }
+            }
+            line.push_str(&format!("{:#06x}, ", entry));
+        }
+        if !line.is_empty() {
+            fmt.line(&line);
+        }
+    });
+    fmt.line("];");
+}
+
+/// Emit the big concatenation of level 2 hash tables.
+fn emit_level2_hashtables(
+    level2_hashtables: &[Option<(String, usize)>],
+    offset_type: &str,
+    level2_doc: &DocTable,
+    fmt: &mut Formatter,
+) {
+    fmt.line(&format!(
+        "pub static LEVEL2: [Level2Entry<{}>; {}] = [",
+        offset_type,
+        level2_hashtables.len()
+    ));
+    fmt.indent(|fmt| {
+        for (offset, entry) in level2_hashtables.iter().enumerate() {
+            if let Some(docs) = level2_doc.get(&offset) {
+                for doc in docs {
+                    fmt.comment(doc);
+                }
+            }
+            match *entry {
+                Some((ref camel_name, enclist_offset)) => fmt.line(&format!(
+                    "Level2Entry {{ opcode: Some(ir::Opcode::{}), offset: {:#08x} }},",
+                    camel_name, enclist_offset
+                )),
+                None => fmt.line("Level2Entry { opcode: None, offset: 0 },"),
+            }
+        }
+    });
+    fmt.line("];");
+}
+
+/// Emit a level 1 hash table for the CPU mode `cpu_mode` of `isa`.
+fn emit_level1_hashtable(
+    shared_defs: &base::Definitions,
+    isa: &TargetIsa,
+    cpu_mode: usize,
+    level1: &Level1Table,
+    offset_type: &str,
+    fmt: &mut Formatter,
+) {
+    let groups = &shared_defs.transform_groups;
+    let legalize_codes = isa.legalize_codes();
+    let legalize_code = |group: XFormGroupIndex| {
+        legalize_codes
+            .iter()
+            .position(|&code| code == group)
+            .unwrap()
+    };
+
+    let mode = &isa.cpu_modes[cpu_mode];
+    let hash_table = generate_table(level1.tables.iter(), level1.tables.len(), |level2| {
+        level2.ty.map_or(0, |ty| ty.number().unwrap() as usize)
+    });
+
+    fmt.line(&format!(
+        "pub static LEVEL1_{}: [Level1Entry<{}>; {}] = [",
+        mode.name.to_uppercase(),
+        offset_type,
+        hash_table.len()
+    ));
+    fmt.indent(|fmt| {
+        for level2 in hash_table {
+            let level2 = match level2 {
+                Some(level2) => level2,
+                None => {
+                    // Empty hash table entry. Include the default
+                    // legalization action.
+                    fmt.line(&format!(
+                        "Level1Entry {{ ty: ir::types::VOID, log2len: !0, offset: 0, legalize: {} }},",
+                        legalize_code(mode.get_default_legalize())
+                    ));
+                    continue;
+                }
+            };
+
+            let tyname = level2
+                .ty
+                .map_or("ir::types::VOID".to_string(), |ty| ty.rust_name());
+            let lcode = legalize_code(level2.legalize);
+            let group = groups.get(level2.legalize);
+
+            if level2.lists.is_empty() {
+                // Empty level 2 table: Only a specialized legalization action,
+                // no actual table. Set an offset that is out of bounds, but
+                // make sure it doesn't overflow its type when adding
+                // `1<<log2len`.
+                fmt.line(&format!(
+                    "Level1Entry {{ ty: {}, log2len: 0, offset: !0 - 1, legalize: {} }}, // {}",
+                    tyname, lcode, group
+                ));
+                continue;
+            }
+
+            // Proper level 2 hash table.
+            let l2l = (level2.hash_table_len as f64).log2() as usize;
+            assert!(l2l > 0, "Level2 hash table too small");
+            fmt.line(&format!(
+                "Level1Entry {{ ty: {}, log2len: {}, offset: {:#08x}, legalize: {} }}, // {}",
+                tyname, l2l, level2.hash_table_offset, lcode, group
+            ));
+        }
+    });
+    fmt.line("];");
+}
+
+/// Compute an appropriate Rust integer type to use for offsets into a table of
+/// the given length.
+fn offset_type(length: usize) -> &'static str {
+    if length <= 0x10000 {
+        "u16"
+    } else {
+        assert!(length <= 0x1_0000_0000, "Table too big");
+        "u32"
+    }
+}
+
+/// Emit a table of encoding recipe names keyed by recipe number.
+///
+/// This is used for pretty-printing encodings.
+fn emit_recipe_names(isa: &TargetIsa, fmt: &mut Formatter) {
+    fmt.line(&format!(
+        "static RECIPE_NAMES: [&str; {}] = [",
+        isa.recipes.len()
+    ));
+    fmt.indent(|fmt| {
+        for recipe in isa.recipes.iter() {
+            fmt.line(&format!("\"{}\",", recipe.name));
+        }
+    });
+    fmt.line("];");
+}
+
+/// Emit a struct field initializer for an array of operand constraints.
+///
+/// `tied` maps operand numbers to their tied counterparts, and `fixed_ops` is
+/// the set of fixed registers on the other side of the instruction.
+fn emit_operand_constraints(
+    isa: &TargetIsa,
+    recipe: &EncodingRecipe,
+    constraints: &[OperandConstraint],
+    field_name: &str,
+    tied: &[Option<usize>],
+    fixed_ops: &[Register],
+    fmt: &mut Formatter,
+) {
+    if constraints.is_empty() {
+        fmt.line(&format!("{}: &[],", field_name));
+        return;
+    }
+
+    let regclass_name = |index: usize| isa.regs.classes[index].name;
+
+    fmt.line(&format!("{}: &[", field_name));
+    fmt.indent(|fmt| {
+        for (n, constraint) in constraints.iter().enumerate() {
+            fmt.line("OperandConstraint {");
+            fmt.indent(|fmt| match *constraint {
+                OperandConstraint::RegClass(regclass) => {
+                    match tied[n] {
+                        Some(num) => fmt.line(&format!("kind: ConstraintKind::Tied({}),", num)),
+                        None => fmt.line("kind: ConstraintKind::Reg,"),
+                    }
+                    fmt.line(&format!("regclass: &{}_DATA,", regclass_name(regclass)));
+                }
+                OperandConstraint::FixedReg(reg) => {
+                    assert!(tied[n].is_none(), "Can't tie fixed register operand");
+                    // See if this fixed register is also on the other side.
+                    let kind = if fixed_ops.contains(&reg) {
+                        "FixedTied"
+                    } else {
+                        "FixedReg"
+                    };
+                    fmt.line(&format!("kind: ConstraintKind::{}({}),", kind, reg.unit));
+                    fmt.line(&format!("regclass: &{}_DATA,", regclass_name(reg.regclass)));
+                }
+                OperandConstraint::TiedInput(num) => {
+                    // This is a tied output constraint. It should never happen
+                    // for input constraints.
+                    assert_eq!(Some(num), tied[n], "Invalid tied constraint");
+                    fmt.line(&format!("kind: ConstraintKind::Tied({}),", num));
+                    let regclass = match recipe.operands_in[num] {
+                        OperandConstraint::RegClass(regclass) => regclass,
+                        _ => panic!(
+                            "tied input of recipe {} must be a register class",
+                            recipe.name
+                        ),
+                    };
+                    fmt.line(&format!("regclass: &{}_DATA,", regclass_name(regclass)));
+                }
+                OperandConstraint::Stack(stack) => {
+                    assert!(tied[n].is_none(), "Can't tie stack operand");
+                    fmt.line("kind: ConstraintKind::Stack,");
+                    fmt.line(&format!(
+                        "regclass: &{}_DATA,",
+                        regclass_name(stack.regclass)
+                    ));
+                }
+            });
+            fmt.line("},");
+        }
+    });
+    fmt.line("],");
+}
+
+/// Emit a table of encoding recipe operand constraints keyed by recipe number.
+///
+/// These are used by the register allocator to pick registers that can be
+/// properly encoded.
+fn emit_recipe_constraints(isa: &TargetIsa, fmt: &mut Formatter) {
+    fmt.line(&format!(
+        "static RECIPE_CONSTRAINTS: [RecipeConstraints; {}] = [",
+        isa.recipes.len()
+    ));
+    fmt.indent(|fmt| {
+        for recipe in isa.recipes.iter() {
+            let tied_inputs = recipe.tied_inputs();
+            let tied_outputs: Vec<Option<usize>> = recipe
+                .operands_out
+                .iter()
+                .map(|constraint| match *constraint {
+                    OperandConstraint::TiedInput(num) => Some(num),
+                    _ => None,
+                })
+                .collect();
+            let fixed_ins = EncodingRecipe::fixed_registers(&recipe.operands_in);
+            let fixed_outs = EncodingRecipe::fixed_registers(&recipe.operands_out);
+
+            fmt.comment(&format!("Constraints for recipe {}:", recipe.name));
+            fmt.line("RecipeConstraints {");
+            fmt.indent(|fmt| {
+                emit_operand_constraints(
+                    isa,
+                    recipe,
+                    &recipe.operands_in,
+                    "ins",
+                    &tied_inputs,
+                    &fixed_outs,
+                    fmt,
+                );
+                emit_operand_constraints(
+                    isa,
+                    recipe,
+                    &recipe.operands_out,
+                    "outs",
+                    &tied_outputs,
+                    &fixed_ins,
+                    fmt,
+                );
+                fmt.line(&format!("fixed_ins: {},", !fixed_ins.is_empty()));
+                fmt.line(&format!("fixed_outs: {},", !fixed_outs.is_empty()));
+                fmt.line(&format!(
+                    "tied_ops: {},",
+                    tied_inputs.iter().any(|t| t.is_some())
+                ));
+                fmt.line(&format!("clobbers_flags: {},", recipe.clobbers_flags));
+            });
+            fmt.line("},");
+        }
+    });
+    fmt.line("];");
+}
+
+/// Emit a table of encoding recipe code size information.
+fn emit_recipe_sizing(isa: &TargetIsa, fmt: &mut Formatter) {
+    fmt.line(&format!(
+        "static RECIPE_SIZING: [RecipeSizing; {}] = [",
+        isa.recipes.len()
+    ));
+    fmt.indent(|fmt| {
+        for recipe in isa.recipes.iter() {
+            fmt.comment(&format!(
+                "Code size information for recipe {}:",
+                recipe.name
+            ));
+            fmt.line("RecipeSizing {");
+            fmt.indent(|fmt| {
+                fmt.line(&format!("bytes: {},", recipe.base_size));
+                match recipe.branch_range {
+                    Some(range) => fmt.line(&format!(
+                        "branch_range: Some(BranchRange {{ origin: {}, bits: {} }}),",
+                        range.origin, range.bits
+                    )),
+                    None => fmt.line("branch_range: None,"),
+                }
+            });
+            fmt.line("},");
+        }
+    });
+    fmt.line("];");
+}
+
+fn gen_isa(
+    shared_defs: &base::Definitions,
+    opcode_numbers: &HashMap<&str, usize>,
+    isa: &TargetIsa,
+    fmt: &mut Formatter,
+) {
+    let instps = isa.inst_predicates();
+
+    // Make the `RECIPE_PREDICATES` table.
+    emit_recipe_predicates(isa, fmt);
+
+    // Make the `INST_PREDICATES` table.
+    emit_inst_predicates(&instps, fmt);
+
+    // Level1 tables, one per CPU mode.
+    let mut level1_tables = Vec::new();
+
+    // Tables for enclists with comments.
+    let mut seq_table = UniqueSeqTable::new();
+    let mut doc_table = DocTable::new();
+
+    // Single table containing all the level2 hash tables.
+    let mut level2_hashtables = Vec::new();
+    let mut level2_doc = DocTable::new();
+
+    for (cpu_mode, mode) in isa.cpu_modes.iter().enumerate() {
+        level2_doc
+            .entry(level2_hashtables.len())
+            .or_default()
+            .push(mode.name.to_string());
+
+        let mut level1 = make_tables(isa, cpu_mode);
+
+        for level2 in level1.l2tables() {
+            let ty = level2.ty;
+            for enclist in &mut level2.lists {
+                let name = enclist.name(ty, mode.name);
+                let mut encoder = Encoder::new(isa, &instps);
+                enclist.encode(&name, &mut encoder, &mut seq_table, &mut doc_table);
+            }
+        }
+
+        for level2 in level1.l2tables() {
+            level2.layout_hashtable(opcode_numbers, &mut level2_hashtables, &mut level2_doc);
+        }
+
+        level1_tables.push(level1);
+    }
+
+    // Level 1 table encodes offsets into the level 2 table.
+    let level1_offset_type = offset_type(level2_hashtables.len());
+    // Level 2 tables encodes offsets into seq_table.
+    let level2_offset_type = offset_type(seq_table.len());
+
+    emit_enclists(&seq_table, &doc_table, fmt);
+    emit_level2_hashtables(&level2_hashtables, level2_offset_type, &level2_doc, fmt);
+    for (cpu_mode, level1) in level1_tables.iter().enumerate() {
+        emit_level1_hashtable(shared_defs, isa, cpu_mode, level1, level1_offset_type, fmt);
+    }
+
+    emit_recipe_names(isa, fmt);
+    emit_recipe_constraints(isa, fmt);
+    emit_recipe_sizing(isa, fmt);
+
+    // Finally, tie it all together in an `EncInfo`.
+    fmt.line("pub static INFO: isa::EncInfo = isa::EncInfo {");
+    fmt.indent(|fmt| {
+        fmt.line("constraints: &RECIPE_CONSTRAINTS,");
+        fmt.line("sizing: &RECIPE_SIZING,");
+        fmt.line("names: &RECIPE_NAMES,");
+    });
+    fmt.line("};");
+}
+
+/// Generate the `<filename_prefix>-<isa>.rs` encoding tables for each ISA.
+pub fn generate(
+    shared_defs: &base::Definitions,
+    isas: &[TargetIsa],
+    filename_prefix: &str,
+    out_dir: &str,
+) -> Result<(), error::Error> {
+    // The level 2 tables are keyed by opcode number. Opcodes are numbered
+    // from 1 in the order of the `Opcode` enum.
+    let opcode_numbers: HashMap<&str, usize> = shared_defs
+        .instruction_groups(isas)
+        .into_iter()
+        .flat_map(|group| group.iter())
+        .enumerate()
+        .map(|(i, inst)| (inst.name, i + 1))
+        .collect();
+
+    for isa in isas {
+        let mut fmt = Formatter::new();
+        gen_isa(shared_defs, &opcode_numbers, isa, &mut fmt);
+        fmt.update_file(&format!("{}-{}.rs", filename_prefix, isa.name), out_dir)?;
+    }
+    Ok(())
+}
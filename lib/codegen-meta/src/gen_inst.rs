@@ -0,0 +1,1089 @@
+//! Generate sources with instruction info.
+//!
+//! This generates the `opcodes.rs` file with the instruction formats, opcodes
+//! and type constraints, and the `inst_builder.rs` file with the
+//! `InstBuilder` trait.
+
+use base;
+use cdsl::camel_case;
+use cdsl::formats::{FormatRegistry, InstructionFormat};
+use cdsl::instructions::{Instruction, InstructionGroup};
+use cdsl::isa::TargetIsa;
+use cdsl::operands::{Operand, OperandKindFields};
+use cdsl::typevar::{TypeSet, TypeVar};
+use constant_hash;
+use error;
+use srcgen::{Formatter, Match};
+use unique_table::{UniqueSeqTable, UniqueTable};
+
+/// TypeSet indexes are encoded in 8 bits, with `0xff` reserved.
+const TYPESET_LIMIT: usize = 0xff;
+
+/// Generate an instruction format enumeration.
+fn gen_formats(registry: &FormatRegistry, fmt: &mut Formatter) {
+    fmt.doc_comment(
+        r#"
+        An instruction format
+
+        Every opcode has a corresponding instruction format
+        which is represented by both the `InstructionFormat`
+        and the `InstructionData` enums.
+        "#,
+    );
+    fmt.line("#[derive(Copy, Clone, PartialEq, Eq, Debug)]");
+    fmt.line("pub enum InstructionFormat {");
+    fmt.indent(|fmt| {
+        for f in registry.iter() {
+            fmt.doc_comment(&f.to_string());
+            fmt.line(&format!("{},", f.name));
+        }
+    });
+    fmt.line("}");
+    fmt.line("");
+
+    // Emit a From<InstructionData> which also serves to verify that
+    // InstructionFormat and InstructionData are in sync.
+    fmt.line("impl<'a> From<&'a InstructionData> for InstructionFormat {");
+    fmt.indent(|fmt| {
+        fmt.line("fn from(inst: &'a InstructionData) -> Self {");
+        fmt.indent(|fmt| {
+            let mut m = Match::new("*inst");
+            for f in registry.iter() {
+                m.arm(
+                    format!("InstructionData::{}", f.name),
+                    vec!["..".to_string()],
+                    format!("InstructionFormat::{}", f.name),
+                );
+            }
+            fmt.add_match(m);
+        });
+        fmt.line("}");
+    });
+    fmt.line("}");
+    fmt.line("");
+}
+
+/// Generate the InstructionData enum.
+///
+/// Every variant must contain `opcode` and `ty` fields. An instruction that
+/// doesn't produce a value should have its `ty` field set to `VOID`. The size
+/// of `InstructionData` should be kept at 16 bytes on 64-bit architectures. If
+/// more space is needed to represent an instruction, use a `Box<AuxData>` to
+/// store the additional information out of line.
+fn gen_instruction_data(registry: &FormatRegistry, fmt: &mut Formatter) {
+    fmt.line("#[derive(Clone, Debug)]");
+    fmt.line("#[allow(missing_docs)]");
+    fmt.line("pub enum InstructionData {");
+    fmt.indent(|fmt| {
+        for f in registry.iter() {
+            fmt.line(&format!("{} {{", f.name));
+            fmt.indent(|fmt| {
+                fmt.line("opcode: Opcode,");
+                if f.typevar_operand.is_none() {
+                    // No value operands.
+                } else if f.has_value_list {
+                    fmt.line("args: ValueList,");
+                } else if f.num_value_operands == 1 {
+                    fmt.line("arg: Value,");
+                } else {
+                    fmt.line(&format!("args: [Value; {}],", f.num_value_operands));
+                }
+                for field in &f.imm_fields {
+                    fmt.line(&format!("{}: {},", field.member, field.kind.rust_type));
+                }
+            });
+            fmt.line("},");
+        }
+    });
+    fmt.line("}");
+}
+
+fn gen_arguments_method(registry: &FormatRegistry, fmt: &mut Formatter, is_mut: bool) {
+    let (method, mut_, rslice, as_slice) = if is_mut {
+        ("arguments_mut", "mut ", "ref_slice_mut", "as_mut_slice")
+    } else {
+        ("arguments", "", "ref_slice", "as_slice")
+    };
+
+    fmt.line(&format!(
+        "pub fn {f}<'a>(&'a {m}self, pool: &'a {m}ir::ValueListPool) -> &{m}[Value] {{",
+        f = method,
+        m = mut_
+    ));
+    fmt.indent(|fmt| {
+        let mut m = Match::new("*self");
+        for f in registry.iter() {
+            let name = format!("InstructionData::{}", f.name);
+
+            // Formats with a value list put all of their arguments in the
+            // list. We don't split them up, just return it all as variable
+            // arguments. (I expect the distinction to go away).
+            if f.has_value_list {
+                m.arm(
+                    name,
+                    vec![format!("ref {}args", mut_), "..".to_string()],
+                    format!("args.{}(pool)", as_slice),
+                );
+                continue;
+            }
+
+            // Fixed args.
+            let mut fields = Vec::new();
+            let arg = if f.num_value_operands == 0 {
+                format!("&{}[]", mut_)
+            } else if f.num_value_operands == 1 {
+                fields.push(format!("ref {}arg", mut_));
+                format!("{}(arg)", rslice)
+            } else {
+                let arg = format!("args_arity{}", f.num_value_operands);
+                fields.push(format!("args: ref {}{}", mut_, arg));
+                arg
+            };
+            fields.push("..".to_string());
+
+            m.arm(name, fields, arg);
+        }
+        fmt.add_match(m);
+    });
+    fmt.line("}");
+}
+
+/// Generate the boring parts of the InstructionData implementation.
+///
+/// These methods in `impl InstructionData` can be generated automatically from
+/// the instruction formats:
+///
+/// - `pub fn opcode(&self) -> Opcode`
+/// - `pub fn arguments(&self, &pool) -> &[Value]`
+/// - `pub fn arguments_mut(&mut self, &pool) -> &mut [Value]`
+/// - `pub fn take_value_list(&mut self) -> Option<ir::ValueList>`
+/// - `pub fn put_value_list(&mut self, args: ir::ValueList>`
+/// - `pub fn eq(&self, &other: Self, &pool) -> bool`
+/// - `pub fn hash<H: Hasher>(&self, state: &mut H, &pool)`
+fn gen_instruction_data_impl(registry: &FormatRegistry, fmt: &mut Formatter) {
+    // The `opcode` method simply reads the `opcode` members. This is really a
+    // workaround for Rust's enum types missing shared members.
+    fmt.line("impl InstructionData {");
+    fmt.indent(|fmt| {
+        fmt.doc_comment("Get the opcode of this instruction.");
+        fmt.line("pub fn opcode(&self) -> Opcode {");
+        fmt.indent(|fmt| {
+            let mut m = Match::new("*self");
+            for f in registry.iter() {
+                m.arm(
+                    format!("InstructionData::{}", f.name),
+                    vec!["opcode".to_string(), "..".to_string()],
+                    "opcode".to_string(),
+                );
+            }
+            fmt.add_match(m);
+        });
+        fmt.line("}");
+        fmt.line("");
+
+        fmt.doc_comment("Get the controlling type variable operand.");
+        fmt.line("pub fn typevar_operand(&self, pool: &ir::ValueListPool) -> Option<Value> {");
+        fmt.indent(|fmt| {
+            let mut m = Match::new("*self");
+            for f in registry.iter() {
+                let name = format!("InstructionData::{}", f.name);
+                match f.typevar_operand {
+                    None => {
+                        m.arm(name, vec!["..".to_string()], "None".to_string());
+                    }
+                    Some(typevar_operand) if f.has_value_list => {
+                        // We keep all arguments in a value list.
+                        m.arm(
+                            name,
+                            vec!["ref args".to_string(), "..".to_string()],
+                            format!("args.get({}, pool)", typevar_operand),
+                        );
+                    }
+                    Some(_) if f.num_value_operands == 1 => {
+                        // We have a single value operand called 'arg'.
+                        m.arm(
+                            name,
+                            vec!["arg".to_string(), "..".to_string()],
+                            "Some(arg)".to_string(),
+                        );
+                    }
+                    Some(typevar_operand) => {
+                        // We have multiple value operands and an array `args`.
+                        // Which `args` index to use?
+                        let args = format!("args_arity{}", f.num_value_operands);
+                        m.arm(
+                            name,
+                            vec![format!("args: ref {}", args), "..".to_string()],
+                            format!("Some({}[{}])", args, typevar_operand),
+                        );
+                    }
+                }
+            }
+            fmt.add_match(m);
+        });
+        fmt.line("}");
+        fmt.line("");
+
+        fmt.doc_comment("Get the value arguments to this instruction.");
+        gen_arguments_method(registry, fmt, false);
+        fmt.line("");
+
+        fmt.doc_comment(
+            r#"
+            Get mutable references to the value arguments to this
+            instruction.
+            "#,
+        );
+        gen_arguments_method(registry, fmt, true);
+        fmt.line("");
+
+        fmt.doc_comment(
+            r#"
+            Take out the value list with all the value arguments and return
+            it.
+
+            This leaves the value list in the instruction empty. Use
+            `put_value_list` to put the value list back.
+            "#,
+        );
+        fmt.line("pub fn take_value_list(&mut self) -> Option<ir::ValueList> {");
+        fmt.indent(|fmt| {
+            let mut m = Match::new("*self");
+            for f in registry.iter().filter(|f| f.has_value_list) {
+                m.arm(
+                    format!("InstructionData::{}", f.name),
+                    vec!["ref mut args".to_string(), "..".to_string()],
+                    "Some(args.take())".to_string(),
+                );
+            }
+            m.arm("_".to_string(), Vec::new(), "None".to_string());
+            fmt.add_match(m);
+        });
+        fmt.line("}");
+        fmt.line("");
+
+        fmt.doc_comment(
+            r#"
+            Put back a value list.
+
+            After removing a value list with `take_value_list()`, use this
+            method to put it back. It is required that this instruction has
+            a format that accepts a value list, and that the existing value
+            list is empty. This avoids leaking list pool memory.
+            "#,
+        );
+        fmt.line("pub fn put_value_list(&mut self, vlist: ir::ValueList) {");
+        fmt.indent(|fmt| {
+            fmt.line("let args = match *self {");
+            fmt.indent(|fmt| {
+                for f in registry.iter().filter(|f| f.has_value_list) {
+                    fmt.line(&format!(
+                        "InstructionData::{} {{ ref mut args, .. }} => args,",
+                        f.name
+                    ));
+                }
+                fmt.line("_ => panic!(\"No value list: {:?}\", self),");
+            });
+            fmt.line("};");
+            fmt.line("debug_assert!(args.is_empty(), \"Value list already in use\");");
+            fmt.line("*args = vlist;");
+        });
+        fmt.line("}");
+        fmt.line("");
+
+        fmt.doc_comment(
+            r#"
+            Compare two `InstructionData` for equality.
+
+            This operation requires a reference to a `ValueListPool` to
+            determine if the contents of any `ValueLists` are equal.
+            "#,
+        );
+        fmt.line("pub fn eq(&self, other: &Self, pool: &ir::ValueListPool) -> bool {");
+        fmt.indent(|fmt| {
+            fmt.line("if ::std::mem::discriminant(self) != ::std::mem::discriminant(other) {");
+            fmt.indent(|fmt| fmt.line("return false;"));
+            fmt.line("}");
+
+            fmt.line("match (self, other) {");
+            fmt.indent(|fmt| {
+                for f in registry.iter() {
+                    let name = format!("&InstructionData::{}", f.name);
+                    let mut members = vec!["opcode"];
+                    let args_eq = if f.typevar_operand.is_none() {
+                        None
+                    } else if f.has_value_list {
+                        members.push("args");
+                        Some("args1.as_slice(pool) == args2.as_slice(pool)")
+                    } else if f.num_value_operands == 1 {
+                        members.push("arg");
+                        Some("arg1 == arg2")
+                    } else {
+                        members.push("args");
+                        Some("args1 == args2")
+                    };
+                    for field in &f.imm_fields {
+                        members.push(field.member);
+                    }
+
+                    let pat1 = members
+                        .iter()
+                        .map(|x| format!("{}: ref {}1", x, x))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    let pat2 = members
+                        .iter()
+                        .map(|x| format!("{}: ref {}2", x, x))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    fmt.line(&format!(
+                        "({} {{ {} }}, {} {{ {} }}) => {{",
+                        name, pat1, name, pat2
+                    ));
+                    fmt.indent(|fmt| {
+                        fmt.line("opcode1 == opcode2");
+                        for field in &f.imm_fields {
+                            fmt.line(&format!("&& {}1 == {}2", field.member, field.member));
+                        }
+                        if let Some(args_eq) = args_eq {
+                            fmt.line(&format!("&& {}", args_eq));
+                        }
+                    });
+                    fmt.line("}");
+                }
+                fmt.line("_ => unreachable!()");
+            });
+            fmt.line("}");
+        });
+        fmt.line("}");
+        fmt.line("");
+
+        fmt.doc_comment(
+            r#"
+            Hash an `InstructionData`.
+
+            This operation requires a reference to a `ValueListPool` to
+            hash the contents of any `ValueLists`.
+            "#,
+        );
+        fmt.line(
+            "pub fn hash<H: ::std::hash::Hasher>(&self, state: &mut H, pool: &ir::ValueListPool) {",
+        );
+        fmt.indent(|fmt| {
+            fmt.line("match *self {");
+            fmt.indent(|fmt| {
+                for f in registry.iter() {
+                    let name = format!("InstructionData::{}", f.name);
+                    let mut members = vec!["opcode"];
+                    let args = if f.typevar_operand.is_none() {
+                        "&()"
+                    } else if f.has_value_list {
+                        members.push("ref args");
+                        "args.as_slice(pool)"
+                    } else if f.num_value_operands == 1 {
+                        members.push("ref arg");
+                        "arg"
+                    } else {
+                        members.push("ref args");
+                        "args"
+                    };
+                    for field in &f.imm_fields {
+                        members.push(field.member);
+                    }
+
+                    fmt.line(&format!("{} {{ {} }} => {{", name, members.join(", ")));
+                    fmt.indent(|fmt| {
+                        fmt.line(
+                            "::std::hash::Hash::hash( &::std::mem::discriminant(self), state);",
+                        );
+                        fmt.line("::std::hash::Hash::hash(&opcode, state);");
+                        for field in &f.imm_fields {
+                            fmt.line(&format!(
+                                "::std::hash::Hash::hash(&{}, state);",
+                                field.member
+                            ));
+                        }
+                        fmt.line(&format!("::std::hash::Hash::hash({}, state);", args));
+                    });
+                    fmt.line("}");
+                }
+            });
+            fmt.line("}");
+        });
+        fmt.line("}");
+    });
+    fmt.line("}");
+}
+
+/// The boolean instruction attributes, in sorted order, with the doc comment
+/// for the generated Rust code.
+const ATTRIBS: [(&str, &str); 9] = [
+    ("can_load", "Can this instruction read from memory?"),
+    ("can_store", "Can this instruction write to memory?"),
+    ("can_trap", "Can this instruction cause a trap?"),
+    ("is_branch", "True for all branch or jump instructions."),
+    ("is_call", "Is this a call instruction?"),
+    ("is_return", "Is this a return instruction?"),
+    (
+        "is_terminator",
+        "True for instructions that terminate the EBB.",
+    ),
+    (
+        "other_side_effects",
+        "Does this instruction have other side effects besides can_*",
+    ),
+    (
+        "writes_cpu_flags",
+        "Does this instruction write to CPU flags?",
+    ),
+];
+
+fn get_attrib(inst: &Instruction, attrib: &str) -> bool {
+    match attrib {
+        "can_load" => inst.can_load,
+        "can_store" => inst.can_store,
+        "can_trap" => inst.can_trap,
+        "is_branch" => inst.is_branch,
+        "is_call" => inst.is_call,
+        "is_return" => inst.is_return,
+        "is_terminator" => inst.is_terminator,
+        "other_side_effects" => inst.other_side_effects,
+        "writes_cpu_flags" => inst.writes_cpu_flags,
+        _ => panic!("unknown instruction attribute {}", attrib),
+    }
+}
+
+/// Generate opcode enumerations.
+///
+/// Return a list of all instructions.
+fn gen_opcodes<'a>(
+    registry: &FormatRegistry,
+    groups: &[&'a InstructionGroup],
+    fmt: &mut Formatter,
+) -> Vec<&'a Instruction> {
+    fmt.doc_comment(
+        r#"
+        An instruction opcode.
+
+        All instructions from all supported ISAs are present.
+        "#,
+    );
+    fmt.line("#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]");
+    let mut instrs = Vec::new();
+
+    // We explicitly set the discriminant of the first variant to 1, which
+    // allows us to take advantage of the NonZero optimization, meaning that
+    // wrapping enums can use the 0 discriminant instead of increasing the size
+    // if the whole type, and so SIZEOF(Option<Opcode>>) == SIZEOF(Opcode)
+    fmt.line("pub enum Opcode {");
+    fmt.indent(|fmt| {
+        for inst in groups.iter().flat_map(|g| g.iter()) {
+            let format = registry.get(inst.format);
+            fmt.doc_comment(&format!("`{}`. ({})", inst, format.name));
+
+            // Document polymorphism.
+            if let Some(ref poly) = inst.polymorphic_info {
+                if poly.use_typevar_operand {
+                    let opnum = inst.value_opnums[format.typevar_operand.unwrap()];
+                    fmt.doc_comment(&format!("Type inferred from {}.", inst.operands_in[opnum]));
+                }
+            }
+
+            // Enum variant itself.
+            if instrs.is_empty() {
+                fmt.line(&format!("{} = 1,", inst.camel_name));
+            } else {
+                fmt.line(&format!("{},", inst.camel_name));
+            }
+            instrs.push(inst);
+        }
+    });
+    fmt.line("}");
+    fmt.line("");
+
+    fmt.line("impl Opcode {");
+    fmt.indent(|fmt| {
+        for &(attrib, doc) in ATTRIBS.iter() {
+            fmt.doc_comment(doc);
+            fmt.line(&format!("pub fn {}(self) -> bool {{", attrib));
+            fmt.indent(|fmt| {
+                let mut m = Match::new("self");
+                for inst in instrs.iter().filter(|inst| get_attrib(inst, attrib)) {
+                    m.arm(
+                        format!("Opcode::{}", inst.camel_name),
+                        Vec::new(),
+                        "true".to_string(),
+                    );
+                }
+                m.arm("_".to_string(), Vec::new(), "false".to_string());
+                fmt.add_match(m);
+            });
+            fmt.line("}");
+            fmt.line("");
+        }
+    });
+    fmt.line("}");
+    fmt.line("");
+
+    // Generate a private opcode_format table.
+    fmt.line(&format!(
+        "const OPCODE_FORMAT: [InstructionFormat; {}] = [",
+        instrs.len()
+    ));
+    fmt.indent(|fmt| {
+        for inst in &instrs {
+            fmt.line(&format!(
+                "InstructionFormat::{}, // {}",
+                registry.get(inst.format).name,
+                inst.name
+            ));
+        }
+    });
+    fmt.line("];");
+    fmt.line("");
+
+    // Generate a private opcode_name function.
+    fmt.line("fn opcode_name(opc: Opcode) -> &\'static str {");
+    fmt.indent(|fmt| {
+        let mut m = Match::new("opc");
+        for inst in &instrs {
+            m.arm(
+                format!("Opcode::{}", inst.camel_name),
+                Vec::new(),
+                format!("\"{}\"", inst.name),
+            );
+        }
+        fmt.add_match(m);
+    });
+    fmt.line("}");
+    fmt.line("");
+
+    // Generate an opcode hash table for looking up opcodes by name.
+    let hash_table = constant_hash::generate_table(instrs.iter(), instrs.len(), |inst| {
+        constant_hash::simple_hash(inst.name)
+    });
+    fmt.line(&format!(
+        "const OPCODE_HASH_TABLE: [Option<Opcode>; {}] = [",
+        hash_table.len()
+    ));
+    fmt.indent(|fmt| {
+        for i in hash_table {
+            match i {
+                Some(inst) => fmt.line(&format!("Some(Opcode::{}),", inst.camel_name)),
+                None => fmt.line("None,"),
+            }
+        }
+    });
+    fmt.line("];");
+    fmt.line("");
+
+    instrs
+}
+
+/// Get the value type constraint for an SSA value operand, where
+/// `ctrl_typevar` is the controlling type variable.
+///
+/// Each operand constraint is represented as a string, one of:
+///
+/// - `Concrete(vt)`, where `vt` is a value type name.
+/// - `Free(idx)` where `idx` is an index into `type_sets`.
+/// - `Same`, `Lane`, `AsBool` for controlling typevar-derived constraints.
+fn get_constraint(
+    operand: &Operand,
+    ctrl_typevar: Option<&TypeVar>,
+    type_sets: &mut UniqueTable<TypeSet>,
+) -> String {
+    let tv = operand
+        .type_var()
+        .expect("constraints only apply to values");
+
+    // A concrete value type.
+    if let Some(typ) = tv.singleton_type() {
+        return format!("Concrete({})", typ.rust_name());
+    }
+
+    if tv.free_typevar().as_ref() != ctrl_typevar {
+        assert!(!tv.is_derived());
+        return format!("Free({})", type_sets.add(&tv.get_typeset()));
+    }
+
+    if let Some(base) = tv.base() {
+        assert!(
+            Some(&base.type_var) == ctrl_typevar,
+            "Not derived from ctrl_typevar"
+        );
+        return camel_case(base.derived_func.name());
+    }
+
+    assert!(Some(tv) == ctrl_typevar);
+    "Same".to_string()
+}
+
+/// Emit the table of ValueTypeSets described by `type_sets`.
+pub fn gen_typesets_table(type_sets: &UniqueTable<TypeSet>, fmt: &mut Formatter) {
+    if type_sets.len() == 0 {
+        return;
+    }
+
+    fmt.comment("Table of value type sets.");
+    assert!(type_sets.len() <= TYPESET_LIMIT, "Too many type sets");
+    fmt.line(&format!(
+        "const TYPE_SETS: [ir::instructions::ValueTypeSet; {}] = [",
+        type_sets.len()
+    ));
+    fmt.indent(|fmt| {
+        for ts in type_sets.iter() {
+            fmt.line("ir::instructions::ValueTypeSet {");
+            fmt.indent(|fmt| {
+                assert!(ts.bitvecs.is_empty(), "Bitvector types are not emitable.");
+                fmt.comment(&ts.to_string());
+                let fields = [
+                    ("lanes", 16, &ts.lanes),
+                    ("ints", 8, &ts.ints),
+                    ("floats", 8, &ts.floats),
+                    ("bools", 8, &ts.bools),
+                ];
+                for &(field, bits, set) in fields.iter() {
+                    let bitset = set
+                        .iter()
+                        .map(|x| 1u64 << x.trailing_zeros())
+                        .fold(0, |acc, bit| acc | bit);
+                    fmt.line(&format!("{}: BitSet::<u{}>({}),", field, bits, bitset));
+                }
+            });
+            fmt.line("},");
+        }
+    });
+    fmt.line("];");
+}
+
+/// Generate value type constraints for all instructions.
+///
+/// - Emit a compact constant table of ValueTypeSet objects.
+/// - Emit a compact constant table of OperandConstraint objects.
+/// - Emit an opcode-indexed table of instruction constraints.
+fn gen_type_constraints(instrs: &[&Instruction], fmt: &mut Formatter) {
+    // Table of TypeSet instances.
+    let mut type_sets = UniqueTable::new();
+
+    // Table of operand constraint sequences (as tuples). Each operand
+    // constraint is represented as a string, one of:
+    // - `Concrete(vt)`, where `vt` is a value type name.
+    // - `Free(idx)` where `idx` isan index into `type_sets`.
+    // - `Same`, `Lane`, `AsBool` for controlling typevar-derived constraints.
+    let mut operand_seqs = UniqueSeqTable::new();
+
+    // Preload table with constraints for typical binops.
+    operand_seqs.add(&vec!["Same".to_string(); 3]);
+
+    fmt.comment("Table of opcode constraints.");
+    fmt.line(&format!(
+        "const OPCODE_CONSTRAINTS: [OpcodeConstraints; {}] = [",
+        instrs.len()
+    ));
+    fmt.indent(|fmt| {
+        for inst in instrs {
+            // Collect constraints for the value results, not including
+            // `variable_args` results which are always special cased.
+            let mut constraints = Vec::new();
+            let (ctrl_typevar, ctrl_typeset) = match inst.polymorphic_info {
+                Some(ref poly) => (
+                    Some(&poly.ctrl_typevar),
+                    type_sets.add(&poly.ctrl_typevar.get_typeset()),
+                ),
+                None => (None, TYPESET_LIMIT),
+            };
+            for &index in &inst.value_results {
+                constraints.push(get_constraint(
+                    &inst.operands_out[index],
+                    ctrl_typevar,
+                    &mut type_sets,
+                ));
+            }
+            for &index in &inst.value_opnums {
+                constraints.push(get_constraint(
+                    &inst.operands_in[index],
+                    ctrl_typevar,
+                    &mut type_sets,
+                ));
+            }
+            let offset = operand_seqs.add(&constraints);
+
+            let fixed_results = inst.value_results.len();
+            let fixed_values = inst.value_opnums.len();
+
+            // Can the controlling type variable be inferred from the
+            // designated operand?
+            let use_typevar_operand = match inst.polymorphic_info {
+                Some(ref poly) => poly.use_typevar_operand,
+                None => false,
+            };
+            // Can the controlling type variable be inferred from the result?
+            let use_result = fixed_results > 0
+                && inst.operands_out[inst.value_results[0]].type_var() == ctrl_typevar;
+            // Are we required to use the designated operand instead of the
+            // result?
+            let requires_typevar_operand = use_typevar_operand && !use_result;
+
+            fmt.comment(&format!(
+                "{}: fixed_results={}, use_typevar_operand={}, requires_typevar_operand={}, fixed_values={}",
+                inst.camel_name,
+                fixed_results,
+                py_bool(use_typevar_operand),
+                py_bool(requires_typevar_operand),
+                fixed_values
+            ));
+            fmt.comment(&format!(
+                "Constraints=[{}]",
+                constraints
+                    .iter()
+                    .map(|c| format!("'{}'", c))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+            if let Some(ctrl_typevar) = ctrl_typevar {
+                fmt.comment(&format!(
+                    "Polymorphic over {}",
+                    ctrl_typevar.get_typeset()
+                ));
+            }
+
+            // Compute the bit field encoding, c.f. instructions.rs.
+            assert!(fixed_results < 8, "Bit field encoding too tight");
+            let mut flags = fixed_results;
+            if use_typevar_operand {
+                flags |= 8;
+            }
+            if requires_typevar_operand {
+                flags |= 0x10;
+            }
+            assert!(fixed_values < 8, "Bit field encoding too tight");
+            flags |= fixed_values << 5;
+
+            fmt.line("OpcodeConstraints {");
+            fmt.indent(|fmt| {
+                fmt.line(&format!("flags: {:#04x},", flags));
+                fmt.line(&format!("typeset_offset: {},", ctrl_typeset));
+                fmt.line(&format!("constraint_offset: {},", offset));
+            });
+            fmt.line("},");
+        }
+    });
+    fmt.line("];");
+    fmt.line("");
+
+    gen_typesets_table(&type_sets, fmt);
+    fmt.line("");
+
+    fmt.comment("Table of operand constraint sequences.");
+    fmt.line(&format!(
+        "const OPERAND_CONSTRAINTS: [OperandConstraint; {}] = [",
+        operand_seqs.len()
+    ));
+    fmt.indent(|fmt| {
+        for constraint in operand_seqs.iter() {
+            fmt.line(&format!("OperandConstraint::{},", constraint));
+        }
+    });
+    fmt.line("];");
+}
+
+/// Format a boolean the way the generated comments have always shown them.
+fn py_bool(b: bool) -> &'static str {
+    if b {
+        "True"
+    } else {
+        "False"
+    }
+}
+
+/// Emit member initializers for an instruction format.
+fn gen_member_inits(format: &InstructionFormat, fmt: &mut Formatter) {
+    // Immediate operands.
+    // We have local variables with the same names as the members.
+    for field in &format.imm_fields {
+        fmt.line(&format!("{}: {},", field.member, field.member));
+    }
+
+    // Value operands.
+    if format.has_value_list {
+        fmt.line("args,");
+    } else if format.num_value_operands == 1 {
+        fmt.line("arg: arg0,");
+    } else if format.num_value_operands > 1 {
+        let args = (0..format.num_value_operands)
+            .map(|i| format!("arg{}", i))
+            .collect::<Vec<_>>()
+            .join(", ");
+        fmt.line(&format!("args: [{}],", args));
+    }
+}
+
+/// Emit a method for creating and inserting an instruction of format
+/// `format`.
+///
+/// All instruction formats take an `opcode` argument and a `ctrl_typevar`
+/// argument for deducing the result types.
+fn gen_format_constructor(format: &InstructionFormat, fmt: &mut Formatter) {
+    // Construct method arguments.
+    let mut args = vec![
+        "self".to_string(),
+        "opcode: Opcode".to_string(),
+        "ctrl_typevar: Type".to_string(),
+    ];
+
+    // Normal operand arguments. Start with the immediate operands.
+    for field in &format.imm_fields {
+        args.push(format!("{}: {}", field.member, field.kind.rust_type));
+    }
+
+    // Then the value operands.
+    if format.has_value_list {
+        // Take all value arguments as a finished value list. The value lists
+        // are created by the individual instruction constructors.
+        args.push("args: ir::ValueList".to_string());
+    } else {
+        // Take a fixed number of value operands.
+        for i in 0..format.num_value_operands {
+            args.push(format!("arg{}: Value", i));
+        }
+    }
+
+    let proto = format!(
+        "{}({}) -> (Inst, &'f mut ir::DataFlowGraph)",
+        format.name,
+        args.join(", ")
+    );
+
+    fmt.doc_comment(&format.to_string());
+    fmt.line("#[allow(non_snake_case)]");
+    fmt.line(&format!("fn {} {{", proto));
+    fmt.indent(|fmt| {
+        // Generate the instruction data.
+        fmt.line(&format!(
+            "let data = ir::InstructionData::{} {{",
+            format.name
+        ));
+        fmt.indent(|fmt| {
+            fmt.line("opcode,");
+            gen_member_inits(format, fmt);
+        });
+        fmt.line("};");
+        fmt.line("self.build(data, ctrl_typevar)");
+    });
+    fmt.line("}");
+}
+
+/// Emit a method for generating the instruction `inst`.
+///
+/// The method will create and insert an instruction, then return the result
+/// values, or the instruction reference itself for instructions that don't
+/// have results.
+fn gen_inst_builder(inst: &Instruction, format: &InstructionFormat, fmt: &mut Formatter) {
+    // Construct method arguments.
+    let mut args = vec![if format.has_value_list {
+        "mut self".to_string()
+    } else {
+        "self".to_string()
+    }];
+
+    // The controlling type variable will be inferred from the input values if
+    // possible. Otherwise, it is the first method argument.
+    let explicit_ctrl_typevar = match inst.polymorphic_info {
+        Some(ref poly) if !poly.use_typevar_operand => Some(&poly.ctrl_typevar),
+        _ => None,
+    };
+    if let Some(ctrl_typevar) = explicit_ctrl_typevar {
+        args.push(format!("{}: ir::Type", ctrl_typevar.name()));
+    }
+
+    let mut tmpl_types = Vec::new();
+    let mut into_args = Vec::new();
+    for op in &inst.operands_in {
+        let t = match op.kind.fields {
+            OperandKindFields::ImmValue | OperandKindFields::ImmEnum(_) => {
+                let t = format!("T{}{}", tmpl_types.len() + 1, op.kind.name);
+                tmpl_types.push(format!("{}: Into<{}>", t, op.kind.rust_type));
+                into_args.push(op.name);
+                t
+            }
+            _ => op.kind.rust_type.clone(),
+        };
+        args.push(format!("{}: {}", op.name, t));
+    }
+
+    // Return the inst reference for result-less instructions.
+    let rtype = match inst.value_results.len() {
+        0 => "Inst".to_string(),
+        1 => "Value".to_string(),
+        n => format!("({})", vec!["Value"; n].join(", ")),
+    };
+
+    let tmpl = if !tmpl_types.is_empty() {
+        format!("<{}>", tmpl_types.join(", "))
+    } else {
+        "".to_string()
+    };
+
+    let proto = format!(
+        "{}{}({}) -> {}",
+        inst.snake_name(),
+        tmpl,
+        args.join(", "),
+        rtype
+    );
+
+    fmt.doc_comment(&format!("`{}`\n\n{}", inst, inst.blurb()));
+    fmt.line("#[allow(non_snake_case)]");
+    fmt.line(&format!("fn {} {{", proto));
+    fmt.indent(|fmt| {
+        // Convert all of the `Into<>` arguments.
+        for arg in &into_args {
+            fmt.line(&format!("let {} = {}.into();", arg, arg));
+        }
+
+        // Arguments for instruction constructor.
+        let mut args = vec![format!("Opcode::{}", inst.camel_name)];
+
+        match inst.polymorphic_info {
+            Some(ref poly) if !poly.use_typevar_operand => {
+                // This was an explicit method argument.
+                args.push(poly.ctrl_typevar.name().to_string());
+            }
+            Some(_) => {
+                // Infer the controlling type variable from the input operands.
+                let opnum = inst.value_opnums[format.typevar_operand.unwrap()];
+                fmt.line(&format!(
+                    "let ctrl_typevar = self.data_flow_graph().value_type({});",
+                    inst.operands_in[opnum].name
+                ));
+                // The format constructor will resolve the result types from
+                // the type var.
+                args.push("ctrl_typevar".to_string());
+            }
+            None => {
+                // No controlling type variable needed.
+                args.push("types::VOID".to_string());
+            }
+        }
+
+        // Now add all of the immediate operands to the constructor arguments.
+        for &opnum in &inst.imm_opnums {
+            args.push(inst.operands_in[opnum].name.to_string());
+        }
+
+        // Finally, the value operands.
+        if format.has_value_list {
+            // We need to build a value list with all the arguments.
+            fmt.line("let mut vlist = ir::ValueList::default();");
+            args.push("vlist".to_string());
+            fmt.line("{");
+            fmt.indent(|fmt| {
+                fmt.line("let pool = &mut self.data_flow_graph_mut().value_lists;");
+                for op in &inst.operands_in {
+                    if op.is_value() {
+                        fmt.line(&format!("vlist.push({}, pool);", op.name));
+                    } else if op.is_varargs() {
+                        fmt.line(&format!("vlist.extend({}.iter().cloned(), pool);", op.name));
+                    }
+                }
+            });
+            fmt.line("}");
+        } else {
+            // With no value list, we're guaranteed to just have a set of fixed
+            // value operands.
+            for &opnum in &inst.value_opnums {
+                args.push(inst.operands_in[opnum].name.to_string());
+            }
+        }
+
+        // Call to the format constructor,
+        let fcall = format!("self.{}({})", format.name, args.join(", "));
+
+        if inst.value_results.is_empty() {
+            fmt.line(&format!("{}.0", fcall));
+            return;
+        }
+
+        fmt.line(&format!("let (inst, dfg) = {};", fcall));
+        if inst.value_results.len() == 1 {
+            fmt.line("dfg.first_result(inst)");
+            return;
+        }
+
+        fmt.line(&format!(
+            "let results = &dfg.inst_results(inst)[0..{}];",
+            inst.value_results.len()
+        ));
+        fmt.line(&format!(
+            "({})",
+            (0..inst.value_results.len())
+                .map(|i| format!("results[{}]", i))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    });
+    fmt.line("}")
+}
+
+/// Generate a Builder trait with methods for all instructions.
+fn gen_builder(registry: &FormatRegistry, instrs: &[&Instruction], fmt: &mut Formatter) {
+    fmt.doc_comment(
+        r#"
+        Convenience methods for building instructions.
+
+        The `InstBuilder` trait has one method per instruction opcode for
+        conveniently constructing the instruction with minimum arguments.
+        Polymorphic instructions infer their result types from the input
+        arguments when possible. In some cases, an explicit `ctrl_typevar`
+        argument is required.
+
+        The opcode methods return the new instruction's result values, or
+        the `Inst` itself for instructions that don't have any results.
+
+        There is also a method per instruction format. These methods all
+        return an `Inst`.
+        "#,
+    );
+    fmt.line("pub trait InstBuilder<'f>: InstBuilderBase<'f> {");
+    fmt.indent(|fmt| {
+        for inst in instrs {
+            gen_inst_builder(inst, registry.get(inst.format), fmt);
+        }
+        for format in registry.iter() {
+            gen_format_constructor(format, fmt);
+        }
+    });
+    fmt.line("}");
+}
+
+/// Generate `opcode_filename` and `inst_builder_filename` in `out_dir`, for
+/// the shared instructions and the instructions of all the `isas`.
+pub fn generate(
+    shared_defs: &base::Definitions,
+    isas: &[TargetIsa],
+    opcode_filename: &str,
+    inst_builder_filename: &str,
+    out_dir: &str,
+) -> Result<(), error::Error> {
+    let registry = &shared_defs.format_registry;
+    let groups = shared_defs.instruction_groups(isas);
+
+    // opcodes.rs
+    let mut fmt = Formatter::new();
+    gen_formats(registry, &mut fmt);
+    gen_instruction_data(registry, &mut fmt);
+    fmt.line("");
+    gen_instruction_data_impl(registry, &mut fmt);
+    fmt.line("");
+    let instrs = gen_opcodes(registry, &groups, &mut fmt);
+    gen_type_constraints(&instrs, &mut fmt);
+    fmt.update_file(opcode_filename, out_dir)?;
+
+    // inst_builder.rs
+    let mut fmt = Formatter::new();
+    gen_builder(registry, &instrs, &mut fmt);
+    fmt.update_file(inst_builder_filename, out_dir)?;
+
+    Ok(())
+}
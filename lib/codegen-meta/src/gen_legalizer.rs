@@ -0,0 +1,591 @@
+//! Generate legalizer transformations.
+//!
+//! The transformations defined in the `base::legalize` module are all of the
+//! macro-expansion form where the input pattern is a single instruction. We
+//! generate a Rust function for each `XFormGroup` which takes a `Cursor`
+//! pointing at the instruction to be legalized. The expanded destination
+//! pattern replaces the input instruction.
+
+use std::collections::BTreeMap;
+
+use base;
+use cdsl::ast::{Def, Expr, Var};
+use cdsl::formats::FormatRegistry;
+use cdsl::isa::TargetIsa;
+use cdsl::type_inference::{ti_rtl, Constraint, TypeEnvironment};
+use cdsl::typevar::{DerivedFunc, TypeSet, TypeVar};
+use cdsl::xform::{Rtl, SymbolTable, XForm, XFormGroup, XFormGroupIndex, XFormGroups};
+use error;
+use gen_inst::gen_typesets_table;
+use srcgen::Formatter;
+use unique_table::UniqueTable;
+
+/// Given a `XForm` build a list of runtime type checks necessary to determine
+/// if it applies. We have 2 types of runtime checks:
+///
+/// 1. Type variable tv belongs to typeset T - needed for free type variables
+///    whose typeset is constrained by their use in the dst pattern.
+/// 2. tv1 == tv2 where tv1 and tv2 are derived type variables - caused by
+///    unification of non-bijective functions.
+fn get_runtime_typechecks(xform: &XForm) -> Vec<Constraint> {
+    let mut checks = Vec::new();
+
+    // 1) Perform type inference only on a copy of the source pattern.
+    //    Accumulate any free type variables that have a different inferred
+    //    type in src, compared to the type inferred for both src and dst.
+    //    The types bound to the instructions are checked by the instruction
+    //    predicates, so they are left out of the copy.
+    let src_copy = Rtl::new(
+        xform
+            .src
+            .rtl
+            .iter()
+            .map(|def| {
+                let mut def = def.clone();
+                def.expr.value_types.clear();
+                def
+            })
+            .collect(),
+    );
+    let src_symbols = SymbolTable::new_inputs(&src_copy);
+    let mut src_env = TypeEnvironment::new();
+    ti_rtl(&src_copy, &src_symbols, &mut src_env).unwrap_or_else(|err| panic!("{}", err));
+
+    for symbol in xform.symbols.iter() {
+        if !symbol.has_free_typevar() || !xform.ti.vars().contains(symbol.type_var()) {
+            continue;
+        }
+
+        // In Rust the local variable containing a free type variable
+        // associated with the variable `v` is named `typeof_v`. We rely on the
+        // type variables having the same name.
+        assert_eq!(format!("typeof_{}", symbol.name), symbol.type_var().name());
+
+        let src_symbol = match src_symbols.lookup(symbol.name) {
+            Some(src_symbol) => src_symbol,
+            // We can have singleton variables defined only in dst. Ignore
+            // them.
+            None => {
+                assert!(symbol.type_var().singleton_type().is_some());
+                continue;
+            }
+        };
+
+        let src_ts = src_env.get_equivalent(src_symbol.type_var()).get_typeset();
+        let xform_ts = symbol.type_var().get_typeset();
+
+        assert!(xform_ts.is_subset(&src_ts));
+        if src_ts != xform_ts {
+            checks.push(Constraint::in_typeset(symbol.type_var().clone(), xform_ts));
+        }
+    }
+
+    // 2,3) Add any constraints that appear in the type environment of the
+    // transformation.
+    checks.extend(xform.ti.constraints.iter().cloned());
+
+    checks
+}
+
+/// Build an expression of type `Option<Type>` corresponding to a concrete
+/// type transformed by the sequence of derivation functions in `tv`.
+///
+/// We are using `Option<Type>`, as some constraints may cause an
+/// over/underflow on patterns that do not match them. We want to capture
+/// this without panicking at runtime.
+fn build_derived_expr(tv: &TypeVar) -> String {
+    let base = match tv.base() {
+        Some(base) => base,
+        None => {
+            assert!(tv.name().starts_with("typeof_"));
+            return format!("Some({})", tv.name());
+        }
+    };
+
+    let base_expr = build_derived_expr(&base.type_var);
+    match base.derived_func {
+        DerivedFunc::LaneOf => format!("{}.map(|t: ir::Type| t.lane_type())", base_expr),
+        DerivedFunc::AsBool => format!("{}.map(|t: ir::Type| t.as_bool())", base_expr),
+        DerivedFunc::HalfWidth => format!("{}.and_then(|t: ir::Type| t.half_width())", base_expr),
+        DerivedFunc::DoubleWidth => {
+            format!("{}.and_then(|t: ir::Type| t.double_width())", base_expr)
+        }
+        DerivedFunc::HalfVector => {
+            format!("{}.and_then(|t: ir::Type| t.half_vector())", base_expr)
+        }
+        DerivedFunc::DoubleVector => format!("{}.and_then(|t: ir::Type| t.by(2))", base_expr),
+        DerivedFunc::ToBitVec => panic!("Unknown derived function {}", tv),
+    }
+}
+
+/// Emit Rust code for the given check.
+///
+/// The emitted code is a statement redefining the `predicate` variable like
+/// this:
+///
+/// ```ignore
+/// let predicate = predicate && ...
+/// ```
+fn emit_runtime_typecheck(
+    check: &Constraint,
+    fmt: &mut Formatter,
+    type_sets: &mut UniqueTable<TypeSet>,
+) {
+    match *check {
+        Constraint::InTypeset(ref tv, ref ts) => {
+            assert!(!tv.is_derived());
+            let index = type_sets.add(ts);
+            fmt.comment(&format!("{} must belong to {}", tv.name(), ts));
+            fmt.line(&format!(
+                "let predicate = predicate && TYPE_SETS[{}].contains({});",
+                index,
+                tv.name()
+            ));
+        }
+        Constraint::TypesEqual(ref tv1, ref tv2) | Constraint::WiderOrEq(ref tv1, ref tv2) => {
+            let comparison = match *check {
+                Constraint::TypesEqual(..) => "a == b",
+                _ => "a.wider_or_equal(b)",
+            };
+            fmt.line(&format!(
+                "let predicate = predicate && match ({}, {}) {{",
+                build_derived_expr(tv1),
+                build_derived_expr(tv2)
+            ));
+            fmt.indent(|fmt| {
+                fmt.line(&format!("(Some(a), Some(b)) => {},", comparison));
+                fmt.comment("On overflow, constraint doesn't appply");
+                fmt.line("_ => false,");
+            });
+            fmt.line("};");
+        }
+        Constraint::SameWidth(..) => panic!("Unknown check {}", check),
+    }
+}
+
+/// Given a `Def` node, emit code that extracts all the instruction fields
+/// from `pos.func.dfg[inst]`.
+///
+/// Create local variables named after the `Var` instances in `def`.
+///
+/// Also create a local variable named `predicate` with the value of the
+/// evaluated instruction predicate, or `true` if the node has no predicate.
+///
+/// Returns true if the instruction arguments were not detached, expecting a
+/// replacement instruction to overwrite the original.
+fn unwrap_inst(
+    def: &Def,
+    xform: &XForm,
+    format_registry: &FormatRegistry,
+    fmt: &mut Formatter,
+) -> bool {
+    fmt.comment(&format!("Unwrap {}", def));
+    let apply = &def.expr;
+    let inst = &apply.inst;
+    let iform = format_registry.get(inst.format);
+    let nvops = iform.num_value_operands;
+
+    // The tuple of locals to extract is the `Var` instances in `apply.args`.
+    let arg_names = apply
+        .args
+        .iter()
+        .map(|arg| match *arg {
+            Expr::Var(var) => var.name,
+            Expr::Literal(_) => "_",
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    fmt.line(&format!(
+        "let ({}, predicate) = if let ir::InstructionData::{} {{",
+        arg_names, iform.name
+    ));
+    fmt.indent(|fmt| {
+        // Fields are encoded directly.
+        for field in &iform.imm_fields {
+            fmt.line(&format!("{},", field.member));
+        }
+        if nvops == 1 {
+            fmt.line("arg,");
+        } else if iform.has_value_list || nvops > 1 {
+            fmt.line("ref args,");
+        }
+        fmt.line("..");
+
+        fmt.outdented_line("} = pos.func.dfg[inst] {");
+        fmt.line("let func = &pos.func;");
+        if iform.has_value_list {
+            fmt.line("let args = args.as_slice(&func.dfg.value_lists);");
+        } else if nvops == 1 {
+            fmt.line("let args = [arg];");
+        }
+
+        // Generate the values for the tuple.
+        fmt.line("(");
+        fmt.indent(|fmt| {
+            for (opnum, op) in inst.operands_in.iter().enumerate() {
+                if op.is_immediate() {
+                    let n = inst.imm_opnums.iter().position(|&i| i == opnum).unwrap();
+                    fmt.line(&format!("{},", iform.imm_fields[n].member));
+                } else if op.is_value() {
+                    let n = inst.value_opnums.iter().position(|&i| i == opnum).unwrap();
+                    fmt.line(&format!("func.dfg.resolve_aliases(args[{}]),", n));
+                }
+            }
+
+            // Evaluate the instruction predicate, if any.
+            match apply.inst_predicate_with_ctrl_typevar(format_registry) {
+                Some(pred) => fmt.line(&pred.rust_predicate(0)),
+                None => fmt.line("true"),
+            }
+        });
+        fmt.line(")");
+
+        fmt.outdented_line("} else {");
+        fmt.line(r#"unreachable!("bad instruction format")"#);
+    });
+    fmt.line("};");
+
+    // Get the types of any variables where it is needed.
+    for &var in &apply.value_args() {
+        if xform.symbols.get(var).has_free_typevar() {
+            fmt.line(&format!(
+                "let typeof_{0} = pos.func.dfg.value_type({0});",
+                var
+            ));
+        }
+    }
+
+    // If the node has results, detach the values and place them in locals.
+    if def.defs.is_empty() {
+        return false;
+    }
+
+    let dst_def = &xform.dst.rtl[xform.symbols.get(def.defs[0]).dst_def.unwrap()];
+    if def.defs == dst_def.defs {
+        // Special case: The instruction replacing node defines the exact same
+        // values.
+        fmt.comment(&format!("Results handled by {}.", dst_def));
+        return true;
+    }
+
+    // Boring case: Detach the result values, capture them in locals.
+    for var in &def.defs {
+        fmt.line(&format!("let {};", var));
+    }
+    fmt.line("{");
+    fmt.indent(|fmt| {
+        fmt.line("let r = pos.func.dfg.inst_results(inst);");
+        for (i, var) in def.defs.iter().enumerate() {
+            fmt.line(&format!("{} = r[{}];", var, i));
+        }
+    });
+    fmt.line("}");
+    for &var in &def.defs {
+        if xform.symbols.get(var).has_free_typevar() {
+            fmt.line(&format!(
+                "let typeof_{0} = pos.func.dfg.value_type({0});",
+                var
+            ));
+        }
+    }
+
+    false
+}
+
+/// Format `vars` as a single variable name or a tuple.
+fn wrap_tup(vars: &[Var]) -> String {
+    if vars.len() == 1 {
+        return vars[0].to_string();
+    }
+    let names = vars.iter().map(|var| var.to_string()).collect::<Vec<_>>();
+    format!("({})", names.join(", "))
+}
+
+/// Determine if `def` represents one of the value splitting instructions:
+/// `isplit` or `vsplit`. These instructions are lowered specially by the
+/// `legalize::split` module.
+fn is_value_split(def: &Def) -> bool {
+    let name = def.expr.inst.name;
+    def.defs.len() == 2 && (name == "isplit" || name == "vsplit")
+}
+
+fn emit_dst_inst(def: &Def, xform: &XForm, fmt: &mut Formatter) {
+    let mut replaced_inst = false;
+
+    if is_value_split(def) {
+        // Split instructions are not emitted with the builder, but by calling
+        // special functions in the `legalizer::split` module. These functions
+        // will eliminate concat-split patterns.
+        fmt.line("let curpos = pos.position();");
+        fmt.line("let srcloc = pos.srcloc();");
+        fmt.line(&format!(
+            "let {} = split::{}(pos.func, cfg, curpos, srcloc, {});",
+            wrap_tup(&def.defs),
+            def.expr.inst.snake_name(),
+            def.expr.args[0]
+        ));
+        return;
+    }
+
+    let builder = if def.defs.is_empty() {
+        // This node doesn't define any values, so just insert the new
+        // instruction.
+        "pos.ins()".to_string()
+    } else {
+        let symbol = xform.symbols.get(def.defs[0]);
+        match symbol.src_def {
+            Some(src_def) if def.defs == xform.src.rtl[src_def].defs => {
+                // The replacement instruction defines the exact same values as
+                // the source pattern. Unwrapping would have left the results
+                // intact. Replace the whole instruction.
+                replaced_inst = true;
+                format!("let {} = pos.func.dfg.replace(inst)", wrap_tup(&def.defs))
+            }
+            _ => {
+                // Insert a new instruction.
+                let mut builder = format!("let {} = pos.ins()", wrap_tup(&def.defs));
+                // We may want to reuse some of the detached output values.
+                let is_output = |var: &Var| xform.symbols.get(*var).is_output();
+                if def.defs.len() == 1 && symbol.is_output() {
+                    // Reuse the single source result value.
+                    builder += &format!(".with_result({})", def.defs[0]);
+                } else if def.defs.iter().any(&is_output) {
+                    // We have some output values to be reused.
+                    let array = def
+                        .defs
+                        .iter()
+                        .map(|var| {
+                            if is_output(var) {
+                                format!("Some({})", var)
+                            } else {
+                                "None".to_string()
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    builder += &format!(".with_results([{}])", array);
+                }
+                builder
+            }
+        }
+    };
+
+    fmt.line(&format!(
+        "{}.{};",
+        builder,
+        def.expr.rust_builder(&def.defs, &xform.symbols)
+    ));
+
+    // If we just replaced an instruction, we need to bump the cursor so
+    // following instructions are inserted *after* the replaced instruction.
+    if replaced_inst {
+        fmt.line("if pos.current_inst() == Some(inst) {");
+        fmt.indent(|fmt| fmt.line("pos.next_inst();"));
+        fmt.line("}");
+    }
+}
+
+/// Emit code for `xform`, assuming that the opcode of xform's root
+/// instruction has already been matched.
+///
+/// `inst: Inst` is the variable to be replaced. It is pointed to by
+/// `pos: Cursor`. `dfg: DataFlowGraph` is available and mutable.
+fn gen_xform(
+    xform: &XForm,
+    format_registry: &FormatRegistry,
+    fmt: &mut Formatter,
+    type_sets: &mut UniqueTable<TypeSet>,
+) {
+    // Unwrap the source instruction, create local variables for the input
+    // variables.
+    let replace_inst = unwrap_inst(&xform.src.rtl[0], xform, format_registry, fmt);
+
+    // Emit any runtime checks. These will rebind `predicate` emitted by
+    // unwrap_inst().
+    for check in get_runtime_typechecks(xform) {
+        emit_runtime_typecheck(&check, fmt, type_sets);
+    }
+
+    // Guard the actual expansion by `predicate`.
+    fmt.line("if predicate {");
+    fmt.indent(|fmt| {
+        // If we're going to delete `inst`, we need to detach its results first
+        // so they can be reattached during pattern expansion.
+        if !replace_inst {
+            fmt.line("pos.func.dfg.clear_results(inst);");
+        }
+
+        // Emit the destination pattern.
+        for def in &xform.dst.rtl {
+            emit_dst_inst(def, xform, fmt);
+        }
+
+        // Delete the original instruction if we didn't have an opportunity to
+        // replace it.
+        if !replace_inst {
+            fmt.line("let removed = pos.remove_inst();");
+            fmt.line("debug_assert_eq!(removed, inst);");
+        }
+        fmt.line("return true;");
+    });
+    fmt.line("}");
+}
+
+fn gen_xform_group(
+    group: &XFormGroup,
+    groups: &XFormGroups,
+    format_registry: &FormatRegistry,
+    fmt: &mut Formatter,
+    type_sets: &mut UniqueTable<TypeSet>,
+) {
+    fmt.doc_comment("Legalize `inst`.");
+    fmt.line("#[allow(unused_variables,unused_assignments,non_snake_case)]");
+    fmt.line(&format!("pub fn {}(", group.name));
+    fmt.indent(|fmt| {
+        fmt.line("inst: ir::Inst,");
+        fmt.line("func: &mut ir::Function,");
+        fmt.line("cfg: &mut ::flowgraph::ControlFlowGraph,");
+        fmt.line("isa: &::isa::TargetIsa,");
+    });
+    fmt.line(") -> bool {");
+    fmt.indent(|fmt| {
+        fmt.line("use ir::InstBuilder;");
+        fmt.line("use cursor::{Cursor, FuncCursor};");
+        fmt.line("let mut pos = FuncCursor::new(func).at_inst(inst);");
+        fmt.line("pos.use_srcloc(inst);");
+
+        // Group the xforms by opcode so we can generate a big switch.
+        // Preserve ordering.
+        let mut xforms: BTreeMap<&str, Vec<&XForm>> = BTreeMap::new();
+        for xform in &group.xforms {
+            let camel_name = &xform.src.rtl[0].expr.inst.camel_name;
+            xforms.entry(camel_name).or_default().push(xform);
+        }
+
+        fmt.line("{");
+        fmt.indent(|fmt| {
+            fmt.line("match pos.func.dfg[inst].opcode() {");
+            fmt.indent(|fmt| {
+                for (camel_name, xforms) in &xforms {
+                    fmt.line(&format!("ir::Opcode::{} => {{", camel_name));
+                    fmt.indent(|fmt| {
+                        for xform in xforms {
+                            gen_xform(xform, format_registry, fmt, type_sets);
+                        }
+                    });
+                    fmt.line("}");
+                }
+
+                // Emit the custom transforms. The Rust compiler will complain
+                // about any overlap with the normal xforms.
+                for &(ref inst, funcname) in &group.custom {
+                    fmt.line(&format!("ir::Opcode::{} => {{", inst.camel_name));
+                    fmt.indent(|fmt| {
+                        fmt.line(&format!("if {}(inst, pos.func, cfg, isa) {{", funcname));
+                        fmt.indent(|fmt| fmt.line("return true;"));
+                        fmt.line("}");
+                    });
+                    fmt.line("}");
+                }
+
+                // We'll assume there are uncovered opcodes.
+                fmt.line("_ => {},");
+            });
+            fmt.line("}");
+        });
+        fmt.line("}");
+
+        // If we fall through, nothing was expanded. Call the chain if any.
+        match group.chain {
+            Some(chain) => fmt.line(&format!(
+                "{}(inst, pos.func, cfg, isa)",
+                groups.get(chain).rust_name()
+            )),
+            None => fmt.line("false"),
+        }
+    });
+    fmt.line("}");
+}
+
+/// Generate legalization functions for `isa` and add any shared `XFormGroup`s
+/// encountered to `shared_groups`.
+///
+/// Generate `TYPE_SETS` and `LEGALIZE_ACTIONS` tables.
+fn gen_isa(
+    isa: &TargetIsa,
+    shared_defs: &base::Definitions,
+    shared_groups: &mut BTreeMap<&'static str, XFormGroupIndex>,
+    fmt: &mut Formatter,
+) {
+    let groups = &shared_defs.transform_groups;
+    let legalize_codes = isa.legalize_codes();
+
+    let mut type_sets = UniqueTable::new();
+    for &index in &legalize_codes {
+        let group = groups.get(index);
+        match group.isa {
+            None => {
+                shared_groups.insert(group.name, index);
+            }
+            Some(isa_name) => {
+                assert_eq!(isa_name, isa.name);
+                gen_xform_group(
+                    group,
+                    groups,
+                    &shared_defs.format_registry,
+                    fmt,
+                    &mut type_sets,
+                );
+            }
+        }
+    }
+
+    gen_typesets_table(&type_sets, fmt);
+
+    fmt.line(&format!(
+        "pub static LEGALIZE_ACTIONS: [isa::Legalize; {}] = [",
+        legalize_codes.len()
+    ));
+    fmt.indent(|fmt| {
+        for &index in &legalize_codes {
+            fmt.line(&format!("{},", groups.get(index).rust_name()));
+        }
+    });
+    fmt.line("];");
+}
+
+/// Generate the `<filename_prefix>-<isa>.rs` files for each ISA and the
+/// `legalizer_filename` file for the shared transformation groups.
+pub fn generate(
+    shared_defs: &base::Definitions,
+    isas: &[TargetIsa],
+    filename_prefix: &str,
+    legalizer_filename: &str,
+    out_dir: &str,
+) -> Result<(), error::Error> {
+    let mut shared_groups = BTreeMap::new();
+
+    for isa in isas {
+        let mut fmt = Formatter::new();
+        gen_isa(isa, shared_defs, &mut shared_groups, &mut fmt);
+        fmt.update_file(&format!("{}-{}.rs", filename_prefix, isa.name), out_dir)?;
+    }
+
+    // Shared xform groups, sorted by name.
+    let mut fmt = Formatter::new();
+    let mut type_sets = UniqueTable::new();
+    for &index in shared_groups.values() {
+        gen_xform_group(
+            shared_defs.transform_groups.get(index),
+            &shared_defs.transform_groups,
+            &shared_defs.format_registry,
+            &mut fmt,
+            &mut type_sets,
+        );
+    }
+    gen_typesets_table(&type_sets, &mut fmt);
+    fmt.update_file(legalizer_filename, out_dir)?;
+
+    Ok(())
+}
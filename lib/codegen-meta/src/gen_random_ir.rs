@@ -0,0 +1,401 @@
+//! Generate the instruction tables of the random IR generator.
+//!
+//! The `random_ir` module of `cranelift-codegen` builds random functions for
+//! fuzzing. This generates the part of it that depends on the instruction
+//! definitions: a function inserting each *candidate*, an instruction with
+//! concrete scalar types for its controlling type variable and its operands,
+//! and the lists of candidates supported by each CPU mode, as found by the
+//! coverage analysis.
+//!
+//! Control flow, calls and the instructions using entities or value lists
+//! are left to the hand-written part of the generator, or not generated at
+//! all.
+
+use std::collections::HashMap;
+
+use base;
+use cdsl::formats::FormatRegistry;
+use cdsl::instructions::Instruction;
+use cdsl::isa::TargetIsa;
+use cdsl::operands::{OperandKind, OperandKindFields};
+use cdsl::type_inference::{subst, Constraint};
+use cdsl::types::ValueType;
+use cdsl::typevar::TypeVar;
+use error;
+use gen_coverage;
+use srcgen::Formatter;
+
+/// Instructions which are only meaningful around register allocation.
+const EXCLUDED: [&str; 4] = ["spill", "fill", "copy_special", "copy_nop"];
+
+/// An instruction with concrete types, which can be inserted by the random
+/// IR generator.
+struct Candidate<'a> {
+    inst: &'a Instruction,
+    /// The controlling type, or `None` for monomorphic instructions.
+    ctrl_type: Option<ValueType>,
+    /// The types of the value operands.
+    arg_types: Vec<ValueType>,
+}
+
+impl<'a> Candidate<'a> {
+    fn ctrl_rust_type(&self) -> String {
+        match self.ctrl_type {
+            Some(ref ty) => ty.rust_name(),
+            None => "ir::types::VOID".to_string(),
+        }
+    }
+}
+
+/// Get a Rust expression producing a random immediate operand of the kind
+/// `kind`, or `None` if the generator doesn't support it.
+fn imm_expr(kind: &OperandKind) -> Option<String> {
+    if let OperandKindFields::ImmEnum(ref values) = kind.fields {
+        let values = values
+            .iter()
+            .map(|&(name, _)| kind.rust_enumerator(name))
+            .collect::<Vec<_>>();
+        return Some(format!("entropy.choose(&[{}])", values.join(", ")));
+    }
+    let expr = match kind.name {
+        "imm64" => "entropy.imm64()",
+        "offset32" => "entropy.offset32()",
+        "ieee32" => "entropy.ieee32()",
+        "ieee64" => "entropy.ieee64()",
+        "bool" => "entropy.bool()",
+        "memflags" => "ir::MemFlags::new()",
+        _ => return None,
+    };
+    Some(expr.to_string())
+}
+
+/// Is `inst` a plain instruction that the generator can insert anywhere?
+fn is_plain(inst: &Instruction, format_registry: &FormatRegistry) -> bool {
+    !(inst.is_terminator
+        || inst.is_branch
+        || inst.is_call
+        || inst.is_return
+        || inst.other_side_effects
+        || EXCLUDED.contains(&inst.name)
+        || format_registry.get(inst.format).has_value_list)
+        && inst
+            .operands_in
+            .iter()
+            .chain(inst.operands_out.iter())
+            .all(|op| !op.is_varargs() && !op.is_cpu_flags())
+        && inst
+            .imm_opnums
+            .iter()
+            .all(|&opnum| imm_expr(&inst.operands_in[opnum].kind).is_some())
+}
+
+/// Get the scalar types of the type variable `tv`.
+fn scalar_types(tv: &TypeVar) -> Vec<ValueType> {
+    tv.get_typeset()
+        .concrete_types()
+        .into_iter()
+        .filter(|ty| match *ty {
+            ValueType::Lane(_) => true,
+            _ => false,
+        })
+        .collect()
+}
+
+/// Get the scalar type of the type variable `tv` under the concrete
+/// `typing`, if it has one.
+fn concrete_type(tv: &TypeVar, typing: &HashMap<TypeVar, TypeVar>) -> Option<ValueType> {
+    match subst(tv, typing).singleton_type() {
+        Some(ty @ ValueType::Lane(_)) => Some(ty),
+        _ => None,
+    }
+}
+
+/// Get the candidates for `inst`, one per valid assignment of scalar types
+/// to its type variables.
+fn inst_candidates<'a>(inst: &'a Instruction) -> Vec<Candidate<'a>> {
+    let poly = match inst.polymorphic_info {
+        Some(ref poly) => poly,
+        None => {
+            return vec![Candidate {
+                inst,
+                ctrl_type: None,
+                arg_types: inst
+                    .value_opnums
+                    .iter()
+                    .map(|&opnum| {
+                        let tv = inst.operands_in[opnum].type_var().unwrap();
+                        tv.singleton_type().unwrap()
+                    })
+                    .collect(),
+            }];
+        }
+    };
+
+    // Enumerate the typings of the controlling and the other type variables.
+    let mut typings = vec![HashMap::new()];
+    for tv in Some(&poly.ctrl_typevar)
+        .into_iter()
+        .chain(poly.other_typevars.iter())
+    {
+        let types = scalar_types(tv);
+        typings = typings
+            .into_iter()
+            .flat_map(|typing: HashMap<TypeVar, TypeVar>| {
+                types.iter().map(move |ty| {
+                    let mut typing = typing.clone();
+                    typing.insert(tv.clone(), TypeVar::new_singleton(ty.clone()));
+                    typing
+                })
+            })
+            .collect();
+    }
+
+    let mut candidates = Vec::new();
+    'typings: for typing in typings {
+        for constraint in &inst.constraints {
+            let constraint = constraint.translate_with_map(&typing);
+            if !constraint.is_concrete() || !constraint.eval() {
+                continue 'typings;
+            }
+            // The verifier requires the extensions and reductions to change
+            // the width.
+            if let Constraint::WiderOrEq(ref wide, ref narrow) = constraint {
+                if wide.singleton_type() == narrow.singleton_type() {
+                    continue 'typings;
+                }
+            }
+        }
+        for &result in &inst.value_results {
+            let tv = inst.operands_out[result].type_var().unwrap();
+            if concrete_type(tv, &typing).is_none() {
+                continue 'typings;
+            }
+        }
+        let mut arg_types = Vec::new();
+        for &opnum in &inst.value_opnums {
+            let tv = inst.operands_in[opnum].type_var().unwrap();
+            match concrete_type(tv, &typing) {
+                Some(ty) => arg_types.push(ty),
+                None => continue 'typings,
+            }
+        }
+        candidates.push(Candidate {
+            inst,
+            ctrl_type: concrete_type(&poly.ctrl_typevar, &typing),
+            arg_types,
+        });
+    }
+    candidates
+}
+
+/// Get all the candidates of the base instruction set.
+fn candidates<'a>(shared_defs: &'a base::Definitions) -> Vec<Candidate<'a>> {
+    shared_defs
+        .instructions
+        .iter()
+        .filter(|inst| is_plain(inst, &shared_defs.format_registry))
+        .flat_map(inst_candidates)
+        .collect()
+}
+
+fn gen_insert_candidate(
+    candidates: &[Candidate],
+    format_registry: &FormatRegistry,
+    fmt: &mut Formatter,
+) {
+    fmt.doc_comment(
+        r#"
+        Insert the candidate instruction number `candidate` at `pos`, with
+        operands taken from `values` and random immediates.
+        "#,
+    );
+    fmt.line("fn insert_candidate(");
+    fmt.indent(|fmt| {
+        fmt.line("candidate: u16,");
+        fmt.line("pos: &mut FuncCursor,");
+        fmt.line("values: &mut ValuePool,");
+        fmt.line("entropy: &mut Entropy,");
+    });
+    fmt.line(") -> ir::Inst {");
+    fmt.indent(|fmt| {
+        fmt.line("match candidate {");
+        fmt.indent(|fmt| {
+            for (number, candidate) in candidates.iter().enumerate() {
+                let inst = candidate.inst;
+                fmt.line(&format!("{} => {{", number));
+                fmt.indent(|fmt| {
+                    match candidate.ctrl_type {
+                        Some(ref ty) => fmt.comment(&format!("{}.{}", inst.name, ty)),
+                        None => fmt.comment(inst.name),
+                    }
+                    let mut args = vec![
+                        format!("ir::Opcode::{}", inst.camel_name),
+                        candidate.ctrl_rust_type(),
+                    ];
+                    for (i, &opnum) in inst.imm_opnums.iter().enumerate() {
+                        let expr = imm_expr(&inst.operands_in[opnum].kind).unwrap();
+                        fmt.line(&format!("let imm{} = {};", i, expr));
+                        args.push(format!("imm{}", i));
+                    }
+                    for (i, ty) in candidate.arg_types.iter().enumerate() {
+                        fmt.line(&format!(
+                            "let arg{} = values.get(pos, entropy, {});",
+                            i,
+                            ty.rust_name()
+                        ));
+                        args.push(format!("arg{}", i));
+                    }
+                    fmt.line(&format!(
+                        "pos.ins().{}({}).0",
+                        format_registry.get(inst.format).name,
+                        args.join(", ")
+                    ));
+                });
+                fmt.line("}");
+            }
+            fmt.line("_ => panic!(\"invalid candidate {}\", candidate),");
+        });
+        fmt.line("}");
+    });
+    fmt.line("}");
+}
+
+fn gen_supported_candidates(
+    candidates: &[Candidate],
+    shared_defs: &base::Definitions,
+    isas: &[TargetIsa],
+    fmt: &mut Formatter,
+) {
+    fmt.doc_comment(
+        r#"
+        Get the candidates which the CPU mode named `cpu_mode` of the ISA named
+        `isa` encodes or legalizes.
+        "#,
+    );
+    fmt.line("fn supported_candidates(isa: &str, cpu_mode: &str) -> &'static [u16] {");
+    fmt.indent(|fmt| {
+        fmt.line("match (isa, cpu_mode) {");
+        fmt.indent(|fmt| {
+            for isa in isas {
+                for coverage in gen_coverage::analyze(isa, shared_defs) {
+                    let supported = candidates
+                        .iter()
+                        .enumerate()
+                        .filter(|&(_, candidate)| {
+                            coverage
+                                .entries
+                                .iter()
+                                .find(|entry| {
+                                    entry.inst == candidate.inst.name
+                                        && entry.ctrl_type == candidate.ctrl_type
+                                })
+                                .map_or(false, |entry| entry.support.is_supported())
+                        })
+                        .map(|(number, _)| number.to_string())
+                        .collect::<Vec<_>>();
+                    if supported.is_empty() {
+                        continue;
+                    }
+                    fmt.line(&format!(
+                        "(\"{}\", \"{}\") => &[",
+                        isa.name, coverage.cpu_mode
+                    ));
+                    fmt.indent(|fmt| {
+                        for chunk in supported.chunks(16) {
+                            fmt.line(&format!("{},", chunk.join(", ")));
+                        }
+                    });
+                    fmt.line("],");
+                }
+            }
+            fmt.line("_ => &[],");
+        });
+        fmt.line("}");
+    });
+    fmt.line("}");
+}
+
+/// Generate the instruction tables of the random IR generator into
+/// `filename` in `out_dir`.
+pub fn generate(
+    shared_defs: &base::Definitions,
+    isas: &[TargetIsa],
+    filename: &str,
+    out_dir: &str,
+) -> Result<(), error::Error> {
+    let candidates = candidates(shared_defs);
+    let mut fmt = Formatter::new();
+    fmt.doc_comment("The number of candidate instructions.");
+    fmt.line(&format!(
+        "pub const NUM_CANDIDATES: usize = {};",
+        candidates.len()
+    ));
+    fmt.line("");
+    gen_insert_candidate(&candidates, &shared_defs.format_registry, &mut fmt);
+    fmt.line("");
+    gen_supported_candidates(&candidates, shared_defs, isas, &mut fmt);
+    fmt.update_file(filename, out_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn describe(candidate: &Candidate) -> String {
+        match candidate.ctrl_type {
+            Some(ref ty) => format!("{}.{}", candidate.inst.name, ty),
+            None => candidate.inst.name.to_string(),
+        }
+    }
+
+    #[test]
+    fn candidate_types() {
+        let shared_defs = base::define();
+        let candidates = candidates(&shared_defs);
+
+        // The operand types of the candidates named `name`.
+        let arg_types = |name: &str| {
+            candidates
+                .iter()
+                .filter(|candidate| describe(candidate) == name)
+                .map(|candidate| {
+                    candidate
+                        .arg_types
+                        .iter()
+                        .map(|ty| ty.to_string())
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(arg_types("iadd.i32"), [["i32", "i32"]]);
+        assert_eq!(arg_types("iadd_imm.i64"), [["i64"]]);
+        assert_eq!(arg_types("isplit.i64"), [["i64"]]);
+        assert!(arg_types("select.f64").contains(&vec![
+            "b1".to_string(),
+            "f64".to_string(),
+            "f64".to_string()
+        ]));
+
+        // The extensions only go to wider types.
+        let uextends = candidates
+            .iter()
+            .filter(|candidate| candidate.inst.name == "uextend")
+            .map(|candidate| format!("{}{}", describe(candidate), candidate.arg_types[0]))
+            .collect::<Vec<_>>();
+        assert!(uextends.contains(&"uextend.i64i32".to_string()));
+        assert!(!uextends.contains(&"uextend.i32i64".to_string()));
+        assert!(!uextends.contains(&"uextend.i32i32".to_string()));
+
+        // No control flow, vectors or CPU flags.
+        for candidate in &candidates {
+            let name = describe(candidate);
+            assert!(!candidate.inst.is_branch, "{}", name);
+            assert!(!name.starts_with("ifcmp"), "{}", name);
+            assert!(
+                candidate.arg_types.iter().all(|ty| ty.lane_count() == 1),
+                "{}",
+                name
+            );
+        }
+    }
+}
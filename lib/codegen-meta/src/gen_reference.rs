@@ -0,0 +1,727 @@
+//! Generate a reference of the meta definitions.
+//!
+//! This generates a `reference.json` file describing the value types, the
+//! instructions, the settings and the encodings of every target ISA, for the
+//! use of external tools, and a `reference.md` file presenting the same
+//! information as documentation.
+
+use std::collections::BTreeMap;
+
+use base;
+use cdsl::formats::FormatRegistry;
+use cdsl::instructions::{Instruction, InstructionGroup};
+use cdsl::isa::TargetIsa;
+use cdsl::operands::{Operand, OperandKindFields};
+use cdsl::settings::{Setting, SettingGroup, SpecificSetting};
+use cdsl::types::ValueType;
+use cdsl::typevar::TypeVar;
+use cdsl::xform::XFormGroups;
+use error;
+use gen_types;
+use srcgen::{parse_multiline, Formatter};
+
+/// A JSON value.
+///
+/// The members of objects are kept in order, so the generated file is
+/// stable.
+enum Json {
+    Null,
+    Bool(bool),
+    Number(u64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>),
+}
+
+impl Json {
+    fn string<S: Into<String>>(s: S) -> Json {
+        Json::String(s.into())
+    }
+
+    fn strings<S: Into<String>, I: IntoIterator<Item = S>>(items: I) -> Json {
+        Json::Array(items.into_iter().map(Json::string).collect())
+    }
+
+    fn optional<T, F: FnOnce(T) -> Json>(value: Option<T>, f: F) -> Json {
+        value.map_or(Json::Null, f)
+    }
+}
+
+/// Quote and escape `s` as a JSON string.
+fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Emit `value` on its own lines, preceded by `prefix` and followed by
+/// `suffix`.
+fn emit_json(value: &Json, prefix: &str, suffix: &str, fmt: &mut Formatter) {
+    let scalar = match *value {
+        Json::Null => "null".to_string(),
+        Json::Bool(b) => b.to_string(),
+        Json::Number(n) => n.to_string(),
+        Json::String(ref s) => quote(s),
+        Json::Array(ref items) if items.is_empty() => "[]".to_string(),
+        Json::Object(ref members) if members.is_empty() => "{}".to_string(),
+        Json::Array(ref items) => {
+            fmt.line(&format!("{}[", prefix));
+            fmt.indent(|fmt| {
+                for (i, item) in items.iter().enumerate() {
+                    let sep = if i + 1 < items.len() { "," } else { "" };
+                    emit_json(item, "", sep, fmt);
+                }
+            });
+            fmt.line(&format!("]{}", suffix));
+            return;
+        }
+        Json::Object(ref members) => {
+            fmt.line(&format!("{}{{", prefix));
+            fmt.indent(|fmt| {
+                for (i, &(key, ref member)) in members.iter().enumerate() {
+                    let sep = if i + 1 < members.len() { "," } else { "" };
+                    emit_json(member, &format!("{}: ", quote(key)), sep, fmt);
+                }
+            });
+            fmt.line(&format!("}}{}", suffix));
+            return;
+        }
+    };
+    fmt.line(&format!("{}{}{}", prefix, scalar, suffix));
+}
+
+/// Strip the common indentation of a doc string.
+fn doc_text(doc: &str) -> String {
+    parse_multiline(doc).join("\n")
+}
+
+/// Get the names of all the types in the type set of `tv`.
+fn type_names(tv: &TypeVar) -> Vec<String> {
+    tv.get_typeset()
+        .concrete_types()
+        .iter()
+        .map(|ty| ty.to_string())
+        .collect()
+}
+
+fn describe_type(ty: &ValueType) -> Json {
+    Json::Object(vec![
+        ("name", Json::string(ty.to_string())),
+        (
+            "number",
+            Json::optional(ty.number(), |n| Json::Number(n.into())),
+        ),
+        ("lane_bits", Json::Number(ty.lane_bits())),
+        ("lane_count", Json::Number(ty.lane_count())),
+        ("doc", Json::string(ty.doc())),
+    ])
+}
+
+fn describe_type_var(tv: &TypeVar) -> Json {
+    let derived_from = Json::optional(tv.base(), |base| {
+        Json::Object(vec![
+            ("type_var", Json::string(base.type_var.name())),
+            ("function", Json::string(base.derived_func.name())),
+        ])
+    });
+    Json::Object(vec![
+        ("name", Json::string(tv.name())),
+        ("doc", Json::string(doc_text(tv.doc()))),
+        ("derived_from", derived_from),
+        ("types", Json::strings(type_names(tv))),
+    ])
+}
+
+fn describe_operand(operand: &Operand) -> Json {
+    let enumerators = match operand.kind.fields {
+        OperandKindFields::ImmEnum(ref values) => {
+            Json::strings(values.iter().map(|&(value, _)| value))
+        }
+        _ => Json::Null,
+    };
+    Json::Object(vec![
+        ("name", Json::string(operand.name)),
+        ("kind", Json::string(operand.kind.name)),
+        (
+            "doc",
+            Json::optional(operand.doc(), |doc| Json::string(doc_text(doc))),
+        ),
+        (
+            "type_var",
+            Json::optional(operand.type_var(), |tv| Json::string(tv.name())),
+        ),
+        ("enumerators", enumerators),
+    ])
+}
+
+/// Get the names of the properties of `inst` which are set.
+fn properties(inst: &Instruction) -> Vec<&'static str> {
+    let flags = [
+        ("is_terminator", inst.is_terminator),
+        ("is_branch", inst.is_branch),
+        ("is_call", inst.is_call),
+        ("is_return", inst.is_return),
+        ("can_load", inst.can_load),
+        ("can_store", inst.can_store),
+        ("can_trap", inst.can_trap),
+        ("other_side_effects", inst.other_side_effects),
+        ("writes_cpu_flags", inst.writes_cpu_flags),
+    ];
+    flags
+        .iter()
+        .filter(|&&(_, set)| set)
+        .map(|&(name, _)| name)
+        .collect()
+}
+
+fn describe_instruction(inst: &Instruction, format_registry: &FormatRegistry) -> Json {
+    let ctrl_typevar = inst
+        .polymorphic_info
+        .as_ref()
+        .map(|poly| poly.ctrl_typevar.name());
+    Json::Object(vec![
+        ("name", Json::string(inst.name)),
+        ("syntax", Json::string(inst.to_string())),
+        ("doc", Json::string(doc_text(inst.doc))),
+        (
+            "format",
+            Json::string(format_registry.get(inst.format).name),
+        ),
+        (
+            "operands_in",
+            Json::Array(inst.operands_in.iter().map(describe_operand).collect()),
+        ),
+        (
+            "operands_out",
+            Json::Array(inst.operands_out.iter().map(describe_operand).collect()),
+        ),
+        ("ctrl_typevar", Json::optional(ctrl_typevar, Json::string)),
+        (
+            "type_vars",
+            Json::Array(
+                inst.all_typevars()
+                    .into_iter()
+                    .map(describe_type_var)
+                    .collect(),
+            ),
+        ),
+        (
+            "constraints",
+            Json::strings(inst.constraints.iter().map(|c| c.to_string())),
+        ),
+        ("properties", Json::strings(properties(inst))),
+    ])
+}
+
+fn describe_instruction_group(group: &InstructionGroup, format_registry: &FormatRegistry) -> Json {
+    Json::Object(vec![
+        ("name", Json::string(group.name)),
+        ("doc", Json::string(doc_text(group.doc))),
+        (
+            "instructions",
+            Json::Array(
+                group
+                    .iter()
+                    .map(|inst| describe_instruction(inst, format_registry))
+                    .collect(),
+            ),
+        ),
+    ])
+}
+
+/// Get the kind and the default value of `setting`, as strings.
+fn setting_kind(setting: &Setting) -> (&'static str, String) {
+    match setting.specific {
+        SpecificSetting::Bool(ref bool_setting) => ("bool", bool_setting.default.to_string()),
+        SpecificSetting::Enum(ref values) => ("enum", values[0].to_string()),
+        SpecificSetting::Num(default) => ("num", default.to_string()),
+    }
+}
+
+fn describe_setting(setting: &Setting) -> Json {
+    let (kind, default) = setting_kind(setting);
+    let values = match setting.specific {
+        SpecificSetting::Enum(ref values) => Json::strings(values.iter().cloned()),
+        _ => Json::Null,
+    };
+    Json::Object(vec![
+        ("name", Json::string(setting.name)),
+        ("doc", Json::string(doc_text(setting.comment))),
+        ("kind", Json::string(kind)),
+        ("default", Json::string(default)),
+        ("values", values),
+    ])
+}
+
+fn describe_setting_group(group: &SettingGroup) -> Json {
+    let presets = group
+        .presets
+        .iter()
+        .map(|preset| {
+            Json::Object(vec![
+                ("name", Json::string(preset.name)),
+                ("settings", Json::strings(preset.setting_names(group))),
+            ])
+        })
+        .collect();
+    Json::Object(vec![
+        ("name", Json::string(group.name)),
+        (
+            "settings",
+            Json::Array(group.settings.iter().map(describe_setting).collect()),
+        ),
+        ("presets", Json::Array(presets)),
+    ])
+}
+
+/// The encodings of one CPU mode, grouped by instruction name.
+fn encoded_types(isa: &TargetIsa, cpu_mode: usize) -> BTreeMap<&'static str, Vec<String>> {
+    let mut encoded: BTreeMap<_, Vec<String>> = BTreeMap::new();
+    for encoding in isa.cpu_modes[cpu_mode].encodings() {
+        let types = encoded.entry(encoding.inst.name).or_insert_with(Vec::new);
+        if let Some(ref ty) = encoding.bound_type {
+            let ty = ty.to_string();
+            if !types.contains(&ty) {
+                types.push(ty);
+            }
+        }
+    }
+    encoded
+}
+
+/// Get the names of the instructions available on `isa` which have no
+/// encoding in any of its CPU modes.
+fn unencoded_instructions<'a>(
+    isa: &'a TargetIsa,
+    shared_defs: &'a base::Definitions,
+) -> Vec<&'a str> {
+    shared_defs
+        .instructions
+        .iter()
+        .chain(isa.instructions.iter())
+        .map(|inst| inst.name)
+        .filter(|name| {
+            !isa.cpu_modes
+                .iter()
+                .any(|mode| mode.encodings().iter().any(|enc| enc.inst.name == *name))
+        })
+        .collect()
+}
+
+fn describe_legalize_actions(isa: &TargetIsa, cpu_mode: usize, groups: &XFormGroups) -> Json {
+    let cpu_mode = &isa.cpu_modes[cpu_mode];
+    let name = |value_type| Json::string(groups.get(cpu_mode.get_legalize_action(value_type)).name);
+    let mut actions = vec![(
+        "default",
+        Json::string(groups.get(cpu_mode.get_default_legalize()).name),
+    )];
+    let mut types = Vec::new();
+    for value_type in cpu_mode.legalized_types() {
+        match value_type {
+            None => actions.push(("monomorphic", name(None))),
+            Some(ty) => types.push(Json::Object(vec![
+                ("type", Json::string(ty.to_string())),
+                ("action", name(Some(ty))),
+            ])),
+        }
+    }
+    actions.push(("types", Json::Array(types)));
+    Json::Object(actions)
+}
+
+fn describe_isa(isa: &TargetIsa, shared_defs: &base::Definitions) -> Json {
+    let format_registry = &shared_defs.format_registry;
+    let recipes = isa
+        .recipes
+        .iter()
+        .map(|recipe| {
+            Json::Object(vec![
+                ("name", Json::string(recipe.name.clone())),
+                (
+                    "format",
+                    Json::string(format_registry.get(recipe.format).name),
+                ),
+                ("base_size", Json::Number(recipe.base_size)),
+                ("clobbers_flags", Json::Bool(recipe.clobbers_flags)),
+            ])
+        })
+        .collect();
+
+    let cpu_modes = isa
+        .cpu_modes
+        .iter()
+        .enumerate()
+        .map(|(index, cpu_mode)| {
+            let encodings = cpu_mode
+                .encodings()
+                .iter()
+                .map(|encoding| {
+                    Json::Object(vec![
+                        ("instruction", Json::string(encoding.inst.name)),
+                        (
+                            "type",
+                            Json::optional(encoding.bound_type.as_ref(), |ty| {
+                                Json::string(ty.to_string())
+                            }),
+                        ),
+                        (
+                            "recipe",
+                            Json::string(isa.recipes.get(encoding.recipe).name.clone()),
+                        ),
+                        ("bits", Json::string(format!("{:#06x}", encoding.encbits))),
+                        (
+                            "isa_predicate",
+                            Json::optional(encoding.isa_predicate, |number| {
+                                Json::string(isa.settings.describe_predicate(number))
+                            }),
+                        ),
+                        (
+                            "inst_predicate",
+                            Json::optional(encoding.inst_predicate.as_ref(), |pred| {
+                                Json::string(pred.to_string())
+                            }),
+                        ),
+                    ])
+                })
+                .collect();
+            Json::Object(vec![
+                ("name", Json::string(cpu_mode.name)),
+                (
+                    "legalize",
+                    describe_legalize_actions(isa, index, &shared_defs.transform_groups),
+                ),
+                ("encodings", Json::Array(encodings)),
+            ])
+        })
+        .collect();
+
+    let num_instructions =
+        shared_defs.instructions.iter().count() + isa.instructions.iter().count();
+    let unencoded = unencoded_instructions(isa, shared_defs);
+    let coverage = Json::Object(vec![
+        ("instructions", Json::Number(num_instructions as u64)),
+        (
+            "encoded",
+            Json::Number((num_instructions - unencoded.len()) as u64),
+        ),
+        ("unencoded", Json::strings(unencoded)),
+    ]);
+
+    Json::Object(vec![
+        ("name", Json::string(isa.name)),
+        ("settings", describe_setting_group(&isa.settings)),
+        ("instructions", Json::string(isa.instructions.name)),
+        ("recipes", Json::Array(recipes)),
+        ("cpu_modes", Json::Array(cpu_modes)),
+        ("coverage", coverage),
+    ])
+}
+
+fn gen_json(
+    shared_settings: &SettingGroup,
+    shared_defs: &base::Definitions,
+    isas: &[TargetIsa],
+    fmt: &mut Formatter,
+) {
+    let format_registry = &shared_defs.format_registry;
+    let instruction_groups = Some(&shared_defs.instructions)
+        .into_iter()
+        .chain(isas.iter().map(|isa| &isa.instructions))
+        .map(|group| describe_instruction_group(group, format_registry))
+        .collect();
+    let reference = Json::Object(vec![
+        (
+            "types",
+            Json::Array(gen_types::all_types().iter().map(describe_type).collect()),
+        ),
+        ("instruction_groups", Json::Array(instruction_groups)),
+        ("settings", describe_setting_group(shared_settings)),
+        (
+            "isas",
+            Json::Array(
+                isas.iter()
+                    .map(|isa| describe_isa(isa, shared_defs))
+                    .collect(),
+            ),
+        ),
+    ]);
+    emit_json(&reference, "", "", fmt);
+}
+
+/// Make `s` fit in one cell of a Markdown table.
+fn table_cell(s: &str) -> String {
+    s.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace('|', "\\|")
+}
+
+fn gen_markdown_settings(group: &SettingGroup, fmt: &mut Formatter) {
+    fmt.line("| Setting | Kind | Default | Description |");
+    fmt.line("|---------|------|---------|-------------|");
+    for setting in &group.settings {
+        let (kind, default) = setting_kind(setting);
+        let kind = match setting.specific {
+            SpecificSetting::Enum(ref values) => format!("enum: {}", values.join(", ")),
+            _ => kind.to_string(),
+        };
+        fmt.line(&format!(
+            "| `{}` | {} | `{}` | {} |",
+            setting.name,
+            kind,
+            default,
+            table_cell(setting.comment)
+        ));
+    }
+    fmt.line("");
+    for preset in &group.presets {
+        let settings = preset
+            .setting_names(group)
+            .iter()
+            .map(|name| format!("`{}`", name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        if settings.is_empty() {
+            fmt.line(&format!("- Preset `{}`: no settings enabled", preset.name));
+        } else {
+            fmt.line(&format!("- Preset `{}`: {}", preset.name, settings));
+        }
+    }
+    if !group.presets.is_empty() {
+        fmt.line("");
+    }
+}
+
+fn gen_markdown_operands(title: &str, operands: &[Operand], fmt: &mut Formatter) {
+    if operands.is_empty() {
+        return;
+    }
+    fmt.line(&format!("| {} | Kind | Description |", title));
+    fmt.line("|---------|------|-------------|");
+    for operand in operands {
+        fmt.line(&format!(
+            "| `{}` | {} | {} |",
+            operand.name,
+            operand.kind.name,
+            table_cell(operand.doc().unwrap_or(""))
+        ));
+    }
+    fmt.line("");
+}
+
+fn gen_markdown_instruction(
+    inst: &Instruction,
+    format_registry: &FormatRegistry,
+    fmt: &mut Formatter,
+) {
+    fmt.line(&format!("#### `{}`", inst.name));
+    fmt.line("");
+    fmt.line("```");
+    fmt.line(&inst.to_string());
+    fmt.line("```");
+    fmt.line("");
+    fmt.multi_line(inst.doc);
+    fmt.line("");
+    fmt.line(&format!(
+        "Format: `{}`.",
+        format_registry.get(inst.format).name
+    ));
+    let properties = properties(inst);
+    if !properties.is_empty() {
+        let properties = properties
+            .iter()
+            .map(|name| format!("`{}`", name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        fmt.line(&format!("Properties: {}.", properties));
+    }
+    fmt.line("");
+    gen_markdown_operands("Input", &inst.operands_in, fmt);
+    gen_markdown_operands("Output", &inst.operands_out, fmt);
+    for tv in inst.all_typevars() {
+        let types = match tv.base() {
+            Some(base) => format!("`{}({})`", base.derived_func.name(), base.type_var.name()),
+            None => type_names(tv).join(", "),
+        };
+        fmt.line(&format!("- Type variable `{}`: {}", tv.name(), types));
+    }
+    for constraint in &inst.constraints {
+        fmt.line(&format!("- Constraint: `{}`", constraint));
+    }
+    if inst.is_polymorphic() || !inst.constraints.is_empty() {
+        fmt.line("");
+    }
+}
+
+fn gen_markdown_isa(isa: &TargetIsa, shared_defs: &base::Definitions, fmt: &mut Formatter) {
+    fmt.line(&format!("### {}", isa.name));
+    fmt.line("");
+    fmt.line(&format!("#### Settings of `{}`", isa.settings.name));
+    fmt.line("");
+    gen_markdown_settings(&isa.settings, fmt);
+
+    let num_instructions =
+        shared_defs.instructions.iter().count() + isa.instructions.iter().count();
+    let unencoded = unencoded_instructions(isa, shared_defs);
+    fmt.line("#### Encoding coverage");
+    fmt.line("");
+    fmt.line(&format!(
+        "{} of the {} instructions available on {} have an encoding in some CPU mode, \
+         using {} encoding recipes.",
+        num_instructions - unencoded.len(),
+        num_instructions,
+        isa.name,
+        isa.recipes.len()
+    ));
+    fmt.line("");
+    if !unencoded.is_empty() {
+        let unencoded = unencoded
+            .iter()
+            .map(|name| format!("`{}`", name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        fmt.line(&format!("Instructions without encodings: {}.", unencoded));
+        fmt.line("");
+    }
+
+    let groups = &shared_defs.transform_groups;
+    for (index, cpu_mode) in isa.cpu_modes.iter().enumerate() {
+        fmt.line(&format!("#### CPU mode `{}`", cpu_mode.name));
+        fmt.line("");
+        fmt.line(&format!(
+            "{} encodings. Instructions without an encoding are legalized with `{}` by default.",
+            cpu_mode.encodings().len(),
+            groups.get(cpu_mode.get_default_legalize()).name
+        ));
+        fmt.line("");
+        let encoded = encoded_types(isa, index);
+        if encoded.is_empty() {
+            continue;
+        }
+        fmt.line("| Instruction | Types |");
+        fmt.line("|-------------|-------|");
+        for (name, types) in &encoded {
+            fmt.line(&format!("| `{}` | {} |", name, types.join(", ")));
+        }
+        fmt.line("");
+    }
+}
+
+fn gen_markdown(
+    shared_settings: &SettingGroup,
+    shared_defs: &base::Definitions,
+    isas: &[TargetIsa],
+    fmt: &mut Formatter,
+) {
+    let format_registry = &shared_defs.format_registry;
+
+    fmt.line("# Cranelift ISA reference");
+    fmt.line("");
+    fmt.line("This file is generated by the `cranelift-codegen-meta` crate.");
+    fmt.line("");
+
+    fmt.line("## Types");
+    fmt.line("");
+    fmt.line("| Type | Description |");
+    fmt.line("|------|-------------|");
+    for ty in gen_types::all_types() {
+        fmt.line(&format!("| `{}` | {} |", ty, table_cell(&ty.doc())));
+    }
+    fmt.line("");
+
+    fmt.line("## Instructions");
+    fmt.line("");
+    let instruction_groups = Some(&shared_defs.instructions)
+        .into_iter()
+        .chain(isas.iter().map(|isa| &isa.instructions));
+    for group in instruction_groups {
+        if group.iter().next().is_none() {
+            continue;
+        }
+        fmt.line(&format!("### {}", doc_text(group.doc)));
+        fmt.line("");
+        for inst in group.iter() {
+            gen_markdown_instruction(inst, format_registry, fmt);
+        }
+    }
+
+    fmt.line("## Settings");
+    fmt.line("");
+    gen_markdown_settings(shared_settings, fmt);
+
+    fmt.line("## Target ISAs");
+    fmt.line("");
+    for isa in isas {
+        gen_markdown_isa(isa, shared_defs, fmt);
+    }
+}
+
+/// Generate the JSON and Markdown references of the meta definitions, named
+/// `<prefix>.json` and `<prefix>.md`.
+pub fn generate(
+    shared_settings: &SettingGroup,
+    shared_defs: &base::Definitions,
+    isas: &[TargetIsa],
+    prefix: &str,
+    out_dir: &str,
+) -> Result<(), error::Error> {
+    let mut fmt = Formatter::new();
+    gen_json(shared_settings, shared_defs, isas, &mut fmt);
+    fmt.update_file(&format!("{}.json", prefix), out_dir)?;
+
+    let mut fmt = Formatter::new();
+    gen_markdown(shared_settings, shared_defs, isas, &mut fmt);
+    fmt.update_file(&format!("{}.md", prefix), out_dir)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    fn render(value: &Json) -> String {
+        let mut fmt = Formatter::new();
+        emit_json(value, "", "", &mut fmt);
+        let out_dir = env::temp_dir();
+        let file_name = "cranelift-meta-reference-test.json";
+        fmt.update_file(file_name, out_dir.to_str().unwrap())
+            .unwrap();
+        fs::read_to_string(out_dir.join(file_name)).unwrap()
+    }
+
+    #[test]
+    fn json_rendering() {
+        let value = Json::Object(vec![
+            ("name", Json::string("a \"quoted\"\nline")),
+            ("empty", Json::Array(vec![])),
+            (
+                "items",
+                Json::Array(vec![Json::Number(1), Json::Null, Json::Bool(true)]),
+            ),
+        ]);
+        assert_eq!(
+            render(&value),
+            "{\n    \"name\": \"a \\\"quoted\\\"\\nline\",\n    \"empty\": [],\n    \
+             \"items\": [\n        1,\n        null,\n        true\n    ]\n}\n"
+        );
+    }
+
+    #[test]
+    fn markdown_table_cells() {
+        assert_eq!(table_cell("Enable   x |\n   y"), "Enable x \\| y");
+    }
+}
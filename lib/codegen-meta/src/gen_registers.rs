@@ -0,0 +1,125 @@
+//! Generate register bank descriptions for each ISA.
+//!
+//! This generates a `registers-<isa>.rs` file for each ISA, included in
+//! `lib/codegen/src/isa/<isa>/registers.rs`.
+
+use cdsl::isa::TargetIsa;
+use cdsl::registers::{IsaRegs, RegBank, RegClass};
+use error;
+use srcgen::Formatter;
+
+/// Emit a static data definition for `bank`.
+fn gen_regbank(bank: &RegBank, fmt: &mut Formatter) {
+    let names = bank
+        .names
+        .iter()
+        .map(|name| format!("\"{}\"", name))
+        .collect::<Vec<_>>()
+        .join(", ");
+    fmt.line("RegBank {");
+    fmt.indent(|fmt| {
+        fmt.line(&format!("name: \"{}\",", bank.name));
+        fmt.line(&format!("first_unit: {},", bank.first_unit));
+        fmt.line(&format!("units: {},", bank.units));
+        fmt.line(&format!("names: &[{}],", names));
+        fmt.line(&format!("prefix: \"{}\",", bank.prefix));
+        fmt.line(&format!("first_toprc: {},", bank.toprcs[0]));
+        fmt.line(&format!("num_toprcs: {},", bank.toprcs.len()));
+        fmt.line(&format!("pressure_tracking: {},", bank.pressure_tracking));
+    });
+    fmt.line("},");
+}
+
+/// Emit constants for all the register units in `bank`.
+fn gen_regbank_units(bank: &RegBank, fmt: &mut Formatter) {
+    for unit in 0..bank.units {
+        let v = unit + bank.first_unit;
+        fmt.line(&format!("{} = {},", bank.unit_name(unit), v));
+    }
+}
+
+/// Emit a static data definition for a register class.
+fn gen_regclass(regs: &IsaRegs, rc: &RegClass, fmt: &mut Formatter) {
+    let bank = regs.bank(rc.bank);
+    let mask = rc
+        .mask(bank.first_unit)
+        .iter()
+        .map(|x| format!("0x{:08x}", x))
+        .collect::<Vec<_>>()
+        .join(", ");
+    fmt.line(&format!(
+        "pub static {}_DATA: RegClassData = RegClassData {{",
+        rc.name
+    ));
+    fmt.indent(|fmt| {
+        fmt.line(&format!("name: \"{}\",", rc.name));
+        fmt.line(&format!("index: {},", rc.index));
+        fmt.line(&format!("width: {},", rc.width));
+        fmt.line(&format!("bank: {},", rc.bank.index()));
+        fmt.line(&format!("toprc: {},", rc.toprc));
+        fmt.line(&format!("first: {},", bank.first_unit + rc.start()));
+        fmt.line(&format!("subclasses: 0x{:x},", rc.subclass_mask()));
+        fmt.line(&format!("mask: [{}],", mask));
+        fmt.line("info: &INFO,");
+    });
+    fmt.line("};");
+    // Also emit a convenient reference for use by hand-written code.
+    fmt.line("#[allow(dead_code)]");
+    fmt.line(&format!("pub static {0}: RegClass = &{0}_DATA;", rc.name));
+}
+
+/// Generate register tables for `isa`.
+fn gen_isa(isa: &TargetIsa, fmt: &mut Formatter) {
+    let regs = &isa.regs;
+
+    fmt.line("pub static INFO: RegInfo = RegInfo {");
+    fmt.indent(|fmt| {
+        // Bank descriptors.
+        fmt.line("banks: &[");
+        fmt.indent(|fmt| {
+            for bank in &regs.banks {
+                gen_regbank(bank, fmt);
+            }
+        });
+        fmt.line("],");
+        fmt.line("classes: &[");
+        fmt.indent(|fmt| {
+            for rc in &regs.classes {
+                fmt.line(&format!("&{}_DATA,", rc.name));
+            }
+        });
+        fmt.line("],");
+    });
+    fmt.line("};");
+
+    // Register class descriptors.
+    for rc in &regs.classes {
+        gen_regclass(regs, rc, fmt);
+    }
+
+    // Emit constants for all the register units.
+    fmt.line("#[allow(dead_code, non_camel_case_types)]");
+    fmt.line("#[derive(Clone, Copy)]");
+    fmt.line("pub enum RU {");
+    fmt.indent(|fmt| {
+        for bank in &regs.banks {
+            gen_regbank_units(bank, fmt);
+        }
+    });
+    fmt.line("}");
+    fmt.line("impl Into<RegUnit> for RU {");
+    fmt.indent(|fmt| {
+        fmt.line("fn into(self) -> RegUnit {");
+        fmt.indent(|fmt| fmt.line("self as RegUnit"));
+        fmt.line("}");
+    });
+    fmt.line("}");
+}
+
+/// Generate the registers file of the ISA `isa`, named `<prefix>-<isa>.rs`.
+pub fn generate(isa: &TargetIsa, prefix: &str, out_dir: &str) -> Result<(), error::Error> {
+    let mut fmt = Formatter::new();
+    gen_isa(isa, &mut fmt);
+    fmt.update_file(&format!("{}-{}.rs", prefix, isa.name), out_dir)?;
+    Ok(())
+}
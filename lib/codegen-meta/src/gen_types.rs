@@ -28,35 +28,37 @@ fn emit_type(ty: &cdsl_types::ValueType, fmt: &mut srcgen::Formatter) -> Result<
     Ok(())
 }
 
-/// Emit definition for all vector types with `bits` total size.
-fn emit_vectors(bits: u64, fmt: &mut srcgen::Formatter) -> Result<(), error::Error> {
+/// Get all the vector types with `bits` total size.
+fn vector_types(bits: u64) -> impl Iterator<Item = cdsl_types::ValueType> {
     let vec_size: u64 = bits / 8;
     cdsl_types::ValueType::all_lane_types()
         .map(|ty| (ty, cdsl_types::ValueType::from(ty).membytes()))
-        .filter(|(_, lane_size)| *lane_size != 0 && *lane_size < vec_size)
-        .map(|(ty, lane_size)| (ty, vec_size / lane_size))
+        .filter(move |(_, lane_size)| *lane_size != 0 && *lane_size < vec_size)
+        .map(move |(ty, lane_size)| (ty, vec_size / lane_size))
         .map(|(ty, lanes)| cdsl_types::VectorType::new(ty, lanes))
-        .try_for_each(|vec| emit_type(&cdsl_types::ValueType::from(vec), fmt))?;
-
-    Ok(())
+        .map(cdsl_types::ValueType::from)
 }
 
-/// Emit types using the given formatter object.
-fn emit_types(fmt: &mut srcgen::Formatter) -> Result<(), error::Error> {
-    // Emit all of the special types, such as types for CPU flags.
-    cdsl_types::ValueType::all_special_types().try_for_each(|spec| emit_type(&spec, fmt))?;
+/// Get all the types which have a constant definition, in the order they are
+/// emitted.
+pub fn all_types() -> Vec<cdsl_types::ValueType> {
+    // All of the special types, such as types for CPU flags.
+    let mut types = cdsl_types::ValueType::all_special_types().collect::<Vec<_>>();
 
-    // Emit all of the lane types, such integers, floats, and booleans.
-    cdsl_types::ValueType::all_lane_types()
-        .map(cdsl_types::ValueType::from)
-        .try_for_each(|ty| emit_type(&ty, fmt))?;
+    // All of the lane types, such integers, floats, and booleans.
+    types.extend(cdsl_types::ValueType::all_lane_types().map(cdsl_types::ValueType::from));
 
-    // Emit vector definitions for common SIMD sizes.
-    [64_u64, 128, 256, 512]
-        .into_iter()
-        .try_for_each(|&b| emit_vectors(b, fmt))?;
+    // Vector types for common SIMD sizes.
+    for &bits in &[64_u64, 128, 256, 512] {
+        types.extend(vector_types(bits));
+    }
 
-    Ok(())
+    types
+}
+
+/// Emit types using the given formatter object.
+fn emit_types(fmt: &mut srcgen::Formatter) -> Result<(), error::Error> {
+    all_types().iter().try_for_each(|ty| emit_type(ty, fmt))
 }
 
 /// Generate the types file.
@@ -0,0 +1,510 @@
+//! ARM32 Encodings.
+
+use base;
+use cdsl::encodings::{Encoding, EncodingBuilder, InstSpec};
+use cdsl::instructions::{Bindable, InstructionGroup};
+use cdsl::recipes::RecipeGroup;
+use cdsl::settings::SettingGroup;
+use cdsl::types::ValueType;
+
+use base::types::{Bool, Float, Int};
+
+use super::recipes::{a32_bits, b_bits, vfp_bits, vx_bits};
+
+/// The floating point instruction templates below are the single precision
+/// variants. This bit selects the double precision variants.
+const DOUBLE: u32 = 1 << 8;
+
+/// The encodings of the A32 CPU mode.
+pub struct PerCpuModeEncodings<'defs> {
+    pub enc32: Vec<Encoding>,
+    recipes: &'defs RecipeGroup,
+    shared_defs: &'defs base::Definitions,
+}
+
+impl<'defs> PerCpuModeEncodings<'defs> {
+    fn enc<I: Into<InstSpec>>(&mut self, inst: I, recipe: &str, bits: u16) {
+        let recipe = self.recipes.by_name(recipe);
+        let encoding = EncodingBuilder::new(inst, recipe, bits)
+            .build(self.recipes, &self.shared_defs.format_registry);
+        self.enc32.push(encoding);
+    }
+
+    fn enc_isap<I: Into<InstSpec>>(&mut self, inst: I, recipe: &str, bits: u16, isap: u8) {
+        let recipe = self.recipes.by_name(recipe);
+        let encoding = EncodingBuilder::new(inst, recipe, bits)
+            .isa_predicate(isap)
+            .build(self.recipes, &self.shared_defs.format_registry);
+        self.enc32.push(encoding);
+    }
+}
+
+pub fn define<'defs>(
+    shared_defs: &'defs base::Definitions,
+    isa_settings: &SettingGroup,
+    arm32_instructions: &InstructionGroup,
+    recipes: &'defs RecipeGroup,
+) -> PerCpuModeEncodings<'defs> {
+    let insts = &shared_defs.instructions;
+
+    let mut e = PerCpuModeEncodings {
+        enc32: Vec::new(),
+        recipes,
+        shared_defs,
+    };
+
+    // Instructions shorthands.
+    let adjust_sp_down_imm = insts.by_name("adjust_sp_down_imm");
+    let adjust_sp_up_imm = insts.by_name("adjust_sp_up_imm");
+    let band = insts.by_name("band");
+    let band_imm = insts.by_name("band_imm");
+    let band_not = insts.by_name("band_not");
+    let bconst = insts.by_name("bconst");
+    let bint = insts.by_name("bint");
+    let bitcast = insts.by_name("bitcast");
+    let bnot = insts.by_name("bnot");
+    let bor = insts.by_name("bor");
+    let bor_imm = insts.by_name("bor_imm");
+    let br_icmp = insts.by_name("br_icmp");
+    let brnz = insts.by_name("brnz");
+    let brz = insts.by_name("brz");
+    let bxor = insts.by_name("bxor");
+    let bxor_imm = insts.by_name("bxor_imm");
+    let call = insts.by_name("call");
+    let call_indirect = insts.by_name("call_indirect");
+    let clz = insts.by_name("clz");
+    let copy = insts.by_name("copy");
+    let copy_special = insts.by_name("copy_special");
+    let fabs = insts.by_name("fabs");
+    let fadd = insts.by_name("fadd");
+    let fcmp = insts.by_name("fcmp");
+    let fcvt_from_sint = insts.by_name("fcvt_from_sint");
+    let fcvt_from_uint = insts.by_name("fcvt_from_uint");
+    let fdemote = insts.by_name("fdemote");
+    let fdiv = insts.by_name("fdiv");
+    let fill = insts.by_name("fill");
+    let fmul = insts.by_name("fmul");
+    let fneg = insts.by_name("fneg");
+    let fpromote = insts.by_name("fpromote");
+    let fsub = insts.by_name("fsub");
+    let iadd = insts.by_name("iadd");
+    let iadd_imm = insts.by_name("iadd_imm");
+    let icmp = insts.by_name("icmp");
+    let icmp_imm = insts.by_name("icmp_imm");
+    let iconst = insts.by_name("iconst");
+    let imul = insts.by_name("imul");
+    let ireduce = insts.by_name("ireduce");
+    let irsub_imm = insts.by_name("irsub_imm");
+    let ishl = insts.by_name("ishl");
+    let ishl_imm = insts.by_name("ishl_imm");
+    let istore16 = insts.by_name("istore16");
+    let istore8 = insts.by_name("istore8");
+    let isub = insts.by_name("isub");
+    let jump = insts.by_name("jump");
+    let load = insts.by_name("load");
+    let regfill = insts.by_name("regfill");
+    let regmove = insts.by_name("regmove");
+    let regspill = insts.by_name("regspill");
+    let rotr = insts.by_name("rotr");
+    let rotr_imm = insts.by_name("rotr_imm");
+    let sdiv = insts.by_name("sdiv");
+    let sextend = insts.by_name("sextend");
+    let sload16 = insts.by_name("sload16");
+    let sload8 = insts.by_name("sload8");
+    let smulhi = insts.by_name("smulhi");
+    let spill = insts.by_name("spill");
+    let sqrt = insts.by_name("sqrt");
+    let srem = insts.by_name("srem");
+    let sshr = insts.by_name("sshr");
+    let sshr_imm = insts.by_name("sshr_imm");
+    let stack_addr = insts.by_name("stack_addr");
+    let store = insts.by_name("store");
+    let trap = insts.by_name("trap");
+    let udiv = insts.by_name("udiv");
+    let uextend = insts.by_name("uextend");
+    let uload16 = insts.by_name("uload16");
+    let uload8 = insts.by_name("uload8");
+    let umulhi = insts.by_name("umulhi");
+    let urem = insts.by_name("urem");
+    let ushr = insts.by_name("ushr");
+    let ushr_imm = insts.by_name("ushr_imm");
+    let x_return = insts.by_name("return");
+
+    // Types shorthands.
+    let b1 = ValueType::from(Bool::B1);
+    let i8 = ValueType::from(Int::I8);
+    let i16 = ValueType::from(Int::I16);
+    let i32 = ValueType::from(Int::I32);
+
+    // The types held in integer registers.
+    let types32 = [i8, i16, i32, b1];
+
+    // ARM32 specific instructions.
+    let arm32_pop = arm32_instructions.by_name("arm32_pop");
+    let arm32_push = arm32_instructions.by_name("arm32_push");
+
+    // Settings predicates.
+    let has_idiv = isa_settings.predicate_by_name("has_idiv");
+
+    // Integer instructions on registers.
+    for &(inst, op) in &[
+        (iadd, 0xe080_0000),
+        (isub, 0xe040_0000),
+        (band, 0xe000_0000),
+        (bor, 0xe180_0000),
+        (bxor, 0xe020_0000),
+        (band_not, 0xe1c0_0000),
+    ] {
+        e.enc(inst.bind(Int::I32), "R", a32_bits(op));
+    }
+
+    // Also add `b1` encodings for the logic instructions.
+    e.enc(band.bind(Bool::B1), "R", a32_bits(0xe000_0000));
+    e.enc(bor.bind(Bool::B1), "R", a32_bits(0xe180_0000));
+    e.enc(bxor.bind(Bool::B1), "R", a32_bits(0xe020_0000));
+
+    e.enc(imul.bind(Int::I32), "Rmul", a32_bits(0xe000_0090));
+    e.enc(umulhi.bind(Int::I32), "Rmulhi", a32_bits(0xe080_0090));
+    e.enc(smulhi.bind(Int::I32), "Rmulhi", a32_bits(0xe0c0_0090));
+
+    e.enc(bnot.bind(Int::I32), "Rmov", a32_bits(0xe1e0_0000));
+
+    // Shifts are aliases of `mov` with a shifted register operand.
+    for &(inst, op) in &[
+        (ishl, 0xe1a0_0000),
+        (ushr, 0xe1a0_0020),
+        (sshr, 0xe1a0_0040),
+        (rotr, 0xe1a0_0060),
+    ] {
+        e.enc(
+            inst.bind(Int::I32).bind(Int::I32),
+            "Rshift",
+            a32_bits(op | 0x10),
+        );
+    }
+
+    for &(inst, op) in &[
+        (ishl_imm, 0xe1a0_0000),
+        (ushr_imm, 0xe1a0_0020),
+        (sshr_imm, 0xe1a0_0040),
+        (rotr_imm, 0xe1a0_0060),
+    ] {
+        e.enc(inst.bind(Int::I32), "Rshi", a32_bits(op));
+    }
+
+    // Data processing with an immediate operand.
+    for &(inst, op) in &[
+        (iadd_imm, 0xe280_0000),
+        (irsub_imm, 0xe260_0000),
+        (band_imm, 0xe200_0000),
+        (bor_imm, 0xe380_0000),
+        (bxor_imm, 0xe220_0000),
+    ] {
+        e.enc(inst.bind(Int::I32), "Ri", a32_bits(op));
+    }
+
+    // Integer division traps like the clif base instructions, which the A32
+    // division instructions don't. They are optional in ARMv7-A.
+    e.enc_isap(udiv.bind(Int::I32), "Rdiv", a32_bits(0xe730_0010), has_idiv);
+    e.enc_isap(
+        sdiv.bind(Int::I32),
+        "Rsdiv",
+        a32_bits(0xe710_0010),
+        has_idiv,
+    );
+    e.enc_isap(urem.bind(Int::I32), "Rrem", a32_bits(0xe730_0010), has_idiv);
+    e.enc_isap(srem.bind(Int::I32), "Rrem", a32_bits(0xe710_0010), has_idiv);
+
+    e.enc(clz.bind(Int::I32), "Rclz", a32_bits(0xe160_0010));
+
+    // Comparisons are `cmp` followed by conditional moves.
+    e.enc(icmp.bind(Int::I32), "Ricmp", a32_bits(0xe150_0000));
+    e.enc(icmp_imm.bind(Int::I32), "Iicmp", a32_bits(0xe350_0000));
+
+    // Integer constants.
+    e.enc(iconst.bind(Int::I32), "Iz", a32_bits(0xe300_0000));
+    e.enc(iconst.bind(Int::I32), "Iconst", a32_bits(0xe300_0000));
+    e.enc(bconst.bind(Bool::B1), "Izb", a32_bits(0xe300_0000));
+
+    // Extensions.
+    e.enc(
+        uextend.bind(Int::I32).bind(Int::I8),
+        "Rext",
+        a32_bits(0xe6e0_0070),
+    );
+    e.enc(
+        uextend.bind(Int::I32).bind(Int::I16),
+        "Rext",
+        a32_bits(0xe6f0_0070),
+    );
+    e.enc(
+        sextend.bind(Int::I32).bind(Int::I8),
+        "Rext",
+        a32_bits(0xe6a0_0070),
+    );
+    e.enc(
+        sextend.bind(Int::I32).bind(Int::I16),
+        "Rext",
+        a32_bits(0xe6b0_0070),
+    );
+
+    // Reductions ignore the high bits of the register, and booleans are 0 or 1.
+    e.enc(ireduce.bind(Int::I8).bind(Int::I32), "null", 0);
+    e.enc(ireduce.bind(Int::I16).bind(Int::I32), "null", 0);
+    e.enc(bint.bind(Int::I32).bind(Bool::B1), "null", 0);
+
+    // Register copies.
+    for ty in &types32 {
+        e.enc(copy.bind(ty.clone()), "Rmov", a32_bits(0xe1a0_0000));
+        e.enc(regmove.bind(ty.clone()), "Rrmov", a32_bits(0xe1a0_0000));
+    }
+
+    e.enc(copy.bind(Float::F32), "Sr", vfp_bits(0xeeb0_0a40));
+    e.enc(copy.bind(Float::F64), "Dr", vfp_bits(0xeeb0_0a40 | DOUBLE));
+    e.enc(regmove.bind(Float::F32), "Srmov", vfp_bits(0xeeb0_0a40));
+    e.enc(
+        regmove.bind(Float::F64),
+        "Drmov",
+        vfp_bits(0xeeb0_0a40 | DOUBLE),
+    );
+
+    // Loads and stores. The templates are the immediate offset variants, with
+    // an added offset.
+    for &recipe in &["LD", "LDr"] {
+        e.enc(
+            load.bind(Int::I32).bind_any(),
+            recipe,
+            a32_bits(0xe590_0000),
+        );
+        e.enc(
+            uload8.bind(Int::I32).bind_any(),
+            recipe,
+            a32_bits(0xe5d0_0000),
+        );
+    }
+
+    for &recipe in &["LDh", "LDhr"] {
+        e.enc(
+            sload8.bind(Int::I32).bind_any(),
+            recipe,
+            a32_bits(0xe1d0_00d0),
+        );
+        e.enc(
+            uload16.bind(Int::I32).bind_any(),
+            recipe,
+            a32_bits(0xe1d0_00b0),
+        );
+        e.enc(
+            sload16.bind(Int::I32).bind_any(),
+            recipe,
+            a32_bits(0xe1d0_00f0),
+        );
+    }
+
+    for &recipe in &["ST", "STr"] {
+        e.enc(
+            store.bind(Int::I32).bind_any(),
+            recipe,
+            a32_bits(0xe580_0000),
+        );
+        e.enc(
+            istore8.bind(Int::I32).bind_any(),
+            recipe,
+            a32_bits(0xe5c0_0000),
+        );
+    }
+
+    for &recipe in &["STh", "SThr"] {
+        e.enc(
+            istore16.bind(Int::I32).bind_any(),
+            recipe,
+            a32_bits(0xe1c0_00b0),
+        );
+    }
+
+    for &recipe in &["SLD", "SLDr"] {
+        e.enc(
+            load.bind(Float::F32).bind_any(),
+            recipe,
+            vx_bits(0xed90_0a00),
+        );
+    }
+
+    for &recipe in &["DLD", "DLDr"] {
+        e.enc(
+            load.bind(Float::F64).bind_any(),
+            recipe,
+            vx_bits(0xed90_0a00 | DOUBLE),
+        );
+    }
+
+    for &recipe in &["SST", "SSTr"] {
+        e.enc(
+            store.bind(Float::F32).bind_any(),
+            recipe,
+            vx_bits(0xed80_0a00),
+        );
+    }
+
+    for &recipe in &["DST", "DSTr"] {
+        e.enc(
+            store.bind(Float::F64).bind_any(),
+            recipe,
+            vx_bits(0xed80_0a00 | DOUBLE),
+        );
+    }
+
+    e.enc(stack_addr.bind(Int::I32), "Iaddr", a32_bits(0xe080_0000));
+
+    // Spill and fill. Spill slots are at least 4 bytes, so the narrow types are
+    // spilled with word stores.
+    for ty in &types32 {
+        e.enc(spill.bind(ty.clone()), "GPsp", a32_bits(0xe580_0000));
+        e.enc(fill.bind(ty.clone()), "GPfi", a32_bits(0xe590_0000));
+        e.enc(regspill.bind(ty.clone()), "GPrsp", a32_bits(0xe580_0000));
+        e.enc(regfill.bind(ty.clone()), "GPrfi", a32_bits(0xe590_0000));
+    }
+
+    e.enc(spill.bind(Float::F32), "Ssp", vx_bits(0xed80_0a00));
+    e.enc(fill.bind(Float::F32), "Sfi", vx_bits(0xed90_0a00));
+    e.enc(regspill.bind(Float::F32), "Srsp", vx_bits(0xed80_0a00));
+    e.enc(regfill.bind(Float::F32), "Srfi", vx_bits(0xed90_0a00));
+    e.enc(spill.bind(Float::F64), "Dsp", vx_bits(0xed80_0a00 | DOUBLE));
+    e.enc(fill.bind(Float::F64), "Dfi", vx_bits(0xed90_0a00 | DOUBLE));
+    e.enc(
+        regspill.bind(Float::F64),
+        "Drsp",
+        vx_bits(0xed80_0a00 | DOUBLE),
+    );
+    e.enc(
+        regfill.bind(Float::F64),
+        "Drfi",
+        vx_bits(0xed90_0a00 | DOUBLE),
+    );
+
+    // Floating point arithmetic.
+    for &(inst, op) in &[
+        (fadd, 0xee30_0a00),
+        (fsub, 0xee30_0a40),
+        (fmul, 0xee20_0a00),
+        (fdiv, 0xee80_0a00),
+    ] {
+        e.enc(inst.bind(Float::F32), "Srr", vfp_bits(op));
+        e.enc(inst.bind(Float::F64), "Drr", vfp_bits(op | DOUBLE));
+    }
+
+    for &(inst, op) in &[
+        (fneg, 0xeeb1_0a40),
+        (fabs, 0xeeb0_0ac0),
+        (sqrt, 0xeeb1_0ac0),
+    ] {
+        e.enc(inst.bind(Float::F32), "Sr", vfp_bits(op));
+        e.enc(inst.bind(Float::F64), "Dr", vfp_bits(op | DOUBLE));
+    }
+
+    e.enc(
+        fpromote.bind(Float::F64).bind(Float::F32),
+        "Rpromote",
+        vfp_bits(0xeeb7_0ac0),
+    );
+    e.enc(
+        fdemote.bind(Float::F32).bind(Float::F64),
+        "Rdemote",
+        vfp_bits(0xeeb7_0ac0 | DOUBLE),
+    );
+
+    // Floating point comparisons. The recipes select the supported condition
+    // codes.
+    e.enc(fcmp.bind(Float::F32), "Scmp", vfp_bits(0xeeb4_0a40));
+    e.enc(fcmp.bind(Float::F32), "Scmp2", vfp_bits(0xeeb4_0a40));
+    e.enc(
+        fcmp.bind(Float::F64),
+        "Dcmp",
+        vfp_bits(0xeeb4_0a40 | DOUBLE),
+    );
+    e.enc(
+        fcmp.bind(Float::F64),
+        "Dcmp2",
+        vfp_bits(0xeeb4_0a40 | DOUBLE),
+    );
+
+    // Moves between integer and single precision registers.
+    e.enc(
+        bitcast.bind(Float::F32).bind(Int::I32),
+        "Svmov",
+        vx_bits(0xee00_0a10),
+    );
+    e.enc(
+        bitcast.bind(Int::I32).bind(Float::F32),
+        "Rvmov",
+        vx_bits(0xee10_0a10),
+    );
+
+    e.enc(
+        fcvt_from_sint.bind(Float::F32).bind(Int::I32),
+        "Scvt",
+        vfp_bits(0xeeb8_0ac0),
+    );
+    e.enc(
+        fcvt_from_sint.bind(Float::F64).bind(Int::I32),
+        "Dcvt",
+        vfp_bits(0xeeb8_0ac0 | DOUBLE),
+    );
+    e.enc(
+        fcvt_from_uint.bind(Float::F32).bind(Int::I32),
+        "Scvt",
+        vfp_bits(0xeeb8_0a40),
+    );
+    e.enc(
+        fcvt_from_uint.bind(Float::F64).bind(Int::I32),
+        "Dcvt",
+        vfp_bits(0xeeb8_0a40 | DOUBLE),
+    );
+
+    // Control flow.
+
+    // Unconditional branches.
+    e.enc(jump, "UJ", b_bits(0xea00_0000));
+    e.enc(call, "UJcall", b_bits(0xeb00_0000));
+
+    // Conditional branches.
+    e.enc(br_icmp.bind(Int::I32), "SB", a32_bits(0xe150_0000));
+
+    for &(inst, op) in &[(brz, 0x0a00_0000), (brnz, 0x1a00_0000)] {
+        e.enc(inst.bind(Int::I32), "Bz", b_bits(op));
+        e.enc(inst.bind(Bool::B1), "Bz", b_bits(op));
+    }
+
+    // Returns branch to the address in the link register `lr`, which is
+    // provided by a special-purpose `link` return value added by the prologue.
+    e.enc(x_return, "Iret", a32_bits(0xe120_0010));
+    e.enc(call_indirect.bind(Int::I32), "Icall", a32_bits(0xe120_0030));
+
+    e.enc(trap, "Iudf", 0);
+
+    // Prologue and epilogue.
+    e.enc(arm32_push.bind(Int::I32), "Push", a32_bits(0xe5a0_0000));
+    e.enc(arm32_pop.bind(Int::I32), "Pop", a32_bits(0xe490_0000));
+    e.enc(
+        arm32_push.bind(Float::F64),
+        "Dpush",
+        vx_bits(0xed20_0a00 | DOUBLE),
+    );
+    e.enc(
+        arm32_pop.bind(Float::F64),
+        "Dpop",
+        vx_bits(0xecb0_0a00 | DOUBLE),
+    );
+
+    e.enc(copy_special, "Icopysp", a32_bits(0xe1a0_0000));
+
+    for &(recipe, down, up) in &[
+        ("Iadjsp", 0xe240_0000, 0xe280_0000),
+        ("Iadjsp16", 0xe040_0000, 0xe080_0000),
+    ] {
+        e.enc(adjust_sp_down_imm, recipe, a32_bits(down));
+        e.enc(adjust_sp_up_imm, recipe, a32_bits(up));
+    }
+
+    e
+}
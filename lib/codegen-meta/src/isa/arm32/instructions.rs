@@ -0,0 +1,60 @@
+//! Supplementary instruction definitions for ARM32.
+//!
+//! This module defines additional instructions that are useful only to the
+//! ARM32 target ISA.
+
+#![allow(non_snake_case)]
+
+use cdsl::formats::FormatRegistry;
+use cdsl::instructions::{InstructionBuilder as Inst, InstructionGroup, InstructionGroupBuilder};
+use cdsl::operands::Operand;
+use cdsl::typevar::{TypeSetBuilder, TypeVar};
+
+pub fn define(format_registry: &FormatRegistry) -> InstructionGroup {
+    let mut ig =
+        InstructionGroupBuilder::new("arm32", "arm32-specific instruction set", format_registry);
+
+    let Reg = &TypeVar::new(
+        "Reg",
+        "A 32-bit integer or 64-bit floating point register",
+        TypeSetBuilder::new().ints(32..=32).floats(64..=64).finish(),
+    );
+
+    let x = &Operand::new("x", Reg);
+
+    ig.push(
+        Inst::new(
+            "arm32_push",
+            r#"
+        Pushes a value onto the stack.
+
+        Decrements the stack pointer by the size of `x` and stores it on to the
+        top.
+
+        This is polymorphic in i32 and f64.
+        "#,
+        )
+        .operands_in(vec![x])
+        .can_store(true)
+        .other_side_effects(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "arm32_pop",
+            r#"
+        Pops a value from the stack.
+
+        Loads a value from the top of the stack and then increments the stack
+        pointer by its size. This is the inverse of :inst:`arm32_push`.
+
+        This is polymorphic in i32 and f64.
+        "#,
+        )
+        .operands_out(vec![x])
+        .can_load(true)
+        .other_side_effects(true),
+    );
+
+    ig.finish()
+}
@@ -0,0 +1,54 @@
+//! ARM 32-bit Target Architecture.
+
+use base;
+use base::types::{Bool, Float, Int};
+use cdsl::cpu_modes::CpuMode;
+use cdsl::isa::TargetIsa;
+use cdsl::settings::SettingGroup;
+
+mod encodings;
+mod instructions;
+mod recipes;
+mod registers;
+mod settings;
+
+pub fn define(shared_settings: &SettingGroup, shared_defs: &mut base::Definitions) -> TargetIsa {
+    let settings = settings::define(shared_settings);
+    let regs = registers::define();
+
+    let instructions = instructions::define(&shared_defs.format_registry);
+
+    let groups = &shared_defs.transform_groups;
+    let narrow = groups.by_name("narrow");
+    let widen = groups.by_name("widen");
+    let expand = groups.by_name("expand");
+
+    let mut a32 = CpuMode::new("A32");
+    let mut t32 = CpuMode::new("T32");
+
+    a32.legalize_monomorphic(expand);
+    a32.legalize_default(narrow);
+    a32.legalize_type(Bool::B1, expand);
+    a32.legalize_type(Int::I8, widen);
+    a32.legalize_type(Int::I16, widen);
+    a32.legalize_type(Int::I32, expand);
+    a32.legalize_type(Float::F32, expand);
+    a32.legalize_type(Float::F64, expand);
+
+    // There are no T32 encodings yet.
+    t32.legalize_default(narrow);
+
+    let recipes = recipes::define(shared_defs, &regs);
+
+    let encodings = encodings::define(shared_defs, &settings, &instructions, &recipes);
+    a32.set_encodings(encodings.enc32);
+
+    TargetIsa::new(
+        "arm32",
+        settings,
+        regs,
+        instructions,
+        recipes,
+        vec![a32, t32],
+    )
+}
@@ -0,0 +1,839 @@
+//! ARM32 Encoding recipes.
+//!
+//! All A32 instructions are 32 bits wide. The encoding recipes defined here
+//! correspond to the instruction classes described in the reference:
+//!
+//! > ARM Architecture Reference Manual
+//! > ARMv7-A and ARMv7-R edition
+//!
+//! Only the A32 instruction set is covered. The instructions are all
+//! unconditional, except for the conditional branches and moves emitted by
+//! some of the recipes.
+
+use base;
+use cdsl::formats::InstructionFormat;
+use cdsl::instructions::InstructionPredicate;
+use cdsl::operands::OperandKind;
+use cdsl::recipes::{EncodingRecipeBuilder, OperandConstraint, RecipeGroup, Stack};
+use cdsl::registers::IsaRegs;
+
+// The encbits of a recipe hold the opcode bits of the instructions it encodes.
+// The operand fields and the condition are filled in by the `put_*` functions
+// of `isa/arm32/binemit.rs`, so the layout of the encbits depends on the
+// instruction class. The functions below compute the encbits from an
+// instruction template with all its operand fields zero.
+
+/// Data processing, multiplication and load/store instructions:
+/// `inst[27:20] | (inst[7:4] << 8)`.
+///
+/// The four register fields `inst[19:16]`, `inst[15:12]`, `inst[11:8]` and
+/// `inst[3:0]` must be zero. Fixed register fields are supplied by the
+/// recipes.
+pub fn a32_bits(inst: u32) -> u16 {
+    assert!(inst >> 28 == 0xe, "{:#x}", inst);
+    assert!(inst & 0x000f_ff0f == 0, "{:#x}", inst);
+    (((inst >> 20) & 0xff) | (((inst >> 4) & 0xf) << 8)) as u16
+}
+
+/// Floating point data processing: `inst[27:16] | (inst[8] << 12) |
+/// (inst[7:6] << 13)`.
+///
+/// The `Vd` and `Vm` register fields must be zero, as well as the `Vn` field
+/// of the instructions with two sources.
+pub fn vfp_bits(inst: u32) -> u16 {
+    assert!(inst >> 24 == 0xee, "{:#x}", inst);
+    assert!((inst >> 9) & 0b111 == 0b101, "{:#x}", inst);
+    assert!(inst & 0x0040_f03f == 0, "{:#x}", inst);
+    (((inst >> 16) & 0xfff) | (((inst >> 8) & 1) << 12) | (((inst >> 6) & 3) << 13)) as u16
+}
+
+/// Floating point loads and stores, and transfers between integer and
+/// floating point registers: `inst[27:20] | (inst[8] << 8) | (inst[4] << 9)`.
+pub fn vx_bits(inst: u32) -> u16 {
+    assert!(inst >> 28 == 0xe, "{:#x}", inst);
+    assert!((inst >> 9) & 0b111 == 0b101, "{:#x}", inst);
+    assert!(inst & 0x004f_f0ef == 0, "{:#x}", inst);
+    (((inst >> 20) & 0xff) | (((inst >> 8) & 1) << 8) | (((inst >> 4) & 1) << 9)) as u16
+}
+
+/// Branches to an immediate displacement, including their condition:
+/// `inst[31:24]`.
+pub fn b_bits(inst: u32) -> u16 {
+    assert!(inst & 0x00ff_ffff == 0, "{:#x}", inst);
+    (inst >> 24) as u16
+}
+
+/// The floating point condition codes which can be tested with a single
+/// condition after `vcmp`.
+const SUPPORTED_FLOATCCS: [&str; 12] = [
+    "eq", "ne", "lt", "le", "gt", "ge", "uno", "ord", "ult", "ule", "ugt", "uge",
+];
+
+/// Get an instruction predicate checking that the `cond` field of `format` is
+/// one of the floating point condition codes in `ccs`.
+fn floatccs(
+    format: &InstructionFormat,
+    floatcc: &OperandKind,
+    ccs: &[&str],
+) -> InstructionPredicate {
+    InstructionPredicate::Or(
+        ccs.iter()
+            .map(|cc| InstructionPredicate::is_equal(format, "cond", floatcc.rust_enumerator(cc)))
+            .collect(),
+    )
+}
+
+pub fn define(shared_defs: &base::Definitions, regs: &IsaRegs) -> RecipeGroup {
+    let formats = &shared_defs.format_registry;
+    let floatcc = &shared_defs.imm.floatcc;
+
+    // Format shorthands.
+    let f_binary = formats.by_name("Binary");
+    let f_binary_imm = formats.by_name("BinaryImm");
+    let f_branch = formats.by_name("Branch");
+    let f_branch_icmp = formats.by_name("BranchIcmp");
+    let f_call = formats.by_name("Call");
+    let f_call_indirect = formats.by_name("CallIndirect");
+    let f_copy_special = formats.by_name("CopySpecial");
+    let f_float_compare = formats.by_name("FloatCompare");
+    let f_int_compare = formats.by_name("IntCompare");
+    let f_int_compare_imm = formats.by_name("IntCompareImm");
+    let f_jump = formats.by_name("Jump");
+    let f_load = formats.by_name("Load");
+    let f_multiary = formats.by_name("MultiAry");
+    let f_nullary = formats.by_name("NullAry");
+    let f_regfill = formats.by_name("RegFill");
+    let f_regmove = formats.by_name("RegMove");
+    let f_regspill = formats.by_name("RegSpill");
+    let f_stack_load = formats.by_name("StackLoad");
+    let f_store = formats.by_name("Store");
+    let f_trap = formats.by_name("Trap");
+    let f_unary = formats.by_name("Unary");
+    let f_unary_bool = formats.by_name("UnaryBool");
+    let f_unary_imm = formats.by_name("UnaryImm");
+
+    // Register classes shorthands.
+    let gpr_class = regs.class_by_name("GPR");
+    let gpr = OperandConstraint::from(gpr_class);
+    let s_class = regs.class_by_name("S");
+    let s = OperandConstraint::from(s_class);
+    let d_class = regs.class_by_name("D");
+    let d = OperandConstraint::from(d_class);
+
+    let mut recipes = RecipeGroup::new();
+
+    // Integer instructions on registers.
+    recipes.push(
+        EncodingRecipeBuilder::new("R", f_binary, 4)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit("put_a32(bits, in_reg0, out_reg0, 0, in_reg1, sink);")
+            .build(formats),
+    );
+
+    // Multiplication, which has its destination in `inst[19:16]`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rmul", f_binary, 4)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit("put_a32(bits, out_reg0, 0, in_reg1, in_reg0, sink);")
+            .build(formats),
+    );
+
+    // High half of a long multiplication. The low half goes to the scratch
+    // register `ip`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rmulhi", f_binary, 4)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit("put_a32(bits, out_reg0, 12, in_reg1, in_reg0, sink);")
+            .build(formats),
+    );
+
+    // Copy of a GPR with `mov`, or its complement with `mvn`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rmov", f_unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit("put_a32(bits, 0, out_reg0, 0, in_reg0, sink);")
+            .build(formats),
+    );
+
+    // Same for a GPR regmove.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rrmov", f_regmove, 4)
+            .operands_in(vec![gpr])
+            .emit("put_a32(bits, 0, dst, 0, src, sink);")
+            .build(formats),
+    );
+
+    // A null unary instruction that takes a GPR register. Can be used for
+    // no-op conversions.
+    recipes.push(
+        EncodingRecipeBuilder::new("null", f_unary, 0)
+            .operands_in(vec![gpr])
+            .operands_out(vec![OperandConstraint::TiedInput(0)])
+            .emit("")
+            .build(formats),
+    );
+
+    // Count leading zeros. The unused register fields are all ones.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rclz", f_unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit("put_a32(bits, 15, out_reg0, 15, in_reg0, sink);")
+            .build(formats),
+    );
+
+    // Sign and zero extensions with `sxtb`, `uxth` and friends. The `rn` field
+    // is all ones, which selects the variants without an addend.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rext", f_unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit("put_a32(bits, 15, out_reg0, 0, in_reg0, sink);")
+            .build(formats),
+    );
+
+    // Unsigned division, trapping on a zero divisor.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rdiv", f_binary, 16)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    put_trap_zero(in_reg1, func.srclocs[inst], sink);
+                    put_a32(bits, out_reg0, 15, in_reg1, in_reg0, sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    // Signed division, trapping on a zero divisor and on overflow.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rsdiv", f_binary, 32)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    put_trap_zero(in_reg1, func.srclocs[inst], sink);
+                    put_trap_sdiv_overflow(in_reg0, in_reg1, func.srclocs[inst], sink);
+                    put_a32(bits, out_reg0, 15, in_reg1, in_reg0, sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    // Remainder of a division, trapping on a zero divisor. The quotient is
+    // computed in the scratch register `ip` and multiplied back with `mls`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rrem", f_binary, 20)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    put_trap_zero(in_reg1, func.srclocs[inst], sink);
+                    put_a32(bits, 12, 15, in_reg1, in_reg0, sink);
+                    put_mls(12, in_reg1, in_reg0, out_reg0, sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    // Shifts by a register amount. The amount is masked to 5 bits first, since
+    // the A32 shifts use the low byte of the amount register.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rshift", f_binary, 8)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit("put_shift(bits, in_reg0, in_reg1, out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Shifts by an immediate amount.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rshi", f_binary_imm, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit("put_shi(bits, in_reg0, imm.into(), out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Data processing with an 8-bit unsigned immediate.
+    recipes.push(
+        EncodingRecipeBuilder::new("Ri", f_binary_imm, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::is_unsigned_int(
+                formats.get(f_binary_imm),
+                "imm",
+                8,
+                0,
+            ))
+            .emit("put_dpi(bits, in_reg0, imm.into(), out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Constants with 16 significant bits, with `movw`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Iz", f_unary_imm, 4)
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::is_unsigned_int(
+                formats.get(f_unary_imm),
+                "imm",
+                16,
+                0,
+            ))
+            .emit("put_movw(bits, imm.into(), out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Any 32-bit constant, with a `movw` and a `movt`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Iconst", f_unary_imm, 8)
+            .operands_out(vec![gpr])
+            .emit("put_const(bits, imm.into(), out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Boolean constants are materialized with `movw`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Izb", f_unary_bool, 4)
+            .operands_out(vec![gpr])
+            .emit("put_movw(bits, if imm { 1 } else { 0 }, out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Integer comparison: `cmp` followed by a conditional `mov`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Ricmp", f_int_compare, 12)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    put_a32(bits, in_reg0, 0, 0, in_reg1, sink);
+                    put_cset(icc2cond(cond), out_reg0, sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    // Integer comparison with an 8-bit unsigned immediate.
+    recipes.push(
+        EncodingRecipeBuilder::new("Iicmp", f_int_compare_imm, 12)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::is_unsigned_int(
+                formats.get(f_int_compare_imm),
+                "imm",
+                8,
+                0,
+            ))
+            .emit(
+                r#"
+                    put_dpi(bits, in_reg0, imm.into(), 0, sink);
+                    put_cset(icc2cond(cond), out_reg0, sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    // Floating point comparison: `vcmp` and `vmrs` to copy the flags, followed
+    // by a conditional `mov`.
+    for &(name, reg) in &[("Scmp", s), ("Dcmp", d)] {
+        recipes.push(
+            EncodingRecipeBuilder::new(name, f_float_compare, 16)
+                .operands_in(vec![reg, reg])
+                .operands_out(vec![gpr])
+                .inst_predicate(floatccs(
+                    formats.get(f_float_compare),
+                    floatcc,
+                    &SUPPORTED_FLOATCCS,
+                ))
+                .emit(
+                    r#"
+                        put_vfp1(bits, in_reg1, in_reg0, sink);
+                        put_vmrs(sink);
+                        put_cset(fcc2cond(cond), out_reg0, sink);
+                    "#,
+                )
+                .build(formats),
+        );
+    }
+
+    // Floating point comparisons which need two condition codes, with one
+    // conditional `mov` for each.
+    for &(name, reg) in &[("Scmp2", s), ("Dcmp2", d)] {
+        recipes.push(
+            EncodingRecipeBuilder::new(name, f_float_compare, 20)
+                .operands_in(vec![reg, reg])
+                .operands_out(vec![gpr])
+                .inst_predicate(floatccs(
+                    formats.get(f_float_compare),
+                    floatcc,
+                    &["ueq", "one"],
+                ))
+                .emit(
+                    r#"
+                        put_vfp1(bits, in_reg1, in_reg0, sink);
+                        put_vmrs(sink);
+                        put_cset2(cond, out_reg0, sink);
+                    "#,
+                )
+                .build(formats),
+        );
+    }
+
+    // Floating point instructions on registers.
+    for &(name, reg) in &[("Srr", s), ("Drr", d)] {
+        recipes.push(
+            EncodingRecipeBuilder::new(name, f_binary, 4)
+                .operands_in(vec![reg, reg])
+                .operands_out(vec![reg])
+                .emit("put_vfp2(bits, in_reg0, in_reg1, out_reg0, sink);")
+                .build(formats),
+        );
+    }
+
+    // Floating point instructions with a single register operand, including
+    // copies.
+    for &(name, reg) in &[("Sr", s), ("Dr", d)] {
+        recipes.push(
+            EncodingRecipeBuilder::new(name, f_unary, 4)
+                .operands_in(vec![reg])
+                .operands_out(vec![reg])
+                .emit("put_vfp1(bits, in_reg0, out_reg0, sink);")
+                .build(formats),
+        );
+    }
+
+    // Same for floating point regmoves.
+    for &(name, reg) in &[("Srmov", s), ("Drmov", d)] {
+        recipes.push(
+            EncodingRecipeBuilder::new(name, f_regmove, 4)
+                .operands_in(vec![reg])
+                .emit("put_vfp1(bits, src, dst, sink);")
+                .build(formats),
+        );
+    }
+
+    // Conversions between single and double precision.
+    for &(name, from, to) in &[("Rpromote", s, d), ("Rdemote", d, s)] {
+        recipes.push(
+            EncodingRecipeBuilder::new(name, f_unary, 4)
+                .operands_in(vec![from])
+                .operands_out(vec![to])
+                .emit("put_vfp1(bits, in_reg0, out_reg0, sink);")
+                .build(formats),
+        );
+    }
+
+    // Conversions from integers. The integer is moved to the low single
+    // precision half of the destination with `vmov` first.
+    for &(name, reg) in &[("Scvt", s), ("Dcvt", d)] {
+        recipes.push(
+            EncodingRecipeBuilder::new(name, f_unary, 8)
+                .operands_in(vec![gpr])
+                .operands_out(vec![reg])
+                .emit(
+                    r#"
+                        put_vmov(VMOV_TO_S, out_reg0, in_reg0, sink);
+                        put_vfp1(bits, out_reg0, out_reg0, sink);
+                    "#,
+                )
+                .build(formats),
+        );
+    }
+
+    // Moves between integer and single precision registers with `vmov`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Svmov", f_unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![s])
+            .emit("put_vmov(bits, out_reg0, in_reg0, sink);")
+            .build(formats),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("Rvmov", f_unary, 4)
+            .operands_in(vec![s])
+            .operands_out(vec![gpr])
+            .emit("put_vmov(bits, in_reg0, out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Word and unsigned byte loads with a 12-bit offset, and halfword and
+    // signed byte loads with an 8-bit offset. The offsets are limited to signed
+    // bits to keep the predicates simple.
+    for &(name, width, put) in &[("LD", 12, "put_ldst"), ("LDh", 8, "put_ldsth")] {
+        recipes.push(
+            EncodingRecipeBuilder::new(name, f_load, 4)
+                .operands_in(vec![gpr])
+                .operands_out(vec![gpr])
+                .inst_predicate(InstructionPredicate::is_signed_int(
+                    formats.get(f_load),
+                    "offset",
+                    width,
+                    0,
+                ))
+                .emit(format!(
+                    "{}(bits, out_reg0, in_reg0, offset.into(), sink);",
+                    put
+                ))
+                .build(formats),
+        );
+    }
+
+    // Loads with any offset, materialized in the scratch register `ip`.
+    for &(name, put) in &[("LDr", "put_ldst_offset"), ("LDhr", "put_ldsth_offset")] {
+        recipes.push(
+            EncodingRecipeBuilder::new(name, f_load, 12)
+                .operands_in(vec![gpr])
+                .operands_out(vec![gpr])
+                .emit(format!(
+                    "{}(bits, out_reg0, in_reg0, offset.into(), sink);",
+                    put
+                ))
+                .build(formats),
+        );
+    }
+
+    // Stores, with the same offset variants as the loads.
+    for &(name, width, put) in &[("ST", 12, "put_ldst"), ("STh", 8, "put_ldsth")] {
+        recipes.push(
+            EncodingRecipeBuilder::new(name, f_store, 4)
+                .operands_in(vec![gpr, gpr])
+                .inst_predicate(InstructionPredicate::is_signed_int(
+                    formats.get(f_store),
+                    "offset",
+                    width,
+                    0,
+                ))
+                .emit(format!(
+                    "{}(bits, in_reg0, in_reg1, offset.into(), sink);",
+                    put
+                ))
+                .build(formats),
+        );
+    }
+
+    for &(name, put) in &[("STr", "put_ldst_offset"), ("SThr", "put_ldsth_offset")] {
+        recipes.push(
+            EncodingRecipeBuilder::new(name, f_store, 12)
+                .operands_in(vec![gpr, gpr])
+                .emit(format!(
+                    "{}(bits, in_reg0, in_reg1, offset.into(), sink);",
+                    put
+                ))
+                .build(formats),
+        );
+    }
+
+    // Floating point loads and stores with a word-aligned 10-bit offset.
+    for &(name, reg) in &[("SLD", s), ("DLD", d)] {
+        recipes.push(
+            EncodingRecipeBuilder::new(name, f_load, 4)
+                .operands_in(vec![gpr])
+                .operands_out(vec![reg])
+                .inst_predicate(InstructionPredicate::is_signed_int(
+                    formats.get(f_load),
+                    "offset",
+                    10,
+                    2,
+                ))
+                .emit("put_vldst(bits, out_reg0, in_reg0, offset.into(), sink);")
+                .build(formats),
+        );
+    }
+
+    for &(name, reg) in &[("SST", s), ("DST", d)] {
+        recipes.push(
+            EncodingRecipeBuilder::new(name, f_store, 4)
+                .operands_in(vec![reg, gpr])
+                .inst_predicate(InstructionPredicate::is_signed_int(
+                    formats.get(f_store),
+                    "offset",
+                    10,
+                    2,
+                ))
+                .emit("put_vldst(bits, in_reg0, in_reg1, offset.into(), sink);")
+                .build(formats),
+        );
+    }
+
+    // Floating point loads and stores with any offset. The address is computed
+    // in the scratch register `ip`.
+    for &(name, reg) in &[("SLDr", s), ("DLDr", d)] {
+        recipes.push(
+            EncodingRecipeBuilder::new(name, f_load, 16)
+                .operands_in(vec![gpr])
+                .operands_out(vec![reg])
+                .emit("put_vldst_offset(bits, out_reg0, in_reg0, offset.into(), sink);")
+                .build(formats),
+        );
+    }
+
+    for &(name, reg) in &[("SSTr", s), ("DSTr", d)] {
+        recipes.push(
+            EncodingRecipeBuilder::new(name, f_store, 16)
+                .operands_in(vec![reg, gpr])
+                .emit("put_vldst_offset(bits, in_reg0, in_reg1, offset.into(), sink);")
+                .build(formats),
+        );
+    }
+
+    // Address of a stack slot, computed from the stack pointer.
+    recipes.push(
+        EncodingRecipeBuilder::new("Iaddr", f_stack_load, 12)
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    let sp = StackRef::sp(stack_slot, &func.stack_slots);
+                    let offset: i32 = offset.into();
+                    put_const(MOVW, i64::from(sp.offset + offset), out_reg0, sink);
+                    put_a32(bits, 13, out_reg0, 0, out_reg0, sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    // Spill and fill of a GPR, relative to the stack pointer. The stack frame
+    // is limited to 64 KB, so the offset is materialized with a single `movw`.
+    // The floating point registers are spilled and filled with the address
+    // computed in the scratch register `ip`.
+    for &(spill, fill, regclass, size, put) in &[
+        ("GPsp", "GPfi", gpr_class, 8, "put_ldst_sp"),
+        ("Ssp", "Sfi", s_class, 12, "put_vldst_sp"),
+        ("Dsp", "Dfi", d_class, 12, "put_vldst_sp"),
+    ] {
+        recipes.push(
+            EncodingRecipeBuilder::new(spill, f_unary, size)
+                .operands_in(vec![OperandConstraint::from(regclass)])
+                .operands_out(vec![Stack::new(regclass).into()])
+                .emit(format!(
+                    r#"
+                        sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                        {}(bits, in_reg0, out_stk0.offset, sink);
+                    "#,
+                    put
+                ))
+                .build(formats),
+        );
+
+        recipes.push(
+            EncodingRecipeBuilder::new(fill, f_unary, size)
+                .operands_in(vec![Stack::new(regclass).into()])
+                .operands_out(vec![OperandConstraint::from(regclass)])
+                .emit(format!("{}(bits, out_reg0, in_stk0.offset, sink);", put))
+                .build(formats),
+        );
+    }
+
+    // Regspill and regfill.
+    for &(spill, fill, regclass, size, put) in &[
+        ("GPrsp", "GPrfi", gpr_class, 8, "put_ldst_sp"),
+        ("Srsp", "Srfi", s_class, 12, "put_vldst_sp"),
+        ("Drsp", "Drfi", d_class, 12, "put_vldst_sp"),
+    ] {
+        recipes.push(
+            EncodingRecipeBuilder::new(spill, f_regspill, size)
+                .operands_in(vec![OperandConstraint::from(regclass)])
+                .emit(format!(
+                    r#"
+                        sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                        let dst = StackRef::sp(dst, &func.stack_slots);
+                        {}(bits, src, dst.offset, sink);
+                    "#,
+                    put
+                ))
+                .build(formats),
+        );
+
+        recipes.push(
+            EncodingRecipeBuilder::new(fill, f_regfill, size)
+                .operands_in(vec![Stack::new(regclass).into()])
+                .emit(format!(
+                    r#"
+                        let src = StackRef::sp(src, &func.stack_slots);
+                        {}(bits, dst, src.offset, sink);
+                    "#,
+                    put
+                ))
+                .build(formats),
+        );
+    }
+
+    // Push and pop a GPR with a pre-decrementing `str` and a post-incrementing
+    // `ldr`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Push", f_unary, 4)
+            .operands_in(vec![gpr])
+            .emit(
+                r#"
+                    sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                    put_ldst(bits, in_reg0, 13, -4, sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("Pop", f_nullary, 4)
+            .operands_out(vec![gpr])
+            .emit("put_ldst(bits, out_reg0, 13, 4, sink);")
+            .build(formats),
+    );
+
+    // Push and pop a double precision register with `vpush` and `vpop`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Dpush", f_unary, 4)
+            .operands_in(vec![d])
+            .emit(
+                r#"
+                    sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                    put_vpush_pop(bits, in_reg0, sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("Dpop", f_nullary, 4)
+            .operands_out(vec![d])
+            .emit("put_vpush_pop(bits, out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Copy of the stack pointer with `mov`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Icopysp", f_copy_special, 4)
+            .emit("put_a32(bits, 0, dst, 0, src, sink);")
+            .build(formats),
+    );
+
+    // Adjustments of the stack pointer by an 8-bit immediate.
+    recipes.push(
+        EncodingRecipeBuilder::new("Iadjsp", f_unary_imm, 4)
+            .inst_predicate(InstructionPredicate::is_unsigned_int(
+                formats.get(f_unary_imm),
+                "imm",
+                8,
+                0,
+            ))
+            .emit("put_dpi(bits, 13, imm.into(), 13, sink);")
+            .build(formats),
+    );
+
+    // Adjustments of the stack pointer by a 16-bit immediate, materialized in
+    // the scratch register `ip`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Iadjsp16", f_unary_imm, 8)
+            .inst_predicate(InstructionPredicate::is_unsigned_int(
+                formats.get(f_unary_imm),
+                "imm",
+                16,
+                0,
+            ))
+            .emit(
+                r#"
+                    put_movw(MOVW, imm.into(), 12, sink);
+                    put_a32(bits, 13, 13, 0, 12, sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    // Return is a `bx` to the link register. The return address is provided by
+    // the special-purpose `link` argument added by the prologue.
+    // The variable return values are not encoded.
+    recipes.push(
+        EncodingRecipeBuilder::new("Iret", f_multiary, 4)
+            .emit("put_a32(bits, 15, 15, 15, 14, sink);")
+            .build(formats),
+    );
+
+    // Indirect call with `blx`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Icall", f_call_indirect, 4)
+            .operands_in(vec![gpr])
+            .emit("put_a32(bits, 15, 15, 15, in_reg0, sink);")
+            .build(formats),
+    );
+
+    // Unconditional branch. The displacement is relative to the address of the
+    // branch plus 8.
+    recipes.push(
+        EncodingRecipeBuilder::new("UJ", f_jump, 4)
+            .branch_range((8, 26))
+            .emit(
+                r#"
+                    let dest = i64::from(func.offsets[destination]);
+                    let disp = dest - i64::from(sink.offset());
+                    put_b(bits, disp, sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    // Direct call with `bl`, relocated. The relocation provides the
+    // displacement, so the offset field is left zero.
+    recipes.push(
+        EncodingRecipeBuilder::new("UJcall", f_call, 4)
+            .emit(
+                r#"
+                    sink.reloc_external(Reloc::Arm32Call,
+                                        &func.dfg.ext_funcs[func_ref].name,
+                                        -8);
+                    put_b(bits, 8, sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    // Compare with zero and branch.
+    recipes.push(
+        EncodingRecipeBuilder::new("Bz", f_branch, 8)
+            .operands_in(vec![gpr])
+            .branch_range((12, 26))
+            .emit(
+                r#"
+                    put_cmp_zero(in_reg0, sink);
+                    let dest = i64::from(func.offsets[destination]);
+                    let disp = dest - i64::from(sink.offset());
+                    put_b(bits, disp, sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    // Integer comparison and conditional branch: `cmp` followed by `b<cond>`.
+    recipes.push(
+        EncodingRecipeBuilder::new("SB", f_branch_icmp, 8)
+            .operands_in(vec![gpr, gpr])
+            .branch_range((12, 26))
+            .emit(
+                r#"
+                    put_a32(bits, in_reg0, 0, 0, in_reg1, sink);
+                    let dest = i64::from(func.offsets[destination]);
+                    let disp = dest - i64::from(sink.offset());
+                    put_bcond(icc2cond(cond), disp, sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    // Traps are the permanently undefined instruction `udf`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Iudf", f_trap, 4)
+            .emit(
+                r#"
+                    sink.trap(code, func.srclocs[inst]);
+                    put_udf(sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    recipes
+}
@@ -0,0 +1,50 @@
+//! ARM32 register banks.
+
+use cdsl::registers::{IsaRegs, IsaRegsBuilder, RegBankBuilder, RegClassBuilder};
+
+pub fn define() -> IsaRegs {
+    let mut regs = IsaRegsBuilder::new();
+
+    // Define the larger float bank first to avoid the alignment gap.
+    let float_regs = regs.add_bank(
+        RegBankBuilder::new(
+            "FloatRegs",
+            r#"
+            Floating point registers.
+
+            The floating point register units correspond to the S-registers, but
+            extended as if there were 64 registers.
+
+            - S registers are one unit each.
+            - D registers are two units each, even D16 and above.
+            - Q registers are 4 units each.
+            "#,
+        )
+        .units(64)
+        .prefix("s"),
+    );
+
+    // Special register units:
+    // - r15 is the program counter.
+    // - r14 is the link register.
+    // - r13 is usually the stack pointer.
+    let int_regs = regs.add_bank(
+        RegBankBuilder::new("IntRegs", "General purpose registers")
+            .units(16)
+            .prefix("r"),
+    );
+    let flag_regs = regs.add_bank(
+        RegBankBuilder::new("FlagRegs", "Flag registers")
+            .units(1)
+            .names(vec!["nzcv"])
+            .track_pressure(false),
+    );
+
+    regs.add_class(RegClassBuilder::new_toplevel("GPR", int_regs));
+    regs.add_class(RegClassBuilder::new_toplevel("S", float_regs).count(32));
+    regs.add_class(RegClassBuilder::new_toplevel("D", float_regs).width(2));
+    regs.add_class(RegClassBuilder::new_toplevel("Q", float_regs).width(4));
+    regs.add_class(RegClassBuilder::new_toplevel("FLAG", flag_regs));
+
+    regs.finish()
+}
@@ -0,0 +1,13 @@
+//! ARM32 settings.
+
+use cdsl::settings::{SettingGroup, SettingGroupBuilder};
+
+pub fn define(_shared: &SettingGroup) -> SettingGroup {
+    let mut settings = SettingGroupBuilder::new("arm32");
+    settings.add_bool(
+        "has_idiv",
+        "Integer division: the `sdiv` and `udiv` instructions in ARM state",
+        false,
+    );
+    settings.finish()
+}
@@ -0,0 +1,592 @@
+//! ARM64 Encodings.
+
+use base;
+use cdsl::encodings::{Encoding, EncodingBuilder, InstSpec};
+use cdsl::instructions::{Bindable, InstructionGroup};
+use cdsl::recipes::RecipeGroup;
+use cdsl::types::ValueType;
+
+use base::types::{Bool, Float, Int};
+
+use super::recipes::{
+    b_bits, cb_bits, dp_bits, ext_bits, fp1_bits, imm_bits, ldst_bits, movw_bits, reg_bits,
+};
+
+/// The instruction templates below are the 32-bit or single precision
+/// variants of the instructions. These bits select the other variants.
+const SF: u32 = 1 << 31;
+const N: u32 = 1 << 22;
+const DOUBLE: u32 = 1 << 22;
+
+/// The encodings of the A64 CPU mode.
+pub struct PerCpuModeEncodings<'defs> {
+    pub enc64: Vec<Encoding>,
+    recipes: &'defs RecipeGroup,
+    shared_defs: &'defs base::Definitions,
+}
+
+impl<'defs> PerCpuModeEncodings<'defs> {
+    fn enc<I: Into<InstSpec>>(&mut self, inst: I, recipe: &str, bits: u16) {
+        let recipe = self.recipes.by_name(recipe);
+        let encoding = EncodingBuilder::new(inst, recipe, bits)
+            .build(self.recipes, &self.shared_defs.format_registry);
+        self.enc64.push(encoding);
+    }
+}
+
+pub fn define<'defs>(
+    shared_defs: &'defs base::Definitions,
+    arm64_instructions: &InstructionGroup,
+    recipes: &'defs RecipeGroup,
+) -> PerCpuModeEncodings<'defs> {
+    let insts = &shared_defs.instructions;
+
+    let mut e = PerCpuModeEncodings {
+        enc64: Vec::new(),
+        recipes,
+        shared_defs,
+    };
+
+    // Instructions shorthands.
+    let adjust_sp_down_imm = insts.by_name("adjust_sp_down_imm");
+    let adjust_sp_up_imm = insts.by_name("adjust_sp_up_imm");
+    let band = insts.by_name("band");
+    let band_not = insts.by_name("band_not");
+    let bconst = insts.by_name("bconst");
+    let bint = insts.by_name("bint");
+    let bitcast = insts.by_name("bitcast");
+    let bor = insts.by_name("bor");
+    let bor_not = insts.by_name("bor_not");
+    let br_icmp = insts.by_name("br_icmp");
+    let brnz = insts.by_name("brnz");
+    let brz = insts.by_name("brz");
+    let bxor = insts.by_name("bxor");
+    let bxor_not = insts.by_name("bxor_not");
+    let call = insts.by_name("call");
+    let call_indirect = insts.by_name("call_indirect");
+    let ceil = insts.by_name("ceil");
+    let clz = insts.by_name("clz");
+    let copy = insts.by_name("copy");
+    let copy_special = insts.by_name("copy_special");
+    let fabs = insts.by_name("fabs");
+    let fadd = insts.by_name("fadd");
+    let fcmp = insts.by_name("fcmp");
+    let fcvt_from_sint = insts.by_name("fcvt_from_sint");
+    let fcvt_from_uint = insts.by_name("fcvt_from_uint");
+    let fdemote = insts.by_name("fdemote");
+    let fdiv = insts.by_name("fdiv");
+    let fill = insts.by_name("fill");
+    let floor = insts.by_name("floor");
+    let fmax = insts.by_name("fmax");
+    let fmin = insts.by_name("fmin");
+    let fmul = insts.by_name("fmul");
+    let fneg = insts.by_name("fneg");
+    let fpromote = insts.by_name("fpromote");
+    let fsub = insts.by_name("fsub");
+    let iadd = insts.by_name("iadd");
+    let iadd_imm = insts.by_name("iadd_imm");
+    let icmp = insts.by_name("icmp");
+    let icmp_imm = insts.by_name("icmp_imm");
+    let iconst = insts.by_name("iconst");
+    let imul = insts.by_name("imul");
+    let ireduce = insts.by_name("ireduce");
+    let ishl = insts.by_name("ishl");
+    let ishl_imm = insts.by_name("ishl_imm");
+    let istore16 = insts.by_name("istore16");
+    let istore32 = insts.by_name("istore32");
+    let istore8 = insts.by_name("istore8");
+    let isub = insts.by_name("isub");
+    let jump = insts.by_name("jump");
+    let load = insts.by_name("load");
+    let nearest = insts.by_name("nearest");
+    let regfill = insts.by_name("regfill");
+    let regmove = insts.by_name("regmove");
+    let regspill = insts.by_name("regspill");
+    let rotr = insts.by_name("rotr");
+    let sdiv = insts.by_name("sdiv");
+    let sextend = insts.by_name("sextend");
+    let sload16 = insts.by_name("sload16");
+    let sload32 = insts.by_name("sload32");
+    let sload8 = insts.by_name("sload8");
+    let smulhi = insts.by_name("smulhi");
+    let spill = insts.by_name("spill");
+    let sqrt = insts.by_name("sqrt");
+    let srem = insts.by_name("srem");
+    let sshr = insts.by_name("sshr");
+    let sshr_imm = insts.by_name("sshr_imm");
+    let stack_addr = insts.by_name("stack_addr");
+    let store = insts.by_name("store");
+    let trap = insts.by_name("trap");
+    let trunc = insts.by_name("trunc");
+    let udiv = insts.by_name("udiv");
+    let uextend = insts.by_name("uextend");
+    let uload16 = insts.by_name("uload16");
+    let uload32 = insts.by_name("uload32");
+    let uload8 = insts.by_name("uload8");
+    let umulhi = insts.by_name("umulhi");
+    let urem = insts.by_name("urem");
+    let ushr = insts.by_name("ushr");
+    let ushr_imm = insts.by_name("ushr_imm");
+    let x_return = insts.by_name("return");
+
+    // Types shorthands.
+    let b1 = ValueType::from(Bool::B1);
+    let i8 = ValueType::from(Int::I8);
+    let i16 = ValueType::from(Int::I16);
+    let i32 = ValueType::from(Int::I32);
+
+    // The types held in 32-bit registers.
+    let types32 = [i8, i16, i32, b1];
+
+    // ARM64 specific instructions.
+    let arm64_pop_pair = arm64_instructions.by_name("arm64_pop_pair");
+    let arm64_push_pair = arm64_instructions.by_name("arm64_push_pair");
+
+    // Integer instructions on registers. `mul` is an alias of `madd` with the
+    // zero register as addend.
+    for &(inst, op) in &[
+        (iadd, 0x0b00_0000),
+        (isub, 0x4b00_0000),
+        (band, 0x0a00_0000),
+        (bor, 0x2a00_0000),
+        (bxor, 0x4a00_0000),
+        (band_not, 0x0a20_0000),
+        (bor_not, 0x2a20_0000),
+        (bxor_not, 0x4a20_0000),
+        (imul, 0x1b00_7c00),
+    ] {
+        e.enc(inst.bind(Int::I32), "R", dp_bits(op));
+        e.enc(inst.bind(Int::I64), "R", dp_bits(op | SF));
+    }
+
+    // The high half of a 32-bit multiplication is shifted down from the 64-bit
+    // product of `smull` or `umull`.
+    e.enc(smulhi.bind(Int::I32), "Rmulhi", dp_bits(0x9b20_7c00));
+    e.enc(smulhi.bind(Int::I64), "R", dp_bits(0x9b40_7c00));
+    e.enc(umulhi.bind(Int::I32), "Rmulhi", dp_bits(0x9ba0_7c00));
+    e.enc(umulhi.bind(Int::I64), "R", dp_bits(0x9bc0_7c00));
+
+    // Also add `b1` encodings for the logic instructions.
+    e.enc(band.bind(Bool::B1), "R", dp_bits(0x0a00_0000));
+    e.enc(bor.bind(Bool::B1), "R", dp_bits(0x2a00_0000));
+    e.enc(bxor.bind(Bool::B1), "R", dp_bits(0x4a00_0000));
+
+    // Dynamic shifts use the low bits of the shift amount, like the clif base
+    // instructions.
+    for &(inst, op) in &[
+        (ishl, 0x1ac0_2000),
+        (ushr, 0x1ac0_2400),
+        (sshr, 0x1ac0_2800),
+        (rotr, 0x1ac0_2c00),
+    ] {
+        e.enc(inst.bind(Int::I32).bind(Int::I32), "R", dp_bits(op));
+        e.enc(inst.bind(Int::I32).bind(Int::I64), "R", dp_bits(op));
+        e.enc(inst.bind(Int::I64).bind(Int::I64), "R", dp_bits(op | SF));
+        e.enc(inst.bind(Int::I64).bind(Int::I32), "R", dp_bits(op | SF));
+    }
+
+    // Immediate shifts are aliases of the bitfield moves `ubfm` and `sbfm`.
+    for &(inst, recipe, op) in &[
+        (ishl_imm, "Rlsl", 0x5300_0000),
+        (ushr_imm, "Rshr", 0x5300_0000),
+        (sshr_imm, "Rshr", 0x1300_0000),
+    ] {
+        e.enc(inst.bind(Int::I32), recipe, imm_bits(op));
+        e.enc(inst.bind(Int::I64), recipe, imm_bits(op | SF | N));
+    }
+
+    e.enc(iadd_imm.bind(Int::I32), "Ii", imm_bits(0x1100_0000));
+    e.enc(iadd_imm.bind(Int::I64), "Ii", imm_bits(0x1100_0000 | SF));
+
+    // Integer division traps like the clif base instructions, which the A64
+    // division instructions don't.
+    e.enc(udiv.bind(Int::I32), "Rdiv", dp_bits(0x1ac0_0800));
+    e.enc(udiv.bind(Int::I64), "Rdiv", dp_bits(0x1ac0_0800 | SF));
+    e.enc(sdiv.bind(Int::I32), "Rsdiv", dp_bits(0x1ac0_0c00));
+    e.enc(sdiv.bind(Int::I64), "Rsdiv", dp_bits(0x1ac0_0c00 | SF));
+    e.enc(urem.bind(Int::I32), "Rrem", dp_bits(0x1ac0_0800));
+    e.enc(urem.bind(Int::I64), "Rrem", dp_bits(0x1ac0_0800 | SF));
+    e.enc(srem.bind(Int::I32), "Rrem", dp_bits(0x1ac0_0c00));
+    e.enc(srem.bind(Int::I64), "Rrem", dp_bits(0x1ac0_0c00 | SF));
+
+    e.enc(clz.bind(Int::I32), "Rr", dp_bits(0x5ac0_1000));
+    e.enc(clz.bind(Int::I64), "Rr", dp_bits(0x5ac0_1000 | SF));
+
+    // Comparisons are `subs` to the zero register, followed by a `cset`.
+    e.enc(icmp.bind(Int::I32), "Ricmp", dp_bits(0x6b00_0000));
+    e.enc(icmp.bind(Int::I64), "Ricmp", dp_bits(0x6b00_0000 | SF));
+    e.enc(icmp_imm.bind(Int::I32), "Iicmp", imm_bits(0x7100_0000));
+    e.enc(icmp_imm.bind(Int::I64), "Iicmp", imm_bits(0x7100_0000 | SF));
+
+    // Integer constants.
+    e.enc(iconst.bind(Int::I32), "Iz", movw_bits(0x5280_0000));
+    e.enc(iconst.bind(Int::I64), "Iz", movw_bits(0x5280_0000 | SF));
+    e.enc(iconst.bind(Int::I32), "Iconst32", movw_bits(0x5280_0000));
+    e.enc(
+        iconst.bind(Int::I64),
+        "Iconst64",
+        movw_bits(0x5280_0000 | SF),
+    );
+    e.enc(bconst.bind(Bool::B1), "Izb", movw_bits(0x5280_0000));
+
+    // Extensions. Writing a 32-bit register clears the high bits of the 64-bit
+    // register, so the zero extensions to i64 use the 32-bit `ubfm`.
+    let ubfm = 0x5300_0000;
+    let sbfm = 0x1300_0000;
+    e.enc(
+        uextend.bind(Int::I32).bind(Int::I8),
+        "Rext",
+        ext_bits(ubfm, 7),
+    );
+    e.enc(
+        uextend.bind(Int::I32).bind(Int::I16),
+        "Rext",
+        ext_bits(ubfm, 15),
+    );
+    e.enc(
+        uextend.bind(Int::I64).bind(Int::I8),
+        "Rext",
+        ext_bits(ubfm, 7),
+    );
+    e.enc(
+        uextend.bind(Int::I64).bind(Int::I16),
+        "Rext",
+        ext_bits(ubfm, 15),
+    );
+    e.enc(
+        uextend.bind(Int::I64).bind(Int::I32),
+        "Rext",
+        ext_bits(ubfm, 31),
+    );
+    e.enc(
+        sextend.bind(Int::I32).bind(Int::I8),
+        "Rext",
+        ext_bits(sbfm, 7),
+    );
+    e.enc(
+        sextend.bind(Int::I32).bind(Int::I16),
+        "Rext",
+        ext_bits(sbfm, 15),
+    );
+    e.enc(
+        sextend.bind(Int::I64).bind(Int::I8),
+        "Rext",
+        ext_bits(sbfm | SF | N, 7),
+    );
+    e.enc(
+        sextend.bind(Int::I64).bind(Int::I16),
+        "Rext",
+        ext_bits(sbfm | SF | N, 15),
+    );
+    e.enc(
+        sextend.bind(Int::I64).bind(Int::I32),
+        "Rext",
+        ext_bits(sbfm | SF | N, 31),
+    );
+
+    // Reductions ignore the high bits of the register, and booleans are 0 or 1.
+    e.enc(ireduce.bind(Int::I8).bind(Int::I32), "null", 0);
+    e.enc(ireduce.bind(Int::I16).bind(Int::I32), "null", 0);
+    e.enc(ireduce.bind(Int::I8).bind(Int::I64), "null", 0);
+    e.enc(ireduce.bind(Int::I16).bind(Int::I64), "null", 0);
+    e.enc(ireduce.bind(Int::I32).bind(Int::I64), "null", 0);
+    e.enc(bint.bind(Int::I32).bind(Bool::B1), "null", 0);
+    e.enc(bint.bind(Int::I64).bind(Bool::B1), "null", 0);
+
+    // Register copies.
+    for ty in &types32 {
+        e.enc(copy.bind(ty.clone()), "Rmov", dp_bits(0x2a00_0000));
+        e.enc(regmove.bind(ty.clone()), "Rrmov", dp_bits(0x2a00_0000));
+    }
+    e.enc(copy.bind(Int::I64), "Rmov", dp_bits(0x2a00_0000 | SF));
+    e.enc(regmove.bind(Int::I64), "Rrmov", dp_bits(0x2a00_0000 | SF));
+
+    e.enc(copy.bind(Float::F32), "Rf1", fp1_bits(0x1e20_4000));
+    e.enc(copy.bind(Float::F64), "Rf1", fp1_bits(0x1e20_4000 | DOUBLE));
+    e.enc(regmove.bind(Float::F32), "Rfrmov", fp1_bits(0x1e20_4000));
+    e.enc(
+        regmove.bind(Float::F64),
+        "Rfrmov",
+        fp1_bits(0x1e20_4000 | DOUBLE),
+    );
+
+    // Loads and stores. The templates are the unscaled offset variants.
+    for &recipe in &["LDur", "LDr"] {
+        e.enc(
+            load.bind(Int::I32).bind_any(),
+            recipe,
+            ldst_bits(0xb840_0000),
+        );
+        e.enc(
+            load.bind(Int::I64).bind_any(),
+            recipe,
+            ldst_bits(0xf840_0000),
+        );
+        e.enc(
+            uload8.bind(Int::I32).bind_any(),
+            recipe,
+            ldst_bits(0x3840_0000),
+        );
+        e.enc(
+            uload8.bind(Int::I64).bind_any(),
+            recipe,
+            ldst_bits(0x3840_0000),
+        );
+        e.enc(
+            sload8.bind(Int::I32).bind_any(),
+            recipe,
+            ldst_bits(0x38c0_0000),
+        );
+        e.enc(
+            sload8.bind(Int::I64).bind_any(),
+            recipe,
+            ldst_bits(0x3880_0000),
+        );
+        e.enc(
+            uload16.bind(Int::I32).bind_any(),
+            recipe,
+            ldst_bits(0x7840_0000),
+        );
+        e.enc(
+            uload16.bind(Int::I64).bind_any(),
+            recipe,
+            ldst_bits(0x7840_0000),
+        );
+        e.enc(
+            sload16.bind(Int::I32).bind_any(),
+            recipe,
+            ldst_bits(0x78c0_0000),
+        );
+        e.enc(
+            sload16.bind(Int::I64).bind_any(),
+            recipe,
+            ldst_bits(0x7880_0000),
+        );
+        e.enc(uload32.bind(Int::I64), recipe, ldst_bits(0xb840_0000));
+        e.enc(sload32.bind(Int::I64), recipe, ldst_bits(0xb880_0000));
+    }
+
+    for &recipe in &["STur", "STr"] {
+        e.enc(
+            store.bind(Int::I32).bind_any(),
+            recipe,
+            ldst_bits(0xb800_0000),
+        );
+        e.enc(
+            store.bind(Int::I64).bind_any(),
+            recipe,
+            ldst_bits(0xf800_0000),
+        );
+        e.enc(
+            istore8.bind(Int::I32).bind_any(),
+            recipe,
+            ldst_bits(0x3800_0000),
+        );
+        e.enc(
+            istore8.bind(Int::I64).bind_any(),
+            recipe,
+            ldst_bits(0x3800_0000),
+        );
+        e.enc(
+            istore16.bind(Int::I32).bind_any(),
+            recipe,
+            ldst_bits(0x7800_0000),
+        );
+        e.enc(
+            istore16.bind(Int::I64).bind_any(),
+            recipe,
+            ldst_bits(0x7800_0000),
+        );
+        e.enc(
+            istore32.bind(Int::I64).bind_any(),
+            recipe,
+            ldst_bits(0xb800_0000),
+        );
+    }
+
+    for &recipe in &["fLDur", "fLDr"] {
+        e.enc(
+            load.bind(Float::F32).bind_any(),
+            recipe,
+            ldst_bits(0xbc40_0000),
+        );
+        e.enc(
+            load.bind(Float::F64).bind_any(),
+            recipe,
+            ldst_bits(0xfc40_0000),
+        );
+    }
+
+    for &recipe in &["fSTur", "fSTr"] {
+        e.enc(
+            store.bind(Float::F32).bind_any(),
+            recipe,
+            ldst_bits(0xbc00_0000),
+        );
+        e.enc(
+            store.bind(Float::F64).bind_any(),
+            recipe,
+            ldst_bits(0xfc00_0000),
+        );
+    }
+
+    e.enc(stack_addr.bind(Int::I64), "Iaddr", imm_bits(0x9100_0000));
+
+    // Spill and fill. Spill slots are at least 4 bytes, so the narrow types are
+    // spilled with 32-bit stores.
+    for ty in &types32 {
+        e.enc(spill.bind(ty.clone()), "GPsp", ldst_bits(0xb800_0000));
+        e.enc(fill.bind(ty.clone()), "GPfi", ldst_bits(0xb840_0000));
+        e.enc(regspill.bind(ty.clone()), "GPrsp", ldst_bits(0xb800_0000));
+        e.enc(regfill.bind(ty.clone()), "GPrfi", ldst_bits(0xb840_0000));
+    }
+    e.enc(spill.bind(Int::I64), "GPsp", ldst_bits(0xf800_0000));
+    e.enc(fill.bind(Int::I64), "GPfi", ldst_bits(0xf840_0000));
+    e.enc(regspill.bind(Int::I64), "GPrsp", ldst_bits(0xf800_0000));
+    e.enc(regfill.bind(Int::I64), "GPrfi", ldst_bits(0xf840_0000));
+
+    e.enc(spill.bind(Float::F32), "FPsp", ldst_bits(0xbc00_0000));
+    e.enc(fill.bind(Float::F32), "FPfi", ldst_bits(0xbc40_0000));
+    e.enc(regspill.bind(Float::F32), "FPrsp", ldst_bits(0xbc00_0000));
+    e.enc(regfill.bind(Float::F32), "FPrfi", ldst_bits(0xbc40_0000));
+    e.enc(spill.bind(Float::F64), "FPsp", ldst_bits(0xfc00_0000));
+    e.enc(fill.bind(Float::F64), "FPfi", ldst_bits(0xfc40_0000));
+    e.enc(regspill.bind(Float::F64), "FPrsp", ldst_bits(0xfc00_0000));
+    e.enc(regfill.bind(Float::F64), "FPrfi", ldst_bits(0xfc40_0000));
+
+    // Floating point arithmetic.
+    for &(inst, op) in &[
+        (fadd, 0x1e20_2800),
+        (fsub, 0x1e20_3800),
+        (fmul, 0x1e20_0800),
+        (fdiv, 0x1e20_1800),
+        (fmin, 0x1e20_5800),
+        (fmax, 0x1e20_4800),
+    ] {
+        e.enc(inst.bind(Float::F32), "Rf", dp_bits(op));
+        e.enc(inst.bind(Float::F64), "Rf", dp_bits(op | DOUBLE));
+    }
+
+    for &(inst, op) in &[
+        (fneg, 0x1e21_4000),
+        (fabs, 0x1e20_c000),
+        (sqrt, 0x1e21_c000),
+        (nearest, 0x1e24_4000),
+        (ceil, 0x1e24_c000),
+        (floor, 0x1e25_4000),
+        (trunc, 0x1e25_c000),
+    ] {
+        e.enc(inst.bind(Float::F32), "Rf1", fp1_bits(op));
+        e.enc(inst.bind(Float::F64), "Rf1", fp1_bits(op | DOUBLE));
+    }
+
+    e.enc(
+        fpromote.bind(Float::F64).bind(Float::F32),
+        "Rf1",
+        fp1_bits(0x1e22_c000),
+    );
+    e.enc(
+        fdemote.bind(Float::F32).bind(Float::F64),
+        "Rf1",
+        fp1_bits(0x1e22_4000 | DOUBLE),
+    );
+
+    // Floating point comparisons. The recipes select the supported condition
+    // codes.
+    for &recipe in &["Rfcmp", "Rfcmp2"] {
+        e.enc(fcmp.bind(Float::F32), recipe, dp_bits(0x1e20_2000));
+        e.enc(fcmp.bind(Float::F64), recipe, dp_bits(0x1e20_2000 | DOUBLE));
+    }
+
+    // Conversions between integer and floating point registers. `fmov` moves
+    // the bits unchanged.
+    e.enc(
+        bitcast.bind(Float::F32).bind(Int::I32),
+        "Rcvt",
+        reg_bits(0x1e27_0000),
+    );
+    e.enc(
+        bitcast.bind(Float::F64).bind(Int::I64),
+        "Rcvt",
+        reg_bits(0x1e27_0000 | SF | DOUBLE),
+    );
+    e.enc(
+        bitcast.bind(Int::I32).bind(Float::F32),
+        "Rfcvt",
+        reg_bits(0x1e26_0000),
+    );
+    e.enc(
+        bitcast.bind(Int::I64).bind(Float::F64),
+        "Rfcvt",
+        reg_bits(0x1e26_0000 | SF | DOUBLE),
+    );
+
+    for &(inst, op) in &[(fcvt_from_sint, 0x1e22_0000), (fcvt_from_uint, 0x1e23_0000)] {
+        e.enc(inst.bind(Float::F32).bind(Int::I32), "Rcvt", reg_bits(op));
+        e.enc(
+            inst.bind(Float::F64).bind(Int::I32),
+            "Rcvt",
+            reg_bits(op | DOUBLE),
+        );
+        e.enc(
+            inst.bind(Float::F32).bind(Int::I64),
+            "Rcvt",
+            reg_bits(op | SF),
+        );
+        e.enc(
+            inst.bind(Float::F64).bind(Int::I64),
+            "Rcvt",
+            reg_bits(op | SF | DOUBLE),
+        );
+    }
+
+    // Control flow.
+
+    // Unconditional branches.
+    e.enc(jump, "UJ", b_bits(0x1400_0000));
+    e.enc(call, "UJcall", b_bits(0x9400_0000));
+
+    // Conditional branches.
+    e.enc(br_icmp.bind(Int::I32), "SB", dp_bits(0x6b00_0000));
+    e.enc(br_icmp.bind(Int::I64), "SB", dp_bits(0x6b00_0000 | SF));
+
+    for &(inst, op) in &[(brz, 0x3400_0000), (brnz, 0x3500_0000)] {
+        e.enc(inst.bind(Int::I32), "CBz", cb_bits(op));
+        e.enc(inst.bind(Int::I64), "CBz", cb_bits(op | SF));
+        e.enc(inst.bind(Bool::B1), "CBz", cb_bits(op));
+    }
+
+    // Returns branch to the address in the link register `x30`, which is
+    // provided by a special-purpose `link` return value added by the prologue.
+    e.enc(x_return, "Iret", reg_bits(0xd65f_0000));
+    e.enc(call_indirect.bind(Int::I64), "Icall", reg_bits(0xd63f_0000));
+
+    e.enc(trap, "Iudf", 0);
+
+    // Prologue and epilogue.
+    e.enc(
+        arm64_push_pair.bind(Int::I64),
+        "Pushp",
+        imm_bits(0xa980_0000),
+    );
+    e.enc(arm64_pop_pair.bind(Int::I64), "Popp", imm_bits(0xa8c0_0000));
+    e.enc(
+        arm64_push_pair.bind(Float::F64),
+        "fPushp",
+        imm_bits(0x6d80_0000),
+    );
+    e.enc(
+        arm64_pop_pair.bind(Float::F64),
+        "fPopp",
+        imm_bits(0x6cc0_0000),
+    );
+
+    e.enc(copy_special, "Icopysp", imm_bits(0x9100_0000 | SF));
+
+    for &recipe in &["Iadjsp", "Iadjsp24"] {
+        e.enc(adjust_sp_down_imm, recipe, imm_bits(0x5100_0000 | SF));
+        e.enc(adjust_sp_up_imm, recipe, imm_bits(0x1100_0000 | SF));
+    }
+
+    e
+}
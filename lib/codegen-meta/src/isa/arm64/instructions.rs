@@ -0,0 +1,61 @@
+//! Supplementary instruction definitions for ARM64.
+//!
+//! This module defines additional instructions that are useful only to the
+//! ARM64 target ISA.
+
+#![allow(non_snake_case)]
+
+use cdsl::formats::FormatRegistry;
+use cdsl::instructions::{InstructionBuilder as Inst, InstructionGroup, InstructionGroupBuilder};
+use cdsl::operands::Operand;
+use cdsl::typevar::{TypeSetBuilder, TypeVar};
+
+pub fn define(format_registry: &FormatRegistry) -> InstructionGroup {
+    let mut ig =
+        InstructionGroupBuilder::new("arm64", "arm64-specific instruction set", format_registry);
+
+    let Reg64 = &TypeVar::new(
+        "Reg64",
+        "A 64-bit integer or floating point register",
+        TypeSetBuilder::new().ints(64..=64).floats(64..=64).finish(),
+    );
+
+    let x = &Operand::new("x", Reg64).with_doc("Value stored at the lower address");
+    let y = &Operand::new("y", Reg64).with_doc("Value stored at the higher address");
+
+    ig.push(
+        Inst::new(
+            "arm64_push_pair",
+            r#"
+        Pushes a pair of values onto the stack.
+
+        Decrements the stack pointer by 16 and stores `x` and `y` on to the top,
+        keeping the stack pointer 16-byte aligned as required by AAPCS64.
+
+        This is polymorphic in i64 and f64.
+        "#,
+        )
+        .operands_in(vec![x, y])
+        .can_store(true)
+        .other_side_effects(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "arm64_pop_pair",
+            r#"
+        Pops a pair of values from the stack.
+
+        Loads two values from the top of the stack and then increments the stack
+        pointer by 16. This is the inverse of :inst:`arm64_push_pair`.
+
+        This is polymorphic in i64 and f64.
+        "#,
+        )
+        .operands_out(vec![x, y])
+        .can_load(true)
+        .other_side_effects(true),
+    );
+
+    ig.finish()
+}
@@ -0,0 +1,44 @@
+//! ARM 64-bit Target Architecture.
+
+use base;
+use base::types::{Bool, Float, Int};
+use cdsl::cpu_modes::CpuMode;
+use cdsl::isa::TargetIsa;
+use cdsl::settings::SettingGroup;
+
+mod encodings;
+mod instructions;
+mod recipes;
+mod registers;
+mod settings;
+
+pub fn define(shared_settings: &SettingGroup, shared_defs: &mut base::Definitions) -> TargetIsa {
+    let settings = settings::define(shared_settings);
+    let regs = registers::define();
+
+    let instructions = instructions::define(&shared_defs.format_registry);
+
+    let groups = &shared_defs.transform_groups;
+    let narrow = groups.by_name("narrow");
+    let widen = groups.by_name("widen");
+    let expand = groups.by_name("expand");
+
+    let mut a64 = CpuMode::new("A64");
+
+    a64.legalize_monomorphic(expand);
+    a64.legalize_default(narrow);
+    a64.legalize_type(Bool::B1, expand);
+    a64.legalize_type(Int::I8, widen);
+    a64.legalize_type(Int::I16, widen);
+    a64.legalize_type(Int::I32, expand);
+    a64.legalize_type(Int::I64, expand);
+    a64.legalize_type(Float::F32, expand);
+    a64.legalize_type(Float::F64, expand);
+
+    let recipes = recipes::define(shared_defs, &regs);
+
+    let encodings = encodings::define(shared_defs, &instructions, &recipes);
+    a64.set_encodings(encodings.enc64);
+
+    TargetIsa::new("arm64", settings, regs, instructions, recipes, vec![a64])
+}
@@ -0,0 +1,725 @@
+//! ARM64 Encoding recipes.
+//!
+//! All A64 instructions are 32 bits wide. The encoding recipes defined here
+//! correspond to the instruction classes described in the reference:
+//!
+//! > ARM Architecture Reference Manual
+//! > ARMv8, for ARMv8-A architecture profile
+
+use base;
+use cdsl::formats::InstructionFormat;
+use cdsl::instructions::InstructionPredicate;
+use cdsl::operands::OperandKind;
+use cdsl::recipes::{EncodingRecipeBuilder, OperandConstraint, RecipeGroup, Stack};
+use cdsl::registers::IsaRegs;
+
+// The encbits of a recipe hold the opcode bits of the instructions it encodes.
+// The operand fields are filled in by the `put_*` functions of
+// `isa/arm64/binemit.rs`, so the layout of the encbits depends on the
+// instruction class. The functions below compute the encbits from an
+// instruction template with all its operand fields zero.
+
+/// Data processing on registers: `inst[31:21] | (inst[14:10] << 11)`.
+///
+/// This covers the integer and floating point instructions with up to two
+/// register operands and a destination register. Bit 15 must be zero.
+pub fn dp_bits(inst: u32) -> u16 {
+    assert!(inst & 0x001f_83ff == 0, "{:#x}", inst);
+    ((inst >> 21) | (((inst >> 10) & 0x1f) << 11)) as u16
+}
+
+/// Floating point data processing with one source: `inst[31:21] |
+/// (inst[19:15] << 11)`.
+pub fn fp1_bits(inst: u32) -> u16 {
+    assert!((inst >> 10) & 0x1f == 0b10000, "{:#x}", inst);
+    assert!(inst & 0x0010_03ff == 0, "{:#x}", inst);
+    ((inst >> 21) | (((inst >> 15) & 0x1f) << 11)) as u16
+}
+
+/// Bitfield move with a fixed `imms` field: `inst[31:22] | (imms << 10)`.
+pub fn ext_bits(inst: u32, imms: u32) -> u16 {
+    assert!(inst & 0x003f_ffff == 0, "{:#x}", inst);
+    assert!(imms <= 0b11_1111);
+    ((inst >> 22) | (imms << 10)) as u16
+}
+
+/// The high bits of `inst`, above the operand fields in `inst[low-1:0]`.
+fn hi_bits(inst: u32, low: u32) -> u16 {
+    assert!(inst & ((1 << low) - 1) == 0, "{:#x}", inst);
+    let bits = inst >> low;
+    assert!(bits <= 0xffff);
+    bits as u16
+}
+
+/// Instructions with an immediate or bitfield operand, and pairs of loads and
+/// stores: `inst[31:22]`.
+pub fn imm_bits(inst: u32) -> u16 {
+    hi_bits(inst, 22)
+}
+
+/// Move wide immediate: `inst[31:23]`.
+pub fn movw_bits(inst: u32) -> u16 {
+    hi_bits(inst, 23)
+}
+
+/// Loads and stores, from the template of the unscaled immediate offset
+/// variant: `inst[31:21]`. The register offset and scaled unsigned offset
+/// variants are derived from it.
+pub fn ldst_bits(inst: u32) -> u16 {
+    hi_bits(inst, 21)
+}
+
+/// Conversions between integer and floating point registers and branches to
+/// a register: `inst[31:16]`.
+pub fn reg_bits(inst: u32) -> u16 {
+    hi_bits(inst, 16)
+}
+
+/// Compare and branch: `inst[31:24]`.
+pub fn cb_bits(inst: u32) -> u16 {
+    hi_bits(inst, 24)
+}
+
+/// Unconditional branch to an immediate displacement: `inst[31:26]`.
+pub fn b_bits(inst: u32) -> u16 {
+    hi_bits(inst, 26)
+}
+
+/// The floating point condition codes which can be tested with a single
+/// condition after `fcmp`.
+const SUPPORTED_FLOATCCS: [&str; 12] = [
+    "eq", "ne", "lt", "le", "gt", "ge", "uno", "ord", "ult", "ule", "ugt", "uge",
+];
+
+/// Get an instruction predicate checking that the `cond` field of `format` is
+/// one of the floating point condition codes in `ccs`.
+fn floatccs(
+    format: &InstructionFormat,
+    floatcc: &OperandKind,
+    ccs: &[&str],
+) -> InstructionPredicate {
+    InstructionPredicate::Or(
+        ccs.iter()
+            .map(|cc| InstructionPredicate::is_equal(format, "cond", floatcc.rust_enumerator(cc)))
+            .collect(),
+    )
+}
+
+pub fn define(shared_defs: &base::Definitions, regs: &IsaRegs) -> RecipeGroup {
+    let formats = &shared_defs.format_registry;
+    let floatcc = &shared_defs.imm.floatcc;
+
+    // Format shorthands.
+    let f_binary = formats.by_name("Binary");
+    let f_binary_imm = formats.by_name("BinaryImm");
+    let f_branch = formats.by_name("Branch");
+    let f_branch_icmp = formats.by_name("BranchIcmp");
+    let f_call = formats.by_name("Call");
+    let f_call_indirect = formats.by_name("CallIndirect");
+    let f_copy_special = formats.by_name("CopySpecial");
+    let f_float_compare = formats.by_name("FloatCompare");
+    let f_int_compare = formats.by_name("IntCompare");
+    let f_int_compare_imm = formats.by_name("IntCompareImm");
+    let f_jump = formats.by_name("Jump");
+    let f_load = formats.by_name("Load");
+    let f_multiary = formats.by_name("MultiAry");
+    let f_nullary = formats.by_name("NullAry");
+    let f_regfill = formats.by_name("RegFill");
+    let f_regmove = formats.by_name("RegMove");
+    let f_regspill = formats.by_name("RegSpill");
+    let f_stack_load = formats.by_name("StackLoad");
+    let f_store = formats.by_name("Store");
+    let f_trap = formats.by_name("Trap");
+    let f_unary = formats.by_name("Unary");
+    let f_unary_bool = formats.by_name("UnaryBool");
+    let f_unary_imm = formats.by_name("UnaryImm");
+
+    // Register classes shorthands.
+    let gpr_class = regs.class_by_name("GPR");
+    let gpr = OperandConstraint::from(gpr_class);
+    let fpr_class = regs.class_by_name("FPR");
+    let fpr = OperandConstraint::from(fpr_class);
+
+    let mut recipes = RecipeGroup::new();
+
+    // Integer instructions on registers.
+    recipes.push(
+        EncodingRecipeBuilder::new("R", f_binary, 4)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit("put_r(bits, in_reg0, in_reg1, out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Integer instructions with a single register operand.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rr", f_unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit("put_r(bits, in_reg0, 0, out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Copy of a GPR is implemented as `orr rd, zr, rm`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rmov", f_unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit("put_r(bits, 31, in_reg0, out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Same for a GPR regmove.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rrmov", f_regmove, 4)
+            .operands_in(vec![gpr])
+            .emit("put_r(bits, 31, src, dst, sink);")
+            .build(formats),
+    );
+
+    // A null unary instruction that takes a GPR register. Can be used for
+    // no-op conversions.
+    recipes.push(
+        EncodingRecipeBuilder::new("null", f_unary, 0)
+            .operands_in(vec![gpr])
+            .operands_out(vec![OperandConstraint::TiedInput(0)])
+            .emit("")
+            .build(formats),
+    );
+
+    // Unsigned division, trapping on a zero divisor.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rdiv", f_binary, 12)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    put_trap_zero(bits, in_reg1, func.srclocs[inst], sink);
+                    put_r(bits, in_reg0, in_reg1, out_reg0, sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    // Signed division, trapping on a zero divisor and on overflow.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rsdiv", f_binary, 28)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    put_trap_zero(bits, in_reg1, func.srclocs[inst], sink);
+                    put_trap_sdiv_overflow(
+                        bits, in_reg0, in_reg1, func.srclocs[inst], sink);
+                    put_r(bits, in_reg0, in_reg1, out_reg0, sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    // Remainder of a division, trapping on a zero divisor. The quotient is
+    // computed in the scratch register `x16` and multiplied back with `msub`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rrem", f_binary, 16)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    put_trap_zero(bits, in_reg1, func.srclocs[inst], sink);
+                    put_r(bits, in_reg0, in_reg1, 16, sink);
+                    put_msub(bits, 16, in_reg1, in_reg0, out_reg0, sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    // High half of a 32-bit multiplication, computed as a 64-bit product with
+    // `smull` or `umull` and shifted down.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rmulhi", f_binary, 8)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit("put_mulhi32(bits, in_reg0, in_reg1, out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Add and subtract with a 12-bit unsigned immediate.
+    recipes.push(
+        EncodingRecipeBuilder::new("Ii", f_binary_imm, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::is_unsigned_int(
+                formats.get(f_binary_imm),
+                "imm",
+                12,
+                0,
+            ))
+            .emit("put_i(bits, in_reg0, imm.into(), out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Left shift by an immediate amount, as an alias of `ubfm`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rlsl", f_binary_imm, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit("put_lsl(bits, in_reg0, imm.into(), out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Right shifts by an immediate amount, as aliases of `ubfm` and `sbfm`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rshr", f_binary_imm, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit("put_shr(bits, in_reg0, imm.into(), out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Sign and zero extensions, as aliases of `sbfm` and `ubfm`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rext", f_unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit("put_ext(bits, in_reg0, out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Move wide with zero or with not, for constants with 16 significant bits.
+    recipes.push(
+        EncodingRecipeBuilder::new("Iz", f_unary_imm, 4)
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_unary_imm),
+                "imm",
+                17,
+                0,
+            ))
+            .emit("put_mov16(bits, imm.into(), out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Any 32-bit constant, with a `movz` and a `movk`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Iconst32", f_unary_imm, 8)
+            .operands_out(vec![gpr])
+            .emit("put_const(bits, imm.into(), 2, out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Any 64-bit constant, with a `movz` and three `movk`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Iconst64", f_unary_imm, 16)
+            .operands_out(vec![gpr])
+            .emit("put_const(bits, imm.into(), 4, out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Boolean constants are materialized with `movz`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Izb", f_unary_bool, 4)
+            .operands_out(vec![gpr])
+            .emit("put_movw(bits, 0, if imm { 1 } else { 0 }, out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Integer comparison: `cmp` followed by `cset`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Ricmp", f_int_compare, 8)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    put_r(bits, in_reg0, in_reg1, 31, sink);
+                    put_cset(icc2cond(cond), out_reg0, sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    // Integer comparison with a 12-bit unsigned immediate.
+    recipes.push(
+        EncodingRecipeBuilder::new("Iicmp", f_int_compare_imm, 8)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::is_unsigned_int(
+                formats.get(f_int_compare_imm),
+                "imm",
+                12,
+                0,
+            ))
+            .emit(
+                r#"
+                    put_i(bits, in_reg0, imm.into(), 31, sink);
+                    put_cset(icc2cond(cond), out_reg0, sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    // Floating point comparison: `fcmp` followed by `cset`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rfcmp", f_float_compare, 8)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![gpr])
+            .inst_predicate(floatccs(
+                formats.get(f_float_compare),
+                floatcc,
+                &SUPPORTED_FLOATCCS,
+            ))
+            .emit(
+                r#"
+                    put_r(bits, in_reg0, in_reg1, 0, sink);
+                    put_cset(fcc2cond(cond), out_reg0, sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    // Floating point comparisons which need two condition codes: `cset` for
+    // the first one, then `csinc` sets the result if the second one holds.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rfcmp2", f_float_compare, 12)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![gpr])
+            .inst_predicate(floatccs(
+                formats.get(f_float_compare),
+                floatcc,
+                &["ueq", "one"],
+            ))
+            .emit(
+                r#"
+                    put_r(bits, in_reg0, in_reg1, 0, sink);
+                    use ir::condcodes::FloatCC::*;
+                    let (first, second) = match cond {
+                        UnorderedOrEqual => (COND_EQ, COND_VS),
+                        OrderedNotEqual => (COND_MI, COND_GT),
+                        _ => panic!("{} not supported by Rfcmp2", cond),
+                    };
+                    put_cset(first, out_reg0, sink);
+                    put_csinc(second ^ 1, out_reg0, 31, out_reg0, sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    // Floating point instructions on registers.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rf", f_binary, 4)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![fpr])
+            .emit("put_r(bits, in_reg0, in_reg1, out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Floating point instructions with a single register operand, including
+    // copies.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rf1", f_unary, 4)
+            .operands_in(vec![fpr])
+            .operands_out(vec![fpr])
+            .emit("put_rf1(bits, in_reg0, out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Same for an FPR regmove.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rfrmov", f_regmove, 4)
+            .operands_in(vec![fpr])
+            .emit("put_rf1(bits, src, dst, sink);")
+            .build(formats),
+    );
+
+    // Conversion from a GPR to an FPR.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rcvt", f_unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![fpr])
+            .emit("put_reg(bits, in_reg0, out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Conversion from an FPR to a GPR.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rfcvt", f_unary, 4)
+            .operands_in(vec![fpr])
+            .operands_out(vec![gpr])
+            .emit("put_reg(bits, in_reg0, out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Loads with a signed 9-bit unscaled offset.
+    for &(name, out) in &[("LDur", gpr), ("fLDur", fpr)] {
+        recipes.push(
+            EncodingRecipeBuilder::new(name, f_load, 4)
+                .operands_in(vec![gpr])
+                .operands_out(vec![out])
+                .inst_predicate(InstructionPredicate::is_signed_int(
+                    formats.get(f_load),
+                    "offset",
+                    9,
+                    0,
+                ))
+                .emit("put_ldst(bits, out_reg0, in_reg0, offset.into(), sink);")
+                .build(formats),
+        );
+    }
+
+    // Loads with any offset, materialized in the scratch register `x16`.
+    for &(name, out) in &[("LDr", gpr), ("fLDr", fpr)] {
+        recipes.push(
+            EncodingRecipeBuilder::new(name, f_load, 12)
+                .operands_in(vec![gpr])
+                .operands_out(vec![out])
+                .emit("put_ldst_offset(bits, out_reg0, in_reg0, offset.into(), sink);")
+                .build(formats),
+        );
+    }
+
+    // Stores with a signed 9-bit unscaled offset.
+    for &(name, value) in &[("STur", gpr), ("fSTur", fpr)] {
+        recipes.push(
+            EncodingRecipeBuilder::new(name, f_store, 4)
+                .operands_in(vec![value, gpr])
+                .inst_predicate(InstructionPredicate::is_signed_int(
+                    formats.get(f_store),
+                    "offset",
+                    9,
+                    0,
+                ))
+                .emit("put_ldst(bits, in_reg0, in_reg1, offset.into(), sink);")
+                .build(formats),
+        );
+    }
+
+    // Stores with any offset, materialized in the scratch register `x16`.
+    for &(name, value) in &[("STr", gpr), ("fSTr", fpr)] {
+        recipes.push(
+            EncodingRecipeBuilder::new(name, f_store, 12)
+                .operands_in(vec![value, gpr])
+                .emit("put_ldst_offset(bits, in_reg0, in_reg1, offset.into(), sink);")
+                .build(formats),
+        );
+    }
+
+    // Address of a stack slot, computed from the stack pointer.
+    recipes.push(
+        EncodingRecipeBuilder::new("Iaddr", f_stack_load, 8)
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    let sp = StackRef::sp(stack_slot, &func.stack_slots);
+                    let offset: i32 = offset.into();
+                    let offset = i64::from(sp.offset + offset);
+                    put_i(bits | 1, 31, offset >> 12, out_reg0, sink);
+                    put_i(bits, out_reg0, offset & 0xfff, out_reg0, sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    // Spill and fill of a GPR and of an FPR, relative to the stack pointer.
+    for &(spill, fill, regclass) in &[("GPsp", "GPfi", gpr_class), ("FPsp", "FPfi", fpr_class)] {
+        recipes.push(
+            EncodingRecipeBuilder::new(spill, f_unary, 4)
+                .operands_in(vec![OperandConstraint::from(regclass)])
+                .operands_out(vec![Stack::new(regclass).into()])
+                .emit(
+                    r#"
+                        sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                        put_ldst_sp(bits, in_reg0, out_stk0.offset, sink);
+                    "#,
+                )
+                .build(formats),
+        );
+
+        recipes.push(
+            EncodingRecipeBuilder::new(fill, f_unary, 4)
+                .operands_in(vec![Stack::new(regclass).into()])
+                .operands_out(vec![OperandConstraint::from(regclass)])
+                .emit("put_ldst_sp(bits, out_reg0, in_stk0.offset, sink);")
+                .build(formats),
+        );
+    }
+
+    // Regspill and regfill of a GPR and of an FPR.
+    for &(spill, fill, regclass) in &[("GPrsp", "GPrfi", gpr_class), ("FPrsp", "FPrfi", fpr_class)]
+    {
+        recipes.push(
+            EncodingRecipeBuilder::new(spill, f_regspill, 4)
+                .operands_in(vec![OperandConstraint::from(regclass)])
+                .emit(
+                    r#"
+                        sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                        let dst = StackRef::sp(dst, &func.stack_slots);
+                        put_ldst_sp(bits, src, dst.offset, sink);
+                    "#,
+                )
+                .build(formats),
+        );
+
+        recipes.push(
+            EncodingRecipeBuilder::new(fill, f_regfill, 4)
+                .operands_in(vec![Stack::new(regclass).into()])
+                .emit(
+                    r#"
+                        let src = StackRef::sp(src, &func.stack_slots);
+                        put_ldst_sp(bits, dst, src.offset, sink);
+                    "#,
+                )
+                .build(formats),
+        );
+    }
+
+    // Push and pop a pair of registers with `stp` and `ldp`, pre-decrementing
+    // and post-incrementing the stack pointer.
+    for &(push, pop, reg) in &[("Pushp", "Popp", gpr), ("fPushp", "fPopp", fpr)] {
+        recipes.push(
+            EncodingRecipeBuilder::new(push, f_binary, 4)
+                .operands_in(vec![reg, reg])
+                .emit(
+                    r#"
+                        sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                        put_pair(bits, -16, in_reg0, in_reg1, sink);
+                    "#,
+                )
+                .build(formats),
+        );
+
+        recipes.push(
+            EncodingRecipeBuilder::new(pop, f_nullary, 4)
+                .operands_out(vec![reg, reg])
+                .emit("put_pair(bits, 16, out_reg0, out_reg1, sink);")
+                .build(formats),
+        );
+    }
+
+    // Copy of the stack pointer, implemented as `add rd, rn, #0`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Icopysp", f_copy_special, 4)
+            .emit("put_i(bits, src, 0, dst, sink);")
+            .build(formats),
+    );
+
+    // Adjustments of the stack pointer by a 12-bit immediate.
+    recipes.push(
+        EncodingRecipeBuilder::new("Iadjsp", f_unary_imm, 4)
+            .inst_predicate(InstructionPredicate::is_unsigned_int(
+                formats.get(f_unary_imm),
+                "imm",
+                12,
+                0,
+            ))
+            .emit("put_i(bits, 31, imm.into(), 31, sink);")
+            .build(formats),
+    );
+
+    // Adjustments of the stack pointer by a 24-bit immediate, in two steps.
+    recipes.push(
+        EncodingRecipeBuilder::new("Iadjsp24", f_unary_imm, 8)
+            .inst_predicate(InstructionPredicate::is_unsigned_int(
+                formats.get(f_unary_imm),
+                "imm",
+                24,
+                0,
+            ))
+            .emit(
+                r#"
+                    let imm: i64 = imm.into();
+                    put_i(bits | 1, 31, imm >> 12, 31, sink);
+                    put_i(bits, 31, imm & 0xfff, 31, sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    // Return is a `ret` to the link register. The return address is provided
+    // by the special-purpose `link` argument added by the prologue.
+    // The variable return values are not encoded.
+    recipes.push(
+        EncodingRecipeBuilder::new("Iret", f_multiary, 4)
+            .emit("put_br(bits, 30, sink);")
+            .build(formats),
+    );
+
+    // Indirect call with `blr`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Icall", f_call_indirect, 4)
+            .operands_in(vec![gpr])
+            .emit("put_br(bits, in_reg0, sink);")
+            .build(formats),
+    );
+
+    // Unconditional branch.
+    recipes.push(
+        EncodingRecipeBuilder::new("UJ", f_jump, 4)
+            .branch_range((0, 28))
+            .emit(
+                r#"
+                    let dest = i64::from(func.offsets[destination]);
+                    let disp = dest - i64::from(sink.offset());
+                    put_b(bits, disp, sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    // Direct call with `bl`, relocated.
+    recipes.push(
+        EncodingRecipeBuilder::new("UJcall", f_call, 4)
+            .emit(
+                r#"
+                    sink.reloc_external(Reloc::Arm64Call,
+                                        &func.dfg.ext_funcs[func_ref].name,
+                                        0);
+                    put_b(bits, 0, sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    // Compare with zero and branch.
+    recipes.push(
+        EncodingRecipeBuilder::new("CBz", f_branch, 4)
+            .operands_in(vec![gpr])
+            .branch_range((0, 21))
+            .emit(
+                r#"
+                    let dest = i64::from(func.offsets[destination]);
+                    let disp = dest - i64::from(sink.offset());
+                    put_cb(bits, disp, in_reg0, sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    // Integer comparison and conditional branch: `cmp` followed by `b.cond`.
+    recipes.push(
+        EncodingRecipeBuilder::new("SB", f_branch_icmp, 8)
+            .operands_in(vec![gpr, gpr])
+            .branch_range((4, 21))
+            .emit(
+                r#"
+                    put_r(bits, in_reg0, in_reg1, 31, sink);
+                    let dest = i64::from(func.offsets[destination]);
+                    let disp = dest - i64::from(sink.offset());
+                    put_bcond(icc2cond(cond), disp, sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    // Traps are the permanently undefined instruction `udf`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Iudf", f_trap, 4)
+            .emit(
+                r#"
+                    sink.trap(code, func.srclocs[inst]);
+                    put_udf(sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    recipes
+}
@@ -0,0 +1,32 @@
+//! Aarch64 register banks.
+
+use cdsl::registers::{IsaRegs, IsaRegsBuilder, RegBankBuilder, RegClassBuilder};
+
+pub fn define() -> IsaRegs {
+    let mut regs = IsaRegsBuilder::new();
+
+    // The `x31` regunit serves as the stack pointer / zero register depending
+    // on context. We reserve it and don't model the difference.
+    let int_regs = regs.add_bank(
+        RegBankBuilder::new("IntRegs", "General purpose registers")
+            .units(32)
+            .prefix("x"),
+    );
+    let float_regs = regs.add_bank(
+        RegBankBuilder::new("FloatRegs", "Floating point registers")
+            .units(32)
+            .prefix("v"),
+    );
+    let flag_regs = regs.add_bank(
+        RegBankBuilder::new("FlagRegs", "Flag registers")
+            .units(1)
+            .names(vec!["nzcv"])
+            .track_pressure(false),
+    );
+
+    regs.add_class(RegClassBuilder::new_toplevel("GPR", int_regs));
+    regs.add_class(RegClassBuilder::new_toplevel("FPR", float_regs));
+    regs.add_class(RegClassBuilder::new_toplevel("FLAG", flag_regs));
+
+    regs.finish()
+}
@@ -0,0 +1,8 @@
+//! ARM64 settings.
+
+use cdsl::settings::{SettingGroup, SettingGroupBuilder};
+
+pub fn define(_shared: &SettingGroup) -> SettingGroup {
+    let settings = SettingGroupBuilder::new("arm64");
+    settings.finish()
+}
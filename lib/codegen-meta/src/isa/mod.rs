@@ -0,0 +1,27 @@
+//! Cranelift target ISA definitions.
+//!
+//! The `isa` module contains sub-modules for each target instruction set
+//! architecture supported by Cranelift.
+
+use base;
+use cdsl::isa::TargetIsa;
+use cdsl::settings::SettingGroup;
+
+mod arm32;
+mod arm64;
+mod riscv;
+mod x86;
+
+/// Define all of the supported target ISAs, in the same order as the Python
+/// `isa.all_isas()` function.
+pub fn define_all(
+    shared_settings: &SettingGroup,
+    shared_defs: &mut base::Definitions,
+) -> Vec<TargetIsa> {
+    vec![
+        riscv::define(shared_settings, shared_defs),
+        x86::define(shared_settings, shared_defs),
+        arm32::define(shared_settings, shared_defs),
+        arm64::define(shared_settings, shared_defs),
+    ]
+}
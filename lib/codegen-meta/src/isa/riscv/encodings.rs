@@ -0,0 +1,893 @@
+//! RISC-V Encodings.
+
+use base;
+use cdsl::ast::{var, Literal};
+use cdsl::encodings::{Encoding, EncodingBuilder};
+use cdsl::instructions::Bindable;
+use cdsl::recipes::RecipeGroup;
+use cdsl::settings::SettingGroup;
+use cdsl::types::ValueType;
+
+use base::types::{Bool, Float, Int};
+
+use super::recipes::{
+    branch_bits, jal_bits, jalr_bits, load_bits, load_fp_bits, lui_bits, madd_bits, op32_bits,
+    op_bits, op_fp_bits, opimm32_bits, opimm_bits, store_bits, store_fp_bits,
+};
+
+/// The encodings of the RV32 and RV64 CPU modes.
+pub struct PerCpuModeEncodings<'defs> {
+    pub enc32: Vec<Encoding>,
+    pub enc64: Vec<Encoding>,
+    recipes: &'defs RecipeGroup,
+    shared_defs: &'defs base::Definitions,
+}
+
+impl<'defs> PerCpuModeEncodings<'defs> {
+    fn add32(&mut self, encoding: EncodingBuilder) {
+        let encoding = encoding.build(self.recipes, &self.shared_defs.format_registry);
+        self.enc32.push(encoding);
+    }
+
+    fn add64(&mut self, encoding: EncodingBuilder) {
+        let encoding = encoding.build(self.recipes, &self.shared_defs.format_registry);
+        self.enc64.push(encoding);
+    }
+}
+
+pub fn define<'defs>(
+    shared_defs: &'defs base::Definitions,
+    isa_settings: &SettingGroup,
+    recipes: &'defs RecipeGroup,
+) -> PerCpuModeEncodings<'defs> {
+    let insts = &shared_defs.instructions;
+    let imm = &shared_defs.imm;
+
+    let mut e = PerCpuModeEncodings {
+        enc32: Vec::new(),
+        enc64: Vec::new(),
+        recipes,
+        shared_defs,
+    };
+
+    // Instructions shorthands.
+    let band = insts.by_name("band");
+    let band_imm = insts.by_name("band_imm");
+    let bitcast = insts.by_name("bitcast");
+    let bnot = insts.by_name("bnot");
+    let bor = insts.by_name("bor");
+    let bor_imm = insts.by_name("bor_imm");
+    let br_icmp = insts.by_name("br_icmp");
+    let brnz = insts.by_name("brnz");
+    let brz = insts.by_name("brz");
+    let bxor = insts.by_name("bxor");
+    let bxor_imm = insts.by_name("bxor_imm");
+    let call = insts.by_name("call");
+    let call_indirect = insts.by_name("call_indirect");
+    let copy = insts.by_name("copy");
+    let fabs = insts.by_name("fabs");
+    let fadd = insts.by_name("fadd");
+    let fcmp = insts.by_name("fcmp");
+    let fcopysign = insts.by_name("fcopysign");
+    let fcvt_from_sint = insts.by_name("fcvt_from_sint");
+    let fcvt_from_uint = insts.by_name("fcvt_from_uint");
+    let fcvt_to_sint = insts.by_name("fcvt_to_sint");
+    let fcvt_to_uint = insts.by_name("fcvt_to_uint");
+    let fdemote = insts.by_name("fdemote");
+    let fdiv = insts.by_name("fdiv");
+    let fill = insts.by_name("fill");
+    let fma = insts.by_name("fma");
+    let fmul = insts.by_name("fmul");
+    let fneg = insts.by_name("fneg");
+    let fpromote = insts.by_name("fpromote");
+    let fsub = insts.by_name("fsub");
+    let iadd = insts.by_name("iadd");
+    let iadd_imm = insts.by_name("iadd_imm");
+    let iconst = insts.by_name("iconst");
+    let icmp = insts.by_name("icmp");
+    let icmp_imm = insts.by_name("icmp_imm");
+    let imul = insts.by_name("imul");
+    let ishl = insts.by_name("ishl");
+    let ishl_imm = insts.by_name("ishl_imm");
+    let isub = insts.by_name("isub");
+    let jump = insts.by_name("jump");
+    let load = insts.by_name("load");
+    let regmove = insts.by_name("regmove");
+    let spill = insts.by_name("spill");
+    let sqrt = insts.by_name("sqrt");
+    let sshr = insts.by_name("sshr");
+    let sshr_imm = insts.by_name("sshr_imm");
+    let store = insts.by_name("store");
+    let ushr = insts.by_name("ushr");
+    let ushr_imm = insts.by_name("ushr_imm");
+    let x_return = insts.by_name("return");
+
+    // Recipes shorthands.
+    let r_r = recipes.by_name("R");
+    let r_ii = recipes.by_name("Ii");
+    let r_ibnot = recipes.by_name("Ibnot");
+    let r_iz = recipes.by_name("Iz");
+    let r_rshamt = recipes.by_name("Rshamt");
+    let r_ricmp = recipes.by_name("Ricmp");
+    let r_iicmp = recipes.by_name("Iicmp");
+    let r_u = recipes.by_name("U");
+    let r_uj = recipes.by_name("UJ");
+    let r_uj_call = recipes.by_name("UJcall");
+    let r_sb = recipes.by_name("SB");
+    let r_sb_zero = recipes.by_name("SBzero");
+    let r_iret = recipes.by_name("Iret");
+    let r_icall = recipes.by_name("Icall");
+    let r_gp_sp = recipes.by_name("GPsp");
+    let r_gp_fi = recipes.by_name("GPfi");
+    let r_icopy = recipes.by_name("Icopy");
+    let r_irmov = recipes.by_name("Irmov");
+    let r_rf = recipes.by_name("Rf");
+    let r_rfunary = recipes.by_name("Rfunary");
+    let r_rfsgn = recipes.by_name("Rfsgn");
+    let r_rfrmov = recipes.by_name("Rfrmov");
+    let r_rfcmp = recipes.by_name("Rfcmp");
+    let r_rfcmpswap = recipes.by_name("Rfcmpswap");
+    let r_rgf = recipes.by_name("Rgf");
+    let r_rfg = recipes.by_name("Rfg");
+    let r_rfgtrap = recipes.by_name("Rfgtrap");
+    let r_r4 = recipes.by_name("R4");
+    let r_ifload = recipes.by_name("Ifload");
+    let r_sfstore = recipes.by_name("Sfstore");
+    let r_fp_sp = recipes.by_name("FPsp");
+    let r_fp_fi = recipes.by_name("FPfi");
+    let r_iload = recipes.by_name("Iload");
+    let r_sstore = recipes.by_name("Sstore");
+    let r_cr_mov = recipes.by_name("CRmov");
+    let r_cr_rmov = recipes.by_name("CRrmov");
+    let r_cr_add = recipes.by_name("CRadd");
+    let r_cr_ret = recipes.by_name("CRret");
+    let r_cr_call = recipes.by_name("CRcall");
+    let r_ci_addi = recipes.by_name("CIaddi");
+    let r_ci_li = recipes.by_name("CIli");
+    let r_ci_shamt = recipes.by_name("CIshamt");
+    let r_ca = recipes.by_name("CA");
+    let r_cb_shamt = recipes.by_name("CBshamt");
+    let r_cb_andi = recipes.by_name("CBandi");
+    let r_cl_w = recipes.by_name("CLw");
+    let r_cs_w = recipes.by_name("CSw");
+    let r_cl_d = recipes.by_name("CLd");
+    let r_cs_d = recipes.by_name("CSd");
+    let r_cj = recipes.by_name("CJ");
+    let r_cb_zero = recipes.by_name("CBzero");
+
+    // Predicates shorthands.
+    let use_m = isa_settings.predicate_by_name("use_m");
+    let use_f = isa_settings.predicate_by_name("use_f");
+    let use_d = isa_settings.predicate_by_name("use_d");
+    let supports_c = isa_settings.predicate_by_name("supports_c");
+
+    // Types shorthands.
+    let b1 = ValueType::from(Bool::B1);
+    let f32 = ValueType::from(Float::F32);
+    let f64 = ValueType::from(Float::F64);
+    let i32 = ValueType::from(Int::I32);
+    let i64 = ValueType::from(Int::I64);
+
+    // Dummies for instruction predicates.
+    let x = var("x");
+    let y = var("y");
+    let dest = var("dest");
+    let args = var("args");
+
+    // Basic arithmetic binary instructions are encoded in an R-type instruction.
+    for &(inst, inst_imm, f3, f7) in &[
+        (iadd, Some(iadd_imm), 0b000, 0b000_0000),
+        (isub, None, 0b000, 0b010_0000),
+        (bxor, Some(bxor_imm), 0b100, 0b000_0000),
+        (bor, Some(bor_imm), 0b110, 0b000_0000),
+        (band, Some(band_imm), 0b111, 0b000_0000),
+    ] {
+        e.add32(EncodingBuilder::new(
+            inst.bind(i32.clone()),
+            r_r,
+            op_bits(f3, f7),
+        ));
+        e.add64(EncodingBuilder::new(
+            inst.bind(i64.clone()),
+            r_r,
+            op_bits(f3, f7),
+        ));
+
+        // Immediate versions for add/xor/or/and.
+        if let Some(inst_imm) = inst_imm {
+            e.add32(EncodingBuilder::new(
+                inst_imm.bind(i32.clone()),
+                r_ii,
+                opimm_bits(f3, 0),
+            ));
+            e.add64(EncodingBuilder::new(
+                inst_imm.bind(i64.clone()),
+                r_ii,
+                opimm_bits(f3, 0),
+            ));
+        }
+    }
+
+    // Also add `b1` encodings for the logic instructions.
+    for &(inst, f3) in &[(bxor, 0b100), (bor, 0b110), (band, 0b111)] {
+        e.add32(EncodingBuilder::new(
+            inst.bind(b1.clone()),
+            r_r,
+            op_bits(f3, 0b000_0000),
+        ));
+        e.add64(EncodingBuilder::new(
+            inst.bind(b1.clone()),
+            r_r,
+            op_bits(f3, 0b000_0000),
+        ));
+    }
+    e.add32(EncodingBuilder::new(
+        bnot.bind(b1.clone()),
+        r_ibnot,
+        opimm_bits(0b100, 0),
+    ));
+    e.add64(EncodingBuilder::new(
+        bnot.bind(b1.clone()),
+        r_ibnot,
+        opimm_bits(0b100, 0),
+    ));
+
+    // 32-bit ops in RV64.
+    e.add64(EncodingBuilder::new(
+        iadd.bind(i32.clone()),
+        r_r,
+        op32_bits(0b000, 0b000_0000),
+    ));
+    e.add64(EncodingBuilder::new(
+        isub.bind(i32.clone()),
+        r_r,
+        op32_bits(0b000, 0b010_0000),
+    ));
+    // There are no andiw/oriw/xoriw variations.
+    e.add64(EncodingBuilder::new(
+        iadd_imm.bind(i32.clone()),
+        r_ii,
+        opimm32_bits(0b000, 0),
+    ));
+
+    // Use iadd_imm with %x0 to materialize constants.
+    e.add32(EncodingBuilder::new(
+        iconst.bind(i32.clone()),
+        r_iz,
+        opimm_bits(0b000, 0),
+    ));
+    e.add64(EncodingBuilder::new(
+        iconst.bind(i32.clone()),
+        r_iz,
+        opimm_bits(0b000, 0),
+    ));
+    e.add64(EncodingBuilder::new(
+        iconst.bind(i64.clone()),
+        r_iz,
+        opimm_bits(0b000, 0),
+    ));
+
+    // Dynamic shifts have the same masking semantics as the clif base instructions.
+    for &(inst, inst_imm, f3, f7) in &[
+        (ishl, ishl_imm, 0b001, 0b000_0000),
+        (ushr, ushr_imm, 0b101, 0b000_0000),
+        (sshr, sshr_imm, 0b101, 0b010_0000),
+    ] {
+        e.add32(EncodingBuilder::new(
+            inst.bind(i32.clone()).bind(i32.clone()),
+            r_r,
+            op_bits(f3, f7),
+        ));
+        e.add64(EncodingBuilder::new(
+            inst.bind(i64.clone()).bind(i64.clone()),
+            r_r,
+            op_bits(f3, f7),
+        ));
+        e.add64(EncodingBuilder::new(
+            inst.bind(i32.clone()).bind(i32.clone()),
+            r_r,
+            op32_bits(f3, f7),
+        ));
+        // Allow i32 shift amounts in 64-bit shifts.
+        e.add64(EncodingBuilder::new(
+            inst.bind(i64.clone()).bind(i32.clone()),
+            r_r,
+            op_bits(f3, f7),
+        ));
+        e.add64(EncodingBuilder::new(
+            inst.bind(i32.clone()).bind(i64.clone()),
+            r_r,
+            op32_bits(f3, f7),
+        ));
+
+        // Immediate shifts.
+        e.add32(EncodingBuilder::new(
+            inst_imm.bind(i32.clone()),
+            r_rshamt,
+            opimm_bits(f3, f7),
+        ));
+        e.add64(EncodingBuilder::new(
+            inst_imm.bind(i64.clone()),
+            r_rshamt,
+            opimm_bits(f3, f7),
+        ));
+        e.add64(EncodingBuilder::new(
+            inst_imm.bind(i32.clone()),
+            r_rshamt,
+            opimm32_bits(f3, f7),
+        ));
+    }
+
+    // Signed and unsigned integer 'less than'. There are no 'w' variants for
+    // comparing 32-bit numbers in RV64.
+    let intcc_slt = Literal::enumerator_for(&imm.intcc, "slt");
+    let intcc_ult = Literal::enumerator_for(&imm.intcc, "ult");
+
+    e.add32(EncodingBuilder::new(
+        apply!(icmp.i32(intcc_slt, x, y)),
+        r_ricmp,
+        op_bits(0b010, 0b000_0000),
+    ));
+    e.add64(EncodingBuilder::new(
+        apply!(icmp.i64(intcc_slt, x, y)),
+        r_ricmp,
+        op_bits(0b010, 0b000_0000),
+    ));
+    e.add32(EncodingBuilder::new(
+        apply!(icmp.i32(intcc_ult, x, y)),
+        r_ricmp,
+        op_bits(0b011, 0b000_0000),
+    ));
+    e.add64(EncodingBuilder::new(
+        apply!(icmp.i64(intcc_ult, x, y)),
+        r_ricmp,
+        op_bits(0b011, 0b000_0000),
+    ));
+
+    e.add32(EncodingBuilder::new(
+        apply!(icmp_imm.i32(intcc_slt, x, y)),
+        r_iicmp,
+        opimm_bits(0b010, 0),
+    ));
+    e.add64(EncodingBuilder::new(
+        apply!(icmp_imm.i64(intcc_slt, x, y)),
+        r_iicmp,
+        opimm_bits(0b010, 0),
+    ));
+    e.add32(EncodingBuilder::new(
+        apply!(icmp_imm.i32(intcc_ult, x, y)),
+        r_iicmp,
+        opimm_bits(0b011, 0),
+    ));
+    e.add64(EncodingBuilder::new(
+        apply!(icmp_imm.i64(intcc_ult, x, y)),
+        r_iicmp,
+        opimm_bits(0b011, 0),
+    ));
+
+    // Integer constants with the low 12 bits clear are materialized by lui.
+    e.add32(EncodingBuilder::new(
+        iconst.bind(i32.clone()),
+        r_u,
+        lui_bits(),
+    ));
+    e.add64(EncodingBuilder::new(
+        iconst.bind(i32.clone()),
+        r_u,
+        lui_bits(),
+    ));
+    e.add64(EncodingBuilder::new(
+        iconst.bind(i64.clone()),
+        r_u,
+        lui_bits(),
+    ));
+
+    // "M" Standard Extension for Integer Multiplication and Division.
+    // Gated by the `use_m` flag.
+    e.add32(
+        EncodingBuilder::new(imul.bind(i32.clone()), r_r, op_bits(0b000, 0b000_0001))
+            .isa_predicate(use_m),
+    );
+    e.add64(
+        EncodingBuilder::new(imul.bind(i64.clone()), r_r, op_bits(0b000, 0b000_0001))
+            .isa_predicate(use_m),
+    );
+    e.add64(
+        EncodingBuilder::new(imul.bind(i32.clone()), r_r, op32_bits(0b000, 0b000_0001))
+            .isa_predicate(use_m),
+    );
+
+    // Integer loads and stores.
+    let load_i32 = load.bind(i32.clone()).bind_any();
+    let load_i64 = load.bind(i64.clone()).bind_any();
+    let store_i32 = store.bind(i32.clone()).bind_any();
+    let store_i64 = store.bind(i64.clone()).bind_any();
+    e.add32(EncodingBuilder::new(
+        load_i32.clone(),
+        r_iload,
+        load_bits(0b010),
+    ));
+    e.add64(EncodingBuilder::new(
+        load_i32.clone(),
+        r_iload,
+        load_bits(0b010),
+    ));
+    e.add64(EncodingBuilder::new(
+        load_i64.clone(),
+        r_iload,
+        load_bits(0b011),
+    ));
+    e.add32(EncodingBuilder::new(
+        store_i32.clone(),
+        r_sstore,
+        store_bits(0b010),
+    ));
+    e.add64(EncodingBuilder::new(
+        store_i32.clone(),
+        r_sstore,
+        store_bits(0b010),
+    ));
+    e.add64(EncodingBuilder::new(
+        store_i64.clone(),
+        r_sstore,
+        store_bits(0b011),
+    ));
+
+    // "F" and "D" Standard Extensions for single and double precision floating point.
+    // Gated by the `use_f` and `use_d` flags. Rounding instructions use the round to nearest,
+    // ties to even mode.
+    for &(inst, f5) in &[
+        (fadd, 0b00000),
+        (fsub, 0b00001),
+        (fmul, 0b00010),
+        (fdiv, 0b00011),
+    ] {
+        let bits_s = op_fp_bits(0b000, f5, 0b00, 0);
+        let bits_d = op_fp_bits(0b000, f5, 0b01, 0);
+        e.add32(EncodingBuilder::new(inst.bind(f32.clone()), r_rf, bits_s).isa_predicate(use_f));
+        e.add64(EncodingBuilder::new(inst.bind(f32.clone()), r_rf, bits_s).isa_predicate(use_f));
+        e.add32(EncodingBuilder::new(inst.bind(f64.clone()), r_rf, bits_d).isa_predicate(use_d));
+        e.add64(EncodingBuilder::new(inst.bind(f64.clone()), r_rf, bits_d).isa_predicate(use_d));
+    }
+
+    let bits_s = op_fp_bits(0b000, 0b01011, 0b00, 0);
+    let bits_d = op_fp_bits(0b000, 0b01011, 0b01, 0);
+    e.add32(EncodingBuilder::new(sqrt.bind(f32.clone()), r_rfunary, bits_s).isa_predicate(use_f));
+    e.add64(EncodingBuilder::new(sqrt.bind(f32.clone()), r_rfunary, bits_s).isa_predicate(use_f));
+    e.add32(EncodingBuilder::new(sqrt.bind(f64.clone()), r_rfunary, bits_d).isa_predicate(use_d));
+    e.add64(EncodingBuilder::new(sqrt.bind(f64.clone()), r_rfunary, bits_d).isa_predicate(use_d));
+
+    let bits_s = madd_bits(0b000, 0b00);
+    let bits_d = madd_bits(0b000, 0b01);
+    e.add32(EncodingBuilder::new(fma.bind(f32.clone()), r_r4, bits_s).isa_predicate(use_f));
+    e.add64(EncodingBuilder::new(fma.bind(f32.clone()), r_r4, bits_s).isa_predicate(use_f));
+    e.add32(EncodingBuilder::new(fma.bind(f64.clone()), r_r4, bits_d).isa_predicate(use_d));
+    e.add64(EncodingBuilder::new(fma.bind(f64.clone()), r_r4, bits_d).isa_predicate(use_d));
+
+    // Sign injection. Injecting the sign of a register into itself implements copies, fneg and
+    // fabs.
+    let bits_s = op_fp_bits(0b000, 0b00100, 0b00, 0);
+    let bits_d = op_fp_bits(0b000, 0b00100, 0b01, 0);
+    e.add32(EncodingBuilder::new(fcopysign.bind(f32.clone()), r_rf, bits_s).isa_predicate(use_f));
+    e.add64(EncodingBuilder::new(fcopysign.bind(f32.clone()), r_rf, bits_s).isa_predicate(use_f));
+    e.add32(EncodingBuilder::new(fcopysign.bind(f64.clone()), r_rf, bits_d).isa_predicate(use_d));
+    e.add64(EncodingBuilder::new(fcopysign.bind(f64.clone()), r_rf, bits_d).isa_predicate(use_d));
+
+    for &(inst, f3) in &[(copy, 0b000), (fneg, 0b001), (fabs, 0b010)] {
+        let bits_s = op_fp_bits(f3, 0b00100, 0b00, 0);
+        let bits_d = op_fp_bits(f3, 0b00100, 0b01, 0);
+        e.add32(EncodingBuilder::new(inst.bind(f32.clone()), r_rfsgn, bits_s).isa_predicate(use_f));
+        e.add64(EncodingBuilder::new(inst.bind(f32.clone()), r_rfsgn, bits_s).isa_predicate(use_f));
+        e.add32(EncodingBuilder::new(inst.bind(f64.clone()), r_rfsgn, bits_d).isa_predicate(use_d));
+        e.add64(EncodingBuilder::new(inst.bind(f64.clone()), r_rfsgn, bits_d).isa_predicate(use_d));
+    }
+
+    let bits_s = op_fp_bits(0b000, 0b00100, 0b00, 0);
+    let bits_d = op_fp_bits(0b000, 0b00100, 0b01, 0);
+    e.add32(EncodingBuilder::new(regmove.bind(f32.clone()), r_rfrmov, bits_s).isa_predicate(use_f));
+    e.add64(EncodingBuilder::new(regmove.bind(f32.clone()), r_rfrmov, bits_s).isa_predicate(use_f));
+    e.add32(EncodingBuilder::new(regmove.bind(f64.clone()), r_rfrmov, bits_d).isa_predicate(use_d));
+    e.add64(EncodingBuilder::new(regmove.bind(f64.clone()), r_rfrmov, bits_d).isa_predicate(use_d));
+
+    // Ordered comparisons. `gt` and `ge` swap the operands of `flt` and `fle`.
+    for &(cond, recipe, f3) in &[
+        ("eq", r_rfcmp, 0b010),
+        ("lt", r_rfcmp, 0b001),
+        ("le", r_rfcmp, 0b000),
+        ("gt", r_rfcmpswap, 0b001),
+        ("ge", r_rfcmpswap, 0b000),
+    ] {
+        let cond = Literal::enumerator_for(&imm.floatcc, cond);
+        let bits_s = op_fp_bits(f3, 0b10100, 0b00, 0);
+        let bits_d = op_fp_bits(f3, 0b10100, 0b01, 0);
+        e.add32(
+            EncodingBuilder::new(apply!(fcmp.f32(cond, x, y)), recipe, bits_s).isa_predicate(use_f),
+        );
+        e.add64(
+            EncodingBuilder::new(apply!(fcmp.f32(cond, x, y)), recipe, bits_s).isa_predicate(use_f),
+        );
+        e.add32(
+            EncodingBuilder::new(apply!(fcmp.f64(cond, x, y)), recipe, bits_d).isa_predicate(use_d),
+        );
+        e.add64(
+            EncodingBuilder::new(apply!(fcmp.f64(cond, x, y)), recipe, bits_d).isa_predicate(use_d),
+        );
+    }
+
+    // Conversions between single and double precision.
+    let promote = fpromote.bind(f64.clone()).bind(f32.clone());
+    let demote = fdemote.bind(f32.clone()).bind(f64.clone());
+    let bits_promote = op_fp_bits(0b000, 0b01000, 0b01, 0);
+    let bits_demote = op_fp_bits(0b000, 0b01000, 0b00, 1);
+    e.add32(EncodingBuilder::new(promote.clone(), r_rfunary, bits_promote).isa_predicate(use_d));
+    e.add64(EncodingBuilder::new(promote, r_rfunary, bits_promote).isa_predicate(use_d));
+    e.add32(EncodingBuilder::new(demote.clone(), r_rfunary, bits_demote).isa_predicate(use_d));
+    e.add64(EncodingBuilder::new(demote, r_rfunary, bits_demote).isa_predicate(use_d));
+
+    // Integer to floating point conversions. The rs2 field selects the signedness and width of
+    // the integer operand.
+    for &(inst, rs2w, rs2l) in &[(fcvt_from_sint, 0, 2), (fcvt_from_uint, 1, 3)] {
+        for &(ty, fmt, isap) in &[(&f32, 0b00, use_f), (&f64, 0b01, use_d)] {
+            let bits_w = op_fp_bits(0b000, 0b11010, fmt, rs2w);
+            let bits_l = op_fp_bits(0b000, 0b11010, fmt, rs2l);
+            let from_i32 = inst.bind(ty.clone()).bind(i32.clone());
+            let from_i64 = inst.bind(ty.clone()).bind(i64.clone());
+            e.add32(EncodingBuilder::new(from_i32.clone(), r_rgf, bits_w).isa_predicate(isap));
+            e.add64(EncodingBuilder::new(from_i32, r_rgf, bits_w).isa_predicate(isap));
+            e.add64(EncodingBuilder::new(from_i64, r_rgf, bits_l).isa_predicate(isap));
+        }
+    }
+
+    // Floating point to integer conversions round toward zero, and trap on NaN and on values out
+    // of range. The rs2 field selects the signedness and width of the integer result.
+    for &(inst, rs2w, rs2l) in &[(fcvt_to_sint, 0, 2), (fcvt_to_uint, 1, 3)] {
+        for &(ty, fmt, isap) in &[(&f32, 0b00, use_f), (&f64, 0b01, use_d)] {
+            let bits_w = op_fp_bits(0b001, 0b11000, fmt, rs2w);
+            let bits_l = op_fp_bits(0b001, 0b11000, fmt, rs2l);
+            let to_i32 = inst.bind(i32.clone()).bind(ty.clone());
+            let to_i64 = inst.bind(i64.clone()).bind(ty.clone());
+            e.add32(EncodingBuilder::new(to_i32.clone(), r_rfgtrap, bits_w).isa_predicate(isap));
+            e.add64(EncodingBuilder::new(to_i32, r_rfgtrap, bits_w).isa_predicate(isap));
+            e.add64(EncodingBuilder::new(to_i64, r_rfgtrap, bits_l).isa_predicate(isap));
+        }
+    }
+
+    // Moves between integer and floating point registers. Only RV64 can move a double precision
+    // value.
+    let bits_s = op_fp_bits(0b000, 0b11110, 0b00, 0);
+    let bits_d = op_fp_bits(0b000, 0b11110, 0b01, 0);
+    let f32_from_i32 = bitcast.bind(f32.clone()).bind(i32.clone());
+    let f64_from_i64 = bitcast.bind(f64.clone()).bind(i64.clone());
+    e.add32(EncodingBuilder::new(f32_from_i32.clone(), r_rgf, bits_s).isa_predicate(use_f));
+    e.add64(EncodingBuilder::new(f32_from_i32, r_rgf, bits_s).isa_predicate(use_f));
+    e.add64(EncodingBuilder::new(f64_from_i64, r_rgf, bits_d).isa_predicate(use_d));
+
+    let bits_s = op_fp_bits(0b000, 0b11100, 0b00, 0);
+    let bits_d = op_fp_bits(0b000, 0b11100, 0b01, 0);
+    let i32_from_f32 = bitcast.bind(i32.clone()).bind(f32.clone());
+    let i64_from_f64 = bitcast.bind(i64.clone()).bind(f64.clone());
+    e.add32(EncodingBuilder::new(i32_from_f32.clone(), r_rfg, bits_s).isa_predicate(use_f));
+    e.add64(EncodingBuilder::new(i32_from_f32, r_rfg, bits_s).isa_predicate(use_f));
+    e.add64(EncodingBuilder::new(i64_from_f64, r_rfg, bits_d).isa_predicate(use_d));
+
+    // Floating point loads and stores.
+    for &(ty, f3, isap) in &[(&f32, 0b010, use_f), (&f64, 0b011, use_d)] {
+        let load_fp = load.bind(ty.clone()).bind_any();
+        let store_fp = store.bind(ty.clone()).bind_any();
+        let bits = load_fp_bits(f3);
+        e.add32(EncodingBuilder::new(load_fp.clone(), r_ifload, bits).isa_predicate(isap));
+        e.add64(EncodingBuilder::new(load_fp, r_ifload, bits).isa_predicate(isap));
+        let bits = store_fp_bits(f3);
+        e.add32(EncodingBuilder::new(store_fp.clone(), r_sfstore, bits).isa_predicate(isap));
+        e.add64(EncodingBuilder::new(store_fp, r_sfstore, bits).isa_predicate(isap));
+    }
+
+    // Control flow.
+
+    // Unconditional branches.
+    e.add32(EncodingBuilder::new(jump, r_uj, jal_bits()));
+    e.add64(EncodingBuilder::new(jump, r_uj, jal_bits()));
+    e.add32(EncodingBuilder::new(call, r_uj_call, jal_bits()));
+    e.add64(EncodingBuilder::new(call, r_uj_call, jal_bits()));
+
+    // Conditional branches.
+    for &(cond, f3) in &[
+        ("eq", 0b000),
+        ("ne", 0b001),
+        ("slt", 0b100),
+        ("sge", 0b101),
+        ("ult", 0b110),
+        ("uge", 0b111),
+    ] {
+        let cond = Literal::enumerator_for(&imm.intcc, cond);
+        e.add32(EncodingBuilder::new(
+            apply!(br_icmp.i32(cond, x, y, dest, args)),
+            r_sb,
+            branch_bits(f3),
+        ));
+        e.add64(EncodingBuilder::new(
+            apply!(br_icmp.i64(cond, x, y, dest, args)),
+            r_sb,
+            branch_bits(f3),
+        ));
+    }
+
+    for &(inst, f3) in &[(brz, 0b000), (brnz, 0b001)] {
+        e.add32(EncodingBuilder::new(
+            inst.bind(i32.clone()),
+            r_sb_zero,
+            branch_bits(f3),
+        ));
+        e.add64(EncodingBuilder::new(
+            inst.bind(i64.clone()),
+            r_sb_zero,
+            branch_bits(f3),
+        ));
+        e.add32(EncodingBuilder::new(
+            inst.bind(b1.clone()),
+            r_sb_zero,
+            branch_bits(f3),
+        ));
+        e.add64(EncodingBuilder::new(
+            inst.bind(b1.clone()),
+            r_sb_zero,
+            branch_bits(f3),
+        ));
+    }
+
+    // Returns are a special case of JALR using %x1 to hold the return address.
+    // The return address is provided by a special-purpose `link` return value that
+    // is added by legalize_signature().
+    e.add32(EncodingBuilder::new(x_return, r_iret, jalr_bits()));
+    e.add64(EncodingBuilder::new(x_return, r_iret, jalr_bits()));
+    e.add32(EncodingBuilder::new(
+        call_indirect.bind(i32.clone()),
+        r_icall,
+        jalr_bits(),
+    ));
+    e.add64(EncodingBuilder::new(
+        call_indirect.bind(i64.clone()),
+        r_icall,
+        jalr_bits(),
+    ));
+
+    // Spill and fill.
+    e.add32(EncodingBuilder::new(
+        spill.bind(i32.clone()),
+        r_gp_sp,
+        store_bits(0b010),
+    ));
+    e.add64(EncodingBuilder::new(
+        spill.bind(i32.clone()),
+        r_gp_sp,
+        store_bits(0b010),
+    ));
+    e.add64(EncodingBuilder::new(
+        spill.bind(i64.clone()),
+        r_gp_sp,
+        store_bits(0b011),
+    ));
+    e.add32(EncodingBuilder::new(
+        fill.bind(i32.clone()),
+        r_gp_fi,
+        load_bits(0b010),
+    ));
+    e.add64(EncodingBuilder::new(
+        fill.bind(i32.clone()),
+        r_gp_fi,
+        load_bits(0b010),
+    ));
+    e.add64(EncodingBuilder::new(
+        fill.bind(i64.clone()),
+        r_gp_fi,
+        load_bits(0b011),
+    ));
+    for &(ty, f3, isap) in &[(&f32, 0b010, use_f), (&f64, 0b011, use_d)] {
+        let bits = store_fp_bits(f3);
+        e.add32(EncodingBuilder::new(spill.bind(ty.clone()), r_fp_sp, bits).isa_predicate(isap));
+        e.add64(EncodingBuilder::new(spill.bind(ty.clone()), r_fp_sp, bits).isa_predicate(isap));
+    }
+    for &(ty, f3, isap) in &[(&f32, 0b010, use_f), (&f64, 0b011, use_d)] {
+        let bits = load_fp_bits(f3);
+        e.add32(EncodingBuilder::new(fill.bind(ty.clone()), r_fp_fi, bits).isa_predicate(isap));
+        e.add64(EncodingBuilder::new(fill.bind(ty.clone()), r_fp_fi, bits).isa_predicate(isap));
+    }
+
+    // Register copies.
+    e.add32(EncodingBuilder::new(
+        copy.bind(i32.clone()),
+        r_icopy,
+        opimm_bits(0b000, 0),
+    ));
+    e.add64(EncodingBuilder::new(
+        copy.bind(i64.clone()),
+        r_icopy,
+        opimm_bits(0b000, 0),
+    ));
+    e.add64(EncodingBuilder::new(
+        copy.bind(i32.clone()),
+        r_icopy,
+        opimm32_bits(0b000, 0),
+    ));
+
+    e.add32(EncodingBuilder::new(
+        regmove.bind(i32.clone()),
+        r_irmov,
+        opimm_bits(0b000, 0),
+    ));
+    e.add64(EncodingBuilder::new(
+        regmove.bind(i64.clone()),
+        r_irmov,
+        opimm_bits(0b000, 0),
+    ));
+    e.add64(EncodingBuilder::new(
+        regmove.bind(i32.clone()),
+        r_irmov,
+        opimm32_bits(0b000, 0),
+    ));
+
+    e.add32(EncodingBuilder::new(
+        copy.bind(b1.clone()),
+        r_icopy,
+        opimm_bits(0b000, 0),
+    ));
+    e.add64(EncodingBuilder::new(
+        copy.bind(b1.clone()),
+        r_icopy,
+        opimm_bits(0b000, 0),
+    ));
+    e.add32(EncodingBuilder::new(
+        regmove.bind(b1.clone()),
+        r_irmov,
+        opimm_bits(0b000, 0),
+    ));
+    e.add64(EncodingBuilder::new(
+        regmove.bind(b1.clone()),
+        r_irmov,
+        opimm_bits(0b000, 0),
+    ));
+
+    // Narrow integers live in whole registers, so the register allocator copies and moves them like
+    // the wider ones.
+    for ty in &[Int::I8, Int::I16] {
+        e.add32(EncodingBuilder::new(
+            copy.bind(*ty),
+            r_icopy,
+            opimm_bits(0b000, 0),
+        ));
+        e.add64(EncodingBuilder::new(
+            copy.bind(*ty),
+            r_icopy,
+            opimm_bits(0b000, 0),
+        ));
+        e.add32(EncodingBuilder::new(
+            regmove.bind(*ty),
+            r_irmov,
+            opimm_bits(0b000, 0),
+        ));
+        e.add64(EncodingBuilder::new(
+            regmove.bind(*ty),
+            r_irmov,
+            opimm_bits(0b000, 0),
+        ));
+    }
+
+    // "C" Standard Extension for Compressed Instructions. Gated by the `supports_c` flag. The
+    // 16-bit encodings come after the 32-bit ones, so the register allocator works with the full
+    // register classes. The shrink_instructions pass then picks them when the operands allow it.
+    for &(inst, recipe) in &[(copy, r_cr_mov), (regmove, r_cr_rmov)] {
+        let c = |ty: &ValueType| EncodingBuilder::new(inst.bind(ty.clone()), recipe, 0x8002);
+        e.add32(c(&i32).isa_predicate(supports_c));
+        e.add64(c(&i64).isa_predicate(supports_c));
+        e.add64(c(&i32).isa_predicate(supports_c));
+        e.add32(c(&b1).isa_predicate(supports_c));
+        e.add64(c(&b1).isa_predicate(supports_c));
+    }
+
+    e.add32(
+        EncodingBuilder::new(iadd.bind(i32.clone()), r_cr_add, 0x9002).isa_predicate(supports_c),
+    );
+    e.add64(
+        EncodingBuilder::new(iadd.bind(i64.clone()), r_cr_add, 0x9002).isa_predicate(supports_c),
+    );
+    e.add64(EncodingBuilder::new(iadd.bind(i32.clone()), r_ca, 0x9c21).isa_predicate(supports_c));
+    e.add64(EncodingBuilder::new(isub.bind(i32.clone()), r_ca, 0x9c01).isa_predicate(supports_c));
+
+    for &(inst, bits) in &[
+        (isub, 0x8c01),
+        (bxor, 0x8c21),
+        (bor, 0x8c41),
+        (band, 0x8c61),
+    ] {
+        e.add32(EncodingBuilder::new(inst.bind(i32.clone()), r_ca, bits).isa_predicate(supports_c));
+        e.add64(EncodingBuilder::new(inst.bind(i64.clone()), r_ca, bits).isa_predicate(supports_c));
+    }
+
+    e.add32(
+        EncodingBuilder::new(iadd_imm.bind(i32.clone()), r_ci_addi, 0x0001)
+            .isa_predicate(supports_c),
+    );
+    e.add64(
+        EncodingBuilder::new(iadd_imm.bind(i64.clone()), r_ci_addi, 0x0001)
+            .isa_predicate(supports_c),
+    );
+    e.add64(
+        EncodingBuilder::new(iadd_imm.bind(i32.clone()), r_ci_addi, 0x2001)
+            .isa_predicate(supports_c),
+    );
+    e.add32(
+        EncodingBuilder::new(band_imm.bind(i32.clone()), r_cb_andi, 0x8801)
+            .isa_predicate(supports_c),
+    );
+    e.add64(
+        EncodingBuilder::new(band_imm.bind(i64.clone()), r_cb_andi, 0x8801)
+            .isa_predicate(supports_c),
+    );
+
+    e.add32(
+        EncodingBuilder::new(iconst.bind(i32.clone()), r_ci_li, 0x4001).isa_predicate(supports_c),
+    );
+    e.add64(
+        EncodingBuilder::new(iconst.bind(i32.clone()), r_ci_li, 0x4001).isa_predicate(supports_c),
+    );
+    e.add64(
+        EncodingBuilder::new(iconst.bind(i64.clone()), r_ci_li, 0x4001).isa_predicate(supports_c),
+    );
+
+    e.add32(
+        EncodingBuilder::new(ishl_imm.bind(i32.clone()), r_ci_shamt, 0x0002)
+            .isa_predicate(supports_c),
+    );
+    e.add64(
+        EncodingBuilder::new(ishl_imm.bind(i64.clone()), r_ci_shamt, 0x0002)
+            .isa_predicate(supports_c),
+    );
+    for &(inst, bits) in &[(ushr_imm, 0x8001), (sshr_imm, 0x8401)] {
+        e.add32(
+            EncodingBuilder::new(inst.bind(i32.clone()), r_cb_shamt, bits)
+                .isa_predicate(supports_c),
+        );
+        e.add64(
+            EncodingBuilder::new(inst.bind(i64.clone()), r_cb_shamt, bits)
+                .isa_predicate(supports_c),
+        );
+    }
+
+    e.add32(EncodingBuilder::new(load_i32.clone(), r_cl_w, 0x4000).isa_predicate(supports_c));
+    e.add64(EncodingBuilder::new(load_i32, r_cl_w, 0x4000).isa_predicate(supports_c));
+    e.add64(EncodingBuilder::new(load_i64, r_cl_d, 0x6000).isa_predicate(supports_c));
+    e.add32(EncodingBuilder::new(store_i32.clone(), r_cs_w, 0xc000).isa_predicate(supports_c));
+    e.add64(EncodingBuilder::new(store_i32, r_cs_w, 0xc000).isa_predicate(supports_c));
+    e.add64(EncodingBuilder::new(store_i64, r_cs_d, 0xe000).isa_predicate(supports_c));
+
+    e.add32(EncodingBuilder::new(jump, r_cj, 0xa001).isa_predicate(supports_c));
+    e.add64(EncodingBuilder::new(jump, r_cj, 0xa001).isa_predicate(supports_c));
+
+    for &(inst, bits) in &[(brz, 0xc001), (brnz, 0xe001)] {
+        e.add32(
+            EncodingBuilder::new(inst.bind(i32.clone()), r_cb_zero, bits).isa_predicate(supports_c),
+        );
+        e.add64(
+            EncodingBuilder::new(inst.bind(i64.clone()), r_cb_zero, bits).isa_predicate(supports_c),
+        );
+        e.add32(
+            EncodingBuilder::new(inst.bind(b1.clone()), r_cb_zero, bits).isa_predicate(supports_c),
+        );
+        e.add64(
+            EncodingBuilder::new(inst.bind(b1.clone()), r_cb_zero, bits).isa_predicate(supports_c),
+        );
+    }
+
+    e.add32(EncodingBuilder::new(x_return, r_cr_ret, 0x8002).isa_predicate(supports_c));
+    e.add64(EncodingBuilder::new(x_return, r_cr_ret, 0x8002).isa_predicate(supports_c));
+    e.add32(
+        EncodingBuilder::new(call_indirect.bind(i32.clone()), r_cr_call, 0x9002)
+            .isa_predicate(supports_c),
+    );
+    e.add64(
+        EncodingBuilder::new(call_indirect.bind(i64.clone()), r_cr_call, 0x9002)
+            .isa_predicate(supports_c),
+    );
+
+    e
+}
@@ -0,0 +1,85 @@
+//! Custom legalization patterns for RISC-V.
+
+use base;
+use cdsl::ast::{var, Literal};
+use cdsl::xform::{XFormGroupBuilder, XFormGroupIndex};
+
+/// Define the RISC-V legalization group, and return the index of `riscv_expand`.
+pub fn define(shared: &mut base::Definitions) -> XFormGroupIndex {
+    let insts = &shared.instructions;
+    let imm = &shared.imm;
+    let groups = &mut shared.transform_groups;
+
+    let mut group = XFormGroupBuilder::new(
+        "riscv_expand",
+        r#"
+        Legalize instructions by expansion.
+
+        Use RISC-V specific patterns if needed.
+        "#,
+    )
+    .isa("riscv")
+    .chain(groups.by_name("expand"));
+
+    // List of instructions.
+    let band = insts.by_name("band");
+    let bnot = insts.by_name("bnot");
+    let bor = insts.by_name("bor");
+    let fcmp = insts.by_name("fcmp");
+
+    let a = var("a");
+    let x = var("x");
+    let y = var("y");
+    let a1 = var("a1");
+    let a2 = var("a2");
+
+    // Floating point condition codes.
+    //
+    // The `feq`, `flt` and `fle` instructions only implement the ordered `eq`,
+    // `lt` and `le` conditions, and swapping their operands gives `gt` and `ge`.
+    // The remaining codes are built from these.
+    let floatcc = |name: &'static str| Literal::enumerator_for(&imm.floatcc, name);
+
+    let floatcc_ord = floatcc("ord");
+    let floatcc_eq = floatcc("eq");
+    group.legalize(
+        def!(a = fcmp(floatcc_ord, x, y)),
+        vec![
+            def!(a1 = fcmp(floatcc_eq, x, x)),
+            def!(a2 = fcmp(floatcc_eq, y, y)),
+            def!(a = band(a1, a2)),
+        ],
+    );
+
+    let floatcc_one = floatcc("one");
+    let floatcc_lt = floatcc("lt");
+    let floatcc_gt = floatcc("gt");
+    group.legalize(
+        def!(a = fcmp(floatcc_one, x, y)),
+        vec![
+            def!(a1 = fcmp(floatcc_lt, x, y)),
+            def!(a2 = fcmp(floatcc_gt, x, y)),
+            def!(a = bor(a1, a2)),
+        ],
+    );
+
+    // The unordered conditions are the negations of the ordered ones.
+    for &(cc, neg_cc) in &[
+        ("uno", "ord"),
+        ("ne", "eq"),
+        ("ueq", "one"),
+        ("ult", "ge"),
+        ("ule", "gt"),
+        ("ugt", "le"),
+        ("uge", "lt"),
+    ] {
+        let cc = floatcc(cc);
+        let neg_cc = floatcc(neg_cc);
+        group.legalize(
+            def!(a = fcmp(cc, x, y)),
+            vec![def!(a1 = fcmp(neg_cc, x, y)), def!(a = bnot(a1))],
+        );
+    }
+
+    group.finish_and_add_to(groups)
+}
@@ -0,0 +1,64 @@
+//! RISC-V Target Architecture.
+
+use base;
+use base::types::{Float, Int};
+use cdsl::cpu_modes::CpuMode;
+use cdsl::instructions::InstructionGroupBuilder;
+use cdsl::isa::TargetIsa;
+use cdsl::settings::SettingGroup;
+
+mod encodings;
+mod legalize;
+mod recipes;
+mod registers;
+mod settings;
+
+pub fn define(shared_settings: &SettingGroup, shared_defs: &mut base::Definitions) -> TargetIsa {
+    let settings = settings::define(shared_settings);
+    let regs = registers::define();
+
+    // No ISA-specific instructions.
+    let instructions = InstructionGroupBuilder::new(
+        "riscv",
+        "riscv specific instruction set",
+        &shared_defs.format_registry,
+    )
+    .finish();
+
+    let riscv_expand = legalize::define(shared_defs);
+
+    let groups = &shared_defs.transform_groups;
+    let narrow = groups.by_name("narrow");
+    let expand = groups.by_name("expand");
+
+    let mut rv32 = CpuMode::new("RV32");
+    let mut rv64 = CpuMode::new("RV64");
+
+    rv32.legalize_monomorphic(expand);
+    rv32.legalize_default(narrow);
+    rv32.legalize_type(Int::I32, expand);
+    rv32.legalize_type(Float::F32, riscv_expand);
+    rv32.legalize_type(Float::F64, riscv_expand);
+
+    rv64.legalize_monomorphic(expand);
+    rv64.legalize_default(narrow);
+    rv64.legalize_type(Int::I32, expand);
+    rv64.legalize_type(Int::I64, expand);
+    rv64.legalize_type(Float::F32, riscv_expand);
+    rv64.legalize_type(Float::F64, riscv_expand);
+
+    let recipes = recipes::define(&shared_defs.format_registry, &regs);
+
+    let encodings = encodings::define(shared_defs, &settings, &recipes);
+    rv32.set_encodings(encodings.enc32);
+    rv64.set_encodings(encodings.enc64);
+
+    TargetIsa::new(
+        "riscv",
+        settings,
+        regs,
+        instructions,
+        recipes,
+        vec![rv32, rv64],
+    )
+}
@@ -0,0 +1,733 @@
+//! RISC-V Encoding recipes.
+//!
+//! The encoding recipes defined here more or less correspond to the RISC-V
+//! native instruction formats described in the reference:
+//!
+//! > The RISC-V Instruction Set Manual
+//! > Volume I: User-Level ISA
+//! > Version 2.1
+
+use cdsl::formats::FormatRegistry;
+use cdsl::instructions::InstructionPredicate;
+use cdsl::recipes::{EncodingRecipeBuilder, OperandConstraint, RecipeGroup, Stack};
+use cdsl::registers::IsaRegs;
+
+// The low 7 bits of a RISC-V instruction is the base opcode. All 32-bit
+// instructions have 11 as the two low bits, with bits 6:2 determining the base
+// opcode.
+//
+// Encbits for the 32-bit recipes are opcode[6:2] | (funct3 << 5) | ...
+// The functions below encode the encbits.
+
+pub fn load_bits(funct3: u16) -> u16 {
+    assert!(funct3 <= 0b111);
+    funct3 << 5
+}
+
+pub fn store_bits(funct3: u16) -> u16 {
+    assert!(funct3 <= 0b111);
+    0b01000 | (funct3 << 5)
+}
+
+pub fn branch_bits(funct3: u16) -> u16 {
+    assert!(funct3 <= 0b111);
+    0b11000 | (funct3 << 5)
+}
+
+pub fn jalr_bits() -> u16 {
+    0b11001
+}
+
+pub fn jal_bits() -> u16 {
+    0b11011
+}
+
+pub fn opimm_bits(funct3: u16, funct7: u16) -> u16 {
+    assert!(funct3 <= 0b111);
+    0b00100 | (funct3 << 5) | (funct7 << 8)
+}
+
+pub fn opimm32_bits(funct3: u16, funct7: u16) -> u16 {
+    assert!(funct3 <= 0b111);
+    0b00110 | (funct3 << 5) | (funct7 << 8)
+}
+
+pub fn op_bits(funct3: u16, funct7: u16) -> u16 {
+    assert!(funct3 <= 0b111);
+    assert!(funct7 <= 0b111_1111);
+    0b01100 | (funct3 << 5) | (funct7 << 8)
+}
+
+pub fn op32_bits(funct3: u16, funct7: u16) -> u16 {
+    assert!(funct3 <= 0b111);
+    assert!(funct7 <= 0b111_1111);
+    0b01110 | (funct3 << 5) | (funct7 << 8)
+}
+
+pub fn load_fp_bits(funct3: u16) -> u16 {
+    assert!(funct3 <= 0b111);
+    0b00001 | (funct3 << 5)
+}
+
+pub fn store_fp_bits(funct3: u16) -> u16 {
+    assert!(funct3 <= 0b111);
+    0b01001 | (funct3 << 5)
+}
+
+pub fn madd_bits(funct3: u16, fmt: u16) -> u16 {
+    assert!(funct3 <= 0b111);
+    assert!(fmt <= 0b11);
+    0b10000 | (funct3 << 5) | (fmt << 8)
+}
+
+/// The OP-FP opcode is implied by the recipes, which leaves room for the fixed rs2 field of the
+/// unary operations.
+pub fn op_fp_bits(funct3: u16, funct5: u16, fmt: u16, rs2: u16) -> u16 {
+    assert!(funct3 <= 0b111);
+    assert!(funct5 <= 0b1_1111);
+    assert!(fmt <= 0b11);
+    assert!(rs2 <= 0b1_1111);
+    funct3 | (fmt << 3) | (funct5 << 5) | (rs2 << 10)
+}
+
+pub fn lui_bits() -> u16 {
+    0b01101
+}
+
+pub fn define(formats: &FormatRegistry, regs: &IsaRegs) -> RecipeGroup {
+    // Format shorthands.
+    let f_binary = formats.by_name("Binary");
+    let f_binary_imm = formats.by_name("BinaryImm");
+    let f_branch = formats.by_name("Branch");
+    let f_branch_icmp = formats.by_name("BranchIcmp");
+    let f_call = formats.by_name("Call");
+    let f_call_indirect = formats.by_name("CallIndirect");
+    let f_float_compare = formats.by_name("FloatCompare");
+    let f_int_compare = formats.by_name("IntCompare");
+    let f_int_compare_imm = formats.by_name("IntCompareImm");
+    let f_jump = formats.by_name("Jump");
+    let f_load = formats.by_name("Load");
+    let f_multiary = formats.by_name("MultiAry");
+    let f_regmove = formats.by_name("RegMove");
+    let f_store = formats.by_name("Store");
+    let f_ternary = formats.by_name("Ternary");
+    let f_unary = formats.by_name("Unary");
+    let f_unary_imm = formats.by_name("UnaryImm");
+
+    // Register classes shorthands.
+    let gpr_class = regs.class_by_name("GPR");
+    let gpr = OperandConstraint::from(gpr_class);
+    let fpr_class = regs.class_by_name("FPR");
+    let fpr = OperandConstraint::from(fpr_class);
+    let gpr8 = OperandConstraint::from(regs.class_by_name("GPR8"));
+
+    let mut recipes = RecipeGroup::new();
+
+    // R-type 32-bit instructions: These are mostly binary arithmetic
+    // instructions. The encbits are `opcode[6:2] | (funct3 << 5) | (funct7 << 8)
+    recipes.push(
+        EncodingRecipeBuilder::new("R", f_binary, 4)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit("put_r(bits, in_reg0, in_reg1, out_reg0, sink);")
+            .build(formats),
+    );
+
+    // R-type with an immediate shift amount instead of rs2.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rshamt", f_binary_imm, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit("put_rshamt(bits, in_reg0, imm.into(), out_reg0, sink);")
+            .build(formats),
+    );
+
+    // R-type encoding of an integer comparison.
+    recipes.push(
+        EncodingRecipeBuilder::new("Ricmp", f_int_compare, 4)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit("put_r(bits, in_reg0, in_reg1, out_reg0, sink);")
+            .build(formats),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("Ii", f_binary_imm, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_binary_imm),
+                "imm",
+                12,
+                0,
+            ))
+            .emit("put_i(bits, in_reg0, imm.into(), out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Negation of a `b1` with an I-type `xori rd, rs1, 1`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Ibnot", f_unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit("put_i(bits, in_reg0, 1, out_reg0, sink);")
+            .build(formats),
+    );
+
+    // I-type instruction with a hardcoded %x0 rs1.
+    recipes.push(
+        EncodingRecipeBuilder::new("Iz", f_unary_imm, 4)
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_unary_imm),
+                "imm",
+                12,
+                0,
+            ))
+            .emit("put_i(bits, 0, imm.into(), out_reg0, sink);")
+            .build(formats),
+    );
+
+    // I-type encoding of an integer comparison.
+    recipes.push(
+        EncodingRecipeBuilder::new("Iicmp", f_int_compare_imm, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_int_compare_imm),
+                "imm",
+                12,
+                0,
+            ))
+            .emit("put_i(bits, in_reg0, imm.into(), out_reg0, sink);")
+            .build(formats),
+    );
+
+    // I-type encoding for `jalr` as a return instruction. We won't use the
+    // immediate offset. The variable return values are not encoded.
+    recipes.push(
+        EncodingRecipeBuilder::new("Iret", f_multiary, 4)
+            .emit(
+                r#"
+                    // Return instructions are always a jalr to %x1.
+                    // The return address is provided as a special-purpose link argument.
+                    put_i(
+                        bits,
+                        1, // rs1 = %x1
+                        0, // no offset.
+                        0, // rd = %x0: no address written.
+                        sink,
+                    );
+                "#,
+            )
+            .build(formats),
+    );
+
+    // I-type encoding for `jalr` as a call_indirect.
+    recipes.push(
+        EncodingRecipeBuilder::new("Icall", f_call_indirect, 4)
+            .operands_in(vec![gpr])
+            .emit(
+                r#"
+                    // call_indirect instructions are jalr with rd=%x1.
+                    put_i(
+                        bits,
+                        in_reg0,
+                        0, // no offset.
+                        1, // rd = %x1: link register.
+                        sink,
+                    );
+                "#,
+            )
+            .build(formats),
+    );
+
+    // Copy of a GPR is implemented as addi x, 0.
+    recipes.push(
+        EncodingRecipeBuilder::new("Icopy", f_unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit("put_i(bits, in_reg0, 0, out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Same for a GPR regmove.
+    recipes.push(
+        EncodingRecipeBuilder::new("Irmov", f_regmove, 4)
+            .operands_in(vec![gpr])
+            .emit("put_i(bits, src, 0, dst, sink);")
+            .build(formats),
+    );
+
+    // U-type instructions have a 20-bit immediate that targets bits 12-31.
+    recipes.push(
+        EncodingRecipeBuilder::new("U", f_unary_imm, 4)
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_unary_imm),
+                "imm",
+                32,
+                12,
+            ))
+            .emit("put_u(bits, imm.into(), out_reg0, sink);")
+            .build(formats),
+    );
+
+    // UJ-type unconditional branch instructions.
+    recipes.push(
+        EncodingRecipeBuilder::new("UJ", f_jump, 4)
+            .branch_range((0, 21))
+            .emit(
+                r#"
+                    let dest = i64::from(func.offsets[destination]);
+                    let disp = dest - i64::from(sink.offset());
+                    put_uj(bits, disp, 0, sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("UJcall", f_call, 4)
+            .emit(
+                r#"
+                    sink.reloc_external(Reloc::RiscvCall,
+                                        &func.dfg.ext_funcs[func_ref].name,
+                                        0);
+                    // rd=%x1 is the standard link register.
+                    put_uj(bits, 0, 1, sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    // SB-type branch instructions.
+    recipes.push(
+        EncodingRecipeBuilder::new("SB", f_branch_icmp, 4)
+            .operands_in(vec![gpr, gpr])
+            .branch_range((0, 13))
+            .emit(
+                r#"
+                    let dest = i64::from(func.offsets[destination]);
+                    let disp = dest - i64::from(sink.offset());
+                    put_sb(bits, disp, in_reg0, in_reg1, sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    // SB-type branch instruction with rs2 fixed to zero.
+    recipes.push(
+        EncodingRecipeBuilder::new("SBzero", f_branch, 4)
+            .operands_in(vec![gpr])
+            .branch_range((0, 13))
+            .emit(
+                r#"
+                    let dest = i64::from(func.offsets[destination]);
+                    let disp = dest - i64::from(sink.offset());
+                    put_sb(bits, disp, in_reg0, 0, sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    // I-type load of a GPR.
+    recipes.push(
+        EncodingRecipeBuilder::new("Iload", f_load, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_load),
+                "offset",
+                12,
+                0,
+            ))
+            .emit("put_i(bits, in_reg0, offset.into(), out_reg0, sink);")
+            .build(formats),
+    );
+
+    // S-type store of a GPR.
+    recipes.push(
+        EncodingRecipeBuilder::new("Sstore", f_store, 4)
+            .operands_in(vec![gpr, gpr])
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_store),
+                "offset",
+                12,
+                0,
+            ))
+            .emit("put_s(bits, in_reg1, offset.into(), in_reg0, sink);")
+            .build(formats),
+    );
+
+    // Spill of a GPR, stored relative to the stack pointer.
+    recipes.push(
+        EncodingRecipeBuilder::new("GPsp", f_unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![Stack::new(gpr_class).into()])
+            .emit("put_s_sp(bits, out_stk0.offset, in_reg0, sink);")
+            .build(formats),
+    );
+
+    // Fill of a GPR, loaded relative to the stack pointer.
+    recipes.push(
+        EncodingRecipeBuilder::new("GPfi", f_unary, 4)
+            .operands_in(vec![Stack::new(gpr_class).into()])
+            .operands_out(vec![gpr])
+            .emit("put_i_sp(bits, in_stk0.offset, out_reg0, sink);")
+            .build(formats),
+    );
+
+    // R-type floating point instructions. The encbits are
+    // `funct3 | (funct7 << 3) | (rs2 << 10)`, the OP-FP opcode being implied.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rf", f_binary, 4)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![fpr])
+            .emit("put_opfp(bits, in_reg0, in_reg1, out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Unary floating point instruction with the rs2 field taken from the encbits.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rfunary", f_unary, 4)
+            .operands_in(vec![fpr])
+            .operands_out(vec![fpr])
+            .emit("put_opfp(bits, in_reg0, 0, out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Sign injection with rs1 = rs2 implements copies, fneg and fabs.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rfsgn", f_unary, 4)
+            .operands_in(vec![fpr])
+            .operands_out(vec![fpr])
+            .emit("put_opfp(bits, in_reg0, in_reg0, out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Same for an FPR regmove.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rfrmov", f_regmove, 4)
+            .operands_in(vec![fpr])
+            .emit("put_opfp(bits, src, src, dst, sink);")
+            .build(formats),
+    );
+
+    // Floating point comparison writing 0 or 1 to a GPR.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rfcmp", f_float_compare, 4)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![gpr])
+            .emit("put_opfp(bits, in_reg0, in_reg1, out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Same with the operands swapped, so `gt` and `ge` can use `flt` and `fle`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rfcmpswap", f_float_compare, 4)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![gpr])
+            .emit("put_opfp(bits, in_reg1, in_reg0, out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Conversion or bit move from a GPR to an FPR.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rgf", f_unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![fpr])
+            .emit("put_opfp(bits, in_reg0, 0, out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Bit move from an FPR to a GPR.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rfg", f_unary, 4)
+            .operands_in(vec![fpr])
+            .operands_out(vec![gpr])
+            .emit("put_opfp(bits, in_reg0, 0, out_reg0, sink);")
+            .build(formats),
+    );
+
+    // Conversion from an FPR to a GPR, trapping on NaN and on values out of range.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rfgtrap", f_unary, 40)
+            .operands_in(vec![fpr])
+            .operands_out(vec![gpr])
+            .emit("put_fcvt_trap(bits, in_reg0, out_reg0, func.srclocs[inst], sink);")
+            .build(formats),
+    );
+
+    // R4-type fused multiply-add. The encbits are `opcode[6:2] | (funct3 << 5) | (fmt << 8)`.
+    recipes.push(
+        EncodingRecipeBuilder::new("R4", f_ternary, 4)
+            .operands_in(vec![fpr, fpr, fpr])
+            .operands_out(vec![fpr])
+            .emit("put_r4(bits, in_reg0, in_reg1, in_reg2, out_reg0, sink);")
+            .build(formats),
+    );
+
+    // I-type load of an FPR.
+    recipes.push(
+        EncodingRecipeBuilder::new("Ifload", f_load, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![fpr])
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_load),
+                "offset",
+                12,
+                0,
+            ))
+            .emit("put_i(bits, in_reg0, offset.into(), out_reg0, sink);")
+            .build(formats),
+    );
+
+    // S-type store of an FPR.
+    recipes.push(
+        EncodingRecipeBuilder::new("Sfstore", f_store, 4)
+            .operands_in(vec![fpr, gpr])
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_store),
+                "offset",
+                12,
+                0,
+            ))
+            .emit("put_s(bits, in_reg1, offset.into(), in_reg0, sink);")
+            .build(formats),
+    );
+
+    // Spill of an FPR, stored relative to the stack pointer.
+    recipes.push(
+        EncodingRecipeBuilder::new("FPsp", f_unary, 4)
+            .operands_in(vec![fpr])
+            .operands_out(vec![Stack::new(fpr_class).into()])
+            .emit("put_s_sp(bits, out_stk0.offset, in_reg0, sink);")
+            .build(formats),
+    );
+
+    // Fill of an FPR, loaded relative to the stack pointer.
+    recipes.push(
+        EncodingRecipeBuilder::new("FPfi", f_unary, 4)
+            .operands_in(vec![Stack::new(fpr_class).into()])
+            .operands_out(vec![fpr])
+            .emit("put_i_sp(bits, in_stk0.offset, out_reg0, sink);")
+            .build(formats),
+    );
+
+    // 16-bit instructions from the "C" Standard Extension for Compressed Instructions. The
+    // encbits are the complete instruction with the operand fields cleared.
+
+    // CR-type move, `c.mv`.
+    recipes.push(
+        EncodingRecipeBuilder::new("CRmov", f_unary, 2)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit("put_cr(bits, out_reg0, in_reg0, sink);")
+            .build(formats),
+    );
+
+    // Same for a GPR regmove.
+    recipes.push(
+        EncodingRecipeBuilder::new("CRrmov", f_regmove, 2)
+            .operands_in(vec![gpr])
+            .emit("put_cr(bits, dst, src, sink);")
+            .build(formats),
+    );
+
+    // CR-type two-address arithmetic, `c.add`.
+    recipes.push(
+        EncodingRecipeBuilder::new("CRadd", f_binary, 2)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![OperandConstraint::TiedInput(0)])
+            .emit("put_cr(bits, in_reg0, in_reg1, sink);")
+            .build(formats),
+    );
+
+    // CR-type return, `c.jr %x1`.
+    recipes.push(
+        EncodingRecipeBuilder::new("CRret", f_multiary, 2)
+            .emit("put_cr(bits, 1, 0, sink);")
+            .build(formats),
+    );
+
+    // CR-type indirect call, `c.jalr`.
+    recipes.push(
+        EncodingRecipeBuilder::new("CRcall", f_call_indirect, 2)
+            .operands_in(vec![gpr])
+            .emit("put_cr(bits, in_reg0, 0, sink);")
+            .build(formats),
+    );
+
+    // CI-type two-address add of a 6-bit signed immediate.
+    recipes.push(
+        EncodingRecipeBuilder::new("CIaddi", f_binary_imm, 2)
+            .operands_in(vec![gpr])
+            .operands_out(vec![OperandConstraint::TiedInput(0)])
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_binary_imm),
+                "imm",
+                6,
+                0,
+            ))
+            .emit("put_ci(bits, in_reg0, imm.into(), sink);")
+            .build(formats),
+    );
+
+    // CI-type load of a 6-bit signed immediate, `c.li`.
+    recipes.push(
+        EncodingRecipeBuilder::new("CIli", f_unary_imm, 2)
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_unary_imm),
+                "imm",
+                6,
+                0,
+            ))
+            .emit("put_ci(bits, out_reg0, imm.into(), sink);")
+            .build(formats),
+    );
+
+    // CI-type two-address left shift, `c.slli`.
+    recipes.push(
+        EncodingRecipeBuilder::new("CIshamt", f_binary_imm, 2)
+            .operands_in(vec![gpr])
+            .operands_out(vec![OperandConstraint::TiedInput(0)])
+            .inst_predicate(InstructionPredicate::is_unsigned_int(
+                formats.get(f_binary_imm),
+                "imm",
+                5,
+                0,
+            ))
+            .emit("put_ci(bits, in_reg0, imm.into(), sink);")
+            .build(formats),
+    );
+
+    // CA-type two-address arithmetic on `x8`-`x15`.
+    recipes.push(
+        EncodingRecipeBuilder::new("CA", f_binary, 2)
+            .operands_in(vec![gpr8, gpr8])
+            .operands_out(vec![OperandConstraint::TiedInput(0)])
+            .emit("put_ca(bits, in_reg0, in_reg1, sink);")
+            .build(formats),
+    );
+
+    // CB-type two-address right shifts on `x8`-`x15`.
+    recipes.push(
+        EncodingRecipeBuilder::new("CBshamt", f_binary_imm, 2)
+            .operands_in(vec![gpr8])
+            .operands_out(vec![OperandConstraint::TiedInput(0)])
+            .inst_predicate(InstructionPredicate::is_unsigned_int(
+                formats.get(f_binary_imm),
+                "imm",
+                5,
+                0,
+            ))
+            .emit("put_cbi(bits, in_reg0, imm.into(), sink);")
+            .build(formats),
+    );
+
+    // CB-type two-address `c.andi` on `x8`-`x15`.
+    recipes.push(
+        EncodingRecipeBuilder::new("CBandi", f_binary_imm, 2)
+            .operands_in(vec![gpr8])
+            .operands_out(vec![OperandConstraint::TiedInput(0)])
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_binary_imm),
+                "imm",
+                6,
+                0,
+            ))
+            .emit("put_cbi(bits, in_reg0, imm.into(), sink);")
+            .build(formats),
+    );
+
+    // CL-type word load, `c.lw`.
+    recipes.push(
+        EncodingRecipeBuilder::new("CLw", f_load, 2)
+            .operands_in(vec![gpr8])
+            .operands_out(vec![gpr8])
+            .inst_predicate(InstructionPredicate::is_unsigned_int(
+                formats.get(f_load),
+                "offset",
+                7,
+                2,
+            ))
+            .emit("put_clw(bits, in_reg0, offset.into(), out_reg0, sink);")
+            .build(formats),
+    );
+
+    // CS-type word store, `c.sw`.
+    recipes.push(
+        EncodingRecipeBuilder::new("CSw", f_store, 2)
+            .operands_in(vec![gpr8, gpr8])
+            .inst_predicate(InstructionPredicate::is_unsigned_int(
+                formats.get(f_store),
+                "offset",
+                7,
+                2,
+            ))
+            .emit("put_clw(bits, in_reg1, offset.into(), in_reg0, sink);")
+            .build(formats),
+    );
+
+    // CL-type double word load, `c.ld`.
+    recipes.push(
+        EncodingRecipeBuilder::new("CLd", f_load, 2)
+            .operands_in(vec![gpr8])
+            .operands_out(vec![gpr8])
+            .inst_predicate(InstructionPredicate::is_unsigned_int(
+                formats.get(f_load),
+                "offset",
+                8,
+                3,
+            ))
+            .emit("put_cld(bits, in_reg0, offset.into(), out_reg0, sink);")
+            .build(formats),
+    );
+
+    // CS-type double word store, `c.sd`.
+    recipes.push(
+        EncodingRecipeBuilder::new("CSd", f_store, 2)
+            .operands_in(vec![gpr8, gpr8])
+            .inst_predicate(InstructionPredicate::is_unsigned_int(
+                formats.get(f_store),
+                "offset",
+                8,
+                3,
+            ))
+            .emit("put_cld(bits, in_reg1, offset.into(), in_reg0, sink);")
+            .build(formats),
+    );
+
+    // CJ-type unconditional branch, `c.j`.
+    recipes.push(
+        EncodingRecipeBuilder::new("CJ", f_jump, 2)
+            .branch_range((0, 12))
+            .emit(
+                r#"
+                    let dest = i64::from(func.offsets[destination]);
+                    let disp = dest - i64::from(sink.offset());
+                    put_cj(bits, disp, sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    // CB-type branch comparing `x8`-`x15` to zero, `c.beqz` and `c.bnez`.
+    recipes.push(
+        EncodingRecipeBuilder::new("CBzero", f_branch, 2)
+            .operands_in(vec![gpr8])
+            .branch_range((0, 9))
+            .emit(
+                r#"
+                    let dest = i64::from(func.offsets[destination]);
+                    let disp = dest - i64::from(sink.offset());
+                    put_cb(bits, disp, in_reg0, sink);
+                "#,
+            )
+            .build(formats),
+    );
+
+    recipes
+}
@@ -0,0 +1,26 @@
+//! RISC-V register banks.
+
+use cdsl::registers::{IsaRegs, IsaRegsBuilder, RegBankBuilder, RegClassBuilder};
+
+pub fn define() -> IsaRegs {
+    let mut regs = IsaRegsBuilder::new();
+
+    // We include `x0`, a.k.a `zero` in the register bank. It will be reserved.
+    let int_regs = regs.add_bank(
+        RegBankBuilder::new("IntRegs", "General purpose registers")
+            .units(32)
+            .prefix("x"),
+    );
+    let float_regs = regs.add_bank(
+        RegBankBuilder::new("FloatRegs", "Floating point registers")
+            .units(32)
+            .prefix("f"),
+    );
+
+    let gpr = regs.add_class(RegClassBuilder::new_toplevel("GPR", int_regs));
+    regs.add_class(RegClassBuilder::new_toplevel("FPR", float_regs));
+    // The registers `x8`-`x15` addressable by the 3-bit fields of compressed instructions.
+    regs.add_class(RegClassBuilder::subclass_of("GPR8", gpr, 8, 16));
+
+    regs.finish()
+}
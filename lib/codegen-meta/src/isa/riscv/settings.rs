@@ -0,0 +1,60 @@
+//! RISC-V settings.
+
+use cdsl::settings::{SettingGroup, SettingGroupBuilder};
+
+pub fn define(shared: &SettingGroup) -> SettingGroup {
+    let mut settings = SettingGroupBuilder::new("riscv");
+
+    let supports_m = settings.add_bool(
+        "supports_m",
+        "CPU supports the 'M' extension (mul/div)",
+        false,
+    );
+    let supports_a = settings.add_bool(
+        "supports_a",
+        "CPU supports the 'A' extension (atomics)",
+        false,
+    );
+    let supports_f = settings.add_bool(
+        "supports_f",
+        "CPU supports the 'F' extension (float)",
+        false,
+    );
+    let supports_d = settings.add_bool(
+        "supports_d",
+        "CPU supports the 'D' extension (double)",
+        false,
+    );
+    settings.add_bool(
+        "supports_c",
+        "CPU supports the 'C' extension (compressed instructions)",
+        false,
+    );
+
+    let enable_m = settings.add_bool(
+        "enable_m",
+        "Enable the use of 'M' instructions if available",
+        true,
+    );
+
+    settings.add_bool(
+        "enable_e",
+        "Enable the 'RV32E' instruction set with only 16 registers",
+        false,
+    );
+
+    let shared_enable_atomics = shared.get_bool("enable_atomics");
+    let shared_enable_float = shared.get_bool("enable_float");
+    let shared_enable_simd = shared.get_bool("enable_simd");
+
+    settings.add_predicate("use_m", predicate!(supports_m && enable_m));
+    settings.add_predicate("use_a", predicate!(supports_a && shared_enable_atomics));
+    settings.add_predicate("use_f", predicate!(supports_f && shared_enable_float));
+    settings.add_predicate("use_d", predicate!(supports_d && shared_enable_float));
+    settings.add_predicate(
+        "full_float",
+        predicate!(shared_enable_simd && supports_f && supports_d),
+    );
+
+    settings.finish()
+}
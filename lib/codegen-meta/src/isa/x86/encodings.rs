@@ -0,0 +1,2071 @@
+//! x86 Encodings.
+
+use std::collections::HashMap;
+
+use base;
+use cdsl::encodings::{Encoding, EncodingBuilder, InstSpec};
+use cdsl::instructions::{Bindable, Instruction, InstructionGroup, InstructionPredicate};
+use cdsl::recipes::{self, EncodingRecipe, EncodingRecipeNumber};
+use cdsl::settings::SettingGroup;
+
+use base::types::{Bool, Float, Int};
+use cdsl::types::{LaneType, ValueType};
+
+use super::recipes::{RecipeGroup, Template};
+
+/// The encodings of the I32 and I64 CPU modes, and the recipes they use.
+pub struct PerCpuModeEncodings<'defs> {
+    pub enc32: Vec<Encoding>,
+    pub enc64: Vec<Encoding>,
+    pub recipes: recipes::RecipeGroup,
+    recipes_by_name: HashMap<String, EncodingRecipeNumber>,
+    shared_defs: &'defs base::Definitions,
+}
+
+impl<'defs> PerCpuModeEncodings<'defs> {
+    fn add_recipe(&mut self, recipe: EncodingRecipe) -> EncodingRecipeNumber {
+        if let Some(&number) = self.recipes_by_name.get(&recipe.name) {
+            assert!(
+                *self.recipes.get(number) == recipe,
+                "different recipes with the same name {}",
+                recipe.name
+            );
+            return number;
+        }
+        let name = recipe.name.clone();
+        let number = self.recipes.push(recipe);
+        self.recipes_by_name.insert(name, number);
+        number
+    }
+
+    fn make_encoding<I, F>(
+        &mut self,
+        inst: I,
+        recipe: EncodingRecipe,
+        bits: u16,
+        builder_closure: F,
+    ) -> Encoding
+    where
+        I: Into<InstSpec>,
+        F: FnOnce(EncodingBuilder) -> EncodingBuilder,
+    {
+        let recipe = self.add_recipe(recipe);
+        let builder = builder_closure(EncodingBuilder::new(inst, recipe, bits));
+        builder.build(&self.recipes, &self.shared_defs.format_registry)
+    }
+
+    fn enc32_func<I, F>(&mut self, inst: I, template: Template, builder_closure: F)
+    where
+        I: Into<InstSpec>,
+        F: FnOnce(EncodingBuilder) -> EncodingBuilder,
+    {
+        let (recipe, bits) = template.build(&self.shared_defs.format_registry);
+        let encoding = self.make_encoding(inst, recipe, bits, builder_closure);
+        self.enc32.push(encoding);
+    }
+    fn enc32<I: Into<InstSpec>>(&mut self, inst: I, template: Template) {
+        self.enc32_func(inst, template, |x| x);
+    }
+    fn enc32_isap<I: Into<InstSpec>>(&mut self, inst: I, template: Template, isap: u8) {
+        self.enc32_func(inst, template, |x| x.isa_predicate(isap));
+    }
+    fn enc32_instp<I: Into<InstSpec>>(
+        &mut self,
+        inst: I,
+        template: Template,
+        instp: InstructionPredicate,
+    ) {
+        self.enc32_func(inst, template, |x| x.inst_predicate(instp));
+    }
+    fn enc32_rec<I: Into<InstSpec>>(&mut self, inst: I, recipe: &EncodingRecipe, bits: u16) {
+        let encoding = self.make_encoding(inst, recipe.clone(), bits, |x| x);
+        self.enc32.push(encoding);
+    }
+
+    fn enc64_func<I, F>(&mut self, inst: I, template: Template, builder_closure: F)
+    where
+        I: Into<InstSpec>,
+        F: FnOnce(EncodingBuilder) -> EncodingBuilder,
+    {
+        let (recipe, bits) = template.build(&self.shared_defs.format_registry);
+        let encoding = self.make_encoding(inst, recipe, bits, builder_closure);
+        self.enc64.push(encoding);
+    }
+    fn enc64<I: Into<InstSpec>>(&mut self, inst: I, template: Template) {
+        self.enc64_func(inst, template, |x| x);
+    }
+    fn enc64_isap<I: Into<InstSpec>>(&mut self, inst: I, template: Template, isap: u8) {
+        self.enc64_func(inst, template, |x| x.isa_predicate(isap));
+    }
+    fn enc64_instp<I: Into<InstSpec>>(
+        &mut self,
+        inst: I,
+        template: Template,
+        instp: InstructionPredicate,
+    ) {
+        self.enc64_func(inst, template, |x| x.inst_predicate(instp));
+    }
+    fn enc64_rec<I: Into<InstSpec>>(&mut self, inst: I, recipe: &EncodingRecipe, bits: u16) {
+        let encoding = self.make_encoding(inst, recipe.clone(), bits, |x| x);
+        self.enc64.push(encoding);
+    }
+
+    /// Add encodings for `inst` to X86_64 with and without a REX prefix.
+    fn enc_x86_64<I: Clone + Into<InstSpec>>(&mut self, inst: I, template: Template) {
+        self.enc64(inst.clone(), template.rex());
+        self.enc64(inst, template);
+    }
+
+    /// Add encodings for `inst` to X86_64 with and without a REX prefix.
+    fn enc_x86_64_instp<I: Clone + Into<InstSpec>>(
+        &mut self,
+        inst: I,
+        template: Template,
+        instp: InstructionPredicate,
+    ) {
+        self.enc64_instp(inst.clone(), template.rex(), instp.clone());
+        self.enc64_instp(inst, template, instp);
+    }
+
+    /// Add encodings for `inst` to both X86_32 and X86_64.
+    fn enc_both<I: Clone + Into<InstSpec>>(&mut self, inst: I, template: Template) {
+        self.enc32(inst.clone(), template.clone());
+        self.enc_x86_64(inst, template);
+    }
+
+    /// Add VEX encodings for `inst` to both X86_32 and X86_64.
+    fn enc_vex<I: Clone + Into<InstSpec>>(&mut self, inst: I, template: Template) {
+        self.enc32(inst.clone(), template.vex());
+        self.enc64(inst, template.vex());
+    }
+
+    /// Add VEX encodings for `inst.i32` to both X86_32 and X86_64, and for `inst.i64` to X86_64
+    /// with the VEX.W bit set.
+    fn enc_vex_i32_i64<B: Bindable>(&mut self, inst: &B, template: Template) {
+        self.enc32(inst.bind(Int::I32), template.vex());
+        self.enc64(inst.bind(Int::I64), template.vex().w());
+        self.enc64(inst.bind(Int::I32), template.vex());
+    }
+
+    /// Add encodings for `inst` to both X86_32 and X86_64.
+    fn enc_both_instp<I: Clone + Into<InstSpec>>(
+        &mut self,
+        inst: I,
+        template: Template,
+        instp: InstructionPredicate,
+    ) {
+        self.enc32_instp(inst.clone(), template.clone(), instp.clone());
+        self.enc_x86_64_instp(inst, template, instp);
+    }
+
+    /// Add encodings for `inst.i32` to X86_32.
+    /// Add encodings for `inst.i32` to X86_64 with and without REX.
+    /// Add encodings for `inst.i64` to X86_64 with a REX.W prefix.
+    fn enc_i32_i64<B: Bindable>(&mut self, inst: &B, template: Template) {
+        self.enc32(inst.bind(Int::I32), template.clone());
+
+        // REX-less encoding must come after REX encoding so we don't use it by
+        // default. Otherwise reg-alloc would never use r8 and up.
+        self.enc64(inst.bind(Int::I32), template.rex());
+        self.enc64(inst.bind(Int::I32), template.clone());
+
+        self.enc64(inst.bind(Int::I64), template.rex().w());
+    }
+
+    /// Add encodings for `inst.i32` to X86_32.
+    /// Add encodings for `inst.i32` to X86_64 with and without REX.
+    /// Add encodings for `inst.i64` to X86_64 with a REX.W prefix.
+    ///
+    /// Similar to `enc_i32_i64` but applies `instp` to each encoding.
+    fn enc_i32_i64_instp<B: Bindable>(
+        &mut self,
+        inst: &B,
+        template: Template,
+        instp: InstructionPredicate,
+    ) {
+        self.enc32_instp(inst.bind(Int::I32), template.clone(), instp.clone());
+
+        // REX-less encoding must come after REX encoding so we don't use it by
+        // default. Otherwise reg-alloc would never use r8 and up.
+        self.enc64_instp(inst.bind(Int::I32), template.rex(), instp.clone());
+        self.enc64_instp(inst.bind(Int::I32), template.clone(), instp.clone());
+
+        self.enc64_instp(inst.bind(Int::I64), template.rex().w(), instp);
+    }
+
+    /// Add encodings for `inst.i32` to X86_32.
+    /// Add encodings for `inst.i32` to X86_64 with and without REX.
+    /// Add encodings for `inst.i64` to X86_64 with a REX prefix, using the
+    /// `w_bit` argument to determine whether or not to set the REX.W bit.
+    fn enc_i32_i64_ld_st(&mut self, inst: &Instruction, w_bit: bool, template: Template) {
+        self.enc32(inst.bind(Int::I32).bind_any(), template.clone());
+
+        // REX-less encoding must come after REX encoding so we don't use it by
+        // default. Otherwise reg-alloc would never use r8 and up.
+        self.enc64(inst.bind(Int::I32).bind_any(), template.rex());
+        self.enc64(inst.bind(Int::I32).bind_any(), template.clone());
+
+        if w_bit {
+            self.enc64(inst.bind(Int::I64).bind_any(), template.rex().w());
+        } else {
+            self.enc64(inst.bind(Int::I64).bind_any(), template.rex());
+            self.enc64(inst.bind(Int::I64).bind_any(), template);
+        }
+    }
+}
+
+pub fn define<'defs>(
+    shared_defs: &'defs base::Definitions,
+    shared_settings: &SettingGroup,
+    isa_settings: &SettingGroup,
+    x86: &InstructionGroup,
+    r: &RecipeGroup,
+) -> PerCpuModeEncodings<'defs> {
+    let shared = &shared_defs.instructions;
+    let formats = &shared_defs.format_registry;
+
+    let mut e = PerCpuModeEncodings {
+        enc32: Vec::new(),
+        enc64: Vec::new(),
+        recipes: recipes::RecipeGroup::new(),
+        recipes_by_name: HashMap::new(),
+        shared_defs,
+    };
+
+    // Shared instructions.
+    let adjust_sp_down = shared.by_name("adjust_sp_down");
+    let adjust_sp_down_imm = shared.by_name("adjust_sp_down_imm");
+    let adjust_sp_up_imm = shared.by_name("adjust_sp_up_imm");
+    let atomic_add = shared.by_name("atomic_add");
+    let atomic_cas = shared.by_name("atomic_cas");
+    let atomic_load = shared.by_name("atomic_load");
+    let atomic_xchg = shared.by_name("atomic_xchg");
+    let band = shared.by_name("band");
+    let band_imm = shared.by_name("band_imm");
+    let band_not = shared.by_name("band_not");
+    let bconst = shared.by_name("bconst");
+    let bint = shared.by_name("bint");
+    let bitcast = shared.by_name("bitcast");
+    let bnot = shared.by_name("bnot");
+    let bor = shared.by_name("bor");
+    let bor_imm = shared.by_name("bor_imm");
+    let brff = shared.by_name("brff");
+    let brif = shared.by_name("brif");
+    let brnz = shared.by_name("brnz");
+    let brz = shared.by_name("brz");
+    let bxor = shared.by_name("bxor");
+    let bxor_imm = shared.by_name("bxor_imm");
+    let call = shared.by_name("call");
+    let call_indirect = shared.by_name("call_indirect");
+    let ceil = shared.by_name("ceil");
+    let clz = shared.by_name("clz");
+    let copy = shared.by_name("copy");
+    let copy_special = shared.by_name("copy_special");
+    let ctz = shared.by_name("ctz");
+    let extractlane = shared.by_name("extractlane");
+    let f32const = shared.by_name("f32const");
+    let f64const = shared.by_name("f64const");
+    let fadd = shared.by_name("fadd");
+    let fcmp = shared.by_name("fcmp");
+    let fcvt_from_sint = shared.by_name("fcvt_from_sint");
+    let fdemote = shared.by_name("fdemote");
+    let fdiv = shared.by_name("fdiv");
+    let ffcmp = shared.by_name("ffcmp");
+    let fence = shared.by_name("fence");
+    let fill = shared.by_name("fill");
+    let floor = shared.by_name("floor");
+    let fmul = shared.by_name("fmul");
+    let fpromote = shared.by_name("fpromote");
+    let fsub = shared.by_name("fsub");
+    let func_addr = shared.by_name("func_addr");
+    let globalsym_addr = shared.by_name("globalsym_addr");
+    let iadd = shared.by_name("iadd");
+    let iadd_imm = shared.by_name("iadd_imm");
+    let icmp = shared.by_name("icmp");
+    let icmp_imm = shared.by_name("icmp_imm");
+    let iconst = shared.by_name("iconst");
+    let ifcmp = shared.by_name("ifcmp");
+    let ifcmp_imm = shared.by_name("ifcmp_imm");
+    let ifcmp_sp = shared.by_name("ifcmp_sp");
+    let imul = shared.by_name("imul");
+    let insertlane = shared.by_name("insertlane");
+    let ireduce = shared.by_name("ireduce");
+    let ishl = shared.by_name("ishl");
+    let ishl_imm = shared.by_name("ishl_imm");
+    let istore16 = shared.by_name("istore16");
+    let istore16_complex = shared.by_name("istore16_complex");
+    let istore32 = shared.by_name("istore32");
+    let istore32_complex = shared.by_name("istore32_complex");
+    let istore8 = shared.by_name("istore8");
+    let istore8_complex = shared.by_name("istore8_complex");
+    let isub = shared.by_name("isub");
+    let jump = shared.by_name("jump");
+    let load = shared.by_name("load");
+    let load_complex = shared.by_name("load_complex");
+    let nearest = shared.by_name("nearest");
+    let popcnt = shared.by_name("popcnt");
+    let regfill = shared.by_name("regfill");
+    let regmove = shared.by_name("regmove");
+    let regspill = shared.by_name("regspill");
+    let rotl = shared.by_name("rotl");
+    let rotr = shared.by_name("rotr");
+    let rotr_imm = shared.by_name("rotr_imm");
+    let selectif = shared.by_name("selectif");
+    let sextend = shared.by_name("sextend");
+    let sload16 = shared.by_name("sload16");
+    let sload16_complex = shared.by_name("sload16_complex");
+    let sload32 = shared.by_name("sload32");
+    let sload32_complex = shared.by_name("sload32_complex");
+    let sload8 = shared.by_name("sload8");
+    let sload8_complex = shared.by_name("sload8_complex");
+    let spill = shared.by_name("spill");
+    let sqrt = shared.by_name("sqrt");
+    let sshr = shared.by_name("sshr");
+    let sshr_imm = shared.by_name("sshr_imm");
+    let stack_addr = shared.by_name("stack_addr");
+    let store = shared.by_name("store");
+    let store_complex = shared.by_name("store_complex");
+    let trap = shared.by_name("trap");
+    let trapff = shared.by_name("trapff");
+    let trapif = shared.by_name("trapif");
+    let trueff = shared.by_name("trueff");
+    let trueif = shared.by_name("trueif");
+    let trunc = shared.by_name("trunc");
+    let uextend = shared.by_name("uextend");
+    let uload16 = shared.by_name("uload16");
+    let uload16_complex = shared.by_name("uload16_complex");
+    let uload32 = shared.by_name("uload32");
+    let uload32_complex = shared.by_name("uload32_complex");
+    let uload8 = shared.by_name("uload8");
+    let uload8_complex = shared.by_name("uload8_complex");
+    let ushr = shared.by_name("ushr");
+    let ushr_imm = shared.by_name("ushr_imm");
+    let vconcat = shared.by_name("vconcat");
+    let vselect = shared.by_name("vselect");
+    let vsplit = shared.by_name("vsplit");
+    let x_return = shared.by_name("return");
+
+    // x86-specific instructions.
+    let x86_bsf = x86.by_name("x86_bsf");
+    let x86_blsi = x86.by_name("x86_blsi");
+    let x86_blsr = x86.by_name("x86_blsr");
+    let x86_bsr = x86.by_name("x86_bsr");
+    let x86_cvtt2si = x86.by_name("x86_cvtt2si");
+    let x86_fmax = x86.by_name("x86_fmax");
+    let x86_fmin = x86.by_name("x86_fmin");
+    let x86_pop = x86.by_name("x86_pop");
+    let x86_pshufd = x86.by_name("x86_pshufd");
+    let x86_push = x86.by_name("x86_push");
+    let x86_scalar_to_vector = x86.by_name("x86_scalar_to_vector");
+    let x86_sdivmodx = x86.by_name("x86_sdivmodx");
+    let x86_smulx = x86.by_name("x86_smulx");
+    let x86_udivmodx = x86.by_name("x86_udivmodx");
+    let x86_umulx = x86.by_name("x86_umulx");
+    let x86_vzeroupper = x86.by_name("x86_vzeroupper");
+
+    // Recipes shorthands, prefixed with rec_.
+    let rec_adjustsp = r.template("adjustsp");
+    let rec_adjustsp_ib = r.template("adjustsp_ib");
+    let rec_adjustsp_id = r.template("adjustsp_id");
+    let rec_blend = r.template("blend");
+    let rec_allones_fnaddr4 = r.template("allones_fnaddr4");
+    let rec_allones_fnaddr8 = r.template("allones_fnaddr8");
+    let rec_brfb = r.template("brfb");
+    let rec_brfd = r.template("brfd");
+    let rec_brib = r.template("brib");
+    let rec_brid = r.template("brid");
+    let rec_bsf_and_bsr = r.template("bsf_and_bsr");
+    let rec_call_id = r.template("call_id");
+    let rec_call_plt_id = r.template("call_plt_id");
+    let rec_call_r = r.template("call_r");
+    let rec_cmov = r.template("cmov");
+    let rec_copysp = r.template("copysp");
+    let rec_div = r.template("div");
+    let rec_f32imm_z = r.template("f32imm_z");
+    let rec_f64imm_z = r.template("f64imm_z");
+    let rec_fa = r.template("fa");
+    let rec_fa_insertps = r.template("fa_insertps");
+    let rec_fa_lane = r.template("fa_lane");
+    let rec_fax = r.template("fax");
+    let rec_fcmp = r.template("fcmp");
+    let rec_fcscc = r.template("fcscc");
+    let rec_fence = r.template("fence");
+    let rec_ffill_sib32 = r.template("ffillSib32");
+    let rec_fill_sib32 = r.template("fillSib32");
+    let rec_fld = r.template("fld");
+    let rec_fld_disp32 = r.template("fldDisp32");
+    let rec_fld_disp8 = r.template("fldDisp8");
+    let rec_fld_with_index = r.template("fldWithIndex");
+    let rec_fld_with_index_disp32 = r.template("fldWithIndexDisp32");
+    let rec_fld_with_index_disp8 = r.template("fldWithIndexDisp8");
+    let rec_fnaddr4 = r.template("fnaddr4");
+    let rec_fnaddr8 = r.template("fnaddr8");
+    let rec_fregfill32 = r.template("fregfill32");
+    let rec_fregspill32 = r.template("fregspill32");
+    let rec_frmov = r.template("frmov");
+    let rec_frurm = r.template("frurm");
+    let rec_frurmi_lane = r.template("frurmi_lane");
+    let rec_fshuf_lane = r.template("fshuf_lane");
+    let rec_fspill_sib32 = r.template("fspillSib32");
+    let rec_fst = r.template("fst");
+    let rec_fst_disp32 = r.template("fstDisp32");
+    let rec_fst_disp8 = r.template("fstDisp8");
+    let rec_fst_with_index = r.template("fstWithIndex");
+    let rec_fst_with_index_disp32 = r.template("fstWithIndexDisp32");
+    let rec_fst_with_index_disp8 = r.template("fstWithIndexDisp8");
+    let rec_furm = r.template("furm");
+    let rec_furmi_lane = r.template("furmi_lane");
+    let rec_furmi_rnd = r.template("furmi_rnd");
+    let rec_got_fnaddr8 = r.template("got_fnaddr8");
+    let rec_got_gvaddr8 = r.template("got_gvaddr8");
+    let rec_gvaddr4 = r.template("gvaddr4");
+    let rec_gvaddr8 = r.template("gvaddr8");
+    let rec_icscc = r.template("icscc");
+    let rec_icscc_ib = r.template("icscc_ib");
+    let rec_icscc_id = r.template("icscc_id");
+    let rec_jmpb = r.template("jmpb");
+    let rec_jmpd = r.template("jmpd");
+    let rec_lcas = r.template("lcas");
+    let rec_ld = r.template("ld");
+    let rec_ld_disp32 = r.template("ldDisp32");
+    let rec_ld_disp8 = r.template("ldDisp8");
+    let rec_ld_with_index = r.template("ldWithIndex");
+    let rec_ld_with_index_disp32 = r.template("ldWithIndexDisp32");
+    let rec_ld_with_index_disp8 = r.template("ldWithIndexDisp8");
+    let rec_lrmw = r.template("lrmw");
+    let rec_lrmw_disp32 = r.template("lrmwDisp32");
+    let rec_lrmw_disp8 = r.template("lrmwDisp8");
+    let rec_mulx = r.template("mulx");
+    let rec_null = r.recipe("null");
+    let rec_null_fpr = r.recipe("null_fpr");
+    let rec_pcrel_fnaddr8 = r.template("pcrel_fnaddr8");
+    let rec_pcrel_gvaddr8 = r.template("pcrel_gvaddr8");
+    let rec_popq = r.template("popq");
+    let rec_pu_id = r.template("pu_id");
+    let rec_pu_id_bool = r.template("pu_id_bool");
+    let rec_pu_iq = r.template("pu_iq");
+    let rec_pushq = r.template("pushq");
+    let rec_r_ib = r.template("r_ib");
+    let rec_r_id = r.template("r_id");
+    let rec_rc = r.template("rc");
+    let rec_rcmp = r.template("rcmp");
+    let rec_rcmp_ib = r.template("rcmp_ib");
+    let rec_rcmp_id = r.template("rcmp_id");
+    let rec_rcmp_sp = r.template("rcmp_sp");
+    let rec_regfill32 = r.template("regfill32");
+    let rec_regspill32 = r.template("regspill32");
+    let rec_ret = r.template("ret");
+    let rec_rfumr = r.template("rfumr");
+    let rec_rfumr_lane = r.template("rfumr_lane");
+    let rec_rfurm = r.template("rfurm");
+    let rec_rmov = r.template("rmov");
+    let rec_rmw = r.template("rmw");
+    let rec_rmw_disp32 = r.template("rmwDisp32");
+    let rec_rmw_disp8 = r.template("rmwDisp8");
+    let rec_rr = r.template("rr");
+    let rec_rrx = r.template("rrx");
+    let rec_setf_abcd = r.template("setf_abcd");
+    let rec_seti_abcd = r.template("seti_abcd");
+    let rec_spaddr4_id = r.template("spaddr4_id");
+    let rec_spaddr8_id = r.template("spaddr8_id");
+    let rec_spill_sib32 = r.template("spillSib32");
+    let rec_st = r.template("st");
+    let rec_st_disp32 = r.template("stDisp32");
+    let rec_st_disp32_abcd = r.template("stDisp32_abcd");
+    let rec_st_disp8 = r.template("stDisp8");
+    let rec_st_disp8_abcd = r.template("stDisp8_abcd");
+    let rec_st_with_index = r.template("stWithIndex");
+    let rec_st_with_index_disp32 = r.template("stWithIndexDisp32");
+    let rec_st_with_index_disp32_abcd = r.template("stWithIndexDisp32_abcd");
+    let rec_st_with_index_disp8 = r.template("stWithIndexDisp8");
+    let rec_st_with_index_disp8_abcd = r.template("stWithIndexDisp8_abcd");
+    let rec_st_with_index_abcd = r.template("stWithIndex_abcd");
+    let rec_st_abcd = r.template("st_abcd");
+    let rec_t8jccb_abcd = r.template("t8jccb_abcd");
+    let rec_t8jccd_abcd = r.template("t8jccd_abcd");
+    let rec_t8jccd_long = r.template("t8jccd_long");
+    let rec_tjccb = r.template("tjccb");
+    let rec_tjccd = r.template("tjccd");
+    let rec_trap = r.template("trap");
+    let rec_trapif = r.recipe("trapif");
+    let rec_trapff = r.recipe("trapff");
+    let rec_u_id = r.template("u_id");
+    let rec_umr = r.template("umr");
+    let rec_ur = r.template("ur");
+    let rec_urm = r.template("urm");
+    let rec_urm_noflags = r.template("urm_noflags");
+    let rec_urm_noflags_abcd = r.template("urm_noflags_abcd");
+    let rec_vfa = r.template("vfa");
+    let rec_vfax = r.template("vfax");
+    let rec_vfconcat = r.template("vfconcat");
+    let rec_vffill_sib32 = r.template("vffillSib32");
+    let rec_vfld = r.template("vfld");
+    let rec_vfld_disp32 = r.template("vfldDisp32");
+    let rec_vfld_disp8 = r.template("vfldDisp8");
+    let rec_vfregfill32 = r.template("vfregfill32");
+    let rec_vfregspill32 = r.template("vfregspill32");
+    let rec_vfrmov = r.template("vfrmov");
+    let rec_vfspill_sib32 = r.template("vfspillSib32");
+    let rec_vfsplit = r.template("vfsplit");
+    let rec_vfst = r.template("vfst");
+    let rec_vfst_disp32 = r.template("vfstDisp32");
+    let rec_vfst_disp8 = r.template("vfstDisp8");
+    let rec_vfurm = r.template("vfurm");
+    let rec_vop = r.template("vop");
+    let rec_vr_ib = r.template("vr_ib");
+    let rec_vrrx = r.template("vrrx");
+    let rec_vrsh = r.template("vrsh");
+    let rec_vur = r.template("vur");
+
+    // Predicates shorthands.
+    let allones_funcaddrs = shared_settings.get_bool("allones_funcaddrs");
+    let is_pic = shared_settings.get_bool("is_pic");
+    let all_ones_funcaddrs_and_not_is_pic = isa_settings
+        .predicate_number(&predicate!(allones_funcaddrs && !is_pic))
+        .unwrap();
+    let not_all_ones_funcaddrs_and_not_is_pic = isa_settings
+        .predicate_number(&predicate!(!allones_funcaddrs && !is_pic))
+        .unwrap();
+    let not_is_pic = isa_settings.predicate_number(&predicate!(!is_pic)).unwrap();
+    let is_pic = isa_settings.predicate_number(&predicate!(is_pic)).unwrap();
+    let use_popcnt = isa_settings.predicate_by_name("use_popcnt");
+    let use_lzcnt = isa_settings.predicate_by_name("use_lzcnt");
+    let use_bmi1 = isa_settings.predicate_by_name("use_bmi1");
+    let use_sse41 = isa_settings.predicate_by_name("use_sse41");
+
+    // Definitions.
+
+    e.enc_i32_i64(iadd, rec_rr.opcodes(&[0x01]));
+    e.enc_i32_i64(isub, rec_rr.opcodes(&[0x29]));
+    e.enc_i32_i64(band, rec_rr.opcodes(&[0x21]));
+    e.enc_i32_i64(bor, rec_rr.opcodes(&[0x09]));
+    e.enc_i32_i64(bxor, rec_rr.opcodes(&[0x31]));
+
+    // x86 has a bitwise not instruction NOT.
+    e.enc_i32_i64(bnot, rec_ur.opcodes(&[0xf7]).rrr(2));
+
+    // Also add a `b1` encodings for the logic instructions.
+    // TODO: Should this be done with 8-bit instructions? It would improve
+    // partial register dependencies.
+    e.enc_both(band.bind(Bool::B1), rec_rr.opcodes(&[0x21]));
+    e.enc_both(bor.bind(Bool::B1), rec_rr.opcodes(&[0x09]));
+    e.enc_both(bxor.bind(Bool::B1), rec_rr.opcodes(&[0x31]));
+
+    e.enc_i32_i64(imul, rec_rrx.opcodes(&[0x0f, 0xaf]));
+    e.enc_i32_i64(x86_sdivmodx, rec_div.opcodes(&[0xf7]).rrr(7));
+    e.enc_i32_i64(x86_udivmodx, rec_div.opcodes(&[0xf7]).rrr(6));
+
+    e.enc_i32_i64(x86_smulx, rec_mulx.opcodes(&[0xf7]).rrr(5));
+    e.enc_i32_i64(x86_umulx, rec_mulx.opcodes(&[0xf7]).rrr(4));
+
+    e.enc_i32_i64(copy, rec_umr.opcodes(&[0x89]));
+    e.enc_both(copy.bind(Bool::B1), rec_umr.opcodes(&[0x89]));
+    e.enc_both(copy.bind(Int::I8), rec_umr.opcodes(&[0x89]));
+    e.enc_both(copy.bind(Int::I16), rec_umr.opcodes(&[0x89]));
+
+    // For x86-64, only define REX forms for now, since we can't describe the
+    // special regunit immediate operands with the current constraint language.
+    for &ty in &[Int::I8, Int::I16, Int::I32] {
+        e.enc32(regmove.bind(ty), rec_rmov.opcodes(&[0x89]));
+        e.enc64(regmove.bind(ty), rec_rmov.opcodes(&[0x89]).rex());
+    }
+    e.enc64(regmove.bind(Int::I64), rec_rmov.opcodes(&[0x89]).rex().w());
+
+    e.enc_both(regmove.bind(Bool::B1), rec_rmov.opcodes(&[0x89]));
+    e.enc_both(regmove.bind(Int::I8), rec_rmov.opcodes(&[0x89]));
+
+    // Immediate instructions with sign-extended 8-bit and 32-bit immediate.
+    for &(inst, rrr) in &[(iadd_imm, 0), (band_imm, 4), (bor_imm, 1), (bxor_imm, 6)] {
+        e.enc_i32_i64(inst, rec_r_ib.opcodes(&[0x83]).rrr(rrr));
+        e.enc_i32_i64(inst, rec_r_id.opcodes(&[0x81]).rrr(rrr));
+    }
+
+    // TODO: band_imm.i64 with an unsigned 32-bit immediate can be encoded as
+    // band_imm.i32. Can even use the single-byte immediate for 0xffff_ffXX masks.
+
+    // Immediate constants.
+    e.enc32(iconst.bind(Int::I32), rec_pu_id.opcodes(&[0xb8]));
+
+    e.enc64(iconst.bind(Int::I32), rec_pu_id.rex().opcodes(&[0xb8]));
+    e.enc64(iconst.bind(Int::I32), rec_pu_id.opcodes(&[0xb8]));
+
+    // The 32-bit immediate movl also zero-extends to 64 bits.
+    let f_unary_imm = formats.get(formats.by_name("UnaryImm"));
+    let is_unsigned_int32 = InstructionPredicate::is_unsigned_int(f_unary_imm, "imm", 32, 0);
+
+    e.enc64_instp(
+        iconst.bind(Int::I64),
+        rec_pu_id.opcodes(&[0xb8]).rex(),
+        is_unsigned_int32.clone(),
+    );
+    e.enc64_instp(
+        iconst.bind(Int::I64),
+        rec_pu_id.opcodes(&[0xb8]),
+        is_unsigned_int32,
+    );
+
+    // Sign-extended 32-bit immediate.
+    e.enc64(
+        iconst.bind(Int::I64),
+        rec_u_id.rex().opcodes(&[0xc7]).rrr(0).w(),
+    );
+
+    // Finally, the 0xb8 opcode takes an 8-byte immediate with a REX.W prefix.
+    e.enc64(iconst.bind(Int::I64), rec_pu_iq.opcodes(&[0xb8]).rex().w());
+
+    // Bool constants.
+    e.enc_both(bconst.bind(Bool::B1), rec_pu_id_bool.opcodes(&[0xb8]));
+
+    // BMI2 shifts take the shift amount in any register, so they come before the
+    // shifts by %cl below.
+    for &(inst, pp) in &[(ishl, 0x66), (ushr, 0xf2), (sshr, 0xf3)] {
+        e.enc32(
+            inst.bind(Int::I32).bind_any(),
+            rec_vrsh.opcodes(&[pp, 0x0f, 0x38, 0xf7]).vex(),
+        );
+        e.enc64(
+            inst.bind(Int::I64).bind_any(),
+            rec_vrsh.opcodes(&[pp, 0x0f, 0x38, 0xf7]).vex().w(),
+        );
+        e.enc64(
+            inst.bind(Int::I32).bind_any(),
+            rec_vrsh.opcodes(&[pp, 0x0f, 0x38, 0xf7]).vex(),
+        );
+    }
+
+    // Shifts and rotates.
+    // Note that the dynamic shift amount is only masked by 5 or 6 bits; the 8-bit
+    // and 16-bit shifts would need explicit masking.
+
+    for &(inst, rrr) in &[(rotl, 0), (rotr, 1), (ishl, 4), (ushr, 5), (sshr, 7)] {
+        // Cannot use enc_i32_i64 for this pattern because instructions require
+        // to bind any.
+        e.enc32(
+            inst.bind(Int::I32).bind_any(),
+            rec_rc.opcodes(&[0xd3]).rrr(rrr),
+        );
+        e.enc64(
+            inst.bind(Int::I64).bind_any(),
+            rec_rc.opcodes(&[0xd3]).rrr(rrr).rex().w(),
+        );
+        e.enc64(
+            inst.bind(Int::I32).bind_any(),
+            rec_rc.opcodes(&[0xd3]).rrr(rrr).rex(),
+        );
+        e.enc64(
+            inst.bind(Int::I32).bind_any(),
+            rec_rc.opcodes(&[0xd3]).rrr(rrr),
+        );
+    }
+
+    for &(inst, rrr) in &[(ishl_imm, 4), (ushr_imm, 5), (sshr_imm, 7)] {
+        e.enc_i32_i64(inst, rec_r_ib.opcodes(&[0xc1]).rrr(rrr));
+    }
+
+    // Population count.
+    e.enc32_isap(
+        popcnt.bind(Int::I32),
+        rec_urm.opcodes(&[0xf3, 0x0f, 0xb8]),
+        use_popcnt,
+    );
+    e.enc64_isap(
+        popcnt.bind(Int::I64),
+        rec_urm.opcodes(&[0xf3, 0x0f, 0xb8]).rex().w(),
+        use_popcnt,
+    );
+    e.enc64_isap(
+        popcnt.bind(Int::I32),
+        rec_urm.opcodes(&[0xf3, 0x0f, 0xb8]).rex(),
+        use_popcnt,
+    );
+    e.enc64_isap(
+        popcnt.bind(Int::I32),
+        rec_urm.opcodes(&[0xf3, 0x0f, 0xb8]),
+        use_popcnt,
+    );
+
+    // Count leading zero bits.
+    e.enc32_isap(
+        clz.bind(Int::I32),
+        rec_urm.opcodes(&[0xf3, 0x0f, 0xbd]),
+        use_lzcnt,
+    );
+    e.enc64_isap(
+        clz.bind(Int::I64),
+        rec_urm.opcodes(&[0xf3, 0x0f, 0xbd]).rex().w(),
+        use_lzcnt,
+    );
+    e.enc64_isap(
+        clz.bind(Int::I32),
+        rec_urm.opcodes(&[0xf3, 0x0f, 0xbd]).rex(),
+        use_lzcnt,
+    );
+    e.enc64_isap(
+        clz.bind(Int::I32),
+        rec_urm.opcodes(&[0xf3, 0x0f, 0xbd]),
+        use_lzcnt,
+    );
+
+    // Count trailing zero bits.
+    e.enc32_isap(
+        ctz.bind(Int::I32),
+        rec_urm.opcodes(&[0xf3, 0x0f, 0xbc]),
+        use_bmi1,
+    );
+    e.enc64_isap(
+        ctz.bind(Int::I64),
+        rec_urm.opcodes(&[0xf3, 0x0f, 0xbc]).rex().w(),
+        use_bmi1,
+    );
+    e.enc64_isap(
+        ctz.bind(Int::I32),
+        rec_urm.opcodes(&[0xf3, 0x0f, 0xbc]).rex(),
+        use_bmi1,
+    );
+    e.enc64_isap(
+        ctz.bind(Int::I32),
+        rec_urm.opcodes(&[0xf3, 0x0f, 0xbc]),
+        use_bmi1,
+    );
+
+    // Other BMI1 and BMI2 instructions.
+    // The `andn(x,y)` instruction computes `~x&y`, while band_not(x,y)` is `x&~y.
+    e.enc_vex_i32_i64(band_not, rec_vrrx.opcodes(&[0x0f, 0x38, 0xf2]));
+    e.enc_vex_i32_i64(x86_blsr, rec_vur.opcodes(&[0x0f, 0x38, 0xf3]).rrr(1));
+    e.enc_vex_i32_i64(x86_blsi, rec_vur.opcodes(&[0x0f, 0x38, 0xf3]).rrr(3));
+    // rorx doesn't touch the flags or tie its output to the input.
+    e.enc_vex_i32_i64(rotr_imm, rec_vr_ib.opcodes(&[0xf2, 0x0f, 0x3a, 0xf0]));
+
+    // Loads and stores.
+    let f_load_complex = formats.get(formats.by_name("LoadComplex"));
+    let is_load_complex_length_two = InstructionPredicate::length_equals(f_load_complex, 2);
+
+    for recipe in &[
+        rec_ld_with_index,
+        rec_ld_with_index_disp8,
+        rec_ld_with_index_disp32,
+    ] {
+        e.enc_i32_i64_instp(
+            load_complex,
+            recipe.opcodes(&[0x8b]),
+            is_load_complex_length_two.clone(),
+        );
+        e.enc_x86_64_instp(
+            uload32_complex,
+            recipe.opcodes(&[0x8b]),
+            is_load_complex_length_two.clone(),
+        );
+
+        e.enc64_instp(
+            sload32_complex,
+            recipe.opcodes(&[0x63]).rex().w(),
+            is_load_complex_length_two.clone(),
+        );
+
+        e.enc_i32_i64_instp(
+            uload16_complex,
+            recipe.opcodes(&[0x0f, 0xb7]),
+            is_load_complex_length_two.clone(),
+        );
+        e.enc_i32_i64_instp(
+            sload16_complex,
+            recipe.opcodes(&[0x0f, 0xbf]),
+            is_load_complex_length_two.clone(),
+        );
+
+        e.enc_i32_i64_instp(
+            uload8_complex,
+            recipe.opcodes(&[0x0f, 0xb6]),
+            is_load_complex_length_two.clone(),
+        );
+
+        e.enc_i32_i64_instp(
+            sload8_complex,
+            recipe.opcodes(&[0x0f, 0xbe]),
+            is_load_complex_length_two.clone(),
+        );
+    }
+
+    let f_store_complex = formats.get(formats.by_name("StoreComplex"));
+    let is_store_complex_length_three = InstructionPredicate::length_equals(f_store_complex, 3);
+
+    for recipe in &[
+        rec_st_with_index,
+        rec_st_with_index_disp8,
+        rec_st_with_index_disp32,
+    ] {
+        e.enc_i32_i64_instp(
+            store_complex,
+            recipe.opcodes(&[0x89]),
+            is_store_complex_length_three.clone(),
+        );
+        e.enc_x86_64_instp(
+            istore32_complex,
+            recipe.opcodes(&[0x89]),
+            is_store_complex_length_three.clone(),
+        );
+        e.enc_both_instp(
+            istore16_complex.bind(Int::I32),
+            recipe.opcodes(&[0x66, 0x89]),
+            is_store_complex_length_three.clone(),
+        );
+        e.enc_x86_64_instp(
+            istore16_complex.bind(Int::I64),
+            recipe.opcodes(&[0x66, 0x89]),
+            is_store_complex_length_three.clone(),
+        );
+    }
+
+    for recipe in &[
+        rec_st_with_index_abcd,
+        rec_st_with_index_disp8_abcd,
+        rec_st_with_index_disp32_abcd,
+    ] {
+        e.enc_both_instp(
+            istore8_complex.bind(Int::I32),
+            recipe.opcodes(&[0x88]),
+            is_store_complex_length_three.clone(),
+        );
+        e.enc_x86_64_instp(
+            istore8_complex.bind(Int::I64),
+            recipe.opcodes(&[0x88]),
+            is_store_complex_length_three.clone(),
+        );
+    }
+
+    for recipe in &[rec_st, rec_st_disp8, rec_st_disp32] {
+        e.enc_i32_i64_ld_st(store, true, recipe.opcodes(&[0x89]));
+        e.enc_x86_64(istore32.bind(Int::I64).bind_any(), recipe.opcodes(&[0x89]));
+        e.enc_i32_i64_ld_st(istore16, false, recipe.opcodes(&[0x66, 0x89]));
+    }
+
+    // Byte stores are more complicated because the registers they can address
+    // depends of the presence of a REX prefix. The st*_abcd recipes fall back to
+    // the corresponding st* recipes when a REX prefix is applied.
+
+    for recipe in &[rec_st_abcd, rec_st_disp8_abcd, rec_st_disp32_abcd] {
+        e.enc_both(istore8.bind(Int::I32).bind_any(), recipe.opcodes(&[0x88]));
+        e.enc_x86_64(istore8.bind(Int::I64).bind_any(), recipe.opcodes(&[0x88]));
+    }
+
+    e.enc_i32_i64(spill, rec_spill_sib32.opcodes(&[0x89]));
+    e.enc_i32_i64(regspill, rec_regspill32.opcodes(&[0x89]));
+
+    // Use a 32-bit write for spilling `b1` to avoid constraining the permitted
+    // registers.
+    // See MIN_SPILL_SLOT_SIZE which makes this safe.
+
+    e.enc_both(spill.bind(Bool::B1), rec_spill_sib32.opcodes(&[0x89]));
+    e.enc_both(regspill.bind(Bool::B1), rec_regspill32.opcodes(&[0x89]));
+
+    // Small integers are spilled the same way, so they don't need byte registers.
+    for &ty in &[Int::I8, Int::I16] {
+        e.enc_both(spill.bind(ty), rec_spill_sib32.opcodes(&[0x89]));
+        e.enc_both(regspill.bind(ty), rec_regspill32.opcodes(&[0x89]));
+    }
+
+    for recipe in &[rec_ld, rec_ld_disp8, rec_ld_disp32] {
+        e.enc_i32_i64_ld_st(load, true, recipe.opcodes(&[0x8b]));
+        e.enc_x86_64(uload32.bind(Int::I64), recipe.opcodes(&[0x8b]));
+        e.enc64(sload32.bind(Int::I64), recipe.opcodes(&[0x63]).rex().w());
+        e.enc_i32_i64_ld_st(uload16, true, recipe.opcodes(&[0x0f, 0xb7]));
+        e.enc_i32_i64_ld_st(sload16, true, recipe.opcodes(&[0x0f, 0xbf]));
+        e.enc_i32_i64_ld_st(uload8, true, recipe.opcodes(&[0x0f, 0xb6]));
+        e.enc_i32_i64_ld_st(sload8, true, recipe.opcodes(&[0x0f, 0xbe]));
+    }
+
+    e.enc_i32_i64(fill, rec_fill_sib32.opcodes(&[0x8b]));
+    e.enc_i32_i64(regfill, rec_regfill32.opcodes(&[0x8b]));
+
+    // Load 32 bits from `b1` spill slots. See `spill.b1` above.
+
+    e.enc_both(fill.bind(Bool::B1), rec_fill_sib32.opcodes(&[0x8b]));
+    e.enc_both(regfill.bind(Bool::B1), rec_regfill32.opcodes(&[0x8b]));
+
+    // Load 32 bits from small integer spill slots too. Only the low bits are used.
+    for &ty in &[Int::I8, Int::I16] {
+        e.enc_both(fill.bind(ty), rec_fill_sib32.opcodes(&[0x8b]));
+        e.enc_both(regfill.bind(ty), rec_regfill32.opcodes(&[0x8b]));
+    }
+
+    // Atomic memory operations.
+    // Aligned loads are atomic, and the x86 memory model makes them acquire loads.
+    // Sequentially consistent stores are legalized to `atomic_xchg`.
+    for recipe in &[rec_ld, rec_ld_disp8, rec_ld_disp32] {
+        e.enc_i32_i64_ld_st(atomic_load, true, recipe.opcodes(&[0x8b]));
+    }
+
+    // xchg with a memory operand is implicitly locked.
+    for recipe in &[rec_rmw, rec_rmw_disp8, rec_rmw_disp32] {
+        e.enc_i32_i64_ld_st(atomic_xchg, true, recipe.opcodes(&[0x87]));
+    }
+
+    // lock xadd
+    for recipe in &[rec_lrmw, rec_lrmw_disp8, rec_lrmw_disp32] {
+        e.enc_i32_i64_ld_st(atomic_add, true, recipe.opcodes(&[0x0f, 0xc1]));
+    }
+
+    // lock cmpxchg
+    e.enc_i32_i64_ld_st(atomic_cas, true, rec_lcas.opcodes(&[0x0f, 0xb1]));
+
+    // mfence
+    e.enc_both(fence, rec_fence.opcodes(&[0x0f, 0xae]).rrr(6));
+
+    // Push and Pop.
+    e.enc32(x86_push.bind(Int::I32), rec_pushq.opcodes(&[0x50]));
+    e.enc_x86_64(x86_push.bind(Int::I64), rec_pushq.opcodes(&[0x50]));
+
+    e.enc32(x86_pop.bind(Int::I32), rec_popq.opcodes(&[0x58]));
+    e.enc_x86_64(x86_pop.bind(Int::I64), rec_popq.opcodes(&[0x58]));
+
+    // Copy Special
+    // For x86-64, only define REX forms for now, since we can't describe the
+    // special regunit immediate operands with the current constraint language.
+    e.enc64(copy_special, rec_copysp.opcodes(&[0x89]).rex().w());
+    e.enc32(copy_special, rec_copysp.opcodes(&[0x89]));
+
+    // Adjust SP down by a dynamic value (or up, with a negative operand).
+    e.enc32(adjust_sp_down.bind(Int::I32), rec_adjustsp.opcodes(&[0x29]));
+    e.enc64(
+        adjust_sp_down.bind(Int::I64),
+        rec_adjustsp.opcodes(&[0x29]).rex().w(),
+    );
+
+    // Adjust SP up by an immediate (or down, with a negative immediate).
+    e.enc32(adjust_sp_up_imm, rec_adjustsp_ib.opcodes(&[0x83]));
+    e.enc32(adjust_sp_up_imm, rec_adjustsp_id.opcodes(&[0x81]));
+    e.enc64(adjust_sp_up_imm, rec_adjustsp_ib.opcodes(&[0x83]).rex().w());
+    e.enc64(adjust_sp_up_imm, rec_adjustsp_id.opcodes(&[0x81]).rex().w());
+
+    // Adjust SP down by an immediate (or up, with a negative immediate).
+    e.enc32(adjust_sp_down_imm, rec_adjustsp_ib.opcodes(&[0x83]).rrr(5));
+    e.enc32(adjust_sp_down_imm, rec_adjustsp_id.opcodes(&[0x81]).rrr(5));
+    e.enc64(
+        adjust_sp_down_imm,
+        rec_adjustsp_ib.opcodes(&[0x83]).rrr(5).rex().w(),
+    );
+    e.enc64(
+        adjust_sp_down_imm,
+        rec_adjustsp_id.opcodes(&[0x81]).rrr(5).rex().w(),
+    );
+
+    // Float loads and stores.
+    e.enc_both(
+        load.bind(Float::F32).bind_any(),
+        rec_fld.opcodes(&[0xf3, 0x0f, 0x10]),
+    );
+    e.enc_both(
+        load.bind(Float::F32).bind_any(),
+        rec_fld_disp8.opcodes(&[0xf3, 0x0f, 0x10]),
+    );
+    e.enc_both(
+        load.bind(Float::F32).bind_any(),
+        rec_fld_disp32.opcodes(&[0xf3, 0x0f, 0x10]),
+    );
+
+    e.enc_both(
+        load_complex.bind(Float::F32),
+        rec_fld_with_index.opcodes(&[0xf3, 0x0f, 0x10]),
+    );
+    e.enc_both(
+        load_complex.bind(Float::F32),
+        rec_fld_with_index_disp8.opcodes(&[0xf3, 0x0f, 0x10]),
+    );
+    e.enc_both(
+        load_complex.bind(Float::F32),
+        rec_fld_with_index_disp32.opcodes(&[0xf3, 0x0f, 0x10]),
+    );
+
+    e.enc_both(
+        load.bind(Float::F64).bind_any(),
+        rec_fld.opcodes(&[0xf2, 0x0f, 0x10]),
+    );
+    e.enc_both(
+        load.bind(Float::F64).bind_any(),
+        rec_fld_disp8.opcodes(&[0xf2, 0x0f, 0x10]),
+    );
+    e.enc_both(
+        load.bind(Float::F64).bind_any(),
+        rec_fld_disp32.opcodes(&[0xf2, 0x0f, 0x10]),
+    );
+
+    e.enc_both(
+        load_complex.bind(Float::F64),
+        rec_fld_with_index.opcodes(&[0xf2, 0x0f, 0x10]),
+    );
+    e.enc_both(
+        load_complex.bind(Float::F64),
+        rec_fld_with_index_disp8.opcodes(&[0xf2, 0x0f, 0x10]),
+    );
+    e.enc_both(
+        load_complex.bind(Float::F64),
+        rec_fld_with_index_disp32.opcodes(&[0xf2, 0x0f, 0x10]),
+    );
+
+    e.enc_both(
+        store.bind(Float::F32).bind_any(),
+        rec_fst.opcodes(&[0xf3, 0x0f, 0x11]),
+    );
+    e.enc_both(
+        store.bind(Float::F32).bind_any(),
+        rec_fst_disp8.opcodes(&[0xf3, 0x0f, 0x11]),
+    );
+    e.enc_both(
+        store.bind(Float::F32).bind_any(),
+        rec_fst_disp32.opcodes(&[0xf3, 0x0f, 0x11]),
+    );
+
+    e.enc_both(
+        store_complex.bind(Float::F32),
+        rec_fst_with_index.opcodes(&[0xf3, 0x0f, 0x11]),
+    );
+    e.enc_both(
+        store_complex.bind(Float::F32),
+        rec_fst_with_index_disp8.opcodes(&[0xf3, 0x0f, 0x11]),
+    );
+    e.enc_both(
+        store_complex.bind(Float::F32),
+        rec_fst_with_index_disp32.opcodes(&[0xf3, 0x0f, 0x11]),
+    );
+
+    e.enc_both(
+        store.bind(Float::F64).bind_any(),
+        rec_fst.opcodes(&[0xf2, 0x0f, 0x11]),
+    );
+    e.enc_both(
+        store.bind(Float::F64).bind_any(),
+        rec_fst_disp8.opcodes(&[0xf2, 0x0f, 0x11]),
+    );
+    e.enc_both(
+        store.bind(Float::F64).bind_any(),
+        rec_fst_disp32.opcodes(&[0xf2, 0x0f, 0x11]),
+    );
+
+    e.enc_both(
+        store_complex.bind(Float::F64),
+        rec_fst_with_index.opcodes(&[0xf2, 0x0f, 0x11]),
+    );
+    e.enc_both(
+        store_complex.bind(Float::F64),
+        rec_fst_with_index_disp8.opcodes(&[0xf2, 0x0f, 0x11]),
+    );
+    e.enc_both(
+        store_complex.bind(Float::F64),
+        rec_fst_with_index_disp32.opcodes(&[0xf2, 0x0f, 0x11]),
+    );
+
+    e.enc_both(
+        fill.bind(Float::F32),
+        rec_ffill_sib32.opcodes(&[0xf3, 0x0f, 0x10]),
+    );
+    e.enc_both(
+        regfill.bind(Float::F32),
+        rec_fregfill32.opcodes(&[0xf3, 0x0f, 0x10]),
+    );
+    e.enc_both(
+        fill.bind(Float::F64),
+        rec_ffill_sib32.opcodes(&[0xf2, 0x0f, 0x10]),
+    );
+    e.enc_both(
+        regfill.bind(Float::F64),
+        rec_fregfill32.opcodes(&[0xf2, 0x0f, 0x10]),
+    );
+
+    e.enc_both(
+        spill.bind(Float::F32),
+        rec_fspill_sib32.opcodes(&[0xf3, 0x0f, 0x11]),
+    );
+    e.enc_both(
+        regspill.bind(Float::F32),
+        rec_fregspill32.opcodes(&[0xf3, 0x0f, 0x11]),
+    );
+    e.enc_both(
+        spill.bind(Float::F64),
+        rec_fspill_sib32.opcodes(&[0xf2, 0x0f, 0x11]),
+    );
+    e.enc_both(
+        regspill.bind(Float::F64),
+        rec_fregspill32.opcodes(&[0xf2, 0x0f, 0x11]),
+    );
+
+    // Function addresses.
+
+    // Non-PIC, all-ones funcaddresses.
+    e.enc32_isap(
+        func_addr.bind(Int::I32),
+        rec_fnaddr4.opcodes(&[0xb8]),
+        not_all_ones_funcaddrs_and_not_is_pic,
+    );
+    e.enc64_isap(
+        func_addr.bind(Int::I64),
+        rec_fnaddr8.opcodes(&[0xb8]).rex().w(),
+        not_all_ones_funcaddrs_and_not_is_pic,
+    );
+
+    // Non-PIC, all-zeros funcaddresses.
+    e.enc32_isap(
+        func_addr.bind(Int::I32),
+        rec_allones_fnaddr4.opcodes(&[0xb8]),
+        all_ones_funcaddrs_and_not_is_pic,
+    );
+    e.enc64_isap(
+        func_addr.bind(Int::I64),
+        rec_allones_fnaddr8.opcodes(&[0xb8]).rex().w(),
+        all_ones_funcaddrs_and_not_is_pic,
+    );
+
+    // 64-bit, colocated, both PIC and non-PIC. Use the lea instruction's pc-relative field.
+    let f_func_addr = formats.get(formats.by_name("FuncAddr"));
+    let is_colocated_func = InstructionPredicate::is_colocated_func(f_func_addr, "func_ref");
+    e.enc64_instp(
+        func_addr.bind(Int::I64),
+        rec_pcrel_fnaddr8.opcodes(&[0x8d]).rex().w(),
+        is_colocated_func,
+    );
+
+    // 64-bit, non-colocated, PIC.
+    e.enc64_isap(
+        func_addr.bind(Int::I64),
+        rec_got_fnaddr8.opcodes(&[0x8b]).rex().w(),
+        is_pic,
+    );
+
+    // Global addresses.
+
+    // Non-PIC.
+    e.enc32_isap(
+        globalsym_addr.bind(Int::I32),
+        rec_gvaddr4.opcodes(&[0xb8]),
+        not_is_pic,
+    );
+    e.enc64_isap(
+        globalsym_addr.bind(Int::I64),
+        rec_gvaddr8.opcodes(&[0xb8]).rex().w(),
+        not_is_pic,
+    );
+
+    // PIC, colocated.
+    let f_unary_global_value = formats.get(formats.by_name("UnaryGlobalValue"));
+    let is_colocated_data =
+        InstructionPredicate::is_colocated_data(f_unary_global_value, "global_value");
+    e.enc64_func(
+        globalsym_addr.bind(Int::I64),
+        rec_pcrel_gvaddr8.opcodes(&[0x8d]).rex().w(),
+        |encoding| {
+            encoding
+                .isa_predicate(is_pic)
+                .inst_predicate(is_colocated_data)
+        },
+    );
+
+    // PIC, non-colocated.
+    e.enc64_isap(
+        globalsym_addr.bind(Int::I64),
+        rec_got_gvaddr8.opcodes(&[0x8b]).rex().w(),
+        is_pic,
+    );
+
+    // Stack addresses.
+    //
+    // TODO: Add encoding rules for stack_load and stack_store, so that they
+    // don't get legalized to stack_addr + load/store.
+    e.enc32(stack_addr.bind(Int::I32), rec_spaddr4_id.opcodes(&[0x8d]));
+    e.enc64(
+        stack_addr.bind(Int::I64),
+        rec_spaddr8_id.opcodes(&[0x8d]).rex().w(),
+    );
+
+    // Call/return
+
+    // 32-bit, both PIC and non-PIC.
+    e.enc32(call, rec_call_id.opcodes(&[0xe8]));
+
+    // 64-bit, colocated, both PIC and non-PIC. Use the call instruction's pc-relative field.
+    let f_call = formats.get(formats.by_name("Call"));
+    let is_colocated_func = InstructionPredicate::is_colocated_func(f_call, "func_ref");
+    e.enc64_instp(call, rec_call_id.opcodes(&[0xe8]), is_colocated_func);
+
+    // 64-bit, non-colocated, PIC. There is no 64-bit non-colocated non-PIC version, since non-PIC
+    // is currently using the large model, which requires calls be lowered to
+    // func_addr+call_indirect.
+    e.enc64_isap(call, rec_call_plt_id.opcodes(&[0xe8]), is_pic);
+
+    e.enc32(
+        call_indirect.bind(Int::I32),
+        rec_call_r.opcodes(&[0xff]).rrr(2),
+    );
+    e.enc64(
+        call_indirect.bind(Int::I64),
+        rec_call_r.opcodes(&[0xff]).rrr(2).rex(),
+    );
+    e.enc64(
+        call_indirect.bind(Int::I64),
+        rec_call_r.opcodes(&[0xff]).rrr(2),
+    );
+
+    e.enc32(x_return, rec_ret.opcodes(&[0xc3]));
+    e.enc64(x_return, rec_ret.opcodes(&[0xc3]));
+
+    // Branches.
+    e.enc_both(jump, rec_jmpb.opcodes(&[0xeb]));
+    e.enc_both(jump, rec_jmpd.opcodes(&[0xe9]));
+
+    e.enc_both(brif, rec_brib.opcodes(&[0x70]));
+    e.enc_both(brif, rec_brid.opcodes(&[0x0f, 0x80]));
+
+    // Not all float condition codes are legal, see `supported_floatccs`.
+    e.enc_both(brff, rec_brfb.opcodes(&[0x70]));
+    e.enc_both(brff, rec_brfd.opcodes(&[0x0f, 0x80]));
+
+    // Note that the tjccd opcode will be prefixed with 0x0f.
+    e.enc_i32_i64(brz, rec_tjccb.opcodes(&[0x74]));
+    e.enc_i32_i64(brz, rec_tjccd.opcodes(&[0x84]));
+    e.enc_i32_i64(brnz, rec_tjccb.opcodes(&[0x75]));
+    e.enc_i32_i64(brnz, rec_tjccd.opcodes(&[0x85]));
+
+    // Branch on a b1 value in a register only looks at the low 8 bits. See also
+    // bint encodings below.
+    //
+    // Start with the worst-case encoding for X86_32 only. The register allocator
+    // can't handle a branch with an ABCD-constrained operand.
+    e.enc32(brz.bind(Bool::B1), rec_t8jccd_long.opcodes(&[0x84]));
+    e.enc32(brnz.bind(Bool::B1), rec_t8jccd_long.opcodes(&[0x85]));
+
+    e.enc_both(brz.bind(Bool::B1), rec_t8jccb_abcd.opcodes(&[0x74]));
+    e.enc_both(brz.bind(Bool::B1), rec_t8jccd_abcd.opcodes(&[0x84]));
+    e.enc_both(brnz.bind(Bool::B1), rec_t8jccb_abcd.opcodes(&[0x75]));
+    e.enc_both(brnz.bind(Bool::B1), rec_t8jccd_abcd.opcodes(&[0x85]));
+
+    // Trap as ud2
+    e.enc32(trap, rec_trap.opcodes(&[0x0f, 0x0b]));
+    e.enc64(trap, rec_trap.opcodes(&[0x0f, 0x0b]));
+
+    // Using a standard EncodingRecipe, not the template recipes.
+    e.enc32_rec(trapif, rec_trapif, 0);
+    e.enc64_rec(trapif, rec_trapif, 0);
+    e.enc32_rec(trapff, rec_trapff, 0);
+    e.enc64_rec(trapff, rec_trapff, 0);
+
+    // Comparisons
+    e.enc_i32_i64(icmp, rec_icscc.opcodes(&[0x39]));
+    e.enc_i32_i64(icmp_imm, rec_icscc_ib.opcodes(&[0x83]).rrr(7));
+    e.enc_i32_i64(icmp_imm, rec_icscc_id.opcodes(&[0x81]).rrr(7));
+    e.enc_i32_i64(ifcmp, rec_rcmp.opcodes(&[0x39]));
+    e.enc_i32_i64(ifcmp_imm, rec_rcmp_ib.opcodes(&[0x83]).rrr(7));
+    e.enc_i32_i64(ifcmp_imm, rec_rcmp_id.opcodes(&[0x81]).rrr(7));
+    // TODO: We could special-case ifcmp_imm(x, 0) to TEST(x, x).
+
+    e.enc32(ifcmp_sp.bind(Int::I32), rec_rcmp_sp.opcodes(&[0x39]));
+    e.enc64(
+        ifcmp_sp.bind(Int::I64),
+        rec_rcmp_sp.opcodes(&[0x39]).rex().w(),
+    );
+
+    // Convert flags to bool.
+    // This encodes `b1` as an 8-bit low register with the value 0 or 1.
+    e.enc_both(trueif, rec_seti_abcd.opcodes(&[0x0f, 0x90]));
+    e.enc_both(trueff, rec_setf_abcd.opcodes(&[0x0f, 0x90]));
+
+    // Conditional move (a.k.a integer select).
+    e.enc_i32_i64(selectif, rec_cmov.opcodes(&[0x0f, 0x40]));
+
+    // Bit scan forwards and reverse
+    e.enc_i32_i64(x86_bsf, rec_bsf_and_bsr.opcodes(&[0x0f, 0xbc]));
+    e.enc_i32_i64(x86_bsr, rec_bsf_and_bsr.opcodes(&[0x0f, 0xbd]));
+
+    // Convert bool to int.
+    //
+    // This assumes that b1 is represented as an 8-bit low register with the value 0
+    // or 1.
+    //
+    // Encode movzbq as movzbl, because it's equivalent and shorter.
+    e.enc32(
+        bint.bind(Int::I32).bind(Bool::B1),
+        rec_urm_noflags_abcd.opcodes(&[0x0f, 0xb6]),
+    );
+
+    e.enc64(
+        bint.bind(Int::I64).bind(Bool::B1),
+        rec_urm_noflags.opcodes(&[0x0f, 0xb6]).rex(),
+    );
+    e.enc64(
+        bint.bind(Int::I64).bind(Bool::B1),
+        rec_urm_noflags_abcd.opcodes(&[0x0f, 0xb6]),
+    );
+    e.enc64(
+        bint.bind(Int::I32).bind(Bool::B1),
+        rec_urm_noflags.opcodes(&[0x0f, 0xb6]).rex(),
+    );
+    e.enc64(
+        bint.bind(Int::I32).bind(Bool::B1),
+        rec_urm_noflags_abcd.opcodes(&[0x0f, 0xb6]),
+    );
+
+    // Numerical conversions.
+
+    // Reducing an integer is a no-op.
+    e.enc32_rec(ireduce.bind(Int::I8).bind(Int::I32), rec_null, 0);
+    e.enc32_rec(ireduce.bind(Int::I16).bind(Int::I32), rec_null, 0);
+
+    e.enc64_rec(ireduce.bind(Int::I8).bind(Int::I32), rec_null, 0);
+    e.enc64_rec(ireduce.bind(Int::I16).bind(Int::I32), rec_null, 0);
+    e.enc64_rec(ireduce.bind(Int::I8).bind(Int::I64), rec_null, 0);
+    e.enc64_rec(ireduce.bind(Int::I16).bind(Int::I64), rec_null, 0);
+    e.enc64_rec(ireduce.bind(Int::I32).bind(Int::I64), rec_null, 0);
+
+    // TODO: Add encodings for cbw, cwde, cdqe, which are sign-extending
+    // instructions for %al/%ax/%eax to %ax/%eax/%rax.
+
+    // movsbl
+    e.enc32(
+        sextend.bind(Int::I32).bind(Int::I8),
+        rec_urm_noflags_abcd.opcodes(&[0x0f, 0xbe]),
+    );
+    e.enc64(
+        sextend.bind(Int::I32).bind(Int::I8),
+        rec_urm_noflags.opcodes(&[0x0f, 0xbe]).rex(),
+    );
+    e.enc64(
+        sextend.bind(Int::I32).bind(Int::I8),
+        rec_urm_noflags_abcd.opcodes(&[0x0f, 0xbe]),
+    );
+
+    // movswl
+    e.enc32(
+        sextend.bind(Int::I32).bind(Int::I16),
+        rec_urm_noflags.opcodes(&[0x0f, 0xbf]),
+    );
+    e.enc64(
+        sextend.bind(Int::I32).bind(Int::I16),
+        rec_urm_noflags.opcodes(&[0x0f, 0xbf]).rex(),
+    );
+    e.enc64(
+        sextend.bind(Int::I32).bind(Int::I16),
+        rec_urm_noflags.opcodes(&[0x0f, 0xbf]),
+    );
+
+    // movsbq
+    e.enc64(
+        sextend.bind(Int::I64).bind(Int::I8),
+        rec_urm_noflags.opcodes(&[0x0f, 0xbe]).rex().w(),
+    );
+
+    // movswq
+    e.enc64(
+        sextend.bind(Int::I64).bind(Int::I16),
+        rec_urm_noflags.opcodes(&[0x0f, 0xbf]).rex().w(),
+    );
+
+    // movslq
+    e.enc64(
+        sextend.bind(Int::I64).bind(Int::I32),
+        rec_urm_noflags.opcodes(&[0x63]).rex().w(),
+    );
+
+    // movzbl
+    e.enc32(
+        uextend.bind(Int::I32).bind(Int::I8),
+        rec_urm_noflags_abcd.opcodes(&[0x0f, 0xb6]),
+    );
+    e.enc64(
+        uextend.bind(Int::I32).bind(Int::I8),
+        rec_urm_noflags.opcodes(&[0x0f, 0xb6]).rex(),
+    );
+    e.enc64(
+        uextend.bind(Int::I32).bind(Int::I8),
+        rec_urm_noflags_abcd.opcodes(&[0x0f, 0xb6]),
+    );
+
+    // movzwl
+    e.enc32(
+        uextend.bind(Int::I32).bind(Int::I16),
+        rec_urm_noflags.opcodes(&[0x0f, 0xb7]),
+    );
+    e.enc64(
+        uextend.bind(Int::I32).bind(Int::I16),
+        rec_urm_noflags.opcodes(&[0x0f, 0xb7]).rex(),
+    );
+    e.enc64(
+        uextend.bind(Int::I32).bind(Int::I16),
+        rec_urm_noflags.opcodes(&[0x0f, 0xb7]),
+    );
+
+    // movzbq, encoded as movzbl because it's equivalent and shorter.
+    e.enc64(
+        uextend.bind(Int::I64).bind(Int::I8),
+        rec_urm_noflags.opcodes(&[0x0f, 0xb6]).rex(),
+    );
+    e.enc64(
+        uextend.bind(Int::I64).bind(Int::I8),
+        rec_urm_noflags.opcodes(&[0x0f, 0xb6]),
+    );
+
+    // movzwq, encoded as movzwl because it's equivalent and shorter
+    e.enc64(
+        uextend.bind(Int::I64).bind(Int::I16),
+        rec_urm_noflags.opcodes(&[0x0f, 0xb7]).rex(),
+    );
+    e.enc64(
+        uextend.bind(Int::I64).bind(Int::I16),
+        rec_urm_noflags.opcodes(&[0x0f, 0xb7]),
+    );
+
+    // A 32-bit register copy clears the high 32 bits.
+    e.enc64(
+        uextend.bind(Int::I64).bind(Int::I32),
+        rec_umr.opcodes(&[0x89]).rex(),
+    );
+    e.enc64(
+        uextend.bind(Int::I64).bind(Int::I32),
+        rec_umr.opcodes(&[0x89]),
+    );
+
+    // Floating point
+
+    // Floating-point constants equal to 0.0 can be encoded using either `xorps` or `xorpd`, for
+    // 32-bit and 64-bit floats respectively.
+    let f_unary_ieee32 = formats.get(formats.by_name("UnaryIeee32"));
+    let is_zero_32_bit_float = InstructionPredicate::is_zero_32_bit_float(f_unary_ieee32, "imm");
+    e.enc32_instp(
+        f32const,
+        rec_f32imm_z.opcodes(&[0x0f, 0x57]),
+        is_zero_32_bit_float.clone(),
+    );
+
+    let f_unary_ieee64 = formats.get(formats.by_name("UnaryIeee64"));
+    let is_zero_64_bit_float = InstructionPredicate::is_zero_64_bit_float(f_unary_ieee64, "imm");
+    e.enc32_instp(
+        f64const,
+        rec_f64imm_z.opcodes(&[0x66, 0x0f, 0x57]),
+        is_zero_64_bit_float.clone(),
+    );
+
+    e.enc_x86_64_instp(
+        f32const,
+        rec_f32imm_z.opcodes(&[0x0f, 0x57]),
+        is_zero_32_bit_float,
+    );
+    e.enc_x86_64_instp(
+        f64const,
+        rec_f64imm_z.opcodes(&[0x66, 0x0f, 0x57]),
+        is_zero_64_bit_float,
+    );
+
+    // movd
+    e.enc_both(
+        bitcast.bind(Float::F32).bind(Int::I32),
+        rec_frurm.opcodes(&[0x66, 0x0f, 0x6e]),
+    );
+    e.enc_both(
+        bitcast.bind(Int::I32).bind(Float::F32),
+        rec_rfumr.opcodes(&[0x66, 0x0f, 0x7e]),
+    );
+
+    // movq
+    e.enc64(
+        bitcast.bind(Float::F64).bind(Int::I64),
+        rec_frurm.opcodes(&[0x66, 0x0f, 0x6e]).rex().w(),
+    );
+    e.enc64(
+        bitcast.bind(Int::I64).bind(Float::F64),
+        rec_rfumr.opcodes(&[0x66, 0x0f, 0x7e]).rex().w(),
+    );
+
+    // movaps
+    e.enc_both(copy.bind(Float::F32), rec_furm.opcodes(&[0x0f, 0x28]));
+    e.enc_both(copy.bind(Float::F64), rec_furm.opcodes(&[0x0f, 0x28]));
+
+    // For x86-64, only define REX forms for now, since we can't describe the special regunit
+    // immediate operands with the current constraint language.
+    e.enc32(regmove.bind(Float::F32), rec_frmov.opcodes(&[0x0f, 0x28]));
+    e.enc64(
+        regmove.bind(Float::F32),
+        rec_frmov.opcodes(&[0x0f, 0x28]).rex(),
+    );
+
+    // For x86-64, only define REX forms for now, since we can't describe the special regunit
+    // immediate operands with the current constraint language.
+    e.enc32(regmove.bind(Float::F64), rec_frmov.opcodes(&[0x0f, 0x28]));
+    e.enc64(
+        regmove.bind(Float::F64),
+        rec_frmov.opcodes(&[0x0f, 0x28]).rex(),
+    );
+
+    // cvtsi2ss
+    e.enc_i32_i64(
+        &fcvt_from_sint.bind(Float::F32),
+        rec_frurm.opcodes(&[0xf3, 0x0f, 0x2a]),
+    );
+
+    // cvtsi2sd
+    e.enc_i32_i64(
+        &fcvt_from_sint.bind(Float::F64),
+        rec_frurm.opcodes(&[0xf2, 0x0f, 0x2a]),
+    );
+
+    // cvtss2sd
+    e.enc_both(
+        fpromote.bind(Float::F64).bind(Float::F32),
+        rec_furm.opcodes(&[0xf3, 0x0f, 0x5a]),
+    );
+
+    // cvtsd2ss
+    e.enc_both(
+        fdemote.bind(Float::F32).bind(Float::F64),
+        rec_furm.opcodes(&[0xf2, 0x0f, 0x5a]),
+    );
+
+    // cvttss2si
+    e.enc_both(
+        x86_cvtt2si.bind(Int::I32).bind(Float::F32),
+        rec_rfurm.opcodes(&[0xf3, 0x0f, 0x2c]),
+    );
+    e.enc64(
+        x86_cvtt2si.bind(Int::I64).bind(Float::F32),
+        rec_rfurm.opcodes(&[0xf3, 0x0f, 0x2c]).rex().w(),
+    );
+
+    // cvttsd2si
+    e.enc_both(
+        x86_cvtt2si.bind(Int::I32).bind(Float::F64),
+        rec_rfurm.opcodes(&[0xf2, 0x0f, 0x2c]),
+    );
+    e.enc64(
+        x86_cvtt2si.bind(Int::I64).bind(Float::F64),
+        rec_rfurm.opcodes(&[0xf2, 0x0f, 0x2c]).rex().w(),
+    );
+
+    // Exact square roots.
+    e.enc_both(sqrt.bind(Float::F32), rec_furm.opcodes(&[0xf3, 0x0f, 0x51]));
+    e.enc_both(sqrt.bind(Float::F64), rec_furm.opcodes(&[0xf2, 0x0f, 0x51]));
+
+    // Rounding. The recipe looks at the opcode to pick an immediate, and is
+    // only available with SSE 4.1.
+    for inst in &[nearest, floor, ceil, trunc] {
+        e.enc_both(
+            inst.bind(Float::F32),
+            rec_furmi_rnd.opcodes(&[0x66, 0x0f, 0x3a, 0x0a]),
+        );
+        e.enc_both(
+            inst.bind(Float::F64),
+            rec_furmi_rnd.opcodes(&[0x66, 0x0f, 0x3a, 0x0b]),
+        );
+    }
+
+    // The three-operand AVX forms come first, so they are preferred over the
+    // two-operand SSE forms below when AVX is available.
+    let f32x4 = LaneType::from(Float::F32).by(4);
+    let f64x2 = LaneType::from(Float::F64).by(2);
+    let f32x8 = LaneType::from(Float::F32).by(8);
+    let f64x4 = LaneType::from(Float::F64).by(4);
+
+    for &(inst, opc) in &[
+        (fadd, 0x58),
+        (fsub, 0x5c),
+        (fmul, 0x59),
+        (fdiv, 0x5e),
+        (x86_fmin, 0x5d),
+        (x86_fmax, 0x5f),
+    ] {
+        e.enc_vex(inst.bind(Float::F32), rec_vfa.opcodes(&[0xf3, 0x0f, opc]));
+        e.enc_vex(inst.bind(Float::F64), rec_vfa.opcodes(&[0xf2, 0x0f, opc]));
+        e.enc_vex(inst.bind(f32x4.clone()), rec_vfa.opcodes(&[0x0f, opc]));
+        e.enc_vex(
+            inst.bind(f64x2.clone()),
+            rec_vfa.opcodes(&[0x66, 0x0f, opc]),
+        );
+        e.enc_vex(inst.bind(f32x8.clone()), rec_vfa.opcodes(&[0x0f, opc]).l());
+        e.enc_vex(
+            inst.bind(f64x4.clone()),
+            rec_vfa.opcodes(&[0x66, 0x0f, opc]).l(),
+        );
+    }
+
+    // vandps, vorps, vxorps and vandnps. AVX doesn't have 256-bit integer
+    // instructions, but the float ones work for any vector type.
+    let mut avx_bitwise_types: Vec<ValueType> = vec![
+        LaneType::from(Float::F32).into(),
+        LaneType::from(Float::F64).into(),
+    ];
+    avx_bitwise_types.extend(simd_floats());
+    for ty in &avx_bitwise_types {
+        e.enc_vex(band.bind(ty.clone()), rec_vfa.opcodes(&[0x0f, 0x54]));
+        e.enc_vex(bor.bind(ty.clone()), rec_vfa.opcodes(&[0x0f, 0x56]));
+        e.enc_vex(bxor.bind(ty.clone()), rec_vfa.opcodes(&[0x0f, 0x57]));
+        e.enc_vex(band_not.bind(ty.clone()), rec_vfax.opcodes(&[0x0f, 0x55]));
+    }
+
+    for ty in &simd256_types() {
+        e.enc_vex(band.bind(ty.clone()), rec_vfa.opcodes(&[0x0f, 0x54]).l());
+        e.enc_vex(bor.bind(ty.clone()), rec_vfa.opcodes(&[0x0f, 0x56]).l());
+        e.enc_vex(bxor.bind(ty.clone()), rec_vfa.opcodes(&[0x0f, 0x57]).l());
+        e.enc_vex(
+            band_not.bind(ty.clone()),
+            rec_vfax.opcodes(&[0x0f, 0x55]).l(),
+        );
+    }
+
+    // Binary arithmetic ops.
+    for &(inst, opc) in &[
+        (fadd, 0x58),
+        (fsub, 0x5c),
+        (fmul, 0x59),
+        (fdiv, 0x5e),
+        (x86_fmin, 0x5d),
+        (x86_fmax, 0x5f),
+    ] {
+        e.enc_both(inst.bind(Float::F32), rec_fa.opcodes(&[0xf3, 0x0f, opc]));
+        e.enc_both(inst.bind(Float::F64), rec_fa.opcodes(&[0xf2, 0x0f, opc]));
+    }
+
+    // Binary bitwise ops.
+    for &(inst, opc) in &[(band, 0x54), (bor, 0x56), (bxor, 0x57)] {
+        e.enc_both(inst.bind(Float::F32), rec_fa.opcodes(&[0x0f, opc]));
+        e.enc_both(inst.bind(Float::F64), rec_fa.opcodes(&[0x0f, opc]));
+    }
+
+    // The `andnps(x,y)` instruction computes `~x&y`, while band_not(x,y)` is `x&~y.
+    e.enc_both(band_not.bind(Float::F32), rec_fax.opcodes(&[0x0f, 0x55]));
+    e.enc_both(band_not.bind(Float::F64), rec_fax.opcodes(&[0x0f, 0x55]));
+
+    // Comparisons.
+    //
+    // This only covers the condition codes in `supported_floatccs`, the rest are
+    // handled by legalization patterns.
+    e.enc_both(fcmp.bind(Float::F32), rec_fcscc.opcodes(&[0x0f, 0x2e]));
+    e.enc_both(
+        fcmp.bind(Float::F64),
+        rec_fcscc.opcodes(&[0x66, 0x0f, 0x2e]),
+    );
+
+    e.enc_both(ffcmp.bind(Float::F32), rec_fcmp.opcodes(&[0x0f, 0x2e]));
+    e.enc_both(
+        ffcmp.bind(Float::F64),
+        rec_fcmp.opcodes(&[0x66, 0x0f, 0x2e]),
+    );
+
+    // SIMD.
+    //
+    // The vector types with 128 bits are held in the XMM registers. The encodings
+    // below only need SSE2, which is part of the x86-64 baseline, except for the
+    // ones marked with the `use_sse41` predicate.
+    let simd_ints = simd_ints();
+    let simd_floats = simd_floats();
+    let simd_types = simd_types();
+    let simd_ints_and_floats: Vec<ValueType> =
+        simd_ints.iter().chain(&simd_floats).cloned().collect();
+    let simd_ints_and_bools: Vec<ValueType> =
+        simd_ints.iter().chain(&simd_bools()).cloned().collect();
+    let i8x16 = LaneType::from(Int::I8).by(16);
+    let i16x8 = LaneType::from(Int::I16).by(8);
+    let i32x4 = LaneType::from(Int::I32).by(4);
+    let i64x2 = LaneType::from(Int::I64).by(2);
+
+    for ty in &simd_types {
+        // movaps
+        e.enc_both(copy.bind(ty.clone()), rec_furm.opcodes(&[0x0f, 0x28]));
+
+        // For x86-64, only define REX forms for now, since we can't describe the special regunit
+        // immediate operands with the current constraint language.
+        e.enc32(regmove.bind(ty.clone()), rec_frmov.opcodes(&[0x0f, 0x28]));
+        e.enc64(
+            regmove.bind(ty.clone()),
+            rec_frmov.opcodes(&[0x0f, 0x28]).rex(),
+        );
+
+        // movups, since spill slots are not aligned to 16 bytes.
+        e.enc_both(
+            fill.bind(ty.clone()),
+            rec_ffill_sib32.opcodes(&[0x0f, 0x10]),
+        );
+        e.enc_both(
+            regfill.bind(ty.clone()),
+            rec_fregfill32.opcodes(&[0x0f, 0x10]),
+        );
+        e.enc_both(
+            spill.bind(ty.clone()),
+            rec_fspill_sib32.opcodes(&[0x0f, 0x11]),
+        );
+        e.enc_both(
+            regspill.bind(ty.clone()),
+            rec_fregspill32.opcodes(&[0x0f, 0x11]),
+        );
+    }
+
+    // Vector loads and stores use movups, so they don't require any alignment.
+    for ty in &simd_ints_and_floats {
+        for recipe in &[&rec_fld, &rec_fld_disp8, &rec_fld_disp32] {
+            e.enc_both(
+                load.bind(ty.clone()).bind_any(),
+                recipe.opcodes(&[0x0f, 0x10]),
+            );
+        }
+        for recipe in &[
+            &rec_fld_with_index,
+            &rec_fld_with_index_disp8,
+            &rec_fld_with_index_disp32,
+        ] {
+            e.enc_both(load_complex.bind(ty.clone()), recipe.opcodes(&[0x0f, 0x10]));
+        }
+        for recipe in &[&rec_fst, &rec_fst_disp8, &rec_fst_disp32] {
+            e.enc_both(
+                store.bind(ty.clone()).bind_any(),
+                recipe.opcodes(&[0x0f, 0x11]),
+            );
+        }
+        for recipe in &[
+            &rec_fst_with_index,
+            &rec_fst_with_index_disp8,
+            &rec_fst_with_index_disp32,
+        ] {
+            e.enc_both(
+                store_complex.bind(ty.clone()),
+                recipe.opcodes(&[0x0f, 0x11]),
+            );
+        }
+    }
+
+    // Reinterpreting the bits of a vector is a no-op.
+    for to_ty in &simd_ints_and_floats {
+        for from_ty in &simd_ints_and_floats {
+            if to_ty != from_ty {
+                let inst = bitcast.bind(to_ty.clone()).bind(from_ty.clone());
+                e.enc32_rec(inst.clone(), rec_null_fpr, 0);
+                e.enc64_rec(inst, rec_null_fpr, 0);
+            }
+        }
+    }
+
+    // Integer arithmetic: padd and psub.
+    for &(ty, add, sub) in &[
+        (&i8x16, 0xfc, 0xf8),
+        (&i16x8, 0xfd, 0xf9),
+        (&i32x4, 0xfe, 0xfa),
+        (&i64x2, 0xd4, 0xfb),
+    ] {
+        e.enc_both(iadd.bind(ty.clone()), rec_fa.opcodes(&[0x66, 0x0f, add]));
+        e.enc_both(isub.bind(ty.clone()), rec_fa.opcodes(&[0x66, 0x0f, sub]));
+    }
+
+    // pmullw
+    e.enc_both(
+        imul.bind(i16x8.clone()),
+        rec_fa.opcodes(&[0x66, 0x0f, 0xd5]),
+    );
+
+    // pmulld
+    e.enc32_isap(
+        imul.bind(i32x4.clone()),
+        rec_fa.opcodes(&[0x66, 0x0f, 0x38, 0x40]),
+        use_sse41,
+    );
+    e.enc64_isap(
+        imul.bind(i32x4.clone()),
+        rec_fa.opcodes(&[0x66, 0x0f, 0x38, 0x40]).rex(),
+        use_sse41,
+    );
+    e.enc64_isap(
+        imul.bind(i32x4.clone()),
+        rec_fa.opcodes(&[0x66, 0x0f, 0x38, 0x40]),
+        use_sse41,
+    );
+
+    // Bitwise ops: pand, por, pxor and pandn.
+    for ty in &simd_ints_and_bools {
+        e.enc_both(band.bind(ty.clone()), rec_fa.opcodes(&[0x66, 0x0f, 0xdb]));
+        e.enc_both(bor.bind(ty.clone()), rec_fa.opcodes(&[0x66, 0x0f, 0xeb]));
+        e.enc_both(bxor.bind(ty.clone()), rec_fa.opcodes(&[0x66, 0x0f, 0xef]));
+        e.enc_both(
+            band_not.bind(ty.clone()),
+            rec_fax.opcodes(&[0x66, 0x0f, 0xdf]),
+        );
+    }
+
+    for ty in &simd_floats {
+        e.enc_both(band.bind(ty.clone()), rec_fa.opcodes(&[0x0f, 0x54]));
+        e.enc_both(bor.bind(ty.clone()), rec_fa.opcodes(&[0x0f, 0x56]));
+        e.enc_both(bxor.bind(ty.clone()), rec_fa.opcodes(&[0x0f, 0x57]));
+        e.enc_both(band_not.bind(ty.clone()), rec_fax.opcodes(&[0x0f, 0x55]));
+    }
+
+    // Packed float arithmetic.
+    for &(inst, opc) in &[
+        (fadd, 0x58),
+        (fsub, 0x5c),
+        (fmul, 0x59),
+        (fdiv, 0x5e),
+        (x86_fmin, 0x5d),
+        (x86_fmax, 0x5f),
+    ] {
+        e.enc_both(inst.bind(f32x4.clone()), rec_fa.opcodes(&[0x0f, opc]));
+        e.enc_both(inst.bind(f64x2.clone()), rec_fa.opcodes(&[0x66, 0x0f, opc]));
+    }
+
+    e.enc_both(sqrt.bind(f32x4.clone()), rec_furm.opcodes(&[0x0f, 0x51]));
+    e.enc_both(
+        sqrt.bind(f64x2.clone()),
+        rec_furm.opcodes(&[0x66, 0x0f, 0x51]),
+    );
+
+    // Blends with the mask in %xmm0: pblendvb, blendvps and blendvpd.
+    for ty in &simd_ints_and_bools {
+        e.enc_both(
+            vselect.bind(ty.clone()),
+            rec_blend.opcodes(&[0x66, 0x0f, 0x38, 0x10]),
+        );
+    }
+    e.enc_both(
+        vselect.bind(f32x4.clone()),
+        rec_blend.opcodes(&[0x66, 0x0f, 0x38, 0x14]),
+    );
+    e.enc_both(
+        vselect.bind(f64x2.clone()),
+        rec_blend.opcodes(&[0x66, 0x0f, 0x38, 0x15]),
+    );
+
+    // Moving a scalar into the lowest lane: movd and movq. A float is already in
+    // the lowest lane of its XMM register.
+    for ty in &[&i8x16, &i16x8, &i32x4] {
+        e.enc_both(
+            x86_scalar_to_vector.bind((*ty).clone()),
+            rec_frurm.opcodes(&[0x66, 0x0f, 0x6e]),
+        );
+    }
+    e.enc64(
+        x86_scalar_to_vector.bind(i64x2.clone()),
+        rec_frurm.opcodes(&[0x66, 0x0f, 0x6e]).rex().w(),
+    );
+    for ty in &simd_floats {
+        e.enc32_rec(x86_scalar_to_vector.bind(ty.clone()), rec_null_fpr, 0);
+        e.enc64_rec(x86_scalar_to_vector.bind(ty.clone()), rec_null_fpr, 0);
+    }
+
+    // pshufd
+    for ty in &simd_ints_and_floats {
+        e.enc_both(
+            x86_pshufd.bind(ty.clone()),
+            rec_furmi_lane.opcodes(&[0x66, 0x0f, 0x70]),
+        );
+    }
+
+    // Lane extraction: pextrb, pextrw, pextrd and pextrq into a GPR. A float lane
+    // is shuffled into the lowest lane with pshufd or shufpd.
+    e.enc_both(
+        extractlane.bind(i8x16.clone()),
+        rec_rfumr_lane.opcodes(&[0x66, 0x0f, 0x3a, 0x14]),
+    );
+    e.enc_both(
+        extractlane.bind(i16x8.clone()),
+        rec_rfumr_lane.opcodes(&[0x66, 0x0f, 0x3a, 0x15]),
+    );
+    e.enc_both(
+        extractlane.bind(i32x4.clone()),
+        rec_rfumr_lane.opcodes(&[0x66, 0x0f, 0x3a, 0x16]),
+    );
+    e.enc64(
+        extractlane.bind(i64x2.clone()),
+        rec_rfumr_lane.opcodes(&[0x66, 0x0f, 0x3a, 0x16]).rex().w(),
+    );
+    e.enc_both(
+        extractlane.bind(f32x4.clone()),
+        rec_furmi_lane.opcodes(&[0x66, 0x0f, 0x70]),
+    );
+    e.enc_both(
+        extractlane.bind(f64x2.clone()),
+        rec_fshuf_lane.opcodes(&[0x66, 0x0f, 0xc6]),
+    );
+
+    // Lane insertion: pinsrb, pinsrw, pinsrd and pinsrq from a GPR. Only pinsrw
+    // is available without SSE 4.1.
+    for &(ty, opc) in &[(&i8x16, 0x20), (&i32x4, 0x22)] {
+        let template = rec_frurmi_lane.opcodes(&[0x66, 0x0f, 0x3a, opc]);
+        e.enc32_isap(insertlane.bind(ty.clone()), template.clone(), use_sse41);
+        e.enc64_isap(insertlane.bind(ty.clone()), template.rex(), use_sse41);
+        e.enc64_isap(insertlane.bind(ty.clone()), template, use_sse41);
+    }
+    e.enc_both(
+        insertlane.bind(i16x8.clone()),
+        rec_frurmi_lane.opcodes(&[0x66, 0x0f, 0xc4]),
+    );
+    e.enc64_isap(
+        insertlane.bind(i64x2.clone()),
+        rec_frurmi_lane.opcodes(&[0x66, 0x0f, 0x3a, 0x22]).rex().w(),
+        use_sse41,
+    );
+
+    // insertps
+    e.enc_both(
+        insertlane.bind(f32x4.clone()),
+        rec_fa_insertps.opcodes(&[0x66, 0x0f, 0x3a, 0x21]),
+    );
+
+    // movsd into the low lane, and movlhps into the high lane.
+    let f_insert_lane = formats.get(formats.by_name("InsertLane"));
+    e.enc_both_instp(
+        insertlane.bind(f64x2.clone()),
+        rec_fa_lane.opcodes(&[0xf2, 0x0f, 0x10]),
+        InstructionPredicate::is_equal(f_insert_lane, "lane", "0"),
+    );
+    e.enc_both_instp(
+        insertlane.bind(f64x2),
+        rec_fa_lane.opcodes(&[0x0f, 0x16]),
+        InstructionPredicate::is_equal(f_insert_lane, "lane", "1"),
+    );
+
+    // 256-bit vectors.
+    //
+    // The vector types with 256 bits are held in the YMM registers, and they need
+    // AVX. The arithmetic instructions are above, with the AVX forms of the scalar
+    // float instructions.
+    let simd256_ints_and_floats: Vec<ValueType> =
+        simd256_ints().into_iter().chain(simd256_floats()).collect();
+
+    for ty in &simd256_types() {
+        // vmovaps
+        e.enc_vex(copy.bind(ty.clone()), rec_vfurm.opcodes(&[0x0f, 0x28]).l());
+        e.enc_vex(
+            regmove.bind(ty.clone()),
+            rec_vfrmov.opcodes(&[0x0f, 0x28]).l(),
+        );
+
+        // vmovups, since spill slots are not aligned to 32 bytes.
+        e.enc_vex(
+            fill.bind(ty.clone()),
+            rec_vffill_sib32.opcodes(&[0x0f, 0x10]).l(),
+        );
+        e.enc_vex(
+            regfill.bind(ty.clone()),
+            rec_vfregfill32.opcodes(&[0x0f, 0x10]).l(),
+        );
+        e.enc_vex(
+            spill.bind(ty.clone()),
+            rec_vfspill_sib32.opcodes(&[0x0f, 0x11]).l(),
+        );
+        e.enc_vex(
+            regspill.bind(ty.clone()),
+            rec_vfregspill32.opcodes(&[0x0f, 0x11]).l(),
+        );
+    }
+
+    for ty in &simd256_ints_and_floats {
+        for recipe in &[&rec_vfld, &rec_vfld_disp8, &rec_vfld_disp32] {
+            e.enc_vex(
+                load.bind(ty.clone()).bind_any(),
+                recipe.opcodes(&[0x0f, 0x10]).l(),
+            );
+        }
+        for recipe in &[&rec_vfst, &rec_vfst_disp8, &rec_vfst_disp32] {
+            e.enc_vex(
+                store.bind(ty.clone()).bind_any(),
+                recipe.opcodes(&[0x0f, 0x11]).l(),
+            );
+        }
+    }
+
+    // Reinterpreting the bits of a vector is a no-op.
+    for to_ty in &simd256_ints_and_floats {
+        for from_ty in &simd256_ints_and_floats {
+            if to_ty != from_ty {
+                let inst = bitcast.bind(to_ty.clone()).bind(from_ty.clone());
+                e.enc32_rec(inst.clone(), rec_null_fpr, 0);
+                e.enc64_rec(inst, rec_null_fpr, 0);
+            }
+        }
+    }
+
+    // Vectors that were split in two, because AVX doesn't have 256-bit integer
+    // arithmetic, are taken apart and put back together in registers.
+    for (ty, half) in simd256_types().into_iter().zip(simd_types.iter()) {
+        // vextractf128
+        e.enc_vex(
+            vsplit.bind(ty),
+            rec_vfsplit.opcodes(&[0x66, 0x0f, 0x3a, 0x19]).l(),
+        );
+        // vinsertf128
+        e.enc_vex(
+            vconcat.bind(half.clone()),
+            rec_vfconcat.opcodes(&[0x66, 0x0f, 0x3a, 0x18]).l(),
+        );
+    }
+
+    e.enc_vex(sqrt.bind(f32x8), rec_vfurm.opcodes(&[0x0f, 0x51]).l());
+    e.enc_vex(sqrt.bind(f64x4), rec_vfurm.opcodes(&[0x66, 0x0f, 0x51]).l());
+
+    // vzeroupper
+    e.enc_vex(x86_vzeroupper, rec_vop.opcodes(&[0x0f, 0x77]));
+
+    e
+}
+
+/// Vector types with integer lanes held in the 128-bit XMM registers.
+pub fn simd_ints() -> Vec<ValueType> {
+    vec![
+        LaneType::from(Int::I8).by(16),
+        LaneType::from(Int::I16).by(8),
+        LaneType::from(Int::I32).by(4),
+        LaneType::from(Int::I64).by(2),
+    ]
+}
+
+/// Vector types with float lanes held in the 128-bit XMM registers.
+pub fn simd_floats() -> Vec<ValueType> {
+    vec![
+        LaneType::from(Float::F32).by(4),
+        LaneType::from(Float::F64).by(2),
+    ]
+}
+
+/// Vector types with boolean lanes held in the 128-bit XMM registers.
+pub fn simd_bools() -> Vec<ValueType> {
+    vec![
+        LaneType::from(Bool::B8).by(16),
+        LaneType::from(Bool::B16).by(8),
+        LaneType::from(Bool::B32).by(4),
+        LaneType::from(Bool::B64).by(2),
+    ]
+}
+
+/// All the vector types held in the 128-bit XMM registers.
+pub fn simd_types() -> Vec<ValueType> {
+    let mut types = simd_ints();
+    types.extend(simd_floats());
+    types.extend(simd_bools());
+    types
+}
+
+/// Vector types with integer lanes held in the 256-bit YMM registers.
+pub fn simd256_ints() -> Vec<ValueType> {
+    vec![
+        LaneType::from(Int::I8).by(32),
+        LaneType::from(Int::I16).by(16),
+        LaneType::from(Int::I32).by(8),
+        LaneType::from(Int::I64).by(4),
+    ]
+}
+
+/// Vector types with float lanes held in the 256-bit YMM registers.
+pub fn simd256_floats() -> Vec<ValueType> {
+    vec![
+        LaneType::from(Float::F32).by(8),
+        LaneType::from(Float::F64).by(4),
+    ]
+}
+
+/// Vector types with boolean lanes held in the 256-bit YMM registers.
+pub fn simd256_bools() -> Vec<ValueType> {
+    vec![
+        LaneType::from(Bool::B8).by(32),
+        LaneType::from(Bool::B16).by(16),
+        LaneType::from(Bool::B32).by(8),
+        LaneType::from(Bool::B64).by(4),
+    ]
+}
+
+/// All the vector types held in the 256-bit YMM registers, only supported with
+/// AVX.
+pub fn simd256_types() -> Vec<ValueType> {
+    let mut types = simd256_ints();
+    types.extend(simd256_floats());
+    types.extend(simd256_bools());
+    types
+}
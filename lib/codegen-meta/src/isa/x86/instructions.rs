@@ -0,0 +1,343 @@
+//! Supplementary instruction definitions for x86.
+//!
+//! This module defines additional instructions that are useful only to the
+//! x86 target ISA.
+
+#![allow(non_snake_case)]
+
+use cdsl::formats::FormatRegistry;
+use cdsl::instructions::{InstructionBuilder as Inst, InstructionGroup, InstructionGroupBuilder};
+use cdsl::operands::Operand;
+use cdsl::typevar::{Interval, TypeSetBuilder, TypeVar};
+
+use base::immediates::Immediates;
+use base::types;
+
+pub fn define(format_registry: &FormatRegistry, imm: &Immediates) -> InstructionGroup {
+    let mut ig =
+        InstructionGroupBuilder::new("x86", "x86-specific instruction set", format_registry);
+
+    let iflags: &TypeVar = &TypeVar::new_singleton(types::Flag::IFlags.into());
+
+    let iWord = &TypeVar::new(
+        "iWord",
+        "A scalar integer machine word",
+        TypeSetBuilder::new().ints(32..=64).finish(),
+    );
+
+    let nlo = &Operand::new("nlo", iWord).with_doc("Low part of numerator");
+    let nhi = &Operand::new("nhi", iWord).with_doc("High part of numerator");
+    let d = &Operand::new("d", iWord).with_doc("Denominator");
+    let q = &Operand::new("q", iWord).with_doc("Quotient");
+    let r = &Operand::new("r", iWord).with_doc("Remainder");
+
+    ig.push(
+        Inst::new(
+            "x86_udivmodx",
+            r#"
+            Extended unsigned division.
+
+            Concatenate the bits in `nhi` and `nlo` to form the numerator.
+            Interpret the bits as an unsigned number and divide by the unsigned
+            denominator `d`. Trap when `d` is zero or if the quotient is larger
+            than the range of the output.
+
+            Return both quotient and remainder.
+            "#,
+        )
+        .operands_in(vec![nlo, nhi, d])
+        .operands_out(vec![q, r])
+        .can_trap(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "x86_sdivmodx",
+            r#"
+            Extended signed division.
+
+            Concatenate the bits in `nhi` and `nlo` to form the numerator.
+            Interpret the bits as a signed number and divide by the signed
+            denominator `d`. Trap when `d` is zero or if the quotient is outside
+            the range of the output.
+
+            Return both quotient and remainder.
+            "#,
+        )
+        .operands_in(vec![nlo, nhi, d])
+        .operands_out(vec![q, r])
+        .can_trap(true),
+    );
+
+    let argL = &Operand::new("argL", iWord);
+    let argR = &Operand::new("argR", iWord);
+    let resLo = &Operand::new("resLo", iWord);
+    let resHi = &Operand::new("resHi", iWord);
+
+    ig.push(
+        Inst::new(
+            "x86_umulx",
+            r#"
+            Unsigned integer multiplication, producing a double-length result.
+
+            Polymorphic over all scalar integer types, but does not support vector
+            types.
+            "#,
+        )
+        .operands_in(vec![argL, argR])
+        .operands_out(vec![resLo, resHi]),
+    );
+
+    ig.push(
+        Inst::new(
+            "x86_smulx",
+            r#"
+            Signed integer multiplication, producing a double-length result.
+
+            Polymorphic over all scalar integer types, but does not support vector
+            types.
+            "#,
+        )
+        .operands_in(vec![argL, argR])
+        .operands_out(vec![resLo, resHi]),
+    );
+
+    let Float = &TypeVar::new(
+        "Float",
+        "A scalar or vector floating point number",
+        TypeSetBuilder::new()
+            .floats(Interval::All)
+            .simd_lanes(Interval::All)
+            .finish(),
+    );
+    let IntTo = &TypeVar::new(
+        "IntTo",
+        "An integer type with the same number of lanes",
+        TypeSetBuilder::new()
+            .ints(32..=64)
+            .simd_lanes(Interval::All)
+            .finish(),
+    );
+
+    let x = &Operand::new("x", Float);
+    let a = &Operand::new("a", IntTo);
+
+    ig.push(
+        Inst::new(
+            "x86_cvtt2si",
+            r#"
+            Convert with truncation floating point to signed integer.
+
+            The source floating point operand is converted to a signed integer by
+            rounding towards zero. If the result can't be represented in the output
+            type, returns the smallest signed value the output type can represent.
+
+            This instruction does not trap.
+            "#,
+        )
+        .operands_in(vec![x])
+        .operands_out(vec![a]),
+    );
+
+    let x = &Operand::new("x", Float);
+    let a = &Operand::new("a", Float);
+    let y = &Operand::new("y", Float);
+
+    ig.push(
+        Inst::new(
+            "x86_fmin",
+            r#"
+            Floating point minimum with x86 semantics.
+
+            This is equivalent to the C ternary operator `x < y ? x : y` which
+            differs from :inst:`fmin` when either operand is NaN or when comparing
+            +0.0 to -0.0.
+
+            When the two operands don't compare as LT, `y` is returned unchanged,
+            even if it is a signalling NaN.
+            "#,
+        )
+        .operands_in(vec![x, y])
+        .operands_out(vec![a]),
+    );
+
+    ig.push(
+        Inst::new(
+            "x86_fmax",
+            r#"
+            Floating point maximum with x86 semantics.
+
+            This is equivalent to the C ternary operator `x > y ? x : y` which
+            differs from :inst:`fmax` when either operand is NaN or when comparing
+            +0.0 to -0.0.
+
+            When the two operands don't compare as GT, `y` is returned unchanged,
+            even if it is a signalling NaN.
+            "#,
+        )
+        .operands_in(vec![x, y])
+        .operands_out(vec![a]),
+    );
+
+    let x = &Operand::new("x", iWord);
+
+    ig.push(
+        Inst::new(
+            "x86_push",
+            r#"
+        Pushes a value onto the stack.
+
+        Decrements the stack pointer and stores the specified value on to the top.
+
+        This is polymorphic in i32 and i64. However, it is only implemented for i64
+        in 64-bit mode, and only for i32 in 32-bit mode.
+        "#,
+        )
+        .operands_in(vec![x])
+        .can_store(true)
+        .other_side_effects(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "x86_pop",
+            r#"
+        Pops a value from the stack.
+
+        Loads a value from the top of the stack and then increments the stack
+        pointer.
+
+        This is polymorphic in i32 and i64. However, it is only implemented for i64
+        in 64-bit mode, and only for i32 in 32-bit mode.
+        "#,
+        )
+        .operands_out(vec![x])
+        .can_load(true)
+        .other_side_effects(true),
+    );
+
+    let y = &Operand::new("y", iWord);
+    let rflags = &Operand::new("rflags", iflags);
+
+    ig.push(
+        Inst::new(
+            "x86_bsr",
+            r#"
+        Bit Scan Reverse -- returns the bit-index of the most significant 1
+        in the word. Result is undefined if the argument is zero. However, it
+        sets the Z flag depending on the argument, so it is at least easy to
+        detect and handle that case.
+
+        This is polymorphic in i32 and i64. It is implemented for both i64 and
+        i32 in 64-bit mode, and only for i32 in 32-bit mode.
+        "#,
+        )
+        .operands_in(vec![x])
+        .operands_out(vec![y, rflags]),
+    );
+
+    ig.push(
+        Inst::new(
+            "x86_bsf",
+            r#"
+        Bit Scan Forwards -- returns the bit-index of the least significant 1
+        in the word. Is otherwise identical to 'bsr', just above.
+        "#,
+        )
+        .operands_in(vec![x])
+        .operands_out(vec![y, rflags]),
+    );
+
+    let a = &Operand::new("a", iWord);
+
+    ig.push(
+        Inst::new(
+            "x86_blsr",
+            r#"
+        Reset Lowest Set Bit -- computes ``x & (x - 1)``, clearing the least
+        significant 1 in the word.
+
+        This is polymorphic in i32 and i64, and it is only available with BMI1.
+        The post-legalization pass forms it from the equivalent ``band``.
+        "#,
+        )
+        .operands_in(vec![x])
+        .operands_out(vec![a]),
+    );
+
+    ig.push(
+        Inst::new(
+            "x86_blsi",
+            r#"
+        Extract Lowest Set Bit -- computes ``x & -x``, keeping only the least
+        significant 1 in the word. Is otherwise identical to 'blsr', just above.
+        "#,
+        )
+        .operands_in(vec![x])
+        .operands_out(vec![a]),
+    );
+
+    let TxN = &TypeVar::new(
+        "TxN",
+        "A SIMD vector type",
+        TypeSetBuilder::new()
+            .ints(Interval::All)
+            .floats(Interval::All)
+            .bools(Interval::All)
+            .simd_lanes(Interval::All)
+            .includes_scalars(false)
+            .finish(),
+    );
+    let x = &Operand::new("x", &TxN.lane_of());
+    let a = &Operand::new("a", TxN);
+
+    ig.push(
+        Inst::new(
+            "x86_scalar_to_vector",
+            r#"
+        Move a scalar into the lowest lane of a vector.
+
+        The other lanes of the result are undefined. This is used for building
+        vectors out of scalars, and it is a no-op when the scalar is already held
+        in an XMM register.
+        "#,
+        )
+        .operands_in(vec![x])
+        .operands_out(vec![a]),
+    );
+
+    let x = &Operand::new("x", TxN);
+    let Order = &Operand::new("Order", &imm.uimm8).with_doc("Lane selectors");
+
+    ig.push(
+        Inst::new(
+            "x86_pshufd",
+            r#"
+        Shuffle the 32-bit lanes of a vector.
+
+        Each 2-bit field of ``Order``, from the least significant one, is the
+        index of the 32-bit lane of `x` that is copied into the corresponding
+        32-bit lane of the result. The bits of `x` are shuffled the same way
+        regardless of its lane type.
+        "#,
+        )
+        .operands_in(vec![x, Order])
+        .operands_out(vec![a]),
+    );
+
+    ig.push(
+        Inst::new(
+            "x86_vzeroupper",
+            r#"
+        Clear the upper 128 bits of all the YMM registers.
+
+        This avoids the penalty of mixing legacy SSE instructions with AVX
+        instructions that leave the upper halves of the YMM registers dirty, so it
+        is inserted before calls and returns in functions using 256-bit vectors.
+        "#,
+        )
+        .other_side_effects(true),
+    );
+
+    ig.finish()
+}
@@ -0,0 +1,367 @@
+//! Custom legalization patterns for x86.
+
+use base;
+use cdsl::ast::{var, Literal};
+use cdsl::instructions::InstructionGroup;
+use cdsl::types::{LaneType, ValueType};
+use cdsl::xform::{XFormGroupBuilder, XFormGroupIndex};
+
+use base::types::{Float, Int};
+
+/// Define the x86 legalization groups, and return the indices of `x86_expand`
+/// and `x86_simd`.
+pub fn define(
+    shared: &mut base::Definitions,
+    x86_instructions: &InstructionGroup,
+) -> (XFormGroupIndex, XFormGroupIndex) {
+    let insts = &shared.instructions;
+    let imm = &shared.imm;
+    let groups = &mut shared.transform_groups;
+
+    let mut group = XFormGroupBuilder::new(
+        "x86_expand",
+        r#"
+        Legalize instructions by expansion.
+
+        Use x86-specific instructions if needed.
+        "#,
+    )
+    .isa("x86")
+    .chain(groups.by_name("expand_flags"));
+
+    let mut simd = XFormGroupBuilder::new(
+        "x86_simd",
+        r#"
+        Legalize 128-bit vector instructions.
+
+        Use SSE instructions for the vector operations that don't have a
+        direct encoding, and split the other ones into halves.
+        "#,
+    )
+    .isa("x86")
+    .chain(groups.by_name("narrow"));
+
+    // List of instructions.
+    let band = insts.by_name("band");
+    let bitcast = insts.by_name("bitcast");
+    let bor = insts.by_name("bor");
+    let clz = insts.by_name("clz");
+    let ctz = insts.by_name("ctz");
+    let extractlane = insts.by_name("extractlane");
+    let fcmp = insts.by_name("fcmp");
+    let iadd = insts.by_name("iadd");
+    let iconst = insts.by_name("iconst");
+    let imul = insts.by_name("imul");
+    let imul_imm = insts.by_name("imul_imm");
+    let insertlane = insts.by_name("insertlane");
+    let isub = insts.by_name("isub");
+    let popcnt = insts.by_name("popcnt");
+    let selectif = insts.by_name("selectif");
+    let smulhi = insts.by_name("smulhi");
+    let splat = insts.by_name("splat");
+    let uextend = insts.by_name("uextend");
+    let umulhi = insts.by_name("umulhi");
+    let ushr_imm = insts.by_name("ushr_imm");
+
+    let x86_bsf = x86_instructions.by_name("x86_bsf");
+    let x86_bsr = x86_instructions.by_name("x86_bsr");
+    let x86_pshufd = x86_instructions.by_name("x86_pshufd");
+    let x86_scalar_to_vector = x86_instructions.by_name("x86_scalar_to_vector");
+    let x86_smulx = x86_instructions.by_name("x86_smulx");
+    let x86_umulx = x86_instructions.by_name("x86_umulx");
+
+    // List of types.
+    let i32 = ValueType::from(Int::I32);
+    let i64 = ValueType::from(Int::I64);
+    let i8x16 = LaneType::from(Int::I8).by(16);
+    let i16x8 = LaneType::from(Int::I16).by(8);
+    let i32x4 = LaneType::from(Int::I32).by(4);
+    let i64x2 = LaneType::from(Int::I64).by(2);
+    let f32x4 = LaneType::from(Float::F32).by(4);
+    let f64x2 = LaneType::from(Float::F64).by(2);
+
+    // Division and remainder.
+    //
+    // The srem expansion requires custom code because srem INT_MIN, -1 is not
+    // allowed to trap. The other ops need to check avoid_div_traps.
+    group.custom_legalize(insts.by_name("sdiv"), "expand_sdivrem");
+    group.custom_legalize(insts.by_name("srem"), "expand_sdivrem");
+    group.custom_legalize(insts.by_name("udiv"), "expand_udivrem");
+    group.custom_legalize(insts.by_name("urem"), "expand_udivrem");
+
+    // Double length (widening) multiplication.
+    let x = var("x");
+    let y = var("y");
+    let res_lo = var("resLo");
+    let res_hi = var("resHi");
+
+    group.legalize(
+        def!(res_hi = umulhi(x, y)),
+        vec![def!((res_lo, res_hi) = x86_umulx(x, y))],
+    );
+
+    group.legalize(
+        def!(res_hi = smulhi(x, y)),
+        vec![def!((res_lo, res_hi) = x86_smulx(x, y))],
+    );
+
+    // Floating point condition codes.
+    //
+    // The 8 condition codes in `supported_floatccs` are directly supported by
+    // a `ucomiss` or `ucomisd` instruction. The remaining codes need
+    // legalization patterns.
+    let floatcc = |name: &'static str| Literal::enumerator_for(&imm.floatcc, name);
+
+    let a = var("a");
+    let a1 = var("a1");
+    let a2 = var("a2");
+
+    // Equality needs an explicit `ord` test which checks the parity bit.
+    let floatcc_eq = floatcc("eq");
+    let floatcc_ord = floatcc("ord");
+    let floatcc_ueq = floatcc("ueq");
+    group.legalize(
+        def!(a = fcmp(floatcc_eq, x, y)),
+        vec![
+            def!(a1 = fcmp(floatcc_ord, x, y)),
+            def!(a2 = fcmp(floatcc_ueq, x, y)),
+            def!(a = band(a1, a2)),
+        ],
+    );
+
+    let floatcc_ne = floatcc("ne");
+    let floatcc_uno = floatcc("uno");
+    let floatcc_one = floatcc("one");
+    group.legalize(
+        def!(a = fcmp(floatcc_ne, x, y)),
+        vec![
+            def!(a1 = fcmp(floatcc_uno, x, y)),
+            def!(a2 = fcmp(floatcc_one, x, y)),
+            def!(a = bor(a1, a2)),
+        ],
+    );
+
+    // Inequalities that need to be reversed.
+    for &(cc, rev_cc) in &[("lt", "gt"), ("le", "ge"), ("ugt", "ult"), ("uge", "ule")] {
+        let cc = floatcc(cc);
+        let rev_cc = floatcc(rev_cc);
+        group.legalize(def!(a = fcmp(cc, x, y)), vec![def!(a = fcmp(rev_cc, y, x))]);
+    }
+
+    // We need to modify the CFG for min/max legalization.
+    group.custom_legalize(insts.by_name("fmin"), "expand_minmax");
+    group.custom_legalize(insts.by_name("fmax"), "expand_minmax");
+
+    // Conversions from unsigned need special handling.
+    group.custom_legalize(insts.by_name("fcvt_from_uint"), "expand_fcvt_from_uint");
+    // Conversions from float to int can trap.
+    group.custom_legalize(insts.by_name("fcvt_to_sint"), "expand_fcvt_to_sint");
+    group.custom_legalize(insts.by_name("fcvt_to_uint"), "expand_fcvt_to_uint");
+
+    // Only sequentially consistent stores need a locked instruction.
+    group.custom_legalize(insts.by_name("atomic_store"), "expand_atomic_store");
+
+    // Count leading and trailing zeroes, for baseline x86_64
+    let c_minus_one = var("c_minus_one");
+    let c_thirty_one = var("c_thirty_one");
+    let c_thirty_two = var("c_thirty_two");
+    let c_sixty_three = var("c_sixty_three");
+    let c_sixty_four = var("c_sixty_four");
+    let index1 = var("index1");
+    let r2flags = var("r2flags");
+    let index2 = var("index2");
+
+    let intcc_eq = Literal::enumerator_for(&imm.intcc, "eq");
+    let imm64_minus_one = Literal::constant(&imm.imm64, -1);
+    let imm64_63 = Literal::constant(&imm.imm64, 63);
+    group.legalize(
+        def!(a = clz.i64(x)),
+        vec![
+            def!(c_minus_one = iconst(imm64_minus_one)),
+            def!(c_sixty_three = iconst(imm64_63)),
+            def!((index1, r2flags) = x86_bsr(x)),
+            def!(index2 = selectif(intcc_eq, r2flags, c_minus_one, index1)),
+            def!(a = isub(c_sixty_three, index2)),
+        ],
+    );
+
+    let imm64_31 = Literal::constant(&imm.imm64, 31);
+    group.legalize(
+        def!(a = clz.i32(x)),
+        vec![
+            def!(c_minus_one = iconst(imm64_minus_one)),
+            def!(c_thirty_one = iconst(imm64_31)),
+            def!((index1, r2flags) = x86_bsr(x)),
+            def!(index2 = selectif(intcc_eq, r2flags, c_minus_one, index1)),
+            def!(a = isub(c_thirty_one, index2)),
+        ],
+    );
+
+    let imm64_64 = Literal::constant(&imm.imm64, 64);
+    group.legalize(
+        def!(a = ctz.i64(x)),
+        vec![
+            def!(c_sixty_four = iconst(imm64_64)),
+            def!((index1, r2flags) = x86_bsf(x)),
+            def!(a = selectif(intcc_eq, r2flags, c_sixty_four, index1)),
+        ],
+    );
+
+    let imm64_32 = Literal::constant(&imm.imm64, 32);
+    group.legalize(
+        def!(a = ctz.i32(x)),
+        vec![
+            def!(c_thirty_two = iconst(imm64_32)),
+            def!((index1, r2flags) = x86_bsf(x)),
+            def!(a = selectif(intcc_eq, r2flags, c_thirty_two, index1)),
+        ],
+    );
+
+    // Population count for baseline x86_64
+    let qv1 = var("qv1");
+    let qv3 = var("qv3");
+    let qv4 = var("qv4");
+    let qv5 = var("qv5");
+    let qv6 = var("qv6");
+    let qv7 = var("qv7");
+    let qv8 = var("qv8");
+    let qv9 = var("qv9");
+    let qv10 = var("qv10");
+    let qv11 = var("qv11");
+    let qv12 = var("qv12");
+    let qv13 = var("qv13");
+    let qv14 = var("qv14");
+    let qv15 = var("qv15");
+    let qv16 = var("qv16");
+    let qc77 = var("qc77");
+    let qc0f = var("qc0F");
+    let qc01 = var("qc01");
+
+    let imm64_1 = Literal::constant(&imm.imm64, 1);
+    let imm64_4 = Literal::constant(&imm.imm64, 4);
+    group.legalize(
+        def!(qv16 = popcnt.i64(qv1)),
+        vec![
+            def!(qv3 = ushr_imm(qv1, imm64_1)),
+            def!(qc77 = iconst(Literal::constant(&imm.imm64, 0x7777777777777777))),
+            def!(qv4 = band(qv3, qc77)),
+            def!(qv5 = isub(qv1, qv4)),
+            def!(qv6 = ushr_imm(qv4, imm64_1)),
+            def!(qv7 = band(qv6, qc77)),
+            def!(qv8 = isub(qv5, qv7)),
+            def!(qv9 = ushr_imm(qv7, imm64_1)),
+            def!(qv10 = band(qv9, qc77)),
+            def!(qv11 = isub(qv8, qv10)),
+            def!(qv12 = ushr_imm(qv11, imm64_4)),
+            def!(qv13 = iadd(qv11, qv12)),
+            def!(qc0f = iconst(Literal::constant(&imm.imm64, 0x0F0F0F0F0F0F0F0F))),
+            def!(qv14 = band(qv13, qc0f)),
+            def!(qc01 = iconst(Literal::constant(&imm.imm64, 0x0101010101010101))),
+            def!(qv15 = imul(qv14, qc01)),
+            def!(qv16 = ushr_imm(qv15, Literal::constant(&imm.imm64, 56))),
+        ],
+    );
+
+    let lv1 = var("lv1");
+    let lv3 = var("lv3");
+    let lv4 = var("lv4");
+    let lv5 = var("lv5");
+    let lv6 = var("lv6");
+    let lv7 = var("lv7");
+    let lv8 = var("lv8");
+    let lv9 = var("lv9");
+    let lv10 = var("lv10");
+    let lv11 = var("lv11");
+    let lv12 = var("lv12");
+    let lv13 = var("lv13");
+    let lv14 = var("lv14");
+    let lv15 = var("lv15");
+    let lv16 = var("lv16");
+    let lc77 = var("lc77");
+    let lc0f = var("lc0F");
+    let lc01 = var("lc01");
+
+    group.legalize(
+        def!(lv16 = popcnt.i32(lv1)),
+        vec![
+            def!(lv3 = ushr_imm(lv1, imm64_1)),
+            def!(lc77 = iconst(Literal::constant(&imm.imm64, 0x77777777))),
+            def!(lv4 = band(lv3, lc77)),
+            def!(lv5 = isub(lv1, lv4)),
+            def!(lv6 = ushr_imm(lv4, imm64_1)),
+            def!(lv7 = band(lv6, lc77)),
+            def!(lv8 = isub(lv5, lv7)),
+            def!(lv9 = ushr_imm(lv7, imm64_1)),
+            def!(lv10 = band(lv9, lc77)),
+            def!(lv11 = isub(lv8, lv10)),
+            def!(lv12 = ushr_imm(lv11, imm64_4)),
+            def!(lv13 = iadd(lv11, lv12)),
+            def!(lc0f = iconst(Literal::constant(&imm.imm64, 0x0F0F0F0F))),
+            def!(lv14 = band(lv13, lc0f)),
+            def!(lc01 = iconst(Literal::constant(&imm.imm64, 0x01010101))),
+            def!(lv15 = imul(lv14, lc01)),
+            def!(lv16 = ushr_imm(lv15, Literal::constant(&imm.imm64, 24))),
+        ],
+    );
+
+    // SIMD.
+    let b = var("b");
+    let c = var("c");
+    let xl = var("xl");
+    let xh = var("xh");
+
+    // Splat a scalar by moving it into the lowest lane and shuffling that
+    // lane into the other ones.
+    for &(ty, order) in &[
+        (&i32x4, 0x00),
+        (&i64x2, 0x44),
+        (&f32x4, 0x00),
+        (&f64x2, 0x44),
+    ] {
+        simd.legalize(
+            def!(a = splat.ty(x)),
+            vec![
+                def!(b = x86_scalar_to_vector.ty(x)),
+                def!(a = x86_pshufd(b, Literal::constant(&imm.uimm8, order))),
+            ],
+        );
+    }
+
+    // Narrower lanes are first repeated in a 32-bit integer.
+    for &(ty, repeat) in &[(&i8x16, 0x01010101), (&i16x8, 0x00010001)] {
+        simd.legalize(
+            def!(a = splat.ty(x)),
+            vec![
+                def!(a1 = uextend.i32(x)),
+                def!(a2 = imul_imm(a1, Literal::constant(&imm.imm64, repeat))),
+                def!(b = x86_scalar_to_vector.i32x4(a2)),
+                def!(c = x86_pshufd(b, Literal::constant(&imm.uimm8, 0x00))),
+                def!(a = bitcast.ty(c)),
+            ],
+        );
+    }
+
+    // There is no SSE instruction for multiplying 64-bit lanes, so multiply
+    // the lanes one at a time.
+    let yl = var("yl");
+    let yh = var("yh");
+    let al = var("al");
+    let ah = var("ah");
+    simd.legalize(
+        def!(a = imul.i64x2(x, y)),
+        vec![
+            def!(xl = extractlane(x, Literal::constant(&imm.uimm8, 0))),
+            def!(xh = extractlane(x, Literal::constant(&imm.uimm8, 1))),
+            def!(yl = extractlane(y, Literal::constant(&imm.uimm8, 0))),
+            def!(yh = extractlane(y, Literal::constant(&imm.uimm8, 1))),
+            def!(al = imul(xl, yl)),
+            def!(ah = imul(xh, yh)),
+            def!(b = x86_scalar_to_vector.i64x2(al)),
+            def!(a = insertlane(b, Literal::constant(&imm.uimm8, 1), ah)),
+        ],
+    );
+
+    let x86_expand = group.finish_and_add_to(groups);
+    let x86_simd = simd.finish_and_add_to(groups);
+    (x86_expand, x86_simd)
+}
@@ -0,0 +1,83 @@
+//! x86 Target Architecture.
+
+use base;
+use base::types::{Bool, Float, Int};
+use cdsl::cpu_modes::CpuMode;
+use cdsl::isa::TargetIsa;
+use cdsl::settings::SettingGroup;
+
+mod encodings;
+mod instructions;
+mod legalize;
+mod recipes;
+mod registers;
+mod settings;
+
+pub fn define(shared_settings: &SettingGroup, shared_defs: &mut base::Definitions) -> TargetIsa {
+    let settings = settings::define(shared_settings);
+    let regs = registers::define();
+
+    let instructions = instructions::define(&shared_defs.format_registry, &shared_defs.imm);
+    let (x86_expand, x86_simd) = legalize::define(shared_defs, &instructions);
+
+    let groups = &shared_defs.transform_groups;
+    let narrow = groups.by_name("narrow");
+    let widen = groups.by_name("widen");
+    let expand_flags = groups.by_name("expand_flags");
+
+    let mut x86_64 = CpuMode::new("I64");
+    let mut x86_32 = CpuMode::new("I32");
+
+    x86_32.legalize_monomorphic(expand_flags);
+    x86_32.legalize_default(narrow);
+    x86_32.legalize_type(Bool::B1, expand_flags);
+    x86_32.legalize_type(Int::I8, widen);
+    x86_32.legalize_type(Int::I16, widen);
+    x86_32.legalize_type(Int::I32, x86_expand);
+    x86_32.legalize_type(Float::F32, x86_expand);
+    x86_32.legalize_type(Float::F64, x86_expand);
+    for ty in encodings::simd_types() {
+        x86_32.legalize_type(ty, x86_simd);
+    }
+    for ty in encodings::simd256_types() {
+        x86_32.legalize_type(ty, x86_simd);
+    }
+
+    x86_64.legalize_monomorphic(expand_flags);
+    x86_64.legalize_default(narrow);
+    x86_64.legalize_type(Bool::B1, expand_flags);
+    x86_64.legalize_type(Int::I8, widen);
+    x86_64.legalize_type(Int::I16, widen);
+    x86_64.legalize_type(Int::I32, x86_expand);
+    x86_64.legalize_type(Int::I64, x86_expand);
+    x86_64.legalize_type(Float::F32, x86_expand);
+    x86_64.legalize_type(Float::F64, x86_expand);
+    for ty in encodings::simd_types() {
+        x86_64.legalize_type(ty, x86_simd);
+    }
+    for ty in encodings::simd256_types() {
+        x86_64.legalize_type(ty, x86_simd);
+    }
+
+    let recipes = recipes::define(shared_defs, &settings, &regs);
+
+    let encodings = encodings::define(
+        shared_defs,
+        shared_settings,
+        &settings,
+        &instructions,
+        &recipes,
+    );
+    x86_32.set_encodings(encodings.enc32);
+    x86_64.set_encodings(encodings.enc64);
+    let recipes = encodings.recipes;
+
+    TargetIsa::new(
+        "x86",
+        settings,
+        regs,
+        instructions,
+        recipes,
+        vec![x86_64, x86_32],
+    )
+}
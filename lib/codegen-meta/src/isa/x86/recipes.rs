@@ -0,0 +1,3479 @@
+//! x86 Encoding recipes.
+
+use cdsl::formats::{FormatRegistry, InstructionFormat};
+use cdsl::instructions::InstructionPredicate;
+use cdsl::operands::OperandKind;
+use cdsl::recipes::{EncodingRecipe, EncodingRecipeBuilder, OperandConstraint, Register, Stack};
+use cdsl::registers::IsaRegs;
+use cdsl::settings::SettingGroup;
+
+use base;
+
+// Opcode representation.
+//
+// Cranelift requires each recipe to have a single encoding size in bytes, and
+// x86 opcodes are variable length, so we use separate recipes for different
+// styles of opcodes and prefixes. The opcode format is indicated by the recipe
+// name prefix.
+//
+// The match case below does not include the REX prefix which goes after the
+// mandatory prefix. XOP and EVEX prefixes are not yet supported. Encodings
+// using any of these prefixes are represented by separate recipes.
+//
+// VEX encodings always use the three-byte VEX prefix which holds the pp and mm
+// fields, so their recipe names are prefixed with `Vex128` or `Vex256`
+// instead, depending on the VEX.L bit.
+//
+// The encoding bits are:
+//
+// 0-7:   The opcode byte <op>.
+// 8-9:   pp, mandatory prefix:
+//        00 none (Op*)
+//        01 66   (Mp*)
+//        10 F3   (Mp*)
+//        11 F2   (Mp*)
+// 10-11: mm, opcode map:
+//        00 <op>        (Op1/Mp1)
+//        01 0F <op>     (Op2/Mp2)
+//        10 0F 38 <op>  (Op3/Mp3)
+//        11 0F 3A <op>  (Op3/Mp3)
+// 12-14  rrr, opcode bits for the ModR/M byte for certain opcodes.
+// 15:    REX.W bit (or VEX.W/E)
+//
+// There is some redundancy between bits 8-11 and the recipe names, but we have
+// enough bits, and the pp+mm format is ready for supporting VEX prefixes.
+
+/// Get the recipe name prefix and the `mmpp` encoding bits for the opcode
+/// bytes preceding the last one.
+fn opcode_prefix(prefix: &[u8]) -> (&'static str, u16) {
+    match prefix {
+        [] => ("Op1", 0b0000),
+        [0x66] => ("Mp1", 0b0001),
+        [0xf3] => ("Mp1", 0b0010),
+        [0xf2] => ("Mp1", 0b0011),
+        [0x0f] => ("Op2", 0b0100),
+        [0x66, 0x0f] => ("Mp2", 0b0101),
+        [0xf3, 0x0f] => ("Mp2", 0b0110),
+        [0xf2, 0x0f] => ("Mp2", 0b0111),
+        [0x0f, 0x38] => ("Op3", 0b1000),
+        [0x66, 0x0f, 0x38] => ("Mp3", 0b1001),
+        [0xf3, 0x0f, 0x38] => ("Mp3", 0b1010),
+        [0xf2, 0x0f, 0x38] => ("Mp3", 0b1011),
+        [0x0f, 0x3a] => ("Op3", 0b1100),
+        [0x66, 0x0f, 0x3a] => ("Mp3", 0b1101),
+        [0xf3, 0x0f, 0x3a] => ("Mp3", 0b1110),
+        [0xf2, 0x0f, 0x3a] => ("Mp3", 0b1111),
+        _ => panic!("unexpected opcode sequence: {:?}", prefix),
+    }
+}
+
+/// Given a sequence of opcode bytes, compute the recipe name prefix and
+/// encoding bits.
+fn decode_opcodes(op_bytes: &[u8], rrr: u16, w: u16) -> (&'static str, u16) {
+    assert!(!op_bytes.is_empty(), "at least one opcode byte");
+    assert!(rrr <= 0b111);
+    assert!(w <= 1);
+    let (name, mmpp) = opcode_prefix(&op_bytes[..op_bytes.len() - 1]);
+    let op = u16::from(op_bytes[op_bytes.len() - 1]);
+    (name, op | (mmpp << 8) | (rrr << 12) | (w << 15))
+}
+
+/// Given a snippet of Rust code, replace the `PUT_OP` macro with the
+/// corresponding `put_*` function from the `binemit.rs` module.
+fn replace_put_op(code: Option<String>, prefix: &str) -> Option<String> {
+    code.map(|code| code.replace("PUT_OP", &format!("put_{}", prefix.to_lowercase())))
+}
+
+/// Map the register classes of `constraints` to the ones that can be encoded
+/// without a REX prefix.
+fn map_regs_norex(regs: &IsaRegs, constraints: &[OperandConstraint]) -> Vec<OperandConstraint> {
+    constraints
+        .iter()
+        .map(|constraint| match *constraint {
+            OperandConstraint::RegClass(index) => {
+                let norex_name = match regs.classes[index].name {
+                    "GPR" => "GPR8",
+                    "GPR_DEREF_SAFE" => "GPR8_DEREF_SAFE",
+                    "GPR_ZERO_DEREF_SAFE" => "GPR8_ZERO_DEREF_SAFE",
+                    "FPR" => "FPR8",
+                    _ => return *constraint,
+                };
+                OperandConstraint::from(regs.class_by_name(norex_name))
+            }
+            _ => *constraint,
+        })
+        .collect()
+}
+
+/// Generate encoding recipes on demand.
+///
+/// x86 encodings are somewhat orthogonal with the opcode representation on
+/// one side and the ModR/M, SIB and immediate fields on the other side.
+///
+/// A `Template` represents the part of an encoding that follows the opcode.
+/// It is used to generate full encoding recipes on demand when combined with
+/// an opcode.
+///
+/// The recipe builder of a template is the same as for any other recipe,
+/// except that its size and branch range origin don't include the size of
+/// the opcode. The text `PUT_OP` in its emitted code is substituted with the
+/// proper `put_*` function from the `x86/binemit.rs` module.
+#[derive(Clone)]
+pub struct Template<'builder> {
+    regs: &'builder IsaRegs,
+    recipe: EncodingRecipeBuilder,
+    /// A template that should be substituted for this one when a REX (or VEX)
+    /// prefix is present. This is relevant for recipes that can only access
+    /// the ABCD registers without a REX prefix, but are able to access all
+    /// registers with a prefix.
+    when_prefixed: Option<Box<Template<'builder>>>,
+    /// This template can't be used without a REX prefix.
+    requires_prefix: bool,
+    /// This template encodes an operand in the VEX.vvvv field, so it can only
+    /// be used with a VEX prefix. Its `PUT_OP` function takes the register of
+    /// that operand after the REX bits.
+    requires_vex: bool,
+
+    // Specialization of the template for a given opcode.
+    op_bytes: Vec<u8>,
+    rrr_bits: u16,
+    w_bit: u16,
+    rex: bool,
+    vex: bool,
+    l_bit: u16,
+}
+
+impl<'builder> Template<'builder> {
+    pub fn new(recipe: EncodingRecipeBuilder, regs: &'builder IsaRegs) -> Self {
+        Self {
+            regs,
+            recipe,
+            when_prefixed: None,
+            requires_prefix: false,
+            requires_vex: false,
+            op_bytes: Vec::new(),
+            rrr_bits: 0,
+            w_bit: 0,
+            rex: false,
+            vex: false,
+            l_bit: 0,
+        }
+    }
+
+    fn when_prefixed(mut self, template: Template<'builder>) -> Self {
+        assert!(self.when_prefixed.is_none());
+        self.when_prefixed = Some(Box::new(template));
+        self
+    }
+
+    fn requires_prefix(mut self, value: bool) -> Self {
+        self.requires_prefix = value;
+        self
+    }
+
+    fn requires_vex(mut self, value: bool) -> Self {
+        self.requires_vex = value;
+        self
+    }
+
+    /// Specialize this template for the opcode bytes `op_bytes`.
+    pub fn opcodes(&self, op_bytes: &[u8]) -> Self {
+        assert!(!op_bytes.is_empty());
+        let mut copy = self.clone();
+        copy.op_bytes = op_bytes.to_vec();
+        copy
+    }
+
+    /// Set the opcode bits for the ModR/M byte.
+    pub fn rrr(&self, value: u16) -> Self {
+        assert!(value <= 0b111);
+        let mut copy = self.clone();
+        copy.rrr_bits = value;
+        copy
+    }
+
+    /// Set the REX.W bit, or the VEX.W bit with a VEX prefix.
+    pub fn w(&self) -> Self {
+        let mut copy = self.clone();
+        copy.w_bit = 1;
+        copy
+    }
+
+    /// Always emit a REX prefix, whether it is required or not.
+    ///
+    /// For instructions that don't require a REX prefix, two encodings should
+    /// be added: One with REX and one without.
+    pub fn rex(&self) -> Self {
+        // Use the prefixed alternative template when applicable.
+        if let Some(ref prefixed) = self.when_prefixed {
+            let mut copy = prefixed.rex();
+            copy.op_bytes = self.op_bytes.clone();
+            copy.rrr_bits = self.rrr_bits;
+            copy.w_bit = self.w_bit;
+            return copy;
+        }
+        assert!(
+            !self.requires_vex,
+            "{} requires a VEX prefix",
+            self.recipe.name
+        );
+        let mut copy = self.clone();
+        copy.rex = true;
+        copy
+    }
+
+    /// Use a VEX prefix.
+    ///
+    /// The mandatory prefix and the opcode map of the opcode bytes are moved
+    /// into the three-byte VEX prefix.
+    pub fn vex(&self) -> Self {
+        assert!(
+            self.requires_vex,
+            "{} doesn't support a VEX prefix",
+            self.recipe.name
+        );
+        let mut copy = self.clone();
+        copy.vex = true;
+        copy
+    }
+
+    /// Set the VEX.L bit, for instructions operating on 256-bit vectors.
+    pub fn l(&self) -> Self {
+        let mut copy = self.clone();
+        copy.l_bit = 1;
+        copy
+    }
+
+    /// Create the encoding recipe and the encoding bits of this specialized
+    /// template.
+    pub fn build(self, formats: &FormatRegistry) -> (EncodingRecipe, u16) {
+        let (name, bits) = decode_opcodes(&self.op_bytes, self.rrr_bits, self.w_bit);
+
+        let (name, size_addendum) = if self.vex {
+            let name = if self.l_bit == 1 { "Vex256" } else { "Vex128" };
+            (name.to_string(), 4)
+        } else if self.rex {
+            (format!("Rex{}", name), 1 + self.op_bytes.len() as u64)
+        } else {
+            assert!(
+                !self.requires_prefix,
+                "{} requires a REX prefix",
+                self.recipe.name
+            );
+            assert!(
+                !self.requires_vex,
+                "{} requires a VEX prefix",
+                self.recipe.name
+            );
+            (name.to_string(), self.op_bytes.len() as u64)
+        };
+
+        let mut recipe = self.recipe;
+        recipe.name = format!("{}{}", name, recipe.name);
+        recipe.base_size += size_addendum;
+
+        // All branch ranges are relative to the end of the instruction.
+        if let Some(ref mut range) = recipe.branch_range {
+            range.origin += size_addendum;
+        }
+
+        recipe.emit = replace_put_op(recipe.emit, &name);
+        if !self.rex && !self.vex {
+            recipe.operands_in = map_regs_norex(self.regs, &recipe.operands_in);
+            recipe.operands_out = map_regs_norex(self.regs, &recipe.operands_out);
+        }
+
+        (recipe.build(formats), bits)
+    }
+}
+
+/// The x86 encoding recipes and recipe templates.
+pub struct RecipeGroup<'builder> {
+    formats: &'builder FormatRegistry,
+    regs: &'builder IsaRegs,
+    recipes: Vec<EncodingRecipe>,
+    templates: Vec<Template<'builder>>,
+}
+
+impl<'builder> RecipeGroup<'builder> {
+    fn new(formats: &'builder FormatRegistry, regs: &'builder IsaRegs) -> Self {
+        Self {
+            formats,
+            regs,
+            recipes: Vec::new(),
+            templates: Vec::new(),
+        }
+    }
+
+    fn add_recipe(&mut self, recipe: EncodingRecipeBuilder) {
+        self.recipes.push(recipe.build(self.formats));
+    }
+
+    fn add_template_recipe(&mut self, recipe: EncodingRecipeBuilder) {
+        let template = Template::new(recipe, self.regs);
+        self.add_template(template);
+    }
+
+    fn add_template(&mut self, template: Template<'builder>) {
+        assert!(
+            self.templates
+                .iter()
+                .all(|t| t.recipe.name != template.recipe.name),
+            "duplicate template {}",
+            template.recipe.name
+        );
+        self.templates.push(template);
+    }
+
+    /// Get the recipe named `name`, which doesn't need an opcode.
+    pub fn recipe(&self, name: &str) -> &EncodingRecipe {
+        self.recipes
+            .iter()
+            .find(|recipe| recipe.name == name)
+            .unwrap_or_else(|| panic!("unknown recipe name: {}", name))
+    }
+
+    /// Get the template named `name`.
+    pub fn template(&self, name: &str) -> &Template<'builder> {
+        self.templates
+            .iter()
+            .find(|template| template.recipe.name == name)
+            .unwrap_or_else(|| panic!("unknown template name: {}", name))
+    }
+}
+
+/// The floating point condition codes that are directly supported by the
+/// x86 recipes. The others are handled by legalization patterns.
+const SUPPORTED_FLOATCCS: [&str; 8] = ["ord", "uno", "one", "ueq", "gt", "ge", "ult", "ule"];
+
+/// Get an instruction predicate checking that the `cond` field of `format` is
+/// one of the directly supported floating point condition codes.
+fn floatccs(format: &InstructionFormat, floatcc: &OperandKind) -> InstructionPredicate {
+    InstructionPredicate::Or(
+        SUPPORTED_FLOATCCS
+            .iter()
+            .map(|cc| InstructionPredicate::is_equal(format, "cond", floatcc.rust_enumerator(cc)))
+            .collect(),
+    )
+}
+
+pub fn define<'builder>(
+    shared_defs: &'builder base::Definitions,
+    isa_settings: &SettingGroup,
+    regs: &'builder IsaRegs,
+) -> RecipeGroup<'builder> {
+    let formats = &shared_defs.format_registry;
+    let floatcc = &shared_defs.imm.floatcc;
+
+    // Format shorthands.
+    let f_atomic_cas = formats.by_name("AtomicCas");
+    let f_binary = formats.by_name("Binary");
+    let f_binary_imm = formats.by_name("BinaryImm");
+    let f_branch = formats.by_name("Branch");
+    let f_branch_float = formats.by_name("BranchFloat");
+    let f_branch_int = formats.by_name("BranchInt");
+    let f_call = formats.by_name("Call");
+    let f_call_indirect = formats.by_name("CallIndirect");
+    let f_copy_special = formats.by_name("CopySpecial");
+    let f_extract_lane = formats.by_name("ExtractLane");
+    let f_float_compare = formats.by_name("FloatCompare");
+    let f_float_cond = formats.by_name("FloatCond");
+    let f_float_cond_trap = formats.by_name("FloatCondTrap");
+    let f_func_addr = formats.by_name("FuncAddr");
+    let f_insert_lane = formats.by_name("InsertLane");
+    let f_int_compare = formats.by_name("IntCompare");
+    let f_int_compare_imm = formats.by_name("IntCompareImm");
+    let f_int_cond = formats.by_name("IntCond");
+    let f_int_cond_trap = formats.by_name("IntCondTrap");
+    let f_int_select = formats.by_name("IntSelect");
+    let f_jump = formats.by_name("Jump");
+    let f_load = formats.by_name("Load");
+    let f_load_complex = formats.by_name("LoadComplex");
+    let f_multiary = formats.by_name("MultiAry");
+    let f_nullary = formats.by_name("NullAry");
+    let f_regfill = formats.by_name("RegFill");
+    let f_regmove = formats.by_name("RegMove");
+    let f_regspill = formats.by_name("RegSpill");
+    let f_stack_load = formats.by_name("StackLoad");
+    let f_store = formats.by_name("Store");
+    let f_store_complex = formats.by_name("StoreComplex");
+    let f_ternary = formats.by_name("Ternary");
+    let f_trap = formats.by_name("Trap");
+    let f_unary = formats.by_name("Unary");
+    let f_unary_bool = formats.by_name("UnaryBool");
+    let f_unary_global_value = formats.by_name("UnaryGlobalValue");
+    let f_unary_ieee32 = formats.by_name("UnaryIeee32");
+    let f_unary_ieee64 = formats.by_name("UnaryIeee64");
+    let f_unary_imm = formats.by_name("UnaryImm");
+
+    // Register classes shorthands.
+    let abcd = OperandConstraint::from(regs.class_by_name("ABCD"));
+    let gpr = OperandConstraint::from(regs.class_by_name("GPR"));
+    let gpr_deref_safe = OperandConstraint::from(regs.class_by_name("GPR_DEREF_SAFE"));
+    let gpr_zero_deref_safe = OperandConstraint::from(regs.class_by_name("GPR_ZERO_DEREF_SAFE"));
+    let fpr = OperandConstraint::from(regs.class_by_name("FPR"));
+
+    // Specific registers and stack slots shorthands.
+    let gpr_class = regs.class_by_name("GPR");
+    let fpr_class = regs.class_by_name("FPR");
+    let flag_class = regs.class_by_name("FLAG");
+    let reg_rax = OperandConstraint::from(Register::new(
+        gpr_class,
+        regs.regunit_by_name(gpr_class, "rax"),
+    ));
+    let reg_rcx = OperandConstraint::from(Register::new(
+        gpr_class,
+        regs.regunit_by_name(gpr_class, "rcx"),
+    ));
+    let reg_rdx = OperandConstraint::from(Register::new(
+        gpr_class,
+        regs.regunit_by_name(gpr_class, "rdx"),
+    ));
+    let reg_xmm0 = OperandConstraint::from(Register::new(
+        fpr_class,
+        regs.regunit_by_name(fpr_class, "xmm0"),
+    ));
+    let reg_rflags = OperandConstraint::from(Register::new(
+        flag_class,
+        regs.regunit_by_name(flag_class, "rflags"),
+    ));
+    let stack_gpr32 = OperandConstraint::from(Stack::new(gpr_class));
+    let stack_fpr32 = OperandConstraint::from(Stack::new(fpr_class));
+
+    // Predicates shorthands.
+    let use_sse41 = isa_settings.predicate_by_name("use_sse41");
+    let use_avx = isa_settings.predicate_by_name("use_avx");
+    let use_bmi1 = isa_settings.predicate_by_name("use_bmi1");
+    let use_bmi2 = isa_settings.predicate_by_name("use_bmi2");
+
+    let mut recipes = RecipeGroup::new(formats, regs);
+
+    // A null unary instruction that takes a GPR register. Can be used for identity
+    // copies and no-op conversions.
+    recipes.add_recipe(
+        EncodingRecipeBuilder::new("null", f_unary, 0)
+            .operands_in(vec![gpr])
+            .operands_out(vec![OperandConstraint::TiedInput(0)])
+            .emit(""),
+    );
+
+    // The same null instruction for an FPR register. Used for no-op conversions
+    // between values held in XMM registers.
+    recipes.add_recipe(
+        EncodingRecipeBuilder::new("null_fpr", f_unary, 0)
+            .operands_in(vec![fpr])
+            .operands_out(vec![OperandConstraint::TiedInput(0)])
+            .emit(""),
+    );
+
+    // XX opcode, no ModR/M.
+    recipes.add_template_recipe(EncodingRecipeBuilder::new("trap", f_trap, 0).emit(
+        r#"
+                    sink.trap(code, func.srclocs[inst]);
+                    PUT_OP(bits, BASE_REX, sink);
+                "#,
+    ));
+
+    // Macro: conditional jump over a ud2.
+    recipes.add_recipe(
+        EncodingRecipeBuilder::new("trapif", f_int_cond_trap, 4)
+            .operands_in(vec![reg_rflags])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    // Jump over a 2-byte ud2.
+                    sink.put1(0x70 | (icc2opc(cond.inverse()) as u8));
+                    sink.put1(2);
+                    // ud2.
+                    sink.trap(code, func.srclocs[inst]);
+                    sink.put1(0x0f);
+                    sink.put1(0x0b);
+                "#,
+            ),
+    );
+
+    recipes.add_recipe(
+        EncodingRecipeBuilder::new("trapff", f_float_cond_trap, 4)
+            .operands_in(vec![reg_rflags])
+            .clobbers_flags(false)
+            .inst_predicate(floatccs(formats.get(f_float_cond_trap), floatcc))
+            .emit(
+                r#"
+                    // Jump over a 2-byte ud2.
+                    sink.put1(0x70 | (fcc2opc(cond.inverse()) as u8));
+                    sink.put1(2);
+                    // ud2.
+                    sink.trap(code, func.srclocs[inst]);
+                    sink.put1(0x0f);
+                    sink.put1(0x0b);
+                "#,
+            ),
+    );
+
+    // XX /r
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("rr", f_binary, 1)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![OperandConstraint::TiedInput(0)])
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(in_reg0, in_reg1), sink);
+                    modrm_rr(in_reg0, in_reg1, sink);
+                "#,
+            ),
+    );
+
+    // XX /r with operands swapped. (RM form).
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("rrx", f_binary, 1)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![OperandConstraint::TiedInput(0)])
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+                    modrm_rr(in_reg1, in_reg0, sink);
+                "#,
+            ),
+    );
+
+    // XX /r with FPR ins and outs. A form.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("fa", f_binary, 1)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![OperandConstraint::TiedInput(0)])
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+                    modrm_rr(in_reg1, in_reg0, sink);
+                "#,
+            ),
+    );
+
+    // XX /r with FPR ins and outs. A form with input operands swapped.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("fax", f_binary, 1)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![OperandConstraint::TiedInput(1)])
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(in_reg0, in_reg1), sink);
+                    modrm_rr(in_reg0, in_reg1, sink);
+                "#,
+            ),
+    );
+
+    // XX /n for a unary operation with extension bits.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("ur", f_unary, 1)
+            .operands_in(vec![gpr])
+            .operands_out(vec![OperandConstraint::TiedInput(0)])
+            .emit(
+                r#"
+                    PUT_OP(bits, rex1(in_reg0), sink);
+                    modrm_r_bits(in_reg0, bits, sink);
+                "#,
+            ),
+    );
+
+    // XX /r, but for a unary operator with separate input/output register, like
+    // copies. MR form, preserving flags.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("umr", f_unary, 1)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(out_reg0, in_reg0), sink);
+                    modrm_rr(out_reg0, in_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Same as umr, but with FPR -> GPR registers.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("rfumr", f_unary, 1)
+            .operands_in(vec![fpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(out_reg0, in_reg0), sink);
+                    modrm_rr(out_reg0, in_reg0, sink);
+                "#,
+            ),
+    );
+
+    // XX /r, but for a unary operator with separate input/output register.
+    // RM form. Clobbers FLAGS.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("urm", f_unary, 1)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(in_reg0, out_reg0), sink);
+                    modrm_rr(in_reg0, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // XX /r. Same as urm, but doesn't clobber FLAGS.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("urm_noflags", f_unary, 1)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(in_reg0, out_reg0), sink);
+                    modrm_rr(in_reg0, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // XX /r. Same as urm_noflags, but input limited to ABCD.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("urm_noflags_abcd", f_unary, 1)
+                .operands_in(vec![abcd])
+                .operands_out(vec![gpr])
+                .clobbers_flags(false)
+                .emit(
+                    r#"
+                        PUT_OP(bits, rex2(in_reg0, out_reg0), sink);
+                        modrm_rr(in_reg0, out_reg0, sink);
+                    "#,
+                ),
+            regs,
+        )
+        .when_prefixed(recipes.template("urm_noflags").clone()),
+    );
+
+    // XX /r, RM form, FPR -> FPR.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("furm", f_unary, 1)
+            .operands_in(vec![fpr])
+            .operands_out(vec![fpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(in_reg0, out_reg0), sink);
+                    modrm_rr(in_reg0, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // XX /r, RM form, GPR -> FPR.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("frurm", f_unary, 1)
+            .operands_in(vec![gpr])
+            .operands_out(vec![fpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(in_reg0, out_reg0), sink);
+                    modrm_rr(in_reg0, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // XX /r, RM form, FPR -> GPR.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("rfurm", f_unary, 1)
+            .operands_in(vec![fpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(in_reg0, out_reg0), sink);
+                    modrm_rr(in_reg0, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // XX /r, RMI form for one of the roundXX SSE 4.1 instructions.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("furmi_rnd", f_unary, 2)
+            .operands_in(vec![fpr])
+            .operands_out(vec![fpr])
+            .isa_predicate(use_sse41)
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(in_reg0, out_reg0), sink);
+                    modrm_rr(in_reg0, out_reg0, sink);
+                    sink.put1(match opcode {
+                        Opcode::Nearest => 0b00,
+                        Opcode::Floor => 0b01,
+                        Opcode::Ceil => 0b10,
+                        Opcode::Trunc => 0b11,
+                        x => panic!("{} unexpected for furmi_rnd", opcode),
+                    });
+                "#,
+            ),
+    );
+
+    // XX /r ib, RMI form, FPR -> FPR with the lane immediate. Used for pshufd.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("furmi_lane", f_extract_lane, 2)
+            .operands_in(vec![fpr])
+            .operands_out(vec![fpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(in_reg0, out_reg0), sink);
+                    modrm_rr(in_reg0, out_reg0, sink);
+                    sink.put1(lane);
+                "#,
+            ),
+    );
+
+    // XX /r ib, shuffling an FPR with itself to move a lane into the lowest lane.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("fshuf_lane", f_extract_lane, 2)
+            .operands_in(vec![fpr])
+            .operands_out(vec![OperandConstraint::TiedInput(0)])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(in_reg0, in_reg0), sink);
+                    modrm_rr(in_reg0, in_reg0, sink);
+                    sink.put1(lane);
+                "#,
+            ),
+    );
+
+    // XX /r ib, MR form, extracting a vector lane from an FPR into a GPR.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("rfumr_lane", f_extract_lane, 2)
+            .operands_in(vec![fpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .isa_predicate(use_sse41)
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(out_reg0, in_reg0), sink);
+                    modrm_rr(out_reg0, in_reg0, sink);
+                    sink.put1(lane);
+                "#,
+            ),
+    );
+
+    // XX /r ib, RMI form, inserting a GPR into a vector lane.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("frurmi_lane", f_insert_lane, 2)
+            .operands_in(vec![fpr, gpr])
+            .operands_out(vec![OperandConstraint::TiedInput(0)])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+                    modrm_rr(in_reg1, in_reg0, sink);
+                    sink.put1(lane);
+                "#,
+            ),
+    );
+
+    // XX /r ib, RMI form for insertps. The destination lane goes in bits 4-5 of
+    // the immediate.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("fa_insertps", f_insert_lane, 2)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![OperandConstraint::TiedInput(0)])
+            .clobbers_flags(false)
+            .isa_predicate(use_sse41)
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+                    modrm_rr(in_reg1, in_reg0, sink);
+                    sink.put1(lane << 4);
+                "#,
+            ),
+    );
+
+    // XX /r, RM form, inserting the lowest lane of an FPR into a vector lane. The
+    // lane is implied by the opcode.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("fa_lane", f_insert_lane, 1)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![OperandConstraint::TiedInput(0)])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+                    modrm_rr(in_reg1, in_reg0, sink);
+                "#,
+            ),
+    );
+
+    // XX /r, RM form for the SSE 4.1 variable blends. The mask is implicitly in
+    // %xmm0, and the lanes of the first operand are blended into the second.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("blend", f_ternary, 1)
+            .operands_in(vec![reg_xmm0, fpr, fpr])
+            .operands_out(vec![OperandConstraint::TiedInput(2)])
+            .clobbers_flags(false)
+            .isa_predicate(use_sse41)
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(in_reg1, in_reg2), sink);
+                    modrm_rr(in_reg1, in_reg2, sink);
+                "#,
+            ),
+    );
+
+    // XX /r, for regmove instructions.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("rmov", f_regmove, 1)
+            .operands_in(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(dst, src), sink);
+                    modrm_rr(dst, src, sink);
+                "#,
+            ),
+    );
+
+    // XX /r, for regmove instructions (FPR version, RM encoded).
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("frmov", f_regmove, 1)
+            .operands_in(vec![fpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(src, dst), sink);
+                    modrm_rr(src, dst, sink);
+                "#,
+            ),
+    );
+
+    // XX /n with one arg in %rcx, for shifts.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("rc", f_binary, 1)
+            .operands_in(vec![gpr, reg_rcx])
+            .operands_out(vec![OperandConstraint::TiedInput(0)])
+            .emit(
+                r#"
+                    PUT_OP(bits, rex1(in_reg0), sink);
+                    modrm_r_bits(in_reg0, bits, sink);
+                "#,
+            ),
+    );
+
+    // XX /n for division: inputs in %rax, %rdx, r. Outputs in %rax, %rdx.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("div", f_ternary, 1)
+            .operands_in(vec![reg_rax, reg_rdx, gpr])
+            .operands_out(vec![reg_rax, reg_rdx])
+            .emit(
+                r#"
+                    sink.trap(TrapCode::IntegerDivisionByZero, func.srclocs[inst]);
+                    PUT_OP(bits, rex1(in_reg2), sink);
+                    modrm_r_bits(in_reg2, bits, sink);
+                "#,
+            ),
+    );
+
+    // XX /n for {s,u}mulx: inputs in %rax, r. Outputs in %rdx(hi):%rax(lo)
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("mulx", f_binary, 1)
+            .operands_in(vec![reg_rax, gpr])
+            .operands_out(vec![reg_rax, reg_rdx])
+            .emit(
+                r#"
+                    PUT_OP(bits, rex1(in_reg1), sink);
+                    modrm_r_bits(in_reg1, bits, sink);
+                "#,
+            ),
+    );
+
+    // XX /n ib with 8-bit immediate sign-extended.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("r_ib", f_binary_imm, 2)
+            .operands_in(vec![gpr])
+            .operands_out(vec![OperandConstraint::TiedInput(0)])
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_binary_imm),
+                "imm",
+                8,
+                0,
+            ))
+            .emit(
+                r#"
+                    PUT_OP(bits, rex1(in_reg0), sink);
+                    modrm_r_bits(in_reg0, bits, sink);
+                    let imm: i64 = imm.into();
+                    sink.put1(imm as u8);
+                "#,
+            ),
+    );
+
+    // XX /n id with 32-bit immediate sign-extended.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("r_id", f_binary_imm, 5)
+            .operands_in(vec![gpr])
+            .operands_out(vec![OperandConstraint::TiedInput(0)])
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_binary_imm),
+                "imm",
+                32,
+                0,
+            ))
+            .emit(
+                r#"
+                    PUT_OP(bits, rex1(in_reg0), sink);
+                    modrm_r_bits(in_reg0, bits, sink);
+                    let imm: i64 = imm.into();
+                    sink.put4(imm as u32);
+                "#,
+            ),
+    );
+
+    // XX /n id with 32-bit immediate sign-extended. UnaryImm version.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("u_id", f_unary_imm, 5)
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_unary_imm),
+                "imm",
+                32,
+                0,
+            ))
+            .emit(
+                r#"
+                    PUT_OP(bits, rex1(out_reg0), sink);
+                    modrm_r_bits(out_reg0, bits, sink);
+                    let imm: i64 = imm.into();
+                    sink.put4(imm as u32);
+                "#,
+            ),
+    );
+
+    // XX+rd id unary with 32-bit immediate. Note no recipe predicate.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("pu_id", f_unary_imm, 4)
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    // The destination register is encoded in the low bits of the opcode.
+                    // No ModR/M.
+                    PUT_OP(bits | (out_reg0 & 7), rex1(out_reg0), sink);
+                    let imm: i64 = imm.into();
+                    sink.put4(imm as u32);
+                "#,
+            ),
+    );
+
+    // XX+rd id unary with bool immediate. Note no recipe predicate.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("pu_id_bool", f_unary_bool, 4)
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    // The destination register is encoded in the low bits of the opcode.
+                    // No ModR/M.
+                    PUT_OP(bits | (out_reg0 & 7), rex1(out_reg0), sink);
+                    let imm: u32 = if imm { 1 } else { 0 };
+                    sink.put4(imm);
+                "#,
+            ),
+    );
+
+    // XX+rd iq unary with 64-bit immediate.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("pu_iq", f_unary_imm, 8)
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    PUT_OP(bits | (out_reg0 & 7), rex1(out_reg0), sink);
+                    let imm: i64 = imm.into();
+                    sink.put8(imm as u64);
+                "#,
+            ),
+    );
+
+    // XX /n Unary with floating point 32-bit immediate equal to zero.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("f32imm_z", f_unary_ieee32, 1)
+            .operands_out(vec![fpr])
+            .inst_predicate(InstructionPredicate::is_zero_32_bit_float(
+                formats.get(f_unary_ieee32),
+                "imm",
+            ))
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(out_reg0, out_reg0), sink);
+                    modrm_rr(out_reg0, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // XX /n Unary with floating point 64-bit immediate equal to zero.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("f64imm_z", f_unary_ieee64, 1)
+            .operands_out(vec![fpr])
+            .inst_predicate(InstructionPredicate::is_zero_64_bit_float(
+                formats.get(f_unary_ieee64),
+                "imm",
+            ))
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(out_reg0, out_reg0), sink);
+                    modrm_rr(out_reg0, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("pushq", f_unary, 0)
+            .operands_in(vec![gpr])
+            .emit(
+                r#"
+                    sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                    PUT_OP(bits | (in_reg0 & 7), rex1(in_reg0), sink);
+                "#,
+            ),
+    );
+
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("popq", f_nullary, 0)
+            .operands_out(vec![gpr])
+            .emit("PUT_OP(bits | (out_reg0 & 7), rex1(out_reg0), sink);"),
+    );
+
+    // XX /r, for regmove instructions.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("copysp", f_copy_special, 1)
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(dst, src), sink);
+                    modrm_rr(dst, src, sink);
+                "#,
+            ),
+    );
+
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("adjustsp", f_unary, 1)
+            .operands_in(vec![gpr])
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(RU::rsp.into(), in_reg0), sink);
+                    modrm_rr(RU::rsp.into(), in_reg0, sink);
+                "#,
+            ),
+    );
+
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("adjustsp_ib", f_unary_imm, 2)
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_unary_imm),
+                "imm",
+                8,
+                0,
+            ))
+            .emit(
+                r#"
+                    PUT_OP(bits, rex1(RU::rsp.into()), sink);
+                    modrm_r_bits(RU::rsp.into(), bits, sink);
+                    let imm: i64 = imm.into();
+                    sink.put1(imm as u8);
+                "#,
+            ),
+    );
+
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("adjustsp_id", f_unary_imm, 5)
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_unary_imm),
+                "imm",
+                32,
+                0,
+            ))
+            .emit(
+                r#"
+                    PUT_OP(bits, rex1(RU::rsp.into()), sink);
+                    modrm_r_bits(RU::rsp.into(), bits, sink);
+                    let imm: i64 = imm.into();
+                    sink.put4(imm as u32);
+                "#,
+            ),
+    );
+
+    // XX+rd id with Abs4 function relocation.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("fnaddr4", f_func_addr, 4)
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    PUT_OP(bits | (out_reg0 & 7), rex1(out_reg0), sink);
+                    sink.reloc_external(Reloc::Abs4,
+                                        &func.dfg.ext_funcs[func_ref].name,
+                                        0);
+                    sink.put4(0);
+                "#,
+            ),
+    );
+
+    // XX+rd iq with Abs8 function relocation.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("fnaddr8", f_func_addr, 8)
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    PUT_OP(bits | (out_reg0 & 7), rex1(out_reg0), sink);
+                    sink.reloc_external(Reloc::Abs8,
+                                        &func.dfg.ext_funcs[func_ref].name,
+                                        0);
+                    sink.put8(0);
+                "#,
+            ),
+    );
+
+    // Similar to fnaddr4, but writes !0 (this is used by BaldrMonkey).
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("allones_fnaddr4", f_func_addr, 4)
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    PUT_OP(bits | (out_reg0 & 7), rex1(out_reg0), sink);
+                    sink.reloc_external(Reloc::Abs4,
+                                        &func.dfg.ext_funcs[func_ref].name,
+                                        0);
+                    // Write the immediate as `!0` for the benefit of BaldrMonkey.
+                    sink.put4(!0);
+                "#,
+            ),
+    );
+
+    // Similar to fnaddr8, but writes !0 (this is used by BaldrMonkey).
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("allones_fnaddr8", f_func_addr, 8)
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    PUT_OP(bits | (out_reg0 & 7), rex1(out_reg0), sink);
+                    sink.reloc_external(Reloc::Abs8,
+                                        &func.dfg.ext_funcs[func_ref].name,
+                                        0);
+                    // Write the immediate as `!0` for the benefit of BaldrMonkey.
+                    sink.put8(!0);
+                "#,
+            ),
+    );
+
+    // rex2 gets passed 0 for r/m register because the upper bit of
+    // r/m doesnt get decoded when in rip-relative addressing mode.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("pcrel_fnaddr8", f_func_addr, 5)
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(0, out_reg0), sink);
+                    modrm_riprel(out_reg0, sink);
+                    // The addend adjusts for the difference between the end of the
+                    // instruction and the beginning of the immediate field.
+                    sink.reloc_external(Reloc::X86PCRel4,
+                                        &func.dfg.ext_funcs[func_ref].name,
+                                        -4);
+                    sink.put4(0);
+                "#,
+            ),
+    );
+
+    // rex2 gets passed 0 for r/m register because the upper bit of
+    // r/m doesnt get decoded when in rip-relative addressing mode.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("got_fnaddr8", f_func_addr, 5)
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(0, out_reg0), sink);
+                    modrm_riprel(out_reg0, sink);
+                    // The addend adjusts for the difference between the end of the
+                    // instruction and the beginning of the immediate field.
+                    sink.reloc_external(Reloc::X86GOTPCRel4,
+                                        &func.dfg.ext_funcs[func_ref].name,
+                                        -4);
+                    sink.put4(0);
+                "#,
+            ),
+    );
+
+    // XX+rd id with Abs4 globalsym relocation.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("gvaddr4", f_unary_global_value, 4)
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    PUT_OP(bits | (out_reg0 & 7), rex1(out_reg0), sink);
+                    sink.reloc_external(Reloc::Abs4,
+                                        &func.global_values[global_value].symbol_name(),
+                                        0);
+                    sink.put4(0);
+                "#,
+            ),
+    );
+
+    // XX+rd iq with Abs8 globalsym relocation.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("gvaddr8", f_unary_global_value, 8)
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    PUT_OP(bits | (out_reg0 & 7), rex1(out_reg0), sink);
+                    sink.reloc_external(Reloc::Abs8,
+                                        &func.global_values[global_value].symbol_name(),
+                                        0);
+                    sink.put8(0);
+                "#,
+            ),
+    );
+
+    // XX+rd iq with PCRel4 globalsym relocation.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("pcrel_gvaddr8", f_unary_global_value, 5)
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(0, out_reg0), sink);
+                    modrm_rm(5, out_reg0, sink);
+                    // The addend adjusts for the difference between the end of the
+                    // instruction and the beginning of the immediate field.
+                    sink.reloc_external(Reloc::X86PCRel4,
+                                        &func.global_values[global_value].symbol_name(),
+                                        -4);
+                    sink.put4(0);
+                "#,
+            ),
+    );
+
+    // XX+rd iq with Abs8 globalsym relocation.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("got_gvaddr8", f_unary_global_value, 5)
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(0, out_reg0), sink);
+                    modrm_rm(5, out_reg0, sink);
+                    // The addend adjusts for the difference between the end of the
+                    // instruction and the beginning of the immediate field.
+                    sink.reloc_external(Reloc::X86GOTPCRel4,
+                                        &func.global_values[global_value].symbol_name(),
+                                        -4);
+                    sink.put4(0);
+                "#,
+            ),
+    );
+
+    //
+    // Stack addresses.
+    //
+    // TODO: Alternative forms for 8-bit immediates, when applicable.
+    //
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("spaddr4_id", f_stack_load, 6)
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    let sp = StackRef::sp(stack_slot, &func.stack_slots);
+                    let base = stk_base(sp.base);
+                    PUT_OP(bits, rex2(out_reg0, base), sink);
+                    modrm_sib_disp8(out_reg0, sink);
+                    sib_noindex(base, sink);
+                    let imm : i32 = offset.into();
+                    sink.put4(sp.offset.checked_add(imm).unwrap() as u32);
+                "#,
+            ),
+    );
+
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("spaddr8_id", f_stack_load, 6)
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    let sp = StackRef::sp(stack_slot, &func.stack_slots);
+                    let base = stk_base(sp.base);
+                    PUT_OP(bits, rex2(base, out_reg0), sink);
+                    modrm_sib_disp32(out_reg0, sink);
+                    sib_noindex(base, sink);
+                    let imm : i32 = offset.into();
+                    sink.put4(sp.offset.checked_add(imm).unwrap() as u32);
+                "#,
+            ),
+    );
+
+    //
+    // Store recipes.
+    //
+
+    // XX /r register-indirect store with no offset.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("st", f_store, 1)
+            .operands_in(vec![gpr, gpr_zero_deref_safe])
+            .clobbers_flags(false)
+            .inst_predicate(InstructionPredicate::is_equal(
+                formats.get(f_store),
+                "offset",
+                "0",
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+                    modrm_rm(in_reg1, in_reg0, sink);
+                "#,
+            ),
+    );
+
+    // XX /r register-indirect store with index and no offset.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("stWithIndex", f_store_complex, 2)
+            .operands_in(vec![gpr, gpr_zero_deref_safe, gpr_deref_safe])
+            .clobbers_flags(false)
+            .inst_predicate(InstructionPredicate::is_equal(
+                formats.get(f_store_complex),
+                "offset",
+                "0",
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    PUT_OP(bits, rex3(in_reg1, in_reg0, in_reg2), sink);
+                    modrm_sib(in_reg0, sink);
+                    sib(0, in_reg2, in_reg1, sink);
+                "#,
+            ),
+    );
+
+    // XX /r register-indirect store with no offset.
+    // Only ABCD allowed for stored value. This is for byte stores with no REX.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("st_abcd", f_store, 1)
+                .operands_in(vec![abcd, gpr])
+                .clobbers_flags(false)
+                .inst_predicate(InstructionPredicate::is_equal(
+                    formats.get(f_store),
+                    "offset",
+                    "0",
+                ))
+                .emit(
+                    r#"
+                        if !flags.notrap() {
+                            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                        }
+                        PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+                        modrm_rm(in_reg1, in_reg0, sink);
+                    "#,
+                ),
+            regs,
+        )
+        .when_prefixed(recipes.template("st").clone()),
+    );
+
+    // XX /r register-indirect store with index and no offset.
+    // Only ABCD allowed for stored value. This is for byte stores with no REX.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("stWithIndex_abcd", f_store_complex, 2)
+            .operands_in(vec![abcd, gpr_zero_deref_safe, gpr_deref_safe])
+            .clobbers_flags(false)
+            .inst_predicate(InstructionPredicate::is_equal(
+                formats.get(f_store_complex),
+                "offset",
+                "0",
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    PUT_OP(bits, rex3(in_reg1, in_reg0, in_reg2), sink);
+                    modrm_sib(in_reg0, sink);
+                    sib(0, in_reg2, in_reg1, sink);
+                "#,
+            ),
+    );
+
+    // XX /r register-indirect store of FPR with no offset.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("fst", f_store, 1)
+            .operands_in(vec![fpr, gpr_zero_deref_safe])
+            .clobbers_flags(false)
+            .inst_predicate(InstructionPredicate::is_equal(
+                formats.get(f_store),
+                "offset",
+                "0",
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+                    modrm_rm(in_reg1, in_reg0, sink);
+                "#,
+            ),
+    );
+
+    // XX /r register-indirect store with index and no offset of FPR.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("fstWithIndex", f_store_complex, 2)
+            .operands_in(vec![fpr, gpr_zero_deref_safe, gpr_deref_safe])
+            .clobbers_flags(false)
+            .inst_predicate(InstructionPredicate::is_equal(
+                formats.get(f_store_complex),
+                "offset",
+                "0",
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    PUT_OP(bits, rex3(in_reg1, in_reg0, in_reg2), sink);
+                    modrm_sib(in_reg0, sink);
+                    sib(0, in_reg2, in_reg1, sink);
+                "#,
+            ),
+    );
+
+    // XX /r register-indirect store with 8-bit offset.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("stDisp8", f_store, 2)
+            .operands_in(vec![gpr, gpr_deref_safe])
+            .clobbers_flags(false)
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_store),
+                "offset",
+                8,
+                0,
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+                    modrm_disp8(in_reg1, in_reg0, sink);
+                    let offset: i32 = offset.into();
+                    sink.put1(offset as u8);
+                "#,
+            ),
+    );
+
+    // XX /r register-indirect store with index and 8-bit offset.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("stWithIndexDisp8", f_store_complex, 3)
+            .operands_in(vec![gpr, gpr, gpr_deref_safe])
+            .clobbers_flags(false)
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_store_complex),
+                "offset",
+                8,
+                0,
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    PUT_OP(bits, rex3(in_reg1, in_reg0, in_reg2), sink);
+                    modrm_sib_disp8(in_reg0, sink);
+                    sib(0, in_reg2, in_reg1, sink);
+                    let offset: i32 = offset.into();
+                    sink.put1(offset as u8);
+                "#,
+            ),
+    );
+
+    // XX /r register-indirect store with 8-bit offset.
+    // Only ABCD allowed for stored value. This is for byte stores with no REX.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("stDisp8_abcd", f_store, 2)
+                .operands_in(vec![abcd, gpr])
+                .clobbers_flags(false)
+                .inst_predicate(InstructionPredicate::is_signed_int(
+                    formats.get(f_store),
+                    "offset",
+                    8,
+                    0,
+                ))
+                .emit(
+                    r#"
+                        if !flags.notrap() {
+                            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                        }
+                        PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+                        modrm_disp8(in_reg1, in_reg0, sink);
+                        let offset: i32 = offset.into();
+                        sink.put1(offset as u8);
+                    "#,
+                ),
+            regs,
+        )
+        .when_prefixed(recipes.template("stDisp8").clone()),
+    );
+
+    // XX /r register-indirect store with index and 8-bit offset.
+    // Only ABCD allowed for stored value. This is for byte stores with no REX.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("stWithIndexDisp8_abcd", f_store_complex, 3)
+            .operands_in(vec![abcd, gpr, gpr_deref_safe])
+            .clobbers_flags(false)
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_store_complex),
+                "offset",
+                8,
+                0,
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    PUT_OP(bits, rex3(in_reg1, in_reg0, in_reg2), sink);
+                    modrm_sib_disp8(in_reg0, sink);
+                    sib(0, in_reg2, in_reg1, sink);
+                    let offset: i32 = offset.into();
+                    sink.put1(offset as u8);
+                "#,
+            ),
+    );
+
+    // XX /r register-indirect store with 8-bit offset of FPR.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("fstDisp8", f_store, 2)
+            .operands_in(vec![fpr, gpr_deref_safe])
+            .clobbers_flags(false)
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_store),
+                "offset",
+                8,
+                0,
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+                    modrm_disp8(in_reg1, in_reg0, sink);
+                    let offset: i32 = offset.into();
+                    sink.put1(offset as u8);
+                "#,
+            ),
+    );
+
+    // XX /r register-indirect store with index and 8-bit offset of FPR.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("fstWithIndexDisp8", f_store_complex, 3)
+            .operands_in(vec![fpr, gpr, gpr_deref_safe])
+            .clobbers_flags(false)
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_store_complex),
+                "offset",
+                8,
+                0,
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    PUT_OP(bits, rex3(in_reg1, in_reg0, in_reg2), sink);
+                    modrm_sib_disp8(in_reg0, sink);
+                    sib(0, in_reg2, in_reg1, sink);
+                    let offset: i32 = offset.into();
+                    sink.put1(offset as u8);
+                "#,
+            ),
+    );
+
+    // XX /r register-indirect store with 32-bit offset.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("stDisp32", f_store, 5)
+            .operands_in(vec![gpr, gpr_deref_safe])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+                    modrm_disp32(in_reg1, in_reg0, sink);
+                    let offset: i32 = offset.into();
+                    sink.put4(offset as u32);
+                "#,
+            ),
+    );
+
+    // XX /r register-indirect store with index and 32-bit offset.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("stWithIndexDisp32", f_store_complex, 6)
+            .operands_in(vec![gpr, gpr, gpr_deref_safe])
+            .clobbers_flags(false)
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_store_complex),
+                "offset",
+                32,
+                0,
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    PUT_OP(bits, rex3(in_reg1, in_reg0, in_reg2), sink);
+                    modrm_sib_disp32(in_reg0, sink);
+                    sib(0, in_reg2, in_reg1, sink);
+                    let offset: i32 = offset.into();
+                    sink.put4(offset as u32);
+                "#,
+            ),
+    );
+
+    // XX /r register-indirect store with 32-bit offset.
+    // Only ABCD allowed for stored value. This is for byte stores with no REX.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("stDisp32_abcd", f_store, 5)
+                .operands_in(vec![abcd, gpr])
+                .clobbers_flags(false)
+                .emit(
+                    r#"
+                        if !flags.notrap() {
+                            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                        }
+                        PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+                        modrm_disp32(in_reg1, in_reg0, sink);
+                        let offset: i32 = offset.into();
+                        sink.put4(offset as u32);
+                    "#,
+                ),
+            regs,
+        )
+        .when_prefixed(recipes.template("stDisp32").clone()),
+    );
+
+    // XX /r register-indirect store with index and 32-bit offset.
+    // Only ABCD allowed for stored value. This is for byte stores with no REX.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("stWithIndexDisp32_abcd", f_store_complex, 6)
+            .operands_in(vec![abcd, gpr, gpr_deref_safe])
+            .clobbers_flags(false)
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_store_complex),
+                "offset",
+                32,
+                0,
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    PUT_OP(bits, rex3(in_reg1, in_reg0, in_reg2), sink);
+                    modrm_sib_disp32(in_reg0, sink);
+                    sib(0, in_reg2, in_reg1, sink);
+                    let offset: i32 = offset.into();
+                    sink.put4(offset as u32);
+                "#,
+            ),
+    );
+
+    // XX /r register-indirect store with 32-bit offset of FPR.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("fstDisp32", f_store, 5)
+            .operands_in(vec![fpr, gpr_deref_safe])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+                    modrm_disp32(in_reg1, in_reg0, sink);
+                    let offset: i32 = offset.into();
+                    sink.put4(offset as u32);
+                "#,
+            ),
+    );
+
+    // XX /r register-indirect store with index and 32-bit offset of FPR.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("fstWithIndexDisp32", f_store_complex, 6)
+            .operands_in(vec![fpr, gpr, gpr_deref_safe])
+            .clobbers_flags(false)
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_store_complex),
+                "offset",
+                32,
+                0,
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    PUT_OP(bits, rex3(in_reg1, in_reg0, in_reg2), sink);
+                    modrm_sib_disp32(in_reg0, sink);
+                    sib(0, in_reg2, in_reg1, sink);
+                    let offset: i32 = offset.into();
+                    sink.put4(offset as u32);
+                "#,
+            ),
+    );
+
+    // Unary spill with SIB and 32-bit displacement.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("spillSib32", f_unary, 6)
+            .operands_in(vec![gpr])
+            .operands_out(vec![stack_gpr32])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                    let base = stk_base(out_stk0.base);
+                    PUT_OP(bits, rex2(base, in_reg0), sink);
+                    modrm_sib_disp32(in_reg0, sink);
+                    sib_noindex(base, sink);
+                    sink.put4(out_stk0.offset as u32);
+                "#,
+            ),
+    );
+
+    // Like spillSib32, but targeting an FPR rather than a GPR.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("fspillSib32", f_unary, 6)
+            .operands_in(vec![fpr])
+            .operands_out(vec![stack_fpr32])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                    let base = stk_base(out_stk0.base);
+                    PUT_OP(bits, rex2(base, in_reg0), sink);
+                    modrm_sib_disp32(in_reg0, sink);
+                    sib_noindex(base, sink);
+                    sink.put4(out_stk0.offset as u32);
+                "#,
+            ),
+    );
+
+    // Regspill using RSP-relative addressing.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("regspill32", f_regspill, 6)
+            .operands_in(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                    let dst = StackRef::sp(dst, &func.stack_slots);
+                    let base = stk_base(dst.base);
+                    PUT_OP(bits, rex2(base, src), sink);
+                    modrm_sib_disp32(src, sink);
+                    sib_noindex(base, sink);
+                    sink.put4(dst.offset as u32);
+                "#,
+            ),
+    );
+
+    // Like regspill32, but targeting an FPR rather than a GPR.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("fregspill32", f_regspill, 6)
+            .operands_in(vec![fpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                    let dst = StackRef::sp(dst, &func.stack_slots);
+                    let base = stk_base(dst.base);
+                    PUT_OP(bits, rex2(base, src), sink);
+                    modrm_sib_disp32(src, sink);
+                    sib_noindex(base, sink);
+                    sink.put4(dst.offset as u32);
+                "#,
+            ),
+    );
+
+    //
+    // Load recipes
+    //
+
+    // XX /r load with no offset.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("ld", f_load, 1)
+            .operands_in(vec![gpr_zero_deref_safe])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .inst_predicate(InstructionPredicate::is_equal(
+                formats.get(f_load),
+                "offset",
+                "0",
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    PUT_OP(bits, rex2(in_reg0, out_reg0), sink);
+                    modrm_rm(in_reg0, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // XX /r load with index and no offset.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("ldWithIndex", f_load_complex, 2)
+            .operands_in(vec![gpr_zero_deref_safe, gpr_deref_safe])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .inst_predicate(InstructionPredicate::is_equal(
+                formats.get(f_load_complex),
+                "offset",
+                "0",
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    PUT_OP(bits, rex3(in_reg0, out_reg0, in_reg1), sink);
+                    modrm_sib(out_reg0, sink);
+                    sib(0, in_reg1, in_reg0, sink);
+                "#,
+            ),
+    );
+
+    // XX /r float load with no offset.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("fld", f_load, 1)
+            .operands_in(vec![gpr_zero_deref_safe])
+            .operands_out(vec![fpr])
+            .clobbers_flags(false)
+            .inst_predicate(InstructionPredicate::is_equal(
+                formats.get(f_load),
+                "offset",
+                "0",
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    PUT_OP(bits, rex2(in_reg0, out_reg0), sink);
+                    modrm_rm(in_reg0, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // XX /r float load with index and no offset.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("fldWithIndex", f_load_complex, 2)
+            .operands_in(vec![gpr_zero_deref_safe, gpr_deref_safe])
+            .operands_out(vec![fpr])
+            .clobbers_flags(false)
+            .inst_predicate(InstructionPredicate::is_equal(
+                formats.get(f_load_complex),
+                "offset",
+                "0",
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    PUT_OP(bits, rex3(in_reg0, out_reg0, in_reg1), sink);
+                    modrm_sib(out_reg0, sink);
+                    sib(0, in_reg1, in_reg0, sink);
+                "#,
+            ),
+    );
+
+    // XX /r load with 8-bit offset.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("ldDisp8", f_load, 2)
+            .operands_in(vec![gpr_deref_safe])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_load),
+                "offset",
+                8,
+                0,
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    PUT_OP(bits, rex2(in_reg0, out_reg0), sink);
+                    modrm_disp8(in_reg0, out_reg0, sink);
+                    let offset: i32 = offset.into();
+                    sink.put1(offset as u8);
+                "#,
+            ),
+    );
+
+    // XX /r load with index and 8-bit offset.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("ldWithIndexDisp8", f_load_complex, 3)
+            .operands_in(vec![gpr, gpr_deref_safe])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_load_complex),
+                "offset",
+                8,
+                0,
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    PUT_OP(bits, rex3(in_reg0, out_reg0, in_reg1), sink);
+                    modrm_sib_disp8(out_reg0, sink);
+                    sib(0, in_reg1, in_reg0, sink);
+                    let offset: i32 = offset.into();
+                    sink.put1(offset as u8);
+                "#,
+            ),
+    );
+
+    // XX /r float load with 8-bit offset.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("fldDisp8", f_load, 2)
+            .operands_in(vec![gpr_deref_safe])
+            .operands_out(vec![fpr])
+            .clobbers_flags(false)
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_load),
+                "offset",
+                8,
+                0,
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    PUT_OP(bits, rex2(in_reg0, out_reg0), sink);
+                    modrm_disp8(in_reg0, out_reg0, sink);
+                    let offset: i32 = offset.into();
+                    sink.put1(offset as u8);
+                "#,
+            ),
+    );
+
+    // XX /r float load with 8-bit offset.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("fldWithIndexDisp8", f_load_complex, 3)
+            .operands_in(vec![gpr, gpr_deref_safe])
+            .operands_out(vec![fpr])
+            .clobbers_flags(false)
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_load_complex),
+                "offset",
+                8,
+                0,
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    PUT_OP(bits, rex3(in_reg0, out_reg0, in_reg1), sink);
+                    modrm_sib_disp8(out_reg0, sink);
+                    sib(0, in_reg1, in_reg0, sink);
+                    let offset: i32 = offset.into();
+                    sink.put1(offset as u8);
+                "#,
+            ),
+    );
+
+    // XX /r load with 32-bit offset.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("ldDisp32", f_load, 5)
+            .operands_in(vec![gpr_deref_safe])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_load),
+                "offset",
+                32,
+                0,
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    PUT_OP(bits, rex2(in_reg0, out_reg0), sink);
+                    modrm_disp32(in_reg0, out_reg0, sink);
+                    let offset: i32 = offset.into();
+                    sink.put4(offset as u32);
+                "#,
+            ),
+    );
+
+    // XX /r load with index and 32-bit offset.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("ldWithIndexDisp32", f_load_complex, 6)
+            .operands_in(vec![gpr, gpr_deref_safe])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_load_complex),
+                "offset",
+                32,
+                0,
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    PUT_OP(bits, rex3(in_reg0, out_reg0, in_reg1), sink);
+                    modrm_sib_disp32(out_reg0, sink);
+                    sib(0, in_reg1, in_reg0, sink);
+                    let offset: i32 = offset.into();
+                    sink.put4(offset as u32);
+                "#,
+            ),
+    );
+
+    // XX /r float load with 32-bit offset.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("fldDisp32", f_load, 5)
+            .operands_in(vec![gpr_deref_safe])
+            .operands_out(vec![fpr])
+            .clobbers_flags(false)
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_load),
+                "offset",
+                32,
+                0,
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    PUT_OP(bits, rex2(in_reg0, out_reg0), sink);
+                    modrm_disp32(in_reg0, out_reg0, sink);
+                    let offset: i32 = offset.into();
+                    sink.put4(offset as u32);
+                "#,
+            ),
+    );
+
+    // XX /r float load with index and 32-bit offset.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("fldWithIndexDisp32", f_load_complex, 6)
+            .operands_in(vec![gpr, gpr_deref_safe])
+            .operands_out(vec![fpr])
+            .clobbers_flags(false)
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_load_complex),
+                "offset",
+                32,
+                0,
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    PUT_OP(bits, rex3(in_reg0, out_reg0, in_reg1), sink);
+                    modrm_sib_disp32(out_reg0, sink);
+                    sib(0, in_reg1, in_reg0, sink);
+                    let offset: i32 = offset.into();
+                    sink.put4(offset as u32);
+                "#,
+            ),
+    );
+
+    // Unary fill with SIB and 32-bit displacement.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("fillSib32", f_unary, 6)
+            .operands_in(vec![stack_gpr32])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    let base = stk_base(in_stk0.base);
+                    PUT_OP(bits, rex2(base, out_reg0), sink);
+                    modrm_sib_disp32(out_reg0, sink);
+                    sib_noindex(base, sink);
+                    sink.put4(in_stk0.offset as u32);
+                "#,
+            ),
+    );
+
+    // Like fillSib32, but targeting an FPR rather than a GPR.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("ffillSib32", f_unary, 6)
+            .operands_in(vec![stack_fpr32])
+            .operands_out(vec![fpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    let base = stk_base(in_stk0.base);
+                    PUT_OP(bits, rex2(base, out_reg0), sink);
+                    modrm_sib_disp32(out_reg0, sink);
+                    sib_noindex(base, sink);
+                    sink.put4(in_stk0.offset as u32);
+                "#,
+            ),
+    );
+
+    // Regfill with RSP-relative 32-bit displacement.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("regfill32", f_regfill, 6)
+            .operands_in(vec![stack_gpr32])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    let src = StackRef::sp(src, &func.stack_slots);
+                    let base = stk_base(src.base);
+                    PUT_OP(bits, rex2(base, dst), sink);
+                    modrm_sib_disp32(dst, sink);
+                    sib_noindex(base, sink);
+                    sink.put4(src.offset as u32);
+                "#,
+            ),
+    );
+
+    // Like regfill32, but targeting an FPR rather than a GPR.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("fregfill32", f_regfill, 6)
+            .operands_in(vec![stack_fpr32])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    let src = StackRef::sp(src, &func.stack_slots);
+                    let base = stk_base(src.base);
+                    PUT_OP(bits, rex2(base, dst), sink);
+                    modrm_sib_disp32(dst, sink);
+                    sib_noindex(base, sink);
+                    sink.put4(src.offset as u32);
+                "#,
+            ),
+    );
+
+    //
+    // Call/return
+    //
+    recipes.add_template_recipe(EncodingRecipeBuilder::new("call_id", f_call, 4).emit(
+        r#"
+                    sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                    PUT_OP(bits, BASE_REX, sink);
+                    // The addend adjusts for the difference between the end of the
+                    // instruction and the beginning of the immediate field.
+                    sink.reloc_external(Reloc::X86CallPCRel4,
+                                        &func.dfg.ext_funcs[func_ref].name,
+                                        -4);
+                    sink.put4(0);
+                "#,
+    ));
+
+    recipes.add_template_recipe(EncodingRecipeBuilder::new("call_plt_id", f_call, 4).emit(
+        r#"
+                    sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                    PUT_OP(bits, BASE_REX, sink);
+                    sink.reloc_external(Reloc::X86CallPLTRel4,
+                                        &func.dfg.ext_funcs[func_ref].name,
+                                        -4);
+                    sink.put4(0);
+                "#,
+    ));
+
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("call_r", f_call_indirect, 1)
+            .operands_in(vec![gpr])
+            .emit(
+                r#"
+                    sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                    PUT_OP(bits, rex1(in_reg0), sink);
+                    modrm_r_bits(in_reg0, bits, sink);
+                "#,
+            ),
+    );
+
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("ret", f_multiary, 0).emit("PUT_OP(bits, BASE_REX, sink);"),
+    );
+
+    //
+    // Branches
+    //
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("jmpb", f_jump, 1)
+            .branch_range((1, 8))
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    PUT_OP(bits, BASE_REX, sink);
+                    disp1(destination, func, sink);
+                "#,
+            ),
+    );
+
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("jmpd", f_jump, 4)
+            .branch_range((4, 32))
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    PUT_OP(bits, BASE_REX, sink);
+                    disp4(destination, func, sink);
+                "#,
+            ),
+    );
+
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("brib", f_branch_int, 1)
+            .operands_in(vec![reg_rflags])
+            .branch_range((1, 8))
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    PUT_OP(bits | icc2opc(cond), BASE_REX, sink);
+                    disp1(destination, func, sink);
+                "#,
+            ),
+    );
+
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("brid", f_branch_int, 4)
+            .operands_in(vec![reg_rflags])
+            .branch_range((4, 32))
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    PUT_OP(bits | icc2opc(cond), BASE_REX, sink);
+                    disp4(destination, func, sink);
+                "#,
+            ),
+    );
+
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("brfb", f_branch_float, 1)
+            .operands_in(vec![reg_rflags])
+            .branch_range((1, 8))
+            .clobbers_flags(false)
+            .inst_predicate(floatccs(formats.get(f_branch_float), floatcc))
+            .emit(
+                r#"
+                    PUT_OP(bits | fcc2opc(cond), BASE_REX, sink);
+                    disp1(destination, func, sink);
+                "#,
+            ),
+    );
+
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("brfd", f_branch_float, 4)
+            .operands_in(vec![reg_rflags])
+            .branch_range((4, 32))
+            .clobbers_flags(false)
+            .inst_predicate(floatccs(formats.get(f_branch_float), floatcc))
+            .emit(
+                r#"
+                    PUT_OP(bits | fcc2opc(cond), BASE_REX, sink);
+                    disp4(destination, func, sink);
+                "#,
+            ),
+    );
+
+    //
+    // Test flags and set a register.
+    //
+    // These setCC instructions only set the low 8 bits, and they can only write
+    // ABCD registers without a REX prefix.
+    //
+    // Other instruction encodings accepting `b1` inputs have the same constraints
+    // and only look at the low 8 bits of the input register.
+    //
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("seti", f_int_cond, 1)
+                .operands_in(vec![reg_rflags])
+                .operands_out(vec![gpr])
+                .clobbers_flags(false)
+                .emit(
+                    r#"
+                        PUT_OP(bits | icc2opc(cond), rex1(out_reg0), sink);
+                        modrm_r_bits(out_reg0, bits, sink);
+                    "#,
+                ),
+            regs,
+        )
+        .requires_prefix(true),
+    );
+
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("seti_abcd", f_int_cond, 1)
+                .operands_in(vec![reg_rflags])
+                .operands_out(vec![abcd])
+                .clobbers_flags(false)
+                .emit(
+                    r#"
+                        PUT_OP(bits | icc2opc(cond), rex1(out_reg0), sink);
+                        modrm_r_bits(out_reg0, bits, sink);
+                    "#,
+                ),
+            regs,
+        )
+        .when_prefixed(recipes.template("seti").clone()),
+    );
+
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("setf", f_float_cond, 1)
+                .operands_in(vec![reg_rflags])
+                .operands_out(vec![gpr])
+                .clobbers_flags(false)
+                .emit(
+                    r#"
+                        PUT_OP(bits | fcc2opc(cond), rex1(out_reg0), sink);
+                        modrm_r_bits(out_reg0, bits, sink);
+                    "#,
+                ),
+            regs,
+        )
+        .requires_prefix(true),
+    );
+
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("setf_abcd", f_float_cond, 1)
+                .operands_in(vec![reg_rflags])
+                .operands_out(vec![abcd])
+                .clobbers_flags(false)
+                .emit(
+                    r#"
+                        PUT_OP(bits | fcc2opc(cond), rex1(out_reg0), sink);
+                        modrm_r_bits(out_reg0, bits, sink);
+                    "#,
+                ),
+            regs,
+        )
+        .when_prefixed(recipes.template("setf").clone()),
+    );
+
+    //
+    // Conditional move (a.k.a integer select)
+    // (maybe-REX.W) 0F 4x modrm(r,r)
+    // 1 byte, modrm(r,r), is after the opcode
+    //
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("cmov", f_int_select, 1)
+            .operands_in(vec![reg_rflags, gpr, gpr])
+            .operands_out(vec![OperandConstraint::TiedInput(2)])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    PUT_OP(bits | icc2opc(cond), rex2(in_reg1, in_reg2), sink);
+                    modrm_rr(in_reg1, in_reg2, sink);
+                "#,
+            ),
+    );
+
+    //
+    // Bit scan forwards and reverse
+    //
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("bsf_and_bsr", f_unary, 1)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr, reg_rflags])
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(in_reg0, out_reg0), sink);
+                    modrm_rr(in_reg0, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    //
+    // Compare and set flags.
+    //
+
+    // XX /r, MR form. Compare two GPR registers and set flags.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("rcmp", f_binary, 1)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![reg_rflags])
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(in_reg0, in_reg1), sink);
+                    modrm_rr(in_reg0, in_reg1, sink);
+                "#,
+            ),
+    );
+
+    // XX /r, RM form. Compare two FPR registers and set flags.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("fcmp", f_binary, 1)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![reg_rflags])
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+                    modrm_rr(in_reg1, in_reg0, sink);
+                "#,
+            ),
+    );
+
+    // XX /n, MI form with imm8.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("rcmp_ib", f_binary_imm, 2)
+            .operands_in(vec![gpr])
+            .operands_out(vec![reg_rflags])
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_binary_imm),
+                "imm",
+                8,
+                0,
+            ))
+            .emit(
+                r#"
+                    PUT_OP(bits, rex1(in_reg0), sink);
+                    modrm_r_bits(in_reg0, bits, sink);
+                    let imm: i64 = imm.into();
+                    sink.put1(imm as u8);
+                "#,
+            ),
+    );
+
+    // XX /n, MI form with imm32.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("rcmp_id", f_binary_imm, 5)
+            .operands_in(vec![gpr])
+            .operands_out(vec![reg_rflags])
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_binary_imm),
+                "imm",
+                32,
+                0,
+            ))
+            .emit(
+                r#"
+                    PUT_OP(bits, rex1(in_reg0), sink);
+                    modrm_r_bits(in_reg0, bits, sink);
+                    let imm: i64 = imm.into();
+                    sink.put4(imm as u32);
+                "#,
+            ),
+    );
+
+    // Same as rcmp, but second operand is the stack pointer.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("rcmp_sp", f_unary, 1)
+            .operands_in(vec![gpr])
+            .operands_out(vec![reg_rflags])
+            .emit(
+                r#"
+                    PUT_OP(bits, rex2(in_reg0, RU::rsp.into()), sink);
+                    modrm_rr(in_reg0, RU::rsp.into(), sink);
+                "#,
+            ),
+    );
+
+    // Test-and-branch.
+    //
+    // This recipe represents the macro fusion of a test and a conditional branch.
+    // This serves two purposes:
+    //
+    // 1. Guarantee that the test and branch get scheduled next to each other so
+    //    macro fusion is guaranteed to be possible.
+    // 2. Hide the status flags from Cranelift which doesn't currently model flags.
+    //
+    // The encoding bits affect both the test and the branch instruction:
+    //
+    // Bits 0-7 are the Jcc opcode.
+    // Bits 8-15 control the test instruction which always has opcode byte 0x85.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("tjccb", f_branch, 3)
+            .operands_in(vec![gpr])
+            .branch_range((3, 8))
+            .emit(
+                r#"
+                    // test r, r.
+                    PUT_OP((bits & 0xff00) | 0x85, rex2(in_reg0, in_reg0), sink);
+                    modrm_rr(in_reg0, in_reg0, sink);
+                    // Jcc instruction.
+                    sink.put1(bits as u8);
+                    disp1(destination, func, sink);
+                "#,
+            ),
+    );
+
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("tjccd", f_branch, 7)
+            .operands_in(vec![gpr])
+            .branch_range((7, 32))
+            .emit(
+                r#"
+                    // test r, r.
+                    PUT_OP((bits & 0xff00) | 0x85, rex2(in_reg0, in_reg0), sink);
+                    modrm_rr(in_reg0, in_reg0, sink);
+                    // Jcc instruction.
+                    sink.put1(0x0f);
+                    sink.put1(bits as u8);
+                    disp4(destination, func, sink);
+                "#,
+            ),
+    );
+
+    // 8-bit test-and-branch.
+    //
+    // Same as tjccb, but only looks at the low 8 bits of the register, for b1
+    // types.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("t8jccb", f_branch, 3)
+                .operands_in(vec![gpr])
+                .branch_range((3, 8))
+                .emit(
+                    r#"
+                        // test8 r, r.
+                        PUT_OP((bits & 0xff00) | 0x84, rex2(in_reg0, in_reg0), sink);
+                        modrm_rr(in_reg0, in_reg0, sink);
+                        // Jcc instruction.
+                        sink.put1(bits as u8);
+                        disp1(destination, func, sink);
+                    "#,
+                ),
+            regs,
+        )
+        .requires_prefix(true),
+    );
+
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("t8jccb_abcd", f_branch, 3)
+                .operands_in(vec![abcd])
+                .branch_range((3, 8))
+                .emit(
+                    r#"
+                        // test8 r, r.
+                        PUT_OP((bits & 0xff00) | 0x84, rex2(in_reg0, in_reg0), sink);
+                        modrm_rr(in_reg0, in_reg0, sink);
+                        // Jcc instruction.
+                        sink.put1(bits as u8);
+                        disp1(destination, func, sink);
+                    "#,
+                ),
+            regs,
+        )
+        .when_prefixed(recipes.template("t8jccb").clone()),
+    );
+
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("t8jccd", f_branch, 7)
+                .operands_in(vec![gpr])
+                .branch_range((7, 32))
+                .emit(
+                    r#"
+                        // test8 r, r.
+                        PUT_OP((bits & 0xff00) | 0x84, rex2(in_reg0, in_reg0), sink);
+                        modrm_rr(in_reg0, in_reg0, sink);
+                        // Jcc instruction.
+                        sink.put1(0x0f);
+                        sink.put1(bits as u8);
+                        disp4(destination, func, sink);
+                    "#,
+                ),
+            regs,
+        )
+        .requires_prefix(true),
+    );
+
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("t8jccd_abcd", f_branch, 7)
+                .operands_in(vec![abcd])
+                .branch_range((7, 32))
+                .emit(
+                    r#"
+                        // test8 r, r.
+                        PUT_OP((bits & 0xff00) | 0x84, rex2(in_reg0, in_reg0), sink);
+                        modrm_rr(in_reg0, in_reg0, sink);
+                        // Jcc instruction.
+                        sink.put1(0x0f);
+                        sink.put1(bits as u8);
+                        disp4(destination, func, sink);
+                    "#,
+                ),
+            regs,
+        )
+        .when_prefixed(recipes.template("t8jccd").clone()),
+    );
+
+    // Worst case test-and-branch recipe for brz.b1 and brnz.b1 in 32-bit mode.
+    // The register allocator can't handle a branch instruction with constrained
+    // operands like the t8jccd_abcd above. This variant can accept the b1 opernd in
+    // any register, but is is larger because it uses a 32-bit test instruction with
+    // a 0xff immediate.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("t8jccd_long", f_branch, 11)
+            .operands_in(vec![gpr])
+            .branch_range((11, 32))
+            .emit(
+                r#"
+                    // test32 r, 0xff.
+                    PUT_OP((bits & 0xff00) | 0xf7, rex1(in_reg0), sink);
+                    modrm_r_bits(in_reg0, bits, sink);
+                    sink.put4(0xff);
+                    // Jcc instruction.
+                    sink.put1(0x0f);
+                    sink.put1(bits as u8);
+                    disp4(destination, func, sink);
+                "#,
+            ),
+    );
+
+    // Comparison that produces a `b1` result in a GPR.
+    //
+    // This is a macro of a `cmp` instruction followed by a `setCC` instruction.
+    // This is not a great solution because:
+    //
+    // - The cmp+setcc combination is not recognized by CPU's macro fusion.
+    // - The 64-bit encoding has issues with REX prefixes. The `cmp` and `setCC`
+    //   instructions may need a REX independently.
+    // - Modeling CPU flags in the type system would be better.
+    //
+    // Since the `setCC` instructions only write an 8-bit register, we use that as
+    // our `b1` representation: A `b1` value is represented as a GPR where the low 8
+    // bits are known to be 0 or 1. The high bits are undefined.
+    //
+    // This bandaid macro doesn't support a REX prefix for the final `setCC`
+    // instruction, so it is limited to the `ABCD` register class for booleans.
+    // The omission of a `when_prefixed` alternative is deliberate here.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("icscc", f_int_compare, 4)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![abcd])
+            .emit(
+                r#"
+                    // Comparison instruction.
+                    PUT_OP(bits, rex2(in_reg0, in_reg1), sink);
+                    modrm_rr(in_reg0, in_reg1, sink);
+                    // `setCC` instruction, no REX.
+                    use ir::condcodes::IntCC::*;
+                    let setcc = match cond {
+                        Equal => 0x94,
+                        NotEqual => 0x95,
+                        SignedLessThan => 0x9c,
+                        SignedGreaterThanOrEqual => 0x9d,
+                        SignedGreaterThan => 0x9f,
+                        SignedLessThanOrEqual => 0x9e,
+                        UnsignedLessThan => 0x92,
+                        UnsignedGreaterThanOrEqual => 0x93,
+                        UnsignedGreaterThan => 0x97,
+                        UnsignedLessThanOrEqual => 0x96,
+                    };
+                    sink.put1(0x0f);
+                    sink.put1(setcc);
+                    modrm_rr(out_reg0, 0, sink);
+                "#,
+            ),
+    );
+
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("icscc_ib", f_int_compare_imm, 5)
+            .operands_in(vec![gpr])
+            .operands_out(vec![abcd])
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_int_compare_imm),
+                "imm",
+                8,
+                0,
+            ))
+            .emit(
+                r#"
+                    // Comparison instruction.
+                    PUT_OP(bits, rex1(in_reg0), sink);
+                    modrm_r_bits(in_reg0, bits, sink);
+                    let imm: i64 = imm.into();
+                    sink.put1(imm as u8);
+                    // `setCC` instruction, no REX.
+                    use ir::condcodes::IntCC::*;
+                    let setcc = match cond {
+                        Equal => 0x94,
+                        NotEqual => 0x95,
+                        SignedLessThan => 0x9c,
+                        SignedGreaterThanOrEqual => 0x9d,
+                        SignedGreaterThan => 0x9f,
+                        SignedLessThanOrEqual => 0x9e,
+                        UnsignedLessThan => 0x92,
+                        UnsignedGreaterThanOrEqual => 0x93,
+                        UnsignedGreaterThan => 0x97,
+                        UnsignedLessThanOrEqual => 0x96,
+                    };
+                    sink.put1(0x0f);
+                    sink.put1(setcc);
+                    modrm_rr(out_reg0, 0, sink);
+                "#,
+            ),
+    );
+
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("icscc_id", f_int_compare_imm, 8)
+            .operands_in(vec![gpr])
+            .operands_out(vec![abcd])
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_int_compare_imm),
+                "imm",
+                32,
+                0,
+            ))
+            .emit(
+                r#"
+                    // Comparison instruction.
+                    PUT_OP(bits, rex1(in_reg0), sink);
+                    modrm_r_bits(in_reg0, bits, sink);
+                    let imm: i64 = imm.into();
+                    sink.put4(imm as u32);
+                    // `setCC` instruction, no REX.
+                    use ir::condcodes::IntCC::*;
+                    let setcc = match cond {
+                        Equal => 0x94,
+                        NotEqual => 0x95,
+                        SignedLessThan => 0x9c,
+                        SignedGreaterThanOrEqual => 0x9d,
+                        SignedGreaterThan => 0x9f,
+                        SignedLessThanOrEqual => 0x9e,
+                        UnsignedLessThan => 0x92,
+                        UnsignedGreaterThanOrEqual => 0x93,
+                        UnsignedGreaterThan => 0x97,
+                        UnsignedLessThanOrEqual => 0x96,
+                    };
+                    sink.put1(0x0f);
+                    sink.put1(setcc);
+                    modrm_rr(out_reg0, 0, sink);
+                "#,
+            ),
+    );
+
+    // Same thing for floating point.
+    //
+    // The ucomiss/ucomisd instructions set the FLAGS bits CF/PF/CF like this:
+    //
+    //    ZPC OSA
+    // UN 111 000
+    // GT 000 000
+    // LT 001 000
+    // EQ 100 000
+    //
+    // Not all floating point condition codes are supported.
+    // The omission of a `when_prefixed` alternative is deliberate here.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("fcscc", f_float_compare, 4)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![abcd])
+            .inst_predicate(floatccs(formats.get(f_float_compare), floatcc))
+            .emit(
+                r#"
+                    // Comparison instruction.
+                    PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+                    modrm_rr(in_reg1, in_reg0, sink);
+                    // `setCC` instruction, no REX.
+                    use ir::condcodes::FloatCC::*;
+                    let setcc = match cond {
+                        Ordered                    => 0x9b, // EQ|LT|GT => setnp (P=0)
+                        Unordered                  => 0x9a, // UN       => setp  (P=1)
+                        OrderedNotEqual            => 0x95, // LT|GT    => setne (Z=0),
+                        UnorderedOrEqual           => 0x94, // UN|EQ    => sete  (Z=1)
+                        GreaterThan                => 0x97, // GT       => seta  (C=0&Z=0)
+                        GreaterThanOrEqual         => 0x93, // GT|EQ    => setae (C=0)
+                        UnorderedOrLessThan        => 0x92, // UN|LT    => setb  (C=1)
+                        UnorderedOrLessThanOrEqual => 0x96, // UN|LT|EQ => setbe (Z=1|C=1)
+                        Equal |                       // EQ
+                        NotEqual |                    // UN|LT|GT
+                        LessThan |                    // LT
+                        LessThanOrEqual |             // LT|EQ
+                        UnorderedOrGreaterThan |      // UN|GT
+                        UnorderedOrGreaterThanOrEqual // UN|GT|EQ
+                        => panic!("{} not supported by fcscc", cond),
+                    };
+                    sink.put1(0x0f);
+                    sink.put1(setcc);
+                    modrm_rr(out_reg0, 0, sink);
+                "#,
+            ),
+    );
+
+    //
+    // VEX recipes
+    //
+    // These recipes can only be used with a VEX prefix, see `Template::vex()`.
+    // The destination of the three-operand forms doesn't have to be one of the
+    // sources, which saves the register copies of the two-operand SSE forms.
+    //
+
+    // VEX.vvvv XX /r with FPR ins and outs. The first operand is in VEX.vvvv.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vfa", f_binary, 1)
+                .operands_in(vec![fpr, fpr])
+                .operands_out(vec![fpr])
+                .clobbers_flags(false)
+                .isa_predicate(use_avx)
+                .emit(
+                    r#"
+                        PUT_OP(bits, rex2(in_reg1, out_reg0), in_reg0, sink);
+                        modrm_rr(in_reg1, out_reg0, sink);
+                    "#,
+                ),
+            regs,
+        )
+        .requires_vex(true),
+    );
+
+    // VEX.vvvv XX /r with FPR ins and outs. A form with input operands swapped.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vfax", f_binary, 1)
+                .operands_in(vec![fpr, fpr])
+                .operands_out(vec![fpr])
+                .clobbers_flags(false)
+                .isa_predicate(use_avx)
+                .emit(
+                    r#"
+                        PUT_OP(bits, rex2(in_reg0, out_reg0), in_reg1, sink);
+                        modrm_rr(in_reg0, out_reg0, sink);
+                    "#,
+                ),
+            regs,
+        )
+        .requires_vex(true),
+    );
+
+    // XX /r, RM form, FPR -> FPR, with an unused VEX.vvvv field.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vfurm", f_unary, 1)
+                .operands_in(vec![fpr])
+                .operands_out(vec![fpr])
+                .clobbers_flags(false)
+                .isa_predicate(use_avx)
+                .emit(
+                    r#"
+                        PUT_OP(bits, rex2(in_reg0, out_reg0), 0, sink);
+                        modrm_rr(in_reg0, out_reg0, sink);
+                    "#,
+                ),
+            regs,
+        )
+        .requires_vex(true),
+    );
+
+    // XX /r, for regmove instructions.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vfrmov", f_regmove, 1)
+                .operands_in(vec![fpr])
+                .clobbers_flags(false)
+                .isa_predicate(use_avx)
+                .emit(
+                    r#"
+                        PUT_OP(bits, rex2(src, dst), 0, sink);
+                        modrm_rr(src, dst, sink);
+                    "#,
+                ),
+            regs,
+        )
+        .requires_vex(true),
+    );
+
+    // XX /r register-indirect store of FPR with no offset.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vfst", f_store, 1)
+                .operands_in(vec![fpr, gpr_zero_deref_safe])
+                .clobbers_flags(false)
+                .inst_predicate(InstructionPredicate::is_equal(
+                    formats.get(f_store),
+                    "offset",
+                    "0",
+                ))
+                .isa_predicate(use_avx)
+                .emit(
+                    r#"
+                        if !flags.notrap() {
+                            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                        }
+                        PUT_OP(bits, rex2(in_reg1, in_reg0), 0, sink);
+                        modrm_rm(in_reg1, in_reg0, sink);
+                    "#,
+                ),
+            regs,
+        )
+        .requires_vex(true),
+    );
+
+    // XX /r register-indirect store of FPR with 8-bit offset.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vfstDisp8", f_store, 2)
+                .operands_in(vec![fpr, gpr_deref_safe])
+                .clobbers_flags(false)
+                .inst_predicate(InstructionPredicate::is_signed_int(
+                    formats.get(f_store),
+                    "offset",
+                    8,
+                    0,
+                ))
+                .isa_predicate(use_avx)
+                .emit(
+                    r#"
+                        if !flags.notrap() {
+                            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                        }
+                        PUT_OP(bits, rex2(in_reg1, in_reg0), 0, sink);
+                        modrm_disp8(in_reg1, in_reg0, sink);
+                        let offset: i32 = offset.into();
+                        sink.put1(offset as u8);
+                    "#,
+                ),
+            regs,
+        )
+        .requires_vex(true),
+    );
+
+    // XX /r register-indirect store of FPR with 32-bit offset.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vfstDisp32", f_store, 5)
+                .operands_in(vec![fpr, gpr_deref_safe])
+                .clobbers_flags(false)
+                .isa_predicate(use_avx)
+                .emit(
+                    r#"
+                        if !flags.notrap() {
+                            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                        }
+                        PUT_OP(bits, rex2(in_reg1, in_reg0), 0, sink);
+                        modrm_disp32(in_reg1, in_reg0, sink);
+                        let offset: i32 = offset.into();
+                        sink.put4(offset as u32);
+                    "#,
+                ),
+            regs,
+        )
+        .requires_vex(true),
+    );
+
+    // Spill of FPR using RSP-relative addressing.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vfspillSib32", f_unary, 6)
+                .operands_in(vec![fpr])
+                .operands_out(vec![stack_fpr32])
+                .clobbers_flags(false)
+                .isa_predicate(use_avx)
+                .emit(
+                    r#"
+                        sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                        let base = stk_base(out_stk0.base);
+                        PUT_OP(bits, rex2(base, in_reg0), 0, sink);
+                        modrm_sib_disp32(in_reg0, sink);
+                        sib_noindex(base, sink);
+                        sink.put4(out_stk0.offset as u32);
+                    "#,
+                ),
+            regs,
+        )
+        .requires_vex(true),
+    );
+
+    // Regspill of FPR using RSP-relative addressing.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vfregspill32", f_regspill, 6)
+                .operands_in(vec![fpr])
+                .clobbers_flags(false)
+                .isa_predicate(use_avx)
+                .emit(
+                    r#"
+                        sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                        let dst = StackRef::sp(dst, &func.stack_slots);
+                        let base = stk_base(dst.base);
+                        PUT_OP(bits, rex2(base, src), 0, sink);
+                        modrm_sib_disp32(src, sink);
+                        sib_noindex(base, sink);
+                        sink.put4(dst.offset as u32);
+                    "#,
+                ),
+            regs,
+        )
+        .requires_vex(true),
+    );
+
+    // XX /r load of FPR with no offset.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vfld", f_load, 1)
+                .operands_in(vec![gpr_zero_deref_safe])
+                .operands_out(vec![fpr])
+                .clobbers_flags(false)
+                .inst_predicate(InstructionPredicate::is_equal(
+                    formats.get(f_load),
+                    "offset",
+                    "0",
+                ))
+                .isa_predicate(use_avx)
+                .emit(
+                    r#"
+                        if !flags.notrap() {
+                            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                        }
+                        PUT_OP(bits, rex2(in_reg0, out_reg0), 0, sink);
+                        modrm_rm(in_reg0, out_reg0, sink);
+                    "#,
+                ),
+            regs,
+        )
+        .requires_vex(true),
+    );
+
+    // XX /r load of FPR with 8-bit offset.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vfldDisp8", f_load, 2)
+                .operands_in(vec![gpr_deref_safe])
+                .operands_out(vec![fpr])
+                .clobbers_flags(false)
+                .inst_predicate(InstructionPredicate::is_signed_int(
+                    formats.get(f_load),
+                    "offset",
+                    8,
+                    0,
+                ))
+                .isa_predicate(use_avx)
+                .emit(
+                    r#"
+                        if !flags.notrap() {
+                            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                        }
+                        PUT_OP(bits, rex2(in_reg0, out_reg0), 0, sink);
+                        modrm_disp8(in_reg0, out_reg0, sink);
+                        let offset: i32 = offset.into();
+                        sink.put1(offset as u8);
+                    "#,
+                ),
+            regs,
+        )
+        .requires_vex(true),
+    );
+
+    // XX /r load of FPR with 32-bit offset.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vfldDisp32", f_load, 5)
+                .operands_in(vec![gpr_deref_safe])
+                .operands_out(vec![fpr])
+                .clobbers_flags(false)
+                .inst_predicate(InstructionPredicate::is_signed_int(
+                    formats.get(f_load),
+                    "offset",
+                    32,
+                    0,
+                ))
+                .isa_predicate(use_avx)
+                .emit(
+                    r#"
+                        if !flags.notrap() {
+                            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                        }
+                        PUT_OP(bits, rex2(in_reg0, out_reg0), 0, sink);
+                        modrm_disp32(in_reg0, out_reg0, sink);
+                        let offset: i32 = offset.into();
+                        sink.put4(offset as u32);
+                    "#,
+                ),
+            regs,
+        )
+        .requires_vex(true),
+    );
+
+    // Fill of FPR with RSP-relative 32-bit displacement.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vffillSib32", f_unary, 6)
+                .operands_in(vec![stack_fpr32])
+                .operands_out(vec![fpr])
+                .clobbers_flags(false)
+                .isa_predicate(use_avx)
+                .emit(
+                    r#"
+                        let base = stk_base(in_stk0.base);
+                        PUT_OP(bits, rex2(base, out_reg0), 0, sink);
+                        modrm_sib_disp32(out_reg0, sink);
+                        sib_noindex(base, sink);
+                        sink.put4(in_stk0.offset as u32);
+                    "#,
+                ),
+            regs,
+        )
+        .requires_vex(true),
+    );
+
+    // Regfill of FPR with RSP-relative 32-bit displacement.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vfregfill32", f_regfill, 6)
+                .operands_in(vec![stack_fpr32])
+                .clobbers_flags(false)
+                .isa_predicate(use_avx)
+                .emit(
+                    r#"
+                        let src = StackRef::sp(src, &func.stack_slots);
+                        let base = stk_base(src.base);
+                        PUT_OP(bits, rex2(base, dst), 0, sink);
+                        modrm_sib_disp32(dst, sink);
+                        sib_noindex(base, sink);
+                        sink.put4(src.offset as u32);
+                    "#,
+                ),
+            regs,
+        )
+        .requires_vex(true),
+    );
+
+    // Split a YMM register into its XMM halves with vextractf128. The low half is
+    // the XMM register aliasing the input, so only the high half is extracted.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vfsplit", f_unary, 2)
+                .operands_in(vec![fpr])
+                .operands_out(vec![OperandConstraint::TiedInput(0), fpr])
+                .clobbers_flags(false)
+                .isa_predicate(use_avx)
+                .emit(
+                    r#"
+                        PUT_OP(bits, rex2(out_reg1, in_reg0), 0, sink);
+                        modrm_rr(out_reg1, in_reg0, sink);
+                        sink.put1(1);
+                    "#,
+                ),
+            regs,
+        )
+        .requires_vex(true),
+    );
+
+    // Concatenate two XMM registers into a YMM register with vinsertf128.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vfconcat", f_binary, 2)
+                .operands_in(vec![fpr, fpr])
+                .operands_out(vec![fpr])
+                .clobbers_flags(false)
+                .isa_predicate(use_avx)
+                .emit(
+                    r#"
+                        PUT_OP(bits, rex2(in_reg1, out_reg0), in_reg0, sink);
+                        modrm_rr(in_reg1, out_reg0, sink);
+                        sink.put1(1);
+                    "#,
+                ),
+            regs,
+        )
+        .requires_vex(true),
+    );
+
+    // An instruction without operands, such as vzeroupper.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vop", f_nullary, 0)
+                .clobbers_flags(false)
+                .isa_predicate(use_avx)
+                .emit(
+                    r#"
+                        PUT_OP(bits, BASE_REX, 0, sink);
+                    "#,
+                ),
+            regs,
+        )
+        .requires_vex(true),
+    );
+
+    // VEX.vvvv XX /r with GPR ins and outs. The second operand is in VEX.vvvv.
+    // This is the BMI1 andn instruction.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vrrx", f_binary, 1)
+                .operands_in(vec![gpr, gpr])
+                .operands_out(vec![gpr])
+                .isa_predicate(use_bmi1)
+                .emit(
+                    r#"
+                        PUT_OP(bits, rex2(in_reg0, out_reg0), in_reg1, sink);
+                        modrm_rr(in_reg0, out_reg0, sink);
+                    "#,
+                ),
+            regs,
+        )
+        .requires_vex(true),
+    );
+
+    // VEX.vvvv XX /r for the BMI2 shifts, which take the shift amount in VEX.vvvv
+    // from any register and leave the flags alone.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vrsh", f_binary, 1)
+                .operands_in(vec![gpr, gpr])
+                .operands_out(vec![gpr])
+                .clobbers_flags(false)
+                .isa_predicate(use_bmi2)
+                .emit(
+                    r#"
+                        PUT_OP(bits, rex2(in_reg0, out_reg0), in_reg1, sink);
+                        modrm_rr(in_reg0, out_reg0, sink);
+                    "#,
+                ),
+            regs,
+        )
+        .requires_vex(true),
+    );
+
+    // VEX.vvvv XX /n with a GPR input, and the GPR output in VEX.vvvv.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vur", f_unary, 1)
+                .operands_in(vec![gpr])
+                .operands_out(vec![gpr])
+                .isa_predicate(use_bmi1)
+                .emit(
+                    r#"
+                        PUT_OP(bits, rex1(in_reg0), out_reg0, sink);
+                        modrm_r_bits(in_reg0, bits, sink);
+                    "#,
+                ),
+            regs,
+        )
+        .requires_vex(true),
+    );
+
+    // VEX XX /r ib with GPR ins and outs. This is the BMI2 rorx instruction.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vr_ib", f_binary_imm, 2)
+                .operands_in(vec![gpr])
+                .operands_out(vec![gpr])
+                .clobbers_flags(false)
+                .isa_predicate(use_bmi2)
+                .emit(
+                    r#"
+                        PUT_OP(bits, rex2(in_reg0, out_reg0), 0, sink);
+                        modrm_rr(in_reg0, out_reg0, sink);
+                        let imm: i64 = imm.into();
+                        sink.put1(imm as u8);
+                    "#,
+                ),
+            regs,
+        )
+        .requires_vex(true),
+    );
+
+    //
+    // Atomic memory operations.
+    //
+
+    // XX /r register-indirect read-modify-write with no offset.
+    // The register operand is replaced by the old memory value, as for xchg.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("rmw", f_store, 1)
+            .operands_in(vec![gpr, gpr_zero_deref_safe])
+            .operands_out(vec![OperandConstraint::TiedInput(0)])
+            .clobbers_flags(false)
+            .inst_predicate(InstructionPredicate::is_equal(
+                formats.get(f_store),
+                "offset",
+                "0",
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+                    modrm_rm(in_reg1, in_reg0, sink);
+                "#,
+            ),
+    );
+
+    // XX /r register-indirect read-modify-write with 8-bit offset.
+    // The register operand is replaced by the old memory value, as for xchg.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("rmwDisp8", f_store, 2)
+            .operands_in(vec![gpr, gpr_deref_safe])
+            .operands_out(vec![OperandConstraint::TiedInput(0)])
+            .clobbers_flags(false)
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_store),
+                "offset",
+                8,
+                0,
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+                    modrm_disp8(in_reg1, in_reg0, sink);
+                    let offset: i32 = offset.into();
+                    sink.put1(offset as u8);
+                "#,
+            ),
+    );
+
+    // XX /r register-indirect read-modify-write with 32-bit offset.
+    // The register operand is replaced by the old memory value, as for xchg.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("rmwDisp32", f_store, 5)
+            .operands_in(vec![gpr, gpr_deref_safe])
+            .operands_out(vec![OperandConstraint::TiedInput(0)])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+                    modrm_disp32(in_reg1, in_reg0, sink);
+                    let offset: i32 = offset.into();
+                    sink.put4(offset as u32);
+                "#,
+            ),
+    );
+
+    // LOCK XX /r register-indirect read-modify-write with no offset.
+    // The register operand is replaced by the old memory value, as for xadd.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("lrmw", f_store, 2)
+            .operands_in(vec![gpr, gpr_zero_deref_safe])
+            .operands_out(vec![OperandConstraint::TiedInput(0)])
+            .inst_predicate(InstructionPredicate::is_equal(
+                formats.get(f_store),
+                "offset",
+                "0",
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    sink.put1(0xf0);
+                    PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+                    modrm_rm(in_reg1, in_reg0, sink);
+                "#,
+            ),
+    );
+
+    // LOCK XX /r register-indirect read-modify-write with 8-bit offset.
+    // The register operand is replaced by the old memory value, as for xadd.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("lrmwDisp8", f_store, 3)
+            .operands_in(vec![gpr, gpr_deref_safe])
+            .operands_out(vec![OperandConstraint::TiedInput(0)])
+            .inst_predicate(InstructionPredicate::is_signed_int(
+                formats.get(f_store),
+                "offset",
+                8,
+                0,
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    sink.put1(0xf0);
+                    PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+                    modrm_disp8(in_reg1, in_reg0, sink);
+                    let offset: i32 = offset.into();
+                    sink.put1(offset as u8);
+                "#,
+            ),
+    );
+
+    // LOCK XX /r register-indirect read-modify-write with 32-bit offset.
+    // The register operand is replaced by the old memory value, as for xadd.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("lrmwDisp32", f_store, 6)
+            .operands_in(vec![gpr, gpr_deref_safe])
+            .operands_out(vec![OperandConstraint::TiedInput(0)])
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    sink.put1(0xf0);
+                    PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+                    modrm_disp32(in_reg1, in_reg0, sink);
+                    let offset: i32 = offset.into();
+                    sink.put4(offset as u32);
+                "#,
+            ),
+    );
+
+    // LOCK XX /r register-indirect compare and swap.
+    // The expected value and the result are in %rax, as for cmpxchg.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("lcas", f_atomic_cas, 2)
+            .operands_in(vec![reg_rax, gpr, gpr_zero_deref_safe])
+            .operands_out(vec![reg_rax])
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    sink.put1(0xf0);
+                    PUT_OP(bits, rex2(in_reg2, in_reg1), sink);
+                    modrm_rm(in_reg2, in_reg1, sink);
+                "#,
+            ),
+    );
+
+    // XX /n with no operands, such as mfence.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("fence", f_nullary, 1)
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    PUT_OP(bits, BASE_REX, sink);
+                    modrm_r_bits(0, bits, sink);
+                "#,
+            ),
+    );
+
+    recipes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode_opcodes;
+
+    #[test]
+    fn opcode_bits() {
+        assert_eq!(decode_opcodes(&[0x01], 0, 0), ("Op1", 0x0001));
+        assert_eq!(decode_opcodes(&[0xf7], 7, 1), ("Op1", 0xf0f7));
+        assert_eq!(decode_opcodes(&[0x0f, 0xaf], 0, 0), ("Op2", 0x04af));
+        assert_eq!(
+            decode_opcodes(&[0x66, 0x0f, 0x3a, 0x0a], 0, 0),
+            ("Mp3", 0x0d0a)
+        );
+    }
+}
@@ -0,0 +1,82 @@
+//! x86 register banks.
+//!
+//! While the floating-point registers are straight-forward, the general
+//! purpose register bank has a few quirks on x86. We have these encodings of
+//! the 8-bit registers:
+//!
+//! ```text
+//!          I32 I64  |  16b 32b  64b
+//!     000  AL  AL   |  AX  EAX  RAX
+//!     001  CL  CL   |  CX  ECX  RCX
+//!     010  DL  DL   |  DX  EDX  RDX
+//!     011  BL  BL   |  BX  EBX  RBX
+//!     100  AH  SPL  |  SP  ESP  RSP
+//!     101  CH  BPL  |  BP  EBP  RBP
+//!     110  DH  SIL  |  SI  ESI  RSI
+//!     111  BH  DIL  |  DI  EDI  RDI
+//! ```
+//!
+//! Here, the I64 column refers to the registers you get with a REX prefix.
+//! Without the REX prefix, you get the I32 registers.
+//!
+//! The 8-bit registers are not that useful since WebAssembly only has i32 and
+//! i64 data types, and the H-registers even less so. Rather than trying to
+//! model the H-registers accurately, we'll avoid using them in both I32 and
+//! I64 modes.
+//!
+//! The AVX instructions operate on the 256-bit YMM registers, whose low 128
+//! bits are the XMM registers. The floating-point register bank models both: A
+//! 256-bit vector takes a single register unit like any other value held in an
+//! FPR, and the register is named after its XMM half.
+
+use cdsl::registers::{IsaRegs, IsaRegsBuilder, RegBankBuilder, RegClassBuilder};
+
+pub fn define() -> IsaRegs {
+    let mut regs = IsaRegsBuilder::new();
+
+    let int_regs = regs.add_bank(
+        RegBankBuilder::new("IntRegs", "General purpose registers")
+            .units(16)
+            .names(vec!["rax", "rcx", "rdx", "rbx", "rsp", "rbp", "rsi", "rdi"]),
+    );
+    let float_regs = regs.add_bank(
+        RegBankBuilder::new("FloatRegs", "SSE and AVX floating point registers")
+            .units(16)
+            .prefix("xmm"),
+    );
+    let flag_regs = regs.add_bank(
+        RegBankBuilder::new("FlagRegs", "Flag registers")
+            .units(1)
+            .names(vec!["rflags"])
+            .track_pressure(false),
+    );
+
+    let gpr = regs.add_class(RegClassBuilder::new_toplevel("GPR", int_regs));
+    // Certain types of deref encodings cannot be used with all registers.
+    //   R13/RBP cannot be used with zero-offset load or store instructions.
+    //   R12 cannot be used with a non-SIB-byte encoding of all derefs.
+    let gpr_deref_safe = regs.add_class(RegClassBuilder::without(
+        "GPR_DEREF_SAFE",
+        gpr,
+        &["rsp", "r12"],
+    ));
+    regs.add_class(RegClassBuilder::without(
+        "GPR_ZERO_DEREF_SAFE",
+        gpr_deref_safe,
+        &["rbp", "r13"],
+    ));
+    let gpr8 = regs.add_class(RegClassBuilder::subclass_of("GPR8", gpr, 0, 8));
+    let gpr8_deref_safe =
+        regs.add_class(RegClassBuilder::without("GPR8_DEREF_SAFE", gpr8, &["rsp"]));
+    regs.add_class(RegClassBuilder::without(
+        "GPR8_ZERO_DEREF_SAFE",
+        gpr8_deref_safe,
+        &["rbp"],
+    ));
+    regs.add_class(RegClassBuilder::subclass_of("ABCD", gpr, 0, 4));
+    let fpr = regs.add_class(RegClassBuilder::new_toplevel("FPR", float_regs));
+    regs.add_class(RegClassBuilder::subclass_of("FPR8", fpr, 0, 8));
+    regs.add_class(RegClassBuilder::new_toplevel("FLAG", flag_regs));
+
+    regs.finish()
+}
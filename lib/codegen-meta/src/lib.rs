@@ -1,7 +1,106 @@
+#[macro_use]
+pub mod cdsl;
+
+pub mod base;
+pub mod differential;
 pub mod error;
+pub mod extension;
+pub mod gen_binemit;
 pub mod gen_build_deps;
+pub mod gen_coverage;
+pub mod gen_encoding;
+pub mod gen_inst;
+pub mod gen_legalizer;
+pub mod gen_random_ir;
+pub mod gen_reference;
+pub mod gen_registers;
+pub mod gen_settings;
 pub mod gen_types;
+pub mod isa;
+pub mod semantics;
 
-mod base;
-mod cdsl;
+mod constant_hash;
 mod srcgen;
+mod unique_table;
+
+/// Generate all of the Rust source files emitted by this crate into
+/// `out_dir`, with their file names prefixed by `prefix`.
+///
+/// While the port from the Python meta language is in progress,
+/// `cranelift-codegen` uses the `new_` prefix so the generated files don't
+/// clobber the Python ones, unless it is built with the `rust-meta` feature.
+pub fn generate(prefix: &str, out_dir: &str) -> Result<(), error::Error> {
+    generate_with_extensions(prefix, out_dir, &[])
+}
+
+/// Generate all of the Rust source files like `generate`, including the
+/// instructions, legalizations and encodings of the out-of-tree
+/// `extensions`.
+pub fn generate_with_extensions(
+    prefix: &str,
+    out_dir: &str,
+    extensions: &[&dyn extension::Extension],
+) -> Result<(), error::Error> {
+    gen_types::generate(&format!("{}types.rs", prefix), out_dir)?;
+
+    let shared_settings =
+        gen_settings::generate_common(&format!("{}settings.rs", prefix), out_dir)?;
+    let mut shared_defs = base::define();
+    let mut isas = isa::define_all(&shared_settings, &mut shared_defs);
+    extension::apply(extensions, &mut shared_defs, &mut isas);
+
+    gen_inst::generate(
+        &shared_defs,
+        &isas,
+        &format!("{}opcodes.rs", prefix),
+        &format!("{}inst_builder.rs", prefix),
+        out_dir,
+    )?;
+
+    gen_legalizer::generate(
+        &shared_defs,
+        &isas,
+        &format!("{}legalize", prefix),
+        &format!("{}legalizer.rs", prefix),
+        out_dir,
+    )?;
+
+    for isa in &isas {
+        gen_settings::generate(isa, &format!("{}settings", prefix), out_dir)?;
+        gen_registers::generate(isa, &format!("{}registers", prefix), out_dir)?;
+    }
+
+    gen_encoding::generate(&shared_defs, &isas, &format!("{}encoding", prefix), out_dir)?;
+    gen_binemit::generate(
+        &shared_defs.format_registry,
+        &isas,
+        &format!("{}binemit", prefix),
+        out_dir,
+    )?;
+
+    gen_reference::generate(
+        &shared_settings,
+        &shared_defs,
+        &isas,
+        &format!("{}reference", prefix),
+        out_dir,
+    )?;
+    gen_coverage::generate(&shared_defs, &isas, &format!("{}coverage", prefix), out_dir)?;
+    gen_random_ir::generate(
+        &shared_defs,
+        &isas,
+        &format!("{}random_ir.rs", prefix),
+        out_dir,
+    )?;
+
+    Ok(())
+}
+
+/// Generate the JSON and Markdown references of the meta definitions into
+/// `out_dir`, without the Rust source files.
+pub fn generate_reference(out_dir: &str) -> Result<(), error::Error> {
+    let shared_settings = base::settings::generate();
+    let mut shared_defs = base::define();
+    let isas = isa::define_all(&shared_settings, &mut shared_defs);
+    gen_reference::generate(&shared_settings, &shared_defs, &isas, "reference", out_dir)
+}
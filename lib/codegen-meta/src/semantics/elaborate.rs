@@ -0,0 +1,481 @@
+//! Elaboration of concrete patterns into primitive instructions.
+//!
+//! A pattern with concrete types is rewritten into a semantically equivalent
+//! pattern containing only primitive instructions, which map well to SMT-LIB
+//! functions.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::mem;
+
+use cdsl::ast::{Expr, Literal};
+use cdsl::instructions::Instruction;
+use cdsl::operands::OperandKindFields;
+use cdsl::type_inference::subst;
+use cdsl::types::ValueType;
+use cdsl::typevar::TypeVar;
+use cdsl::xform::{Rtl, Symbol, SymbolTable, XForm};
+
+use semantics::Definitions;
+
+/// A variable of a concrete pattern.
+pub struct ConcreteVar {
+    pub name: String,
+    /// The concrete type of this variable, or `None` for immediate operands.
+    pub value_type: Option<ValueType>,
+}
+
+/// An argument of an instruction in a concrete pattern.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConcreteArg {
+    /// Index of a variable in the pattern.
+    Var(usize),
+    Literal(Literal),
+}
+
+/// An instruction application in a concrete pattern.
+pub struct ConcreteDef {
+    pub defs: Vec<usize>,
+    pub inst: Instruction,
+    pub args: Vec<ConcreteArg>,
+}
+
+impl ConcreteDef {
+    /// Get the variables used by this definition.
+    pub fn uses(&self) -> impl Iterator<Item = usize> + '_ {
+        self.args.iter().filter_map(|arg| match *arg {
+            ConcreteArg::Var(var) => Some(var),
+            ConcreteArg::Literal(_) => None,
+        })
+    }
+
+    /// Get the variable passed as the argument `opnum`.
+    pub fn arg_var(&self, opnum: usize) -> Result<usize, String> {
+        match self.args[opnum] {
+            ConcreteArg::Var(var) => Ok(var),
+            ConcreteArg::Literal(ref literal) => Err(format!(
+                "unexpected literal {} passed to {}",
+                literal, self.inst.name
+            )),
+        }
+    }
+}
+
+/// A pattern where every value has a concrete type.
+///
+/// Unlike `Rtl`, the variables of a concrete pattern are owned, so fresh
+/// variables can be created while elaborating it.
+pub struct ConcreteRtl {
+    pub vars: Vec<ConcreteVar>,
+    pub defs: Vec<ConcreteDef>,
+}
+
+impl ConcreteRtl {
+    /// Create a concrete instance of the pattern `rtl` of a transformation
+    /// whose variables are described by `symbols`, using the concrete type
+    /// variables of the typing `typing`.
+    ///
+    /// The variables present in `bindings` are replaced with their value,
+    /// which is how enumerated immediate operands are given concrete values.
+    pub fn new(
+        rtl: &Rtl,
+        symbols: &SymbolTable,
+        typing: &HashMap<TypeVar, TypeVar>,
+        bindings: &HashMap<&'static str, ConcreteArg>,
+    ) -> Self {
+        let mut concrete = Self {
+            vars: Vec::new(),
+            defs: Vec::new(),
+        };
+        concrete.defs = concrete.instantiate(rtl, symbols, typing, &mut bindings.clone(), "");
+        concrete
+    }
+
+    /// Get the index of the variable named `name`, if any.
+    pub fn lookup(&self, name: &str) -> Option<usize> {
+        self.vars.iter().position(|var| var.name == name)
+    }
+
+    /// Get the variables defined in this pattern.
+    fn definitions(&self) -> HashSet<usize> {
+        self.defs
+            .iter()
+            .flat_map(|def| def.defs.iter().cloned())
+            .collect()
+    }
+
+    /// Instantiate the definitions of `rtl`, creating new variables for the
+    /// pattern variables that are not in `bindings`. The names of the new
+    /// variables are suffixed with `suffix`.
+    fn instantiate(
+        &mut self,
+        rtl: &Rtl,
+        symbols: &SymbolTable,
+        typing: &HashMap<TypeVar, TypeVar>,
+        bindings: &mut HashMap<&'static str, ConcreteArg>,
+        suffix: &str,
+    ) -> Vec<ConcreteDef> {
+        let mut defs = Vec::new();
+        for def in &rtl.rtl {
+            let inst = &def.expr.inst;
+            let mut lookup = |name: &'static str, is_value: bool| {
+                if let Some(arg) = bindings.get(name) {
+                    return arg.clone();
+                }
+                let value_type = if is_value {
+                    Some(concrete_type(symbols.lookup(name).unwrap(), typing))
+                } else {
+                    None
+                };
+                self.vars.push(ConcreteVar {
+                    name: format!("{}{}", name, suffix),
+                    value_type,
+                });
+                let arg = ConcreteArg::Var(self.vars.len() - 1);
+                bindings.insert(name, arg.clone());
+                arg
+            };
+
+            let args = def
+                .expr
+                .args
+                .iter()
+                .enumerate()
+                .map(|(opnum, arg)| match *arg {
+                    Expr::Var(var) => lookup(var.name, inst.value_opnums.contains(&opnum)),
+                    Expr::Literal(ref literal) => ConcreteArg::Literal(literal.clone()),
+                })
+                .collect();
+            let results = def
+                .defs
+                .iter()
+                .enumerate()
+                .map(
+                    |(i, var)| match lookup(var.name, inst.value_results.contains(&i)) {
+                        ConcreteArg::Var(var) => var,
+                        ConcreteArg::Literal(_) => panic!("{} is defined by {}", var, inst.name),
+                    },
+                )
+                .collect();
+
+            defs.push(ConcreteDef {
+                defs: results,
+                inst: inst.clone(),
+                args,
+            });
+        }
+        defs
+    }
+}
+
+/// A concrete instance of a transformation: one of its concrete typings,
+/// along with one value of each of its enumerated immediate operands.
+pub struct Instance {
+    /// Unique name of the instance, identifying the transformation, its
+    /// typing and its immediate values.
+    pub name: String,
+    pub typing: HashMap<TypeVar, TypeVar>,
+    pub bindings: HashMap<&'static str, ConcreteArg>,
+}
+
+impl Instance {
+    /// Create the concrete source and destination patterns of `xform`.
+    pub fn patterns(&self, xform: &XForm) -> (ConcreteRtl, ConcreteRtl) {
+        (
+            ConcreteRtl::new(&xform.src, &xform.symbols, &self.typing, &self.bindings),
+            ConcreteRtl::new(&xform.dst, &xform.symbols, &self.typing, &self.bindings),
+        )
+    }
+}
+
+/// Get all the concrete instances of `xform`, for all of its concrete
+/// typings and all the values of its enumerated immediate operands.
+///
+/// The names of the instances start with `prefix`.
+pub fn instances(xform: &XForm, prefix: &str) -> Vec<Instance> {
+    let src = &xform.src.rtl[0];
+    let inst = &src.expr.inst;
+
+    // Enumerated immediates which are variables take all their values.
+    let mut variants: Vec<Vec<(&'static str, Literal)>> = vec![Vec::new()];
+    for &opnum in &inst.imm_opnums {
+        let (var, kind) = match (&src.expr.args[opnum], &inst.operands_in[opnum].kind) {
+            (&Expr::Var(var), kind) => (var, kind),
+            _ => continue,
+        };
+        let values = match kind.fields {
+            OperandKindFields::ImmEnum(ref values) => values,
+            _ => continue,
+        };
+        variants = variants
+            .into_iter()
+            .flat_map(|variant| {
+                values.iter().map(move |&(value, _)| {
+                    let mut variant = variant.clone();
+                    variant.push((var.name, Literal::enumerator_for(kind, value)));
+                    variant
+                })
+            })
+            .collect();
+    }
+
+    let mut free_typevars = xform.ti.free_typevars();
+    free_typevars.sort_by(|a, b| a.name().cmp(b.name()));
+
+    let mut instances = Vec::new();
+    for typing in xform.ti.concrete_typings() {
+        for variant in &variants {
+            let mut name = format!("{}-{}", prefix, inst.name);
+            for tv in &free_typevars {
+                let var_name = tv.name().trim_left_matches("typeof_");
+                let value_type = typing[tv].singleton_type().unwrap();
+                write!(name, "-{}.{}", var_name, value_type).unwrap();
+            }
+            let mut bindings = HashMap::new();
+            for &(var_name, ref literal) in variant {
+                if let Literal::Enumerator { value, .. } = *literal {
+                    write!(name, "-{}.{}", var_name, value).unwrap();
+                }
+                bindings.insert(var_name, ConcreteArg::Literal(literal.clone()));
+            }
+            instances.push(Instance {
+                name,
+                typing: typing.clone(),
+                bindings,
+            });
+        }
+    }
+    instances
+}
+
+/// Get the names of the input and output values of `xform`, given its
+/// concrete source pattern `src`.
+///
+/// Enumerated immediates are not variables of the concrete patterns, so they
+/// are neither inputs nor outputs.
+pub fn interface(xform: &XForm, src: &ConcreteRtl) -> (Vec<&'static str>, Vec<&'static str>) {
+    let symbols = xform
+        .symbols
+        .iter()
+        .filter(|symbol| src.lookup(symbol.name).is_some())
+        .collect::<Vec<_>>();
+    let inputs = symbols
+        .iter()
+        .filter(|symbol| symbol.is_input())
+        .map(|symbol| symbol.name)
+        .collect();
+    let outputs = symbols
+        .iter()
+        .filter(|symbol| symbol.is_output())
+        .map(|symbol| symbol.name)
+        .collect();
+    (inputs, outputs)
+}
+
+/// Get the concrete type of `symbol`, given the concrete type variables of
+/// the free type variables in `typing`.
+fn concrete_type(symbol: &Symbol, typing: &HashMap<TypeVar, TypeVar>) -> ValueType {
+    subst(symbol.type_var(), typing)
+        .singleton_type()
+        .unwrap_or_else(|| panic!("no concrete type for {}", symbol.name))
+}
+
+/// A semantic transformation matching a concrete definition, along with the
+/// bindings of the variables of its source pattern and the concrete type
+/// variables of its free type variables.
+type XFormMatch<'a> = (
+    &'a XForm,
+    HashMap<&'static str, ConcreteArg>,
+    HashMap<TypeVar, TypeVar>,
+);
+
+/// Find the unique transformation describing the semantics of the concrete
+/// definition `def`.
+fn find_matching_xform<'a>(
+    def: &ConcreteDef,
+    rtl: &ConcreteRtl,
+    defs: &'a Definitions,
+) -> Result<XFormMatch<'a>, String> {
+    let mut matches = Vec::new();
+    'xforms: for xform in defs.semantics.get(&def.inst) {
+        let pattern = &xform.src.rtl[0];
+
+        let mut bindings = HashMap::new();
+        let mut typing = HashMap::new();
+        let mut bind = |name: &'static str, arg: &ConcreteArg| -> bool {
+            if let ConcreteArg::Var(var) = *arg {
+                if let Some(ref value_type) = rtl.vars[var].value_type {
+                    let type_var = xform.symbols.lookup(name).unwrap().type_var();
+                    typing.insert(type_var.clone(), TypeVar::new_singleton(value_type.clone()));
+                }
+            }
+            bindings
+                .insert(name, arg.clone())
+                .map_or(true, |prev| prev == *arg)
+        };
+
+        for (var, &concrete) in pattern.defs.iter().zip(&def.defs) {
+            if !bind(var.name, &ConcreteArg::Var(concrete)) {
+                continue 'xforms;
+            }
+        }
+        for (arg, concrete) in pattern.expr.args.iter().zip(&def.args) {
+            let matched = match *arg {
+                Expr::Var(var) => bind(var.name, concrete),
+                // There is no substitution if the source pattern has a
+                // different literal, e.g. for the semantics of `icmp.eq`.
+                Expr::Literal(ref literal) => *concrete == ConcreteArg::Literal(literal.clone()),
+            };
+            if !matched {
+                continue 'xforms;
+            }
+        }
+
+        if xform.ti.permits(&typing) {
+            let free_typing = typing
+                .into_iter()
+                .filter(|(type_var, _)| !type_var.is_derived())
+                .collect();
+            matches.push((xform, bindings, free_typing));
+        }
+    }
+
+    if matches.len() != 1 {
+        let types = def
+            .defs
+            .iter()
+            .chain(def.uses().collect::<Vec<_>>().iter())
+            .filter_map(|&var| rtl.vars[var].value_type.as_ref())
+            .map(|value_type| value_type.to_string())
+            .collect::<Vec<_>>();
+        return Err(format!(
+            "{} semantic transformations of {} match the types {}",
+            if matches.is_empty() { "no" } else { "several" },
+            def.inst.name,
+            types.join(", ")
+        ));
+    }
+    Ok(matches.pop().unwrap())
+}
+
+/// Cleanup the redundant conversions created by the elaboration:
+///
+/// 1. `prim_to_bv` of a value defined by `prim_from_bv` is replaced by the
+///    original bitvector.
+/// 2. `prim_to_bv` of a value already converted is replaced by the result of
+///    the first conversion.
+///
+/// Then remove the `prim_from_bv` conversions whose result is unused, unless
+/// they define one of the `outputs`.
+fn cleanup_semantics(mut rtl: ConcreteRtl, outputs: &HashSet<usize>) -> ConcreteRtl {
+    let mut substitution: Vec<usize> = (0..rtl.vars.len()).collect();
+    let mut definition: HashMap<usize, usize> = HashMap::new();
+    let mut prim_to_bv_map: HashMap<usize, usize> = HashMap::new();
+    let mut new_defs: Vec<ConcreteDef> = Vec::new();
+
+    // Pass 1: Remove redundant prim_to_bv.
+    for mut def in mem::replace(&mut rtl.defs, Vec::new()) {
+        for arg in &mut def.args {
+            if let ConcreteArg::Var(ref mut var) = *arg {
+                *var = substitution[*var];
+            }
+        }
+
+        if def.inst.name == "prim_to_bv" {
+            let (arg, df) = (def.arg_var(0).unwrap(), def.defs[0]);
+            if let Some(&index) = definition.get(&arg) {
+                let def_loc = &new_defs[index];
+                if def_loc.inst.name == "prim_from_bv" {
+                    substitution[df] = def_loc.arg_var(0).unwrap();
+                    continue;
+                }
+            }
+            if let Some(&converted) = prim_to_bv_map.get(&arg) {
+                substitution[df] = converted;
+                continue;
+            }
+            prim_to_bv_map.insert(arg, df);
+        }
+
+        for &var in &def.defs {
+            // Guaranteed by SSA.
+            assert!(definition.insert(var, new_defs.len()).is_none());
+        }
+        new_defs.push(def);
+    }
+
+    // Pass 2: Remove dead prim_from_bv.
+    let mut live = outputs.clone();
+    live.extend(new_defs.iter().flat_map(|def| def.uses()));
+    new_defs.retain(|def| def.inst.name != "prim_from_bv" || live.contains(&def.defs[0]));
+
+    rtl.defs = new_defs;
+    rtl
+}
+
+/// Is `inst` a primitive instruction?
+pub fn is_primitive(inst: &Instruction, defs: &Definitions) -> bool {
+    defs.primitives.iter().any(|prim| prim.name == inst.name)
+}
+
+/// Given a concrete pattern `rtl`, return a semantically equivalent pattern
+/// containing only primitive instructions.
+pub fn elaborate(mut rtl: ConcreteRtl, defs: &Definitions) -> Result<ConcreteRtl, String> {
+    let outputs = rtl.definitions();
+    let mut idx = 0;
+
+    loop {
+        let mut fixpoint = true;
+        let mut new_defs = Vec::new();
+        for def in mem::replace(&mut rtl.defs, Vec::new()) {
+            if is_primitive(&def.inst, defs) {
+                new_defs.push(def);
+                continue;
+            }
+            let (xform, mut bindings, typing) = find_matching_xform(&def, &rtl, defs)?;
+            let suffix = format!(".{}", idx);
+            new_defs.extend(rtl.instantiate(
+                &xform.dst,
+                &xform.symbols,
+                &typing,
+                &mut bindings,
+                &suffix,
+            ));
+            idx += 1;
+            fixpoint = false;
+        }
+        rtl.defs = new_defs;
+        if fixpoint {
+            break;
+        }
+    }
+
+    Ok(cleanup_semantics(rtl, &outputs))
+}
+
+/// Describe a definition of a concrete pattern, for comments in the
+/// generated queries.
+pub fn describe(def: &ConcreteDef, rtl: &ConcreteRtl) -> String {
+    let var = |index: usize| {
+        let var = &rtl.vars[index];
+        match var.value_type {
+            Some(ref value_type) => format!("{}: {}", var.name, value_type),
+            None => var.name.clone(),
+        }
+    };
+    let args = def
+        .args
+        .iter()
+        .map(|arg| match *arg {
+            ConcreteArg::Var(index) => var(index),
+            ConcreteArg::Literal(ref literal) => literal.to_string(),
+        })
+        .collect::<Vec<_>>();
+    let defs = def.defs.iter().map(|&index| var(index)).collect::<Vec<_>>();
+    format!(
+        "({}) << {}({})",
+        defs.join(", "),
+        def.inst.name,
+        args.join(", ")
+    )
+}
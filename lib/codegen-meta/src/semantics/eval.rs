@@ -0,0 +1,355 @@
+//! Concrete evaluation of legalizations.
+//!
+//! This is a cheap complement to the SMT-LIB queries of `smtlib`, which
+//! doesn't need an external solver. The source and destination patterns of
+//! a concrete legalization are elaborated into primitive instructions, and
+//! both are evaluated on the same random inputs: any input giving different
+//! outputs is a counterexample.
+//!
+//! Values are evaluated as 128-bit integers, so the legalizations of wider
+//! vectors can't be evaluated.
+
+use std::collections::HashMap;
+
+use cdsl::ast::Literal;
+use cdsl::xform::XForm;
+
+use semantics::elaborate::{elaborate, instances, interface, ConcreteArg, ConcreteRtl, Instance};
+use semantics::Definitions;
+
+/// The widest value that can be evaluated.
+const MAX_WIDTH: u64 = 128;
+
+/// The result of evaluating a legalization on random inputs.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    /// All the inputs gave the same outputs.
+    Passed,
+    /// Some inputs gave different outputs, as described.
+    Mismatch(String),
+    /// The legalization couldn't be evaluated, for the given reason.
+    Unknown(String),
+}
+
+/// The evaluation of one legalization, under one concrete typing.
+pub struct Check {
+    /// Unique name of the check, like the name of the matching SMT-LIB query.
+    pub name: String,
+    pub outcome: Outcome,
+}
+
+/// A deterministic pseudo-random number generator, so that mismatches can be
+/// reproduced.
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // The state of a xorshift generator must not be zero.
+        Self { state: seed | 1 }
+    }
+
+    /// Get the next random number, with the xorshift64* algorithm.
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// Get a random value of `width` bits. The values at the boundaries of
+    /// the signed and unsigned ranges are much more likely than they would
+    /// be by chance, since they expose most of the bugs.
+    fn value(&mut self, width: u64) -> u128 {
+        let sign = 1u128 << (width - 1);
+        let value = match self.next() % 8 {
+            0 => 0,
+            1 => 1,
+            2 => !0,
+            3 => sign,
+            4 => sign - 1,
+            _ => u128::from(self.next()) << 64 | u128::from(self.next()),
+        };
+        value & mask(width)
+    }
+}
+
+/// Get the mask of the low `width` bits.
+fn mask(width: u64) -> u128 {
+    if width >= 128 {
+        !0
+    } else {
+        (1 << width) - 1
+    }
+}
+
+/// Interpret the `width`-bit value `value` as a signed integer.
+fn signed(value: u128, width: u64) -> i128 {
+    let shift = 128 - width;
+    (value << shift) as i128 >> shift
+}
+
+/// Get the width of the variable `var` of `rtl`. Immediate operands are
+/// 64-bit wide.
+fn width(rtl: &ConcreteRtl, var: usize) -> u64 {
+    rtl.vars[var]
+        .value_type
+        .as_ref()
+        .map_or(64, |value_type| value_type.width())
+}
+
+/// Evaluate the primitive pattern `rtl`, given the values of its inputs in
+/// `values`. The values of all the other variables are added to `values`.
+fn evaluate(rtl: &ConcreteRtl, values: &mut HashMap<usize, u128>) -> Result<(), String> {
+    for def in &rtl.defs {
+        let result_width = width(rtl, def.defs[0]);
+        let (x, y) = {
+            let arg = |opnum: usize| -> Result<u128, String> {
+                let var = def.arg_var(opnum)?;
+                values
+                    .get(&var)
+                    .cloned()
+                    .ok_or_else(|| format!("{} is used before being defined", rtl.vars[var].name))
+            };
+            let operands = def.inst.value_opnums.len();
+            (
+                if operands > 0 { arg(0)? } else { 0 },
+                if operands > 1 { arg(1)? } else { 0 },
+            )
+        };
+        let x_width = || width(rtl, def.arg_var(0).unwrap());
+
+        let result = match def.inst.name {
+            "prim_to_bv" | "prim_from_bv" | "bvzeroext" => x,
+            "bv_from_imm64" => match def.args[0] {
+                ConcreteArg::Literal(Literal::Int { value, .. }) => u128::from(value as u64),
+                ConcreteArg::Literal(Literal::Bits { bits, .. }) => u128::from(bits),
+                ConcreteArg::Literal(ref literal) => {
+                    return Err(format!("no bitvector value for {}", literal));
+                }
+                // Immediate operands which are variables are inputs.
+                ConcreteArg::Var(imm) => values[&imm],
+            },
+            "bvite" => {
+                if x != 0 {
+                    y
+                } else {
+                    values[&def.arg_var(2)?]
+                }
+            }
+            "bvsplit" => {
+                let half = x_width() / 2;
+                values.insert(def.defs[1], x >> half);
+                x
+            }
+            "bvconcat" => x | y << x_width(),
+            "bvadd" => x.wrapping_add(y),
+            "bvsub" => x.wrapping_sub(y),
+            "bvmul" => x.wrapping_mul(y),
+            "bvand" => x & y,
+            "bvor" => x | y,
+            "bvxor" => x ^ y,
+            "bvnot" => !x,
+            "bveq" => u128::from(x == y),
+            "bvne" => u128::from(x != y),
+            "bvsge" => u128::from(signed(x, x_width()) >= signed(y, x_width())),
+            "bvsgt" => u128::from(signed(x, x_width()) > signed(y, x_width())),
+            "bvsle" => u128::from(signed(x, x_width()) <= signed(y, x_width())),
+            "bvslt" => u128::from(signed(x, x_width()) < signed(y, x_width())),
+            "bvuge" => u128::from(x >= y),
+            "bvugt" => u128::from(x > y),
+            "bvule" => u128::from(x <= y),
+            "bvult" => u128::from(x < y),
+            "bvsignext" => signed(x, x_width()) as u128,
+            "bvreduce" => x,
+            other => return Err(format!("unknown primitive instruction {}", other)),
+        };
+
+        values.insert(def.defs[0], result & mask(result_width));
+    }
+    Ok(())
+}
+
+/// Evaluate the concrete `instance` of `xform` on `trials` random inputs.
+fn check_instance(
+    defs: &Definitions,
+    xform: &XForm,
+    instance: &Instance,
+    rng: &mut Rng,
+    trials: usize,
+) -> Result<Outcome, String> {
+    let (src, dst) = instance.patterns(xform);
+    let (inputs, outputs) = interface(xform, &src);
+    if outputs.is_empty() {
+        return Err("the legalization has no outputs".into());
+    }
+
+    // Check the widths before the elaboration, which is slow for wide
+    // vectors.
+    for var in src.vars.iter().chain(&dst.vars) {
+        if let Some(ref value_type) = var.value_type {
+            if value_type.width() > MAX_WIDTH {
+                return Err(format!("{} is wider than {} bits", var.name, MAX_WIDTH));
+            }
+        }
+    }
+
+    let src = elaborate(src, defs)?;
+    let dst = elaborate(dst, defs)?;
+
+    for _ in 0..trials {
+        let mut src_values = HashMap::new();
+        let mut dst_values = HashMap::new();
+        let mut assignments = Vec::new();
+        for &input in &inputs {
+            let src_var = src.lookup(input).unwrap();
+            let value = rng.value(width(&src, src_var));
+            src_values.insert(src_var, value);
+            // Inputs can be unused in the destination pattern.
+            if let Some(dst_var) = dst.lookup(input) {
+                dst_values.insert(dst_var, value);
+            }
+            assignments.push(format!("{} = {:#x}", input, value));
+        }
+
+        evaluate(&src, &mut src_values)?;
+        evaluate(&dst, &mut dst_values)?;
+
+        let mut differences = Vec::new();
+        for &output in &outputs {
+            let value = |rtl: &ConcreteRtl, values: &HashMap<usize, u128>| {
+                rtl.lookup(output).and_then(|var| values.get(&var)).cloned()
+            };
+            match (value(&src, &src_values), value(&dst, &dst_values)) {
+                (Some(a), Some(b)) => {
+                    if a != b {
+                        differences.push(format!("{} = {:#x} instead of {:#x}", output, b, a));
+                    }
+                }
+                _ => return Err(format!("output {} isn't computed by both patterns", output)),
+            }
+        }
+        if !differences.is_empty() {
+            return Ok(Outcome::Mismatch(format!(
+                "{} gives {}",
+                assignments.join(", "),
+                differences.join(", ")
+            )));
+        }
+    }
+    Ok(Outcome::Passed)
+}
+
+/// Evaluate the legalization `xform` on `trials` random inputs, for all of
+/// its concrete instances.
+///
+/// The names of the checks start with `prefix`.
+pub fn xform_checks(
+    defs: &Definitions,
+    xform: &XForm,
+    prefix: &str,
+    rng: &mut Rng,
+    trials: usize,
+) -> Vec<Check> {
+    instances(xform, prefix)
+        .into_iter()
+        .map(|instance| Check {
+            outcome: check_instance(defs, xform, &instance, rng, trials)
+                .unwrap_or_else(Outcome::Unknown),
+            name: instance.name,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cdsl::ast::var;
+    use cdsl::xform::Rtl;
+    use semantics::{define, evaluate_legalizations};
+
+    #[test]
+    fn legalizations_preserve_semantics() {
+        let defs = define();
+        let checks = evaluate_legalizations(&defs, 64);
+
+        let mismatches = checks
+            .iter()
+            .filter_map(|check| match check.outcome {
+                Outcome::Mismatch(ref reason) => Some(format!("{}: {}", check.name, reason)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+
+        // Most checks can't be evaluated yet. Make sure the ones that can still pass, so that
+        // turning them all into `Unknown` doesn't go unnoticed.
+        let passed = checks
+            .iter()
+            .filter(|check| check.outcome == Outcome::Passed)
+            .count();
+        assert!(passed >= 500, "only {} checks passed", passed);
+
+        // Find the outcomes of the legalizations of `inst_typing` in `group`, whatever their
+        // position in the group.
+        let outcomes = |group: &str, inst_typing: &str| {
+            let found = checks
+                .iter()
+                .filter(|check| {
+                    let mut parts = check.name.splitn(3, '-');
+                    parts.next() == Some(group)
+                        && parts
+                            .next()
+                            .and_then(|index| index.parse::<usize>().ok())
+                            .is_some()
+                        && parts.next() == Some(inst_typing)
+                })
+                .map(|check| &check.outcome)
+                .collect::<Vec<_>>();
+            assert!(!found.is_empty(), "no {} check {}", group, inst_typing);
+            found
+        };
+        let passes = |group: &str, inst_typing: &str| {
+            outcomes(group, inst_typing)
+                .iter()
+                .all(|&outcome| *outcome == Outcome::Passed)
+        };
+        assert!(passes("narrow", "iadd-x.i64"));
+        assert!(passes("narrow", "band-x.i32x4"));
+        assert!(passes("widen", "iadd"));
+        assert!(passes("expand", "bxor_imm-x.i32"));
+        assert_eq!(
+            outcomes("x86_expand", "umulhi-x.i64"),
+            [&Outcome::Unknown(
+                "no semantic transformations of umulhi match the types i64, i64, i64".into()
+            )]
+        );
+    }
+
+    #[test]
+    fn report_mismatch() {
+        let defs = define();
+        let iadd = defs.shared.instructions.by_name("iadd");
+        let isub = defs.shared.instructions.by_name("isub");
+        let (a, x, y) = (var("a"), var("x"), var("y"));
+        let xform = XForm::new(
+            Rtl::new(vec![def!(a = iadd(x, y))]),
+            Rtl::new(vec![def!(a = isub(x, y))]),
+            vec![],
+        );
+
+        let checks = xform_checks(&defs, &xform, "wrong", &mut Rng::new(1), 16);
+        let check = checks
+            .iter()
+            .find(|check| check.name == "wrong-iadd-x.i8")
+            .unwrap();
+        match check.outcome {
+            Outcome::Mismatch(ref reason) => {
+                assert!(reason.starts_with("x = "), "{}", reason);
+                assert!(reason.contains(" gives a = "), "{}", reason);
+            }
+            ref outcome => panic!("unexpected outcome {:?}", outcome),
+        }
+    }
+}
@@ -0,0 +1,65 @@
+//! Useful semantics "macro" instructions built on top of the primitives.
+
+use cdsl::ast::{var, Literal};
+use cdsl::formats::FormatRegistry;
+use cdsl::instructions::{InstructionBuilder as Inst, InstructionGroup, InstructionGroupBuilder};
+use cdsl::operands::Operand;
+use cdsl::typevar::{TypeSetBuilder, TypeVar};
+
+use base::immediates::Immediates;
+use base::types;
+use semantics::InstructionSemantics;
+
+pub fn define(format_registry: &FormatRegistry) -> InstructionGroup {
+    let mut ig = InstructionGroupBuilder::new(
+        "primitive_macros",
+        "Semantic macros instruction set",
+        format_registry,
+    );
+
+    let b1: &TypeVar = &TypeVar::new_singleton(types::Bool::B1.into());
+    let bv1 = &TypeVar::new("BV1", "", TypeSetBuilder::new().bitvecs(1..=1).finish());
+
+    let cond = &Operand::new("cond", b1);
+    let bv1_op = &Operand::new("bv1_op", bv1);
+
+    ig.push(
+        Inst::new(
+            "bool2bv",
+            r#"
+            Convert a b1 value to a 1-bit BV.
+            "#,
+        )
+        .operands_in(vec![cond])
+        .operands_out(vec![bv1_op]),
+    );
+
+    ig.finish()
+}
+
+/// Describe the semantics of the macro instructions in terms of the
+/// primitives.
+pub fn define_semantics(
+    macros: &InstructionGroup,
+    primitives: &InstructionGroup,
+    immediates: &Immediates,
+    semantics: &mut InstructionSemantics,
+) {
+    let bool2bv = macros.by_name("bool2bv");
+    let bv_from_imm64 = primitives.by_name("bv_from_imm64");
+    let bvite = primitives.by_name("bvite");
+
+    let one = Literal::constant(&immediates.imm64, 1);
+    let zero = Literal::constant(&immediates.imm64, 0);
+    let (v1, v2, bvone, bvzero) = (var("v1"), var("v2"), var("bvone"), var("bvzero"));
+
+    semantics.add(
+        def!(v1 = bool2bv(v2)),
+        vec![
+            def!(bvone = bv_from_imm64(one)),
+            def!(bvzero = bv_from_imm64(zero)),
+            def!(v1 = bvite(v2, bvone, bvzero)),
+        ],
+        vec![],
+    );
+}
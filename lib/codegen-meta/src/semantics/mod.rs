@@ -0,0 +1,238 @@
+//! Semantics of instructions, and verification of legalizations.
+//!
+//! The semantics of an instruction are described by a list of
+//! transformations rewriting it into primitive instructions (see
+//! `primitives`), or into other instructions whose semantics are known.
+//! Exactly one of these transformations must apply to any concrete typing of
+//! the instruction.
+//!
+//! A concrete legalization is elaborated into primitive instructions, which
+//! map to SMT-LIB bitvector functions. This lets us build a query for an SMT
+//! solver which is unsatisfiable if and only if the legalization preserves
+//! the semantics of the instructions it rewrites.
+
+pub mod elaborate;
+pub mod eval;
+pub mod macros;
+pub mod primitives;
+pub mod smtlib;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use cdsl::ast::Def;
+use cdsl::instructions::{Instruction, InstructionGroup};
+use cdsl::type_inference::Constraint;
+use cdsl::xform::{Rtl, XForm};
+
+use base;
+use error;
+use isa;
+
+/// The transformations describing the semantics of each instruction, indexed
+/// by instruction name.
+#[derive(Default)]
+pub struct InstructionSemantics {
+    xforms: HashMap<&'static str, Vec<XForm>>,
+}
+
+impl InstructionSemantics {
+    pub fn new() -> Self {
+        Self {
+            xforms: HashMap::new(),
+        }
+    }
+
+    /// Add a case to the semantics of the instruction of `src`: when the
+    /// type `constraints` hold, `src` is equivalent to `dst`.
+    pub fn add(&mut self, src: Def, dst: Vec<Def>, constraints: Vec<Constraint>) {
+        let name = src.expr.inst.name;
+        let xform = XForm::new(Rtl::new(vec![src]), Rtl::new(dst), constraints);
+        for &var in &xform.src.rtl[0].defs {
+            assert!(
+                xform.symbols.get(var).is_output(),
+                "{} not defined in the semantics of {}",
+                var,
+                name
+            );
+        }
+        self.xforms.entry(name).or_insert_with(Vec::new).push(xform);
+    }
+
+    /// Get the transformations describing the semantics of `inst`.
+    pub fn get(&self, inst: &Instruction) -> &[XForm] {
+        self.xforms
+            .get(inst.name)
+            .map(|xforms| xforms.as_slice())
+            .unwrap_or(&[])
+    }
+}
+
+/// All the definitions needed to elaborate instructions into primitives.
+pub struct Definitions {
+    pub shared: base::Definitions,
+    pub primitives: InstructionGroup,
+    pub macros: InstructionGroup,
+    pub semantics: InstructionSemantics,
+}
+
+pub fn define() -> Definitions {
+    let mut shared = base::define();
+    // The target ISAs add their own legalization groups.
+    isa::define_all(&base::settings::generate(), &mut shared);
+    let primitives = primitives::define(&shared.format_registry, &shared.imm);
+    let macros = macros::define(&shared.format_registry);
+
+    let mut semantics = InstructionSemantics::new();
+    macros::define_semantics(&macros, &primitives, &shared.imm, &mut semantics);
+    base::semantics::define(
+        &shared.instructions,
+        &shared.imm,
+        &primitives,
+        &macros,
+        &mut semantics,
+    );
+
+    Definitions {
+        shared,
+        primitives,
+        macros,
+        semantics,
+    }
+}
+
+/// Names of the legalization groups whose transformations are verified.
+const VERIFIED_GROUPS: [&str; 3] = ["narrow", "widen", "expand"];
+
+/// Build the verification queries for every transformation in the `narrow`,
+/// `widen` and `expand` legalization groups, under all of their concrete
+/// typings.
+pub fn legalization_queries(defs: &Definitions) -> Vec<smtlib::Query> {
+    let groups = &defs.shared.transform_groups;
+    let mut queries = Vec::new();
+    for &group_name in &VERIFIED_GROUPS {
+        let group = groups.get(groups.by_name(group_name));
+        for (index, xform) in group.xforms.iter().enumerate() {
+            queries.extend(smtlib::xform_queries(
+                defs,
+                xform,
+                &format!("{}-{}", group_name, index),
+            ));
+        }
+    }
+    queries
+}
+
+/// Names of the legalization groups whose transformations are evaluated.
+const EVALUATED_GROUPS: [&str; 4] = ["narrow", "widen", "expand", "x86_expand"];
+
+/// Evaluate every transformation in the `narrow`, `widen`, `expand` and
+/// `x86_expand` legalization groups on `trials` random inputs, under all of
+/// their concrete typings.
+pub fn evaluate_legalizations(defs: &Definitions, trials: usize) -> Vec<eval::Check> {
+    let groups = &defs.shared.transform_groups;
+    let mut rng = eval::Rng::new(0x5eed);
+    let mut checks = Vec::new();
+    for &group_name in &EVALUATED_GROUPS {
+        let group = groups.get(groups.by_name(group_name));
+        for (index, xform) in group.xforms.iter().enumerate() {
+            checks.extend(eval::xform_checks(
+                defs,
+                xform,
+                &format!("{}-{}", group_name, index),
+                &mut rng,
+                trials,
+            ));
+        }
+    }
+    checks
+}
+
+/// Write a standalone `.smt2` file into `out_dir` for every legalization
+/// that can be elaborated into primitive instructions.
+///
+/// Return all the queries, including the ones that couldn't be built because
+/// some instruction has no known semantics.
+pub fn write_legalization_queries(out_dir: &Path) -> Result<Vec<smtlib::Query>, error::Error> {
+    let queries = legalization_queries(&define());
+    fs::create_dir_all(out_dir)?;
+    for query in &queries {
+        if let Ok(ref smtlib) = query.smtlib {
+            fs::write(out_dir.join(query.file_name()), smtlib)?;
+        }
+    }
+    Ok(queries)
+}
+
+/// Write the legalization queries into `out_dir`, and run the SMT solver
+/// `solver` on each of them.
+///
+/// Return the name of every query along with its verdict. Queries which
+/// couldn't be built are reported as `Verdict::Unknown`.
+pub fn verify_legalizations(
+    solver: &str,
+    out_dir: &Path,
+) -> Result<Vec<(String, smtlib::Verdict)>, error::Error> {
+    let mut verdicts = Vec::new();
+    for query in write_legalization_queries(out_dir)? {
+        let verdict = match query.smtlib {
+            Ok(_) => smtlib::run_solver(solver, &out_dir.join(query.file_name()))?,
+            Err(reason) => smtlib::Verdict::Unknown(reason),
+        };
+        verdicts.push((query.name, verdict));
+    }
+    Ok(verdicts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn semantics_type_check() {
+        let defs = define();
+        let iadd = defs.shared.instructions.by_name("iadd");
+        assert_eq!(defs.semantics.get(iadd).len(), 2);
+        let icmp = defs.shared.instructions.by_name("icmp");
+        assert_eq!(defs.semantics.get(icmp).len(), 11);
+        let band = defs.shared.instructions.by_name("band");
+        assert_eq!(defs.semantics.get(band).len(), 1);
+        let fadd = defs.shared.instructions.by_name("fadd");
+        assert!(defs.semantics.get(fadd).is_empty());
+    }
+
+    #[test]
+    fn legalization_query_names() {
+        let defs = define();
+        let groups = &defs.shared.transform_groups;
+        let expand = groups.get(groups.by_name("expand"));
+
+        let iadd_cout = smtlib::xform_queries(&defs, &expand.xforms[0], "expand-0");
+        let names = iadd_cout
+            .iter()
+            .map(|query| query.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "expand-0-iadd_cout-x.i8",
+                "expand-0-iadd_cout-x.i16",
+                "expand-0-iadd_cout-x.i32",
+                "expand-0-iadd_cout-x.i64",
+            ]
+        );
+        assert!(iadd_cout.iter().all(|query| query.smtlib.is_ok()));
+
+        // Floating point instructions have no semantics yet.
+        let fabs = expand
+            .xforms
+            .iter()
+            .find(|xform| xform.src.rtl[0].expr.inst.name == "fabs")
+            .unwrap();
+        for query in smtlib::xform_queries(&defs, fabs, "expand") {
+            let reason = query.smtlib.unwrap_err();
+            assert!(reason.starts_with("no semantic transformations of fabs"));
+        }
+    }
+}
@@ -0,0 +1,238 @@
+//! Cranelift primitive instruction set.
+//!
+//! This module defines a primitive instruction set, in terms of which the base
+//! set is described. Most instructions in this set correspond 1-1 with an
+//! SMT-LIB bitvector function.
+
+#![allow(non_snake_case)]
+
+use cdsl::formats::FormatRegistry;
+use cdsl::instructions::{InstructionBuilder as Inst, InstructionGroup, InstructionGroupBuilder};
+use cdsl::operands::Operand;
+use cdsl::type_inference::Constraint;
+use cdsl::typevar::{Interval, TypeSetBuilder, TypeVar};
+
+use base::immediates::Immediates;
+use base::types;
+
+pub fn define(format_registry: &FormatRegistry, immediates: &Immediates) -> InstructionGroup {
+    let mut ig =
+        InstructionGroupBuilder::new("primitive", "Primitive instruction set", format_registry);
+
+    let b1: &TypeVar = &TypeVar::new_singleton(types::Bool::B1.into());
+
+    let BV = &TypeVar::new(
+        "BV",
+        "A bitvector type.",
+        TypeSetBuilder::new().bitvecs(Interval::All).finish(),
+    );
+    let Real = &TypeVar::new(
+        "Real",
+        "Any real type.",
+        TypeSetBuilder::new()
+            .ints(Interval::All)
+            .floats(Interval::All)
+            .bools(Interval::All)
+            .simd_lanes(Interval::All)
+            .finish(),
+    );
+
+    let x = &Operand::new("x", BV).with_doc("A semantic value X");
+    let y = &Operand::new("y", BV).with_doc("A semantic value Y (same width as X)");
+    let a = &Operand::new("a", BV).with_doc("A semantic value A (same width as X)");
+    let cond = &Operand::new("b", b1).with_doc("A b1 value");
+
+    let real = &Operand::new("real", Real).with_doc("A real cranelift value");
+    let fromReal = &Operand::new("fromReal", &Real.to_bitvec())
+        .with_doc("A real cranelift value converted to a BV");
+
+    // BV conversion and materialization.
+
+    ig.push(
+        Inst::new(
+            "prim_to_bv",
+            r#"
+            Convert an SSA Value to a flat bitvector.
+            "#,
+        )
+        .operands_in(vec![real])
+        .operands_out(vec![fromReal]),
+    );
+
+    ig.push(
+        Inst::new(
+            "prim_from_bv",
+            r#"
+            Convert a flat bitvector to a real SSA Value.
+            "#,
+        )
+        .operands_in(vec![fromReal])
+        .operands_out(vec![real]),
+    );
+
+    let N = &Operand::new("N", &immediates.imm64);
+
+    ig.push(
+        Inst::new(
+            "bv_from_imm64",
+            r#"
+            Materialize an imm64 as a bitvector.
+            "#,
+        )
+        .operands_in(vec![N])
+        .operands_out(vec![a]),
+    );
+
+    // Generics.
+
+    ig.push(
+        Inst::new(
+            "bvite",
+            r#"
+            Bitvector ternary operator.
+            "#,
+        )
+        .operands_in(vec![cond, x, y])
+        .operands_out(vec![a]),
+    );
+
+    let xh = &Operand::new("xh", &BV.half_width())
+        .with_doc("A semantic value representing the upper half of X");
+    let xl = &Operand::new("xl", &BV.half_width())
+        .with_doc("A semantic value representing the lower half of X");
+
+    ig.push(
+        Inst::new(
+            "bvsplit",
+            r#"
+            Split a bitvector into its low and high halves.
+            "#,
+        )
+        .operands_in(vec![x])
+        .operands_out(vec![xl, xh]),
+    );
+
+    let xy = &Operand::new("xy", &BV.double_width())
+        .with_doc("A semantic value representing the concatenation of X and Y");
+
+    ig.push(
+        Inst::new(
+            "bvconcat",
+            r#"
+            Concatenate two bitvectors, `x` forming the low half of the result.
+            "#,
+        )
+        .operands_in(vec![x, y])
+        .operands_out(vec![xy]),
+    );
+
+    ig.push(
+        Inst::new(
+            "bvadd",
+            r#"
+            Standard 2's complement addition. Equivalent to wrapping integer
+            addition: `a := x + y \pmod{2^B}`.
+
+            This instruction does not depend on the signed/unsigned
+            interpretation of the operands.
+            "#,
+        )
+        .operands_in(vec![x, y])
+        .operands_out(vec![a]),
+    );
+
+    let binary_ops = [
+        ("bvsub", "Wrapping bitvector subtraction."),
+        ("bvmul", "Wrapping bitvector multiplication."),
+        ("bvand", "Bitwise and."),
+        ("bvor", "Bitwise or."),
+        ("bvxor", "Bitwise xor."),
+    ];
+    for &(name, doc) in &binary_ops {
+        ig.push(
+            Inst::new(name, doc)
+                .operands_in(vec![x, y])
+                .operands_out(vec![a]),
+        );
+    }
+
+    ig.push(
+        Inst::new(
+            "bvnot",
+            r#"
+            Bitwise not.
+            "#,
+        )
+        .operands_in(vec![x])
+        .operands_out(vec![a]),
+    );
+
+    // Bitvector comparisons.
+
+    let comparisons = [
+        ("bveq", "Bitvector equality."),
+        ("bvne", "Bitvector inequality."),
+        ("bvsge", "Signed bitvector greater or equal."),
+        ("bvsgt", "Signed bitvector greater than."),
+        ("bvsle", "Signed bitvector less than or equal."),
+        ("bvslt", "Signed bitvector less than."),
+        ("bvuge", "Unsigned bitvector greater or equal."),
+        ("bvugt", "Unsigned bitvector greater than."),
+        ("bvule", "Unsigned bitvector less than or equal."),
+        ("bvult", "Unsigned bitvector less than."),
+    ];
+    for &(name, doc) in &comparisons {
+        ig.push(
+            Inst::new(name, doc)
+                .operands_in(vec![x, y])
+                .operands_out(vec![cond]),
+        );
+    }
+
+    // Extensions.
+
+    let ToBV = &TypeVar::new(
+        "ToBV",
+        "A bitvector type.",
+        TypeSetBuilder::new().bitvecs(Interval::All).finish(),
+    );
+    let x1 = &Operand::new("x1", ToBV);
+
+    ig.push(
+        Inst::new(
+            "bvzeroext",
+            r#"
+            Unsigned bitvector extension.
+            "#,
+        )
+        .operands_in(vec![x])
+        .operands_out(vec![x1])
+        .constraints(vec![Constraint::WiderOrEq(ToBV.clone(), BV.clone())]),
+    );
+
+    ig.push(
+        Inst::new(
+            "bvsignext",
+            r#"
+            Signed bitvector extension.
+            "#,
+        )
+        .operands_in(vec![x])
+        .operands_out(vec![x1])
+        .constraints(vec![Constraint::WiderOrEq(ToBV.clone(), BV.clone())]),
+    );
+
+    ig.push(
+        Inst::new(
+            "bvreduce",
+            r#"
+            Bitvector truncation, keeping the low bits.
+            "#,
+        )
+        .operands_in(vec![x])
+        .operands_out(vec![x1])
+        .constraints(vec![Constraint::WiderOrEq(BV.clone(), ToBV.clone())]),
+    );
+
+    ig.finish()
+}
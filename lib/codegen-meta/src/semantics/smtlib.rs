@@ -0,0 +1,404 @@
+//! Emit SMT-LIB bitvector queries checking the correctness of legalizations.
+//!
+//! The source and destination patterns of a concrete legalization are
+//! elaborated into primitive instructions, and each primitive instruction is
+//! encoded as an equality assertion. The query asserts that both patterns
+//! get the same inputs, but produce different outputs: if it is
+//! unsatisfiable, the legalization preserves semantics.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::path::Path;
+use std::process;
+
+use cdsl::ast::Literal;
+use cdsl::types::ValueType;
+use cdsl::xform::XForm;
+
+use base::types::Bool;
+use error;
+use semantics::elaborate::{
+    describe, elaborate, instances, interface, ConcreteArg, ConcreteRtl, Instance,
+};
+use semantics::Definitions;
+
+/// A verification query for one legalization, under one concrete typing.
+pub struct Query {
+    /// Unique name of the query, identifying the legalization and its typing.
+    pub name: String,
+    /// The text of the SMT-LIB query, or the reason why it couldn't be built.
+    pub smtlib: Result<String, String>,
+}
+
+impl Query {
+    pub fn file_name(&self) -> String {
+        format!("{}.smt2", self.name)
+    }
+}
+
+/// The result of running an SMT solver on a query.
+#[derive(Debug, PartialEq)]
+pub enum Verdict {
+    /// The query is unsatisfiable: the legalization preserves semantics.
+    Verified,
+    /// The query is satisfiable: some inputs give different results.
+    CounterExample,
+    /// The solver didn't give an answer, with its output.
+    Unknown(String),
+}
+
+/// The SMT-LIB encoding of a pattern of primitive instructions.
+struct Encoding {
+    declarations: Vec<String>,
+    assertions: Vec<String>,
+    /// The bitvector standing for each real Cranelift value.
+    bitvectors: HashMap<usize, String>,
+}
+
+/// Get the SMT-LIB sort of a variable of type `value_type`.
+fn sort(value_type: &ValueType) -> Result<String, String> {
+    match *value_type {
+        ValueType::BV(ref bv) => Ok(format!("(_ BitVec {})", bv.lane_bits())),
+        _ if *value_type == Bool::B1.into() => Ok("Bool".to_string()),
+        _ => Err(format!("no SMT-LIB sort for {}", value_type)),
+    }
+}
+
+/// Get the width of the bitvector variable `var` of `rtl`.
+fn width(rtl: &ConcreteRtl, var: usize) -> u64 {
+    rtl.vars[var].value_type.as_ref().unwrap().width()
+}
+
+/// Get the SMT-LIB bitvector constant for the literal `literal`.
+fn bv_constant(literal: &Literal, width: u64) -> Result<String, String> {
+    let bits = match *literal {
+        Literal::Int { value, .. } => value as u64,
+        Literal::Bits { bits, .. } => bits,
+        Literal::Enumerator { .. } => {
+            return Err(format!("no bitvector value for {}", literal));
+        }
+    };
+    let mask = if width >= 64 { !0 } else { (1u64 << width) - 1 };
+    Ok(format!("(_ bv{} {})", bits & mask, width))
+}
+
+/// Encode the primitive pattern `rtl` as SMT-LIB assertions, suffixing the
+/// names of its variables with `suffix`.
+fn encode(rtl: &ConcreteRtl, suffix: &str) -> Result<Encoding, String> {
+    let name = |var: usize| format!("{}{}", rtl.vars[var].name, suffix);
+
+    let mut bitvectors = HashMap::new();
+    let mut semantic_vars = Vec::new();
+    let mut immediates = Vec::new();
+    let mut assertions = Vec::new();
+
+    for def in &rtl.defs {
+        // The conversions between real values and bitvectors only rename
+        // values, no assertion is needed.
+        match def.inst.name {
+            "prim_to_bv" => {
+                bitvectors.insert(def.arg_var(0)?, name(def.defs[0]));
+                semantic_vars.push(def.defs[0]);
+                continue;
+            }
+            "prim_from_bv" => {
+                let arg = def.arg_var(0)?;
+                bitvectors.insert(def.defs[0], name(arg));
+                semantic_vars.push(arg);
+                continue;
+            }
+            _ => {}
+        }
+
+        semantic_vars.extend(def.defs.iter().cloned());
+        if def.inst.name != "bv_from_imm64" {
+            semantic_vars.extend(def.uses());
+        }
+
+        let result = name(def.defs[0]);
+        let arg = |opnum: usize| def.arg_var(opnum).map(&name);
+        let expr = match def.inst.name {
+            "bv_from_imm64" => match def.args[0] {
+                ConcreteArg::Literal(ref literal) => bv_constant(literal, width(rtl, def.defs[0]))?,
+                // Immediate operands which are variables are 64-bit inputs.
+                ConcreteArg::Var(imm) => {
+                    immediates.push(imm);
+                    bitvectors.insert(imm, name(imm));
+                    match width(rtl, def.defs[0]) {
+                        64 => name(imm),
+                        bits => format!("((_ extract {} 0) {})", bits - 1, name(imm)),
+                    }
+                }
+            },
+            "bvite" => format!("(ite {} {} {})", arg(0)?, arg(1)?, arg(2)?),
+            "bvsplit" => {
+                let bits = width(rtl, def.arg_var(0)?);
+                assertions.push(format!(
+                    "(= {} ((_ extract {} 0) {}))",
+                    result,
+                    bits / 2 - 1,
+                    arg(0)?
+                ));
+                assertions.push(format!(
+                    "(= {} ((_ extract {} {}) {}))",
+                    name(def.defs[1]),
+                    bits - 1,
+                    bits / 2,
+                    arg(0)?
+                ));
+                continue;
+            }
+            "bvconcat" => format!("(concat {} {})", arg(1)?, arg(0)?),
+            "bvadd" | "bvsub" | "bvmul" | "bvand" | "bvor" | "bvxor" | "bvsge" | "bvsgt"
+            | "bvsle" | "bvslt" | "bvuge" | "bvugt" | "bvule" | "bvult" => {
+                format!("({} {} {})", def.inst.name, arg(0)?, arg(1)?)
+            }
+            "bvnot" => format!("(bvnot {})", arg(0)?),
+            "bveq" => format!("(= {} {})", arg(0)?, arg(1)?),
+            "bvne" => format!("(distinct {} {})", arg(0)?, arg(1)?),
+            "bvzeroext" | "bvsignext" => {
+                let extend = if def.inst.name == "bvzeroext" {
+                    "zero_extend"
+                } else {
+                    "sign_extend"
+                };
+                let from = width(rtl, def.arg_var(0)?);
+                let to = width(rtl, def.defs[0]);
+                format!("((_ {} {}) {})", extend, to - from, arg(0)?)
+            }
+            "bvreduce" => {
+                let to = width(rtl, def.defs[0]);
+                format!("((_ extract {} 0) {})", to - 1, arg(0)?)
+            }
+            other => return Err(format!("unknown primitive instruction {}", other)),
+        };
+
+        assertions.push(format!("(= {} {})", result, expr));
+    }
+
+    let mut declared = HashSet::new();
+    let mut declarations = Vec::new();
+    for var in semantic_vars {
+        if !declared.insert(var) {
+            continue;
+        }
+        if bitvectors.contains_key(&var) {
+            return Err(format!(
+                "{} is used both as a real value and as a bitvector",
+                rtl.vars[var].name
+            ));
+        }
+        let value_type = rtl.vars[var]
+            .value_type
+            .as_ref()
+            .ok_or_else(|| format!("{} has no type", rtl.vars[var].name))?;
+        declarations.push(format!(
+            "(declare-fun {} () {})",
+            name(var),
+            sort(value_type)?
+        ));
+    }
+
+    for var in immediates {
+        if declared.insert(var) {
+            declarations.push(format!("(declare-fun {} () (_ BitVec 64))", name(var)));
+        }
+    }
+
+    Ok(Encoding {
+        declarations,
+        assertions,
+        bitvectors,
+    })
+}
+
+/// Build a query checking that the concrete source pattern `src` is
+/// equivalent to the concrete destination pattern `dst`.
+///
+/// The real values named `inputs` are the same in both patterns, and the
+/// query is satisfiable iff some real value named in `outputs` differs.
+fn equivalent(
+    src: &ConcreteRtl,
+    dst: &ConcreteRtl,
+    inputs: &[&str],
+    outputs: &[&str],
+    out: &mut String,
+) -> Result<(), String> {
+    let src_enc = encode(src, ".a")?;
+    let dst_enc = encode(dst, ".b")?;
+
+    let bitvector = |rtl: &ConcreteRtl, enc: &Encoding, name: &str| {
+        rtl.lookup(name)
+            .and_then(|var| enc.bitvectors.get(&var))
+            .cloned()
+    };
+
+    writeln!(out, "(set-logic QF_BV)").unwrap();
+    for line in src_enc.declarations.iter().chain(&dst_enc.declarations) {
+        writeln!(out, "{}", line).unwrap();
+    }
+    for assertion in src_enc.assertions.iter().chain(&dst_enc.assertions) {
+        writeln!(out, "(assert {})", assertion).unwrap();
+    }
+
+    // Inputs that are unused in one of the patterns don't need to be
+    // constrained.
+    for &input in inputs {
+        if let (Some(a), Some(b)) = (
+            bitvector(src, &src_enc, input),
+            bitvector(dst, &dst_enc, input),
+        ) {
+            writeln!(out, "(assert (= {} {}))", a, b).unwrap();
+        }
+    }
+
+    let mut results_eq = Vec::new();
+    for &output in outputs {
+        match (
+            bitvector(src, &src_enc, output),
+            bitvector(dst, &dst_enc, output),
+        ) {
+            (Some(a), Some(b)) => results_eq.push(format!("(= {} {})", a, b)),
+            _ => return Err(format!("output {} isn't computed by both patterns", output)),
+        }
+    }
+    match results_eq.len() {
+        0 => return Err("the legalization has no outputs".into()),
+        1 => writeln!(out, "(assert (not {}))", results_eq[0]).unwrap(),
+        _ => writeln!(out, "(assert (not (and {})))", results_eq.join(" ")).unwrap(),
+    }
+
+    writeln!(out, "(check-sat)").unwrap();
+    Ok(())
+}
+
+/// Build the query checking that `xform` is semantics preserving for its
+/// concrete `instance`.
+fn xform_query(defs: &Definitions, xform: &XForm, instance: &Instance) -> Result<String, String> {
+    let (src, dst) = instance.patterns(xform);
+
+    let mut out = String::new();
+    writeln!(out, "; Source pattern:").unwrap();
+    for def in &src.defs {
+        writeln!(out, ";   {}", describe(def, &src)).unwrap();
+    }
+    writeln!(out, "; Destination pattern:").unwrap();
+    for def in &dst.defs {
+        writeln!(out, ";   {}", describe(def, &dst)).unwrap();
+    }
+    writeln!(
+        out,
+        ";\n; The legalization preserves semantics iff this query is unsatisfiable."
+    )
+    .unwrap();
+
+    let (inputs, outputs) = interface(xform, &src);
+    let prim_src = elaborate(src, defs)?;
+    let prim_dst = elaborate(dst, defs)?;
+    equivalent(&prim_src, &prim_dst, &inputs, &outputs, &mut out)?;
+    Ok(out)
+}
+
+/// Build the queries checking that the legalization `xform` is semantics
+/// preserving, for all of its concrete instances.
+///
+/// The names of the queries start with `prefix`.
+pub fn xform_queries(defs: &Definitions, xform: &XForm, prefix: &str) -> Vec<Query> {
+    instances(xform, prefix)
+        .into_iter()
+        .map(|instance| Query {
+            smtlib: xform_query(defs, xform, &instance),
+            name: instance.name,
+        })
+        .collect()
+}
+
+/// Run the SMT solver `solver` on the query in the file `query`.
+///
+/// The solver must accept SMT-LIB files as its only argument, like `z3` or
+/// `cvc4`.
+pub fn run_solver(solver: &str, query: &Path) -> Result<Verdict, error::Error> {
+    let output = process::Command::new(solver).arg(query).output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(match stdout.lines().next().map(|line| line.trim()) {
+        Some("unsat") => Verdict::Verified,
+        Some("sat") => Verdict::CounterExample,
+        _ => Verdict::Unknown(format!(
+            "{}{}",
+            stdout,
+            String::from_utf8_lossy(&output.stderr)
+        )),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use semantics::{define, legalization_queries};
+    use std::env;
+    use std::fs;
+
+    fn narrow_queries(defs: &Definitions, inst_name: &str) -> Vec<Query> {
+        let groups = &defs.shared.transform_groups;
+        groups
+            .get(groups.by_name("narrow"))
+            .xforms
+            .iter()
+            .filter(|xform| xform.src.rtl[0].expr.inst.name == inst_name)
+            .flat_map(|xform| xform_queries(defs, xform, "narrow"))
+            .collect()
+    }
+
+    #[test]
+    fn narrow_iadd_query() {
+        let query = narrow_queries(&define(), "iadd")
+            .into_iter()
+            .find(|query| query.name == "narrow-iadd-x.i64")
+            .unwrap();
+        let smtlib = query.smtlib.unwrap();
+        assert!(smtlib.starts_with("; Source pattern:\n;   (a: i64) << iadd(x: i64, y: i64)\n"));
+        assert!(smtlib.contains("(declare-fun bvx.0.a () (_ BitVec 64))"));
+        assert!(smtlib.contains("(assert (= bva.0.a (bvadd bvx.0.a bvy.0.a)))"));
+        assert!(smtlib.contains("((_ extract 63 32) bvx.0.b)"));
+        assert!(smtlib.ends_with("(check-sat)\n"));
+    }
+
+    // This runs an SMT solver on every legalization query, so it is only run on demand with
+    // `cargo test -- --ignored`. The solver is `z3` unless `CRANELIFT_SMT_SOLVER` names another
+    // one, and the test fails if it can't be run. The queries for instructions without known
+    // semantics can't be built, so they aren't checked.
+    #[test]
+    #[ignore]
+    fn verify_legalizations_with_solver() {
+        let solver = env::var("CRANELIFT_SMT_SOLVER").unwrap_or_else(|_| "z3".to_string());
+        let out_dir = env::temp_dir().join("cranelift-meta-smtlib-test");
+        fs::create_dir_all(&out_dir).unwrap();
+
+        let mut checked = 0;
+        let mut failures = Vec::new();
+        for query in legalization_queries(&define()) {
+            let smtlib = match query.smtlib {
+                Ok(ref smtlib) => smtlib,
+                Err(_) => continue,
+            };
+            let path = out_dir.join(query.file_name());
+            fs::write(&path, smtlib).unwrap();
+            let verdict = run_solver(&solver, &path)
+                .unwrap_or_else(|err| panic!("can't run the SMT solver {}: {}", solver, err));
+            if verdict != Verdict::Verified {
+                failures.push(format!("{}: {:?}", query.name, verdict));
+            }
+            checked += 1;
+        }
+        fs::remove_dir_all(&out_dir).unwrap();
+
+        assert!(checked > 0);
+        assert!(
+            failures.is_empty(),
+            "{} of {} legalization queries failed:\n{}",
+            failures.len(),
+            checked,
+            failures.join("\n")
+        );
+    }
+}
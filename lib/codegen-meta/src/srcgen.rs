@@ -3,33 +3,15 @@
 //! The `srcgen` module contains generic helper routines and classes for
 //! generating source code.
 
-use std::collections::{BTreeMap, HashSet};
 use std::fs;
-use std::io::{self, Write};
+use std::io;
+use std::ops::{Deref, DerefMut};
 use std::path;
 
 use error;
 
 static SHIFTWIDTH: usize = 4;
 
-struct _IndentedScope {
-    fmt: Formatter,
-    after: Option<String>,
-}
-
-impl _IndentedScope {
-    fn _enter(&mut self) {
-        self.fmt._indent_push();
-    }
-
-    fn _exit(&mut self) {
-        self.fmt._indent_pop();
-        if let Some(ref s) = self.after {
-            self.fmt.line(&s);
-        }
-    }
-}
-
 pub struct Formatter {
     indent: usize,
     lines: Vec<String>,
@@ -46,16 +28,48 @@ impl Formatter {
     }
 
     /// Increase current indentation level by one.
-    pub fn _indent_push(&mut self) {
+    pub fn indent_push(&mut self) {
         self.indent += 1;
     }
 
     /// Decrease indentation by one level.
-    pub fn _indent_pop(&mut self) {
+    pub fn indent_pop(&mut self) {
         assert!(self.indent > 0, "Already at top level indentation");
         self.indent -= 1;
     }
 
+    /// Run `f` with the indentation level increased by one, and return its
+    /// result.
+    pub fn indent<T, F: FnOnce(&mut Formatter) -> T>(&mut self, f: F) -> T {
+        self.indent_push();
+        let ret = f(self);
+        self.indent_pop();
+        ret
+    }
+
+    /// Return a scope object whose lines are indented one level more than the
+    /// current ones. The indentation is restored when the scope is dropped:
+    ///
+    /// ```ignore
+    /// {
+    ///     let mut fmt = fmt.indented("prefix {", "} suffix");
+    ///     fmt.line("hello");
+    /// }
+    /// ```
+    ///
+    /// The `before` and `after` lines surround the scope and are *not*
+    /// indented. They are omitted when empty.
+    pub fn indented(&mut self, before: &str, after: &str) -> IndentedScope<'_> {
+        if !before.is_empty() {
+            self.line(before);
+        }
+        self.indent_push();
+        IndentedScope {
+            fmt: self,
+            after: after.to_string(),
+        }
+    }
+
     /// Get the current whitespace indentation in the form of a String.
     fn get_indent(&self) -> String {
         if self.indent == 0 {
@@ -67,56 +81,47 @@ impl Formatter {
 
     /// Get a string containing whitespace outdented one level. Used for
     /// lines of code that are inside a single indented block.
-    fn _get_outdent(&mut self) -> String {
-        self._indent_push();
+    fn get_outdent(&mut self) -> String {
+        self.indent_pop();
         let s = self.get_indent();
-        self._indent_pop();
+        self.indent_push();
         s
     }
 
-    /// Add an indented line.
+    /// Add an indented line. Empty lines are emitted without indentation.
     pub fn line(&mut self, contents: &str) {
-        let indented_line = format!("{}{}\n", self.get_indent(), contents);
-        self.lines.push(indented_line);
+        if contents.is_empty() {
+            self.lines.push("\n".to_string());
+        } else {
+            let indented_line = format!("{}{}\n", self.get_indent(), contents);
+            self.lines.push(indented_line);
+        }
     }
 
     /// Emit a line outdented one level.
-    pub fn _outdented_line(&mut self, s: &str) {
-        let new_line = format!("{}{}", self._get_outdent(), s);
+    pub fn outdented_line(&mut self, s: &str) {
+        let new_line = format!("{}{}\n", self.get_outdent(), s);
         self.lines.push(new_line);
     }
 
     /// Write `self.lines` to a file.
+    ///
+    /// The file is left untouched if it already has the same contents, so
+    /// that its modification time doesn't change and the crates including it
+    /// aren't needlessly recompiled.
     pub fn update_file(&self, filename: &str, directory: &str) -> Result<(), error::Error> {
-        #[cfg(target_family = "windows")]
-        let path_str = format!("{}\\{}", directory, filename);
-        #[cfg(not(target_family = "windows"))]
-        let path_str = format!("{}/{}", directory, filename);
-
-        let path = path::Path::new(&path_str);
-        let mut f = fs::File::create(path)?;
-
-        for l in self.lines.iter().map(|l| l.as_bytes()) {
-            f.write_all(l)?;
-        }
-
+        let path = path::Path::new(directory).join(filename);
+        write_if_changed(&path, &self.lines.concat())?;
         Ok(())
     }
 
-    /// Return a scope object for use with a `with` statement.
-    /// The optional `before` and `after` parameters are surrounding lines
-    /// which are *not* indented.
-    fn _indented(&self, _before: Option<&str>, _after: Option<&str>) -> _IndentedScope {
-        unimplemented!();
-    }
-
     /// Add one or more lines after stripping common indentation.
-    pub fn _multi_line(&mut self, s: &str) {
+    pub fn multi_line(&mut self, s: &str) {
         parse_multiline(s).into_iter().for_each(|l| self.line(&l));
     }
 
     /// Add a comment line.
-    pub fn _comment(&mut self, s: &str) {
+    pub fn comment(&mut self, s: &str) {
         let commented_line = format!("// {}", s);
         self.line(&commented_line);
     }
@@ -125,77 +130,119 @@ impl Formatter {
     pub fn doc_comment(&mut self, contents: &str) {
         parse_multiline(contents)
             .iter()
-            .map(|l| format!("/// {}", l))
+            .map(|l| {
+                if l.is_empty() {
+                    "///".to_string()
+                } else {
+                    format!("/// {}", l)
+                }
+            })
             .for_each(|s| self.line(s.as_str()));
     }
 
     /// Add a match expression.
-    fn _add_match(&mut self, _m: &_Match) {
-        unimplemented!();
+    pub fn add_match(&mut self, m: Match) {
+        let mut fmt = self.indented(&format!("match {} {{", m.expr), "}");
+        for arm in &m.arms {
+            // name { fields } | name { fields } => { body }
+            let fields_str = if arm.fields.is_empty() {
+                String::new()
+            } else {
+                format!("{{ {} }} ", arm.fields.join(", "))
+            };
+            let mut fmt = fmt.indented("", "}");
+            for (i, name) in arm.names.iter().enumerate() {
+                let suffix = if i + 1 < arm.names.len() { "|" } else { "=> {" };
+                fmt.outdented_line(&format!("{} {}{}", name, fields_str, suffix));
+            }
+            fmt.multi_line(&arm.body);
+        }
     }
 }
 
-/// Compute the indentation of s, or None of an empty line.
-fn _indent(s: &str) -> Option<usize> {
-    if s.is_empty() {
-        None
-    } else {
-        let t = s.trim_left();
-        Some(s.len() - t.len())
+/// An indented block of lines, returned by `Formatter::indented`.
+///
+/// The scope dereferences to its formatter. When it is dropped, the
+/// indentation level is decreased again and the `after` line is emitted.
+pub struct IndentedScope<'a> {
+    fmt: &'a mut Formatter,
+    after: String,
+}
+
+impl<'a> Deref for IndentedScope<'a> {
+    type Target = Formatter;
+
+    fn deref(&self) -> &Formatter {
+        self.fmt
     }
 }
 
+impl<'a> DerefMut for IndentedScope<'a> {
+    fn deref_mut(&mut self) -> &mut Formatter {
+        self.fmt
+    }
+}
+
+impl<'a> Drop for IndentedScope<'a> {
+    fn drop(&mut self) {
+        self.fmt.indent_pop();
+        if !self.after.is_empty() {
+            let after = self.after.clone();
+            self.fmt.line(&after);
+        }
+    }
+}
+
+/// Write `contents` to the file at `path`, unless it already has these contents.
+///
+/// Returns whether the file was written.
+fn write_if_changed(path: &path::Path, contents: &str) -> io::Result<bool> {
+    if let Ok(existing) = fs::read_to_string(path) {
+        if existing == contents {
+            return Ok(false);
+        }
+    }
+    fs::write(path, contents)?;
+    Ok(true)
+}
+
 /// Given a multi-line string, split it into a sequence of lines after
 /// stripping a common indentation. This is useful for strings defined with
 /// doc strings.
-fn parse_multiline(s: &str) -> Vec<String> {
+pub fn parse_multiline(s: &str) -> Vec<String> {
     // Convert tabs into spaces.
     let expanded_tab = format!("{:-1$}", " ", SHIFTWIDTH);
     let lines: Vec<String> = s.lines().map(|l| l.replace("\t", &expanded_tab)).collect();
 
-    // Determine minimum indentation, ignoring the first line.
+    // Determine minimum indentation, ignoring the first line and blank lines.
     let indent = lines
         .iter()
         .skip(1)
+        .filter(|l| !l.trim().is_empty())
         .map(|l| l.len() - l.trim_left().len())
-        .filter(|&i| i > 0)
         .min();
 
-    // Strip off leading blank lines.
-    let mut lines_iter = lines.iter().skip_while(|l| l.is_empty());
+    // Remove indentation (first line is special).
     let mut trimmed = Vec::with_capacity(lines.len());
-
-    // Remove indentation (first line is special)
-    if let Some(s) = lines_iter.next().map(|l| l.trim()).map(|l| l.to_string()) {
-        trimmed.push(s);
+    if let Some(first) = lines.first() {
+        trimmed.push(first.trim().to_string());
     }
 
     // Remove trailing whitespace from other lines.
-    let mut other_lines = if let Some(indent) = indent {
-        lines_iter
-            .map(|l| &l[indent..])
-            .map(|l| l.trim_right())
-            .map(|l| l.to_string())
-            .collect::<Vec<_>>()
-    } else {
-        lines_iter
-            .map(|l| l.trim_right())
-            .map(|l| l.to_string())
-            .collect::<Vec<_>>()
-    };
-
-    trimmed.append(&mut other_lines);
-
-    // Strip off trailing blank lines.
-    while let Some(s) = trimmed.pop() {
-        if s.is_empty() {
-            continue;
-        } else {
-            trimmed.push(s);
-            break;
+    if let Some(indent) = indent {
+        for l in lines.iter().skip(1) {
+            let l = if l.len() > indent { &l[indent..] } else { "" };
+            trimmed.push(l.trim_right().to_string());
         }
     }
 
+    // Strip off trailing and leading blank lines.
+    while let Some(true) = trimmed.last().map(|l| l.is_empty()) {
+        trimmed.pop();
+    }
+    let leading_blanks = trimmed.iter().take_while(|l| l.is_empty()).count();
+    trimmed.drain(..leading_blanks);
+
     trimmed
 }
 
@@ -206,44 +253,97 @@ fn parse_multiline(s: &str) -> Vec<String> {
 ///
 /// Note that this class is ignorant of Rust types, and considers two fields
 /// with the same name to be equivalent.
-struct _Match<'a> {
-    _expr: &'a str,
-    arms: BTreeMap<(Vec<&'a str>, &'a str), HashSet<&'a str>>,
+pub struct Match {
+    expr: String,
+    arms: Vec<MatchArm>,
 }
 
-impl<'a> _Match<'a> {
-    /// Create a new match statemeht on `expr`.
-    fn _new(expr: &'a str) -> Self {
+/// The names sharing a single arm of a `Match`.
+struct MatchArm {
+    fields: Vec<String>,
+    body: String,
+    names: Vec<String>,
+}
+
+impl Match {
+    /// Create a new match statement on `expr`.
+    pub fn new<T: Into<String>>(expr: T) -> Self {
         Self {
-            _expr: expr,
-            arms: BTreeMap::new(),
+            expr: expr.into(),
+            arms: Vec::new(),
         }
     }
 
-    /// Add an arm to the Match statement.
-    fn _arm(&mut self, name: &'a str, fields: Vec<&'a str>, body: &'a str) {
-        // let key = (fields, body);
-        let match_arm = self.arms.entry((fields, body)).or_insert_with(HashSet::new);
-        match_arm.insert(name);
+    /// Add an arm to the Match statement. Arms with the same fields and body
+    /// are merged, in the order they were first added.
+    pub fn arm<T: Into<String>>(&mut self, name: T, fields: Vec<T>, body: T) {
+        let fields: Vec<String> = fields.into_iter().map(|f| f.into()).collect();
+        let body = body.into();
+        let name = name.into();
+        if let Some(arm) = self
+            .arms
+            .iter_mut()
+            .find(|arm| arm.fields == fields && arm.body == body)
+        {
+            if !arm.names.contains(&name) {
+                arm.names.push(name);
+            }
+            return;
+        }
+        self.arms.push(MatchArm {
+            fields,
+            body,
+            names: vec![name],
+        });
     }
 }
 
 #[cfg(test)]
 mod srcgen_tests {
-    use super::_Match;
     use super::parse_multiline;
+    use super::write_if_changed;
     use super::Formatter;
+    use super::Match;
+    use std::env;
+    use std::fs;
+    use std::process;
 
     #[test]
     fn adding_arms_works() {
-        let mut m = _Match::_new("x");
-        m._arm("Orange", vec!["a", "b"], "some body");
-        m._arm("Yellow", vec!["a", "b"], "some body");
-        m._arm("Green", vec!["a", "b"], "different body");
-        m._arm("Blue", vec!["x", "y"], "some body");
+        let mut m = Match::new("x");
+        m.arm("Orange", vec!["a", "b"], "some body");
+        m.arm("Yellow", vec!["a", "b"], "some body");
+        m.arm("Green", vec!["a", "b"], "different body");
+        m.arm("Blue", vec!["x", "y"], "some body");
         assert_eq!(m.arms.len(), 3);
     }
 
+    #[test]
+    fn match_emits_merged_arms() {
+        let mut m = Match::new("x");
+        m.arm("Orange", vec!["a", "b"], "some body");
+        m.arm("Yellow", vec!["a", "b"], "some body");
+        m.arm("Green", vec!["a", "b"], "different body");
+        m.arm("Blue", vec!["x", "y"], "some body");
+        let mut fmt = Formatter::new();
+        fmt.add_match(m);
+        let expected_lines = vec![
+            "match x {\n",
+            "    Orange { a, b } |\n",
+            "    Yellow { a, b } => {\n",
+            "        some body\n",
+            "    }\n",
+            "    Green { a, b } => {\n",
+            "        different body\n",
+            "    }\n",
+            "    Blue { x, y } => {\n",
+            "        some body\n",
+            "    }\n",
+            "}\n",
+        ];
+        assert_eq!(fmt.lines, expected_lines);
+    }
+
     #[test]
     fn parse_multiline_works() {
         let input = "\n    hello\n    world\n";
@@ -252,13 +352,21 @@ mod srcgen_tests {
         assert_eq!(output, expected);
     }
 
+    #[test]
+    fn parse_multiline_keeps_relative_indentation() {
+        let input = "first\n    one\n\n      two\n    ";
+        let expected = vec!["first", "one", "", "  two"];
+        let output = parse_multiline(input);
+        assert_eq!(output, expected);
+    }
+
     #[test]
     fn formatter_basic_example_works() {
         let mut fmt = Formatter::new();
         fmt.line("Hello line 1");
-        fmt._indent_push();
-        fmt._comment("Nested comment");
-        fmt._indent_pop();
+        fmt.indent_push();
+        fmt.comment("Nested comment");
+        fmt.indent_pop();
         fmt.line("Back home again");
         let expected_lines = vec![
             "Hello line 1\n",
@@ -276,9 +384,9 @@ mod srcgen_tests {
         let actual_results = Vec::with_capacity(4);
         (0..3).for_each(|_| {
             fmt.get_indent();
-            fmt._indent_push();
+            fmt.indent_push();
         });
-        (0..3).for_each(|_| fmt._indent_pop());
+        (0..3).for_each(|_| fmt.indent_pop());
         fmt.get_indent();
 
         actual_results
@@ -299,12 +407,62 @@ mod srcgen_tests {
     fn fmt_can_add_indented_line() {
         let mut fmt = Formatter::new();
         fmt.line("hello");
-        fmt._indent_push();
+        fmt.indent_push();
         fmt.line("world");
         let expected_lines = vec!["hello\n", "    world\n"];
         assert_eq!(fmt.lines, expected_lines);
     }
 
+    #[test]
+    fn fmt_can_indent_with_closure() {
+        let mut fmt = Formatter::new();
+        fmt.line("prefix {");
+        fmt.indent(|fmt| fmt.line("hello"));
+        fmt.line("} suffix");
+        let expected_lines = vec!["prefix {\n", "    hello\n", "} suffix\n"];
+        assert_eq!(fmt.lines, expected_lines);
+    }
+
+    #[test]
+    fn fmt_can_use_indented_scopes() {
+        let mut fmt = Formatter::new();
+        {
+            let mut fmt = fmt.indented("prefix {", "} suffix");
+            fmt.line("hello");
+            let mut fmt = fmt.indented("", "}");
+            fmt.line("world");
+        }
+        fmt.line("done");
+        let expected_lines = vec![
+            "prefix {\n",
+            "    hello\n",
+            "        world\n",
+            "    }\n",
+            "} suffix\n",
+            "done\n",
+        ];
+        assert_eq!(fmt.lines, expected_lines);
+    }
+
+    #[test]
+    fn update_file_skips_unchanged_contents() {
+        let dir = env::temp_dir().join(format!("cranelift-srcgen-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("file.rs");
+
+        assert_eq!(write_if_changed(&path, "hello\n").unwrap(), true);
+        assert_eq!(write_if_changed(&path, "hello\n").unwrap(), false);
+        assert_eq!(write_if_changed(&path, "hello\nworld\n").unwrap(), true);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "hello\nworld\n");
+
+        let mut fmt = Formatter::new();
+        fmt.line("hello");
+        fmt.update_file("file.rs", dir.to_str().unwrap()).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "hello\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[test]
     fn fmt_can_add_doc_comments() {
         let mut fmt = Formatter::new();
@@ -312,4 +470,12 @@ mod srcgen_tests {
         let expected_lines = vec!["/// documentation\n", "/// is\n", "/// good\n"];
         assert_eq!(fmt.lines, expected_lines);
     }
+
+    #[test]
+    fn fmt_doc_comments_have_no_trailing_whitespace() {
+        let mut fmt = Formatter::new();
+        fmt.doc_comment("first\n\n    second");
+        let expected_lines = vec!["/// first\n", "///\n", "/// second\n"];
+        assert_eq!(fmt.lines, expected_lines);
+    }
 }
@@ -0,0 +1,122 @@
+//! Generate a table of unique items.
+//!
+//! The `UniqueTable` collects items into an array, removing duplicates. Each
+//! item is mapped to its offset in the final array. The `UniqueSeqTable` does
+//! the same for sequences of items.
+//!
+//! This is a compression technique for compile-time generated tables.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::slice;
+
+/// Collect items into the `table` list, removing duplicates.
+pub struct UniqueTable<T: PartialEq + Clone + Eq + Hash> {
+    table: Vec<T>,
+    index: HashMap<T, usize>,
+}
+
+impl<T: PartialEq + Clone + Eq + Hash> UniqueTable<T> {
+    pub fn new() -> Self {
+        Self {
+            table: Vec::new(),
+            index: HashMap::new(),
+        }
+    }
+
+    /// Add a single item to the table if it isn't already there.
+    ///
+    /// Return the offset into the table of the item.
+    pub fn add(&mut self, item: &T) -> usize {
+        if let Some(&offset) = self.index.get(item) {
+            return offset;
+        }
+        let offset = self.table.len();
+        self.index.insert(item.clone(), offset);
+        self.table.push(item.clone());
+        offset
+    }
+
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, T> {
+        self.table.iter()
+    }
+}
+
+/// Collect sequences into the `table` list, removing duplicates.
+///
+/// Sequences don't have to be of the same length.
+pub struct UniqueSeqTable<T: PartialEq + Clone + Eq + Hash> {
+    table: Vec<T>,
+    index: HashMap<Vec<T>, usize>,
+}
+
+impl<T: PartialEq + Clone + Eq + Hash> UniqueSeqTable<T> {
+    pub fn new() -> Self {
+        Self {
+            table: Vec::new(),
+            index: HashMap::new(),
+        }
+    }
+
+    /// Add a sequence of items to the table. If the table already contains the
+    /// items in `values` in the same order, use those instead.
+    ///
+    /// Return the offset into the table of the beginning of `values`.
+    pub fn add(&mut self, values: &[T]) -> usize {
+        if values.is_empty() {
+            return 0;
+        }
+        if let Some(&offset) = self.index.get(values) {
+            return offset;
+        }
+
+        let offset = self.table.len();
+        self.table.extend_from_slice(values);
+
+        // Add `values` and all sub-sequences to `index`.
+        for length in 1..=values.len() {
+            for start in 0..=(values.len() - length) {
+                self.index
+                    .insert(values[start..start + length].to_vec(), offset + start);
+            }
+        }
+
+        offset
+    }
+
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, T> {
+        self.table.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{UniqueSeqTable, UniqueTable};
+
+    #[test]
+    fn unique_items() {
+        let mut table = UniqueTable::new();
+        assert_eq!(table.add(&"a"), 0);
+        assert_eq!(table.add(&"b"), 1);
+        assert_eq!(table.add(&"a"), 0);
+        assert_eq!(table.len(), 2);
+    }
+
+    #[test]
+    fn reuses_sub_sequences() {
+        let mut table = UniqueSeqTable::new();
+        assert_eq!(table.add(&["a", "b", "c"]), 0);
+        assert_eq!(table.add(&["b", "c"]), 1);
+        assert_eq!(table.add(&["c", "d"]), 3);
+        assert_eq!(table.add(&[]), 0);
+        assert_eq!(table.len(), 5);
+    }
+}
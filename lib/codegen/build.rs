@@ -15,12 +15,22 @@
 //     A setting for conditional compilation of isa targets. Possible values can be "native" or
 //     known isa targets separated by ','.
 //
+// CARGO_FEATURE_RUST_META (Optional)
+//     Set by Cargo when the `rust-meta` feature is enabled. All the generated files then come
+//     from the cranelift-codegen-meta crate, and Python isn't needed.
+//
+// The instruction set extensions registered by the cranelift-codegen-extensions crate are added
+// to the base instructions. They are only generated by the cranelift-codegen-meta crate, so they
+// require the `rust-meta` feature.
+//
 // The build script expects to be run from the directory where this build.rs file lives. The
 // current directory is used to find the sources.
 
+extern crate cranelift_codegen_extensions as extensions;
 extern crate cranelift_codegen_meta as meta;
 
 use std::env;
+use std::path::Path;
 use std::process;
 
 fn main() {
@@ -28,7 +38,7 @@ fn main() {
     let target_triple = env::var("TARGET").expect("The TARGET environment variable must be set");
     let cranelift_targets = env::var("CRANELIFT_TARGETS").ok();
     let cranelift_targets = cranelift_targets.as_ref().map(|s| s.as_ref());
-    let python = identify_python();
+    let rust_meta = env::var("CARGO_FEATURE_RUST_META").is_ok();
 
     // Configure isa targets cfg.
     match isa_targets(cranelift_targets, &target_triple) {
@@ -56,30 +66,19 @@ fn main() {
         crate_dir.join("build.rs").to_str().unwrap()
     );
 
-    // Scripts are in `$crate_dir/meta`.
-    let meta_dir = crate_dir.join("meta");
-    let build_script = meta_dir.join("build.py");
-
-    // Launch build script with Python. We'll just find python in the path.
-    // Use -B to disable .pyc files, because they cause trouble for vendoring
-    // scripts, and this is a build step that isn't run very often anyway.
-    let status = process::Command::new(python)
-        .current_dir(crate_dir)
-        .arg("-B")
-        .arg(build_script)
-        .arg("--out-dir")
-        .arg(out_dir.clone())
-        .status()
-        .expect("Failed to launch second-level build script; is python installed?");
-    if !status.success() {
-        process::exit(status.code().unwrap());
-    }
-
-    // DEVELOPMENT:
-    // ------------------------------------------------------------------------
-    // Now that the Python build process is complete, generate files that are
-    // emitted by the `cretonne_codegen_meta` crate.
-    // ------------------------------------------------------------------------
+    // Without the `rust-meta` feature, the files included by the crate are generated by the
+    // Python meta language, and the `cranelift_codegen_meta` crate emits its own `new_`
+    // prefixed files alongside them.
+    let extensions = extensions::extensions();
+    let prefix = if rust_meta {
+        ""
+    } else if !extensions.is_empty() {
+        eprintln!("Error: Instruction set extensions require the `rust-meta` feature.");
+        process::exit(1);
+    } else {
+        run_python_build(crate_dir, &out_dir);
+        "new_"
+    };
 
     // Identify the directory of the Rust codegen-meta external crate.
     let rust_meta_dir = crate_dir
@@ -90,7 +89,8 @@ fn main() {
             process::exit(1);
         });
 
-    if let Err(err) = meta::gen_types::generate("new_types.rs", &out_dir) {
+    let extensions = extensions.iter().map(|ext| &**ext).collect::<Vec<_>>();
+    if let Err(err) = meta::generate_with_extensions(prefix, &out_dir, &extensions) {
         eprintln!("Error: {}", err);
         process::exit(1);
     } else if let Err(err) = meta::gen_build_deps::generate(&rust_meta_dir) {
@@ -99,6 +99,28 @@ fn main() {
     }
 }
 
+/// Generate the files described by the Python meta language in `$crate_dir/meta`.
+fn run_python_build(crate_dir: &Path, out_dir: &str) {
+    let python = identify_python();
+    let meta_dir = crate_dir.join("meta");
+    let build_script = meta_dir.join("build.py");
+
+    // Launch build script with Python. We'll just find python in the path.
+    // Use -B to disable .pyc files, because they cause trouble for vendoring
+    // scripts, and this is a build step that isn't run very often anyway.
+    let status = process::Command::new(python)
+        .current_dir(crate_dir)
+        .arg("-B")
+        .arg(build_script)
+        .arg("--out-dir")
+        .arg(out_dir)
+        .status()
+        .expect("Failed to launch second-level build script; is python installed?");
+    if !status.success() {
+        process::exit(status.code().unwrap());
+    }
+}
+
 fn identify_python() -> &'static str {
     for python in &["python", "python3", "python2.7"] {
         if process::Command::new(python)
@@ -60,6 +60,7 @@ Load = InstructionFormat(memflags, VALUE, offset32)
 LoadComplex = InstructionFormat(memflags, VARIABLE_ARGS, offset32)
 Store = InstructionFormat(memflags, VALUE, VALUE, offset32)
 StoreComplex = InstructionFormat(memflags, VALUE, VARIABLE_ARGS, offset32)
+AtomicCas = InstructionFormat(memflags, VALUE, VALUE, VALUE)
 
 StackLoad = InstructionFormat(stack_slot, offset32)
 StackStore = InstructionFormat(VALUE, stack_slot, offset32)
@@ -447,6 +447,112 @@ istore32_complex = Instruction(
         """,
         ins=(MemFlags, x, args, Offset), can_store=True)
 
+#
+# Atomic memory operations.
+#
+
+x = Operand('x', iB, doc='Value to be stored')
+a = Operand('a', iB, doc='Value loaded')
+e = Operand('e', iB, doc='Expected value')
+
+atomic_load = Instruction(
+        'atomic_load', r"""
+        Atomically load from memory at ``p + Offset``.
+
+        The memory ordering is given by the ``MemFlags`` immediate and defaults
+        to sequentially consistent.
+        """,
+        ins=(MemFlags, p, Offset), outs=a, can_load=True,
+        other_side_effects=True)
+
+atomic_store = Instruction(
+        'atomic_store', r"""
+        Atomically store ``x`` to memory at ``p + Offset``.
+
+        The memory ordering is given by the ``MemFlags`` immediate and defaults
+        to sequentially consistent.
+        """,
+        ins=(MemFlags, x, p, Offset), can_store=True,
+        other_side_effects=True)
+
+a = Operand('a', iB, doc='Value previously in memory')
+
+atomic_add = Instruction(
+        'atomic_add', r"""
+        Atomically add ``x`` to the value in memory at ``p + Offset``.
+
+        Returns the value that was in memory before the addition.
+        """,
+        ins=(MemFlags, x, p, Offset), outs=a, can_load=True, can_store=True,
+        other_side_effects=True)
+
+atomic_sub = Instruction(
+        'atomic_sub', r"""
+        Atomically subtract ``x`` from the value in memory at ``p + Offset``.
+
+        Returns the value that was in memory before the subtraction.
+        """,
+        ins=(MemFlags, x, p, Offset), outs=a, can_load=True, can_store=True,
+        other_side_effects=True)
+
+atomic_and = Instruction(
+        'atomic_and', r"""
+        Atomically bitwise and ``x`` into the value in memory at ``p + Offset``.
+
+        Returns the value that was in memory before the operation.
+        """,
+        ins=(MemFlags, x, p, Offset), outs=a, can_load=True, can_store=True,
+        other_side_effects=True)
+
+atomic_or = Instruction(
+        'atomic_or', r"""
+        Atomically bitwise or ``x`` into the value in memory at ``p + Offset``.
+
+        Returns the value that was in memory before the operation.
+        """,
+        ins=(MemFlags, x, p, Offset), outs=a, can_load=True, can_store=True,
+        other_side_effects=True)
+
+atomic_xor = Instruction(
+        'atomic_xor', r"""
+        Atomically bitwise xor ``x`` into the value in memory at ``p + Offset``.
+
+        Returns the value that was in memory before the operation.
+        """,
+        ins=(MemFlags, x, p, Offset), outs=a, can_load=True, can_store=True,
+        other_side_effects=True)
+
+atomic_xchg = Instruction(
+        'atomic_xchg', r"""
+        Atomically replace the value in memory at ``p + Offset`` with ``x``.
+
+        Returns the value that was in memory before the exchange.
+        """,
+        ins=(MemFlags, x, p, Offset), outs=a, can_load=True, can_store=True,
+        other_side_effects=True)
+
+atomic_cas = Instruction(
+        'atomic_cas', r"""
+        Atomically compare and swap the value in memory at ``p``.
+
+        If the value in memory is equal to ``e``, replace it with ``x``.
+        Returns the value that was in memory before the operation, which is
+        equal to ``e`` if and only if the swap happened.
+
+        Unlike the other memory operations, there is no offset immediate.
+        """,
+        ins=(MemFlags, e, x, p), outs=a, can_load=True, can_store=True,
+        other_side_effects=True)
+
+fence = Instruction(
+        'fence', r"""
+        A sequentially consistent memory fence.
+
+        No memory operation before the fence can be reordered after it, and no
+        memory operation after the fence can be reordered before it.
+        """,
+        other_side_effects=True)
+
 x = Operand('x', Mem, doc='Value to be stored')
 a = Operand('a', Mem, doc='Value loaded')
 Offset = Operand('Offset', offset32, 'In-bounds offset into stack slot')
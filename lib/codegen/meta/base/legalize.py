@@ -17,19 +17,32 @@ from .instructions import imul, imul_imm
 from .instructions import sdiv, sdiv_imm, udiv, udiv_imm
 from .instructions import srem, srem_imm, urem, urem_imm
 from .instructions import band, bor, bxor, isplit, iconcat
+from .instructions import vsplit, vconcat, vselect
 from .instructions import bnot, band_not, bor_not, bxor_not
 from .instructions import band_imm, bor_imm, bxor_imm
 from .instructions import icmp, icmp_imm, ifcmp, ifcmp_imm
-from .instructions import iconst, bint, select
+from .instructions import iconst, bint, select, copy
 from .instructions import ishl, ishl_imm, sshr, sshr_imm, ushr, ushr_imm
 from .instructions import rotl, rotl_imm, rotr, rotr_imm
 from .instructions import f32const, f64const
+from .instructions import fadd, fsub, fmul, fdiv
 from .instructions import store, load
+from .instructions import uload8, uload16, uload32, sload8, sload16, sload32
+from .instructions import istore8, istore16, istore32
+from .instructions import clz, ctz, popcnt, umulhi, smulhi
+from .instructions import fpromote, fcvt_to_sint, fcvt_to_uint
 from .instructions import br_table
 from cdsl.ast import Var
 from cdsl.xform import Rtl, XFormGroup
 
 
+expand = XFormGroup('expand', """
+        Legalize instructions by expansion.
+
+        Rewrite instructions in terms of other instructions, generally
+        operating on the same types as the original instructions.
+        """)
+
 narrow = XFormGroup('narrow', """
         Legalize instructions by narrowing.
 
@@ -37,7 +50,10 @@ narrow = XFormGroup('narrow', """
         instructions in terms of smaller types. Operations on vector types are
         expressed in terms of vector types with fewer lanes, and integer
         operations are expressed in terms of smaller integer types.
-        """)
+
+        Instructions that can't be narrowed directly fall back to the normal
+        expansions, which are then narrowed in turn.
+        """, chain=expand)
 
 widen = XFormGroup('widen', """
         Legalize instructions by widening.
@@ -46,13 +62,6 @@ widen = XFormGroup('widen', """
         instructions in terms of larger types.
         """)
 
-expand = XFormGroup('expand', """
-        Legalize instructions by expansion.
-
-        Rewrite instructions in terms of other instructions, generally
-        operating on the same types as the original instructions.
-        """)
-
 expand_flags = XFormGroup('expand_flags', """
         Instruction expansions for architectures with flags.
 
@@ -85,6 +94,27 @@ expand.custom_legalize(insts.f64const, 'expand_fconst')
 expand.custom_legalize(insts.stack_load, 'expand_stack_load')
 expand.custom_legalize(insts.stack_store, 'expand_stack_store')
 
+# Custom narrowing of constants and memory accesses, which need to compute the
+# immediate or the offset of the high half.
+narrow.custom_legalize(insts.iconst, 'narrow_iconst')
+narrow.custom_legalize(insts.load, 'narrow_load')
+narrow.custom_legalize(insts.store, 'narrow_store')
+
+# Branches on a split integer only need to know if any of the halves is zero.
+narrow.custom_legalize(insts.brz, 'narrow_brz')
+narrow.custom_legalize(insts.brnz, 'narrow_brz')
+
+# Bit casts that can't be done in registers go through memory.
+expand.custom_legalize(insts.bitcast, 'expand_bitcast')
+
+# Custom expansions of atomic read-modify-write operations into a loop around
+# a compare-and-swap, for targets that don't have the operations natively.
+expand.custom_legalize(insts.atomic_add, 'expand_atomic_rmw')
+expand.custom_legalize(insts.atomic_and, 'expand_atomic_rmw')
+expand.custom_legalize(insts.atomic_or, 'expand_atomic_rmw')
+expand.custom_legalize(insts.atomic_xor, 'expand_atomic_rmw')
+expand.custom_legalize(insts.atomic_xchg, 'expand_atomic_rmw')
+
 x = Var('x')
 y = Var('y')
 a = Var('a')
@@ -93,14 +123,31 @@ a2 = Var('a2')
 b = Var('b')
 b1 = Var('b1')
 b2 = Var('b2')
+b3 = Var('b3')
 b_in = Var('b_in')
 b_int = Var('b_int')
 c = Var('c')
 c1 = Var('c1')
 c2 = Var('c2')
+c3 = Var('c3')
+c4 = Var('c4')
+c5 = Var('c5')
 c_in = Var('c_in')
 c_int = Var('c_int')
 d = Var('d')
+d1 = Var('d1')
+d2 = Var('d2')
+e1 = Var('e1')
+e2 = Var('e2')
+f1 = Var('f1')
+f2 = Var('f2')
+g1 = Var('g1')
+g2 = Var('g2')
+h1 = Var('h1')
+h2 = Var('h2')
+k1 = Var('k1')
+k2 = Var('k2')
+z = Var('z')
 xl = Var('xl')
 xh = Var('xh')
 yl = Var('yl')
@@ -112,6 +159,34 @@ ptr = Var('ptr')
 flags = Var('flags')
 offset = Var('off')
 ss = Var('ss')
+cl = Var('cl')
+ch = Var('ch')
+
+# Split vector operations into two halves. A vector with two lanes is split
+# into two scalars. These patterns come first so that integer vectors are
+# split into fewer lanes rather than into narrower lanes by `isplit`.
+for binop in [iadd, isub, imul, band, bor, bxor, band_not,
+              fadd, fsub, fmul, fdiv]:
+    narrow.legalize(
+            a << binop(x, y),
+            Rtl(
+                (xl, xh) << vsplit(x),
+                (yl, yh) << vsplit(y),
+                al << binop(xl, yl),
+                ah << binop(xh, yh),
+                a << vconcat(al, ah)
+            ))
+
+narrow.legalize(
+        a << vselect(c, x, y),
+        Rtl(
+            (cl, ch) << vsplit(c),
+            (xl, xh) << vsplit(x),
+            (yl, yh) << vsplit(y),
+            al << vselect(cl, xl, yl),
+            ah << vselect(ch, xh, yh),
+            a << vconcat(al, ah)
+        ))
 
 narrow.legalize(
         a << iadd(x, y),
@@ -154,6 +229,224 @@ narrow.legalize(
             a << iconcat(al, ah)
         ))
 
+narrow.legalize(
+        a << bnot(x),
+        Rtl(
+            (xl, xh) << isplit(x),
+            al << bnot(xl),
+            ah << bnot(xh),
+            a << iconcat(al, ah)
+        ))
+
+# The low half of the product only depends on the low halves, and the cross
+# products only contribute to the high half.
+narrow.legalize(
+        a << imul(x, y),
+        Rtl(
+            (xl, xh) << isplit(x),
+            (yl, yh) << isplit(y),
+            al << imul(xl, yl),
+            c << umulhi(xl, yl),
+            c1 << imul(xl, yh),
+            c2 << imul(xh, yl),
+            d << iadd(c1, c2),
+            ah << iadd(c, d),
+            a << iconcat(al, ah)
+        ))
+
+# The high half of the full product accumulates the partial products of the
+# halves, one 32-bit column at a time.
+narrow.legalize(
+        a << umulhi(x, y),
+        Rtl(
+            (xl, xh) << isplit(x),
+            (yl, yh) << isplit(y),
+            z << iconst.i32(imm64(0)),
+            c << umulhi(xl, yl),
+            d1 << imul(xh, yl),
+            d2 << umulhi(xh, yl),
+            (e1, c1) << iadd_cout(d1, c),
+            e2 << iadd_cin(d2, z, c1),
+            f1 << imul(xl, yh),
+            f2 << umulhi(xl, yh),
+            (g1, c2) << iadd_cout(f1, e1),
+            g2 << iadd_cin(f2, z, c2),
+            h1 << imul(xh, yh),
+            h2 << umulhi(xh, yh),
+            (k1, c3) << iadd_cout(h1, e2),
+            k2 << iadd_cin(h2, z, c3),
+            (al, c4) << iadd_cout(k1, g2),
+            ah << iadd_cin(k2, z, c4),
+            a << iconcat(al, ah)
+        ))
+
+# The signed high half subtracts the other operand for each negative operand.
+narrow.legalize(
+        a << smulhi(x, y),
+        Rtl(
+            b << umulhi(x, y),
+            (xl, xh) << isplit(x),
+            (yl, yh) << isplit(y),
+            c1 << sshr_imm(xh, imm64(31)),
+            d1 << iconcat(c1, c1),
+            e1 << band(d1, y),
+            c2 << sshr_imm(yh, imm64(31)),
+            d2 << iconcat(c2, c2),
+            e2 << band(d2, x),
+            f1 << isub(b, e1),
+            a << isub(f1, e2)
+        ))
+
+narrow.legalize(
+        a << copy(x),
+        Rtl(
+            (xl, xh) << isplit(x),
+            al << copy(xl),
+            ah << copy(xh),
+            a << iconcat(al, ah)
+        ))
+
+# Equality compares both halves.
+narrow.legalize(
+        b << icmp(intcc.eq, x, y),
+        Rtl(
+            (xl, xh) << isplit(x),
+            (yl, yh) << isplit(y),
+            b1 << icmp(intcc.eq, xl, yl),
+            b2 << icmp(intcc.eq, xh, yh),
+            b << band(b1, b2)
+        ))
+
+narrow.legalize(
+        b << icmp(intcc.ne, x, y),
+        Rtl(
+            (xl, xh) << isplit(x),
+            (yl, yh) << isplit(y),
+            b1 << icmp(intcc.ne, xl, yl),
+            b2 << icmp(intcc.ne, xh, yh),
+            b << bor(b1, b2)
+        ))
+
+# Ordered comparisons are decided by the high halves, unless they are equal.
+# The low halves are always compared as unsigned numbers.
+for cond,           cond_hi,   cond_lo in [
+        (intcc.slt, intcc.slt, intcc.ult),
+        (intcc.sge, intcc.sgt, intcc.uge),
+        (intcc.sgt, intcc.sgt, intcc.ugt),
+        (intcc.sle, intcc.slt, intcc.ule),
+        (intcc.ult, intcc.ult, intcc.ult),
+        (intcc.uge, intcc.ugt, intcc.uge),
+        (intcc.ugt, intcc.ugt, intcc.ugt),
+        (intcc.ule, intcc.ult, intcc.ule)]:
+    narrow.legalize(
+            b << icmp(cond, x, y),
+            Rtl(
+                (xl, xh) << isplit(x),
+                (yl, yh) << isplit(y),
+                b1 << icmp(cond_hi, xh, yh),
+                b2 << icmp(intcc.eq, xh, yh),
+                b3 << icmp(cond_lo, xl, yl),
+                c << band(b2, b3),
+                b << bor(b1, c)
+            ))
+
+# Bit counts. The count of the high half is 32 exactly when the low half needs
+# to be counted too.
+for count, first, second in [(clz, xh, xl), (ctz, xl, xh)]:
+    narrow.legalize(
+            a << count.i64(x),
+            Rtl(
+                (xl, xh) << isplit(x),
+                c1 << count(first),
+                c2 << count(second),
+                c3 << ushr_imm(c1, imm64(5)),
+                c4 << irsub_imm(c3, imm64(0)),
+                c5 << band(c2, c4),
+                al << iadd(c1, c5),
+                ah << iconst.i32(imm64(0)),
+                a << iconcat(al, ah)
+            ))
+
+narrow.legalize(
+        a << popcnt.i64(x),
+        Rtl(
+            (xl, xh) << isplit(x),
+            c1 << popcnt(xl),
+            c2 << popcnt(xh),
+            al << iadd(c1, c2),
+            ah << iconst.i32(imm64(0)),
+            a << iconcat(al, ah)
+        ))
+
+# Extensions to 64 bits compute the high half from the extended low half.
+for int_ty in [types.i8, types.i16]:
+    narrow.legalize(
+            a << uextend.i64.bind(int_ty)(x),
+            Rtl(
+                al << uextend.i32(x),
+                ah << iconst.i32(imm64(0)),
+                a << iconcat(al, ah)
+            ))
+    narrow.legalize(
+            a << sextend.i64.bind(int_ty)(x),
+            Rtl(
+                al << sextend.i32(x),
+                ah << sshr_imm(al, imm64(31)),
+                a << iconcat(al, ah)
+            ))
+
+narrow.legalize(
+        a << uextend.i64.i32(x),
+        Rtl(
+            ah << iconst.i32(imm64(0)),
+            a << iconcat(x, ah)
+        ))
+
+narrow.legalize(
+        a << sextend.i64.i32(x),
+        Rtl(
+            ah << sshr_imm(x, imm64(31)),
+            a << iconcat(x, ah)
+        ))
+
+narrow.legalize(
+        a << bint.i64(x),
+        Rtl(
+            al << bint.i32(x),
+            ah << iconst.i32(imm64(0)),
+            a << iconcat(al, ah)
+        ))
+
+# Narrow loads and stores only access the low half in memory.
+for load_inst, ext in [
+        (uload8, uextend),
+        (uload16, uextend),
+        (sload8, sextend),
+        (sload16, sextend)]:
+    narrow.legalize(
+            a << load_inst.i64(flags, ptr, offset),
+            Rtl(
+                b << load_inst.i32(flags, ptr, offset),
+                a << ext.i64(b)
+            ))
+
+for store_inst in [istore8, istore16]:
+    narrow.legalize(
+            store_inst.i64(flags, x, ptr, offset),
+            Rtl(
+                (xl, xh) << isplit(x),
+                store_inst(flags, xl, ptr, offset)
+            ))
+
+# There is no 64-bit conversion from f32, but it is exact to go through f64.
+for cvt in [fcvt_to_sint, fcvt_to_uint]:
+    narrow.legalize(
+            a << cvt.i64.f32(x),
+            Rtl(
+                b << fpromote.f64(x),
+                a << cvt(b)
+            ))
+
 for int_ty in [types.i8, types.i16]:
     widen.legalize(
         a << iconst.bind(int_ty)(b),
@@ -225,7 +518,8 @@ for unop in [bnot]:
             )
         )
 
-for binop in [iadd_imm, imul_imm, udiv_imm]:
+for binop in [iadd_imm, imul_imm, udiv_imm, urem_imm, band_imm, bor_imm,
+              bxor_imm]:
     for int_ty in [types.i8, types.i16]:
         widen.legalize(
             a << binop.bind(int_ty)(x, y),
@@ -236,7 +530,7 @@ for binop in [iadd_imm, imul_imm, udiv_imm]:
             )
         )
 
-for binop in [sdiv_imm]:
+for binop in [sdiv_imm, srem_imm]:
     for int_ty in [types.i8, types.i16]:
         widen.legalize(
             a << binop.bind(int_ty)(x, y),
@@ -247,6 +541,15 @@ for binop in [sdiv_imm]:
             )
         )
 
+for int_ty in [types.i8, types.i16]:
+    widen.legalize(
+        a << ireduce.bind(int_ty).i64(x),
+        Rtl(
+            (b, xh) << isplit(x),
+            a << ireduce(b)
+        )
+    )
+
 for int_ty in [types.i8, types.i16]:
     widen.legalize(
         br_table.bind(int_ty)(x, y),
@@ -316,7 +619,7 @@ for inst_imm,      inst in [
         (urem_imm, urem),
         (band_imm, band),
         (bor_imm, bor),
-        (bxor_imm, bor),
+        (bxor_imm, bxor),
         (ifcmp_imm, ifcmp)]:
     expand.legalize(
             a << inst_imm(x, y),
@@ -345,6 +648,27 @@ for inst_imm,      inst in [
                 a << inst(x, a1)
             ))
 
+# Rotates are composed from shifts, whose amounts are taken modulo the number
+# of bits.
+for rot, shl, shr in [(rotl, ishl, ushr), (rotr, ushr, ishl)]:
+    expand.legalize(
+            a << rot(x, y),
+            Rtl(
+                a1 << shl(x, y),
+                b << irsub_imm(y, imm64(0)),
+                a2 << shr(x, b),
+                a << bor(a1, a2)
+            ))
+
+# Reducing a 64-bit integer on a 32-bit target only needs its low half. The
+# split produces new values, so the result is a copy of the low half.
+expand.legalize(
+        a << ireduce.i32.i64(x),
+        Rtl(
+            (xl, xh) << isplit(x),
+            a << copy(xl)
+        ))
+
 expand.legalize(
         a << icmp_imm(cc, x, y),
         Rtl(
@@ -364,6 +688,29 @@ for inst_not,      inst in [
                 a << inst(x, a1)
             ))
 
+# The 32-bit loads and stores of i64 values are monomorphic, so they go through
+# the expansions on 32-bit targets.
+expand.legalize(
+        a << uload32(flags, ptr, offset),
+        Rtl(
+            b << load.i32(flags, ptr, offset),
+            a << uextend.i64(b)
+        ))
+
+expand.legalize(
+        a << sload32(flags, ptr, offset),
+        Rtl(
+            b << load.i32(flags, ptr, offset),
+            a << sextend.i64(b)
+        ))
+
+expand.legalize(
+        istore32(flags, x, ptr, offset),
+        Rtl(
+            (xl, xh) << isplit(x),
+            store(flags, xl, ptr, offset)
+        ))
+
 # Expand bnot using xor.
 expand.legalize(
         a << bnot(x),
@@ -397,6 +744,14 @@ for ty,             minus_zero in [
                 a << bor(a1, a2)
             ))
 
+# Subtract atomically by adding the negated operand.
+expand.legalize(
+        a << insts.atomic_sub(flags, x, ptr, offset),
+        Rtl(
+            y << irsub_imm(x, imm64(0)),
+            a << insts.atomic_add(flags, y, ptr, offset)
+        ))
+
 
 # Expansions using CPU flags.
 
@@ -160,10 +160,20 @@ probestack_size_log2 = NumSetting(
         The log2 of the size of the stack guard region.
 
         Stack frames larger than this size will have stack overflow checked
-        by calling the probestack function.
+        by probing the stack, as selected by `probestack_strategy`.
 
         The default is 12, which translates to a size of 4096.
         """,
         default=12)
 
+probestack_strategy = EnumSetting(
+        """
+        The strategy used to probe the stack of large frames:
+
+        - outline: call the probestack function
+        - inline: probe every page of the frame with a loop in the prologue,
+                  for when no probestack function is available
+        """,
+        'outline', 'inline')
+
 group.close(globals())
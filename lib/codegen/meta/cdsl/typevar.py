@@ -854,11 +854,16 @@ class TypeVar(object):
         """
         Get a Rust expression that computes the type of this type variable.
         """
-        if self.is_derived:
-            return '{}.{}()'.format(
-                    self.base.rust_expr(), self.derived_func)
-        elif self.singleton_type():
+        if self.singleton_type():
+            # This also covers derived type variables with a known base, like
+            # the halves of an `iconcat.i64`.
             return self.singleton_type().rust_name()
+        elif self.is_derived:
+            # `ir::Type` names the `lane_of` function `lane_type()`.
+            func = self.derived_func
+            if func == TypeVar.LANEOF:
+                func = 'lane_type'
+            return '{}.{}()'.format(self.base.rust_expr(), func)
         else:
             return self.name
 
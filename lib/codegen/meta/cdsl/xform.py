@@ -177,7 +177,7 @@ class XForm(object):
 
         # Rewrite variables in src and dst RTL lists to our own copies.
         # Map name -> private Var.
-        symtab = dict()  # type: Dict[str, Var]
+        symtab = OrderedDict()  # type: Dict[str, Var]
         self._rewrite_rtl(src, symtab, Var.SRCCTX)
         num_src_inputs = len(self.inputs)
         self._rewrite_rtl(dst, symtab, Var.DSTCTX)
@@ -416,8 +416,8 @@ class XFormGroup(object):
         The `funcname` parameter is the fully qualified name of a Rust function
         which takes the same arguments as the `isa::Legalize` actions.
 
-        The custom function will be called to legalize `inst` and any return
-        value is ignored.
+        The custom function will be called to legalize `inst`. It returns
+        false if it left `inst` unchanged, and then the chained group is tried.
         """
         assert inst not in self.custom, "Duplicate custom_legalize"
         self.custom[inst] = funcname
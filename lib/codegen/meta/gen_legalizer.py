@@ -49,8 +49,10 @@ def get_runtime_typechecks(xform):
     src_copy = xform.src.copy(symtab)
     src_typenv = get_type_env(ti_rtl(src_copy, TypeEnv()))
 
-    for v in xform.ti.vars:
-        if not v.has_free_typevar():
+    # Visit the variables in the order they appear in the patterns, so the
+    # generated checks don't depend on the iteration order of a set.
+    for v in xform.symtab.values():
+        if v not in xform.ti.vars or not v.has_free_typevar():
             continue
 
         # In rust the local variable containing a free TV associated with var v
@@ -390,8 +392,10 @@ def gen_xform_group(xgrp, fmt, type_sets):
                     with fmt.indented(
                             'ir::Opcode::{} => {{'
                             .format(inst.camel_name), '}'):
-                        fmt.format('{}(inst, pos.func, cfg, isa);', funcname)
-                        fmt.line('return true;')
+                        with fmt.indented(
+                                'if {}(inst, pos.func, cfg, isa) {{'
+                                .format(funcname), '}'):
+                            fmt.line('return true;')
 
                 # We'll assume there are uncovered opcodes.
                 fmt.line('_ => {},')
@@ -6,14 +6,27 @@ Commonly used definitions.
 from __future__ import absolute_import
 from cdsl.isa import TargetISA, CPUMode
 import base.instructions
-from base.legalize import narrow
+from . import instructions as arm32
+from base.immediates import floatcc
 
-ISA = TargetISA('arm32', [base.instructions.GROUP])  # type: TargetISA
+ISA = TargetISA('arm32', [base.instructions.GROUP, arm32.GROUP])  # type: TargetISA
 
 # CPU modes for 32-bit ARM and Thumb2.
 A32 = CPUMode('A32', ISA)
 T32 = CPUMode('T32', ISA)
 
-# TODO: Refine these.
-A32.legalize_type(narrow)
-T32.legalize_type(narrow)
+# The set of floating point condition codes that are tested with a single
+# condition code after `vcmp`. The others need two of them.
+supported_floatccs = [
+        floatcc.eq,
+        floatcc.ne,
+        floatcc.lt,
+        floatcc.le,
+        floatcc.gt,
+        floatcc.ge,
+        floatcc.uno,
+        floatcc.ord,
+        floatcc.ult,
+        floatcc.ule,
+        floatcc.ugt,
+        floatcc.uge]
@@ -0,0 +1,249 @@
+"""
+ARM32 Encodings.
+"""
+from __future__ import absolute_import
+from base import instructions as base
+from base import types
+from .defs import A32, T32
+from . import instructions as arm32
+from . import settings as cfg
+from .recipes import A32 as OP, VFP, VX, B
+from .recipes import R, Rmul, Rmulhi, Rmov, Rrmov, null, Rclz, Rext
+from .recipes import Rdiv, Rsdiv, Rrem, Rshift, Rshi, Ri, Iz, Iconst, Izb
+from .recipes import Ricmp, Iicmp, Scmp, Dcmp, Scmp2, Dcmp2
+from .recipes import Srr, Drr, Sr, Dr, Srmov, Drmov, Rpromote, Rdemote
+from .recipes import Scvt, Dcvt, Svmov, Rvmov
+from .recipes import LD, LDh, LDr, LDhr, ST, STh, STr, SThr
+from .recipes import SLD, DLD, SST, DST, SLDr, DLDr, SSTr, DSTr, Iaddr
+from .recipes import GPsp, GPfi, GPrsp, GPrfi, Ssp, Dsp, Sfi, Dfi
+from .recipes import Srsp, Drsp, Srfi, Drfi, Push, Pop, Dpush, Dpop
+from .recipes import Icopysp, Iadjsp, Iadjsp16, Iret, Icall
+from .recipes import UJ, UJcall, Bz, SB, Iudf
+from base.legalize import narrow, widen, expand
+
+A32.legalize_monomorphic(expand)
+A32.legalize_type(
+        default=narrow,
+        b1=expand,
+        i8=widen,
+        i16=widen,
+        i32=expand,
+        f32=expand,
+        f64=expand)
+
+# There are no T32 encodings yet.
+T32.legalize_type(narrow)
+
+# The floating point instruction templates below are the single precision
+# variants. This bit selects the double precision variants.
+DOUBLE = 1 << 8
+
+# Integer instructions on registers.
+for inst,               op in [
+        (base.iadd,     0xe0800000),
+        (base.isub,     0xe0400000),
+        (base.band,     0xe0000000),
+        (base.bor,      0xe1800000),
+        (base.bxor,     0xe0200000),
+        (base.band_not, 0xe1c00000)
+        ]:
+    A32.enc(inst.i32, R, OP(op))
+
+# Also add `b1` encodings for the logic instructions.
+A32.enc(base.band.b1, R, OP(0xe0000000))
+A32.enc(base.bor.b1, R, OP(0xe1800000))
+A32.enc(base.bxor.b1, R, OP(0xe0200000))
+
+A32.enc(base.imul.i32, Rmul, OP(0xe0000090))
+A32.enc(base.umulhi.i32, Rmulhi, OP(0xe0800090))
+A32.enc(base.smulhi.i32, Rmulhi, OP(0xe0c00090))
+
+A32.enc(base.bnot.i32, Rmov, OP(0xe1e00000))
+
+# Shifts are aliases of `mov` with a shifted register operand.
+for inst,           op in [
+        (base.ishl, 0xe1a00000),
+        (base.ushr, 0xe1a00020),
+        (base.sshr, 0xe1a00040),
+        (base.rotr, 0xe1a00060)
+        ]:
+    A32.enc(inst.i32.i32, Rshift, OP(op | 0x10))
+
+for inst,               op in [
+        (base.ishl_imm, 0xe1a00000),
+        (base.ushr_imm, 0xe1a00020),
+        (base.sshr_imm, 0xe1a00040),
+        (base.rotr_imm, 0xe1a00060)
+        ]:
+    A32.enc(inst.i32, Rshi, OP(op))
+
+# Data processing with an immediate operand.
+for inst,               op in [
+        (base.iadd_imm,  0xe2800000),
+        (base.irsub_imm, 0xe2600000),
+        (base.band_imm,  0xe2000000),
+        (base.bor_imm,   0xe3800000),
+        (base.bxor_imm,  0xe2200000)
+        ]:
+    A32.enc(inst.i32, Ri, OP(op))
+
+# Integer division traps like the clif base instructions, which the A32
+# division instructions don't. They are optional in ARMv7-A.
+A32.enc(base.udiv.i32, Rdiv, OP(0xe7300010), isap=cfg.has_idiv)
+A32.enc(base.sdiv.i32, Rsdiv, OP(0xe7100010), isap=cfg.has_idiv)
+A32.enc(base.urem.i32, Rrem, OP(0xe7300010), isap=cfg.has_idiv)
+A32.enc(base.srem.i32, Rrem, OP(0xe7100010), isap=cfg.has_idiv)
+
+A32.enc(base.clz.i32, Rclz, OP(0xe1600010))
+
+# Comparisons are `cmp` followed by conditional moves.
+A32.enc(base.icmp.i32, Ricmp, OP(0xe1500000))
+A32.enc(base.icmp_imm.i32, Iicmp, OP(0xe3500000))
+
+# Integer constants.
+A32.enc(base.iconst.i32, Iz, OP(0xe3000000))
+A32.enc(base.iconst.i32, Iconst, OP(0xe3000000))
+A32.enc(base.bconst.b1, Izb, OP(0xe3000000))
+
+# Extensions.
+A32.enc(base.uextend.i32.i8, Rext, OP(0xe6e00070))
+A32.enc(base.uextend.i32.i16, Rext, OP(0xe6f00070))
+A32.enc(base.sextend.i32.i8, Rext, OP(0xe6a00070))
+A32.enc(base.sextend.i32.i16, Rext, OP(0xe6b00070))
+
+# Reductions ignore the high bits of the register, and booleans are 0 or 1.
+A32.enc(base.ireduce.i8.i32, null, 0)
+A32.enc(base.ireduce.i16.i32, null, 0)
+A32.enc(base.bint.i32.b1, null, 0)
+
+# Register copies.
+for ty in [types.i8, types.i16, types.i32, types.b1]:
+    A32.enc(base.copy.bind(ty), Rmov, OP(0xe1a00000))
+    A32.enc(base.regmove.bind(ty), Rrmov, OP(0xe1a00000))
+
+A32.enc(base.copy.f32, Sr, VFP(0xeeb00a40))
+A32.enc(base.copy.f64, Dr, VFP(0xeeb00a40 | DOUBLE))
+A32.enc(base.regmove.f32, Srmov, VFP(0xeeb00a40))
+A32.enc(base.regmove.f64, Drmov, VFP(0xeeb00a40 | DOUBLE))
+
+# Loads and stores. The templates are the immediate offset variants, with an
+# added offset.
+for recipe in [LD, LDr]:
+    A32.enc(base.load.i32.any, recipe, OP(0xe5900000))
+    A32.enc(base.uload8.i32.any, recipe, OP(0xe5d00000))
+
+for recipe in [LDh, LDhr]:
+    A32.enc(base.sload8.i32.any, recipe, OP(0xe1d000d0))
+    A32.enc(base.uload16.i32.any, recipe, OP(0xe1d000b0))
+    A32.enc(base.sload16.i32.any, recipe, OP(0xe1d000f0))
+
+for recipe in [ST, STr]:
+    A32.enc(base.store.i32.any, recipe, OP(0xe5800000))
+    A32.enc(base.istore8.i32.any, recipe, OP(0xe5c00000))
+
+for recipe in [STh, SThr]:
+    A32.enc(base.istore16.i32.any, recipe, OP(0xe1c000b0))
+
+for recipe in [SLD, SLDr]:
+    A32.enc(base.load.f32.any, recipe, VX(0xed900a00))
+
+for recipe in [DLD, DLDr]:
+    A32.enc(base.load.f64.any, recipe, VX(0xed900a00 | DOUBLE))
+
+for recipe in [SST, SSTr]:
+    A32.enc(base.store.f32.any, recipe, VX(0xed800a00))
+
+for recipe in [DST, DSTr]:
+    A32.enc(base.store.f64.any, recipe, VX(0xed800a00 | DOUBLE))
+
+A32.enc(base.stack_addr.i32, Iaddr, OP(0xe0800000))
+
+# Spill and fill. Spill slots are at least 4 bytes, so the narrow types are
+# spilled with word stores.
+for ty in [types.i8, types.i16, types.i32, types.b1]:
+    A32.enc(base.spill.bind(ty), GPsp, OP(0xe5800000))
+    A32.enc(base.fill.bind(ty), GPfi, OP(0xe5900000))
+    A32.enc(base.regspill.bind(ty), GPrsp, OP(0xe5800000))
+    A32.enc(base.regfill.bind(ty), GPrfi, OP(0xe5900000))
+
+A32.enc(base.spill.f32, Ssp, VX(0xed800a00))
+A32.enc(base.fill.f32, Sfi, VX(0xed900a00))
+A32.enc(base.regspill.f32, Srsp, VX(0xed800a00))
+A32.enc(base.regfill.f32, Srfi, VX(0xed900a00))
+A32.enc(base.spill.f64, Dsp, VX(0xed800a00 | DOUBLE))
+A32.enc(base.fill.f64, Dfi, VX(0xed900a00 | DOUBLE))
+A32.enc(base.regspill.f64, Drsp, VX(0xed800a00 | DOUBLE))
+A32.enc(base.regfill.f64, Drfi, VX(0xed900a00 | DOUBLE))
+
+# Floating point arithmetic.
+for inst,           op in [
+        (base.fadd, 0xee300a00),
+        (base.fsub, 0xee300a40),
+        (base.fmul, 0xee200a00),
+        (base.fdiv, 0xee800a00)
+        ]:
+    A32.enc(inst.f32, Srr, VFP(op))
+    A32.enc(inst.f64, Drr, VFP(op | DOUBLE))
+
+for inst,           op in [
+        (base.fneg, 0xeeb10a40),
+        (base.fabs, 0xeeb00ac0),
+        (base.sqrt, 0xeeb10ac0)
+        ]:
+    A32.enc(inst.f32, Sr, VFP(op))
+    A32.enc(inst.f64, Dr, VFP(op | DOUBLE))
+
+A32.enc(base.fpromote.f64.f32, Rpromote, VFP(0xeeb70ac0))
+A32.enc(base.fdemote.f32.f64, Rdemote, VFP(0xeeb70ac0 | DOUBLE))
+
+# Floating point comparisons. The recipes select the supported condition
+# codes.
+A32.enc(base.fcmp.f32, Scmp, VFP(0xeeb40a40))
+A32.enc(base.fcmp.f32, Scmp2, VFP(0xeeb40a40))
+A32.enc(base.fcmp.f64, Dcmp, VFP(0xeeb40a40 | DOUBLE))
+A32.enc(base.fcmp.f64, Dcmp2, VFP(0xeeb40a40 | DOUBLE))
+
+# Moves between integer and single precision registers.
+A32.enc(base.bitcast.f32.i32, Svmov, VX(0xee000a10))
+A32.enc(base.bitcast.i32.f32, Rvmov, VX(0xee100a10))
+
+A32.enc(base.fcvt_from_sint.f32.i32, Scvt, VFP(0xeeb80ac0))
+A32.enc(base.fcvt_from_sint.f64.i32, Dcvt, VFP(0xeeb80ac0 | DOUBLE))
+A32.enc(base.fcvt_from_uint.f32.i32, Scvt, VFP(0xeeb80a40))
+A32.enc(base.fcvt_from_uint.f64.i32, Dcvt, VFP(0xeeb80a40 | DOUBLE))
+
+# Control flow.
+
+# Unconditional branches.
+A32.enc(base.jump, UJ, B(0xea000000))
+A32.enc(base.call, UJcall, B(0xeb000000))
+
+# Conditional branches.
+A32.enc(base.br_icmp.i32, SB, OP(0xe1500000))
+
+for inst,           op in [
+        (base.brz,  0x0a000000),
+        (base.brnz, 0x1a000000)
+        ]:
+    A32.enc(inst.i32, Bz, B(op))
+    A32.enc(inst.b1, Bz, B(op))
+
+# Returns branch to the address in the link register `lr`, which is provided
+# by a special-purpose `link` return value added by the prologue.
+A32.enc(base.x_return, Iret, OP(0xe1200010))
+A32.enc(base.call_indirect.i32, Icall, OP(0xe1200030))
+
+A32.enc(base.trap, Iudf, 0)
+
+# Prologue and epilogue.
+A32.enc(arm32.push.i32, Push, OP(0xe5a00000))
+A32.enc(arm32.pop.i32, Pop, OP(0xe4900000))
+A32.enc(arm32.push.f64, Dpush, VX(0xed200a00 | DOUBLE))
+A32.enc(arm32.pop.f64, Dpop, VX(0xecb00a00 | DOUBLE))
+
+A32.enc(base.copy_special, Icopysp, OP(0xe1a00000))
+
+A32.enc(base.adjust_sp_down_imm, Iadjsp, OP(0xe2400000))
+A32.enc(base.adjust_sp_up_imm, Iadjsp, OP(0xe2800000))
+A32.enc(base.adjust_sp_down_imm, Iadjsp16, OP(0xe0400000))
+A32.enc(base.adjust_sp_up_imm, Iadjsp16, OP(0xe0800000))
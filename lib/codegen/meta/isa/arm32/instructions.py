@@ -0,0 +1,43 @@
+"""
+Supplementary instruction definitions for ARM32.
+
+This module defines additional instructions that are useful only to the ARM32
+target ISA.
+"""
+
+from cdsl.operands import Operand
+from cdsl.typevar import TypeVar
+from cdsl.instructions import Instruction, InstructionGroup
+
+
+GROUP = InstructionGroup("arm32", "arm32-specific instruction set")
+
+Reg = TypeVar(
+        'Reg', 'A 32-bit integer or 64-bit floating point register',
+        ints=(32, 32), floats=(64, 64))
+
+x = Operand('x', Reg)
+
+push = Instruction(
+    'arm32_push', r"""
+    Pushes a value onto the stack.
+
+    Decrements the stack pointer by the size of `x` and stores it on to the
+    top.
+
+    This is polymorphic in i32 and f64.
+    """,
+    ins=x, can_store=True, other_side_effects=True)
+
+pop = Instruction(
+    'arm32_pop', r"""
+    Pops a value from the stack.
+
+    Loads a value from the top of the stack and then increments the stack
+    pointer by its size. This is the inverse of :inst:`arm32_push`.
+
+    This is polymorphic in i32 and f64.
+    """,
+    outs=x, can_load=True, other_side_effects=True)
+
+GROUP.close()
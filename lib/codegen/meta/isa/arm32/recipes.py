@@ -0,0 +1,605 @@
+"""
+ARM32 Encoding recipes.
+
+All A32 instructions are 32 bits wide. The encoding recipes defined here
+correspond to the instruction classes described in the reference:
+
+    ARM Architecture Reference Manual
+    ARMv7-A and ARMv7-R edition
+
+Only the A32 instruction set is covered. The instructions are all
+unconditional, except for the conditional branches and moves emitted by some
+of the recipes.
+"""
+from __future__ import absolute_import
+from cdsl.isa import EncRecipe
+from cdsl.predicates import IsSignedInt, IsUnsignedInt, IsEqual, Or
+from cdsl.registers import Stack
+from base.formats import Binary, BinaryImm, MultiAry, NullAry
+from base.formats import IntCompare, IntCompareImm, FloatCompare
+from base.formats import Unary, UnaryImm, UnaryBool, BranchIcmp, Branch, Jump
+from base.formats import Call, CallIndirect, Load, Store, Trap
+from base.formats import RegMove, RegSpill, RegFill, CopySpecial, StackLoad
+from base.immediates import floatcc
+from .defs import supported_floatccs
+from .registers import GPR, S, D
+
+try:
+    from typing import Sequence  # noqa
+    from cdsl.ast import Enumerator  # noqa
+    from cdsl.instructions import InstructionFormat  # noqa
+    from cdsl.isa import PredNode  # noqa
+except ImportError:
+    pass
+
+# The encbits of a recipe hold the opcode bits of the instructions it encodes.
+# The operand fields and the condition are filled in by the `put_*` functions
+# of `isa/arm32/binemit.rs`, so the layout of the encbits depends on the
+# instruction class. The functions below compute the encbits from an
+# instruction template with all its operand fields zero.
+
+
+def A32(inst):
+    # type: (int) -> int
+    """
+    Data processing, multiplication and load/store instructions:
+    `inst[27:20] | (inst[7:4] << 8)`.
+
+    The four register fields `inst[19:16]`, `inst[15:12]`, `inst[11:8]` and
+    `inst[3:0]` must be zero. Fixed register fields are supplied by the
+    recipes.
+    """
+    assert inst >> 28 == 0xe, hex(inst)
+    assert inst & 0x000fff0f == 0, hex(inst)
+    return ((inst >> 20) & 0xff) | (((inst >> 4) & 0xf) << 8)
+
+
+def VFP(inst):
+    # type: (int) -> int
+    """
+    Floating point data processing: `inst[27:16] | (inst[8] << 12) |
+    (inst[7:6] << 13)`.
+
+    The `Vd` and `Vm` register fields must be zero, as well as the `Vn` field
+    of the instructions with two sources.
+    """
+    assert inst >> 24 == 0xee, hex(inst)
+    assert (inst >> 9) & 0b111 == 0b101, hex(inst)
+    assert inst & 0x0040f03f == 0, hex(inst)
+    return (((inst >> 16) & 0xfff) | (((inst >> 8) & 1) << 12) |
+            (((inst >> 6) & 3) << 13))
+
+
+def VX(inst):
+    # type: (int) -> int
+    """
+    Floating point loads and stores, and transfers between integer and
+    floating point registers: `inst[27:20] | (inst[8] << 8) | (inst[4] << 9)`.
+    """
+    assert inst >> 28 == 0xe, hex(inst)
+    assert (inst >> 9) & 0b111 == 0b101, hex(inst)
+    assert inst & 0x004ff0ef == 0, hex(inst)
+    return (((inst >> 20) & 0xff) | (((inst >> 8) & 1) << 8) |
+            (((inst >> 4) & 1) << 9))
+
+
+def B(inst):
+    # type: (int) -> int
+    """
+    Branches to an immediate displacement, including their condition:
+    `inst[31:24]`.
+    """
+    assert inst & 0x00ffffff == 0, hex(inst)
+    return inst >> 24
+
+
+def floatccs(iform, ccs):
+    # type: (InstructionFormat, Sequence[Enumerator]) -> PredNode
+    """
+    Return an instruction predicate that checks if `iform.cond` is one of the
+    floating point condition codes in `ccs`.
+    """
+    return Or(*(IsEqual(iform.cond, cc) for cc in ccs))
+
+
+# Integer instructions on registers.
+R = EncRecipe(
+        'R', Binary, size=4, ins=(GPR, GPR), outs=GPR,
+        emit='put_a32(bits, in_reg0, out_reg0, 0, in_reg1, sink);')
+
+# Multiplication, which has its destination in `inst[19:16]`.
+Rmul = EncRecipe(
+        'Rmul', Binary, size=4, ins=(GPR, GPR), outs=GPR,
+        emit='put_a32(bits, out_reg0, 0, in_reg1, in_reg0, sink);')
+
+# High half of a long multiplication. The low half goes to the scratch
+# register `ip`.
+Rmulhi = EncRecipe(
+        'Rmulhi', Binary, size=4, ins=(GPR, GPR), outs=GPR,
+        emit='put_a32(bits, out_reg0, 12, in_reg1, in_reg0, sink);')
+
+# Copy of a GPR with `mov`, or its complement with `mvn`.
+Rmov = EncRecipe(
+        'Rmov', Unary, size=4, ins=GPR, outs=GPR,
+        emit='put_a32(bits, 0, out_reg0, 0, in_reg0, sink);')
+
+# Same for a GPR regmove.
+Rrmov = EncRecipe(
+        'Rrmov', RegMove, size=4, ins=GPR, outs=(),
+        emit='put_a32(bits, 0, dst, 0, src, sink);')
+
+# A null unary instruction that takes a GPR register. Can be used for no-op
+# conversions.
+null = EncRecipe('null', Unary, size=0, ins=GPR, outs=0, emit='')
+
+# Count leading zeros. The unused register fields are all ones.
+Rclz = EncRecipe(
+        'Rclz', Unary, size=4, ins=GPR, outs=GPR,
+        emit='put_a32(bits, 15, out_reg0, 15, in_reg0, sink);')
+
+# Sign and zero extensions with `sxtb`, `uxth` and friends. The `rn` field is
+# all ones, which selects the variants without an addend.
+Rext = EncRecipe(
+        'Rext', Unary, size=4, ins=GPR, outs=GPR,
+        emit='put_a32(bits, 15, out_reg0, 0, in_reg0, sink);')
+
+# Unsigned division, trapping on a zero divisor.
+Rdiv = EncRecipe(
+        'Rdiv', Binary, size=16, ins=(GPR, GPR), outs=GPR,
+        emit='''
+        put_trap_zero(in_reg1, func.srclocs[inst], sink);
+        put_a32(bits, out_reg0, 15, in_reg1, in_reg0, sink);
+        ''')
+
+# Signed division, trapping on a zero divisor and on overflow.
+Rsdiv = EncRecipe(
+        'Rsdiv', Binary, size=32, ins=(GPR, GPR), outs=GPR,
+        emit='''
+        put_trap_zero(in_reg1, func.srclocs[inst], sink);
+        put_trap_sdiv_overflow(in_reg0, in_reg1, func.srclocs[inst], sink);
+        put_a32(bits, out_reg0, 15, in_reg1, in_reg0, sink);
+        ''')
+
+# Remainder of a division, trapping on a zero divisor. The quotient is
+# computed in the scratch register `ip` and multiplied back with `mls`.
+Rrem = EncRecipe(
+        'Rrem', Binary, size=20, ins=(GPR, GPR), outs=GPR,
+        emit='''
+        put_trap_zero(in_reg1, func.srclocs[inst], sink);
+        put_a32(bits, 12, 15, in_reg1, in_reg0, sink);
+        put_mls(12, in_reg1, in_reg0, out_reg0, sink);
+        ''')
+
+# Shifts by a register amount. The amount is masked to 5 bits first, since
+# the A32 shifts use the low byte of the amount register.
+Rshift = EncRecipe(
+        'Rshift', Binary, size=8, ins=(GPR, GPR), outs=GPR,
+        emit='put_shift(bits, in_reg0, in_reg1, out_reg0, sink);')
+
+# Shifts by an immediate amount.
+Rshi = EncRecipe(
+        'Rshi', BinaryImm, size=4, ins=GPR, outs=GPR,
+        emit='put_shi(bits, in_reg0, imm.into(), out_reg0, sink);')
+
+# Data processing with an 8-bit unsigned immediate.
+Ri = EncRecipe(
+        'Ri', BinaryImm, size=4, ins=GPR, outs=GPR,
+        instp=IsUnsignedInt(BinaryImm.imm, 8),
+        emit='put_dpi(bits, in_reg0, imm.into(), out_reg0, sink);')
+
+# Constants with 16 significant bits, with `movw`.
+Iz = EncRecipe(
+        'Iz', UnaryImm, size=4, ins=(), outs=GPR,
+        instp=IsUnsignedInt(UnaryImm.imm, 16),
+        emit='put_movw(bits, imm.into(), out_reg0, sink);')
+
+# Any 32-bit constant, with a `movw` and a `movt`.
+Iconst = EncRecipe(
+        'Iconst', UnaryImm, size=8, ins=(), outs=GPR,
+        emit='put_const(bits, imm.into(), out_reg0, sink);')
+
+# Boolean constants are materialized with `movw`.
+Izb = EncRecipe(
+        'Izb', UnaryBool, size=4, ins=(), outs=GPR,
+        emit='put_movw(bits, if imm { 1 } else { 0 }, out_reg0, sink);')
+
+# Integer comparison: `cmp` followed by a conditional `mov`.
+Ricmp = EncRecipe(
+        'Ricmp', IntCompare, size=12, ins=(GPR, GPR), outs=GPR,
+        emit='''
+        put_a32(bits, in_reg0, 0, 0, in_reg1, sink);
+        put_cset(icc2cond(cond), out_reg0, sink);
+        ''')
+
+# Integer comparison with an 8-bit unsigned immediate.
+Iicmp = EncRecipe(
+        'Iicmp', IntCompareImm, size=12, ins=GPR, outs=GPR,
+        instp=IsUnsignedInt(IntCompareImm.imm, 8),
+        emit='''
+        put_dpi(bits, in_reg0, imm.into(), 0, sink);
+        put_cset(icc2cond(cond), out_reg0, sink);
+        ''')
+
+# Floating point comparison: `vcmp` and `vmrs` to copy the flags, followed by
+# a conditional `mov`.
+Scmp = EncRecipe(
+        'Scmp', FloatCompare, size=16, ins=(S, S), outs=GPR,
+        instp=floatccs(FloatCompare, supported_floatccs),
+        emit='''
+        put_vfp1(bits, in_reg1, in_reg0, sink);
+        put_vmrs(sink);
+        put_cset(fcc2cond(cond), out_reg0, sink);
+        ''')
+
+Dcmp = EncRecipe(
+        'Dcmp', FloatCompare, size=16, ins=(D, D), outs=GPR,
+        instp=floatccs(FloatCompare, supported_floatccs),
+        emit='''
+        put_vfp1(bits, in_reg1, in_reg0, sink);
+        put_vmrs(sink);
+        put_cset(fcc2cond(cond), out_reg0, sink);
+        ''')
+
+# Floating point comparisons which need two condition codes, with one
+# conditional `mov` for each.
+Scmp2 = EncRecipe(
+        'Scmp2', FloatCompare, size=20, ins=(S, S), outs=GPR,
+        instp=floatccs(FloatCompare, [floatcc.ueq, floatcc.one]),
+        emit='''
+        put_vfp1(bits, in_reg1, in_reg0, sink);
+        put_vmrs(sink);
+        put_cset2(cond, out_reg0, sink);
+        ''')
+
+Dcmp2 = EncRecipe(
+        'Dcmp2', FloatCompare, size=20, ins=(D, D), outs=GPR,
+        instp=floatccs(FloatCompare, [floatcc.ueq, floatcc.one]),
+        emit='''
+        put_vfp1(bits, in_reg1, in_reg0, sink);
+        put_vmrs(sink);
+        put_cset2(cond, out_reg0, sink);
+        ''')
+
+# Floating point instructions on registers.
+Srr = EncRecipe(
+        'Srr', Binary, size=4, ins=(S, S), outs=S,
+        emit='put_vfp2(bits, in_reg0, in_reg1, out_reg0, sink);')
+
+Drr = EncRecipe(
+        'Drr', Binary, size=4, ins=(D, D), outs=D,
+        emit='put_vfp2(bits, in_reg0, in_reg1, out_reg0, sink);')
+
+# Floating point instructions with a single register operand, including
+# copies.
+Sr = EncRecipe(
+        'Sr', Unary, size=4, ins=S, outs=S,
+        emit='put_vfp1(bits, in_reg0, out_reg0, sink);')
+
+Dr = EncRecipe(
+        'Dr', Unary, size=4, ins=D, outs=D,
+        emit='put_vfp1(bits, in_reg0, out_reg0, sink);')
+
+# Same for floating point regmoves.
+Srmov = EncRecipe(
+        'Srmov', RegMove, size=4, ins=S, outs=(),
+        emit='put_vfp1(bits, src, dst, sink);')
+
+Drmov = EncRecipe(
+        'Drmov', RegMove, size=4, ins=D, outs=(),
+        emit='put_vfp1(bits, src, dst, sink);')
+
+# Conversions between single and double precision.
+Rpromote = EncRecipe(
+        'Rpromote', Unary, size=4, ins=S, outs=D,
+        emit='put_vfp1(bits, in_reg0, out_reg0, sink);')
+
+Rdemote = EncRecipe(
+        'Rdemote', Unary, size=4, ins=D, outs=S,
+        emit='put_vfp1(bits, in_reg0, out_reg0, sink);')
+
+# Conversions from integers. The integer is moved to the low single precision
+# half of the destination with `vmov` first.
+Scvt = EncRecipe(
+        'Scvt', Unary, size=8, ins=GPR, outs=S,
+        emit='''
+        put_vmov(VMOV_TO_S, out_reg0, in_reg0, sink);
+        put_vfp1(bits, out_reg0, out_reg0, sink);
+        ''')
+
+Dcvt = EncRecipe(
+        'Dcvt', Unary, size=8, ins=GPR, outs=D,
+        emit='''
+        put_vmov(VMOV_TO_S, out_reg0, in_reg0, sink);
+        put_vfp1(bits, out_reg0, out_reg0, sink);
+        ''')
+
+# Moves between integer and single precision registers with `vmov`.
+Svmov = EncRecipe(
+        'Svmov', Unary, size=4, ins=GPR, outs=S,
+        emit='put_vmov(bits, out_reg0, in_reg0, sink);')
+
+Rvmov = EncRecipe(
+        'Rvmov', Unary, size=4, ins=S, outs=GPR,
+        emit='put_vmov(bits, in_reg0, out_reg0, sink);')
+
+# Word and unsigned byte loads with a 12-bit offset. The offset is limited to
+# 12 signed bits to keep the predicate simple.
+LD = EncRecipe(
+        'LD', Load, size=4, ins=GPR, outs=GPR,
+        instp=IsSignedInt(Load.offset, 12),
+        emit='put_ldst(bits, out_reg0, in_reg0, offset.into(), sink);')
+
+# Halfword and signed byte loads with an 8-bit offset.
+LDh = EncRecipe(
+        'LDh', Load, size=4, ins=GPR, outs=GPR,
+        instp=IsSignedInt(Load.offset, 8),
+        emit='put_ldsth(bits, out_reg0, in_reg0, offset.into(), sink);')
+
+# Loads with any offset, materialized in the scratch register `ip`.
+LDr = EncRecipe(
+        'LDr', Load, size=12, ins=GPR, outs=GPR,
+        emit='put_ldst_offset(bits, out_reg0, in_reg0, offset.into(), sink);')
+
+LDhr = EncRecipe(
+        'LDhr', Load, size=12, ins=GPR, outs=GPR,
+        emit='put_ldsth_offset(bits, out_reg0, in_reg0, offset.into(), sink);')
+
+# Stores, with the same offset variants as the loads.
+ST = EncRecipe(
+        'ST', Store, size=4, ins=(GPR, GPR), outs=(),
+        instp=IsSignedInt(Store.offset, 12),
+        emit='put_ldst(bits, in_reg0, in_reg1, offset.into(), sink);')
+
+STh = EncRecipe(
+        'STh', Store, size=4, ins=(GPR, GPR), outs=(),
+        instp=IsSignedInt(Store.offset, 8),
+        emit='put_ldsth(bits, in_reg0, in_reg1, offset.into(), sink);')
+
+STr = EncRecipe(
+        'STr', Store, size=12, ins=(GPR, GPR), outs=(),
+        emit='put_ldst_offset(bits, in_reg0, in_reg1, offset.into(), sink);')
+
+SThr = EncRecipe(
+        'SThr', Store, size=12, ins=(GPR, GPR), outs=(),
+        emit='put_ldsth_offset(bits, in_reg0, in_reg1, offset.into(), sink);')
+
+# Floating point loads and stores with a word-aligned 10-bit offset.
+SLD = EncRecipe(
+        'SLD', Load, size=4, ins=GPR, outs=S,
+        instp=IsSignedInt(Load.offset, 10, 2),
+        emit='put_vldst(bits, out_reg0, in_reg0, offset.into(), sink);')
+
+DLD = EncRecipe(
+        'DLD', Load, size=4, ins=GPR, outs=D,
+        instp=IsSignedInt(Load.offset, 10, 2),
+        emit='put_vldst(bits, out_reg0, in_reg0, offset.into(), sink);')
+
+SST = EncRecipe(
+        'SST', Store, size=4, ins=(S, GPR), outs=(),
+        instp=IsSignedInt(Store.offset, 10, 2),
+        emit='put_vldst(bits, in_reg0, in_reg1, offset.into(), sink);')
+
+DST = EncRecipe(
+        'DST', Store, size=4, ins=(D, GPR), outs=(),
+        instp=IsSignedInt(Store.offset, 10, 2),
+        emit='put_vldst(bits, in_reg0, in_reg1, offset.into(), sink);')
+
+# Floating point loads and stores with any offset. The address is computed in
+# the scratch register `ip`.
+SLDr = EncRecipe(
+        'SLDr', Load, size=16, ins=GPR, outs=S,
+        emit='put_vldst_offset(bits, out_reg0, in_reg0, offset.into(), sink);')
+
+DLDr = EncRecipe(
+        'DLDr', Load, size=16, ins=GPR, outs=D,
+        emit='put_vldst_offset(bits, out_reg0, in_reg0, offset.into(), sink);')
+
+SSTr = EncRecipe(
+        'SSTr', Store, size=16, ins=(S, GPR), outs=(),
+        emit='put_vldst_offset(bits, in_reg0, in_reg1, offset.into(), sink);')
+
+DSTr = EncRecipe(
+        'DSTr', Store, size=16, ins=(D, GPR), outs=(),
+        emit='put_vldst_offset(bits, in_reg0, in_reg1, offset.into(), sink);')
+
+# Address of a stack slot, computed from the stack pointer.
+Iaddr = EncRecipe(
+        'Iaddr', StackLoad, size=12, ins=(), outs=GPR,
+        emit='''
+        let sp = StackRef::sp(stack_slot, &func.stack_slots);
+        let offset: i32 = offset.into();
+        put_const(MOVW, i64::from(sp.offset + offset), out_reg0, sink);
+        put_a32(bits, 13, out_reg0, 0, out_reg0, sink);
+        ''')
+
+# Spill and fill of a GPR, relative to the stack pointer. The stack frame is
+# limited to 64 KB, so the offset is materialized with a single `movw`.
+GPsp = EncRecipe(
+        'GPsp', Unary, size=8, ins=GPR, outs=Stack(GPR),
+        emit='''
+        sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+        put_ldst_sp(bits, in_reg0, out_stk0.offset, sink);
+        ''')
+
+GPfi = EncRecipe(
+        'GPfi', Unary, size=8, ins=Stack(GPR), outs=GPR,
+        emit='put_ldst_sp(bits, out_reg0, in_stk0.offset, sink);')
+
+# The floating point registers are spilled and filled with the address
+# computed in the scratch register `ip`.
+Ssp = EncRecipe(
+        'Ssp', Unary, size=12, ins=S, outs=Stack(S),
+        emit='''
+        sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+        put_vldst_sp(bits, in_reg0, out_stk0.offset, sink);
+        ''')
+
+Sfi = EncRecipe(
+        'Sfi', Unary, size=12, ins=Stack(S), outs=S,
+        emit='put_vldst_sp(bits, out_reg0, in_stk0.offset, sink);')
+
+Dsp = EncRecipe(
+        'Dsp', Unary, size=12, ins=D, outs=Stack(D),
+        emit='''
+        sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+        put_vldst_sp(bits, in_reg0, out_stk0.offset, sink);
+        ''')
+
+Dfi = EncRecipe(
+        'Dfi', Unary, size=12, ins=Stack(D), outs=D,
+        emit='put_vldst_sp(bits, out_reg0, in_stk0.offset, sink);')
+
+# Regspill and regfill.
+GPrsp = EncRecipe(
+        'GPrsp', RegSpill, size=8, ins=GPR, outs=(),
+        emit='''
+        sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+        let dst = StackRef::sp(dst, &func.stack_slots);
+        put_ldst_sp(bits, src, dst.offset, sink);
+        ''')
+
+GPrfi = EncRecipe(
+        'GPrfi', RegFill, size=8, ins=Stack(GPR), outs=(),
+        emit='''
+        let src = StackRef::sp(src, &func.stack_slots);
+        put_ldst_sp(bits, dst, src.offset, sink);
+        ''')
+
+Srsp = EncRecipe(
+        'Srsp', RegSpill, size=12, ins=S, outs=(),
+        emit='''
+        sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+        let dst = StackRef::sp(dst, &func.stack_slots);
+        put_vldst_sp(bits, src, dst.offset, sink);
+        ''')
+
+Srfi = EncRecipe(
+        'Srfi', RegFill, size=12, ins=Stack(S), outs=(),
+        emit='''
+        let src = StackRef::sp(src, &func.stack_slots);
+        put_vldst_sp(bits, dst, src.offset, sink);
+        ''')
+
+Drsp = EncRecipe(
+        'Drsp', RegSpill, size=12, ins=D, outs=(),
+        emit='''
+        sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+        let dst = StackRef::sp(dst, &func.stack_slots);
+        put_vldst_sp(bits, src, dst.offset, sink);
+        ''')
+
+Drfi = EncRecipe(
+        'Drfi', RegFill, size=12, ins=Stack(D), outs=(),
+        emit='''
+        let src = StackRef::sp(src, &func.stack_slots);
+        put_vldst_sp(bits, dst, src.offset, sink);
+        ''')
+
+# Push and pop a GPR with a pre-decrementing `str` and a post-incrementing
+# `ldr`.
+Push = EncRecipe(
+        'Push', Unary, size=4, ins=GPR, outs=(),
+        emit='''
+        sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+        put_ldst(bits, in_reg0, 13, -4, sink);
+        ''')
+
+Pop = EncRecipe(
+        'Pop', NullAry, size=4, ins=(), outs=GPR,
+        emit='put_ldst(bits, out_reg0, 13, 4, sink);')
+
+# Push and pop a double precision register with `vpush` and `vpop`.
+Dpush = EncRecipe(
+        'Dpush', Unary, size=4, ins=D, outs=(),
+        emit='''
+        sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+        put_vpush_pop(bits, in_reg0, sink);
+        ''')
+
+Dpop = EncRecipe(
+        'Dpop', NullAry, size=4, ins=(), outs=D,
+        emit='put_vpush_pop(bits, out_reg0, sink);')
+
+# Copy of the stack pointer with `mov`.
+Icopysp = EncRecipe(
+        'Icopysp', CopySpecial, size=4, ins=(), outs=(),
+        emit='put_a32(bits, 0, dst, 0, src, sink);')
+
+# Adjustments of the stack pointer by an 8-bit immediate.
+Iadjsp = EncRecipe(
+        'Iadjsp', UnaryImm, size=4, ins=(), outs=(),
+        instp=IsUnsignedInt(UnaryImm.imm, 8),
+        emit='put_dpi(bits, 13, imm.into(), 13, sink);')
+
+# Adjustments of the stack pointer by a 16-bit immediate, materialized in the
+# scratch register `ip`.
+Iadjsp16 = EncRecipe(
+        'Iadjsp16', UnaryImm, size=8, ins=(), outs=(),
+        instp=IsUnsignedInt(UnaryImm.imm, 16),
+        emit='''
+        put_movw(MOVW, imm.into(), 12, sink);
+        put_a32(bits, 13, 13, 0, 12, sink);
+        ''')
+
+# Return is a `bx` to the link register. The return address is provided by the
+# special-purpose `link` argument added by the prologue.
+# The variable return values are not encoded.
+Iret = EncRecipe(
+        'Iret', MultiAry, size=4, ins=(), outs=(),
+        emit='put_a32(bits, 15, 15, 15, 14, sink);')
+
+# Indirect call with `blx`.
+Icall = EncRecipe(
+        'Icall', CallIndirect, size=4, ins=GPR, outs=(),
+        emit='put_a32(bits, 15, 15, 15, in_reg0, sink);')
+
+# Unconditional branch. The displacement is relative to the address of the
+# branch plus 8.
+UJ = EncRecipe(
+        'UJ', Jump, size=4, ins=(), outs=(), branch_range=(8, 26),
+        emit='''
+        let dest = i64::from(func.offsets[destination]);
+        let disp = dest - i64::from(sink.offset());
+        put_b(bits, disp, sink);
+        ''')
+
+# Direct call with `bl`, relocated. The relocation provides the displacement,
+# so the offset field is left zero.
+UJcall = EncRecipe(
+        'UJcall', Call, size=4, ins=(), outs=(),
+        emit='''
+        sink.reloc_external(Reloc::Arm32Call,
+                            &func.dfg.ext_funcs[func_ref].name,
+                            -8);
+        put_b(bits, 8, sink);
+        ''')
+
+# Compare with zero and branch.
+Bz = EncRecipe(
+        'Bz', Branch, size=8, ins=GPR, outs=(),
+        branch_range=(12, 26),
+        emit='''
+        put_cmp_zero(in_reg0, sink);
+        let dest = i64::from(func.offsets[destination]);
+        let disp = dest - i64::from(sink.offset());
+        put_b(bits, disp, sink);
+        ''')
+
+# Integer comparison and conditional branch: `cmp` followed by `b<cond>`.
+SB = EncRecipe(
+        'SB', BranchIcmp, size=8, ins=(GPR, GPR), outs=(),
+        branch_range=(12, 26),
+        emit='''
+        put_a32(bits, in_reg0, 0, 0, in_reg1, sink);
+        let dest = i64::from(func.offsets[destination]);
+        let disp = dest - i64::from(sink.offset());
+        put_bcond(icc2cond(cond), disp, sink);
+        ''')
+
+# Traps are the permanently undefined instruction `udf`.
+Iudf = EncRecipe(
+        'Iudf', Trap, size=4, ins=(), outs=(),
+        emit='''
+        sink.trap(code, func.srclocs[inst]);
+        put_udf(sink);
+        ''')
@@ -2,10 +2,13 @@
 ARM32 settings.
 """
 from __future__ import absolute_import
-from cdsl.settings import SettingGroup
+from cdsl.settings import SettingGroup, BoolSetting
 import base.settings as shared
 from .defs import ISA
 
 ISA.settings = SettingGroup('arm32', parent=shared.group)
 
+has_idiv = BoolSetting(
+        "Integer division: the `sdiv` and `udiv` instructions in ARM state")
+
 ISA.settings.close(globals())
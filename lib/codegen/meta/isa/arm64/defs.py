@@ -6,10 +6,24 @@ Commonly used definitions.
 from __future__ import absolute_import
 from cdsl.isa import TargetISA, CPUMode
 import base.instructions
-from base.legalize import narrow
+from . import instructions as arm64
+from base.immediates import floatcc
 
-ISA = TargetISA('arm64', [base.instructions.GROUP])  # type: TargetISA
+ISA = TargetISA('arm64', [base.instructions.GROUP, arm64.GROUP])  # type: TargetISA
 A64 = CPUMode('A64', ISA)
 
-# TODO: Refine these
-A64.legalize_type(narrow)
+# The set of floating point condition codes that are tested with a single
+# condition code after `fcmp`. The others need two of them.
+supported_floatccs = [
+        floatcc.eq,
+        floatcc.ne,
+        floatcc.lt,
+        floatcc.le,
+        floatcc.gt,
+        floatcc.ge,
+        floatcc.uno,
+        floatcc.ord,
+        floatcc.ult,
+        floatcc.ule,
+        floatcc.ugt,
+        floatcc.uge]
@@ -0,0 +1,291 @@
+"""
+ARM64 Encodings.
+"""
+from __future__ import absolute_import
+from base import instructions as base
+from base import types
+from .defs import A64
+from . import instructions as arm64
+from .recipes import DP, FP1, EXT, IMM, MOVW, LDST, REG, CB, B
+from .recipes import R, Rr, Rmov, Rrmov, null, Rdiv, Rsdiv, Rrem
+from .recipes import Rmulhi
+from .recipes import Ii, Rlsl, Rshr, Rext, Iz, Iconst32, Iconst64, Izb
+from .recipes import Ricmp, Iicmp, Rfcmp, Rfcmp2, Rf, Rf1, Rfrmov
+from .recipes import Rcvt, Rfcvt, LDur, fLDur, LDr, fLDr
+from .recipes import STur, fSTur, STr, fSTr, Iaddr
+from .recipes import GPsp, GPfi, FPsp, FPfi, GPrsp, GPrfi, FPrsp, FPrfi
+from .recipes import Pushp, Popp, fPushp, fPopp, Icopysp, Iadjsp, Iadjsp24
+from .recipes import Iret, Icall, UJ, UJcall, CBz, SB, Iudf
+from base.legalize import narrow, widen, expand
+
+A64.legalize_monomorphic(expand)
+A64.legalize_type(
+        default=narrow,
+        b1=expand,
+        i8=widen,
+        i16=widen,
+        i32=expand,
+        i64=expand,
+        f32=expand,
+        f64=expand)
+
+# The instruction templates below are the 32-bit or single precision variants
+# of the instructions. These bits select the other variants.
+SF = 1 << 31
+N = 1 << 22
+DOUBLE = 1 << 22
+
+# Integer instructions on registers. `mul` is an alias of `madd` with the zero
+# register as addend.
+for inst,               op in [
+        (base.iadd,     0x0b000000),
+        (base.isub,     0x4b000000),
+        (base.band,     0x0a000000),
+        (base.bor,      0x2a000000),
+        (base.bxor,     0x4a000000),
+        (base.band_not, 0x0a200000),
+        (base.bor_not,  0x2a200000),
+        (base.bxor_not, 0x4a200000),
+        (base.imul,     0x1b007c00)
+        ]:
+    A64.enc(inst.i32, R, DP(op))
+    A64.enc(inst.i64, R, DP(op | SF))
+
+# The high half of a 32-bit multiplication is shifted down from the 64-bit
+# product of `smull` or `umull`.
+A64.enc(base.smulhi.i32, Rmulhi, DP(0x9b207c00))
+A64.enc(base.smulhi.i64, R, DP(0x9b407c00))
+A64.enc(base.umulhi.i32, Rmulhi, DP(0x9ba07c00))
+A64.enc(base.umulhi.i64, R, DP(0x9bc07c00))
+
+# Also add `b1` encodings for the logic instructions.
+A64.enc(base.band.b1, R, DP(0x0a000000))
+A64.enc(base.bor.b1, R, DP(0x2a000000))
+A64.enc(base.bxor.b1, R, DP(0x4a000000))
+
+# Dynamic shifts use the low bits of the shift amount, like the clif base
+# instructions.
+for inst,           op in [
+        (base.ishl, 0x1ac02000),
+        (base.ushr, 0x1ac02400),
+        (base.sshr, 0x1ac02800),
+        (base.rotr, 0x1ac02c00)
+        ]:
+    A64.enc(inst.i32.i32, R, DP(op))
+    A64.enc(inst.i32.i64, R, DP(op))
+    A64.enc(inst.i64.i64, R, DP(op | SF))
+    A64.enc(inst.i64.i32, R, DP(op | SF))
+
+# Immediate shifts are aliases of the bitfield moves `ubfm` and `sbfm`.
+for inst,               recipe, op in [
+        (base.ishl_imm, Rlsl,   0x53000000),
+        (base.ushr_imm, Rshr,   0x53000000),
+        (base.sshr_imm, Rshr,   0x13000000)
+        ]:
+    A64.enc(inst.i32, recipe, IMM(op))
+    A64.enc(inst.i64, recipe, IMM(op | SF | N))
+
+A64.enc(base.iadd_imm.i32, Ii, IMM(0x11000000))
+A64.enc(base.iadd_imm.i64, Ii, IMM(0x11000000 | SF))
+
+# Integer division traps like the clif base instructions, which the A64
+# division instructions don't.
+A64.enc(base.udiv.i32, Rdiv, DP(0x1ac00800))
+A64.enc(base.udiv.i64, Rdiv, DP(0x1ac00800 | SF))
+A64.enc(base.sdiv.i32, Rsdiv, DP(0x1ac00c00))
+A64.enc(base.sdiv.i64, Rsdiv, DP(0x1ac00c00 | SF))
+A64.enc(base.urem.i32, Rrem, DP(0x1ac00800))
+A64.enc(base.urem.i64, Rrem, DP(0x1ac00800 | SF))
+A64.enc(base.srem.i32, Rrem, DP(0x1ac00c00))
+A64.enc(base.srem.i64, Rrem, DP(0x1ac00c00 | SF))
+
+A64.enc(base.clz.i32, Rr, DP(0x5ac01000))
+A64.enc(base.clz.i64, Rr, DP(0x5ac01000 | SF))
+
+# Comparisons are `subs` to the zero register, followed by a `cset`.
+A64.enc(base.icmp.i32, Ricmp, DP(0x6b000000))
+A64.enc(base.icmp.i64, Ricmp, DP(0x6b000000 | SF))
+A64.enc(base.icmp_imm.i32, Iicmp, IMM(0x71000000))
+A64.enc(base.icmp_imm.i64, Iicmp, IMM(0x71000000 | SF))
+
+# Integer constants.
+A64.enc(base.iconst.i32, Iz, MOVW(0x52800000))
+A64.enc(base.iconst.i64, Iz, MOVW(0x52800000 | SF))
+A64.enc(base.iconst.i32, Iconst32, MOVW(0x52800000))
+A64.enc(base.iconst.i64, Iconst64, MOVW(0x52800000 | SF))
+A64.enc(base.bconst.b1, Izb, MOVW(0x52800000))
+
+# Extensions. Writing a 32-bit register clears the high bits of the 64-bit
+# register, so the zero extensions to i64 use the 32-bit `ubfm`.
+A64.enc(base.uextend.i32.i8, Rext, EXT(0x53000000, 7))
+A64.enc(base.uextend.i32.i16, Rext, EXT(0x53000000, 15))
+A64.enc(base.uextend.i64.i8, Rext, EXT(0x53000000, 7))
+A64.enc(base.uextend.i64.i16, Rext, EXT(0x53000000, 15))
+A64.enc(base.uextend.i64.i32, Rext, EXT(0x53000000, 31))
+A64.enc(base.sextend.i32.i8, Rext, EXT(0x13000000, 7))
+A64.enc(base.sextend.i32.i16, Rext, EXT(0x13000000, 15))
+A64.enc(base.sextend.i64.i8, Rext, EXT(0x13000000 | SF | N, 7))
+A64.enc(base.sextend.i64.i16, Rext, EXT(0x13000000 | SF | N, 15))
+A64.enc(base.sextend.i64.i32, Rext, EXT(0x13000000 | SF | N, 31))
+
+# Reductions ignore the high bits of the register, and booleans are 0 or 1.
+A64.enc(base.ireduce.i8.i32, null, 0)
+A64.enc(base.ireduce.i16.i32, null, 0)
+A64.enc(base.ireduce.i8.i64, null, 0)
+A64.enc(base.ireduce.i16.i64, null, 0)
+A64.enc(base.ireduce.i32.i64, null, 0)
+A64.enc(base.bint.i32.b1, null, 0)
+A64.enc(base.bint.i64.b1, null, 0)
+
+# Register copies.
+for ty in [types.i8, types.i16, types.i32, types.b1]:
+    A64.enc(base.copy.bind(ty), Rmov, DP(0x2a000000))
+    A64.enc(base.regmove.bind(ty), Rrmov, DP(0x2a000000))
+A64.enc(base.copy.i64, Rmov, DP(0x2a000000 | SF))
+A64.enc(base.regmove.i64, Rrmov, DP(0x2a000000 | SF))
+
+A64.enc(base.copy.f32, Rf1, FP1(0x1e204000))
+A64.enc(base.copy.f64, Rf1, FP1(0x1e204000 | DOUBLE))
+A64.enc(base.regmove.f32, Rfrmov, FP1(0x1e204000))
+A64.enc(base.regmove.f64, Rfrmov, FP1(0x1e204000 | DOUBLE))
+
+# Loads and stores. The templates are the unscaled offset variants.
+for recipe in [LDur, LDr]:
+    A64.enc(base.load.i32.any, recipe, LDST(0xb8400000))
+    A64.enc(base.load.i64.any, recipe, LDST(0xf8400000))
+    A64.enc(base.uload8.i32.any, recipe, LDST(0x38400000))
+    A64.enc(base.uload8.i64.any, recipe, LDST(0x38400000))
+    A64.enc(base.sload8.i32.any, recipe, LDST(0x38c00000))
+    A64.enc(base.sload8.i64.any, recipe, LDST(0x38800000))
+    A64.enc(base.uload16.i32.any, recipe, LDST(0x78400000))
+    A64.enc(base.uload16.i64.any, recipe, LDST(0x78400000))
+    A64.enc(base.sload16.i32.any, recipe, LDST(0x78c00000))
+    A64.enc(base.sload16.i64.any, recipe, LDST(0x78800000))
+    A64.enc(base.uload32.i64, recipe, LDST(0xb8400000))
+    A64.enc(base.sload32.i64, recipe, LDST(0xb8800000))
+
+for recipe in [STur, STr]:
+    A64.enc(base.store.i32.any, recipe, LDST(0xb8000000))
+    A64.enc(base.store.i64.any, recipe, LDST(0xf8000000))
+    A64.enc(base.istore8.i32.any, recipe, LDST(0x38000000))
+    A64.enc(base.istore8.i64.any, recipe, LDST(0x38000000))
+    A64.enc(base.istore16.i32.any, recipe, LDST(0x78000000))
+    A64.enc(base.istore16.i64.any, recipe, LDST(0x78000000))
+    A64.enc(base.istore32.i64.any, recipe, LDST(0xb8000000))
+
+for recipe in [fLDur, fLDr]:
+    A64.enc(base.load.f32.any, recipe, LDST(0xbc400000))
+    A64.enc(base.load.f64.any, recipe, LDST(0xfc400000))
+
+for recipe in [fSTur, fSTr]:
+    A64.enc(base.store.f32.any, recipe, LDST(0xbc000000))
+    A64.enc(base.store.f64.any, recipe, LDST(0xfc000000))
+
+A64.enc(base.stack_addr.i64, Iaddr, IMM(0x91000000))
+
+# Spill and fill. Spill slots are at least 4 bytes, so the narrow types are
+# spilled with 32-bit stores.
+for ty in [types.i8, types.i16, types.i32, types.b1]:
+    A64.enc(base.spill.bind(ty), GPsp, LDST(0xb8000000))
+    A64.enc(base.fill.bind(ty), GPfi, LDST(0xb8400000))
+    A64.enc(base.regspill.bind(ty), GPrsp, LDST(0xb8000000))
+    A64.enc(base.regfill.bind(ty), GPrfi, LDST(0xb8400000))
+A64.enc(base.spill.i64, GPsp, LDST(0xf8000000))
+A64.enc(base.fill.i64, GPfi, LDST(0xf8400000))
+A64.enc(base.regspill.i64, GPrsp, LDST(0xf8000000))
+A64.enc(base.regfill.i64, GPrfi, LDST(0xf8400000))
+
+A64.enc(base.spill.f32, FPsp, LDST(0xbc000000))
+A64.enc(base.fill.f32, FPfi, LDST(0xbc400000))
+A64.enc(base.regspill.f32, FPrsp, LDST(0xbc000000))
+A64.enc(base.regfill.f32, FPrfi, LDST(0xbc400000))
+A64.enc(base.spill.f64, FPsp, LDST(0xfc000000))
+A64.enc(base.fill.f64, FPfi, LDST(0xfc400000))
+A64.enc(base.regspill.f64, FPrsp, LDST(0xfc000000))
+A64.enc(base.regfill.f64, FPrfi, LDST(0xfc400000))
+
+# Floating point arithmetic.
+for inst,           op in [
+        (base.fadd, 0x1e202800),
+        (base.fsub, 0x1e203800),
+        (base.fmul, 0x1e200800),
+        (base.fdiv, 0x1e201800),
+        (base.fmin, 0x1e205800),
+        (base.fmax, 0x1e204800)
+        ]:
+    A64.enc(inst.f32, Rf, DP(op))
+    A64.enc(inst.f64, Rf, DP(op | DOUBLE))
+
+for inst,              op in [
+        (base.fneg,    0x1e214000),
+        (base.fabs,    0x1e20c000),
+        (base.sqrt,    0x1e21c000),
+        (base.nearest, 0x1e244000),
+        (base.ceil,    0x1e24c000),
+        (base.floor,   0x1e254000),
+        (base.trunc,   0x1e25c000)
+        ]:
+    A64.enc(inst.f32, Rf1, FP1(op))
+    A64.enc(inst.f64, Rf1, FP1(op | DOUBLE))
+
+A64.enc(base.fpromote.f64.f32, Rf1, FP1(0x1e22c000))
+A64.enc(base.fdemote.f32.f64, Rf1, FP1(0x1e224000 | DOUBLE))
+
+# Floating point comparisons. The recipes select the supported condition
+# codes.
+for recipe in [Rfcmp, Rfcmp2]:
+    A64.enc(base.fcmp.f32, recipe, DP(0x1e202000))
+    A64.enc(base.fcmp.f64, recipe, DP(0x1e202000 | DOUBLE))
+
+# Conversions between integer and floating point registers. `fmov` moves the
+# bits unchanged.
+A64.enc(base.bitcast.f32.i32, Rcvt, REG(0x1e270000))
+A64.enc(base.bitcast.f64.i64, Rcvt, REG(0x1e270000 | SF | DOUBLE))
+A64.enc(base.bitcast.i32.f32, Rfcvt, REG(0x1e260000))
+A64.enc(base.bitcast.i64.f64, Rfcvt, REG(0x1e260000 | SF | DOUBLE))
+
+for inst,                  op in [
+        (base.fcvt_from_sint, 0x1e220000),
+        (base.fcvt_from_uint, 0x1e230000)
+        ]:
+    A64.enc(inst.f32.i32, Rcvt, REG(op))
+    A64.enc(inst.f64.i32, Rcvt, REG(op | DOUBLE))
+    A64.enc(inst.f32.i64, Rcvt, REG(op | SF))
+    A64.enc(inst.f64.i64, Rcvt, REG(op | SF | DOUBLE))
+
+# Control flow.
+
+# Unconditional branches.
+A64.enc(base.jump, UJ, B(0x14000000))
+A64.enc(base.call, UJcall, B(0x94000000))
+
+# Conditional branches.
+A64.enc(base.br_icmp.i32, SB, DP(0x6b000000))
+A64.enc(base.br_icmp.i64, SB, DP(0x6b000000 | SF))
+
+for inst,           op in [
+        (base.brz,  0x34000000),
+        (base.brnz, 0x35000000)
+        ]:
+    A64.enc(inst.i32, CBz, CB(op))
+    A64.enc(inst.i64, CBz, CB(op | SF))
+    A64.enc(inst.b1, CBz, CB(op))
+
+# Returns branch to the address in the link register `x30`, which is provided
+# by a special-purpose `link` return value added by the prologue.
+A64.enc(base.x_return, Iret, REG(0xd65f0000))
+A64.enc(base.call_indirect.i64, Icall, REG(0xd63f0000))
+
+A64.enc(base.trap, Iudf, 0)
+
+# Prologue and epilogue.
+A64.enc(arm64.push_pair.i64, Pushp, IMM(0xa9800000))
+A64.enc(arm64.pop_pair.i64, Popp, IMM(0xa8c00000))
+A64.enc(arm64.push_pair.f64, fPushp, IMM(0x6d800000))
+A64.enc(arm64.pop_pair.f64, fPopp, IMM(0x6cc00000))
+
+A64.enc(base.copy_special, Icopysp, IMM(0x91000000 | SF))
+
+for recipe in [Iadjsp, Iadjsp24]:
+    A64.enc(base.adjust_sp_down_imm, recipe, IMM(0x51000000 | SF))
+    A64.enc(base.adjust_sp_up_imm, recipe, IMM(0x11000000 | SF))
@@ -0,0 +1,44 @@
+"""
+Supplementary instruction definitions for ARM64.
+
+This module defines additional instructions that are useful only to the ARM64
+target ISA.
+"""
+
+from cdsl.operands import Operand
+from cdsl.typevar import TypeVar
+from cdsl.instructions import Instruction, InstructionGroup
+
+
+GROUP = InstructionGroup("arm64", "arm64-specific instruction set")
+
+Reg64 = TypeVar(
+        'Reg64', 'A 64-bit integer or floating point register',
+        ints=(64, 64), floats=(64, 64))
+
+x = Operand('x', Reg64, doc='Value stored at the lower address')
+y = Operand('y', Reg64, doc='Value stored at the higher address')
+
+push_pair = Instruction(
+    'arm64_push_pair', r"""
+    Pushes a pair of values onto the stack.
+
+    Decrements the stack pointer by 16 and stores `x` and `y` on to the top,
+    keeping the stack pointer 16-byte aligned as required by AAPCS64.
+
+    This is polymorphic in i64 and f64.
+    """,
+    ins=(x, y), can_store=True, other_side_effects=True)
+
+pop_pair = Instruction(
+    'arm64_pop_pair', r"""
+    Pops a pair of values from the stack.
+
+    Loads two values from the top of the stack and then increments the stack
+    pointer by 16. This is the inverse of :inst:`arm64_push_pair`.
+
+    This is polymorphic in i64 and f64.
+    """,
+    outs=(x, y), can_load=True, other_side_effects=True)
+
+GROUP.close()
@@ -0,0 +1,523 @@
+"""
+ARM64 Encoding recipes.
+
+All A64 instructions are 32 bits wide. The encoding recipes defined here
+correspond to the instruction classes described in the reference:
+
+    ARM Architecture Reference Manual
+    ARMv8, for ARMv8-A architecture profile
+"""
+from __future__ import absolute_import
+from cdsl.isa import EncRecipe
+from cdsl.predicates import IsSignedInt, IsUnsignedInt, IsEqual, Or
+from cdsl.registers import Stack
+from base.formats import Binary, BinaryImm, MultiAry, NullAry
+from base.formats import IntCompare, IntCompareImm, FloatCompare
+from base.formats import Unary, UnaryImm, UnaryBool, BranchIcmp, Branch, Jump
+from base.formats import Call, CallIndirect, Load, Store, Trap
+from base.formats import RegMove, RegSpill, RegFill, CopySpecial, StackLoad
+from base.immediates import floatcc
+from .defs import supported_floatccs
+from .registers import GPR, FPR
+
+try:
+    from typing import Sequence  # noqa
+    from cdsl.ast import Enumerator  # noqa
+    from cdsl.instructions import InstructionFormat  # noqa
+    from cdsl.isa import PredNode  # noqa
+except ImportError:
+    pass
+
+# The encbits of a recipe hold the opcode bits of the instructions it encodes.
+# The operand fields are filled in by the `put_*` functions of
+# `isa/arm64/binemit.rs`, so the layout of the encbits depends on the
+# instruction class. The functions below compute the encbits from an
+# instruction template with all its operand fields zero.
+
+
+def DP(inst):
+    # type: (int) -> int
+    """
+    Data processing on registers: `inst[31:21] | (inst[14:10] << 11)`.
+
+    This covers the integer and floating point instructions with up to two
+    register operands and a destination register. Bit 15 must be zero.
+    """
+    assert inst & 0x001f83ff == 0, hex(inst)
+    return (inst >> 21) | (((inst >> 10) & 0x1f) << 11)
+
+
+def FP1(inst):
+    # type: (int) -> int
+    """
+    Floating point data processing with one source: `inst[31:21] |
+    (inst[19:15] << 11)`.
+    """
+    assert (inst >> 10) & 0x1f == 0b10000, hex(inst)
+    assert inst & 0x001003ff == 0, hex(inst)
+    return (inst >> 21) | (((inst >> 15) & 0x1f) << 11)
+
+
+def EXT(inst, imms):
+    # type: (int, int) -> int
+    """
+    Bitfield move with a fixed `imms` field: `inst[31:22] | (imms << 10)`.
+    """
+    assert inst & 0x003fffff == 0, hex(inst)
+    assert imms <= 0b111111
+    return (inst >> 22) | (imms << 10)
+
+
+def hi(inst, low):
+    # type: (int, int) -> int
+    """
+    The high bits of `inst`, above the operand fields in `inst[low-1:0]`.
+    """
+    assert inst & ((1 << low) - 1) == 0, hex(inst)
+    bits = inst >> low
+    assert bits <= 0xffff
+    return bits
+
+
+def IMM(inst):
+    # type: (int) -> int
+    """
+    Instructions with an immediate or bitfield operand, and pairs of loads
+    and stores: `inst[31:22]`.
+    """
+    return hi(inst, 22)
+
+
+def MOVW(inst):
+    # type: (int) -> int
+    """
+    Move wide immediate: `inst[31:23]`.
+    """
+    return hi(inst, 23)
+
+
+def LDST(inst):
+    # type: (int) -> int
+    """
+    Loads and stores, from the template of the unscaled immediate offset
+    variant: `inst[31:21]`. The register offset and scaled unsigned offset
+    variants are derived from it.
+    """
+    return hi(inst, 21)
+
+
+def REG(inst):
+    # type: (int) -> int
+    """
+    Conversions between integer and floating point registers and branches to
+    a register: `inst[31:16]`.
+    """
+    return hi(inst, 16)
+
+
+def CB(inst):
+    # type: (int) -> int
+    """
+    Compare and branch: `inst[31:24]`.
+    """
+    return hi(inst, 24)
+
+
+def B(inst):
+    # type: (int) -> int
+    """
+    Unconditional branch to an immediate displacement: `inst[31:26]`.
+    """
+    return hi(inst, 26)
+
+
+def floatccs(iform, ccs):
+    # type: (InstructionFormat, Sequence[Enumerator]) -> PredNode
+    """
+    Return an instruction predicate that checks if `iform.cond` is one of the
+    floating point condition codes in `ccs`.
+    """
+    return Or(*(IsEqual(iform.cond, cc) for cc in ccs))
+
+
+# Integer instructions on registers.
+R = EncRecipe(
+        'R', Binary, size=4, ins=(GPR, GPR), outs=GPR,
+        emit='put_r(bits, in_reg0, in_reg1, out_reg0, sink);')
+
+# Integer instructions with a single register operand.
+Rr = EncRecipe(
+        'Rr', Unary, size=4, ins=GPR, outs=GPR,
+        emit='put_r(bits, in_reg0, 0, out_reg0, sink);')
+
+# Copy of a GPR is implemented as `orr rd, zr, rm`.
+Rmov = EncRecipe(
+        'Rmov', Unary, size=4, ins=GPR, outs=GPR,
+        emit='put_r(bits, 31, in_reg0, out_reg0, sink);')
+
+# Same for a GPR regmove.
+Rrmov = EncRecipe(
+        'Rrmov', RegMove, size=4, ins=GPR, outs=(),
+        emit='put_r(bits, 31, src, dst, sink);')
+
+# A null unary instruction that takes a GPR register. Can be used for no-op
+# conversions.
+null = EncRecipe('null', Unary, size=0, ins=GPR, outs=0, emit='')
+
+# Unsigned division, trapping on a zero divisor.
+Rdiv = EncRecipe(
+        'Rdiv', Binary, size=12, ins=(GPR, GPR), outs=GPR,
+        emit='''
+        put_trap_zero(bits, in_reg1, func.srclocs[inst], sink);
+        put_r(bits, in_reg0, in_reg1, out_reg0, sink);
+        ''')
+
+# Signed division, trapping on a zero divisor and on overflow.
+Rsdiv = EncRecipe(
+        'Rsdiv', Binary, size=28, ins=(GPR, GPR), outs=GPR,
+        emit='''
+        put_trap_zero(bits, in_reg1, func.srclocs[inst], sink);
+        put_trap_sdiv_overflow(
+            bits, in_reg0, in_reg1, func.srclocs[inst], sink);
+        put_r(bits, in_reg0, in_reg1, out_reg0, sink);
+        ''')
+
+# Remainder of a division, trapping on a zero divisor. The quotient is
+# computed in the scratch register `x16` and multiplied back with `msub`.
+Rrem = EncRecipe(
+        'Rrem', Binary, size=16, ins=(GPR, GPR), outs=GPR,
+        emit='''
+        put_trap_zero(bits, in_reg1, func.srclocs[inst], sink);
+        put_r(bits, in_reg0, in_reg1, 16, sink);
+        put_msub(bits, 16, in_reg1, in_reg0, out_reg0, sink);
+        ''')
+
+# High half of a 32-bit multiplication, computed as a 64-bit product with
+# `smull` or `umull` and shifted down.
+Rmulhi = EncRecipe(
+        'Rmulhi', Binary, size=8, ins=(GPR, GPR), outs=GPR,
+        emit='put_mulhi32(bits, in_reg0, in_reg1, out_reg0, sink);')
+
+# Add and subtract with a 12-bit unsigned immediate.
+Ii = EncRecipe(
+        'Ii', BinaryImm, size=4, ins=GPR, outs=GPR,
+        instp=IsUnsignedInt(BinaryImm.imm, 12),
+        emit='put_i(bits, in_reg0, imm.into(), out_reg0, sink);')
+
+# Left shift by an immediate amount, as an alias of `ubfm`.
+Rlsl = EncRecipe(
+        'Rlsl', BinaryImm, size=4, ins=GPR, outs=GPR,
+        emit='put_lsl(bits, in_reg0, imm.into(), out_reg0, sink);')
+
+# Right shifts by an immediate amount, as aliases of `ubfm` and `sbfm`.
+Rshr = EncRecipe(
+        'Rshr', BinaryImm, size=4, ins=GPR, outs=GPR,
+        emit='put_shr(bits, in_reg0, imm.into(), out_reg0, sink);')
+
+# Sign and zero extensions, as aliases of `sbfm` and `ubfm`.
+Rext = EncRecipe(
+        'Rext', Unary, size=4, ins=GPR, outs=GPR,
+        emit='put_ext(bits, in_reg0, out_reg0, sink);')
+
+# Move wide with zero or with not, for constants with 16 significant bits.
+Iz = EncRecipe(
+        'Iz', UnaryImm, size=4, ins=(), outs=GPR,
+        instp=IsSignedInt(UnaryImm.imm, 17),
+        emit='put_mov16(bits, imm.into(), out_reg0, sink);')
+
+# Any 32-bit constant, with a `movz` and a `movk`.
+Iconst32 = EncRecipe(
+        'Iconst32', UnaryImm, size=8, ins=(), outs=GPR,
+        emit='put_const(bits, imm.into(), 2, out_reg0, sink);')
+
+# Any 64-bit constant, with a `movz` and three `movk`.
+Iconst64 = EncRecipe(
+        'Iconst64', UnaryImm, size=16, ins=(), outs=GPR,
+        emit='put_const(bits, imm.into(), 4, out_reg0, sink);')
+
+# Boolean constants are materialized with `movz`.
+Izb = EncRecipe(
+        'Izb', UnaryBool, size=4, ins=(), outs=GPR,
+        emit='put_movw(bits, 0, if imm { 1 } else { 0 }, out_reg0, sink);')
+
+# Integer comparison: `cmp` followed by `cset`.
+Ricmp = EncRecipe(
+        'Ricmp', IntCompare, size=8, ins=(GPR, GPR), outs=GPR,
+        emit='''
+        put_r(bits, in_reg0, in_reg1, 31, sink);
+        put_cset(icc2cond(cond), out_reg0, sink);
+        ''')
+
+# Integer comparison with a 12-bit unsigned immediate.
+Iicmp = EncRecipe(
+        'Iicmp', IntCompareImm, size=8, ins=GPR, outs=GPR,
+        instp=IsUnsignedInt(IntCompareImm.imm, 12),
+        emit='''
+        put_i(bits, in_reg0, imm.into(), 31, sink);
+        put_cset(icc2cond(cond), out_reg0, sink);
+        ''')
+
+# Floating point comparison: `fcmp` followed by `cset`.
+Rfcmp = EncRecipe(
+        'Rfcmp', FloatCompare, size=8, ins=(FPR, FPR), outs=GPR,
+        instp=floatccs(FloatCompare, supported_floatccs),
+        emit='''
+        put_r(bits, in_reg0, in_reg1, 0, sink);
+        put_cset(fcc2cond(cond), out_reg0, sink);
+        ''')
+
+# Floating point comparisons which need two condition codes: `cset` for the
+# first one, then `csinc` sets the result if the second one holds.
+Rfcmp2 = EncRecipe(
+        'Rfcmp2', FloatCompare, size=12, ins=(FPR, FPR), outs=GPR,
+        instp=floatccs(FloatCompare, [floatcc.ueq, floatcc.one]),
+        emit='''
+        put_r(bits, in_reg0, in_reg1, 0, sink);
+        use ir::condcodes::FloatCC::*;
+        let (first, second) = match cond {
+            UnorderedOrEqual => (COND_EQ, COND_VS),
+            OrderedNotEqual => (COND_MI, COND_GT),
+            _ => panic!("{} not supported by Rfcmp2", cond),
+        };
+        put_cset(first, out_reg0, sink);
+        put_csinc(second ^ 1, out_reg0, 31, out_reg0, sink);
+        ''')
+
+# Floating point instructions on registers.
+Rf = EncRecipe(
+        'Rf', Binary, size=4, ins=(FPR, FPR), outs=FPR,
+        emit='put_r(bits, in_reg0, in_reg1, out_reg0, sink);')
+
+# Floating point instructions with a single register operand, including
+# copies.
+Rf1 = EncRecipe(
+        'Rf1', Unary, size=4, ins=FPR, outs=FPR,
+        emit='put_rf1(bits, in_reg0, out_reg0, sink);')
+
+# Same for an FPR regmove.
+Rfrmov = EncRecipe(
+        'Rfrmov', RegMove, size=4, ins=FPR, outs=(),
+        emit='put_rf1(bits, src, dst, sink);')
+
+# Conversion from a GPR to an FPR.
+Rcvt = EncRecipe(
+        'Rcvt', Unary, size=4, ins=GPR, outs=FPR,
+        emit='put_reg(bits, in_reg0, out_reg0, sink);')
+
+# Conversion from an FPR to a GPR.
+Rfcvt = EncRecipe(
+        'Rfcvt', Unary, size=4, ins=FPR, outs=GPR,
+        emit='put_reg(bits, in_reg0, out_reg0, sink);')
+
+# Loads with a signed 9-bit unscaled offset.
+LDur = EncRecipe(
+        'LDur', Load, size=4, ins=GPR, outs=GPR,
+        instp=IsSignedInt(Load.offset, 9),
+        emit='put_ldst(bits, out_reg0, in_reg0, offset.into(), sink);')
+
+fLDur = EncRecipe(
+        'fLDur', Load, size=4, ins=GPR, outs=FPR,
+        instp=IsSignedInt(Load.offset, 9),
+        emit='put_ldst(bits, out_reg0, in_reg0, offset.into(), sink);')
+
+# Loads with any offset, materialized in the scratch register `x16`.
+LDr = EncRecipe(
+        'LDr', Load, size=12, ins=GPR, outs=GPR,
+        emit='put_ldst_offset(bits, out_reg0, in_reg0, offset.into(), sink);')
+
+fLDr = EncRecipe(
+        'fLDr', Load, size=12, ins=GPR, outs=FPR,
+        emit='put_ldst_offset(bits, out_reg0, in_reg0, offset.into(), sink);')
+
+# Stores with a signed 9-bit unscaled offset.
+STur = EncRecipe(
+        'STur', Store, size=4, ins=(GPR, GPR), outs=(),
+        instp=IsSignedInt(Store.offset, 9),
+        emit='put_ldst(bits, in_reg0, in_reg1, offset.into(), sink);')
+
+fSTur = EncRecipe(
+        'fSTur', Store, size=4, ins=(FPR, GPR), outs=(),
+        instp=IsSignedInt(Store.offset, 9),
+        emit='put_ldst(bits, in_reg0, in_reg1, offset.into(), sink);')
+
+# Stores with any offset, materialized in the scratch register `x16`.
+STr = EncRecipe(
+        'STr', Store, size=12, ins=(GPR, GPR), outs=(),
+        emit='put_ldst_offset(bits, in_reg0, in_reg1, offset.into(), sink);')
+
+fSTr = EncRecipe(
+        'fSTr', Store, size=12, ins=(FPR, GPR), outs=(),
+        emit='put_ldst_offset(bits, in_reg0, in_reg1, offset.into(), sink);')
+
+# Address of a stack slot, computed from the stack pointer.
+Iaddr = EncRecipe(
+        'Iaddr', StackLoad, size=8, ins=(), outs=GPR,
+        emit='''
+        let sp = StackRef::sp(stack_slot, &func.stack_slots);
+        let offset: i32 = offset.into();
+        let offset = i64::from(sp.offset + offset);
+        put_i(bits | 1, 31, offset >> 12, out_reg0, sink);
+        put_i(bits, out_reg0, offset & 0xfff, out_reg0, sink);
+        ''')
+
+# Spill of a GPR, relative to the stack pointer.
+GPsp = EncRecipe(
+        'GPsp', Unary, size=4, ins=GPR, outs=Stack(GPR),
+        emit='''
+        sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+        put_ldst_sp(bits, in_reg0, out_stk0.offset, sink);
+        ''')
+
+# Fill of a GPR.
+GPfi = EncRecipe(
+        'GPfi', Unary, size=4, ins=Stack(GPR), outs=GPR,
+        emit='put_ldst_sp(bits, out_reg0, in_stk0.offset, sink);')
+
+# Spill and fill of an FPR.
+FPsp = EncRecipe(
+        'FPsp', Unary, size=4, ins=FPR, outs=Stack(FPR),
+        emit='''
+        sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+        put_ldst_sp(bits, in_reg0, out_stk0.offset, sink);
+        ''')
+
+FPfi = EncRecipe(
+        'FPfi', Unary, size=4, ins=Stack(FPR), outs=FPR,
+        emit='put_ldst_sp(bits, out_reg0, in_stk0.offset, sink);')
+
+# Regspill and regfill of a GPR.
+GPrsp = EncRecipe(
+        'GPrsp', RegSpill, size=4, ins=GPR, outs=(),
+        emit='''
+        sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+        let dst = StackRef::sp(dst, &func.stack_slots);
+        put_ldst_sp(bits, src, dst.offset, sink);
+        ''')
+
+GPrfi = EncRecipe(
+        'GPrfi', RegFill, size=4, ins=Stack(GPR), outs=(),
+        emit='''
+        let src = StackRef::sp(src, &func.stack_slots);
+        put_ldst_sp(bits, dst, src.offset, sink);
+        ''')
+
+# Regspill and regfill of an FPR.
+FPrsp = EncRecipe(
+        'FPrsp', RegSpill, size=4, ins=FPR, outs=(),
+        emit='''
+        sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+        let dst = StackRef::sp(dst, &func.stack_slots);
+        put_ldst_sp(bits, src, dst.offset, sink);
+        ''')
+
+FPrfi = EncRecipe(
+        'FPrfi', RegFill, size=4, ins=Stack(FPR), outs=(),
+        emit='''
+        let src = StackRef::sp(src, &func.stack_slots);
+        put_ldst_sp(bits, dst, src.offset, sink);
+        ''')
+
+# Push and pop a pair of registers with `stp` and `ldp`, pre-decrementing and
+# post-incrementing the stack pointer.
+Pushp = EncRecipe(
+        'Pushp', Binary, size=4, ins=(GPR, GPR), outs=(),
+        emit='''
+        sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+        put_pair(bits, -16, in_reg0, in_reg1, sink);
+        ''')
+
+Popp = EncRecipe(
+        'Popp', NullAry, size=4, ins=(), outs=(GPR, GPR),
+        emit='put_pair(bits, 16, out_reg0, out_reg1, sink);')
+
+fPushp = EncRecipe(
+        'fPushp', Binary, size=4, ins=(FPR, FPR), outs=(),
+        emit='''
+        sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+        put_pair(bits, -16, in_reg0, in_reg1, sink);
+        ''')
+
+fPopp = EncRecipe(
+        'fPopp', NullAry, size=4, ins=(), outs=(FPR, FPR),
+        emit='put_pair(bits, 16, out_reg0, out_reg1, sink);')
+
+# Copy of the stack pointer, implemented as `add rd, rn, #0`.
+Icopysp = EncRecipe(
+        'Icopysp', CopySpecial, size=4, ins=(), outs=(),
+        emit='put_i(bits, src, 0, dst, sink);')
+
+# Adjustments of the stack pointer by a 12-bit immediate.
+Iadjsp = EncRecipe(
+        'Iadjsp', UnaryImm, size=4, ins=(), outs=(),
+        instp=IsUnsignedInt(UnaryImm.imm, 12),
+        emit='put_i(bits, 31, imm.into(), 31, sink);')
+
+# Adjustments of the stack pointer by a 24-bit immediate, in two steps.
+Iadjsp24 = EncRecipe(
+        'Iadjsp24', UnaryImm, size=8, ins=(), outs=(),
+        instp=IsUnsignedInt(UnaryImm.imm, 24),
+        emit='''
+        let imm: i64 = imm.into();
+        put_i(bits | 1, 31, imm >> 12, 31, sink);
+        put_i(bits, 31, imm & 0xfff, 31, sink);
+        ''')
+
+# Return is a `ret` to the link register. The return address is provided by
+# the special-purpose `link` argument added by the prologue.
+# The variable return values are not encoded.
+Iret = EncRecipe(
+        'Iret', MultiAry, size=4, ins=(), outs=(),
+        emit='put_br(bits, 30, sink);')
+
+# Indirect call with `blr`.
+Icall = EncRecipe(
+        'Icall', CallIndirect, size=4, ins=GPR, outs=(),
+        emit='put_br(bits, in_reg0, sink);')
+
+# Unconditional branch.
+UJ = EncRecipe(
+        'UJ', Jump, size=4, ins=(), outs=(), branch_range=(0, 28),
+        emit='''
+        let dest = i64::from(func.offsets[destination]);
+        let disp = dest - i64::from(sink.offset());
+        put_b(bits, disp, sink);
+        ''')
+
+# Direct call with `bl`, relocated.
+UJcall = EncRecipe(
+        'UJcall', Call, size=4, ins=(), outs=(),
+        emit='''
+        sink.reloc_external(Reloc::Arm64Call,
+                            &func.dfg.ext_funcs[func_ref].name,
+                            0);
+        put_b(bits, 0, sink);
+        ''')
+
+# Compare with zero and branch.
+CBz = EncRecipe(
+        'CBz', Branch, size=4, ins=GPR, outs=(),
+        branch_range=(0, 21),
+        emit='''
+        let dest = i64::from(func.offsets[destination]);
+        let disp = dest - i64::from(sink.offset());
+        put_cb(bits, disp, in_reg0, sink);
+        ''')
+
+# Integer comparison and conditional branch: `cmp` followed by `b.cond`.
+SB = EncRecipe(
+        'SB', BranchIcmp, size=8, ins=(GPR, GPR), outs=(),
+        branch_range=(4, 21),
+        emit='''
+        put_r(bits, in_reg0, in_reg1, 31, sink);
+        let dest = i64::from(func.offsets[destination]);
+        let disp = dest - i64::from(sink.offset());
+        put_bcond(icc2cond(cond), disp, sink);
+        ''')
+
+# Traps are the permanently undefined instruction `udf`.
+Iudf = EncRecipe(
+        'Iudf', Trap, size=4, ins=(), outs=(),
+        emit='''
+        sink.trap(code, func.srclocs[inst]);
+        put_udf(sink);
+        ''')
@@ -3,30 +3,39 @@ RISC-V Encodings.
 """
 from __future__ import absolute_import
 from base import instructions as base
-from base.immediates import intcc
+from base import types
+from base.immediates import intcc, floatcc
 from .defs import RV32, RV64
 from .recipes import OPIMM, OPIMM32, OP, OP32, LUI, BRANCH, JALR, JAL
-from .recipes import LOAD, STORE
+from .recipes import LOAD, STORE, LOAD_FP, STORE_FP, OP_FP, MADD
 from .recipes import R, Rshamt, Ricmp, Ii, Iz, Iicmp, Iret, Icall, Icopy
+from .recipes import Ibnot
 from .recipes import U, UJ, UJcall, SB, SBzero, GPsp, GPfi, Irmov
-from .settings import use_m
+from .recipes import Rf, Rfunary, Rfsgn, Rfrmov, Rfcmp, Rfcmpswap, Rgf, Rfg
+from .recipes import Rfgtrap
+from .recipes import R4, Ifload, Sfstore, FPsp, FPfi, Iload, Sstore
+from .recipes import CRmov, CRrmov, CRadd, CRret, CRcall, CIaddi, CIli
+from .recipes import CIshamt, CA, CBshamt, CBandi, CLw, CSw, CLd, CSd, CJ
+from .recipes import CBzero
+from .settings import use_m, use_f, use_d, supports_c
 from cdsl.ast import Var
+from .legalize import riscv_expand
 from base.legalize import narrow, expand
 
 RV32.legalize_monomorphic(expand)
 RV32.legalize_type(
         default=narrow,
         i32=expand,
-        f32=expand,
-        f64=expand)
+        f32=riscv_expand,
+        f64=riscv_expand)
 
 RV64.legalize_monomorphic(expand)
 RV64.legalize_type(
         default=narrow,
         i32=expand,
         i64=expand,
-        f32=expand,
-        f64=expand)
+        f32=riscv_expand,
+        f64=riscv_expand)
 
 # Dummies for instruction predicates.
 x = Var('x')
@@ -50,6 +59,17 @@ for inst,           inst_imm,      f3,    f7 in [
         RV32.enc(inst_imm.i32, Ii, OPIMM(f3))
         RV64.enc(inst_imm.i64, Ii, OPIMM(f3))
 
+# Also add `b1` encodings for the logic instructions.
+for inst,           f3 in [
+        (base.bxor, 0b100),
+        (base.bor,  0b110),
+        (base.band, 0b111)
+        ]:
+    RV32.enc(inst.b1, R, OP(f3, 0b0000000))
+    RV64.enc(inst.b1, R, OP(f3, 0b0000000))
+RV32.enc(base.bnot.b1, Ibnot, OPIMM(0b100))
+RV64.enc(base.bnot.b1, Ibnot, OPIMM(0b100))
+
 # 32-bit ops in RV64.
 RV64.enc(base.iadd.i32, R, OP32(0b000, 0b0000000))
 RV64.enc(base.isub.i32, R, OP32(0b000, 0b0100000))
@@ -102,6 +122,141 @@ RV32.enc(base.imul.i32, R, OP(0b000, 0b0000001), isap=use_m)
 RV64.enc(base.imul.i64, R, OP(0b000, 0b0000001), isap=use_m)
 RV64.enc(base.imul.i32, R, OP32(0b000, 0b0000001), isap=use_m)
 
+# Integer loads and stores.
+RV32.enc(base.load.i32.any, Iload, LOAD(0b010))
+RV64.enc(base.load.i32.any, Iload, LOAD(0b010))
+RV64.enc(base.load.i64.any, Iload, LOAD(0b011))
+RV32.enc(base.store.i32.any, Sstore, STORE(0b010))
+RV64.enc(base.store.i32.any, Sstore, STORE(0b010))
+RV64.enc(base.store.i64.any, Sstore, STORE(0b011))
+
+# "F" and "D" Standard Extensions for single and double precision floating
+# point. Gated by the `use_f` and `use_d` flags. Rounding instructions use
+# the round to nearest, ties to even mode.
+for inst,           f5 in [
+        (base.fadd, 0b00000),
+        (base.fsub, 0b00001),
+        (base.fmul, 0b00010),
+        (base.fdiv, 0b00011)
+        ]:
+    RV32.enc(inst.f32, Rf, OP_FP(0b000, f5, 0b00), isap=use_f)
+    RV64.enc(inst.f32, Rf, OP_FP(0b000, f5, 0b00), isap=use_f)
+    RV32.enc(inst.f64, Rf, OP_FP(0b000, f5, 0b01), isap=use_d)
+    RV64.enc(inst.f64, Rf, OP_FP(0b000, f5, 0b01), isap=use_d)
+
+RV32.enc(base.sqrt.f32, Rfunary, OP_FP(0b000, 0b01011, 0b00), isap=use_f)
+RV64.enc(base.sqrt.f32, Rfunary, OP_FP(0b000, 0b01011, 0b00), isap=use_f)
+RV32.enc(base.sqrt.f64, Rfunary, OP_FP(0b000, 0b01011, 0b01), isap=use_d)
+RV64.enc(base.sqrt.f64, Rfunary, OP_FP(0b000, 0b01011, 0b01), isap=use_d)
+
+RV32.enc(base.fma.f32, R4, MADD(0b000, 0b00), isap=use_f)
+RV64.enc(base.fma.f32, R4, MADD(0b000, 0b00), isap=use_f)
+RV32.enc(base.fma.f64, R4, MADD(0b000, 0b01), isap=use_d)
+RV64.enc(base.fma.f64, R4, MADD(0b000, 0b01), isap=use_d)
+
+# Sign injection. Injecting the sign of a register into itself implements
+# copies, fneg and fabs.
+RV32.enc(base.fcopysign.f32, Rf, OP_FP(0b000, 0b00100, 0b00), isap=use_f)
+RV64.enc(base.fcopysign.f32, Rf, OP_FP(0b000, 0b00100, 0b00), isap=use_f)
+RV32.enc(base.fcopysign.f64, Rf, OP_FP(0b000, 0b00100, 0b01), isap=use_d)
+RV64.enc(base.fcopysign.f64, Rf, OP_FP(0b000, 0b00100, 0b01), isap=use_d)
+
+for inst,           f3 in [
+        (base.copy, 0b000),
+        (base.fneg, 0b001),
+        (base.fabs, 0b010)
+        ]:
+    RV32.enc(inst.f32, Rfsgn, OP_FP(f3, 0b00100, 0b00), isap=use_f)
+    RV64.enc(inst.f32, Rfsgn, OP_FP(f3, 0b00100, 0b00), isap=use_f)
+    RV32.enc(inst.f64, Rfsgn, OP_FP(f3, 0b00100, 0b01), isap=use_d)
+    RV64.enc(inst.f64, Rfsgn, OP_FP(f3, 0b00100, 0b01), isap=use_d)
+
+RV32.enc(base.regmove.f32, Rfrmov, OP_FP(0b000, 0b00100, 0b00), isap=use_f)
+RV64.enc(base.regmove.f32, Rfrmov, OP_FP(0b000, 0b00100, 0b00), isap=use_f)
+RV32.enc(base.regmove.f64, Rfrmov, OP_FP(0b000, 0b00100, 0b01), isap=use_d)
+RV64.enc(base.regmove.f64, Rfrmov, OP_FP(0b000, 0b00100, 0b01), isap=use_d)
+
+# Ordered comparisons. `gt` and `ge` swap the operands of `flt` and `fle`.
+for cond,             recipe,    f3 in [
+        (floatcc.eq,  Rfcmp,     0b010),
+        (floatcc.lt,  Rfcmp,     0b001),
+        (floatcc.le,  Rfcmp,     0b000),
+        (floatcc.gt,  Rfcmpswap, 0b001),
+        (floatcc.ge,  Rfcmpswap, 0b000)
+        ]:
+    RV32.enc(
+            base.fcmp.f32(cond, x, y), recipe, OP_FP(f3, 0b10100, 0b00),
+            isap=use_f)
+    RV64.enc(
+            base.fcmp.f32(cond, x, y), recipe, OP_FP(f3, 0b10100, 0b00),
+            isap=use_f)
+    RV32.enc(
+            base.fcmp.f64(cond, x, y), recipe, OP_FP(f3, 0b10100, 0b01),
+            isap=use_d)
+    RV64.enc(
+            base.fcmp.f64(cond, x, y), recipe, OP_FP(f3, 0b10100, 0b01),
+            isap=use_d)
+
+# Conversions between single and double precision.
+RV32.enc(
+        base.fpromote.f64.f32, Rfunary, OP_FP(0b000, 0b01000, 0b01, 0),
+        isap=use_d)
+RV64.enc(
+        base.fpromote.f64.f32, Rfunary, OP_FP(0b000, 0b01000, 0b01, 0),
+        isap=use_d)
+RV32.enc(
+        base.fdemote.f32.f64, Rfunary, OP_FP(0b000, 0b01000, 0b00, 1),
+        isap=use_d)
+RV64.enc(
+        base.fdemote.f32.f64, Rfunary, OP_FP(0b000, 0b01000, 0b00, 1),
+        isap=use_d)
+
+# Integer to floating point conversions. The rs2 field selects the signedness
+# and width of the integer operand.
+for inst,                 rs2w, rs2l in [
+        (base.fcvt_from_sint, 0,    2),
+        (base.fcvt_from_uint, 1,    3)
+        ]:
+    RV32.enc(inst.f32.i32, Rgf, OP_FP(0b000, 0b11010, 0b00, rs2w), isap=use_f)
+    RV64.enc(inst.f32.i32, Rgf, OP_FP(0b000, 0b11010, 0b00, rs2w), isap=use_f)
+    RV64.enc(inst.f32.i64, Rgf, OP_FP(0b000, 0b11010, 0b00, rs2l), isap=use_f)
+    RV32.enc(inst.f64.i32, Rgf, OP_FP(0b000, 0b11010, 0b01, rs2w), isap=use_d)
+    RV64.enc(inst.f64.i32, Rgf, OP_FP(0b000, 0b11010, 0b01, rs2w), isap=use_d)
+    RV64.enc(inst.f64.i64, Rgf, OP_FP(0b000, 0b11010, 0b01, rs2l), isap=use_d)
+
+# Floating point to integer conversions round toward zero, and trap on NaN and
+# on values out of range. The rs2 field selects the signedness and width of the
+# integer result.
+for inst,               rs2w, rs2l in [
+        (base.fcvt_to_sint, 0,    2),
+        (base.fcvt_to_uint, 1,    3)
+        ]:
+    for ty, fmt, isap in [(types.f32, 0b00, use_f), (types.f64, 0b01, use_d)]:
+        bits_w = OP_FP(0b001, 0b11000, fmt, rs2w)
+        bits_l = OP_FP(0b001, 0b11000, fmt, rs2l)
+        RV32.enc(inst.i32.bind(ty), Rfgtrap, bits_w, isap=isap)
+        RV64.enc(inst.i32.bind(ty), Rfgtrap, bits_w, isap=isap)
+        RV64.enc(inst.i64.bind(ty), Rfgtrap, bits_l, isap=isap)
+
+# Moves between integer and floating point registers. Only RV64 can move a
+# double precision value.
+RV32.enc(base.bitcast.f32.i32, Rgf, OP_FP(0b000, 0b11110, 0b00), isap=use_f)
+RV64.enc(base.bitcast.f32.i32, Rgf, OP_FP(0b000, 0b11110, 0b00), isap=use_f)
+RV64.enc(base.bitcast.f64.i64, Rgf, OP_FP(0b000, 0b11110, 0b01), isap=use_d)
+RV32.enc(base.bitcast.i32.f32, Rfg, OP_FP(0b000, 0b11100, 0b00), isap=use_f)
+RV64.enc(base.bitcast.i32.f32, Rfg, OP_FP(0b000, 0b11100, 0b00), isap=use_f)
+RV64.enc(base.bitcast.i64.f64, Rfg, OP_FP(0b000, 0b11100, 0b01), isap=use_d)
+
+# Floating point loads and stores.
+RV32.enc(base.load.f32.any, Ifload, LOAD_FP(0b010), isap=use_f)
+RV64.enc(base.load.f32.any, Ifload, LOAD_FP(0b010), isap=use_f)
+RV32.enc(base.load.f64.any, Ifload, LOAD_FP(0b011), isap=use_d)
+RV64.enc(base.load.f64.any, Ifload, LOAD_FP(0b011), isap=use_d)
+RV32.enc(base.store.f32.any, Sfstore, STORE_FP(0b010), isap=use_f)
+RV64.enc(base.store.f32.any, Sfstore, STORE_FP(0b010), isap=use_f)
+RV32.enc(base.store.f64.any, Sfstore, STORE_FP(0b011), isap=use_d)
+RV64.enc(base.store.f64.any, Sfstore, STORE_FP(0b011), isap=use_d)
+
 # Control flow.
 
 # Unconditional branches.
@@ -146,6 +301,14 @@ RV64.enc(base.spill.i64, GPsp, STORE(0b011))
 RV32.enc(base.fill.i32, GPfi, LOAD(0b010))
 RV64.enc(base.fill.i32, GPfi, LOAD(0b010))
 RV64.enc(base.fill.i64, GPfi, LOAD(0b011))
+RV32.enc(base.spill.f32, FPsp, STORE_FP(0b010), isap=use_f)
+RV64.enc(base.spill.f32, FPsp, STORE_FP(0b010), isap=use_f)
+RV32.enc(base.spill.f64, FPsp, STORE_FP(0b011), isap=use_d)
+RV64.enc(base.spill.f64, FPsp, STORE_FP(0b011), isap=use_d)
+RV32.enc(base.fill.f32, FPfi, LOAD_FP(0b010), isap=use_f)
+RV64.enc(base.fill.f32, FPfi, LOAD_FP(0b010), isap=use_f)
+RV32.enc(base.fill.f64, FPfi, LOAD_FP(0b011), isap=use_d)
+RV64.enc(base.fill.f64, FPfi, LOAD_FP(0b011), isap=use_d)
 
 # Register copies.
 RV32.enc(base.copy.i32, Icopy, OPIMM(0b000))
@@ -160,3 +323,83 @@ RV32.enc(base.copy.b1, Icopy, OPIMM(0b000))
 RV64.enc(base.copy.b1, Icopy, OPIMM(0b000))
 RV32.enc(base.regmove.b1, Irmov, OPIMM(0b000))
 RV64.enc(base.regmove.b1, Irmov, OPIMM(0b000))
+
+# Narrow integers live in whole registers, so the register allocator copies
+# and moves them like the wider ones.
+for ty in [types.i8, types.i16]:
+    RV32.enc(base.copy.bind(ty), Icopy, OPIMM(0b000))
+    RV64.enc(base.copy.bind(ty), Icopy, OPIMM(0b000))
+    RV32.enc(base.regmove.bind(ty), Irmov, OPIMM(0b000))
+    RV64.enc(base.regmove.bind(ty), Irmov, OPIMM(0b000))
+
+# "C" Standard Extension for Compressed Instructions. Gated by the `supports_c`
+# flag. The 16-bit encodings come after the 32-bit ones, so the register
+# allocator works with the full register classes. The shrink_instructions pass
+# then picks them when the operands allow it.
+RV32.enc(base.copy.i32, CRmov, 0x8002, isap=supports_c)
+RV64.enc(base.copy.i64, CRmov, 0x8002, isap=supports_c)
+RV64.enc(base.copy.i32, CRmov, 0x8002, isap=supports_c)
+RV32.enc(base.copy.b1, CRmov, 0x8002, isap=supports_c)
+RV64.enc(base.copy.b1, CRmov, 0x8002, isap=supports_c)
+RV32.enc(base.regmove.i32, CRrmov, 0x8002, isap=supports_c)
+RV64.enc(base.regmove.i64, CRrmov, 0x8002, isap=supports_c)
+RV64.enc(base.regmove.i32, CRrmov, 0x8002, isap=supports_c)
+RV32.enc(base.regmove.b1, CRrmov, 0x8002, isap=supports_c)
+RV64.enc(base.regmove.b1, CRrmov, 0x8002, isap=supports_c)
+
+RV32.enc(base.iadd.i32, CRadd, 0x9002, isap=supports_c)
+RV64.enc(base.iadd.i64, CRadd, 0x9002, isap=supports_c)
+RV64.enc(base.iadd.i32, CA, 0x9c21, isap=supports_c)
+RV64.enc(base.isub.i32, CA, 0x9c01, isap=supports_c)
+
+for inst,           bits in [
+        (base.isub, 0x8c01),
+        (base.bxor, 0x8c21),
+        (base.bor,  0x8c41),
+        (base.band, 0x8c61)
+        ]:
+    RV32.enc(inst.i32, CA, bits, isap=supports_c)
+    RV64.enc(inst.i64, CA, bits, isap=supports_c)
+
+RV32.enc(base.iadd_imm.i32, CIaddi, 0x0001, isap=supports_c)
+RV64.enc(base.iadd_imm.i64, CIaddi, 0x0001, isap=supports_c)
+RV64.enc(base.iadd_imm.i32, CIaddi, 0x2001, isap=supports_c)
+RV32.enc(base.band_imm.i32, CBandi, 0x8801, isap=supports_c)
+RV64.enc(base.band_imm.i64, CBandi, 0x8801, isap=supports_c)
+
+RV32.enc(base.iconst.i32, CIli, 0x4001, isap=supports_c)
+RV64.enc(base.iconst.i32, CIli, 0x4001, isap=supports_c)
+RV64.enc(base.iconst.i64, CIli, 0x4001, isap=supports_c)
+
+RV32.enc(base.ishl_imm.i32, CIshamt, 0x0002, isap=supports_c)
+RV64.enc(base.ishl_imm.i64, CIshamt, 0x0002, isap=supports_c)
+for inst,               bits in [
+        (base.ushr_imm, 0x8001),
+        (base.sshr_imm, 0x8401)
+        ]:
+    RV32.enc(inst.i32, CBshamt, bits, isap=supports_c)
+    RV64.enc(inst.i64, CBshamt, bits, isap=supports_c)
+
+RV32.enc(base.load.i32.any, CLw, 0x4000, isap=supports_c)
+RV64.enc(base.load.i32.any, CLw, 0x4000, isap=supports_c)
+RV64.enc(base.load.i64.any, CLd, 0x6000, isap=supports_c)
+RV32.enc(base.store.i32.any, CSw, 0xc000, isap=supports_c)
+RV64.enc(base.store.i32.any, CSw, 0xc000, isap=supports_c)
+RV64.enc(base.store.i64.any, CSd, 0xe000, isap=supports_c)
+
+RV32.enc(base.jump, CJ, 0xa001, isap=supports_c)
+RV64.enc(base.jump, CJ, 0xa001, isap=supports_c)
+
+for inst,           bits in [
+        (base.brz,  0xc001),
+        (base.brnz, 0xe001)
+        ]:
+    RV32.enc(inst.i32, CBzero, bits, isap=supports_c)
+    RV64.enc(inst.i64, CBzero, bits, isap=supports_c)
+    RV32.enc(inst.b1, CBzero, bits, isap=supports_c)
+    RV64.enc(inst.b1, CBzero, bits, isap=supports_c)
+
+RV32.enc(base.x_return, CRret, 0x8002, isap=supports_c)
+RV64.enc(base.x_return, CRret, 0x8002, isap=supports_c)
+RV32.enc(base.call_indirect.i32, CRcall, 0x9002, isap=supports_c)
+RV64.enc(base.call_indirect.i64, CRcall, 0x9002, isap=supports_c)
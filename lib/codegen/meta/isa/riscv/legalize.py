@@ -0,0 +1,61 @@
+"""
+Custom legalization patterns for RISC-V.
+"""
+from __future__ import absolute_import
+from cdsl.ast import Var
+from cdsl.xform import Rtl, XFormGroup
+from base.immediates import floatcc
+from base import legalize as shared
+from base import instructions as insts
+from .defs import ISA
+
+riscv_expand = XFormGroup(
+        'riscv_expand',
+        """
+        Legalize instructions by expansion.
+
+        Use RISC-V specific patterns if needed.
+        """,
+        isa=ISA, chain=shared.expand)
+
+a = Var('a')
+x = Var('x')
+y = Var('y')
+a1 = Var('a1')
+a2 = Var('a2')
+
+# Floating point condition codes.
+#
+# The `feq`, `flt` and `fle` instructions only implement the ordered `eq`,
+# `lt` and `le` conditions, and swapping their operands gives `gt` and `ge`.
+# The remaining codes are built from these.
+riscv_expand.legalize(
+        a << insts.fcmp(floatcc.ord, x, y),
+        Rtl(
+            a1 << insts.fcmp(floatcc.eq, x, x),
+            a2 << insts.fcmp(floatcc.eq, y, y),
+            a << insts.band(a1, a2)
+        ))
+riscv_expand.legalize(
+        a << insts.fcmp(floatcc.one, x, y),
+        Rtl(
+            a1 << insts.fcmp(floatcc.lt, x, y),
+            a2 << insts.fcmp(floatcc.gt, x, y),
+            a << insts.bor(a1, a2)
+        ))
+
+# The unordered conditions are the negations of the ordered ones.
+for cc,               neg_cc in [
+        (floatcc.uno, floatcc.ord),
+        (floatcc.ne,  floatcc.eq),
+        (floatcc.ueq, floatcc.one),
+        (floatcc.ult, floatcc.ge),
+        (floatcc.ule, floatcc.gt),
+        (floatcc.ugt, floatcc.le),
+        (floatcc.uge, floatcc.lt)]:
+    riscv_expand.legalize(
+            a << insts.fcmp(cc, x, y),
+            Rtl(
+                a1 << insts.fcmp(neg_cc, x, y),
+                a << insts.bnot(a1)
+            ))
@@ -10,12 +10,13 @@ instruction formats described in the reference:
 """
 from __future__ import absolute_import
 from cdsl.isa import EncRecipe
-from cdsl.predicates import IsSignedInt
+from cdsl.predicates import IsSignedInt, IsUnsignedInt
 from cdsl.registers import Stack
 from base.formats import Binary, BinaryImm, MultiAry, IntCompare, IntCompareImm
 from base.formats import Unary, UnaryImm, BranchIcmp, Branch, Jump
 from base.formats import Call, CallIndirect, RegMove
-from .registers import GPR
+from base.formats import FloatCompare, Ternary, Load, Store
+from .registers import GPR, FPR, GPR8
 
 # The low 7 bits of a RISC-V instruction is the base opcode. All 32-bit
 # instructions have 11 as the two low bits, with bits 6:2 determining the base
@@ -80,6 +81,36 @@ def OP32(funct3, funct7):
     return 0b01110 | (funct3 << 5) | (funct7 << 8)
 
 
+def LOAD_FP(funct3):
+    # type: (int) -> int
+    assert funct3 <= 0b111
+    return 0b00001 | (funct3 << 5)
+
+
+def STORE_FP(funct3):
+    # type: (int) -> int
+    assert funct3 <= 0b111
+    return 0b01001 | (funct3 << 5)
+
+
+def MADD(funct3, fmt):
+    # type: (int, int) -> int
+    assert funct3 <= 0b111
+    assert fmt <= 0b11
+    return 0b10000 | (funct3 << 5) | (fmt << 8)
+
+
+def OP_FP(funct3, funct5, fmt, rs2=0):
+    # type: (int, int, int, int) -> int
+    # The OP-FP opcode is implied by the recipes, which leaves room for the
+    # fixed rs2 field of the unary operations.
+    assert funct3 <= 0b111
+    assert funct5 <= 0b11111
+    assert fmt <= 0b11
+    assert rs2 <= 0b11111
+    return funct3 | (fmt << 3) | (funct5 << 5) | (rs2 << 10)
+
+
 def AIUPC():
     # type: () -> int
     return 0b00101
@@ -111,6 +142,11 @@ Ii = EncRecipe(
         instp=IsSignedInt(BinaryImm.imm, 12),
         emit='put_i(bits, in_reg0, imm.into(), out_reg0, sink);')
 
+# Negation of a `b1` with an I-type `xori rd, rs1, 1`.
+Ibnot = EncRecipe(
+        'Ibnot', Unary, size=4, ins=GPR, outs=GPR,
+        emit='put_i(bits, in_reg0, 1, out_reg0, sink);')
+
 # I-type instruction with a hardcoded %x0 rs1.
 Iz = EncRecipe(
         'Iz', UnaryImm, size=4, ins=(), outs=GPR,
@@ -212,14 +248,212 @@ SBzero = EncRecipe(
         put_sb(bits, disp, in_reg0, 0, sink);
         ''')
 
-# Spill of a GPR.
+# I-type load of a GPR.
+Iload = EncRecipe(
+        'Iload', Load, size=4, ins=GPR, outs=GPR,
+        instp=IsSignedInt(Load.offset, 12),
+        emit='put_i(bits, in_reg0, offset.into(), out_reg0, sink);')
+
+# S-type store of a GPR.
+Sstore = EncRecipe(
+        'Sstore', Store, size=4, ins=(GPR, GPR), outs=(),
+        instp=IsSignedInt(Store.offset, 12),
+        emit='put_s(bits, in_reg1, offset.into(), in_reg0, sink);')
+
+# Spill of a GPR, stored relative to the stack pointer.
 GPsp = EncRecipe(
         'GPsp', Unary, size=4,
         ins=GPR, outs=Stack(GPR),
-        emit='unimplemented!();')
+        emit='put_s_sp(bits, out_stk0.offset, in_reg0, sink);')
 
-# Fill of a GPR.
+# Fill of a GPR, loaded relative to the stack pointer.
 GPfi = EncRecipe(
         'GPfi', Unary, size=4,
         ins=Stack(GPR), outs=GPR,
-        emit='unimplemented!();')
+        emit='put_i_sp(bits, in_stk0.offset, out_reg0, sink);')
+
+# R-type floating point instructions. The encbits are
+# `funct3 | (funct7 << 3) | (rs2 << 10)`, the OP-FP opcode being implied.
+Rf = EncRecipe(
+        'Rf', Binary, size=4, ins=(FPR, FPR), outs=FPR,
+        emit='put_opfp(bits, in_reg0, in_reg1, out_reg0, sink);')
+
+# Unary floating point instruction with the rs2 field taken from the encbits.
+Rfunary = EncRecipe(
+        'Rfunary', Unary, size=4, ins=FPR, outs=FPR,
+        emit='put_opfp(bits, in_reg0, 0, out_reg0, sink);')
+
+# Sign injection with rs1 = rs2 implements copies, fneg and fabs.
+Rfsgn = EncRecipe(
+        'Rfsgn', Unary, size=4, ins=FPR, outs=FPR,
+        emit='put_opfp(bits, in_reg0, in_reg0, out_reg0, sink);')
+
+# Same for an FPR regmove.
+Rfrmov = EncRecipe(
+        'Rfrmov', RegMove, size=4, ins=FPR, outs=(),
+        emit='put_opfp(bits, src, src, dst, sink);')
+
+# Floating point comparison writing 0 or 1 to a GPR.
+Rfcmp = EncRecipe(
+        'Rfcmp', FloatCompare, size=4, ins=(FPR, FPR), outs=GPR,
+        emit='put_opfp(bits, in_reg0, in_reg1, out_reg0, sink);')
+
+# Same with the operands swapped, so `gt` and `ge` can use `flt` and `fle`.
+Rfcmpswap = EncRecipe(
+        'Rfcmpswap', FloatCompare, size=4, ins=(FPR, FPR), outs=GPR,
+        emit='put_opfp(bits, in_reg1, in_reg0, out_reg0, sink);')
+
+# Conversion or bit move from a GPR to an FPR.
+Rgf = EncRecipe(
+        'Rgf', Unary, size=4, ins=GPR, outs=FPR,
+        emit='put_opfp(bits, in_reg0, 0, out_reg0, sink);')
+
+# Bit move from an FPR to a GPR.
+Rfg = EncRecipe(
+        'Rfg', Unary, size=4, ins=FPR, outs=GPR,
+        emit='put_opfp(bits, in_reg0, 0, out_reg0, sink);')
+
+# Conversion from an FPR to a GPR, trapping on NaN and on values out of range.
+Rfgtrap = EncRecipe(
+        'Rfgtrap', Unary, size=40, ins=FPR, outs=GPR,
+        emit='''
+        put_fcvt_trap(bits, in_reg0, out_reg0, func.srclocs[inst], sink);
+        ''')
+
+# R4-type fused multiply-add. The encbits are
+# `opcode[6:2] | (funct3 << 5) | (fmt << 8)`.
+R4 = EncRecipe(
+        'R4', Ternary, size=4, ins=(FPR, FPR, FPR), outs=FPR,
+        emit='put_r4(bits, in_reg0, in_reg1, in_reg2, out_reg0, sink);')
+
+# I-type load of an FPR.
+Ifload = EncRecipe(
+        'Ifload', Load, size=4, ins=GPR, outs=FPR,
+        instp=IsSignedInt(Load.offset, 12),
+        emit='put_i(bits, in_reg0, offset.into(), out_reg0, sink);')
+
+# S-type store of an FPR.
+Sfstore = EncRecipe(
+        'Sfstore', Store, size=4, ins=(FPR, GPR), outs=(),
+        instp=IsSignedInt(Store.offset, 12),
+        emit='put_s(bits, in_reg1, offset.into(), in_reg0, sink);')
+
+# Spill of an FPR, stored relative to the stack pointer.
+FPsp = EncRecipe(
+        'FPsp', Unary, size=4,
+        ins=FPR, outs=Stack(FPR),
+        emit='put_s_sp(bits, out_stk0.offset, in_reg0, sink);')
+
+# Fill of an FPR, loaded relative to the stack pointer.
+FPfi = EncRecipe(
+        'FPfi', Unary, size=4,
+        ins=Stack(FPR), outs=FPR,
+        emit='put_i_sp(bits, in_stk0.offset, out_reg0, sink);')
+
+# 16-bit instructions from the "C" Standard Extension for Compressed
+# Instructions. The encbits are the complete instruction with the operand
+# fields cleared.
+
+# CR-type move, `c.mv`.
+CRmov = EncRecipe(
+        'CRmov', Unary, size=2, ins=GPR, outs=GPR,
+        emit='put_cr(bits, out_reg0, in_reg0, sink);')
+
+# Same for a GPR regmove.
+CRrmov = EncRecipe(
+        'CRrmov', RegMove, size=2, ins=GPR, outs=(),
+        emit='put_cr(bits, dst, src, sink);')
+
+# CR-type two-address arithmetic, `c.add`.
+CRadd = EncRecipe(
+        'CRadd', Binary, size=2, ins=(GPR, GPR), outs=0,
+        emit='put_cr(bits, in_reg0, in_reg1, sink);')
+
+# CR-type return, `c.jr %x1`.
+CRret = EncRecipe(
+        'CRret', MultiAry, size=2, ins=(), outs=(),
+        emit='put_cr(bits, 1, 0, sink);')
+
+# CR-type indirect call, `c.jalr`.
+CRcall = EncRecipe(
+        'CRcall', CallIndirect, size=2, ins=GPR, outs=(),
+        emit='put_cr(bits, in_reg0, 0, sink);')
+
+# CI-type two-address add of a 6-bit signed immediate.
+CIaddi = EncRecipe(
+        'CIaddi', BinaryImm, size=2, ins=GPR, outs=0,
+        instp=IsSignedInt(BinaryImm.imm, 6),
+        emit='put_ci(bits, in_reg0, imm.into(), sink);')
+
+# CI-type load of a 6-bit signed immediate, `c.li`.
+CIli = EncRecipe(
+        'CIli', UnaryImm, size=2, ins=(), outs=GPR,
+        instp=IsSignedInt(UnaryImm.imm, 6),
+        emit='put_ci(bits, out_reg0, imm.into(), sink);')
+
+# CI-type two-address left shift, `c.slli`.
+CIshamt = EncRecipe(
+        'CIshamt', BinaryImm, size=2, ins=GPR, outs=0,
+        instp=IsUnsignedInt(BinaryImm.imm, 5),
+        emit='put_ci(bits, in_reg0, imm.into(), sink);')
+
+# CA-type two-address arithmetic on `x8`-`x15`.
+CA = EncRecipe(
+        'CA', Binary, size=2, ins=(GPR8, GPR8), outs=0,
+        emit='put_ca(bits, in_reg0, in_reg1, sink);')
+
+# CB-type two-address right shifts on `x8`-`x15`.
+CBshamt = EncRecipe(
+        'CBshamt', BinaryImm, size=2, ins=GPR8, outs=0,
+        instp=IsUnsignedInt(BinaryImm.imm, 5),
+        emit='put_cbi(bits, in_reg0, imm.into(), sink);')
+
+# CB-type two-address `c.andi` on `x8`-`x15`.
+CBandi = EncRecipe(
+        'CBandi', BinaryImm, size=2, ins=GPR8, outs=0,
+        instp=IsSignedInt(BinaryImm.imm, 6),
+        emit='put_cbi(bits, in_reg0, imm.into(), sink);')
+
+# CL-type word load, `c.lw`.
+CLw = EncRecipe(
+        'CLw', Load, size=2, ins=GPR8, outs=GPR8,
+        instp=IsUnsignedInt(Load.offset, 7, 2),
+        emit='put_clw(bits, in_reg0, offset.into(), out_reg0, sink);')
+
+# CS-type word store, `c.sw`.
+CSw = EncRecipe(
+        'CSw', Store, size=2, ins=(GPR8, GPR8), outs=(),
+        instp=IsUnsignedInt(Store.offset, 7, 2),
+        emit='put_clw(bits, in_reg1, offset.into(), in_reg0, sink);')
+
+# CL-type double word load, `c.ld`.
+CLd = EncRecipe(
+        'CLd', Load, size=2, ins=GPR8, outs=GPR8,
+        instp=IsUnsignedInt(Load.offset, 8, 3),
+        emit='put_cld(bits, in_reg0, offset.into(), out_reg0, sink);')
+
+# CS-type double word store, `c.sd`.
+CSd = EncRecipe(
+        'CSd', Store, size=2, ins=(GPR8, GPR8), outs=(),
+        instp=IsUnsignedInt(Store.offset, 8, 3),
+        emit='put_cld(bits, in_reg1, offset.into(), in_reg0, sink);')
+
+# CJ-type unconditional branch, `c.j`.
+CJ = EncRecipe(
+        'CJ', Jump, size=2, ins=(), outs=(), branch_range=(0, 12),
+        emit='''
+        let dest = i64::from(func.offsets[destination]);
+        let disp = dest - i64::from(sink.offset());
+        put_cj(bits, disp, sink);
+        ''')
+
+# CB-type branch comparing `x8`-`x15` to zero, `c.beqz` and `c.bnez`.
+CBzero = EncRecipe(
+        'CBzero', Branch, size=2,
+        ins=GPR8, outs=(),
+        branch_range=(0, 9),
+        emit='''
+        let dest = i64::from(func.offsets[destination]);
+        let disp = dest - i64::from(sink.offset());
+        put_cb(bits, disp, in_reg0, sink);
+        ''')
@@ -19,5 +19,8 @@ FloatRegs = RegBank(
 
 GPR = RegClass(IntRegs)
 FPR = RegClass(FloatRegs)
+# The registers `x8`-`x15` addressable by the 3-bit fields of compressed
+# instructions.
+GPR8 = GPR[8:16]
 
 RegClass.extract_names(globals())
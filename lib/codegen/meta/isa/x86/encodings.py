@@ -3,21 +3,36 @@ x86 Encodings.
 """
 from __future__ import absolute_import
 from cdsl.predicates import IsZero32BitFloat, IsZero64BitFloat
-from cdsl.predicates import IsUnsignedInt, Not, And
+from cdsl.predicates import IsUnsignedInt, IsEqual, Not, And
 from base.predicates import IsColocatedFunc, IsColocatedData, LengthEquals
 from base import instructions as base
 from base import types
 from base.formats import UnaryIeee32, UnaryIeee64, UnaryImm
 from base.formats import FuncAddr, Call, LoadComplex, StoreComplex
+from base.formats import InsertLane
 from .defs import X86_64, X86_32
 from . import recipes as r
 from . import settings as cfg
 from . import instructions as x86
-from .legalize import x86_expand
+from .legalize import x86_expand, x86_simd
 from base.legalize import narrow, widen, expand_flags
 from base.settings import allones_funcaddrs, is_pic
 from .settings import use_sse41
 
+# Vector types held in the 128-bit XMM registers.
+simd_ints = [types.i8.by(16), types.i16.by(8), types.i32.by(4), types.i64.by(2)]
+simd_floats = [types.f32.by(4), types.f64.by(2)]
+simd_bools = [types.b8.by(16), types.b16.by(8), types.b32.by(4), types.b64.by(2)]
+simd_types = simd_ints + simd_floats + simd_bools
+
+# Vector types held in the 256-bit YMM registers, only supported with AVX.
+simd256_ints = [
+        types.i8.by(32), types.i16.by(16), types.i32.by(8), types.i64.by(4)]
+simd256_floats = [types.f32.by(8), types.f64.by(4)]
+simd256_bools = [
+        types.b8.by(32), types.b16.by(16), types.b32.by(8), types.b64.by(4)]
+simd256_types = simd256_ints + simd256_floats + simd256_bools
+
 try:
     from typing import TYPE_CHECKING, Any  # noqa
     if TYPE_CHECKING:
@@ -36,6 +51,8 @@ X86_32.legalize_type(
     i32=x86_expand,
     f32=x86_expand,
     f64=x86_expand)
+X86_32.legalize_type(**{ty.name: x86_simd for ty in simd_types})
+X86_32.legalize_type(**{ty.name: x86_simd for ty in simd256_types})
 
 X86_64.legalize_monomorphic(expand_flags)
 X86_64.legalize_type(
@@ -47,6 +64,8 @@ X86_64.legalize_type(
     i64=x86_expand,
     f32=x86_expand,
     f64=x86_expand)
+X86_64.legalize_type(**{ty.name: x86_simd for ty in simd_types})
+X86_64.legalize_type(**{ty.name: x86_simd for ty in simd256_types})
 
 
 #
@@ -89,6 +108,26 @@ def enc_both_instp(inst, recipe, instp, *args, **kwargs):
     enc_x86_64_instp(inst, recipe, instp, *args, **kwargs)
 
 
+def enc_vex(inst, recipe, *args, **kwargs):
+    # type: (MaybeBoundInst, r.TailRecipe, *int, **int) -> None
+    """
+    Add VEX encodings for `inst` to both X86_32 and X86_64.
+    """
+    X86_32.enc(inst, *recipe.vex(*args, **kwargs))
+    X86_64.enc(inst, *recipe.vex(*args, **kwargs))
+
+
+def enc_vex_i32_i64(inst, recipe, *args, **kwargs):
+    # type: (MaybeBoundInst, r.TailRecipe, *int, **int) -> None
+    """
+    Add VEX encodings for `inst.i32` to both X86_32 and X86_64, and for
+    `inst.i64` to X86_64 with the VEX.W bit set.
+    """
+    X86_32.enc(inst.i32, *recipe.vex(*args, **kwargs))
+    X86_64.enc(inst.i64, *recipe.vex(*args, w=1, **kwargs))
+    X86_64.enc(inst.i32, *recipe.vex(*args, **kwargs))
+
+
 def enc_i32_i64(inst, recipe, *args, **kwargs):
     # type: (MaybeBoundInst, r.TailRecipe, *int, **int) -> None
     """
@@ -173,7 +212,8 @@ enc_i32_i64(x86.smulx, r.mulx, 0xf7, rrr=5)
 enc_i32_i64(x86.umulx, r.mulx, 0xf7, rrr=4)
 
 enc_i32_i64(base.copy, r.umr, 0x89)
-enc_both(base.copy.b1, r.umr, 0x89)
+for ty in [types.b1, types.i8, types.i16]:
+    enc_both(base.copy.bind(ty), r.umr, 0x89)
 
 # For x86-64, only define REX forms for now, since we can't describe the
 # special regunit immediate operands with the current constraint language.
@@ -215,6 +255,16 @@ X86_64.enc(base.iconst.i64, *r.pu_iq.rex(0xb8, w=1))
 # bool constants.
 enc_both(base.bconst.b1, r.pu_id_bool, 0xb8)
 
+# BMI2 shifts take the shift amount in any register, so they come before the
+# shifts by %cl below.
+for inst,           pp in [
+        (base.ishl, 0x66),
+        (base.ushr, 0xf2),
+        (base.sshr, 0xf3)]:
+    X86_32.enc(inst.i32.any, *r.vrsh.vex(pp, 0x0f, 0x38, 0xf7))
+    X86_64.enc(inst.i64.any, *r.vrsh.vex(pp, 0x0f, 0x38, 0xf7, w=1))
+    X86_64.enc(inst.i32.any, *r.vrsh.vex(pp, 0x0f, 0x38, 0xf7))
+
 # Shifts and rotates.
 # Note that the dynamic shift amount is only masked by 5 or 6 bits; the 8-bit
 # and 16-bit shifts would need explicit masking.
@@ -258,6 +308,14 @@ X86_64.enc(base.ctz.i64, *r.urm.rex(0xf3, 0x0f, 0xbc, w=1),
 X86_64.enc(base.ctz.i32, *r.urm.rex(0xf3, 0x0f, 0xbc), isap=cfg.use_bmi1)
 X86_64.enc(base.ctz.i32, *r.urm(0xf3, 0x0f, 0xbc), isap=cfg.use_bmi1)
 
+# Other BMI1 and BMI2 instructions.
+# The `andn(x,y)` instruction computes `~x&y`, while band_not(x,y)` is `x&~y.
+enc_vex_i32_i64(base.band_not, r.vrrx, 0x0f, 0x38, 0xf2)
+enc_vex_i32_i64(x86.blsr, r.vur, 0x0f, 0x38, 0xf3, rrr=1)
+enc_vex_i32_i64(x86.blsi, r.vur, 0x0f, 0x38, 0xf3, rrr=3)
+# rorx doesn't touch the flags or tie its output to the input.
+enc_vex_i32_i64(base.rotr_imm, r.vr_ib, 0xf2, 0x0f, 0x3a, 0xf0)
+
 #
 # Loads and stores.
 #
@@ -307,6 +365,11 @@ enc_i32_i64(base.regspill, r.regspill32, 0x89)
 enc_both(base.spill.b1, r.spillSib32, 0x89)
 enc_both(base.regspill.b1, r.regspill32, 0x89)
 
+# Small integers are spilled the same way, so they don't need byte registers.
+for ty in [types.i8, types.i16]:
+    enc_both(base.spill.bind(ty), r.spillSib32, 0x89)
+    enc_both(base.regspill.bind(ty), r.regspill32, 0x89)
+
 for recipe in [r.ld, r.ldDisp8, r.ldDisp32]:
     enc_i32_i64_ld_st(base.load, True, recipe, 0x8b)
     enc_x86_64(base.uload32.i64, recipe, 0x8b)
@@ -323,6 +386,31 @@ enc_i32_i64(base.regfill, r.regfill32, 0x8b)
 enc_both(base.fill.b1, r.fillSib32, 0x8b)
 enc_both(base.regfill.b1, r.regfill32, 0x8b)
 
+# Load 32 bits from small integer spill slots too. Only the low bits are used.
+for ty in [types.i8, types.i16]:
+    enc_both(base.fill.bind(ty), r.fillSib32, 0x8b)
+    enc_both(base.regfill.bind(ty), r.regfill32, 0x8b)
+
+# Atomic memory operations.
+# Aligned loads are atomic, and the x86 memory model makes them acquire loads.
+# Sequentially consistent stores are legalized to `atomic_xchg`.
+for recipe in [r.ld, r.ldDisp8, r.ldDisp32]:
+    enc_i32_i64_ld_st(base.atomic_load, True, recipe, 0x8b)
+
+# xchg with a memory operand is implicitly locked.
+for recipe in [r.rmw, r.rmwDisp8, r.rmwDisp32]:
+    enc_i32_i64_ld_st(base.atomic_xchg, True, recipe, 0x87)
+
+# lock xadd
+for recipe in [r.lrmw, r.lrmwDisp8, r.lrmwDisp32]:
+    enc_i32_i64_ld_st(base.atomic_add, True, recipe, 0x0f, 0xc1)
+
+# lock cmpxchg
+enc_i32_i64_ld_st(base.atomic_cas, True, r.lcas, 0x0f, 0xb1)
+
+# mfence
+enc_both(base.fence, r.fence, 0x0f, 0xae, rrr=6)
+
 # Push and Pop
 X86_32.enc(x86.push.i32, *r.pushq(0x50))
 enc_x86_64(x86.push.i64, r.pushq, 0x50)
@@ -692,6 +780,39 @@ for inst in [
     enc_both(inst.f64, r.furmi_rnd, 0x66, 0x0f, 0x3a, 0x0b, isap=use_sse41)
 
 
+# The three-operand AVX forms come first, so they are preferred over the
+# two-operand SSE forms below when AVX is available.
+f32x4, f64x2 = simd_floats
+f32x8, f64x4 = simd256_floats
+
+for inst,           opc in [
+        (base.fadd, 0x58),
+        (base.fsub, 0x5c),
+        (base.fmul, 0x59),
+        (base.fdiv, 0x5e),
+        (x86.fmin,  0x5d),
+        (x86.fmax,  0x5f)]:
+    enc_vex(inst.f32, r.vfa, 0xf3, 0x0f, opc)
+    enc_vex(inst.f64, r.vfa, 0xf2, 0x0f, opc)
+    enc_vex(inst.bind(f32x4), r.vfa, 0x0f, opc)
+    enc_vex(inst.bind(f64x2), r.vfa, 0x66, 0x0f, opc)
+    enc_vex(inst.bind(f32x8), r.vfa, 0x0f, opc, l=1)
+    enc_vex(inst.bind(f64x4), r.vfa, 0x66, 0x0f, opc, l=1)
+
+# vandps, vorps, vxorps and vandnps. AVX doesn't have 256-bit integer
+# instructions, but the float ones work for any vector type.
+for ty in [types.f32, types.f64] + simd_floats:
+    enc_vex(base.band.bind(ty), r.vfa, 0x0f, 0x54)
+    enc_vex(base.bor.bind(ty), r.vfa, 0x0f, 0x56)
+    enc_vex(base.bxor.bind(ty), r.vfa, 0x0f, 0x57)
+    enc_vex(base.band_not.bind(ty), r.vfax, 0x0f, 0x55)
+
+for ty in simd256_types:
+    enc_vex(base.band.bind(ty), r.vfa, 0x0f, 0x54, l=1)
+    enc_vex(base.bor.bind(ty), r.vfa, 0x0f, 0x56, l=1)
+    enc_vex(base.bxor.bind(ty), r.vfa, 0x0f, 0x57, l=1)
+    enc_vex(base.band_not.bind(ty), r.vfax, 0x0f, 0x55, l=1)
+
 # Binary arithmetic ops.
 for inst,           opc in [
         (base.fadd, 0x58),
@@ -724,3 +845,194 @@ enc_both(base.fcmp.f64, r.fcscc, 0x66, 0x0f, 0x2e)
 
 enc_both(base.ffcmp.f32, r.fcmp, 0x0f, 0x2e)
 enc_both(base.ffcmp.f64, r.fcmp, 0x66, 0x0f, 0x2e)
+
+#
+# SIMD
+#
+# The vector types with 128 bits are held in the XMM registers. The encodings
+# below only need SSE2, which is part of the x86-64 baseline, except for the
+# ones marked with the `use_sse41` predicate.
+#
+
+i8x16, i16x8, i32x4, i64x2 = simd_ints
+
+for ty in simd_types:
+    # movaps
+    enc_both(base.copy.bind(ty), r.furm, 0x0f, 0x28)
+
+    # For x86-64, only define REX forms for now, since we can't describe the
+    # special regunit immediate operands with the current constraint language.
+    X86_32.enc(base.regmove.bind(ty), *r.frmov(0x0f, 0x28))
+    X86_64.enc(base.regmove.bind(ty), *r.frmov.rex(0x0f, 0x28))
+
+    # movups, since spill slots are not aligned to 16 bytes.
+    enc_both(base.fill.bind(ty), r.ffillSib32, 0x0f, 0x10)
+    enc_both(base.regfill.bind(ty), r.fregfill32, 0x0f, 0x10)
+    enc_both(base.spill.bind(ty), r.fspillSib32, 0x0f, 0x11)
+    enc_both(base.regspill.bind(ty), r.fregspill32, 0x0f, 0x11)
+
+# Vector loads and stores use movups, so they don't require any alignment.
+for ty in simd_ints + simd_floats:
+    for recipe in [r.fld, r.fldDisp8, r.fldDisp32]:
+        enc_both(base.load.bind(ty).any, recipe, 0x0f, 0x10)
+    for recipe in [r.fldWithIndex, r.fldWithIndexDisp8, r.fldWithIndexDisp32]:
+        enc_both(base.load_complex.bind(ty), recipe, 0x0f, 0x10)
+    for recipe in [r.fst, r.fstDisp8, r.fstDisp32]:
+        enc_both(base.store.bind(ty).any, recipe, 0x0f, 0x11)
+    for recipe in [r.fstWithIndex, r.fstWithIndexDisp8, r.fstWithIndexDisp32]:
+        enc_both(base.store_complex.bind(ty), recipe, 0x0f, 0x11)
+
+# Reinterpreting the bits of a vector is a no-op.
+for to_ty in simd_ints + simd_floats:
+    for from_ty in simd_ints + simd_floats:
+        if to_ty != from_ty:
+            X86_32.enc(base.bitcast.bind(to_ty).bind(from_ty), r.null_fpr, 0)
+            X86_64.enc(base.bitcast.bind(to_ty).bind(from_ty), r.null_fpr, 0)
+
+# Integer arithmetic: padd and psub.
+for ty,     add,  sub in [
+        (i8x16, 0xfc, 0xf8),
+        (i16x8, 0xfd, 0xf9),
+        (i32x4, 0xfe, 0xfa),
+        (i64x2, 0xd4, 0xfb)]:
+    enc_both(base.iadd.bind(ty), r.fa, 0x66, 0x0f, add)
+    enc_both(base.isub.bind(ty), r.fa, 0x66, 0x0f, sub)
+
+# pmullw
+enc_both(base.imul.bind(i16x8), r.fa, 0x66, 0x0f, 0xd5)
+
+# pmulld
+X86_32.enc(base.imul.bind(i32x4), *r.fa(0x66, 0x0f, 0x38, 0x40),
+           isap=use_sse41)
+X86_64.enc(base.imul.bind(i32x4), *r.fa.rex(0x66, 0x0f, 0x38, 0x40),
+           isap=use_sse41)
+X86_64.enc(base.imul.bind(i32x4), *r.fa(0x66, 0x0f, 0x38, 0x40),
+           isap=use_sse41)
+
+# Bitwise ops: pand, por, pxor and pandn.
+for ty in simd_ints + simd_bools:
+    enc_both(base.band.bind(ty), r.fa, 0x66, 0x0f, 0xdb)
+    enc_both(base.bor.bind(ty), r.fa, 0x66, 0x0f, 0xeb)
+    enc_both(base.bxor.bind(ty), r.fa, 0x66, 0x0f, 0xef)
+    enc_both(base.band_not.bind(ty), r.fax, 0x66, 0x0f, 0xdf)
+
+for ty in simd_floats:
+    enc_both(base.band.bind(ty), r.fa, 0x0f, 0x54)
+    enc_both(base.bor.bind(ty), r.fa, 0x0f, 0x56)
+    enc_both(base.bxor.bind(ty), r.fa, 0x0f, 0x57)
+    enc_both(base.band_not.bind(ty), r.fax, 0x0f, 0x55)
+
+# Packed float arithmetic.
+for inst,           opc in [
+        (base.fadd, 0x58),
+        (base.fsub, 0x5c),
+        (base.fmul, 0x59),
+        (base.fdiv, 0x5e),
+        (x86.fmin,  0x5d),
+        (x86.fmax,  0x5f)]:
+    enc_both(inst.bind(f32x4), r.fa, 0x0f, opc)
+    enc_both(inst.bind(f64x2), r.fa, 0x66, 0x0f, opc)
+
+enc_both(base.sqrt.bind(f32x4), r.furm, 0x0f, 0x51)
+enc_both(base.sqrt.bind(f64x2), r.furm, 0x66, 0x0f, 0x51)
+
+# Blends with the mask in %xmm0: pblendvb, blendvps and blendvpd.
+for ty in simd_ints + simd_bools:
+    enc_both(base.vselect.bind(ty), r.blend, 0x66, 0x0f, 0x38, 0x10)
+enc_both(base.vselect.bind(f32x4), r.blend, 0x66, 0x0f, 0x38, 0x14)
+enc_both(base.vselect.bind(f64x2), r.blend, 0x66, 0x0f, 0x38, 0x15)
+
+# Moving a scalar into the lowest lane: movd and movq. A float is already in
+# the lowest lane of its XMM register.
+for ty in [i8x16, i16x8, i32x4]:
+    enc_both(x86.scalar_to_vector.bind(ty), r.frurm, 0x66, 0x0f, 0x6e)
+X86_64.enc(x86.scalar_to_vector.bind(i64x2),
+           *r.frurm.rex(0x66, 0x0f, 0x6e, w=1))
+for ty in simd_floats:
+    X86_32.enc(x86.scalar_to_vector.bind(ty), r.null_fpr, 0)
+    X86_64.enc(x86.scalar_to_vector.bind(ty), r.null_fpr, 0)
+
+# pshufd
+for ty in simd_ints + simd_floats:
+    enc_both(x86.pshufd.bind(ty), r.furmi_lane, 0x66, 0x0f, 0x70)
+
+# Lane extraction: pextrb, pextrw, pextrd and pextrq into a GPR. A float lane
+# is shuffled into the lowest lane with pshufd or shufpd.
+enc_both(base.extractlane.bind(i8x16), r.rfumr_lane, 0x66, 0x0f, 0x3a, 0x14)
+enc_both(base.extractlane.bind(i16x8), r.rfumr_lane, 0x66, 0x0f, 0x3a, 0x15)
+enc_both(base.extractlane.bind(i32x4), r.rfumr_lane, 0x66, 0x0f, 0x3a, 0x16)
+X86_64.enc(base.extractlane.bind(i64x2),
+           *r.rfumr_lane.rex(0x66, 0x0f, 0x3a, 0x16, w=1))
+enc_both(base.extractlane.bind(f32x4), r.furmi_lane, 0x66, 0x0f, 0x70)
+enc_both(base.extractlane.bind(f64x2), r.fshuf_lane, 0x66, 0x0f, 0xc6)
+
+# Lane insertion: pinsrb, pinsrw, pinsrd and pinsrq from a GPR. Only pinsrw
+# is available without SSE 4.1.
+for ty,     opc in [
+        (i8x16, (0x66, 0x0f, 0x3a, 0x20)),
+        (i32x4, (0x66, 0x0f, 0x3a, 0x22))]:
+    X86_32.enc(base.insertlane.bind(ty), *r.frurmi_lane(*opc),
+               isap=use_sse41)
+    X86_64.enc(base.insertlane.bind(ty), *r.frurmi_lane.rex(*opc),
+               isap=use_sse41)
+    X86_64.enc(base.insertlane.bind(ty), *r.frurmi_lane(*opc),
+               isap=use_sse41)
+enc_both(base.insertlane.bind(i16x8), r.frurmi_lane, 0x66, 0x0f, 0xc4)
+X86_64.enc(base.insertlane.bind(i64x2),
+           *r.frurmi_lane.rex(0x66, 0x0f, 0x3a, 0x22, w=1),
+           isap=use_sse41)
+
+# insertps
+enc_both(base.insertlane.bind(f32x4), r.fa_insertps, 0x66, 0x0f, 0x3a, 0x21)
+
+# movsd into the low lane, and movlhps into the high lane.
+enc_both_instp(base.insertlane.bind(f64x2), r.fa_lane,
+               IsEqual(InsertLane.lane, 0), 0xf2, 0x0f, 0x10)
+enc_both_instp(base.insertlane.bind(f64x2), r.fa_lane,
+               IsEqual(InsertLane.lane, 1), 0x0f, 0x16)
+
+#
+# 256-bit vectors
+#
+# The vector types with 256 bits are held in the YMM registers, and they need
+# AVX. The arithmetic instructions are above, with the AVX forms of the scalar
+# float instructions.
+#
+
+for ty in simd256_types:
+    # vmovaps
+    enc_vex(base.copy.bind(ty), r.vfurm, 0x0f, 0x28, l=1)
+    enc_vex(base.regmove.bind(ty), r.vfrmov, 0x0f, 0x28, l=1)
+
+    # vmovups, since spill slots are not aligned to 32 bytes.
+    enc_vex(base.fill.bind(ty), r.vffillSib32, 0x0f, 0x10, l=1)
+    enc_vex(base.regfill.bind(ty), r.vfregfill32, 0x0f, 0x10, l=1)
+    enc_vex(base.spill.bind(ty), r.vfspillSib32, 0x0f, 0x11, l=1)
+    enc_vex(base.regspill.bind(ty), r.vfregspill32, 0x0f, 0x11, l=1)
+
+for ty in simd256_ints + simd256_floats:
+    for recipe in [r.vfld, r.vfldDisp8, r.vfldDisp32]:
+        enc_vex(base.load.bind(ty).any, recipe, 0x0f, 0x10, l=1)
+    for recipe in [r.vfst, r.vfstDisp8, r.vfstDisp32]:
+        enc_vex(base.store.bind(ty).any, recipe, 0x0f, 0x11, l=1)
+
+# Reinterpreting the bits of a vector is a no-op.
+for to_ty in simd256_ints + simd256_floats:
+    for from_ty in simd256_ints + simd256_floats:
+        if to_ty != from_ty:
+            X86_32.enc(base.bitcast.bind(to_ty).bind(from_ty), r.null_fpr, 0)
+            X86_64.enc(base.bitcast.bind(to_ty).bind(from_ty), r.null_fpr, 0)
+
+# Vectors that were split in two, because AVX doesn't have 256-bit integer
+# arithmetic, are taken apart and put back together in registers.
+for ty, half in zip(simd256_types, simd_types):
+    # vextractf128
+    enc_vex(base.vsplit.bind(ty), r.vfsplit, 0x66, 0x0f, 0x3a, 0x19, l=1)
+    # vinsertf128
+    enc_vex(base.vconcat.bind(half), r.vfconcat, 0x66, 0x0f, 0x3a, 0x18, l=1)
+
+enc_vex(base.sqrt.bind(f32x8), r.vfurm, 0x0f, 0x51, l=1)
+enc_vex(base.sqrt.bind(f64x4), r.vfurm, 0x66, 0x0f, 0x51, l=1)
+
+# vzeroupper
+enc_vex(x86.vzeroupper, r.vop, 0x0f, 0x77)
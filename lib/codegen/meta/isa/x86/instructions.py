@@ -5,6 +5,7 @@ This module defines additional instructions that are useful only to the x86
 target ISA.
 """
 
+from base.immediates import uimm8
 from base.types import iflags
 from cdsl.operands import Operand
 from cdsl.typevar import TypeVar
@@ -170,4 +171,63 @@ bsf = Instruction(
     """,
     ins=x, outs=(y, rflags))
 
+a = Operand('a', iWord)
+
+blsr = Instruction(
+    'x86_blsr', r"""
+    Reset Lowest Set Bit -- computes ``x & (x - 1)``, clearing the least
+    significant 1 in the word.
+
+    This is polymorphic in i32 and i64, and it is only available with BMI1.
+    The post-legalization pass forms it from the equivalent ``band``.
+    """,
+    ins=x, outs=a)
+
+blsi = Instruction(
+    'x86_blsi', r"""
+    Extract Lowest Set Bit -- computes ``x & -x``, keeping only the least
+    significant 1 in the word. Is otherwise identical to 'blsr', just above.
+    """,
+    ins=x, outs=a)
+
+TxN = TypeVar(
+        'TxN', 'A SIMD vector type',
+        ints=True, floats=True, bools=True, scalars=False, simd=True)
+x = Operand('x', TxN.lane_of())
+a = Operand('a', TxN)
+
+scalar_to_vector = Instruction(
+    'x86_scalar_to_vector', r"""
+    Move a scalar into the lowest lane of a vector.
+
+    The other lanes of the result are undefined. This is used for building
+    vectors out of scalars, and it is a no-op when the scalar is already held
+    in an XMM register.
+    """,
+    ins=x, outs=a)
+
+x = Operand('x', TxN)
+Order = Operand('Order', uimm8, doc='Lane selectors')
+
+pshufd = Instruction(
+    'x86_pshufd', r"""
+    Shuffle the 32-bit lanes of a vector.
+
+    Each 2-bit field of ``Order``, from the least significant one, is the
+    index of the 32-bit lane of `x` that is copied into the corresponding
+    32-bit lane of the result. The bits of `x` are shuffled the same way
+    regardless of its lane type.
+    """,
+    ins=(x, Order), outs=a)
+
+vzeroupper = Instruction(
+    'x86_vzeroupper', r"""
+    Clear the upper 128 bits of all the YMM registers.
+
+    This avoids the penalty of mixing legacy SSE instructions with AVX
+    instructions that leave the upper halves of the YMM registers dirty, so it
+    is inserted before calls and returns in functions using 256-bit vectors.
+    """,
+    other_side_effects=True)
+
 GROUP.close()
@@ -4,8 +4,9 @@ Custom legalization patterns for x86.
 from __future__ import absolute_import
 from cdsl.ast import Var
 from cdsl.xform import Rtl, XFormGroup
-from base.immediates import imm64, intcc, floatcc
+from base.immediates import imm64, uimm8, intcc, floatcc
 from base import legalize as shared
+from base import types
 from base import instructions as insts
 from . import instructions as x86
 from .defs import ISA
@@ -19,6 +20,16 @@ x86_expand = XFormGroup(
         """,
         isa=ISA, chain=shared.expand_flags)
 
+x86_simd = XFormGroup(
+        'x86_simd',
+        """
+        Legalize 128-bit vector instructions.
+
+        Use SSE instructions for the vector operations that don't have a
+        direct encoding, and split the other ones into halves.
+        """,
+        isa=ISA, chain=shared.narrow)
+
 a = Var('a')
 dead = Var('dead')
 x = Var('x')
@@ -98,6 +109,9 @@ x86_expand.custom_legalize(insts.fcvt_from_uint, 'expand_fcvt_from_uint')
 x86_expand.custom_legalize(insts.fcvt_to_sint, 'expand_fcvt_to_sint')
 x86_expand.custom_legalize(insts.fcvt_to_uint, 'expand_fcvt_to_uint')
 
+# Only sequentially consistent stores need a locked instruction.
+x86_expand.custom_legalize(insts.atomic_store, 'expand_atomic_store')
+
 # Count leading and trailing zeroes, for baseline x86_64
 c_minus_one = Var('c_minus_one')
 c_thirty_one = Var('c_thirty_one')
@@ -225,3 +239,58 @@ x86_expand.legalize(
         lv15 << insts.imul(lv14, lc01),
         lv16 << insts.ushr_imm(lv15, imm64(24))
     ))
+
+#
+# SIMD
+#
+b = Var('b')
+c = Var('c')
+xl = Var('xl')
+xh = Var('xh')
+
+# Splat a scalar by moving it into the lowest lane and shuffling that lane
+# into the other ones.
+for ty,                 order in [
+        (types.i32.by(4), 0x00),
+        (types.i64.by(2), 0x44),
+        (types.f32.by(4), 0x00),
+        (types.f64.by(2), 0x44)]:
+    x86_simd.legalize(
+            a << insts.splat.bind(ty)(x),
+            Rtl(
+                b << x86.scalar_to_vector.bind(ty)(x),
+                a << x86.pshufd(b, uimm8(order))
+            ))
+
+# Narrower lanes are first repeated in a 32-bit integer.
+for ty,                 repeat in [
+        (types.i8.by(16), 0x01010101),
+        (types.i16.by(8), 0x00010001)]:
+    x86_simd.legalize(
+            a << insts.splat.bind(ty)(x),
+            Rtl(
+                a1 << insts.uextend.i32(x),
+                a2 << insts.imul_imm(a1, imm64(repeat)),
+                b << x86.scalar_to_vector.i32x4(a2),
+                c << x86.pshufd(b, uimm8(0x00)),
+                a << insts.bitcast.bind(ty)(c)
+            ))
+
+# There is no SSE instruction for multiplying 64-bit lanes, so multiply the
+# lanes one at a time.
+yl = Var('yl')
+yh = Var('yh')
+al = Var('al')
+ah = Var('ah')
+x86_simd.legalize(
+        a << insts.imul.i64x2(x, y),
+        Rtl(
+            xl << insts.extractlane(x, uimm8(0)),
+            xh << insts.extractlane(x, uimm8(1)),
+            yl << insts.extractlane(y, uimm8(0)),
+            yh << insts.extractlane(y, uimm8(1)),
+            al << insts.imul(xl, yl),
+            ah << insts.imul(xh, yh),
+            b << x86.scalar_to_vector.i64x2(al),
+            a << insts.insertlane(b, uimm8(1), ah)
+        ))
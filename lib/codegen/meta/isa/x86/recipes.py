@@ -15,14 +15,15 @@ from base.formats import IntCond, FloatCond
 from base.formats import IntSelect, IntCondTrap, FloatCondTrap
 from base.formats import Jump, Branch, BranchInt, BranchFloat
 from base.formats import Ternary, FuncAddr, UnaryGlobalValue
+from base.formats import InsertLane, ExtractLane
 from base.formats import RegMove, RegSpill, RegFill, CopySpecial
-from base.formats import LoadComplex, StoreComplex
+from base.formats import LoadComplex, StoreComplex, AtomicCas
 from base.formats import StackLoad
 from .registers import GPR, ABCD, FPR, GPR_DEREF_SAFE, GPR_ZERO_DEREF_SAFE
 from .registers import GPR8, FPR8, GPR8_DEREF_SAFE, GPR8_ZERO_DEREF_SAFE, FLAG
 from .registers import StackGPR32, StackFPR32
 from .defs import supported_floatccs
-from .settings import use_sse41
+from .settings import use_sse41, use_avx, use_bmi1, use_bmi2
 
 try:
     from typing import Tuple, Dict, Sequence, Any  # noqa
@@ -60,9 +61,13 @@ OPCODE_PREFIX = {
         }
 
 # The table above does not include the REX prefix which goes after the
-# mandatory prefix. VEX/XOP and EVEX prefixes are not yet supported. Encodings
+# mandatory prefix. XOP and EVEX prefixes are not yet supported. Encodings
 # using any of these prefixes are represented by separate recipes.
 #
+# VEX encodings always use the three-byte VEX prefix which holds the pp and mm
+# fields, so their recipe names are prefixed with `Vex128` or `Vex256`
+# instead, depending on the VEX.L bit.
+#
 # The encoding bits are:
 #
 # 0-7:   The opcode byte <op>.
@@ -146,6 +151,11 @@ class TailRecipe:
     The `requires_prefix` parameter indicates that the recipe can't be used
     without a REX prefix.
 
+    The `requires_vex` parameter indicates that the recipe encodes an operand
+    in the VEX.vvvv field, so it can only be used with a VEX prefix. The
+    `PUT_OP` function of such a recipe takes the register of that operand
+    after the REX bits.
+
     The `emit` parameter contains Rust code to actually emit an encoding, like
     `EncRecipe` does it. Additionally, the text `PUT_OP` is substituted with
     the proper `put_*` function from the `x86/binemit.rs` module.
@@ -164,6 +174,7 @@ class TailRecipe:
             isap=None,              # type: PredNode
             when_prefixed=None,     # type: TailRecipe
             requires_prefix=False,  # type: bool
+            requires_vex=False,     # type: bool
             emit=None               # type: str
             ):
         # type: (...) -> None
@@ -178,6 +189,7 @@ class TailRecipe:
         self.isap = isap
         self.when_prefixed = when_prefixed
         self.requires_prefix = requires_prefix
+        self.requires_vex = requires_vex
         self.emit = emit
 
         # Cached recipes, keyed by name prefix.
@@ -190,6 +202,7 @@ class TailRecipe:
         `ops`.
         """
         assert not self.requires_prefix, "Tail recipe requires REX prefix."
+        assert not self.requires_vex, "Tail recipe requires VEX prefix."
         rrr = kwargs.get('rrr', 0)
         w = kwargs.get('w', 0)
         name, bits = decode_ops(ops, rrr, w)
@@ -232,6 +245,7 @@ class TailRecipe:
         if self.when_prefixed:
             return self.when_prefixed.rex(*ops, **kwargs)
 
+        assert not self.requires_vex, "Tail recipe requires VEX prefix."
         rrr = kwargs.get('rrr', 0)
         w = kwargs.get('w', 0)
         name, bits = decode_ops(ops, rrr, w)
@@ -259,6 +273,40 @@ class TailRecipe:
 
         return (self.recipes[name], bits)
 
+    def vex(self, *ops, **kwargs):
+        # type: (*int, **int) -> Tuple[EncRecipe, int]
+        """
+        Create a VEX encoding recipe and encoding bits for the opcode bytes in
+        `ops`.
+
+        The mandatory prefix and the opcode map of `ops` are moved into the
+        three-byte VEX prefix. Pass `l=1` for instructions operating on
+        256-bit vectors.
+        """
+        assert self.requires_vex, "Tail recipe doesn't support VEX prefix."
+        rrr = kwargs.get('rrr', 0)
+        w = kwargs.get('w', 0)
+        l = kwargs.get('l', 0)
+        assert l <= 1
+        _, bits = decode_ops(ops, rrr, w)
+        name = 'Vex256' if l else 'Vex128'
+        size = 4 + self.size
+
+        if name not in self.recipes:
+            recipe = EncRecipe(
+                name + self.name,
+                self.format,
+                size,
+                ins=self.ins,
+                outs=self.outs,
+                clobbers_flags=self.clobbers_flags,
+                instp=self.instp,
+                isap=self.isap,
+                emit=replace_put_op(self.emit, name))
+            self.recipes[name] = recipe
+
+        return (self.recipes[name], bits)
+
     @staticmethod
     def check_names(globs):
         # type: (Dict[str, Any]) -> None
@@ -280,6 +328,10 @@ def floatccs(iform):
 # copies and no-op conversions.
 null = EncRecipe('null', Unary, size=0, ins=GPR, outs=0, emit='')
 
+# The same null instruction for an FPR register. Used for no-op conversions
+# between values held in XMM registers.
+null_fpr = EncRecipe('null_fpr', Unary, size=0, ins=FPR, outs=0, emit='')
+
 # XX opcode, no ModR/M.
 trap = TailRecipe(
         'trap', Trap, size=0, ins=(), outs=(),
@@ -447,6 +499,80 @@ furmi_rnd = TailRecipe(
         });
         ''')
 
+# XX /r ib, RMI form, FPR -> FPR with the lane immediate. Used for pshufd.
+furmi_lane = TailRecipe(
+        'furmi_lane', ExtractLane, size=2, ins=FPR, outs=FPR,
+        clobbers_flags=False,
+        emit='''
+        PUT_OP(bits, rex2(in_reg0, out_reg0), sink);
+        modrm_rr(in_reg0, out_reg0, sink);
+        sink.put1(lane);
+        ''')
+
+# XX /r ib, shuffling an FPR with itself to move a lane into the lowest lane.
+fshuf_lane = TailRecipe(
+        'fshuf_lane', ExtractLane, size=2, ins=FPR, outs=0,
+        clobbers_flags=False,
+        emit='''
+        PUT_OP(bits, rex2(in_reg0, in_reg0), sink);
+        modrm_rr(in_reg0, in_reg0, sink);
+        sink.put1(lane);
+        ''')
+
+# XX /r ib, MR form, extracting a vector lane from an FPR into a GPR.
+rfumr_lane = TailRecipe(
+        'rfumr_lane', ExtractLane, size=2, ins=FPR, outs=GPR,
+        clobbers_flags=False,
+        isap=use_sse41,
+        emit='''
+        PUT_OP(bits, rex2(out_reg0, in_reg0), sink);
+        modrm_rr(out_reg0, in_reg0, sink);
+        sink.put1(lane);
+        ''')
+
+# XX /r ib, RMI form, inserting a GPR into a vector lane.
+frurmi_lane = TailRecipe(
+        'frurmi_lane', InsertLane, size=2, ins=(FPR, GPR), outs=0,
+        clobbers_flags=False,
+        emit='''
+        PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+        modrm_rr(in_reg1, in_reg0, sink);
+        sink.put1(lane);
+        ''')
+
+# XX /r ib, RMI form for insertps. The destination lane goes in bits 4-5 of
+# the immediate.
+fa_insertps = TailRecipe(
+        'fa_insertps', InsertLane, size=2, ins=(FPR, FPR), outs=0,
+        clobbers_flags=False,
+        isap=use_sse41,
+        emit='''
+        PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+        modrm_rr(in_reg1, in_reg0, sink);
+        sink.put1(lane << 4);
+        ''')
+
+# XX /r, RM form, inserting the lowest lane of an FPR into a vector lane. The
+# lane is implied by the opcode.
+fa_lane = TailRecipe(
+        'fa_lane', InsertLane, size=1, ins=(FPR, FPR), outs=0,
+        clobbers_flags=False,
+        emit='''
+        PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+        modrm_rr(in_reg1, in_reg0, sink);
+        ''')
+
+# XX /r, RM form for the SSE 4.1 variable blends. The mask is implicitly in
+# %xmm0, and the lanes of the first operand are blended into the second.
+blend = TailRecipe(
+        'blend', Ternary, size=1, ins=(FPR.xmm0, FPR, FPR), outs=2,
+        clobbers_flags=False,
+        isap=use_sse41,
+        emit='''
+        PUT_OP(bits, rex2(in_reg1, in_reg2), sink);
+        modrm_rr(in_reg1, in_reg2, sink);
+        ''')
+
 # XX /r, for regmove instructions.
 rmov = TailRecipe(
         'rmov', RegMove, size=1, ins=GPR, outs=(),
@@ -1849,4 +1975,411 @@ fcscc = TailRecipe(
         modrm_rr(out_reg0, 0, sink);
         ''')
 
+#
+# VEX recipes
+#
+# These recipes can only be used with a VEX prefix, see `TailRecipe.vex()`.
+# The destination of the three-operand forms doesn't have to be one of the
+# sources, which saves the register copies of the two-operand SSE forms.
+#
+
+# VEX.vvvv XX /r with FPR ins and outs. The first operand is in VEX.vvvv.
+vfa = TailRecipe(
+        'vfa', Binary, size=1, ins=(FPR, FPR), outs=FPR,
+        clobbers_flags=False,
+        isap=use_avx,
+        requires_vex=True,
+        emit='''
+        PUT_OP(bits, rex2(in_reg1, out_reg0), in_reg0, sink);
+        modrm_rr(in_reg1, out_reg0, sink);
+        ''')
+
+# VEX.vvvv XX /r with FPR ins and outs. A form with input operands swapped.
+vfax = TailRecipe(
+        'vfax', Binary, size=1, ins=(FPR, FPR), outs=FPR,
+        clobbers_flags=False,
+        isap=use_avx,
+        requires_vex=True,
+        emit='''
+        PUT_OP(bits, rex2(in_reg0, out_reg0), in_reg1, sink);
+        modrm_rr(in_reg0, out_reg0, sink);
+        ''')
+
+# XX /r, RM form, FPR -> FPR, with an unused VEX.vvvv field.
+vfurm = TailRecipe(
+        'vfurm', Unary, size=1, ins=FPR, outs=FPR,
+        clobbers_flags=False,
+        isap=use_avx,
+        requires_vex=True,
+        emit='''
+        PUT_OP(bits, rex2(in_reg0, out_reg0), 0, sink);
+        modrm_rr(in_reg0, out_reg0, sink);
+        ''')
+
+# XX /r, for regmove instructions.
+vfrmov = TailRecipe(
+        'vfrmov', RegMove, size=1, ins=FPR, outs=(),
+        clobbers_flags=False,
+        isap=use_avx,
+        requires_vex=True,
+        emit='''
+        PUT_OP(bits, rex2(src, dst), 0, sink);
+        modrm_rr(src, dst, sink);
+        ''')
+
+# XX /r register-indirect store of FPR with no offset.
+vfst = TailRecipe(
+        'vfst', Store, size=1, ins=(FPR, GPR_ZERO_DEREF_SAFE), outs=(),
+        instp=IsEqual(Store.offset, 0),
+        clobbers_flags=False,
+        isap=use_avx,
+        requires_vex=True,
+        emit='''
+        if !flags.notrap() {
+            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+        }
+        PUT_OP(bits, rex2(in_reg1, in_reg0), 0, sink);
+        modrm_rm(in_reg1, in_reg0, sink);
+        ''')
+
+# XX /r register-indirect store of FPR with 8-bit offset.
+vfstDisp8 = TailRecipe(
+        'vfstDisp8', Store, size=2, ins=(FPR, GPR_DEREF_SAFE), outs=(),
+        instp=IsSignedInt(Store.offset, 8),
+        clobbers_flags=False,
+        isap=use_avx,
+        requires_vex=True,
+        emit='''
+        if !flags.notrap() {
+            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+        }
+        PUT_OP(bits, rex2(in_reg1, in_reg0), 0, sink);
+        modrm_disp8(in_reg1, in_reg0, sink);
+        let offset: i32 = offset.into();
+        sink.put1(offset as u8);
+        ''')
+
+# XX /r register-indirect store of FPR with 32-bit offset.
+vfstDisp32 = TailRecipe(
+        'vfstDisp32', Store, size=5, ins=(FPR, GPR_DEREF_SAFE), outs=(),
+        clobbers_flags=False,
+        isap=use_avx,
+        requires_vex=True,
+        emit='''
+        if !flags.notrap() {
+            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+        }
+        PUT_OP(bits, rex2(in_reg1, in_reg0), 0, sink);
+        modrm_disp32(in_reg1, in_reg0, sink);
+        let offset: i32 = offset.into();
+        sink.put4(offset as u32);
+        ''')
+
+# Spill of FPR using RSP-relative addressing.
+vfspillSib32 = TailRecipe(
+        'vfspillSib32', Unary, size=6, ins=FPR, outs=StackFPR32,
+        clobbers_flags=False,
+        isap=use_avx,
+        requires_vex=True,
+        emit='''
+        sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+        let base = stk_base(out_stk0.base);
+        PUT_OP(bits, rex2(base, in_reg0), 0, sink);
+        modrm_sib_disp32(in_reg0, sink);
+        sib_noindex(base, sink);
+        sink.put4(out_stk0.offset as u32);
+        ''')
+
+# Regspill of FPR using RSP-relative addressing.
+vfregspill32 = TailRecipe(
+        'vfregspill32', RegSpill, size=6, ins=FPR, outs=(),
+        clobbers_flags=False,
+        isap=use_avx,
+        requires_vex=True,
+        emit='''
+        sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+        let dst = StackRef::sp(dst, &func.stack_slots);
+        let base = stk_base(dst.base);
+        PUT_OP(bits, rex2(base, src), 0, sink);
+        modrm_sib_disp32(src, sink);
+        sib_noindex(base, sink);
+        sink.put4(dst.offset as u32);
+        ''')
+
+# XX /r load of FPR with no offset.
+vfld = TailRecipe(
+        'vfld', Load, size=1, ins=(GPR_ZERO_DEREF_SAFE), outs=(FPR),
+        instp=IsEqual(Load.offset, 0),
+        clobbers_flags=False,
+        isap=use_avx,
+        requires_vex=True,
+        emit='''
+        if !flags.notrap() {
+            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+        }
+        PUT_OP(bits, rex2(in_reg0, out_reg0), 0, sink);
+        modrm_rm(in_reg0, out_reg0, sink);
+        ''')
+
+# XX /r load of FPR with 8-bit offset.
+vfldDisp8 = TailRecipe(
+        'vfldDisp8', Load, size=2, ins=(GPR_DEREF_SAFE), outs=(FPR),
+        instp=IsSignedInt(Load.offset, 8),
+        clobbers_flags=False,
+        isap=use_avx,
+        requires_vex=True,
+        emit='''
+        if !flags.notrap() {
+            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+        }
+        PUT_OP(bits, rex2(in_reg0, out_reg0), 0, sink);
+        modrm_disp8(in_reg0, out_reg0, sink);
+        let offset: i32 = offset.into();
+        sink.put1(offset as u8);
+        ''')
+
+# XX /r load of FPR with 32-bit offset.
+vfldDisp32 = TailRecipe(
+        'vfldDisp32', Load, size=5, ins=(GPR_DEREF_SAFE), outs=(FPR),
+        instp=IsSignedInt(Load.offset, 32),
+        clobbers_flags=False,
+        isap=use_avx,
+        requires_vex=True,
+        emit='''
+        if !flags.notrap() {
+            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+        }
+        PUT_OP(bits, rex2(in_reg0, out_reg0), 0, sink);
+        modrm_disp32(in_reg0, out_reg0, sink);
+        let offset: i32 = offset.into();
+        sink.put4(offset as u32);
+        ''')
+
+# Fill of FPR with RSP-relative 32-bit displacement.
+vffillSib32 = TailRecipe(
+        'vffillSib32', Unary, size=6, ins=StackFPR32, outs=FPR,
+        clobbers_flags=False,
+        isap=use_avx,
+        requires_vex=True,
+        emit='''
+        let base = stk_base(in_stk0.base);
+        PUT_OP(bits, rex2(base, out_reg0), 0, sink);
+        modrm_sib_disp32(out_reg0, sink);
+        sib_noindex(base, sink);
+        sink.put4(in_stk0.offset as u32);
+        ''')
+
+# Regfill of FPR with RSP-relative 32-bit displacement.
+vfregfill32 = TailRecipe(
+        'vfregfill32', RegFill, size=6, ins=StackFPR32, outs=(),
+        clobbers_flags=False,
+        isap=use_avx,
+        requires_vex=True,
+        emit='''
+        let src = StackRef::sp(src, &func.stack_slots);
+        let base = stk_base(src.base);
+        PUT_OP(bits, rex2(base, dst), 0, sink);
+        modrm_sib_disp32(dst, sink);
+        sib_noindex(base, sink);
+        sink.put4(src.offset as u32);
+        ''')
+
+# Split a YMM register into its XMM halves with vextractf128. The low half is
+# the XMM register aliasing the input, so only the high half is extracted.
+vfsplit = TailRecipe(
+        'vfsplit', Unary, size=2, ins=FPR, outs=(0, FPR),
+        clobbers_flags=False,
+        isap=use_avx,
+        requires_vex=True,
+        emit='''
+        PUT_OP(bits, rex2(out_reg1, in_reg0), 0, sink);
+        modrm_rr(out_reg1, in_reg0, sink);
+        sink.put1(1);
+        ''')
+
+# Concatenate two XMM registers into a YMM register with vinsertf128.
+vfconcat = TailRecipe(
+        'vfconcat', Binary, size=2, ins=(FPR, FPR), outs=FPR,
+        clobbers_flags=False,
+        isap=use_avx,
+        requires_vex=True,
+        emit='''
+        PUT_OP(bits, rex2(in_reg1, out_reg0), in_reg0, sink);
+        modrm_rr(in_reg1, out_reg0, sink);
+        sink.put1(1);
+        ''')
+
+# An instruction without operands, such as vzeroupper.
+vop = TailRecipe(
+        'vop', NullAry, size=0, ins=(), outs=(),
+        clobbers_flags=False,
+        isap=use_avx,
+        requires_vex=True,
+        emit='''
+        PUT_OP(bits, BASE_REX, 0, sink);
+        ''')
+
+# VEX.vvvv XX /r with GPR ins and outs. The second operand is in VEX.vvvv.
+# This is the BMI1 andn instruction.
+vrrx = TailRecipe(
+        'vrrx', Binary, size=1, ins=(GPR, GPR), outs=GPR,
+        isap=use_bmi1,
+        requires_vex=True,
+        emit='''
+        PUT_OP(bits, rex2(in_reg0, out_reg0), in_reg1, sink);
+        modrm_rr(in_reg0, out_reg0, sink);
+        ''')
+
+# VEX.vvvv XX /r for the BMI2 shifts, which take the shift amount in VEX.vvvv
+# from any register and leave the flags alone.
+vrsh = TailRecipe(
+        'vrsh', Binary, size=1, ins=(GPR, GPR), outs=GPR,
+        clobbers_flags=False,
+        isap=use_bmi2,
+        requires_vex=True,
+        emit='''
+        PUT_OP(bits, rex2(in_reg0, out_reg0), in_reg1, sink);
+        modrm_rr(in_reg0, out_reg0, sink);
+        ''')
+
+# VEX.vvvv XX /n with a GPR input, and the GPR output in VEX.vvvv.
+vur = TailRecipe(
+        'vur', Unary, size=1, ins=GPR, outs=GPR,
+        isap=use_bmi1,
+        requires_vex=True,
+        emit='''
+        PUT_OP(bits, rex1(in_reg0), out_reg0, sink);
+        modrm_r_bits(in_reg0, bits, sink);
+        ''')
+
+# VEX XX /r ib with GPR ins and outs. This is the BMI2 rorx instruction.
+vr_ib = TailRecipe(
+        'vr_ib', BinaryImm, size=2, ins=GPR, outs=GPR,
+        clobbers_flags=False,
+        isap=use_bmi2,
+        requires_vex=True,
+        emit='''
+        PUT_OP(bits, rex2(in_reg0, out_reg0), 0, sink);
+        modrm_rr(in_reg0, out_reg0, sink);
+        let imm: i64 = imm.into();
+        sink.put1(imm as u8);
+        ''')
+
+#
+# Atomic memory operations.
+#
+
+# XX /r register-indirect read-modify-write with no offset.
+# The register operand is replaced by the old memory value, as for xchg.
+rmw = TailRecipe(
+        'rmw', Store, size=1, ins=(GPR, GPR_ZERO_DEREF_SAFE), outs=0,
+        instp=IsEqual(Store.offset, 0),
+        clobbers_flags=False,
+        emit='''
+        if !flags.notrap() {
+            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+        }
+        PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+        modrm_rm(in_reg1, in_reg0, sink);
+        ''')
+
+# XX /r register-indirect read-modify-write with 8-bit offset.
+# The register operand is replaced by the old memory value, as for xchg.
+rmwDisp8 = TailRecipe(
+        'rmwDisp8', Store, size=2, ins=(GPR, GPR_DEREF_SAFE), outs=0,
+        instp=IsSignedInt(Store.offset, 8),
+        clobbers_flags=False,
+        emit='''
+        if !flags.notrap() {
+            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+        }
+        PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+        modrm_disp8(in_reg1, in_reg0, sink);
+        let offset: i32 = offset.into();
+        sink.put1(offset as u8);
+        ''')
+
+# XX /r register-indirect read-modify-write with 32-bit offset.
+# The register operand is replaced by the old memory value, as for xchg.
+rmwDisp32 = TailRecipe(
+        'rmwDisp32', Store, size=5, ins=(GPR, GPR_DEREF_SAFE), outs=0,
+        clobbers_flags=False,
+        emit='''
+        if !flags.notrap() {
+            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+        }
+        PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+        modrm_disp32(in_reg1, in_reg0, sink);
+        let offset: i32 = offset.into();
+        sink.put4(offset as u32);
+        ''')
+
+# LOCK XX /r register-indirect read-modify-write with no offset.
+# The register operand is replaced by the old memory value, as for xadd.
+lrmw = TailRecipe(
+        'lrmw', Store, size=2, ins=(GPR, GPR_ZERO_DEREF_SAFE), outs=0,
+        instp=IsEqual(Store.offset, 0),
+        emit='''
+        if !flags.notrap() {
+            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+        }
+        sink.put1(0xf0);
+        PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+        modrm_rm(in_reg1, in_reg0, sink);
+        ''')
+
+# LOCK XX /r register-indirect read-modify-write with 8-bit offset.
+# The register operand is replaced by the old memory value, as for xadd.
+lrmwDisp8 = TailRecipe(
+        'lrmwDisp8', Store, size=3, ins=(GPR, GPR_DEREF_SAFE), outs=0,
+        instp=IsSignedInt(Store.offset, 8),
+        emit='''
+        if !flags.notrap() {
+            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+        }
+        sink.put1(0xf0);
+        PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+        modrm_disp8(in_reg1, in_reg0, sink);
+        let offset: i32 = offset.into();
+        sink.put1(offset as u8);
+        ''')
+
+# LOCK XX /r register-indirect read-modify-write with 32-bit offset.
+# The register operand is replaced by the old memory value, as for xadd.
+lrmwDisp32 = TailRecipe(
+        'lrmwDisp32', Store, size=6, ins=(GPR, GPR_DEREF_SAFE), outs=0,
+        emit='''
+        if !flags.notrap() {
+            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+        }
+        sink.put1(0xf0);
+        PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+        modrm_disp32(in_reg1, in_reg0, sink);
+        let offset: i32 = offset.into();
+        sink.put4(offset as u32);
+        ''')
+
+# LOCK XX /r register-indirect compare and swap.
+# The expected value and the result are in %rax, as for cmpxchg.
+lcas = TailRecipe(
+        'lcas', AtomicCas, size=2, ins=(GPR.rax, GPR, GPR_ZERO_DEREF_SAFE),
+        outs=GPR.rax,
+        emit='''
+        if !flags.notrap() {
+            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+        }
+        sink.put1(0xf0);
+        PUT_OP(bits, rex2(in_reg2, in_reg1), sink);
+        modrm_rm(in_reg2, in_reg1, sink);
+        ''')
+
+# XX /n with no operands, such as mfence.
+fence = TailRecipe(
+        'fence', NullAry, size=1, ins=(), outs=(),
+        clobbers_flags=False,
+        emit='''
+        PUT_OP(bits, BASE_REX, sink);
+        modrm_r_bits(0, bits, sink);
+        ''')
+
 TailRecipe.check_names(globals())
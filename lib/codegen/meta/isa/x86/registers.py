@@ -21,6 +21,11 @@ the REX prefix, you get the I32 registers.
 The 8-bit registers are not that useful since WebAssembly only has i32 and i64
 data types, and the H-registers even less so. Rather than trying to model the
 H-registers accurately, we'll avoid using them in both I32 and I64 modes.
+
+The AVX instructions operate on the 256-bit YMM registers, whose low 128 bits
+are the XMM registers. The floating-point register bank models both: A 256-bit
+vector takes a single register unit like any other value held in an FPR, and
+the register is named after its XMM half.
 """
 from __future__ import absolute_import
 from cdsl.registers import RegBank, RegClass, Stack
@@ -35,7 +40,7 @@ IntRegs = RegBank(
 
 FloatRegs = RegBank(
         'FloatRegs', ISA,
-        'SSE floating point registers',
+        'SSE and AVX floating point registers',
         units=16, prefix='xmm')
 
 FlagRegs = RegBank(
@@ -30,7 +30,7 @@
 use binemit::CodeOffset;
 use cursor::{Cursor, FuncCursor};
 use ir::{Function, InstructionData, Opcode};
-use isa::{EncInfo, TargetIsa};
+use isa::{EncInfo, Encoding, TargetIsa};
 use iterators::IteratorExtras;
 use timing;
 use CodegenResult;
@@ -128,6 +128,21 @@ fn fallthroughs(func: &mut Function) {
     }
 }
 
+/// Can an instruction encoded as `from` be re-encoded as `to` without checking its operands?
+///
+/// This is the case when the constraints of `to` are the same as, or less strict than, those of
+/// `from`. For example, a compressed RISC-V branch only accepts the registers `x8`-`x15`, so the
+/// longer range branch accepting any register can always replace it.
+fn compatible_constraints(encinfo: &EncInfo, from: Encoding, to: Encoding) -> bool {
+    match (
+        encinfo.operand_constraints(from),
+        encinfo.operand_constraints(to),
+    ) {
+        (Some(from), Some(to)) => to.includes(from),
+        (from, to) => from == to,
+    }
+}
+
 /// Relax the branch instruction at `cur` so it can cover the range `offset - dest_offset`.
 ///
 /// Return the size of the replacement instructions up to and including the location where `cur` is
@@ -157,10 +172,8 @@ fn relax_branch(
             if !range.contains(offset, dest_offset) {
                 dbg!("  trying [{}]: out of range", encinfo.display(enc));
                 false
-            } else if encinfo.operand_constraints(enc)
-                != encinfo.operand_constraints(cur.func.encodings[inst])
-            {
-                // Conservatively give up if the encoding has different constraints
+            } else if !compatible_constraints(encinfo, cur.func.encodings[inst], enc) {
+                // Conservatively give up if the encoding has stricter constraints
                 // than the original, so that we don't risk picking a new encoding
                 // which the existing operands don't satisfy. We can't check for
                 // validity directly because we don't have a RegDiversions active so
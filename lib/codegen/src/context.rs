@@ -301,7 +301,12 @@ impl Context {
             self.func.stack_limit.is_none(),
             "stack_limit isn't implemented yet"
         );
+        let num_ebbs = self.func.dfg.num_ebbs();
         isa.prologue_epilogue(&mut self.func)?;
+        // Stack probing loops add EBBs to the function.
+        if self.func.dfg.num_ebbs() != num_ebbs {
+            self.flowgraph();
+        }
         self.verify_if(isa)?;
         self.verify_locations_if(isa)?;
         Ok(())
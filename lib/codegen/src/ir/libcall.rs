@@ -38,9 +38,31 @@ pub enum LibCall {
     NearestF32,
     /// nearest.f64
     NearestF64,
+    /// udiv.i64
+    UdivI64,
+    /// sdiv.i64
+    SdivI64,
+    /// urem.i64
+    UremI64,
+    /// srem.i64
+    SremI64,
+    /// ishl.i64
+    IshlI64,
+    /// ushr.i64
+    UshrI64,
+    /// sshr.i64
+    SshrI64,
+    /// fcvt_to_sint.i64
+    FcvtToSintI64,
+    /// fcvt_to_uint.i64
+    FcvtToUintI64,
+    /// fcvt_from_sint.f32
+    FcvtFromSintF32,
+    /// fcvt_from_sint.f64
+    FcvtFromSintF64,
 }
 
-const NAME: [&str; 9] = [
+const NAME: [&str; 20] = [
     "Probestack",
     "CeilF32",
     "CeilF64",
@@ -50,6 +72,17 @@ const NAME: [&str; 9] = [
     "TruncF64",
     "NearestF32",
     "NearestF64",
+    "UdivI64",
+    "SdivI64",
+    "UremI64",
+    "SremI64",
+    "IshlI64",
+    "UshrI64",
+    "SshrI64",
+    "FcvtToSintI64",
+    "FcvtToUintI64",
+    "FcvtFromSintF32",
+    "FcvtFromSintF64",
 ];
 
 impl fmt::Display for LibCall {
@@ -72,6 +105,17 @@ impl FromStr for LibCall {
             "TruncF64" => Ok(LibCall::TruncF64),
             "NearestF32" => Ok(LibCall::NearestF32),
             "NearestF64" => Ok(LibCall::NearestF64),
+            "UdivI64" => Ok(LibCall::UdivI64),
+            "SdivI64" => Ok(LibCall::SdivI64),
+            "UremI64" => Ok(LibCall::UremI64),
+            "SremI64" => Ok(LibCall::SremI64),
+            "IshlI64" => Ok(LibCall::IshlI64),
+            "UshrI64" => Ok(LibCall::UshrI64),
+            "SshrI64" => Ok(LibCall::SshrI64),
+            "FcvtToSintI64" => Ok(LibCall::FcvtToSintI64),
+            "FcvtToUintI64" => Ok(LibCall::FcvtToUintI64),
+            "FcvtFromSintF32" => Ok(LibCall::FcvtFromSintF32),
+            "FcvtFromSintF64" => Ok(LibCall::FcvtFromSintF64),
             _ => Err(()),
         }
     }
@@ -82,13 +126,31 @@ impl LibCall {
     /// given opcode and controlling type variable.
     ///
     /// Returns `None` if no well-known library routine name exists for that instruction.
+    ///
+    /// The 64-bit integer routines are used on 32-bit targets. The legalizer traps on the operands
+    /// the instructions don't define before calling them, and it passes shift amounts modulo 64,
+    /// so the routines only see operands with a well-defined result. The integer to float
+    /// conversions are only reached with a 64-bit integer argument.
     pub fn for_inst(opcode: Opcode, ctrl_type: Type) -> Option<Self> {
         Some(match ctrl_type {
+            types::I64 => match opcode {
+                Opcode::Udiv => LibCall::UdivI64,
+                Opcode::Sdiv => LibCall::SdivI64,
+                Opcode::Urem => LibCall::UremI64,
+                Opcode::Srem => LibCall::SremI64,
+                Opcode::Ishl => LibCall::IshlI64,
+                Opcode::Ushr => LibCall::UshrI64,
+                Opcode::Sshr => LibCall::SshrI64,
+                Opcode::FcvtToSint => LibCall::FcvtToSintI64,
+                Opcode::FcvtToUint => LibCall::FcvtToUintI64,
+                _ => return None,
+            },
             types::F32 => match opcode {
                 Opcode::Ceil => LibCall::CeilF32,
                 Opcode::Floor => LibCall::FloorF32,
                 Opcode::Trunc => LibCall::TruncF32,
                 Opcode::Nearest => LibCall::NearestF32,
+                Opcode::FcvtFromSint => LibCall::FcvtFromSintF32,
                 _ => return None,
             },
             types::F64 => match opcode {
@@ -96,6 +158,7 @@ impl LibCall {
                 Opcode::Floor => LibCall::FloorF64,
                 Opcode::Trunc => LibCall::TruncF64,
                 Opcode::Nearest => LibCall::NearestF64,
+                Opcode::FcvtFromSint => LibCall::FcvtFromSintF64,
                 _ => return None,
             },
             _ => return None,
@@ -201,10 +264,14 @@ mod test {
     fn display() {
         assert_eq!(LibCall::CeilF32.to_string(), "CeilF32");
         assert_eq!(LibCall::NearestF64.to_string(), "NearestF64");
+        assert_eq!(LibCall::SdivI64.to_string(), "SdivI64");
+        assert_eq!(LibCall::FcvtFromSintF64.to_string(), "FcvtFromSintF64");
     }
 
     #[test]
     fn parsing() {
         assert_eq!("FloorF32".parse(), Ok(LibCall::FloorF32));
+        assert_eq!("UshrI64".parse(), Ok(LibCall::UshrI64));
+        assert_eq!("FcvtToUintI64".parse(), Ok(LibCall::FcvtToUintI64));
     }
 }
@@ -9,11 +9,62 @@ enum FlagBit {
 
 const NAMES: [&str; 2] = ["notrap", "aligned"];
 
+/// The bits above the flag bits hold the atomic ordering, or 0 when no ordering is given.
+const ORDERING_SHIFT: usize = 2;
+const ORDERING_MASK: u8 = 0b111 << ORDERING_SHIFT;
+
+const ORDERINGS: [AtomicOrdering; 5] = [
+    AtomicOrdering::Relaxed,
+    AtomicOrdering::Acquire,
+    AtomicOrdering::Release,
+    AtomicOrdering::AcqRel,
+    AtomicOrdering::SeqCst,
+];
+
+/// Memory ordering constraint of an atomic memory operation.
+///
+/// These orderings have the same meaning as the C++11 memory orderings of the same names.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum AtomicOrdering {
+    /// No ordering constraints beyond the atomicity of the operation itself.
+    Relaxed,
+    /// Later memory operations can't be reordered before this operation.
+    Acquire,
+    /// Earlier memory operations can't be reordered after this operation.
+    Release,
+    /// Both `Acquire` and `Release`.
+    AcqRel,
+    /// `AcqRel`, and all sequentially consistent operations appear in a single total order.
+    SeqCst,
+}
+
+impl AtomicOrdering {
+    /// Get the name of this ordering as it appears in the textual IR.
+    pub fn name(self) -> &'static str {
+        match self {
+            AtomicOrdering::Relaxed => "relaxed",
+            AtomicOrdering::Acquire => "acquire",
+            AtomicOrdering::Release => "release",
+            AtomicOrdering::AcqRel => "acq_rel",
+            AtomicOrdering::SeqCst => "seq_cst",
+        }
+    }
+}
+
+impl fmt::Display for AtomicOrdering {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
 /// Flags for memory operations like load/store.
 ///
 /// Each of these flags introduce a limited form of undefined behavior. The flags each enable
 /// certain optimizations that need to make additional assumptions. Generally, the semantics of a
 /// program does not change when a flag is removed, but adding a flag will.
+///
+/// The flags also carry an optional `AtomicOrdering` which is only meaningful for atomic memory
+/// operations. Atomic operations without an explicit ordering are sequentially consistent.
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
 pub struct MemFlags {
     bits: u8,
@@ -37,11 +88,17 @@ impl MemFlags {
 
     /// Set a flag bit by name.
     ///
+    /// The names of the atomic orderings are also accepted, and replace any previous ordering.
+    ///
     /// Returns true if the flag was found and set, false for an unknown flag name.
     pub fn set_by_name(&mut self, name: &str) -> bool {
-        match NAMES.iter().position(|&s| s == name) {
-            Some(bit) => {
-                self.bits |= 1 << bit;
+        if let Some(bit) = NAMES.iter().position(|&s| s == name) {
+            self.bits |= 1 << bit;
+            return true;
+        }
+        match ORDERINGS.iter().find(|o| o.name() == name) {
+            Some(&ordering) => {
+                self.set_ordering(ordering);
                 true
             }
             None => false,
@@ -79,6 +136,28 @@ impl MemFlags {
     pub fn set_aligned(&mut self) {
         self.set(FlagBit::Aligned)
     }
+
+    /// Get the atomic ordering, if one was set.
+    ///
+    /// Only atomic memory operations can have an ordering, and they treat a missing ordering as
+    /// `SeqCst`.
+    pub fn ordering(self) -> Option<AtomicOrdering> {
+        match (self.bits & ORDERING_MASK) >> ORDERING_SHIFT {
+            0 => None,
+            n => Some(ORDERINGS[n as usize - 1]),
+        }
+    }
+
+    /// Set the atomic ordering, replacing any previous ordering.
+    pub fn set_ordering(&mut self, ordering: AtomicOrdering) {
+        let n = ORDERINGS.iter().position(|&o| o == ordering).unwrap() as u8 + 1;
+        self.bits = (self.bits & !ORDERING_MASK) | (n << ORDERING_SHIFT);
+    }
+
+    /// Remove the atomic ordering.
+    pub fn clear_ordering(&mut self) {
+        self.bits &= !ORDERING_MASK;
+    }
 }
 
 impl fmt::Display for MemFlags {
@@ -88,6 +167,9 @@ impl fmt::Display for MemFlags {
                 write!(f, " {}", n)?;
             }
         }
+        if let Some(ordering) = self.ordering() {
+            write!(f, " {}", ordering)?;
+        }
         Ok(())
     }
 }
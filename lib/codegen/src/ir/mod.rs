@@ -36,7 +36,7 @@ pub use ir::instructions::{InstructionData, Opcode, ValueList, ValueListPool, Va
 pub use ir::jumptable::JumpTableData;
 pub use ir::layout::Layout;
 pub use ir::libcall::{get_libcall_funcref, get_probestack_funcref, LibCall};
-pub use ir::memflags::MemFlags;
+pub use ir::memflags::{AtomicOrdering, MemFlags};
 pub use ir::progpoint::{ExpandedProgramPoint, ProgramOrder, ProgramPoint};
 pub use ir::sourceloc::SourceLoc;
 pub use ir::stackslot::{StackSlotData, StackSlotKind, StackSlots};
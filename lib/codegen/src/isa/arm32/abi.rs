@@ -1,23 +1,155 @@
 //! ARM ABI implementation.
+//!
+//! This follows the hard-float variant of the AAPCS procedure call standard:
+//!
+//! - Integer arguments and return values are passed in `r0`-`r3`. 64-bit integers are split
+//!   into an even-numbered register pair.
+//! - Floating point arguments and return values are passed in `s0`-`s15`, which alias `d0`-`d7`.
+//!   Single precision arguments back-fill the registers skipped to align double precision ones.
+//! - Remaining arguments are passed on the stack in 4-byte slots, 8-byte aligned for 64-bit
+//!   types.
+//! - `r4`-`r11` and `d8`-`d15` are callee-saved.
+//! - The stack pointer is always 8-byte aligned at function calls.
 
 use super::registers::{D, GPR, Q, S};
+use abi::{legalize_args, ArgAction, ArgAssigner, ValueConversion};
+use cursor::{Cursor, CursorPosition, EncCursor};
 use ir;
-use isa::RegClass;
+use ir::immediates::Imm64;
+use ir::stackslot::StackSize;
+use ir::types::{F64, I32};
+use ir::{AbiParam, ArgumentExtension, ArgumentLoc, ArgumentPurpose, InstBuilder, ValueLoc};
+use isa::{RegClass, RegUnit, TargetIsa};
 use regalloc::RegisterSet;
+use result::{CodegenError, CodegenResult};
 use settings as shared_settings;
+use stack_layout::layout_stack;
+use std::i32;
+use std::vec::Vec;
+
+/// Number of integer registers used for passing arguments.
+const GPR_ARGS: usize = 4;
+
+/// Number of single precision registers used for passing arguments.
+const S_ARGS: usize = 16;
+
+/// The scratch register `ip`, used for materializing large offsets.
+const IP: usize = 12;
+
+/// The frame pointer register, `r11`.
+const FP: usize = 11;
+
+/// The stack pointer register, `r13`.
+const SP: usize = 13;
+
+/// The link register, `r14`.
+const LR: usize = 14;
+
+/// The program counter, `r15`.
+const PC: usize = 15;
+
+struct Args {
+    gpr_used: usize,
+    /// Bit mask of the single precision argument registers in use.
+    s_used: u16,
+    /// Once a floating point argument is passed on the stack, all the following ones are too.
+    vfp_stack: bool,
+    offset: u32,
+}
+
+impl Args {
+    fn new() -> Self {
+        Self {
+            gpr_used: 0,
+            s_used: 0,
+            vfp_stack: false,
+            offset: 0,
+        }
+    }
+
+    /// Allocate `count` consecutive single precision registers, aligned to `count`.
+    fn alloc_s(&mut self, count: usize) -> Option<usize> {
+        if self.vfp_stack {
+            return None;
+        }
+        let mask = (1 << count) - 1;
+        let found = (0..S_ARGS)
+            .step_by(count)
+            .find(|&n| self.s_used & (mask << n) == 0);
+        match found {
+            Some(n) => self.s_used |= mask << n,
+            None => self.vfp_stack = true,
+        }
+        found
+    }
+}
+
+impl ArgAssigner for Args {
+    fn assign(&mut self, arg: &AbiParam) -> ArgAction {
+        let ty = arg.value_type;
+
+        // We don't support SIMD yet, so break all vectors down.
+        if ty.is_vector() {
+            return ValueConversion::VectorSplit.into();
+        }
+
+        // Large integers and booleans are broken down to fit in a register.
+        if !ty.is_float() && ty.bits() > 32 {
+            // The halves of a 64-bit value are passed in an even-numbered register pair, or in
+            // an 8-byte aligned stack location.
+            self.gpr_used += self.gpr_used & 1;
+            self.offset = (self.offset + 7) & !7;
+            return ValueConversion::IntSplit.into();
+        }
+
+        // Small integers are extended to the size of a register.
+        if ty.is_int() && ty.bits() < 32 {
+            match arg.extension {
+                ArgumentExtension::None => {}
+                ArgumentExtension::Uext => return ValueConversion::Uext(I32).into(),
+                ArgumentExtension::Sext => return ValueConversion::Sext(I32).into(),
+            }
+        }
+
+        if ty.is_float() {
+            let double = ty.bits() == 64;
+            if let Some(n) = self.alloc_s(if double { 2 } else { 1 }) {
+                let reg = if double { D.unit(n / 2) } else { S.unit(n) };
+                return ArgumentLoc::Reg(reg).into();
+            }
+            if double {
+                self.offset = (self.offset + 7) & !7;
+            }
+        } else if self.gpr_used < GPR_ARGS {
+            let reg = GPR.unit(self.gpr_used);
+            self.gpr_used += 1;
+            return ArgumentLoc::Reg(reg).into();
+        }
+
+        // Assign a stack location.
+        let loc = ArgumentLoc::Stack(self.offset as i32);
+        self.offset += ty.bytes().max(4);
+        debug_assert!(self.offset <= i32::MAX as u32);
+        loc.into()
+    }
+}
 
 /// Legalize `sig`.
 pub fn legalize_signature(
-    _sig: &mut ir::Signature,
+    sig: &mut ir::Signature,
     _flags: &shared_settings::Flags,
     _current: bool,
 ) {
-    unimplemented!()
+    let mut args = Args::new();
+    legalize_args(&mut sig.params, &mut args);
+
+    let mut rets = Args::new();
+    legalize_args(&mut sig.returns, &mut rets);
 }
 
 /// Get register class for a type appearing in a legalized signature.
 pub fn regclass_for_abi_type(ty: ir::Type) -> RegClass {
-    if ty.is_int() {
+    if ty.is_int() || ty.is_bool() {
         GPR
     } else {
         match ty.bits() {
@@ -31,5 +163,174 @@ pub fn regclass_for_abi_type(ty: ir::Type) -> RegClass {
 
 /// Get the set of allocatable registers for `func`.
 pub fn allocatable_registers(_func: &ir::Function) -> RegisterSet {
-    unimplemented!()
+    let mut regs = RegisterSet::new();
+    regs.take(GPR, GPR.unit(FP));
+    regs.take(GPR, GPR.unit(IP));
+    regs.take(GPR, GPR.unit(SP));
+    regs.take(GPR, GPR.unit(PC));
+    // `d16`-`d31` don't alias single precision registers, which the integer conversions use to
+    // access the low half of a double precision register.
+    for n in 16..32 {
+        regs.take(D, D.unit(n));
+    }
+    regs
+}
+
+/// Get the callee-saved register holding the register unit `ru`, if any.
+///
+/// A single precision register is saved with the double precision register it is part of.
+fn callee_saved(ru: RegUnit) -> Option<(RegUnit, ir::Type)> {
+    if ru >= GPR.unit(4) && ru <= GPR.unit(10) {
+        Some((ru, I32))
+    } else if ru >= D.unit(8) && ru < D.unit(16) {
+        Some((ru & !1, F64))
+    } else {
+        None
+    }
+}
+
+/// Get the callee-saved registers which are used by `func`, integer registers first.
+fn callee_saved_used(func: &ir::Function) -> Vec<(RegUnit, ir::Type)> {
+    let mut used = Vec::new();
+    let mut add = |ru| {
+        if let Some(csr) = callee_saved(ru) {
+            if !used.contains(&csr) {
+                used.push(csr);
+            }
+        }
+    };
+
+    for value_loc in func.locations.values() {
+        if let ValueLoc::Reg(ru) = *value_loc {
+            add(ru);
+        }
+    }
+
+    // regmove and regfill instructions may temporarily divert values into other registers,
+    // and these are not reflected in `func.locations`.
+    for ebb in &func.layout {
+        for inst in func.layout.ebb_insts(ebb) {
+            match func.dfg[inst] {
+                ir::instructions::InstructionData::RegMove { dst, .. }
+                | ir::instructions::InstructionData::RegFill { dst, .. } => add(dst),
+                _ => (),
+            }
+        }
+    }
+
+    used.sort_by_key(|&(ru, ty)| (ty == F64, ru));
+    used
+}
+
+/// Insert a prologue and epilogues for `func`.
+pub fn prologue_epilogue(func: &mut ir::Function, isa: &TargetIsa) -> CodegenResult<()> {
+    let csrs = callee_saved_used(func);
+
+    // The reserved stack area is composed of the link register and the frame pointer, followed
+    // by the callee-saved registers.
+    let csr_stack_size = csrs
+        .iter()
+        .fold(8, |size, &(_, ty)| size + ty.bytes() as i32);
+    func.create_stack_slot(ir::StackSlotData {
+        kind: ir::StackSlotKind::IncomingArg,
+        size: csr_stack_size as u32,
+        offset: Some(-csr_stack_size),
+    });
+
+    // Stack slots are addressed with 16-bit offsets from the stack pointer.
+    let total_stack_size = layout_stack(&mut func.stack_slots, 8)?;
+    if total_stack_size >= 1 << 16 {
+        return Err(CodegenError::ImplLimitExceeded);
+    }
+    let local_stack_size = i64::from(total_stack_size - csr_stack_size as StackSize);
+
+    // Add the special registers to the function signature.
+    let lr_arg = AbiParam::special_reg(I32, ArgumentPurpose::Link, GPR.unit(LR));
+    let fp_arg = AbiParam::special_reg(I32, ArgumentPurpose::FramePointer, GPR.unit(FP));
+    func.signature.params.push(lr_arg);
+    func.signature.returns.push(lr_arg);
+    func.signature.params.push(fp_arg);
+    func.signature.returns.push(fp_arg);
+
+    for &(ru, ty) in &csrs {
+        let csr_arg = AbiParam::special_reg(ty, ArgumentPurpose::CalleeSaved, ru);
+        func.signature.params.push(csr_arg);
+        func.signature.returns.push(csr_arg);
+    }
+
+    // Set up the cursor and insert the prologue.
+    let entry_ebb = func.layout.entry_block().expect("missing entry block");
+    let mut pos = EncCursor::new(func, isa).at_first_insertion_point(entry_ebb);
+    insert_prologue(&mut pos, local_stack_size, &csrs);
+
+    // Reset the cursor and insert the epilogues.
+    let mut pos = pos.at_position(CursorPosition::Nowhere);
+    while let Some(ebb) = pos.next_ebb() {
+        pos.goto_last_inst(ebb);
+        if let Some(inst) = pos.current_inst() {
+            if pos.func.dfg[inst].opcode().is_return() {
+                insert_epilogue(inst, local_stack_size, &mut pos, &csrs);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Append an entry EBB parameter living in `ru`.
+fn append_ebb_reg(pos: &mut EncCursor, ebb: ir::Ebb, ty: ir::Type, ru: RegUnit) -> ir::Value {
+    let value = pos.func.dfg.append_ebb_param(ebb, ty);
+    pos.func.locations[value] = ValueLoc::Reg(ru);
+    value
+}
+
+/// Insert the prologue at the cursor position in the entry EBB.
+fn insert_prologue(pos: &mut EncCursor, stack_size: i64, csrs: &[(RegUnit, ir::Type)]) {
+    let ebb = pos.current_ebb().expect("missing ebb under cursor");
+    let lr = append_ebb_reg(pos, ebb, I32, GPR.unit(LR));
+    let fp = append_ebb_reg(pos, ebb, I32, GPR.unit(FP));
+
+    pos.ins().arm32_push(lr);
+    pos.ins().arm32_push(fp);
+    pos.ins().copy_special(GPR.unit(SP), GPR.unit(FP));
+
+    for &(ru, ty) in csrs {
+        let x = append_ebb_reg(pos, ebb, ty, ru);
+        pos.ins().arm32_push(x);
+    }
+
+    // Allocate stack frame storage.
+    if stack_size > 0 {
+        pos.ins().adjust_sp_down_imm(Imm64::new(stack_size));
+    }
+}
+
+/// Insert an epilogue given a specific `return` instruction.
+fn insert_epilogue(
+    inst: ir::Inst,
+    stack_size: i64,
+    pos: &mut EncCursor,
+    csrs: &[(RegUnit, ir::Type)],
+) {
+    if stack_size > 0 {
+        pos.ins().adjust_sp_up_imm(Imm64::new(stack_size));
+    }
+
+    // Pop all the saved registers, stepping backward each time to preserve the correct order.
+    let lr = pos.ins().arm32_pop(I32);
+    pos.prev_inst();
+    pos.func.locations[lr] = ValueLoc::Reg(GPR.unit(LR));
+    pos.func.dfg.append_inst_arg(inst, lr);
+
+    let fp = pos.ins().arm32_pop(I32);
+    pos.prev_inst();
+    pos.func.locations[fp] = ValueLoc::Reg(GPR.unit(FP));
+    pos.func.dfg.append_inst_arg(inst, fp);
+
+    for &(ru, ty) in csrs {
+        let x = pos.ins().arm32_pop(ty);
+        pos.prev_inst();
+        pos.func.locations[x] = ValueLoc::Reg(ru);
+        pos.func.dfg.append_inst_arg(inst, x);
+    }
 }
@@ -1,7 +1,633 @@
 //! Emitting binary ARM32 machine code.
+//!
+//! The encoding bits of a recipe hold the opcode bits of the instructions it encodes. Their
+//! layout depends on the instruction class, and is described for each `put_*` function below.
+//! All the instructions are emitted with the `al` condition, except where noted.
 
-use binemit::{bad_encoding, CodeSink};
-use ir::{Function, Inst};
+use binemit::{bad_encoding, CodeSink, Reloc};
+use ir::condcodes::{FloatCC, IntCC};
+use ir::{Function, Inst, InstructionData, SourceLoc, TrapCode};
+use isa::{RegUnit, StackBaseMask, StackRef};
+use predicates::is_signed_int;
 use regalloc::RegDiversions;
 
 include!(concat!(env!("OUT_DIR"), "/binemit-arm32.rs"));
+
+/// The scratch register `ip` used to materialize large offsets and intermediate results.
+const SCRATCH: u32 = 12;
+
+/// The `put_a32` encoding bits of `movw`. Setting bit 2 gives `movt`.
+const MOVW: u16 = 0x30;
+
+/// The `put_vmov` encoding bits of `vmov sn, rt`.
+const VMOV_TO_S: u16 = 0x2e0;
+
+// A32 condition codes.
+const COND_EQ: u32 = 0x0;
+const COND_NE: u32 = 0x1;
+const COND_HS: u32 = 0x2;
+const COND_LO: u32 = 0x3;
+const COND_MI: u32 = 0x4;
+const COND_PL: u32 = 0x5;
+const COND_VS: u32 = 0x6;
+const COND_VC: u32 = 0x7;
+const COND_HI: u32 = 0x8;
+const COND_LS: u32 = 0x9;
+const COND_GE: u32 = 0xa;
+const COND_LT: u32 = 0xb;
+const COND_GT: u32 = 0xc;
+const COND_LE: u32 = 0xd;
+const COND_AL: u32 = 0xe;
+
+/// Get the A32 condition code to test after comparing integers with `cmp`.
+fn icc2cond(cond: IntCC) -> u32 {
+    use ir::condcodes::IntCC::*;
+    match cond {
+        Equal => COND_EQ,
+        NotEqual => COND_NE,
+        SignedLessThan => COND_LT,
+        SignedGreaterThanOrEqual => COND_GE,
+        SignedGreaterThan => COND_GT,
+        SignedLessThanOrEqual => COND_LE,
+        UnsignedLessThan => COND_LO,
+        UnsignedGreaterThanOrEqual => COND_HS,
+        UnsignedGreaterThan => COND_HI,
+        UnsignedLessThanOrEqual => COND_LS,
+    }
+}
+
+/// Get the A32 condition code to test after comparing floating point numbers with `vcmp` and
+/// copying the flags with `vmrs`.
+///
+/// An unordered comparison sets the C and V flags, so the conditions testing them are the
+/// unordered ones.
+fn fcc2cond(cond: FloatCC) -> u32 {
+    use ir::condcodes::FloatCC::*;
+    match cond {
+        Equal => COND_EQ,
+        NotEqual => COND_NE,
+        LessThan => COND_MI,
+        LessThanOrEqual => COND_LS,
+        GreaterThan => COND_GT,
+        GreaterThanOrEqual => COND_GE,
+        Unordered => COND_VS,
+        Ordered => COND_VC,
+        UnorderedOrLessThan => COND_LT,
+        UnorderedOrLessThanOrEqual => COND_LE,
+        UnorderedOrGreaterThan => COND_HI,
+        UnorderedOrGreaterThanOrEqual => COND_PL,
+        UnorderedOrEqual | OrderedNotEqual => panic!("{} needs two conditions", cond),
+    }
+}
+
+/// Data processing, multiplication and load/store instructions with four register fields.
+///
+///   31   27 19 15 11 7   3
+///   cond op rn rd rs op2 rm
+///     28 20 16 12  8   4  0
+///
+/// Encoding bits: `inst[27:20] | (inst[7:4] << 8)`.
+fn put_a32<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    rd: RegUnit,
+    rs: RegUnit,
+    rm: RegUnit,
+    sink: &mut CS,
+) {
+    let bits = u32::from(bits);
+    let rn = u32::from(rn) & 0xf;
+    let rd = u32::from(rd) & 0xf;
+    let rs = u32::from(rs) & 0xf;
+    let rm = u32::from(rm) & 0xf;
+
+    let mut i = COND_AL << 28;
+    i |= (bits & 0xff) << 20;
+    i |= rn << 16;
+    i |= rd << 12;
+    i |= rs << 8;
+    i |= ((bits >> 8) & 0xf) << 4;
+    i |= rm;
+
+    sink.put4(i);
+}
+
+/// Data processing instructions with an unrotated 8-bit immediate.
+///
+///   31   27 19 15 11  7
+///   cond op rn rd 0000 imm8
+///     28 20 16 12    8    0
+///
+/// Encoding bits: `inst[27:20]`.
+fn put_dpi<CS: CodeSink + ?Sized>(bits: u16, rn: RegUnit, imm: i64, rd: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let rn = u32::from(rn) & 0xf;
+    let rd = u32::from(rd) & 0xf;
+
+    debug_assert!(imm >= 0 && imm < 0x100, "immediate out of range {:#x}", imm);
+
+    let mut i = COND_AL << 28;
+    i |= (bits & 0xff) << 20;
+    i |= rn << 16;
+    i |= rd << 12;
+    i |= imm as u32 & 0xff;
+
+    sink.put4(i);
+}
+
+/// Shifts by an immediate amount, as aliases of `mov` with a shifted register operand.
+///
+///   31   27 15 11   6    4 3
+///   cond op rd imm5 type 0 rm
+///     28 20 12    7    5    0
+///
+/// Encoding bits: `inst[27:20] | (inst[7:4] << 8)`.
+fn put_shi<CS: CodeSink + ?Sized>(bits: u16, rm: RegUnit, imm: i64, rd: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let rm = u32::from(rm) & 0xf;
+    let rd = u32::from(rd) & 0xf;
+    let amount = imm as u32 & 31;
+
+    let mut i = COND_AL << 28;
+    i |= (bits & 0xff) << 20;
+    i |= rd << 12;
+    i |= amount << 7;
+    // A zero amount encodes a shift by 32 for the right shifts, and `rrx` for `ror`. Shifting by
+    // zero is a plain `mov`, which is a left shift by zero.
+    if amount != 0 {
+        i |= ((bits >> 8) & 0xf) << 4;
+    }
+    i |= rm;
+
+    sink.put4(i);
+}
+
+/// Shifts by a register amount, as aliases of `mov` with a register-shifted register operand.
+/// Only the low byte of the `rs` register is used by the instruction, so the amount is first
+/// masked into the scratch register:
+///
+///   and ip, rs, #31
+///   mov rd, rm, <shift> ip
+///
+/// Encoding bits: `inst[27:20] | (inst[7:4] << 8)` of the `mov`.
+fn put_shift<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rm: RegUnit,
+    rs: RegUnit,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    let rs = u32::from(rs) & 0xf;
+
+    sink.put4(0xe200_001f | rs << 16 | SCRATCH << 12);
+    put_a32(bits, 0, rd, SCRATCH as RegUnit, rm, sink);
+}
+
+/// Move a 16-bit immediate: `movw` or `movt`.
+///
+///   31   27 19   15 11
+///   cond op imm4 rd imm12
+///     28 20   16 12     0
+///
+/// Encoding bits: `inst[27:20]`.
+fn put_movw<CS: CodeSink + ?Sized>(bits: u16, imm: i64, rd: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let rd = u32::from(rd) & 0xf;
+    let imm = imm as u32 & 0xffff;
+
+    let mut i = COND_AL << 28;
+    i |= (bits & 0xff) << 20;
+    i |= (imm >> 12) << 16;
+    i |= rd << 12;
+    i |= imm & 0xfff;
+
+    sink.put4(i);
+}
+
+/// Materialize a 32-bit constant with `movw` and `movt`.
+///
+/// Encoding bits: `inst[27:20]` of `movw`.
+fn put_const<CS: CodeSink + ?Sized>(bits: u16, imm: i64, rd: RegUnit, sink: &mut CS) {
+    put_movw(bits, imm, rd, sink);
+    put_movw(bits | 0x04, imm >> 16, rd, sink);
+}
+
+/// Conditional set, as `mov rd, #0` followed by a conditional `mov rd, #1`.
+fn put_cset<CS: CodeSink + ?Sized>(cond: u32, rd: RegUnit, sink: &mut CS) {
+    let rd = u32::from(rd) & 0xf;
+
+    sink.put4(0xe3a0_0000 | rd << 12);
+    sink.put4(cond << 28 | 0x03a0_0001 | rd << 12);
+}
+
+/// Conditional set for the floating point condition codes that need two conditions.
+fn put_cset2<CS: CodeSink + ?Sized>(cond: FloatCC, rd: RegUnit, sink: &mut CS) {
+    let (cond1, cond2) = match cond {
+        FloatCC::UnorderedOrEqual => (COND_EQ, COND_VS),
+        FloatCC::OrderedNotEqual => (COND_MI, COND_GT),
+        _ => panic!("{} needs one condition", cond),
+    };
+    let rd = u32::from(rd) & 0xf;
+
+    sink.put4(0xe3a0_0000 | rd << 12);
+    sink.put4(cond1 << 28 | 0x03a0_0001 | rd << 12);
+    sink.put4(cond2 << 28 | 0x03a0_0001 | rd << 12);
+}
+
+/// Multiply-subtract: `mls rd, rn, rm, ra`, computing `ra - rn * rm`.
+fn put_mls<CS: CodeSink + ?Sized>(
+    rn: RegUnit,
+    rm: RegUnit,
+    ra: RegUnit,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    let rn = u32::from(rn) & 0xf;
+    let rm = u32::from(rm) & 0xf;
+    let ra = u32::from(ra) & 0xf;
+    let rd = u32::from(rd) & 0xf;
+
+    sink.put4(0xe060_0090 | rd << 16 | ra << 12 | rm << 8 | rn);
+}
+
+/// The permanently undefined instruction `udf #0`.
+fn put_udf<CS: CodeSink + ?Sized>(sink: &mut CS) {
+    sink.put4(0xe7f0_00f0);
+}
+
+/// Compare with zero: `cmp rn, #0`.
+fn put_cmp_zero<CS: CodeSink + ?Sized>(rn: RegUnit, sink: &mut CS) {
+    let rn = u32::from(rn) & 0xf;
+
+    sink.put4(0xe350_0000 | rn << 16);
+}
+
+/// Trap if `rm` is zero, with `bne` skipping over a `udf`.
+fn put_trap_zero<CS: CodeSink + ?Sized>(rm: RegUnit, srcloc: SourceLoc, sink: &mut CS) {
+    put_cmp_zero(rm, sink);
+    sink.put4(COND_NE << 28 | 0x0a00_0000);
+    sink.trap(TrapCode::IntegerDivisionByZero, srcloc);
+    put_udf(sink);
+}
+
+/// Trap if the signed division of `rn` by `rm` overflows, which happens when `rm` is -1 and
+/// `rn` is the smallest signed integer:
+///
+///   cmn rm, #1
+///   cmpeq rn, #0x80000000
+///   bne #8
+///   udf
+fn put_trap_sdiv_overflow<CS: CodeSink + ?Sized>(
+    rn: RegUnit,
+    rm: RegUnit,
+    srcloc: SourceLoc,
+    sink: &mut CS,
+) {
+    let rn = u32::from(rn) & 0xf;
+    let rm = u32::from(rm) & 0xf;
+
+    sink.put4(0xe370_0001 | rm << 16);
+    sink.put4(COND_EQ << 28 | 0x0350_0102 | rn << 16);
+    sink.put4(COND_NE << 28 | 0x0a00_0000);
+    sink.trap(TrapCode::IntegerOverflow, srcloc);
+    put_udf(sink);
+}
+
+/// Word and byte loads and stores with a 12-bit immediate offset.
+///
+///   31   27 23 19 15 11
+///   cond op U  rn rt imm12
+///     28 24 23 16 12     0
+///
+/// Encoding bits: `inst[27:20]`. The `U` bit selects adding or subtracting the offset, and is
+/// cleared for negative offsets.
+fn put_ldst<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rt: RegUnit,
+    rn: RegUnit,
+    offset: i32,
+    sink: &mut CS,
+) {
+    let mut bits = u32::from(bits);
+    let rt = u32::from(rt) & 0xf;
+    let rn = u32::from(rn) & 0xf;
+
+    if offset < 0 {
+        bits &= !0x08;
+    }
+    let imm = offset.abs() as u32;
+    debug_assert!(imm < 0x1000, "offset out of range {:#x}", offset);
+
+    let mut i = COND_AL << 28;
+    i |= (bits & 0xff) << 20;
+    i |= rn << 16;
+    i |= rt << 12;
+    i |= imm;
+
+    sink.put4(i);
+}
+
+/// Halfword and signed byte loads and stores with an 8-bit immediate offset.
+///
+///   31   27 23 19 15 11    7  3
+///   cond op U  rn rt imm4h op imm4l
+///     28 24 23 16 12     8  4     0
+///
+/// Encoding bits: `inst[27:20] | (inst[7:4] << 8)`.
+fn put_ldsth<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rt: RegUnit,
+    rn: RegUnit,
+    offset: i32,
+    sink: &mut CS,
+) {
+    let mut bits = u32::from(bits);
+    let rt = u32::from(rt) & 0xf;
+    let rn = u32::from(rn) & 0xf;
+
+    if offset < 0 {
+        bits &= !0x08;
+    }
+    let imm = offset.abs() as u32;
+    debug_assert!(imm < 0x100, "offset out of range {:#x}", offset);
+
+    let mut i = COND_AL << 28;
+    i |= (bits & 0xff) << 20;
+    i |= rn << 16;
+    i |= rt << 12;
+    i |= (imm >> 4) << 8;
+    i |= ((bits >> 8) & 0xf) << 4;
+    i |= imm & 0xf;
+
+    sink.put4(i);
+}
+
+/// Materialize `offset` in the scratch register with `movw` and `movt`.
+fn put_scratch_offset<CS: CodeSink + ?Sized>(offset: i32, sink: &mut CS) {
+    put_const(MOVW, i64::from(offset), SCRATCH as RegUnit, sink);
+}
+
+/// Word and byte loads and stores with any offset. The offset is materialized in the scratch
+/// register, and added to `rn` with the register offset variant of the instruction.
+///
+/// Encoding bits: `inst[27:20]` of the immediate offset variant. Setting bit 5 gives the register
+/// offset variant.
+fn put_ldst_offset<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rt: RegUnit,
+    rn: RegUnit,
+    offset: i32,
+    sink: &mut CS,
+) {
+    put_scratch_offset(offset, sink);
+    put_a32(bits | 0x20, rn, rt, 0, SCRATCH as RegUnit, sink);
+}
+
+/// Halfword and signed byte loads and stores with any offset. The offset is materialized in the
+/// scratch register, and added to `rn` with the register offset variant of the instruction.
+///
+/// Encoding bits: `inst[27:20] | (inst[7:4] << 8)` of the immediate offset variant. Clearing
+/// bit 2 gives the register offset variant.
+fn put_ldsth_offset<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rt: RegUnit,
+    rn: RegUnit,
+    offset: i32,
+    sink: &mut CS,
+) {
+    put_scratch_offset(offset, sink);
+    put_a32(bits & !0x04, rn, rt, 0, SCRATCH as RegUnit, sink);
+}
+
+/// Word loads and stores relative to the stack pointer. The offset is materialized in the
+/// scratch register with `movw`, and added to `sp` with the register offset variant of the
+/// instruction.
+///
+/// Encoding bits: `inst[27:20]` of the immediate offset variant.
+fn put_ldst_sp<CS: CodeSink + ?Sized>(bits: u16, rt: RegUnit, offset: i32, sink: &mut CS) {
+    debug_assert!(
+        offset >= 0 && offset < 0x10000,
+        "stack offset out of range {}",
+        offset
+    );
+    put_movw(MOVW, i64::from(offset), SCRATCH as RegUnit, sink);
+    put_a32(bits | 0x20, 13, rt, 0, SCRATCH as RegUnit, sink);
+}
+
+/// Is `bits` the encoding bits of a double precision floating point instruction?
+fn is_double(bits: u32) -> bool {
+    bits & 1 != 0
+}
+
+/// Get the register number and the extra register bit of the single or double precision
+/// register `reg`, which are split over two fields of the instructions.
+///
+/// The `S` and `D` register classes share the register units of the floating point bank, two
+/// units for each `D` register.
+fn vreg(reg: RegUnit, double: bool) -> (u32, u32) {
+    let reg = u32::from(reg);
+    if double {
+        let d = reg >> 1;
+        (d & 0xf, d >> 4)
+    } else {
+        (reg >> 1, reg & 1)
+    }
+}
+
+/// Floating point data processing instructions.
+///
+///   31   27 22 21 19 15 11  8  7 6   5 4 3
+///   cond op D  op vn vd 101 sz N op2 M 0 vm
+///     28 23 22 20 16 12   9  8 7   6 5 4  0
+///
+/// Encoding bits: `inst[27:16] | (inst[8] << 12) | (inst[7:6] << 13)`.
+fn vfp_base(bits: u16) -> u32 {
+    let bits = u32::from(bits);
+
+    let mut i = COND_AL << 28;
+    i |= (bits & 0xfff) << 16;
+    i |= 0b101 << 9;
+    i |= ((bits >> 12) & 1) << 8;
+    i |= ((bits >> 13) & 3) << 6;
+    i
+}
+
+/// Floating point data processing with two sources.
+///
+/// Encoding bits: see `vfp_base`.
+fn put_vfp2<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    rm: RegUnit,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    let double = is_double(u32::from(bits) >> 12);
+    let (vn, n) = vreg(rn, double);
+    let (vm, m) = vreg(rm, double);
+    let (vd, d) = vreg(rd, double);
+
+    let mut i = vfp_base(bits);
+    i |= d << 22;
+    i |= vn << 16;
+    i |= vd << 12;
+    i |= n << 7;
+    i |= m << 5;
+    i |= vm;
+
+    sink.put4(i);
+}
+
+/// Floating point data processing with one source, including comparisons and conversions.
+///
+/// The `sz` bit gives the precision of both registers, except for the conversions. They are
+/// distinguished by the `inst[19:16]` opcode field:
+///
+/// - `0111`: conversion between single and double precision. `sz` is the precision of `rm`.
+/// - `1000`: conversion from an integer in a single precision register. `sz` is the precision of
+///   `rd`.
+///
+/// Encoding bits: see `vfp_base`.
+fn put_vfp1<CS: CodeSink + ?Sized>(bits: u16, rm: RegUnit, rd: RegUnit, sink: &mut CS) {
+    let sz = is_double(u32::from(bits) >> 12);
+    let (d_double, m_double) = match bits & 0xf {
+        0b0111 => (!sz, sz),
+        0b1000 => (sz, false),
+        _ => (sz, sz),
+    };
+    let (vm, m) = vreg(rm, m_double);
+    let (vd, d) = vreg(rd, d_double);
+
+    let mut i = vfp_base(bits);
+    i |= d << 22;
+    i |= vd << 12;
+    i |= m << 5;
+    i |= vm;
+
+    sink.put4(i);
+}
+
+/// Copy the floating point flags to the integer flags: `vmrs APSR_nzcv, fpscr`.
+fn put_vmrs<CS: CodeSink + ?Sized>(sink: &mut CS) {
+    sink.put4(0xeef1_fa10);
+}
+
+/// Floating point loads and stores, and transfers between integer and floating point registers.
+///
+///   31   27 19 15 11  8  7 6 4 3
+///   cond op rn rd 101 sz 0 0 x 0 imm8
+///     28 20 16 12   9  8 7 6 4    0
+///
+/// Encoding bits: `inst[27:20] | (inst[8] << 8) | (inst[4] << 9)`.
+fn vx_base(bits: u16) -> u32 {
+    let bits = u32::from(bits);
+
+    let mut i = COND_AL << 28;
+    i |= (bits & 0xff) << 20;
+    i |= 0b101 << 9;
+    i |= ((bits >> 8) & 1) << 8;
+    i |= ((bits >> 9) & 1) << 4;
+    i
+}
+
+/// Transfer between an integer register and a single precision register: `vmov sn, rt` or
+/// `vmov rt, sn`.
+///
+/// Encoding bits: see `vx_base`.
+fn put_vmov<CS: CodeSink + ?Sized>(bits: u16, sn: RegUnit, rt: RegUnit, sink: &mut CS) {
+    let (vn, n) = vreg(sn, false);
+    let rt = u32::from(rt) & 0xf;
+
+    sink.put4(vx_base(bits) | vn << 16 | rt << 12 | n << 7);
+}
+
+/// Floating point loads and stores with a scaled 8-bit immediate offset.
+///
+/// Encoding bits: see `vx_base`. The `U` bit is bit 3, and is cleared for negative offsets.
+fn put_vldst<CS: CodeSink + ?Sized>(
+    bits: u16,
+    vd: RegUnit,
+    rn: RegUnit,
+    offset: i32,
+    sink: &mut CS,
+) {
+    let bits = if offset < 0 { bits & !0x08 } else { bits };
+    let (vd, d) = vreg(vd, is_double(u32::from(bits) >> 8));
+    let rn = u32::from(rn) & 0xf;
+
+    debug_assert!(
+        is_signed_int(offset, 10, 2),
+        "offset out of range {:#x}",
+        offset
+    );
+    let imm = offset.abs() as u32 >> 2;
+
+    sink.put4(vx_base(bits) | d << 22 | rn << 16 | vd << 12 | imm);
+}
+
+/// Floating point loads and stores with any offset. The address is computed in the scratch
+/// register:
+///
+///   movw ip, #lo
+///   movt ip, #hi
+///   add ip, rn, ip
+///   vldr vd, [ip]
+///
+/// Encoding bits: see `vx_base`.
+fn put_vldst_offset<CS: CodeSink + ?Sized>(
+    bits: u16,
+    vd: RegUnit,
+    rn: RegUnit,
+    offset: i32,
+    sink: &mut CS,
+) {
+    put_scratch_offset(offset, sink);
+    put_a32(0x08, rn, SCRATCH as RegUnit, 0, SCRATCH as RegUnit, sink);
+    put_vldst(bits, vd, SCRATCH as RegUnit, 0, sink);
+}
+
+/// Floating point loads and stores relative to the stack pointer. The address is computed in the
+/// scratch register like in `put_vldst_offset`.
+///
+/// Encoding bits: see `vx_base`.
+fn put_vldst_sp<CS: CodeSink + ?Sized>(bits: u16, vd: RegUnit, offset: i32, sink: &mut CS) {
+    debug_assert!(
+        offset >= 0 && offset < 0x10000,
+        "stack offset out of range {}",
+        offset
+    );
+    put_movw(MOVW, i64::from(offset), SCRATCH as RegUnit, sink);
+    put_a32(0x08, 13, SCRATCH as RegUnit, 0, SCRATCH as RegUnit, sink);
+    put_vldst(bits, vd, SCRATCH as RegUnit, 0, sink);
+}
+
+/// Push or pop a double precision register: `vpush {dn}` or `vpop {dn}`.
+///
+/// Encoding bits: see `vx_base`.
+fn put_vpush_pop<CS: CodeSink + ?Sized>(bits: u16, dn: RegUnit, sink: &mut CS) {
+    let (vd, d) = vreg(dn, true);
+
+    sink.put4(vx_base(bits) | d << 22 | 13 << 16 | vd << 12 | 2);
+}
+
+/// Branches to an immediate displacement from the start of the instruction.
+///
+///   31   27 23
+///   cond op imm24
+///     28 24     0
+///
+/// Encoding bits: `inst[31:24]`. The offset of the branch target is relative to the address of
+/// the branch plus 8.
+fn put_b<CS: CodeSink + ?Sized>(bits: u16, disp: i64, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let disp = disp - 8;
+
+    debug_assert!(is_signed_int(disp, 26, 2), "B out of range {:#x}", disp);
+
+    sink.put4(bits << 24 | (disp >> 2) as u32 & 0xff_ffff);
+}
+
+/// Conditional branch: `b<cond>`.
+fn put_bcond<CS: CodeSink + ?Sized>(cond: u32, disp: i64, sink: &mut CS) {
+    put_b((cond << 4 | 0x0a) as u16, disp, sink);
+}
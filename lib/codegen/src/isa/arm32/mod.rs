@@ -13,9 +13,11 @@ use isa::enc_tables::{self as shared_enc_tables, lookup_enclist, Encodings};
 use isa::Builder as IsaBuilder;
 use isa::{EncInfo, RegClass, RegInfo, TargetIsa};
 use regalloc;
+use result::CodegenResult;
 use std::boxed::Box;
 use std::fmt;
 use target_lexicon::{Architecture, Triple};
+use timing;
 
 #[allow(dead_code)]
 struct Isa {
@@ -111,6 +113,11 @@ impl TargetIsa for Isa {
         abi::allocatable_registers(func)
     }
 
+    fn prologue_epilogue(&self, func: &mut ir::Function) -> CodegenResult<()> {
+        let _tt = timing::prologue_epilogue();
+        abi::prologue_epilogue(func, self)
+    }
+
     fn emit_inst(
         &self,
         func: &ir::Function,
@@ -1,30 +1,305 @@
 //! ARM 64 ABI implementation.
+//!
+//! This follows the AAPCS64 procedure call standard:
+//!
+//! - Integer arguments and return values are passed in `x0`-`x7`.
+//! - Floating point arguments and return values are passed in `v0`-`v7`.
+//! - Remaining arguments are passed on the stack in 8-byte slots.
+//! - `x19`-`x28` and the low 64 bits of `v8`-`v15` are callee-saved.
+//! - The stack pointer is always 16-byte aligned.
 
 use super::registers::{FPR, GPR};
+use abi::{legalize_args, ArgAction, ArgAssigner, ValueConversion};
+use cursor::{Cursor, CursorPosition, EncCursor};
 use ir;
-use isa::RegClass;
+use ir::immediates::Imm64;
+use ir::stackslot::StackSize;
+use ir::types::{F64, I64};
+use ir::{AbiParam, ArgumentExtension, ArgumentLoc, ArgumentPurpose, InstBuilder, ValueLoc};
+use isa::{RegClass, RegUnit, TargetIsa};
 use regalloc::RegisterSet;
+use result::{CodegenError, CodegenResult};
 use settings as shared_settings;
+use stack_layout::layout_stack;
+use std::i32;
+use std::vec::Vec;
+
+/// Number of registers of each class used for passing arguments.
+const ARG_REGS: usize = 8;
+
+/// The frame pointer register, `x29`.
+const FP: usize = 29;
+
+/// The link register, `x30`.
+const LR: usize = 30;
+
+/// The stack pointer register, `sp`.
+const SP: usize = 31;
+
+struct Args {
+    gpr_used: usize,
+    fpr_used: usize,
+    offset: u32,
+}
+
+impl Args {
+    fn new() -> Self {
+        Self {
+            gpr_used: 0,
+            fpr_used: 0,
+            offset: 0,
+        }
+    }
+}
+
+impl ArgAssigner for Args {
+    fn assign(&mut self, arg: &AbiParam) -> ArgAction {
+        let ty = arg.value_type;
+
+        // We don't support SIMD yet, so break all vectors down.
+        if ty.is_vector() {
+            return ValueConversion::VectorSplit.into();
+        }
+
+        // Large integers and booleans are broken down to fit in a register.
+        if !ty.is_float() && ty.bits() > 64 {
+            // The halves of a 128-bit value are passed in an even-numbered register pair, or in
+            // a 16-byte aligned stack location.
+            self.gpr_used += self.gpr_used & 1;
+            self.offset = (self.offset + 15) & !15;
+            return ValueConversion::IntSplit.into();
+        }
+
+        // Small integers are extended to the size of a register.
+        if ty.is_int() && ty.bits() < 64 {
+            match arg.extension {
+                ArgumentExtension::None => {}
+                ArgumentExtension::Uext => return ValueConversion::Uext(I64).into(),
+                ArgumentExtension::Sext => return ValueConversion::Sext(I64).into(),
+            }
+        }
+
+        if ty.is_float() {
+            if self.fpr_used < ARG_REGS {
+                let reg = FPR.unit(self.fpr_used);
+                self.fpr_used += 1;
+                return ArgumentLoc::Reg(reg).into();
+            }
+        } else if self.gpr_used < ARG_REGS {
+            let reg = GPR.unit(self.gpr_used);
+            self.gpr_used += 1;
+            return ArgumentLoc::Reg(reg).into();
+        }
+
+        // Assign a stack location.
+        let loc = ArgumentLoc::Stack(self.offset as i32);
+        self.offset += 8;
+        debug_assert!(self.offset <= i32::MAX as u32);
+        loc.into()
+    }
+}
 
 /// Legalize `sig`.
 pub fn legalize_signature(
-    _sig: &mut ir::Signature,
+    sig: &mut ir::Signature,
     _flags: &shared_settings::Flags,
     _current: bool,
 ) {
-    unimplemented!()
+    let mut args = Args::new();
+    legalize_args(&mut sig.params, &mut args);
+
+    let mut rets = Args::new();
+    legalize_args(&mut sig.returns, &mut rets);
 }
 
 /// Get register class for a type appearing in a legalized signature.
 pub fn regclass_for_abi_type(ty: ir::Type) -> RegClass {
-    if ty.is_int() {
-        GPR
-    } else {
+    if ty.is_float() {
         FPR
+    } else {
+        GPR
     }
 }
 
 /// Get the set of allocatable registers for `func`.
 pub fn allocatable_registers(_func: &ir::Function) -> RegisterSet {
-    unimplemented!()
+    let mut regs = RegisterSet::new();
+    // `x16` is reserved as a scratch register for materializing large offsets.
+    regs.take(GPR, GPR.unit(16));
+    // `x18` is the platform register.
+    regs.take(GPR, GPR.unit(18));
+    regs.take(GPR, GPR.unit(FP));
+    regs.take(GPR, GPR.unit(SP));
+    regs
+}
+
+/// Get the callee-saved registers in `rc` which are used by `func`.
+///
+/// The result is padded with an unused callee-saved register when necessary, so the registers
+/// can always be saved in pairs.
+fn callee_saved_used(func: &ir::Function, rc: RegClass, csrs: &[RegUnit]) -> Vec<RegUnit> {
+    let mut used = RegisterSet::empty();
+    for value_loc in func.locations.values() {
+        if let ValueLoc::Reg(ru) = *value_loc {
+            if csrs.contains(&ru) && !used.is_avail(rc, ru) {
+                used.free(rc, ru);
+            }
+        }
+    }
+
+    // regmove and regfill instructions may temporarily divert values into other registers,
+    // and these are not reflected in `func.locations`.
+    for ebb in &func.layout {
+        for inst in func.layout.ebb_insts(ebb) {
+            match func.dfg[inst] {
+                ir::instructions::InstructionData::RegMove { dst, .. }
+                | ir::instructions::InstructionData::RegFill { dst, .. } => {
+                    if csrs.contains(&dst) && !used.is_avail(rc, dst) {
+                        used.free(rc, dst);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    let mut regs: Vec<RegUnit> = csrs
+        .iter()
+        .cloned()
+        .filter(|&ru| used.is_avail(rc, ru))
+        .collect();
+    if regs.len() % 2 != 0 {
+        let pad = csrs
+            .iter()
+            .cloned()
+            .find(|ru| !regs.contains(ru))
+            .expect("no callee-saved register left for padding");
+        regs.push(pad);
+    }
+    regs
+}
+
+/// Insert a prologue and epilogues for `func`.
+pub fn prologue_epilogue(func: &mut ir::Function, isa: &TargetIsa) -> CodegenResult<()> {
+    let gpr_csrs: Vec<RegUnit> = (19..29).map(|n| GPR.unit(n)).collect();
+    let fpr_csrs: Vec<RegUnit> = (8..16).map(|n| FPR.unit(n)).collect();
+    let gprs = callee_saved_used(func, GPR, &gpr_csrs);
+    let fprs = callee_saved_used(func, FPR, &fpr_csrs);
+
+    // The reserved stack area is composed of the frame pointer and link register, followed by
+    // the callee-saved registers, all stored in 16-byte pairs.
+    let csr_stack_size = (16 * (1 + gprs.len() / 2 + fprs.len() / 2)) as i32;
+    func.create_stack_slot(ir::StackSlotData {
+        kind: ir::StackSlotKind::IncomingArg,
+        size: csr_stack_size as u32,
+        offset: Some(-csr_stack_size),
+    });
+
+    let total_stack_size = layout_stack(&mut func.stack_slots, 16)?;
+    let local_stack_size = total_stack_size - csr_stack_size as StackSize;
+
+    // The frame is allocated with at most two 12-bit immediate adjustments.
+    if local_stack_size >= 1 << 24 {
+        return Err(CodegenError::ImplLimitExceeded);
+    }
+    let local_stack_size = i64::from(local_stack_size);
+
+    // Add the special registers to the function signature.
+    let fp_arg = AbiParam::special_reg(I64, ArgumentPurpose::FramePointer, GPR.unit(FP));
+    let lr_arg = AbiParam::special_reg(I64, ArgumentPurpose::Link, GPR.unit(LR));
+    func.signature.params.push(fp_arg);
+    func.signature.returns.push(fp_arg);
+    func.signature.params.push(lr_arg);
+    func.signature.returns.push(lr_arg);
+
+    let csrs: Vec<(RegUnit, ir::Type)> = gprs
+        .iter()
+        .map(|&ru| (ru, I64))
+        .chain(fprs.iter().map(|&ru| (ru, F64)))
+        .collect();
+    for &(ru, ty) in &csrs {
+        let csr_arg = AbiParam::special_reg(ty, ArgumentPurpose::CalleeSaved, ru);
+        func.signature.params.push(csr_arg);
+        func.signature.returns.push(csr_arg);
+    }
+
+    // Set up the cursor and insert the prologue.
+    let entry_ebb = func.layout.entry_block().expect("missing entry block");
+    let mut pos = EncCursor::new(func, isa).at_first_insertion_point(entry_ebb);
+    insert_prologue(&mut pos, local_stack_size, &csrs);
+
+    // Reset the cursor and insert the epilogues.
+    let mut pos = pos.at_position(CursorPosition::Nowhere);
+    while let Some(ebb) = pos.next_ebb() {
+        pos.goto_last_inst(ebb);
+        if let Some(inst) = pos.current_inst() {
+            if pos.func.dfg[inst].opcode().is_return() {
+                insert_epilogue(inst, local_stack_size, &mut pos, &csrs);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Append an entry EBB parameter living in `ru`.
+fn append_ebb_reg(pos: &mut EncCursor, ebb: ir::Ebb, ty: ir::Type, ru: RegUnit) -> ir::Value {
+    let value = pos.func.dfg.append_ebb_param(ebb, ty);
+    pos.func.locations[value] = ValueLoc::Reg(ru);
+    value
+}
+
+/// Insert the prologue at the cursor position in the entry EBB.
+fn insert_prologue(pos: &mut EncCursor, stack_size: i64, csrs: &[(RegUnit, ir::Type)]) {
+    let ebb = pos.current_ebb().expect("missing ebb under cursor");
+    let fp = append_ebb_reg(pos, ebb, I64, GPR.unit(FP));
+    let lr = append_ebb_reg(pos, ebb, I64, GPR.unit(LR));
+
+    pos.ins().arm64_push_pair(fp, lr);
+    pos.ins().copy_special(GPR.unit(SP), GPR.unit(FP));
+
+    for pair in csrs.chunks(2) {
+        let (ru0, ty) = pair[0];
+        let (ru1, _) = pair[1];
+        let x = append_ebb_reg(pos, ebb, ty, ru0);
+        let y = append_ebb_reg(pos, ebb, ty, ru1);
+        pos.ins().arm64_push_pair(x, y);
+    }
+
+    // Allocate stack frame storage.
+    if stack_size > 0 {
+        pos.ins().adjust_sp_down_imm(Imm64::new(stack_size));
+    }
+}
+
+/// Insert an epilogue given a specific `return` instruction.
+fn insert_epilogue(
+    inst: ir::Inst,
+    stack_size: i64,
+    pos: &mut EncCursor,
+    csrs: &[(RegUnit, ir::Type)],
+) {
+    if stack_size > 0 {
+        pos.ins().adjust_sp_up_imm(Imm64::new(stack_size));
+    }
+
+    // Pop all the saved register pairs, stepping backward each time to preserve the correct
+    // order.
+    let (fp, lr) = pos.ins().arm64_pop_pair(I64);
+    pos.prev_inst();
+    pos.func.locations[fp] = ValueLoc::Reg(GPR.unit(FP));
+    pos.func.locations[lr] = ValueLoc::Reg(GPR.unit(LR));
+    pos.func.dfg.append_inst_arg(inst, fp);
+    pos.func.dfg.append_inst_arg(inst, lr);
+
+    for pair in csrs.chunks(2) {
+        let (ru0, ty) = pair[0];
+        let (ru1, _) = pair[1];
+        let (x, y) = pos.ins().arm64_pop_pair(ty);
+        pos.prev_inst();
+        pos.func.locations[x] = ValueLoc::Reg(ru0);
+        pos.func.locations[y] = ValueLoc::Reg(ru1);
+        pos.func.dfg.append_inst_arg(inst, x);
+        pos.func.dfg.append_inst_arg(inst, y);
+    }
 }
@@ -1,7 +1,582 @@
 //! Emitting binary ARM64 machine code.
+//!
+//! The encoding bits of a recipe hold the opcode bits of the instructions it encodes. Their
+//! layout depends on the instruction class, and is described for each `put_*` function below.
 
-use binemit::{bad_encoding, CodeSink};
-use ir::{Function, Inst};
+use binemit::{bad_encoding, CodeSink, Reloc};
+use ir::condcodes::{FloatCC, IntCC};
+use ir::{Function, Inst, InstructionData, SourceLoc, TrapCode};
+use isa::{RegUnit, StackBaseMask, StackRef};
+use predicates::is_signed_int;
 use regalloc::RegDiversions;
 
 include!(concat!(env!("OUT_DIR"), "/binemit-arm64.rs"));
+
+/// The scratch register used to materialize large offsets and intermediate results.
+const SCRATCH: u32 = 16;
+
+// A64 condition codes.
+const COND_EQ: u32 = 0x0;
+const COND_NE: u32 = 0x1;
+const COND_HS: u32 = 0x2;
+const COND_LO: u32 = 0x3;
+const COND_MI: u32 = 0x4;
+const COND_PL: u32 = 0x5;
+const COND_VS: u32 = 0x6;
+const COND_VC: u32 = 0x7;
+const COND_HI: u32 = 0x8;
+const COND_LS: u32 = 0x9;
+const COND_GE: u32 = 0xa;
+const COND_LT: u32 = 0xb;
+const COND_GT: u32 = 0xc;
+const COND_LE: u32 = 0xd;
+
+/// Get the A64 condition code to test after comparing integers with `cmp`.
+fn icc2cond(cond: IntCC) -> u32 {
+    use ir::condcodes::IntCC::*;
+    match cond {
+        Equal => COND_EQ,
+        NotEqual => COND_NE,
+        SignedLessThan => COND_LT,
+        SignedGreaterThanOrEqual => COND_GE,
+        SignedGreaterThan => COND_GT,
+        SignedLessThanOrEqual => COND_LE,
+        UnsignedLessThan => COND_LO,
+        UnsignedGreaterThanOrEqual => COND_HS,
+        UnsignedGreaterThan => COND_HI,
+        UnsignedLessThanOrEqual => COND_LS,
+    }
+}
+
+/// Get the A64 condition code to test after comparing floating point numbers with `fcmp`.
+///
+/// An unordered comparison sets the C and V flags, so the conditions testing them are the
+/// unordered ones.
+fn fcc2cond(cond: FloatCC) -> u32 {
+    use ir::condcodes::FloatCC::*;
+    match cond {
+        Equal => COND_EQ,
+        NotEqual => COND_NE,
+        LessThan => COND_MI,
+        LessThanOrEqual => COND_LS,
+        GreaterThan => COND_GT,
+        GreaterThanOrEqual => COND_GE,
+        Unordered => COND_VS,
+        Ordered => COND_VC,
+        UnorderedOrLessThan => COND_LT,
+        UnorderedOrLessThanOrEqual => COND_LE,
+        UnorderedOrGreaterThan => COND_HI,
+        UnorderedOrGreaterThanOrEqual => COND_PL,
+        UnorderedOrEqual | OrderedNotEqual => panic!("{} needs two conditions", cond),
+    }
+}
+
+/// Data processing instructions on registers.
+///
+///   31     20 15 14     9  4
+///   op   rm   0  op2    rn rd
+///      21  16 15     10  5  0
+///
+/// Encoding bits: `inst[31:21] | (inst[14:10] << 11)`.
+fn put_r<CS: CodeSink + ?Sized>(bits: u16, rn: RegUnit, rm: RegUnit, rd: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let rn = u32::from(rn) & 0x1f;
+    let rm = u32::from(rm) & 0x1f;
+    let rd = u32::from(rd) & 0x1f;
+
+    let mut i = (bits & 0x7ff) << 21;
+    i |= ((bits >> 11) & 0x1f) << 10;
+    i |= rm << 16;
+    i |= rn << 5;
+    i |= rd;
+
+    sink.put4(i);
+}
+
+/// Floating point data processing instructions with one source.
+///
+///   31     19  14    9  4
+///   op   op2   10000 rn rd
+///      21  15     10  5  0
+///
+/// Encoding bits: `inst[31:21] | (inst[19:15] << 11)`.
+fn put_rf1<CS: CodeSink + ?Sized>(bits: u16, rn: RegUnit, rd: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let rn = u32::from(rn) & 0x1f;
+    let rd = u32::from(rd) & 0x1f;
+
+    let mut i = (bits & 0x7ff) << 21;
+    i |= ((bits >> 11) & 0x1f) << 15;
+    i |= 0b10000 << 10;
+    i |= rn << 5;
+    i |= rd;
+
+    sink.put4(i);
+}
+
+/// Add and subtract instructions with a 12-bit unsigned immediate.
+///
+///   31   21    9  4
+///   op   imm12 rn rd
+///     22    10  5  0
+///
+/// Encoding bits: `inst[31:22]`. Setting bit 0 shifts the immediate left by 12 bits.
+fn put_i<CS: CodeSink + ?Sized>(bits: u16, rn: RegUnit, imm: i64, rd: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let rn = u32::from(rn) & 0x1f;
+    let rd = u32::from(rd) & 0x1f;
+
+    debug_assert!(
+        imm >= 0 && imm < 0x1000,
+        "immediate out of range {:#x}",
+        imm
+    );
+
+    let mut i = bits << 22;
+    i |= (imm as u32 & 0xfff) << 10;
+    i |= rn << 5;
+    i |= rd;
+
+    sink.put4(i);
+}
+
+/// Bitfield move instructions.
+///
+///   31   21   15   9  4
+///   op   immr imms rn rd
+///     22   16   10  5  0
+///
+/// Encoding bits: `inst[31:22]`.
+fn put_bfm<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    immr: u32,
+    imms: u32,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    let bits = u32::from(bits);
+    let rn = u32::from(rn) & 0x1f;
+    let rd = u32::from(rd) & 0x1f;
+
+    let mut i = bits << 22;
+    i |= (immr & 0x3f) << 16;
+    i |= (imms & 0x3f) << 10;
+    i |= rn << 5;
+    i |= rd;
+
+    sink.put4(i);
+}
+
+/// Get the width of the registers operated on by an instruction with the encoding bits `bits`,
+/// from its `sf` bit at `inst[31]`.
+fn bfm_width(bits: u16) -> u32 {
+    if bits & 0x200 != 0 {
+        64
+    } else {
+        32
+    }
+}
+
+/// Left shift by an immediate amount, as an alias of `ubfm`.
+///
+/// Encoding bits: `inst[31:22]` of `ubfm`.
+fn put_lsl<CS: CodeSink + ?Sized>(bits: u16, rn: RegUnit, imm: i64, rd: RegUnit, sink: &mut CS) {
+    let width = bfm_width(bits);
+    let shift = imm as u32 & (width - 1);
+    put_bfm(
+        bits,
+        rn,
+        (width - shift) & (width - 1),
+        width - 1 - shift,
+        rd,
+        sink,
+    );
+}
+
+/// Right shift by an immediate amount, as an alias of `ubfm` or `sbfm`.
+///
+/// Encoding bits: `inst[31:22]` of `ubfm` or `sbfm`.
+fn put_shr<CS: CodeSink + ?Sized>(bits: u16, rn: RegUnit, imm: i64, rd: RegUnit, sink: &mut CS) {
+    let width = bfm_width(bits);
+    let shift = imm as u32 & (width - 1);
+    put_bfm(bits, rn, shift, width - 1, rd, sink);
+}
+
+/// Sign or zero extension, as an alias of `sbfm` or `ubfm`.
+///
+/// Encoding bits: `inst[31:22] | (imms << 10)`.
+fn put_ext<CS: CodeSink + ?Sized>(bits: u16, rn: RegUnit, rd: RegUnit, sink: &mut CS) {
+    put_bfm(bits & 0x3ff, rn, 0, u32::from(bits) >> 10, rd, sink);
+}
+
+/// Move wide immediate instructions.
+///
+///   31   22 20    4
+///   op   hw imm16 rd
+///     23 21     5  0
+///
+/// Encoding bits: `inst[31:23]` of `movz`. Clearing bit 7 gives `movn`, setting bit 6 gives
+/// `movk`.
+fn put_movw<CS: CodeSink + ?Sized>(bits: u16, hw: u32, imm: u32, rd: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let rd = u32::from(rd) & 0x1f;
+
+    let mut i = bits << 23;
+    i |= (hw & 0x3) << 21;
+    i |= (imm & 0xffff) << 5;
+    i |= rd;
+
+    sink.put4(i);
+}
+
+/// Materialize a constant with 16 significant bits with `movz` or `movn`.
+///
+/// Encoding bits: `inst[31:23]` of `movz`.
+fn put_mov16<CS: CodeSink + ?Sized>(bits: u16, imm: i64, rd: RegUnit, sink: &mut CS) {
+    debug_assert!(
+        is_signed_int(imm, 17, 0),
+        "constant out of range {:#x}",
+        imm
+    );
+    if imm >= 0 {
+        put_movw(bits, 0, imm as u32, rd, sink);
+    } else {
+        put_movw(bits & !0x80, 0, !imm as u32, rd, sink);
+    }
+}
+
+/// Materialize the low `count` 16-bit chunks of a constant with a `movz` and `movk`
+/// instructions.
+///
+/// Encoding bits: `inst[31:23]` of `movz`.
+fn put_const<CS: CodeSink + ?Sized>(bits: u16, imm: i64, count: u32, rd: RegUnit, sink: &mut CS) {
+    put_movw(bits, 0, imm as u32, rd, sink);
+    for hw in 1..count {
+        put_movw(bits | 0x40, hw, (imm >> (16 * hw)) as u32, rd, sink);
+    }
+}
+
+/// Conditional set: `cset wd, cond`, as an alias of `csinc wd, wzr, wzr, !cond`.
+fn put_cset<CS: CodeSink + ?Sized>(cond: u32, rd: RegUnit, sink: &mut CS) {
+    put_csinc(cond ^ 1, 31, 31, rd, sink);
+}
+
+/// Conditional select increment: `csinc wd, wn, wm, cond`.
+///
+///   31   20 15   11 9  4
+///   op   rm cond op rn rd
+///     21 16   12 10  5  0
+fn put_csinc<CS: CodeSink + ?Sized>(
+    cond: u32,
+    rn: RegUnit,
+    rm: RegUnit,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    let rn = u32::from(rn) & 0x1f;
+    let rm = u32::from(rm) & 0x1f;
+    let rd = u32::from(rd) & 0x1f;
+
+    let mut i = 0x1a80_0400;
+    i |= rm << 16;
+    i |= (cond & 0xf) << 12;
+    i |= rn << 5;
+    i |= rd;
+
+    sink.put4(i);
+}
+
+/// Multiply-subtract: `msub rd, rn, rm, ra`, computing `ra - rn * rm`.
+///
+/// Encoding bits: the `put_r` encoding bits of an instruction of the same width.
+fn put_msub<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    rm: RegUnit,
+    ra: RegUnit,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    let sf = u32::from(bits >> 10) & 1;
+    let rn = u32::from(rn) & 0x1f;
+    let rm = u32::from(rm) & 0x1f;
+    let ra = u32::from(ra) & 0x1f;
+    let rd = u32::from(rd) & 0x1f;
+
+    let mut i = 0x1b00_8000;
+    i |= sf << 31;
+    i |= rm << 16;
+    i |= ra << 10;
+    i |= rn << 5;
+    i |= rd;
+
+    sink.put4(i);
+}
+
+/// High half of a 32-bit multiplication: `smull` or `umull` into `rd`, then `lsr xd, xd, #32`.
+///
+/// Encoding bits: the `put_r` encoding bits of `smull` or `umull`.
+fn put_mulhi32<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    rm: RegUnit,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    put_r(bits, rn, rm, rd, sink);
+    // `lsr xd, xd, #32` is `ubfm xd, xd, #32, #63`.
+    put_bfm(0x34d, rd, 32, 63, rd, sink);
+}
+
+/// The permanently undefined instruction `udf #0`.
+fn put_udf<CS: CodeSink + ?Sized>(sink: &mut CS) {
+    sink.put4(0);
+}
+
+/// Trap if `rt` is zero, with `cbnz rt, #8` skipping over a `udf`.
+///
+/// Encoding bits: the `put_r` encoding bits of an instruction of the width of `rt`.
+fn put_trap_zero<CS: CodeSink + ?Sized>(bits: u16, rt: RegUnit, srcloc: SourceLoc, sink: &mut CS) {
+    let sf = u32::from(bits >> 10) & 1;
+    let rt = u32::from(rt) & 0x1f;
+
+    sink.put4(0x3500_0000 | sf << 31 | 2 << 5 | rt);
+    sink.trap(TrapCode::IntegerDivisionByZero, srcloc);
+    put_udf(sink);
+}
+
+/// Trap if the signed division of `rn` by `rm` overflows, which happens when `rm` is -1 and
+/// `rn` is the smallest signed integer. Then `rn - 1` overflows too, which is tested with a
+/// conditional comparison:
+///
+///   cmn rm, #1
+///   ccmp rn, #1, #0, eq
+///   b.vc #8
+///   udf
+///
+/// Encoding bits: the `put_r` encoding bits of an instruction of the width of the operands.
+fn put_trap_sdiv_overflow<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    rm: RegUnit,
+    srcloc: SourceLoc,
+    sink: &mut CS,
+) {
+    let sf = u32::from(bits >> 10) & 1;
+    let rn = u32::from(rn) & 0x1f;
+    let rm = u32::from(rm) & 0x1f;
+
+    sink.put4(0x3100_041f | sf << 31 | rm << 5);
+    sink.put4(0x7a40_0800 | sf << 31 | 1 << 16 | COND_EQ << 12 | rn << 5);
+    sink.put4(0x5400_0000 | 2 << 5 | COND_VC);
+    sink.trap(TrapCode::IntegerOverflow, srcloc);
+    put_udf(sink);
+}
+
+/// Loads and stores with a signed 9-bit unscaled offset.
+///
+///   31   20   11 9  4
+///   op   imm9 00 rn rt
+///     21   12 10  5  0
+///
+/// Encoding bits: `inst[31:21]`.
+fn put_ldst<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rt: RegUnit,
+    rn: RegUnit,
+    offset: i32,
+    sink: &mut CS,
+) {
+    let bits = u32::from(bits);
+    let rt = u32::from(rt) & 0x1f;
+    let rn = u32::from(rn) & 0x1f;
+
+    debug_assert!(
+        is_signed_int(offset, 9, 0),
+        "offset out of range {:#x}",
+        offset
+    );
+
+    let mut i = bits << 21;
+    i |= (offset as u32 & 0x1ff) << 12;
+    i |= rn << 5;
+    i |= rt;
+
+    sink.put4(i);
+}
+
+/// Loads and stores with any offset. The offset is materialized in the scratch register with
+/// `movz` and `movk`, and added to `rn` with the register offset variant of the instruction:
+///
+///   31   20 15  12 11 9  4
+///   op 1 rm 110 0  10 rn rt
+///     21 16  13 12 10  5  0
+///
+/// Encoding bits: `inst[31:21]` of the unscaled offset variant.
+fn put_ldst_offset<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rt: RegUnit,
+    rn: RegUnit,
+    offset: i32,
+    sink: &mut CS,
+) {
+    let rt = u32::from(rt) & 0x1f;
+    let rn = u32::from(rn) & 0x1f;
+    let offset = offset as u32;
+
+    // movz w16, #lo
+    sink.put4(0x5280_0000 | (offset & 0xffff) << 5 | SCRATCH);
+    // movk w16, #hi, lsl #16
+    sink.put4(0x72a0_0000 | (offset >> 16) << 5 | SCRATCH);
+
+    let mut i = u32::from(bits) << 21;
+    i |= 1 << 21;
+    i |= SCRATCH << 16;
+    // Sign-extend the offset register with `sxtw`.
+    i |= 0b110 << 13;
+    i |= 0b10 << 10;
+    i |= rn << 5;
+    i |= rt;
+
+    sink.put4(i);
+}
+
+/// Loads and stores relative to the stack pointer, with a scaled 12-bit unsigned offset.
+///
+///   31     23    9  4
+///   op 01  imm12 rn rt
+///     24 22    10  5  0
+///
+/// Encoding bits: `inst[31:21]` of the unscaled offset variant. The access size is `inst[31:30]`.
+fn put_ldst_sp<CS: CodeSink + ?Sized>(bits: u16, rt: RegUnit, offset: i32, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let rt = u32::from(rt) & 0x1f;
+    let scale = bits >> 9;
+
+    debug_assert!(offset >= 0, "negative stack offset {}", offset);
+    let offset = offset as u32;
+    debug_assert!(
+        offset & ((1 << scale) - 1) == 0,
+        "misaligned stack offset {}",
+        offset
+    );
+    debug_assert!(
+        offset >> scale < 0x1000,
+        "stack offset out of range {}",
+        offset
+    );
+
+    let mut i = bits << 21;
+    i |= 1 << 24;
+    i |= (offset >> scale) << 10;
+    i |= 31 << 5;
+    i |= rt;
+
+    sink.put4(i);
+}
+
+/// Load and store pairs of registers, with the stack pointer as base register and writeback.
+///
+///   31   21   14  9  4
+///   op   imm7 rt2 rn rt
+///     22   15  10  5  0
+///
+/// Encoding bits: `inst[31:22]`.
+fn put_pair<CS: CodeSink + ?Sized>(
+    bits: u16,
+    offset: i32,
+    rt: RegUnit,
+    rt2: RegUnit,
+    sink: &mut CS,
+) {
+    let bits = u32::from(bits);
+    let rt = u32::from(rt) & 0x1f;
+    let rt2 = u32::from(rt2) & 0x1f;
+
+    // Both the 64-bit integer and the double precision registers are scaled by 8.
+    let imm7 = (offset / 8) as u32 & 0x7f;
+
+    let mut i = bits << 22;
+    i |= imm7 << 15;
+    i |= rt2 << 10;
+    i |= 31 << 5;
+    i |= rt;
+
+    sink.put4(i);
+}
+
+/// Conversions and moves between integer and floating point registers.
+///
+///   31   15 9  4
+///   op   0  rn rd
+///     16 10  5  0
+///
+/// Encoding bits: `inst[31:16]`.
+fn put_reg<CS: CodeSink + ?Sized>(bits: u16, rn: RegUnit, rd: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let rn = u32::from(rn) & 0x1f;
+    let rd = u32::from(rd) & 0x1f;
+
+    sink.put4(bits << 16 | rn << 5 | rd);
+}
+
+/// Branches to a register.
+///
+///   31   15 9  4
+///   op   0  rn 0
+///     16 10  5 0
+///
+/// Encoding bits: `inst[31:16]`.
+fn put_br<CS: CodeSink + ?Sized>(bits: u16, rn: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let rn = u32::from(rn) & 0x1f;
+
+    sink.put4(bits << 16 | rn << 5);
+}
+
+/// Unconditional branches to an immediate displacement.
+///
+///   31 25
+///   op imm26
+///   26     0
+///
+/// Encoding bits: `inst[31:26]`.
+fn put_b<CS: CodeSink + ?Sized>(bits: u16, disp: i64, sink: &mut CS) {
+    let bits = u32::from(bits);
+
+    debug_assert!(is_signed_int(disp, 28, 2), "B out of range {:#x}", disp);
+
+    sink.put4(bits << 26 | (disp >> 2) as u32 & 0x3ff_ffff);
+}
+
+/// Compare with zero and branch.
+///
+///   31 23    4
+///   op imm19 rt
+///   24     5  0
+///
+/// Encoding bits: `inst[31:24]`.
+fn put_cb<CS: CodeSink + ?Sized>(bits: u16, disp: i64, rt: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let rt = u32::from(rt) & 0x1f;
+
+    debug_assert!(is_signed_int(disp, 21, 2), "CB out of range {:#x}", disp);
+
+    sink.put4(bits << 24 | ((disp >> 2) as u32 & 0x7ffff) << 5 | rt);
+}
+
+/// Conditional branch: `b.cond`.
+///
+///   31       23    4 3
+///   01010100 imm19 0 cond
+///          24    5 4    0
+fn put_bcond<CS: CodeSink + ?Sized>(cond: u32, disp: i64, sink: &mut CS) {
+    debug_assert!(
+        is_signed_int(disp, 21, 2),
+        "B.cond out of range {:#x}",
+        disp
+    );
+
+    sink.put4(0x5400_0000 | ((disp >> 2) as u32 & 0x7ffff) << 5 | cond);
+}
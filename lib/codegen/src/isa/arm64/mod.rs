@@ -13,9 +13,11 @@ use isa::enc_tables::{lookup_enclist, Encodings};
 use isa::Builder as IsaBuilder;
 use isa::{EncInfo, RegClass, RegInfo, TargetIsa};
 use regalloc;
+use result::CodegenResult;
 use std::boxed::Box;
 use std::fmt;
 use target_lexicon::Triple;
+use timing;
 
 #[allow(dead_code)]
 struct Isa {
@@ -98,6 +100,11 @@ impl TargetIsa for Isa {
         abi::allocatable_registers(func)
     }
 
+    fn prologue_epilogue(&self, func: &mut ir::Function) -> CodegenResult<()> {
+        let _tt = timing::prologue_epilogue();
+        abi::prologue_epilogue(func, self)
+    }
+
     fn emit_inst(
         &self,
         func: &ir::Function,
@@ -148,6 +148,24 @@ impl RecipeConstraints {
 
         true
     }
+
+    /// Check if operands satisfying the `other` constraints are guaranteed to satisfy these
+    /// constraints too.
+    ///
+    /// This is the case when the constraints are the same, except that these may use larger
+    /// register classes.
+    pub fn includes(&self, other: &RecipeConstraints) -> bool {
+        fn includes_operands(a: &[OperandConstraint], b: &[OperandConstraint]) -> bool {
+            a.len() == b.len()
+                && a.iter()
+                    .zip(b)
+                    .all(|(a, b)| a.kind == b.kind && a.regclass.has_subclass(b.regclass))
+        }
+
+        includes_operands(self.ins, other.ins)
+            && includes_operands(self.outs, other.outs)
+            && (other.clobbers_flags || !self.clobbers_flags)
+    }
 }
 
 /// Constraints on the range of a branch instruction.
@@ -199,6 +199,11 @@ pub trait TargetIsa: fmt::Display {
         false
     }
 
+    /// Does the CPU have instructions that clear and extract the lowest set bit?
+    fn uses_lowest_bit_instructions(&self) -> bool {
+        false
+    }
+
     /// Get a data structure describing the registers in this ISA.
     fn register_info(&self) -> RegInfo;
 
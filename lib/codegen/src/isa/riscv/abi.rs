@@ -3,6 +3,11 @@
 //! This module implements the RISC-V calling convention through the primary `legalize_signature()`
 //! entry point.
 //!
+//! This implements the hard-float ABI: floating point arguments are passed in `fa0`-`fa7`,
+//! independently of the integer arguments in `a0`-`a7`. Unlike the standard ABI, floating point
+//! arguments don't fall back to the remaining integer registers once `fa0`-`fa7` are used up;
+//! they go straight to the stack.
+//!
 //! This doesn't support the soft-float ABI at the moment.
 
 use super::registers::{FPR, GPR};
@@ -14,12 +19,16 @@ use regalloc::RegisterSet;
 use std::i32;
 use target_lexicon::Triple;
 
+/// Number of floating point registers used for passing arguments, `fa0`-`fa7`.
+const FPR_LIMIT: u32 = 8;
+
 struct Args {
     pointer_bits: u8,
     pointer_bytes: u8,
     pointer_type: Type,
     regs: u32,
     reg_limit: u32,
+    fpr_regs: u32,
     offset: u32,
 }
 
@@ -31,6 +40,7 @@ impl Args {
             pointer_type: Type::int(u16::from(bits)).unwrap(),
             regs: 0,
             reg_limit: if enable_e { 6 } else { 8 },
+            fpr_regs: 0,
             offset: 0,
         }
     }
@@ -67,22 +77,25 @@ impl ArgAssigner for Args {
             }
         }
 
-        if self.regs < self.reg_limit {
-            // Assign to a register.
-            let reg = if ty.is_float() {
-                FPR.unit(10 + self.regs as usize)
-            } else {
-                GPR.unit(10 + self.regs as usize)
-            };
+        if ty.is_float() {
+            if self.fpr_regs < FPR_LIMIT {
+                let reg = FPR.unit(10 + self.fpr_regs as usize);
+                self.fpr_regs += 1;
+                return ArgumentLoc::Reg(reg).into();
+            }
+        } else if self.regs < self.reg_limit {
+            let reg = GPR.unit(10 + self.regs as usize);
             self.regs += 1;
-            ArgumentLoc::Reg(reg).into()
-        } else {
-            // Assign a stack location.
-            let loc = ArgumentLoc::Stack(self.offset as i32);
-            self.offset += u32::from(self.pointer_bytes);
-            debug_assert!(self.offset <= i32::MAX as u32);
-            loc.into()
+            return ArgumentLoc::Reg(reg).into();
         }
+
+        // Assign a stack location. Doubles are naturally aligned in RV32.
+        let size = u32::from(self.pointer_bytes).max(ty.bytes());
+        self.offset = align(self.offset, size);
+        let loc = ArgumentLoc::Stack(self.offset as i32);
+        self.offset += size;
+        debug_assert!(self.offset <= i32::MAX as u32);
+        loc.into()
     }
 }
 
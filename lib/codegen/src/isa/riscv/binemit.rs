@@ -1,9 +1,10 @@
 //! Emitting binary RISC-V machine code.
 
 use binemit::{bad_encoding, CodeSink, Reloc};
-use ir::{Function, Inst, InstructionData};
+use ir::stackslot::StackOffset;
+use ir::{Function, Inst, InstructionData, SourceLoc, TrapCode};
 use isa::{RegUnit, StackBaseMask, StackRef};
-use predicates::is_signed_int;
+use predicates::{is_signed_int, is_unsigned_int};
 use regalloc::RegDiversions;
 use std::u32;
 
@@ -73,6 +74,125 @@ fn put_rshamt<CS: CodeSink + ?Sized>(
     sink.put4(i);
 }
 
+/// R-type floating point instructions with the OP-FP opcode.
+///
+///   31     24  19  14     11 6
+///   funct7 rs2 rs1 funct3 rd opcode
+///       25  20  15     12  7      0
+///
+/// The rs2 field of unary operations is a fixed selector, which is or'ed into the rs2 operand.
+///
+/// Encoding bits: `funct3 | (funct7 << 3) | (rs2 << 10)`.
+fn put_opfp<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rs1: RegUnit,
+    rs2: RegUnit,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    let bits = u32::from(bits);
+    let funct3 = bits & 0x7;
+    let funct7 = (bits >> 3) & 0x7f;
+    let rs1 = u32::from(rs1) & 0x1f;
+    let rs2 = (u32::from(rs2) | (bits >> 10)) & 0x1f;
+    let rd = u32::from(rd) & 0x1f;
+
+    // 0-6: opcode
+    let mut i = 0x53;
+    i |= rd << 7;
+    i |= funct3 << 12;
+    i |= rs1 << 15;
+    i |= rs2 << 20;
+    i |= funct7 << 25;
+
+    sink.put4(i);
+}
+
+/// Conversion from a float in `rs1` to an integer in `rd`, trapping on NaN and on values out of
+/// range.
+///
+/// The conversion saturates instead of trapping, so it runs a first time to find out from the
+/// invalid operation flag whether the value fits, and a second time for the result:
+///
+///   feq.fmt rd, rs1, rs1
+///   bnez rd, 1f
+///   unimp                          ; bad_toint
+/// 1:
+///   fsflags zero
+///   fcvt.w.fmt rd, rs1, rtz
+///   frflags rd
+///   andi rd, rd, 0x10
+///   beqz rd, 2f
+///   unimp                          ; int_ovf
+/// 2:
+///   fcvt.w.fmt rd, rs1, rtz
+///
+/// Encoding bits: the `put_opfp` encoding bits of the conversion.
+fn put_fcvt_trap<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rs1: RegUnit,
+    rd: RegUnit,
+    srcloc: SourceLoc,
+    sink: &mut CS,
+) {
+    // The `unimp` pseudo-instruction, `csrrw zero, cycle, zero`.
+    const UNIMP: u32 = 0xc000_1073;
+
+    let fmt = (bits >> 3) & 0x3;
+    put_opfp(0b010 | fmt << 3 | 0b10100 << 5, rs1, rs1, rd, sink);
+    put_sb(0b11000 | 0b001 << 5, 8, rd, 0, sink);
+    sink.trap(TrapCode::BadConversionToInteger, srcloc);
+    sink.put4(UNIMP);
+
+    // `csrrw zero, fflags, zero` and `csrrs rd, fflags, zero`.
+    sink.put4(0x0010_1073);
+    put_opfp(bits, rs1, 0, rd, sink);
+    sink.put4(0x0010_2073 | (u32::from(rd) & 0x1f) << 7);
+    put_i(0b00100 | 0b111 << 5, rd, 0x10, rd, sink);
+    put_sb(0b11000, 8, rd, 0, sink);
+    sink.trap(TrapCode::IntegerOverflow, srcloc);
+    sink.put4(UNIMP);
+
+    put_opfp(bits, rs1, 0, rd, sink);
+}
+
+/// R4-type fused multiply-add instructions.
+///
+///   31  26  24  19  14     11 6
+///   rs3 fmt rs2 rs1 funct3 rd opcode
+///    27  25  20  15     12  7      0
+///
+/// Encoding bits: `opcode[6:2] | (funct3 << 5) | (fmt << 8)`.
+fn put_r4<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rs1: RegUnit,
+    rs2: RegUnit,
+    rs3: RegUnit,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    let bits = u32::from(bits);
+    let opcode5 = bits & 0x1f;
+    let funct3 = (bits >> 5) & 0x7;
+    let fmt = (bits >> 8) & 0x3;
+    let rs1 = u32::from(rs1) & 0x1f;
+    let rs2 = u32::from(rs2) & 0x1f;
+    let rs3 = u32::from(rs3) & 0x1f;
+    let rd = u32::from(rd) & 0x1f;
+
+    // 0-6: opcode
+    let mut i = 0x3;
+    i |= opcode5 << 2;
+    i |= rd << 7;
+    i |= funct3 << 12;
+    i |= rs1 << 15;
+    i |= rs2 << 20;
+    i |= fmt << 25;
+    i |= rs3 << 27;
+
+    sink.put4(i);
+}
+
 /// I-type instructions.
 ///
 ///   31  19  14     11 6
@@ -98,6 +218,60 @@ fn put_i<CS: CodeSink + ?Sized>(bits: u16, rs1: RegUnit, imm: i64, rd: RegUnit,
     sink.put4(i);
 }
 
+/// S-type store instructions.
+///
+///   31  24  19  14     11  6
+///   imm rs2 rs1 funct3 imm opcode
+///    25  20  15     12   7      0
+///
+/// Encoding bits: `opcode[6:2] | (funct3 << 5)`
+fn put_s<CS: CodeSink + ?Sized>(bits: u16, rs1: RegUnit, imm: i64, rs2: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let opcode5 = bits & 0x1f;
+    let funct3 = (bits >> 5) & 0x7;
+    let rs1 = u32::from(rs1) & 0x1f;
+    let rs2 = u32::from(rs2) & 0x1f;
+    let imm = imm as u32;
+
+    // 0-6: opcode
+    let mut i = 0x3;
+    i |= opcode5 << 2;
+    i |= (imm & 0x1f) << 7;
+    i |= funct3 << 12;
+    i |= rs1 << 15;
+    i |= rs2 << 20;
+    i |= ((imm >> 5) & 0x7f) << 25;
+
+    sink.put4(i);
+}
+
+/// The stack pointer, `x2`.
+const STACK_POINTER: RegUnit = 2;
+
+/// I-type load of a fill, relative to the stack pointer.
+///
+/// Encoding bits: the `put_i` encoding bits of the load.
+fn put_i_sp<CS: CodeSink + ?Sized>(bits: u16, offset: StackOffset, rd: RegUnit, sink: &mut CS) {
+    debug_assert!(
+        is_signed_int(offset, 12, 0),
+        "stack offset out of range {}",
+        offset
+    );
+    put_i(bits, STACK_POINTER, offset.into(), rd, sink);
+}
+
+/// S-type store of a spill, relative to the stack pointer.
+///
+/// Encoding bits: the `put_s` encoding bits of the store.
+fn put_s_sp<CS: CodeSink + ?Sized>(bits: u16, offset: StackOffset, rs2: RegUnit, sink: &mut CS) {
+    debug_assert!(
+        is_signed_int(offset, 12, 0),
+        "stack offset out of range {}",
+        offset
+    );
+    put_s(bits, STACK_POINTER, offset.into(), rs2, sink);
+}
+
 /// U-type instructions.
 ///
 ///   31  11 6
@@ -180,3 +354,171 @@ fn put_uj<CS: CodeSink + ?Sized>(bits: u16, imm: i64, rd: RegUnit, sink: &mut CS
 
     sink.put4(i);
 }
+
+/// Get the 3-bit field encoding one of the registers `x8`-`x15` in a compressed instruction.
+fn creg(reg: RegUnit) -> u32 {
+    let reg = u32::from(reg) & 0x1f;
+    debug_assert_eq!(reg & !0x7, 8, "x{} isn't a compressed register", reg);
+    reg & 0x7
+}
+
+/// CR-type compressed instructions.
+///
+///   15    11     6   1
+///   funct4 rd/rs1 rs2 op
+///       12      7   2  0
+///
+/// Encoding bits: the complete instruction with the register fields cleared.
+fn put_cr<CS: CodeSink + ?Sized>(bits: u16, rd: RegUnit, rs2: RegUnit, sink: &mut CS) {
+    let rd = u32::from(rd) & 0x1f;
+    let rs2 = u32::from(rs2) & 0x1f;
+
+    let mut i = u32::from(bits);
+    i |= rd << 7;
+    i |= rs2 << 2;
+
+    sink.put2(i as u16);
+}
+
+/// CI-type compressed instructions with a 6-bit immediate.
+///
+///   15     12     11     6        1
+///   funct3 imm[5] rd/rs1 imm[4:0] op
+///       13     12      7        2  0
+///
+/// Encoding bits: the complete instruction with the register and immediate fields cleared.
+fn put_ci<CS: CodeSink + ?Sized>(bits: u16, rd: RegUnit, imm: i64, sink: &mut CS) {
+    let rd = u32::from(rd) & 0x1f;
+    let imm = imm as u32;
+
+    let mut i = u32::from(bits);
+    i |= rd << 7;
+    i |= (imm & 0x1f) << 2;
+    i |= ((imm >> 5) & 0x1) << 12;
+
+    sink.put2(i as u16);
+}
+
+/// CA-type compressed arithmetic instructions on `x8`-`x15`.
+///
+///   15     9       6      4    1
+///   funct6 rd'/rs1' funct2 rs2' op
+///       10       7      5    2  0
+///
+/// Encoding bits: the complete instruction with the register fields cleared.
+fn put_ca<CS: CodeSink + ?Sized>(bits: u16, rd: RegUnit, rs2: RegUnit, sink: &mut CS) {
+    let mut i = u32::from(bits);
+    i |= creg(rd) << 7;
+    i |= creg(rs2) << 2;
+
+    sink.put2(i as u16);
+}
+
+/// CB-type compressed instructions with a 6-bit immediate operating on `x8`-`x15`.
+///
+///   15     12     11     9        6        1
+///   funct3 imm[5] funct2 rd'/rs1' imm[4:0] op
+///       13     12     10        7        2  0
+///
+/// Encoding bits: the complete instruction with the register and immediate fields cleared.
+fn put_cbi<CS: CodeSink + ?Sized>(bits: u16, rd: RegUnit, imm: i64, sink: &mut CS) {
+    let imm = imm as u32;
+
+    let mut i = u32::from(bits);
+    i |= creg(rd) << 7;
+    i |= (imm & 0x1f) << 2;
+    i |= ((imm >> 5) & 0x1) << 12;
+
+    sink.put2(i as u16);
+}
+
+/// CL-type and CS-type compressed word loads and stores.
+///
+///   15     12        9    6      5      4       1
+///   funct3 uimm[5:3] rs1' uimm[2] uimm[6] rd'/rs2' op
+///       13        10    7      6      5       2  0
+///
+/// Encoding bits: the complete instruction with the register and offset fields cleared.
+fn put_clw<CS: CodeSink + ?Sized>(bits: u16, rs1: RegUnit, imm: i64, rd: RegUnit, sink: &mut CS) {
+    debug_assert!(is_unsigned_int(imm, 7, 2), "c.lw offset {}", imm);
+    let imm = imm as u32;
+
+    let mut i = u32::from(bits);
+    i |= creg(rd) << 2;
+    i |= creg(rs1) << 7;
+    i |= ((imm >> 6) & 0x1) << 5;
+    i |= ((imm >> 2) & 0x1) << 6;
+    i |= ((imm >> 3) & 0x7) << 10;
+
+    sink.put2(i as u16);
+}
+
+/// CL-type and CS-type compressed double word loads and stores.
+///
+///   15     12        9    6         4       1
+///   funct3 uimm[5:3] rs1' uimm[7:6] rd'/rs2' op
+///       13        10    7         5       2  0
+///
+/// Encoding bits: the complete instruction with the register and offset fields cleared.
+fn put_cld<CS: CodeSink + ?Sized>(bits: u16, rs1: RegUnit, imm: i64, rd: RegUnit, sink: &mut CS) {
+    debug_assert!(is_unsigned_int(imm, 8, 3), "c.ld offset {}", imm);
+    let imm = imm as u32;
+
+    let mut i = u32::from(bits);
+    i |= creg(rd) << 2;
+    i |= creg(rs1) << 7;
+    i |= ((imm >> 6) & 0x3) << 5;
+    i |= ((imm >> 3) & 0x7) << 10;
+
+    sink.put2(i as u16);
+}
+
+/// CJ-type compressed jump instructions.
+///
+///   15     12                          1
+///   funct3 imm[11|4|9:8|10|6|7|3:1|5] op
+///       13                          2  0
+///
+/// Encoding bits: the complete instruction with the displacement cleared.
+fn put_cj<CS: CodeSink + ?Sized>(bits: u16, imm: i64, sink: &mut CS) {
+    debug_assert!(is_signed_int(imm, 12, 1), "CJ out of range {:#x}", imm);
+    let imm = imm as u32;
+
+    let mut i = u32::from(bits);
+
+    // The displacement is completely hashed up.
+    i |= ((imm >> 5) & 0x1) << 2;
+    i |= ((imm >> 1) & 0x7) << 3;
+    i |= ((imm >> 7) & 0x1) << 6;
+    i |= ((imm >> 6) & 0x1) << 7;
+    i |= ((imm >> 10) & 0x1) << 8;
+    i |= ((imm >> 8) & 0x3) << 9;
+    i |= ((imm >> 4) & 0x1) << 11;
+    i |= ((imm >> 11) & 0x1) << 12;
+
+    sink.put2(i as u16);
+}
+
+/// CB-type compressed branch instructions comparing one of `x8`-`x15` to zero.
+///
+///   15     12         9    6               1
+///   funct3 imm[8|4:3] rs1' imm[7:6|2:1|5] op
+///       13         10    7               2  0
+///
+/// Encoding bits: the complete instruction with the register and displacement cleared.
+fn put_cb<CS: CodeSink + ?Sized>(bits: u16, imm: i64, rs1: RegUnit, sink: &mut CS) {
+    debug_assert!(is_signed_int(imm, 9, 1), "CB out of range {:#x}", imm);
+    let imm = imm as u32;
+
+    let mut i = u32::from(bits);
+    i |= creg(rs1) << 7;
+
+    // The displacement is completely hashed up.
+    i |= ((imm >> 5) & 0x1) << 2;
+    i |= ((imm >> 1) & 0x3) << 3;
+    i |= ((imm >> 6) & 0x3) << 5;
+    i |= ((imm >> 3) & 0x3) << 10;
+    i |= ((imm >> 8) & 0x1) << 12;
+
+    sink.put2(i as u16);
+}
@@ -1,6 +1,7 @@
 //! x86 ABI implementation.
 
 use super::registers::{FPR, GPR, RU};
+use super::settings as isa_settings;
 use abi::{legalize_args, ArgAction, ArgAssigner, ValueConversion};
 use cursor::{Cursor, CursorPosition, EncCursor};
 use ir;
@@ -12,11 +13,14 @@ use ir::{
 };
 use isa::{RegClass, RegUnit, TargetIsa};
 use regalloc::RegisterSet;
-use result::CodegenResult;
-use settings::CallConv;
+use result::{CodegenError, CodegenResult};
+use settings::{CallConv, ProbestackStrategy};
 use stack_layout::layout_stack;
+use std::cmp;
 use std::i32;
+use std::string::ToString;
 use target_lexicon::{PointerWidth, Triple};
+use verifier::VerifierError;
 
 /// Argument registers for x86-64
 static ARG_GPRS: [RU; 6] = [RU::rdi, RU::rsi, RU::rdx, RU::rcx, RU::r8, RU::r9];
@@ -30,6 +34,9 @@ static ARG_GPRS_WIN_FASTCALL_X64: [RU; 4] = [RU::rcx, RU::rdx, RU::r8, RU::r9];
 /// Return value registers for x86-64, when using windows fastcall
 static RET_GPRS_WIN_FASTCALL_X64: [RU; 1] = [RU::rax];
 
+/// Argument and return value register of the probestack function, on both x86-32 and x86-64.
+static PROBESTACK_GPRS: [RU; 1] = [RU::rax];
+
 struct Args {
     pointer_bytes: u8,
     pointer_bits: u8,
@@ -40,10 +47,11 @@ struct Args {
     fpr_used: usize,
     offset: u32,
     call_conv: CallConv,
+    avx: bool,
 }
 
 impl Args {
-    fn new(bits: u8, gpr: &'static [RU], fpr_limit: usize, call_conv: CallConv) -> Self {
+    fn new(bits: u8, gpr: &'static [RU], fpr_limit: usize, call_conv: CallConv, avx: bool) -> Self {
         let offset = if let CallConv::WindowsFastcall = call_conv {
             // [1] "The caller is responsible for allocating space for parameters to the callee,
             // and must always allocate sufficient space to store four register parameters"
@@ -62,6 +70,7 @@ impl Args {
             fpr_used: 0,
             offset,
             call_conv,
+            avx,
         }
     }
 }
@@ -71,8 +80,15 @@ impl ArgAssigner for Args {
         let ty = arg.value_type;
 
         // Check for a legal type.
-        // We don't support SIMD yet, so break all vectors down.
+        // 128-bit vectors are passed in XMM registers while there are some left, and so are 256-bit
+        // vectors in YMM registers when AVX is available. All other vectors are broken down.
         if ty.is_vector() {
+            let fits = ty.bits() == 128 || (ty.bits() == 256 && self.avx);
+            if fits && self.fpr_used < self.fpr_limit {
+                let reg = FPR.unit(self.fpr_used);
+                self.fpr_used += 1;
+                return ArgumentLoc::Reg(reg).into();
+            }
             return ValueConversion::VectorSplit.into();
         }
 
@@ -122,16 +138,23 @@ impl ArgAssigner for Args {
             return ArgumentLoc::Reg(reg).into();
         }
 
-        // Assign a stack location.
+        // Assign a stack location. Every argument takes at least a pointer-sized slot, and an
+        // `f64` takes two of them on 32-bit targets.
         let loc = ArgumentLoc::Stack(self.offset as i32);
-        self.offset += u32::from(self.pointer_bytes);
+        self.offset += cmp::max(ty.bytes(), u32::from(self.pointer_bytes));
         debug_assert!(self.offset <= i32::MAX as u32);
         loc.into()
     }
 }
 
 /// Legalize `sig`.
-pub fn legalize_signature(sig: &mut ir::Signature, triple: &Triple, _current: bool) {
+pub fn legalize_signature(
+    sig: &mut ir::Signature,
+    triple: &Triple,
+    isa_flags: &isa_settings::Flags,
+    _current: bool,
+) {
+    let avx = isa_flags.use_avx();
     let bits;
     let mut args;
 
@@ -139,27 +162,37 @@ pub fn legalize_signature(sig: &mut ir::Signature, triple: &Triple, _current: bo
         PointerWidth::U16 => panic!(),
         PointerWidth::U32 => {
             bits = 32;
-            args = Args::new(bits, &[], 0, sig.call_conv);
+            args = if sig.call_conv == CallConv::Probestack {
+                Args::new(bits, &PROBESTACK_GPRS[..], 0, sig.call_conv, avx)
+            } else {
+                Args::new(bits, &[], 0, sig.call_conv, avx)
+            };
         }
         PointerWidth::U64 => {
             bits = 64;
-            args = if sig.call_conv == CallConv::WindowsFastcall {
-                Args::new(bits, &ARG_GPRS_WIN_FASTCALL_X64[..], 4, sig.call_conv)
-            } else {
-                Args::new(bits, &ARG_GPRS[..], 8, sig.call_conv)
+            args = match sig.call_conv {
+                CallConv::WindowsFastcall => {
+                    Args::new(bits, &ARG_GPRS_WIN_FASTCALL_X64[..], 4, sig.call_conv, avx)
+                }
+                CallConv::Probestack => {
+                    Args::new(bits, &PROBESTACK_GPRS[..], 0, sig.call_conv, avx)
+                }
+                _ => Args::new(bits, &ARG_GPRS[..], 8, sig.call_conv, avx),
             };
         }
     }
 
     legalize_args(&mut sig.params, &mut args);
 
-    let regs = if sig.call_conv == CallConv::WindowsFastcall {
-        &RET_GPRS_WIN_FASTCALL_X64[..]
-    } else {
-        &RET_GPRS[..]
+    let regs = match sig.call_conv {
+        CallConv::WindowsFastcall => &RET_GPRS_WIN_FASTCALL_X64[..],
+        CallConv::Probestack => &PROBESTACK_GPRS[..],
+        _ => &RET_GPRS[..],
     };
 
-    let mut rets = Args::new(bits, regs, 2, sig.call_conv);
+    // Floating point values are returned in XMM registers on 32-bit targets too, rather than on the
+    // x87 stack like the i386 System V ABI requires.
+    let mut rets = Args::new(bits, regs, 2, sig.call_conv, avx);
     legalize_args(&mut sig.returns, &mut rets);
 }
 
@@ -217,9 +250,41 @@ fn callee_saved_gprs(isa: &TargetIsa) -> &'static [RU] {
     }
 }
 
+/// Get the set of registers preserved by the probestack function.
+///
+/// The probestack function is called from prologues, where any register other than its argument
+/// may still hold an incoming argument of the caller.
+fn probestack_saved_gprs(isa: &TargetIsa) -> &'static [RU] {
+    match isa.triple().pointer_width().unwrap() {
+        PointerWidth::U16 => panic!(),
+        PointerWidth::U32 => &[RU::rcx, RU::rdx, RU::rbx, RU::rsi, RU::rdi],
+        PointerWidth::U64 => &[
+            RU::rcx,
+            RU::rdx,
+            RU::rbx,
+            RU::rsi,
+            RU::rdi,
+            RU::r8,
+            RU::r9,
+            RU::r10,
+            RU::r11,
+            RU::r12,
+            RU::r13,
+            RU::r14,
+            RU::r15,
+        ],
+    }
+}
+
 fn callee_saved_gprs_used(isa: &TargetIsa, func: &ir::Function) -> RegisterSet {
+    let saved = if func.signature.call_conv == CallConv::Probestack {
+        probestack_saved_gprs(isa)
+    } else {
+        callee_saved_gprs(isa)
+    };
+
     let mut all_callee_saved = RegisterSet::empty();
-    for reg in callee_saved_gprs(isa) {
+    for reg in saved {
         all_callee_saved.free(GPR, *reg as RegUnit);
     }
 
@@ -259,7 +324,16 @@ fn callee_saved_gprs_used(isa: &TargetIsa, func: &ir::Function) -> RegisterSet {
     used
 }
 
-pub fn prologue_epilogue(func: &mut ir::Function, isa: &TargetIsa) -> CodegenResult<()> {
+pub fn prologue_epilogue(
+    func: &mut ir::Function,
+    isa: &TargetIsa,
+    isa_flags: &isa_settings::Flags,
+) -> CodegenResult<()> {
+    // This goes first so the calls made by the prologue itself are left alone.
+    if isa_flags.use_avx() && uses_ymm_registers(func) {
+        insert_vzeroupper(func, isa);
+    }
+
     match func.signature.call_conv {
         // For now, just translate fast and cold as system_v.
         CallConv::Fast | CallConv::Cold | CallConv::SystemV => {
@@ -267,7 +341,65 @@ pub fn prologue_epilogue(func: &mut ir::Function, isa: &TargetIsa) -> CodegenRes
         }
         CallConv::WindowsFastcall => fastcall_prologue_epilogue(func, isa),
         CallConv::Baldrdash => baldrdash_prologue_epilogue(func, isa),
-        CallConv::Probestack => unimplemented!("probestack calling convention"),
+        CallConv::Probestack => probestack_prologue_epilogue(func, isa),
+    }
+}
+
+/// Is `ty` a vector type held in a full 256-bit YMM register?
+fn is_ymm_type(ty: ir::Type) -> bool {
+    ty.is_vector() && ty.bits() > 128
+}
+
+/// Does `func` have any values held in YMM registers?
+fn uses_ymm_registers(func: &ir::Function) -> bool {
+    func.layout.ebbs().any(|ebb| {
+        func.dfg
+            .ebb_params(ebb)
+            .iter()
+            .chain(
+                func.layout
+                    .ebb_insts(ebb)
+                    .flat_map(|inst| func.dfg.inst_results(inst)),
+            )
+            .any(|&v| is_ymm_type(func.dfg.value_type(v)))
+    })
+}
+
+/// Insert `x86_vzeroupper` instructions where the code leaves the function.
+///
+/// Legacy SSE code running with dirty upper halves in the YMM registers pays a large penalty on
+/// most Intel processors, so the upper halves are cleared before every call and return. This is
+/// not possible when 256-bit vectors are passed along in YMM registers.
+fn insert_vzeroupper(func: &mut ir::Function, isa: &TargetIsa) {
+    let returns_ymm = func
+        .signature
+        .returns
+        .iter()
+        .any(|ret| is_ymm_type(ret.value_type));
+
+    let mut pos = EncCursor::new(func, isa);
+    while let Some(_ebb) = pos.next_ebb() {
+        while let Some(inst) = pos.next_inst() {
+            let opcode = pos.func.dfg[inst].opcode();
+            let clear = if opcode.is_return() {
+                !returns_ymm
+            } else if opcode.is_call() {
+                let sig = pos
+                    .func
+                    .dfg
+                    .call_signature(inst)
+                    .expect("call without signature");
+                !pos.func.dfg.signatures[sig]
+                    .params
+                    .iter()
+                    .any(|arg| is_ymm_type(arg.value_type))
+            } else {
+                false
+            };
+            if clear {
+                pos.ins().x86_vzeroupper();
+            }
+        }
     }
 }
 
@@ -418,6 +550,96 @@ pub fn system_v_prologue_epilogue(func: &mut ir::Function, isa: &TargetIsa) -> C
     Ok(())
 }
 
+/// Insert a prologue and epilogue for the probestack function itself.
+///
+/// The probestack function receives the frame size of its caller in `%rax` and touches every page
+/// of that frame, so the function body is usually just a `return` of its argument. All registers
+/// other than `%rax` are preserved; the body must not use any floating point registers.
+pub fn probestack_prologue_epilogue(func: &mut ir::Function, isa: &TargetIsa) -> CodegenResult<()> {
+    if isa.flags().probestack_func_adjusts_sp() {
+        return Err(CodegenError::Unsupported(
+            "probestack function adjusting the stack pointer".to_string(),
+        ));
+    }
+
+    let stack_align = 16;
+    let pointer_width = isa.triple().pointer_width().unwrap();
+    let word_size = pointer_width.bytes() as usize;
+    let reg_type = ir::Type::int(u16::from(pointer_width.bits())).unwrap();
+
+    // The page counter of the probing loop needs a register of its own, which is saved and
+    // restored like the ones used by the function body.
+    let scratch = RU::rcx as RegUnit;
+    let mut csrs = callee_saved_gprs_used(isa, func);
+    if !csrs.is_avail(GPR, scratch) {
+        csrs.free(GPR, scratch);
+    }
+
+    let csr_stack_size = ((csrs.iter(GPR).len() + 2) * word_size) as i32;
+    func.create_stack_slot(ir::StackSlotData {
+        kind: ir::StackSlotKind::IncomingArg,
+        size: csr_stack_size as u32,
+        offset: Some(-csr_stack_size),
+    });
+
+    let total_stack_size = layout_stack(&mut func.stack_slots, stack_align)? as i32;
+    let local_stack_size = i64::from(total_stack_size - csr_stack_size);
+
+    // Add CSRs to function signature
+    let fp_arg = ir::AbiParam::special_reg(
+        reg_type,
+        ir::ArgumentPurpose::FramePointer,
+        RU::rbp as RegUnit,
+    );
+    func.signature.params.push(fp_arg);
+    func.signature.returns.push(fp_arg);
+
+    for csr in csrs.iter(GPR) {
+        let csr_arg = ir::AbiParam::special_reg(reg_type, ir::ArgumentPurpose::CalleeSaved, csr);
+        func.signature.params.push(csr_arg);
+        func.signature.returns.push(csr_arg);
+    }
+
+    // Set up the cursor and insert the prologue
+    let entry_ebb = func.layout.entry_block().expect("missing entry block");
+    let size_arg = match func.dfg.ebb_params(entry_ebb).first() {
+        Some(&arg) => arg,
+        None => {
+            return Err(CodegenError::Verifier(VerifierError {
+                location: entry_ebb.into(),
+                message: "probestack function must take the frame size".to_string(),
+            }))
+        }
+    };
+    let mut pos = EncCursor::new(func, isa).at_first_insertion_point(entry_ebb);
+    insert_common_prologue(&mut pos, local_stack_size, reg_type, &csrs, isa);
+
+    // Probe the caller's frame one page at a time. Round the page count up so the last partial
+    // page is probed too.
+    let log2_page_size = isa.flags().probestack_size_log2();
+    let count = pos.ins().copy(size_arg);
+    pos.func.locations[count] = ValueLoc::Reg(scratch);
+    let count = pos.ins().iadd_imm(count, (1 << log2_page_size) - 1);
+    pos.func.locations[count] = ValueLoc::Reg(scratch);
+    let count = pos.ins().ushr_imm(count, i64::from(log2_page_size));
+    pos.func.locations[count] = ValueLoc::Reg(scratch);
+    insert_probe_loop(&mut pos, count, scratch, 1 << log2_page_size, reg_type);
+
+    // Then pop the probes again, leaving the stack pointer where the prologue put it.
+    let saved_stack_size = local_stack_size + i64::from(csr_stack_size) - 2 * word_size as i64;
+    pos.ins()
+        .copy_special(RU::rbp as RegUnit, RU::rsp as RegUnit);
+    if saved_stack_size > 0 {
+        pos.ins().adjust_sp_down_imm(Imm64::new(saved_stack_size));
+    }
+
+    // Reset the cursor and insert the epilogue
+    let mut pos = pos.at_position(CursorPosition::Nowhere);
+    insert_common_epilogues(&mut pos, local_stack_size, reg_type, &csrs);
+
+    Ok(())
+}
+
 /// Insert the prologue for a given function.
 /// This is used by common calling conventions such as System V.
 fn insert_common_prologue(
@@ -447,44 +669,21 @@ fn insert_common_prologue(
         pos.ins().x86_push(csr_arg);
     }
 
-    // Allocate stack frame storage.
+    // Allocate stack frame storage. The probestack function never probes its own frame, that
+    // would just call itself again.
     if stack_size > 0 {
+        let page_size = 1 << isa.flags().probestack_size_log2();
         if isa.flags().probestack_enabled()
-            && stack_size > (1 << isa.flags().probestack_size_log2())
+            && stack_size > page_size
+            && pos.func.signature.call_conv != CallConv::Probestack
         {
-            // Emit a stack probe.
-            let rax = RU::rax as RegUnit;
-            let rax_val = ir::ValueLoc::Reg(rax);
-
-            // The probestack function expects its input in %rax.
-            let arg = pos.ins().iconst(reg_type, stack_size);
-            pos.func.locations[arg] = rax_val;
-
-            // Call the probestack function.
-            let callee = get_probestack_funcref(pos.func, reg_type, rax, isa);
-
-            // Make the call.
-            let call = if !isa.flags().is_pic()
-                && isa.triple().pointer_width().unwrap() == PointerWidth::U64
-                && !pos.func.dfg.ext_funcs[callee].colocated
-            {
-                // 64-bit non-PIC non-colocated calls need to be legalized to call_indirect.
-                // Use r11 as it may be clobbered under all supported calling conventions.
-                let r11 = RU::r11 as RegUnit;
-                let sig = pos.func.dfg.ext_funcs[callee].signature;
-                let addr = pos.ins().func_addr(reg_type, callee);
-                pos.func.locations[addr] = ir::ValueLoc::Reg(r11);
-                pos.ins().call_indirect(sig, addr, &[arg])
-            } else {
-                // Otherwise just do a normal call.
-                pos.ins().call(callee, &[arg])
-            };
-
-            // If the probestack function doesn't adjust sp, do it ourselves.
-            if !isa.flags().probestack_func_adjusts_sp() {
-                let result = pos.func.dfg.inst_results(call)[0];
-                pos.func.locations[result] = rax_val;
-                pos.ins().adjust_sp_down(result);
+            match isa.flags().probestack_strategy() {
+                ProbestackStrategy::Outline => {
+                    insert_probestack_call(pos, stack_size, reg_type, isa)
+                }
+                ProbestackStrategy::Inline => {
+                    insert_inline_probes(pos, stack_size, page_size, fp, reg_type)
+                }
             }
         } else {
             // Simply decrement the stack pointer.
@@ -493,6 +692,131 @@ fn insert_common_prologue(
     }
 }
 
+/// Allocate a stack frame of `stack_size` bytes, probing it with a call to the probestack
+/// function.
+fn insert_probestack_call(
+    pos: &mut EncCursor,
+    stack_size: i64,
+    reg_type: ir::types::Type,
+    isa: &TargetIsa,
+) {
+    let rax = RU::rax as RegUnit;
+    let rax_val = ir::ValueLoc::Reg(rax);
+
+    // The probestack function expects its input in %rax.
+    let arg = pos.ins().iconst(reg_type, stack_size);
+    pos.func.locations[arg] = rax_val;
+
+    // Call the probestack function.
+    let callee = get_probestack_funcref(pos.func, reg_type, rax, isa);
+
+    // Make the call.
+    let call = if !isa.flags().is_pic()
+        && isa.triple().pointer_width().unwrap() == PointerWidth::U64
+        && !pos.func.dfg.ext_funcs[callee].colocated
+    {
+        // 64-bit non-PIC non-colocated calls need to be legalized to call_indirect.
+        // Use r11 as it may be clobbered under all supported calling conventions.
+        let r11 = RU::r11 as RegUnit;
+        let sig = pos.func.dfg.ext_funcs[callee].signature;
+        let addr = pos.ins().func_addr(reg_type, callee);
+        pos.func.locations[addr] = ir::ValueLoc::Reg(r11);
+        pos.ins().call_indirect(sig, addr, &[arg])
+    } else {
+        // Otherwise just do a normal call.
+        pos.ins().call(callee, &[arg])
+    };
+
+    // If the probestack function doesn't adjust sp, do it ourselves.
+    if !isa.flags().probestack_func_adjusts_sp() {
+        let result = pos.func.dfg.inst_results(call)[0];
+        pos.func.locations[result] = rax_val;
+        pos.ins().adjust_sp_down(result);
+    }
+}
+
+/// Frames probed in at most this many pages get their probes unrolled rather than looped.
+const PROBE_MAX_UNROLL: i64 = 4;
+
+/// Allocate a stack frame of `stack_size` bytes, probing it with pushes one page at a time.
+///
+/// Each probe writes to the new stack pointer after it has moved, so the probes are never more than
+/// a page apart and the lowest one is at the bottom of the frame.
+fn insert_inline_probes(
+    pos: &mut EncCursor,
+    stack_size: i64,
+    page_size: i64,
+    fp: ir::Value,
+    reg_type: ir::types::Type,
+) {
+    let count = stack_size / page_size;
+    if count <= PROBE_MAX_UNROLL {
+        // Any value will do for the probes, so push the frame pointer.
+        for _ in 0..count {
+            pos.ins()
+                .adjust_sp_down_imm(Imm64::new(page_size - i64::from(reg_type.bytes())));
+            pos.ins().x86_push(fp);
+        }
+    } else {
+        // %rax is free in the prologue, just like when calling the probestack function.
+        let rax = RU::rax as RegUnit;
+        let count = pos.ins().iconst(reg_type, count);
+        pos.func.locations[count] = ir::ValueLoc::Reg(rax);
+        insert_probe_loop(pos, count, rax, page_size, reg_type);
+    }
+
+    // The rest of the frame is less than a page, but it can still reach into the next page.
+    let rest = stack_size % page_size;
+    if rest > 0 {
+        let word_size = i64::from(reg_type.bytes());
+        debug_assert_eq!(
+            rest % word_size,
+            0,
+            "frame size must be a multiple of words"
+        );
+        if rest > word_size {
+            pos.ins().adjust_sp_down_imm(Imm64::new(rest - word_size));
+        }
+        pos.ins().x86_push(fp);
+    }
+}
+
+/// Insert a loop moving the stack pointer down one page and probing the word at the new stack
+/// pointer, `count` times.
+///
+/// The loop counter `count` must live in `scratch`, and the stack pointer ends up `count` pages
+/// lower. The loop goes in new EBBs, leaving `pos` in front of the instruction it was at.
+fn insert_probe_loop(
+    pos: &mut EncCursor,
+    count: ir::Value,
+    scratch: RegUnit,
+    page_size: i64,
+    reg_type: ir::types::Type,
+) {
+    let loop_ebb = pos.func.dfg.make_ebb();
+    let done_ebb = pos.func.dfg.make_ebb();
+    let counter = pos.func.dfg.append_ebb_param(loop_ebb, reg_type);
+    pos.func.locations[counter] = ir::ValueLoc::Reg(scratch);
+    pos.ins().jump(loop_ebb, &[count]);
+
+    let inst = pos
+        .current_inst()
+        .expect("probe loop must precede an instruction");
+    pos.insert_ebb(done_ebb);
+    pos.func.layout.insert_ebb(loop_ebb, done_ebb);
+
+    pos.goto_bottom(loop_ebb);
+    pos.ins().brz(counter, done_ebb, &[]);
+    pos.ins()
+        .adjust_sp_down_imm(Imm64::new(page_size - i64::from(reg_type.bytes())));
+    pos.ins().x86_push(counter);
+    let next = pos.ins().iadd_imm(counter, -1);
+    pos.func.locations[next] = ir::ValueLoc::Reg(scratch);
+    pos.ins().jump(loop_ebb, &[next]);
+
+    pos.goto_inst(inst);
+}
+
 /// Find all `return` instructions and insert epilogues before them.
 fn insert_common_epilogues(
     pos: &mut EncCursor,
@@ -160,6 +160,35 @@ fn put_rexmp3<CS: CodeSink + ?Sized>(bits: u16, rex: u8, sink: &mut CS) {
     sink.put1(bits as u8);
 }
 
+// Emit a three-byte VEX prefix (C4 RXBmmmmm WvvvvLpp) followed by the opcode byte.
+//
+// The R, X and B bits are taken from a `rex` byte computed by the `rex*` functions above, and
+// `vvvv` is the register unit of the extra source operand, or 0 when the instruction doesn't
+// have one. All of these fields are stored inverted.
+fn vex_prefix<CS: CodeSink + ?Sized>(bits: u16, rex: u8, vvvv: RegUnit, l: u8, sink: &mut CS) {
+    debug_assert_eq!(rex & 0xf8, BASE_REX);
+    let mm = ((bits >> 10) & 3) as u8;
+    debug_assert_ne!(mm, 0, "Invalid encoding bits for Vex*");
+    let rxb = !rex & 0b111;
+    sink.put1(0xc4);
+    sink.put1((rxb << 5) | mm);
+    let w = ((bits >> 15) & 1) as u8;
+    let vvvv = !(vvvv as u8) & 0xf;
+    let pp = ((bits >> 8) & 3) as u8;
+    sink.put1((w << 7) | (vvvv << 3) | (l << 2) | pp);
+    sink.put1(bits as u8);
+}
+
+// Emit a VEX prefix and opcode for an instruction operating on 128-bit vectors or scalars.
+fn put_vex128<CS: CodeSink + ?Sized>(bits: u16, rex: u8, vvvv: RegUnit, sink: &mut CS) {
+    vex_prefix(bits, rex, vvvv, 0, sink);
+}
+
+// Emit a VEX prefix and opcode for an instruction operating on 256-bit vectors.
+fn put_vex256<CS: CodeSink + ?Sized>(bits: u16, rex: u8, vvvv: RegUnit, sink: &mut CS) {
+    vex_prefix(bits, rex, vvvv, 1, sink);
+}
+
 /// Emit a ModR/M byte for reg-reg operands.
 fn modrm_rr<CS: CodeSink + ?Sized>(rm: RegUnit, reg: RegUnit, sink: &mut CS) {
     let reg = reg as u8 & 7;
@@ -21,7 +21,7 @@ fn expand_sdivrem(
     func: &mut ir::Function,
     cfg: &mut ControlFlowGraph,
     isa: &isa::TargetIsa,
-) {
+) -> bool {
     let (x, y, is_srem) = match func.dfg[inst] {
         ir::InstructionData::Binary {
             opcode: ir::Opcode::Sdiv,
@@ -47,7 +47,7 @@ fn expand_sdivrem(
         let xhi = pos.ins().sshr_imm(x, i64::from(ty.lane_bits()) - 1);
         pos.ins().with_result(result).x86_sdivmodx(x, xhi, y);
         pos.remove_inst();
-        return;
+        return true;
     }
 
     // EBB handling the -1 divisor case.
@@ -100,6 +100,7 @@ fn expand_sdivrem(
     cfg.recompute_ebb(pos.func, old_ebb);
     cfg.recompute_ebb(pos.func, minus_one);
     cfg.recompute_ebb(pos.func, done);
+    true
 }
 
 /// Expand the `udiv` and `urem` instructions using `x86_udivmodx`.
@@ -108,7 +109,7 @@ fn expand_udivrem(
     func: &mut ir::Function,
     _cfg: &mut ControlFlowGraph,
     isa: &isa::TargetIsa,
-) {
+) -> bool {
     let (x, y, is_urem) = match func.dfg[inst] {
         ir::InstructionData::Binary {
             opcode: ir::Opcode::Udiv,
@@ -142,6 +143,7 @@ fn expand_udivrem(
     };
     pos.ins().with_results(reuse).x86_udivmodx(x, xhi, y);
     pos.remove_inst();
+    true
 }
 
 /// Expand the `fmin` and `fmax` instructions using the x86 `x86_fmin` and `x86_fmax`
@@ -151,7 +153,7 @@ fn expand_minmax(
     func: &mut ir::Function,
     cfg: &mut ControlFlowGraph,
     _isa: &isa::TargetIsa,
-) {
+) -> bool {
     use ir::condcodes::FloatCC;
 
     let (x, y, x86_opc, bitwise_opc) = match func.dfg[inst] {
@@ -236,6 +238,7 @@ fn expand_minmax(
     cfg.recompute_ebb(pos.func, ueq_ebb);
     cfg.recompute_ebb(pos.func, uno_ebb);
     cfg.recompute_ebb(pos.func, done);
+    true
 }
 
 /// x86 has no unsigned-to-float conversions. We handle the easy case of zero-extending i32 to
@@ -244,8 +247,8 @@ fn expand_fcvt_from_uint(
     inst: ir::Inst,
     func: &mut ir::Function,
     cfg: &mut ControlFlowGraph,
-    _isa: &isa::TargetIsa,
-) {
+    isa: &isa::TargetIsa,
+) -> bool {
     use ir::condcodes::IntCC;
 
     let x;
@@ -262,12 +265,12 @@ fn expand_fcvt_from_uint(
     let mut pos = FuncCursor::new(func).at_inst(inst);
     pos.use_srcloc(inst);
 
-    // Conversion from unsigned 32-bit is easy on x86-64.
-    // TODO: This should be guarded by an ISA check.
-    if xty == ir::types::I32 {
+    // Conversion from unsigned 32-bit is easy on x86-64. 32-bit targets don't have the 64-bit
+    // signed conversion, so they use the general expansion below.
+    if xty == ir::types::I32 && isa.pointer_bits() == 64 {
         let wide = pos.ins().uextend(ir::types::I64, x);
         pos.func.dfg.replace(inst).fcvt_from_sint(ty, wide);
-        return;
+        return true;
     }
 
     let old_ebb = pos.func.layout.pp_ebb(inst);
@@ -311,6 +314,7 @@ fn expand_fcvt_from_uint(
     cfg.recompute_ebb(pos.func, old_ebb);
     cfg.recompute_ebb(pos.func, neg_ebb);
     cfg.recompute_ebb(pos.func, done);
+    true
 }
 
 fn expand_fcvt_to_sint(
@@ -318,7 +322,7 @@ fn expand_fcvt_to_sint(
     func: &mut ir::Function,
     cfg: &mut ControlFlowGraph,
     _isa: &isa::TargetIsa,
-) {
+) -> bool {
     use ir::condcodes::{FloatCC, IntCC};
     use ir::immediates::{Ieee32, Ieee64};
 
@@ -403,6 +407,7 @@ fn expand_fcvt_to_sint(
 
     cfg.recompute_ebb(pos.func, old_ebb);
     cfg.recompute_ebb(pos.func, done);
+    true
 }
 
 fn expand_fcvt_to_uint(
@@ -410,7 +415,7 @@ fn expand_fcvt_to_uint(
     func: &mut ir::Function,
     cfg: &mut ControlFlowGraph,
     _isa: &isa::TargetIsa,
-) {
+) -> bool {
     use ir::condcodes::{FloatCC, IntCC};
     use ir::immediates::{Ieee32, Ieee64};
 
@@ -485,4 +490,45 @@ fn expand_fcvt_to_uint(
     cfg.recompute_ebb(pos.func, old_ebb);
     cfg.recompute_ebb(pos.func, large);
     cfg.recompute_ebb(pos.func, done);
+    true
+}
+
+/// Expand an `atomic_store` instruction.
+///
+/// A plain x86 store already has release semantics, which is enough for the weaker orderings. A
+/// sequentially consistent store must also not be reordered with later loads, so it is performed
+/// with an implicitly locked `xchg` instead, discarding the old value. The verifier rejects the
+/// acquire orderings, which don't apply to stores.
+fn expand_atomic_store(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    _isa: &isa::TargetIsa,
+) -> bool {
+    let (flags, x, addr, offset) = match func.dfg[inst] {
+        ir::InstructionData::Store {
+            opcode: ir::Opcode::AtomicStore,
+            flags,
+            args,
+            offset,
+        } => (flags, args[0], args[1], offset),
+        _ => panic!(
+            "Expected atomic_store: {}",
+            func.dfg.display_inst(inst, None)
+        ),
+    };
+
+    match flags.ordering() {
+        Some(ir::AtomicOrdering::Relaxed) | Some(ir::AtomicOrdering::Release) => {
+            // Only atomic instructions can have an ordering.
+            let mut store_flags = flags;
+            store_flags.clear_ordering();
+            func.dfg.replace(inst).store(store_flags, x, addr, offset);
+        }
+        None | Some(ir::AtomicOrdering::SeqCst) => {
+            func.dfg.replace(inst).atomic_xchg(flags, x, addr, offset);
+        }
+        Some(ordering) => panic!("atomic_store can't have {} ordering", ordering),
+    }
+    true
 }
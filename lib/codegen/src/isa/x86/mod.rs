@@ -75,6 +75,10 @@ impl TargetIsa for Isa {
         true
     }
 
+    fn uses_lowest_bit_instructions(&self) -> bool {
+        self.isa_flags.use_bmi1()
+    }
+
     fn register_info(&self) -> RegInfo {
         registers::INFO.clone()
     }
@@ -104,7 +108,7 @@ impl TargetIsa for Isa {
     }
 
     fn legalize_signature(&self, sig: &mut ir::Signature, current: bool) {
-        abi::legalize_signature(sig, &self.triple, current)
+        abi::legalize_signature(sig, &self.triple, &self.isa_flags, current)
     }
 
     fn regclass_for_abi_type(&self, ty: ir::Type) -> RegClass {
@@ -131,7 +135,7 @@ impl TargetIsa for Isa {
 
     fn prologue_epilogue(&self, func: &mut ir::Function) -> CodegenResult<()> {
         let _tt = timing::prologue_epilogue();
-        abi::prologue_epilogue(func, self)
+        abi::prologue_epilogue(func, self, &self.isa_flags)
     }
 }
 
@@ -0,0 +1,86 @@
+//! Legalization of atomic memory operations.
+//!
+//! This module exports the `expand_atomic_rmw` function which transforms an atomic
+//! read-modify-write instruction into a loop around an `atomic_cas` instruction.
+
+use cursor::{Cursor, FuncCursor};
+use flowgraph::ControlFlowGraph;
+use ir::condcodes::IntCC;
+use ir::{self, InstBuilder};
+use isa::TargetIsa;
+
+/// Expand an atomic read-modify-write instruction into a compare-and-swap loop.
+pub fn expand_atomic_rmw(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    cfg: &mut ControlFlowGraph,
+    _isa: &TargetIsa,
+) -> bool {
+    let (opcode, flags, x, addr, offset) = match func.dfg[inst] {
+        ir::InstructionData::Store {
+            opcode,
+            flags,
+            args,
+            offset,
+        } => (opcode, flags, args[0], args[1], offset),
+        _ => panic!(
+            "Wanted atomic read-modify-write: {}",
+            func.dfg.display_inst(inst, None)
+        ),
+    };
+    let old_ebb = func.layout.pp_ebb(inst);
+
+    // The loop EBB receives the value we expect to find in memory.
+    let loop_ebb = func.dfg.make_ebb();
+    let ty = func.dfg.ctrl_typevar(inst);
+    let expected = func.dfg.append_ebb_param(loop_ebb, ty);
+
+    // Move the `inst` result value onto the `done` EBB.
+    let done = func.dfg.make_ebb();
+    let result = func.dfg.first_result(inst);
+    func.dfg.clear_results(inst);
+    func.dfg.attach_ebb_param(done, result);
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+    // `atomic_cas` doesn't take an offset, so compute the effective address up front.
+    let offset: i64 = offset.into();
+    let addr = if offset == 0 {
+        addr
+    } else {
+        pos.ins().iadd_imm(addr, offset)
+    };
+    // The initial load doesn't need to be ordered, the compare-and-swap will catch any stale
+    // value.
+    let mut load_flags = flags;
+    load_flags.set_ordering(ir::AtomicOrdering::Relaxed);
+    let initial = pos.ins().atomic_load(ty, load_flags, addr, 0);
+    pos.ins().jump(loop_ebb, &[initial]);
+
+    // Compute the new value and try to swap it in. If another thread changed the memory in the
+    // meantime, try again with the value it stored.
+    pos.insert_ebb(loop_ebb);
+    let new = match opcode {
+        ir::Opcode::AtomicAdd => pos.ins().iadd(expected, x),
+        ir::Opcode::AtomicAnd => pos.ins().band(expected, x),
+        ir::Opcode::AtomicOr => pos.ins().bor(expected, x),
+        ir::Opcode::AtomicXor => pos.ins().bxor(expected, x),
+        ir::Opcode::AtomicXchg => x,
+        _ => panic!("Unexpected atomic opcode {}", opcode),
+    };
+    let previous = pos.ins().atomic_cas(flags, expected, new, addr);
+    let swapped = pos.ins().icmp(IntCC::Equal, previous, expected);
+    pos.ins().brz(swapped, loop_ebb, &[previous]);
+
+    // Recycle the original instruction as a jump.
+    pos.func.dfg.replace(inst).jump(done, &[previous]);
+
+    // Finally insert a label for the completion.
+    pos.next_inst();
+    pos.insert_ebb(done);
+
+    cfg.recompute_ebb(pos.func, old_ebb);
+    cfg.recompute_ebb(pos.func, loop_ebb);
+    cfg.recompute_ebb(pos.func, done);
+    true
+}
@@ -14,7 +14,7 @@ pub fn expand_call(
     func: &mut ir::Function,
     _cfg: &mut ControlFlowGraph,
     isa: &TargetIsa,
-) {
+) -> bool {
     // Unpack the instruction.
     let (func_ref, old_args) = match func.dfg[inst] {
         ir::InstructionData::Call {
@@ -50,4 +50,5 @@ pub fn expand_call(
     func.dfg
         .replace(inst)
         .CallIndirect(ir::Opcode::CallIndirect, ptr_ty, sig, new_args);
+    true
 }
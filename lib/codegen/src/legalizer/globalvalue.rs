@@ -14,7 +14,7 @@ pub fn expand_global_value(
     func: &mut ir::Function,
     _cfg: &mut ControlFlowGraph,
     _isa: &TargetIsa,
-) {
+) -> bool {
     // Unpack the instruction.
     let gv = match func.dfg[inst] {
         ir::InstructionData::UnaryGlobalValue {
@@ -32,6 +32,7 @@ pub fn expand_global_value(
         ir::GlobalValueData::Deref { base, offset } => deref_addr(inst, func, base, offset.into()),
         ir::GlobalValueData::Sym { .. } => globalsym(inst, func, gv),
     }
+    true
 }
 
 /// Expand a `global_value` instruction for a vmctx global.
@@ -15,7 +15,7 @@ pub fn expand_heap_addr(
     func: &mut ir::Function,
     cfg: &mut ControlFlowGraph,
     _isa: &TargetIsa,
-) {
+) -> bool {
     // Unpack the instruction.
     let (heap, offset, size) = match func.dfg[inst] {
         ir::InstructionData::HeapAddr {
@@ -38,6 +38,7 @@ pub fn expand_heap_addr(
             static_addr(inst, heap, offset, size, bound.into(), func, cfg)
         }
     }
+    true
 }
 
 /// Expand a `heap_addr` for a dynamic heap.
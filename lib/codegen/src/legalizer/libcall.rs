@@ -1,7 +1,10 @@
 //! Expanding instructions as runtime library calls.
 
+use cursor::{Cursor, FuncCursor};
 use ir;
-use ir::{get_libcall_funcref, InstBuilder};
+use ir::condcodes::{FloatCC, IntCC};
+use ir::immediates::Ieee64;
+use ir::{get_libcall_funcref, types, InstBuilder};
 use isa::TargetIsa;
 use legalizer::boundary::legalize_libcall_signature;
 use std::vec::Vec;
@@ -14,6 +17,10 @@ pub fn expand_as_libcall(inst: ir::Inst, func: &mut ir::Function, isa: &TargetIs
         Some(lc) => lc,
         None => return false,
     };
+    if !legalize_libcall_args(libcall, inst, func) {
+        return false;
+    }
+    insert_libcall_traps(libcall, inst, func);
 
     // Now we convert `inst` to a call. First save the arguments.
     let mut args = Vec::new();
@@ -29,3 +36,87 @@ pub fn expand_as_libcall(inst: ir::Inst, func: &mut ir::Function, isa: &TargetIs
 
     true
 }
+
+/// Check that the arguments of `inst` have the types taken by `libcall`, so all the calls to a
+/// routine share its signature. The shift amounts of the 64-bit shifts are converted to `i32` and
+/// reduced modulo 64, since the routines are undefined for larger or negative amounts.
+///
+/// Returns false if the arguments can't be passed to `libcall`.
+fn legalize_libcall_args(libcall: ir::LibCall, inst: ir::Inst, func: &mut ir::Function) -> bool {
+    let args = func.dfg.inst_args(inst);
+    match libcall {
+        ir::LibCall::IshlI64 | ir::LibCall::UshrI64 | ir::LibCall::SshrI64 => {
+            let amount = args[1];
+            let ty = func.dfg.value_type(amount);
+            let mut pos = FuncCursor::new(func).at_inst(inst);
+            pos.use_srcloc(inst);
+            let amount = if ty.bits() > 32 {
+                pos.ins().ireduce(types::I32, amount)
+            } else if ty.bits() < 32 {
+                pos.ins().uextend(types::I32, amount)
+            } else {
+                amount
+            };
+            let amount = pos.ins().band_imm(amount, 63);
+            pos.func.dfg.inst_args_mut(inst)[1] = amount;
+            true
+        }
+        ir::LibCall::FcvtToSintI64 | ir::LibCall::FcvtToUintI64 => {
+            func.dfg.value_type(args[0]) == types::F64
+        }
+        ir::LibCall::FcvtFromSintF32 | ir::LibCall::FcvtFromSintF64 => {
+            func.dfg.value_type(args[0]) == types::I64
+        }
+        _ => true,
+    }
+}
+
+/// Insert the checks that make the call to `libcall` trap where the instruction `inst` would.
+///
+/// The runtime library routines don't trap on division by zero, on a quotient that overflows, or
+/// on a float that doesn't fit the integer type, so test the arguments before the call.
+fn insert_libcall_traps(libcall: ir::LibCall, inst: ir::Inst, func: &mut ir::Function) {
+    let args = func.dfg.inst_args(inst).to_vec();
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+    match libcall {
+        ir::LibCall::UdivI64
+        | ir::LibCall::SdivI64
+        | ir::LibCall::UremI64
+        | ir::LibCall::SremI64 => {
+            let (x, y) = (args[0], args[1]);
+            pos.ins().trapz(y, ir::TrapCode::IntegerDivisionByZero);
+            if libcall == ir::LibCall::SdivI64 {
+                // INT_MIN / -1 doesn't fit in the result. The remainder is simply 0.
+                let is_min = pos.ins().icmp_imm(IntCC::Equal, x, i64::min_value());
+                let is_m1 = pos.ins().icmp_imm(IntCC::Equal, y, -1);
+                let overflow = pos.ins().band(is_min, is_m1);
+                pos.ins().trapnz(overflow, ir::TrapCode::IntegerOverflow);
+            }
+        }
+        ir::LibCall::FcvtToSintI64 | ir::LibCall::FcvtToUintI64 => {
+            let x = args[0];
+            let is_nan = pos.ins().fcmp(FloatCC::Unordered, x, x);
+            pos.ins()
+                .trapnz(is_nan, ir::TrapCode::BadConversionToInteger);
+
+            // The truncated value must be in `-2^63 .. 2^63` or `0 .. 2^64`.
+            let (low_cc, low, high) = if libcall == ir::LibCall::FcvtToSintI64 {
+                (FloatCC::LessThan, Ieee64::pow2(63).neg(), Ieee64::pow2(63))
+            } else {
+                (
+                    FloatCC::LessThanOrEqual,
+                    Ieee64::with_float(-1.0),
+                    Ieee64::pow2(64),
+                )
+            };
+            let flimit = pos.ins().f64const(low);
+            let overflow = pos.ins().fcmp(low_cc, x, flimit);
+            pos.ins().trapnz(overflow, ir::TrapCode::IntegerOverflow);
+            let flimit = pos.ins().f64const(high);
+            let overflow = pos.ins().fcmp(FloatCC::GreaterThanOrEqual, x, flimit);
+            pos.ins().trapnz(overflow, ir::TrapCode::IntegerOverflow);
+        }
+        _ => {}
+    }
+}
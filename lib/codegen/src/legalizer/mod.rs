@@ -18,8 +18,10 @@ use cursor::{Cursor, FuncCursor};
 use flowgraph::ControlFlowGraph;
 use ir::{self, InstBuilder, MemFlags};
 use isa::TargetIsa;
+use predicates;
 use timing;
 
+mod atomics;
 mod boundary;
 mod call;
 mod globalvalue;
@@ -27,6 +29,7 @@ mod heap;
 mod libcall;
 mod split;
 
+use self::atomics::expand_atomic_rmw;
 use self::call::expand_call;
 use self::globalvalue::expand_global_value;
 use self::heap::expand_heap_addr;
@@ -122,7 +125,7 @@ fn expand_cond_trap(
     func: &mut ir::Function,
     cfg: &mut ControlFlowGraph,
     _isa: &TargetIsa,
-) {
+) -> bool {
     // Parse the instruction.
     let trapz;
     let (arg, code) = match func.dfg[inst] {
@@ -164,6 +167,7 @@ fn expand_cond_trap(
     // Finally update the CFG.
     cfg.recompute_ebb(pos.func, old_ebb);
     cfg.recompute_ebb(pos.func, new_ebb);
+    true
 }
 
 /// Jump tables.
@@ -172,7 +176,7 @@ fn expand_br_table(
     func: &mut ir::Function,
     cfg: &mut ControlFlowGraph,
     _isa: &TargetIsa,
-) {
+) -> bool {
     use ir::condcodes::IntCC;
 
     let (arg, table) = match func.dfg[inst] {
@@ -201,6 +205,7 @@ fn expand_br_table(
     let ebb = pos.current_ebb().unwrap();
     pos.remove_inst();
     cfg.recompute_ebb(pos.func, ebb);
+    true
 }
 
 /// Expand the select instruction.
@@ -212,7 +217,7 @@ fn expand_select(
     func: &mut ir::Function,
     cfg: &mut ControlFlowGraph,
     _isa: &TargetIsa,
-) {
+) -> bool {
     let (ctrl, tval, fval) = match func.dfg[inst] {
         ir::InstructionData::Ternary {
             opcode: ir::Opcode::Select,
@@ -240,6 +245,7 @@ fn expand_select(
 
     cfg.recompute_ebb(pos.func, new_ebb);
     cfg.recompute_ebb(pos.func, old_ebb);
+    true
 }
 
 /// Expand illegal `f32const` and `f64const` instructions.
@@ -248,7 +254,7 @@ fn expand_fconst(
     func: &mut ir::Function,
     _cfg: &mut ControlFlowGraph,
     _isa: &TargetIsa,
-) {
+) -> bool {
     let ty = func.dfg.value_type(func.dfg.first_result(inst));
     debug_assert!(!ty.is_vector(), "Only scalar fconst supported: {}", ty);
 
@@ -268,6 +274,7 @@ fn expand_fconst(
         _ => panic!("Expected fconst: {}", pos.func.dfg.display_inst(inst, None)),
     };
     pos.func.dfg.replace(inst).bitcast(ty, ival);
+    true
 }
 
 /// Expand illegal `stack_load` instructions.
@@ -276,7 +283,7 @@ fn expand_stack_load(
     func: &mut ir::Function,
     _cfg: &mut ControlFlowGraph,
     isa: &TargetIsa,
-) {
+) -> bool {
     let ty = func.dfg.value_type(func.dfg.first_result(inst));
     let addr_ty = isa.pointer_type();
 
@@ -302,6 +309,7 @@ fn expand_stack_load(
     mflags.set_notrap();
     mflags.set_aligned();
     pos.func.dfg.replace(inst).load(ty, mflags, addr, 0);
+    true
 }
 
 /// Expand illegal `stack_store` instructions.
@@ -310,7 +318,7 @@ fn expand_stack_store(
     func: &mut ir::Function,
     _cfg: &mut ControlFlowGraph,
     isa: &TargetIsa,
-) {
+) -> bool {
     let addr_ty = isa.pointer_type();
 
     let mut pos = FuncCursor::new(func).at_inst(inst);
@@ -336,4 +344,197 @@ fn expand_stack_store(
     mflags.set_notrap();
     mflags.set_aligned();
     pos.func.dfg.replace(inst).store(mflags, val, addr, 0);
+    true
+}
+
+/// Expand illegal `bitcast` instructions by going through a stack slot.
+fn expand_bitcast(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    _isa: &TargetIsa,
+) -> bool {
+    let arg = match func.dfg[inst] {
+        ir::InstructionData::Unary {
+            opcode: ir::Opcode::Bitcast,
+            arg,
+        } => arg,
+        _ => panic!("Expected bitcast: {}", func.dfg.display_inst(inst, None)),
+    };
+    let ty = func.dfg.ctrl_typevar(inst);
+
+    let stack_slot = func.create_stack_slot(ir::StackSlotData::new(
+        ir::StackSlotKind::ExplicitSlot,
+        ty.bytes(),
+    ));
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+    pos.ins().stack_store(arg, stack_slot, 0);
+    pos.func.dfg.replace(inst).stack_load(ty, stack_slot, 0);
+    true
+}
+
+/// Get the type of the halves of `ty` when it is narrowed, and whether it is split into vectors.
+///
+/// Only vectors and integers from `i16` can be split. The custom narrowings return false for the
+/// other types and leave the instruction unchanged.
+fn narrow_halves(ty: ir::Type) -> Option<(ir::Type, bool)> {
+    if ty.is_vector() {
+        ty.half_vector().map(|half| (half, true))
+    } else if ty.is_int() {
+        ty.half_width().map(|half| (half, false))
+    } else {
+        None
+    }
+}
+
+/// Narrow an `iconst` into constants for the two halves.
+fn narrow_iconst(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    _isa: &TargetIsa,
+) -> bool {
+    let imm: i64 = match func.dfg[inst] {
+        ir::InstructionData::UnaryImm {
+            opcode: ir::Opcode::Iconst,
+            imm,
+        } => imm.into(),
+        _ => panic!("Expected iconst: {}", func.dfg.display_inst(inst, None)),
+    };
+    let ty = func.dfg.ctrl_typevar(inst);
+    let half_ty = match narrow_halves(ty) {
+        Some((half_ty, _)) => half_ty,
+        None => return false,
+    };
+
+    // Both halves are zero-extended from their own width.
+    let bits = half_ty.bits();
+    let mask = (1i64 << bits) - 1;
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+    let low = pos.ins().iconst(half_ty, imm & mask);
+    let high = pos.ins().iconst(half_ty, (imm >> bits) & mask);
+    pos.func.dfg.replace(inst).iconcat(low, high);
+    true
+}
+
+/// Narrow a `load` into loads of the two halves.
+///
+/// The halves are stored in little-endian order.
+fn narrow_load(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    _isa: &TargetIsa,
+) -> bool {
+    let (flags, ptr, offset) = match func.dfg[inst] {
+        ir::InstructionData::Load {
+            opcode: ir::Opcode::Load,
+            flags,
+            arg,
+            offset,
+        } => (flags, arg, offset),
+        _ => panic!("Expected load: {}", func.dfg.display_inst(inst, None)),
+    };
+    let ty = func.dfg.ctrl_typevar(inst);
+    let (half_ty, is_vector) = match narrow_halves(ty) {
+        Some(halves) => halves,
+        None => return false,
+    };
+    let offset: i32 = offset.into();
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+    let low = pos.ins().load(half_ty, flags, ptr, offset);
+    let high = pos
+        .ins()
+        .load(half_ty, flags, ptr, offset + half_ty.bytes() as i32);
+    if is_vector {
+        pos.func.dfg.replace(inst).vconcat(low, high);
+    } else {
+        pos.func.dfg.replace(inst).iconcat(low, high);
+    }
+    true
+}
+
+/// Narrow a `store` into stores of the two halves.
+///
+/// The halves are stored in little-endian order.
+fn narrow_store(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    cfg: &mut ControlFlowGraph,
+    _isa: &TargetIsa,
+) -> bool {
+    let (flags, val, ptr, offset) = match func.dfg[inst] {
+        ir::InstructionData::Store {
+            opcode: ir::Opcode::Store,
+            flags,
+            args,
+            offset,
+        } => (flags, args[0], args[1], offset),
+        _ => panic!("Expected store: {}", func.dfg.display_inst(inst, None)),
+    };
+    let ty = func.dfg.ctrl_typevar(inst);
+    let (half_ty, is_vector) = match narrow_halves(ty) {
+        Some(halves) => halves,
+        None => return false,
+    };
+    let offset: i32 = offset.into();
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+    let curpos = pos.position();
+    let srcloc = pos.srcloc();
+    let (low, high) = if is_vector {
+        split::vsplit(pos.func, cfg, curpos, srcloc, val)
+    } else {
+        split::isplit(pos.func, cfg, curpos, srcloc, val)
+    };
+    pos.ins().store(flags, low, ptr, offset);
+    pos.func
+        .dfg
+        .replace(inst)
+        .store(flags, high, ptr, offset + half_ty.bytes() as i32);
+    true
+}
+
+/// Narrow the controlling value of a `brz` or `brnz` instruction.
+///
+/// A split integer is zero when both halves are zero, so we can test the `bor` of the halves.
+fn narrow_brz(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    cfg: &mut ControlFlowGraph,
+    _isa: &TargetIsa,
+) -> bool {
+    let arg = match func.dfg[inst] {
+        ir::InstructionData::Branch {
+            opcode: ir::Opcode::Brz,
+            ref args,
+            ..
+        }
+        | ir::InstructionData::Branch {
+            opcode: ir::Opcode::Brnz,
+            ref args,
+            ..
+        } => args.first(&func.dfg.value_lists).unwrap(),
+        _ => panic!("Expected brz: {}", func.dfg.display_inst(inst, None)),
+    };
+    match narrow_halves(func.dfg.value_type(arg)) {
+        Some((_, false)) => {}
+        _ => return false,
+    }
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+    let curpos = pos.position();
+    let srcloc = pos.srcloc();
+    let (low, high) = split::isplit(pos.func, cfg, curpos, srcloc, arg);
+    let either = pos.ins().bor(low, high);
+    pos.func.dfg.inst_args_mut(inst)[0] = either;
+    true
 }
@@ -75,6 +75,8 @@ pub mod ir;
 pub mod isa;
 pub mod loop_analysis;
 pub mod print_errors;
+#[cfg(feature = "random-ir")]
+pub mod random_ir;
 pub mod settings;
 pub mod timing;
 pub mod verifier;
@@ -7,7 +7,7 @@ use ir::condcodes::{CondCode, FloatCC, IntCC};
 use ir::dfg::ValueDef;
 use ir::immediates::{Imm64, Offset32};
 use ir::instructions::{Opcode, ValueList};
-use ir::{Ebb, Function, Inst, InstBuilder, InstructionData, MemFlags, Type, Value};
+use ir::{DataFlowGraph, Ebb, Function, Inst, InstBuilder, InstructionData, MemFlags, Type, Value};
 use isa::TargetIsa;
 use timing;
 
@@ -319,6 +319,80 @@ fn optimize_complex_addresses(pos: &mut EncCursor, inst: Inst, isa: &TargetIsa)
     debug_assert!(ok);
 }
 
+/// If `y` is `x - 1` or `-x`, return the opcode of the instruction computing `x & y` in one go.
+fn lowest_bit_opcode(dfg: &DataFlowGraph, x: Value, y: Value) -> Option<Opcode> {
+    let y_inst = match dfg.value_def(y) {
+        ValueDef::Result(inst, _) => inst,
+        ValueDef::Param(_, _) => return None,
+    };
+    match dfg[y_inst] {
+        InstructionData::BinaryImm {
+            opcode: Opcode::IaddImm,
+            arg,
+            imm,
+        } if arg == x => {
+            let imm: i64 = imm.into();
+            if imm == -1 {
+                Some(Opcode::X86Blsr)
+            } else {
+                None
+            }
+        }
+        // `irsub_imm` is legalized to `isub` from a constant, so look for that.
+        InstructionData::Binary {
+            opcode: Opcode::Isub,
+            args,
+        } if args[1] == x => {
+            if let ValueDef::Result(zero_inst, _) = dfg.value_def(args[0]) {
+                if let InstructionData::UnaryImm {
+                    opcode: Opcode::Iconst,
+                    imm,
+                } = dfg[zero_inst]
+                {
+                    let imm: i64 = imm.into();
+                    if imm == 0 {
+                        return Some(Opcode::X86Blsi);
+                    }
+                }
+            }
+            None
+        }
+        _ => None,
+    }
+}
+
+/// Use a single instruction for the `x & (x - 1)` and `x & -x` bit manipulation idioms, which
+/// clear and extract the lowest set bit respectively. These are the x86 BMI1 `blsr` and `blsi`
+/// instructions.
+fn optimize_lowest_bit(pos: &mut EncCursor, inst: Inst, isa: &TargetIsa) {
+    let args = match pos.func.dfg[inst] {
+        InstructionData::Binary {
+            opcode: Opcode::Band,
+            args,
+        } => args,
+        _ => return,
+    };
+
+    // The operands of `band` commute, so try both orders.
+    let (opcode, arg) = if let Some(opcode) = lowest_bit_opcode(&pos.func.dfg, args[0], args[1]) {
+        (opcode, args[0])
+    } else if let Some(opcode) = lowest_bit_opcode(&pos.func.dfg, args[1], args[0]) {
+        (opcode, args[1])
+    } else {
+        return;
+    };
+
+    let ctrl_type = pos.func.dfg.ctrl_typevar(inst);
+    let data = InstructionData::Unary { opcode, arg };
+    if isa.encode(pos.func, &data, ctrl_type).is_err() {
+        return;
+    }
+
+    pos.func.dfg.replace(inst).Unary(opcode, ctrl_type, arg);
+    let ok = pos.func.update_encoding(inst, isa).is_ok();
+    debug_assert!(ok);
+}
+
 //----------------------------------------------------------------------
 //
 // The main post-opt pass.
@@ -329,6 +403,10 @@ pub fn do_postopt(func: &mut Function, isa: &TargetIsa) {
     while let Some(_ebb) = pos.next_ebb() {
         let mut last_flags_clobber = None;
         while let Some(inst) = pos.next_inst() {
+            if isa.uses_lowest_bit_instructions() {
+                optimize_lowest_bit(&mut pos, inst, isa);
+            }
+
             if isa.uses_cpu_flags() {
                 // Optimize instructions to make use of flags.
                 optimize_cpu_flags(&mut pos, inst, last_flags_clobber, isa);
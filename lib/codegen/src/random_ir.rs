@@ -0,0 +1,367 @@
+//! Random IR functions for fuzzing.
+//!
+//! This module builds valid functions from a sequence of random bytes, so a fuzzer can explore
+//! the compiler with well-typed code instead of raw bytes. The instructions come from tables
+//! generated by `cranelift-codegen-meta`: every base instruction which doesn't involve control
+//! flow, entities or CPU flags, with all the scalar types allowed by its type constraints. Only
+//! the instructions which the CPU mode of the target ISA encodes or legalizes are inserted.
+//!
+//! The functions are made of EBBs laid out in a chain: each EBB jumps to the next one, and may
+//! branch forward to any later EBB, passing values as EBB arguments. Every EBB is reachable and
+//! the code only uses values defined in the entry EBB or in the current one, so the generated
+//! functions pass the verifier.
+//!
+//! This module is only available with the `random-ir` feature.
+
+use cursor::{Cursor, FuncCursor};
+use flowgraph::ControlFlowGraph;
+use ir::immediates::{Ieee32, Ieee64, Imm64, Offset32};
+use ir::{self, types, AbiParam, Ebb, ExternalName, Function, InstBuilder, Signature, Type, Value};
+use isa::TargetIsa;
+use legalizer::legalize_function;
+use std::vec::Vec;
+
+#[cfg(feature = "rust-meta")]
+include!(concat!(env!("OUT_DIR"), "/random_ir.rs"));
+#[cfg(not(feature = "rust-meta"))]
+include!(concat!(env!("OUT_DIR"), "/new_random_ir.rs"));
+
+/// The types of the function and EBB parameters.
+const PARAM_TYPES: [Type; 7] = [
+    types::I8,
+    types::I16,
+    types::I32,
+    types::I64,
+    types::F32,
+    types::F64,
+    types::B1,
+];
+
+/// The maximum number of EBBs in a function.
+const MAX_EBBS: usize = 4;
+
+/// The maximum number of parameters of a function or an EBB.
+const MAX_PARAMS: usize = 4;
+
+/// The maximum number of instructions inserted in an EBB, before its branches.
+const MAX_INSTS: usize = 16;
+
+/// A source of random decisions, reading the bytes of a fuzzer input.
+///
+/// Once the input is exhausted, every decision reads zero bytes, so any input makes a function.
+pub struct Entropy<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Entropy<'a> {
+    /// Create a source of random decisions from the bytes `data`.
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn byte(&mut self) -> u8 {
+        match self.data.split_first() {
+            Some((&byte, rest)) => {
+                self.data = rest;
+                byte
+            }
+            None => 0,
+        }
+    }
+
+    fn bits(&mut self, bytes: usize) -> u64 {
+        (0..bytes).fold(0, |bits, _| bits << 8 | u64::from(self.byte()))
+    }
+
+    /// Get a number below `n`, which must be between 1 and 2^16.
+    pub fn below(&mut self, n: usize) -> usize {
+        debug_assert!(n > 0 && n <= 0x1_0000);
+        let bytes = if n <= 0x100 { 1 } else { 2 };
+        self.bits(bytes) as usize % n
+    }
+
+    /// Choose one of `options`, which must not be empty.
+    pub fn choose<T: Copy>(&mut self, options: &[T]) -> T {
+        options[self.below(options.len())]
+    }
+
+    /// Get a random boolean.
+    pub fn bool(&mut self) -> bool {
+        self.byte() & 1 != 0
+    }
+
+    /// Get a random immediate integer. Small numbers are the most likely, as they exercise the
+    /// special cases of the instruction selection.
+    pub fn imm64(&mut self) -> Imm64 {
+        let bytes = self.choose(&[1, 1, 2, 4, 8]);
+        Imm64::new(self.bits(bytes) as i64)
+    }
+
+    /// Get a random address offset.
+    pub fn offset32(&mut self) -> Offset32 {
+        let bytes = self.choose(&[1, 2, 4]);
+        Offset32::new(self.bits(bytes) as i32)
+    }
+
+    /// Get a random 32-bit floating point number, including NaNs and infinities.
+    pub fn ieee32(&mut self) -> Ieee32 {
+        Ieee32::with_bits(self.bits(4) as u32)
+    }
+
+    /// Get a random 64-bit floating point number, including NaNs and infinities.
+    pub fn ieee64(&mut self) -> Ieee64 {
+        Ieee64::with_bits(self.bits(8))
+    }
+}
+
+/// The values which can be used at the insertion point: the parameters of the function, the
+/// parameters of the current EBB and the results of its instructions.
+struct ValuePool {
+    values: Vec<Value>,
+}
+
+impl ValuePool {
+    /// Get a random value of type `ty`, materializing a constant at `pos` if there is none.
+    fn get(&mut self, pos: &mut FuncCursor, entropy: &mut Entropy, ty: Type) -> Value {
+        let values: Vec<Value> = self
+            .values
+            .iter()
+            .cloned()
+            .filter(|&value| pos.func.dfg.value_type(value) == ty)
+            .collect();
+        if !values.is_empty() {
+            return entropy.choose(&values);
+        }
+        let value = constant(pos, entropy, ty);
+        self.values.push(value);
+        value
+    }
+
+    /// Get random values of the types of the parameters of `ebb`.
+    fn args(&mut self, pos: &mut FuncCursor, entropy: &mut Entropy, ebb: Ebb) -> Vec<Value> {
+        let types: Vec<Type> = pos
+            .func
+            .dfg
+            .ebb_params(ebb)
+            .iter()
+            .map(|&param| pos.func.dfg.value_type(param))
+            .collect();
+        types
+            .into_iter()
+            .map(|ty| self.get(pos, entropy, ty))
+            .collect()
+    }
+}
+
+/// Insert a random constant of type `ty` at `pos`. Vector constants splat a random lane.
+fn constant(pos: &mut FuncCursor, entropy: &mut Entropy, ty: Type) -> Value {
+    if ty.is_vector() {
+        let lane = constant(pos, entropy, ty.lane_type());
+        pos.ins().splat(ty, lane)
+    } else if ty.is_int() {
+        // Sign-extend the immediate from the width of `ty`.
+        let shift = 64 - ty.bits();
+        let imm: i64 = entropy.imm64().into();
+        pos.ins().iconst(ty, (imm << shift) >> shift)
+    } else if ty.is_bool() {
+        let imm = entropy.bool();
+        pos.ins().bconst(ty, imm)
+    } else if ty == types::F32 {
+        let imm = entropy.ieee32();
+        pos.ins().f32const(imm)
+    } else if ty == types::F64 {
+        let imm = entropy.ieee64();
+        pos.ins().f64const(imm)
+    } else {
+        panic!("no constants of type {}", ty)
+    }
+}
+
+/// Get the name of the CPU mode of `isa`, as in the meta definitions.
+fn cpu_mode(isa: &TargetIsa) -> &'static str {
+    match (isa.name(), isa.pointer_bits()) {
+        ("riscv", 32) => "RV32",
+        ("riscv", _) => "RV64",
+        ("x86", 32) => "I32",
+        ("x86", _) => "I64",
+        ("arm32", _) => "A32",
+        ("arm64", _) => "A64",
+        _ => "",
+    }
+}
+
+/// Generate a random function for `isa`.
+///
+/// The function only contains instructions that the CPU mode of `isa` can encode or legalize, as
+/// found from the meta definitions. Instruction predicates and ISA settings aren't taken into
+/// account though; use `is_legalizable` to skip the functions which can't be compiled.
+pub fn random_function(isa: &TargetIsa, entropy: &mut Entropy) -> Function {
+    let mut sig = Signature::new(isa.flags().call_conv());
+    for _ in 0..entropy.below(MAX_PARAMS + 1) {
+        sig.params.push(AbiParam::new(entropy.choose(&PARAM_TYPES)));
+    }
+    if entropy.bool() {
+        sig.returns
+            .push(AbiParam::new(entropy.choose(&PARAM_TYPES)));
+    }
+    let mut func = Function::with_name_signature(ExternalName::user(0, 0), sig);
+
+    // Create the EBBs with their parameters upfront, so they can be branched to.
+    let mut ebbs = Vec::new();
+    for i in 0..1 + entropy.below(MAX_EBBS) {
+        let ebb = func.dfg.make_ebb();
+        if i == 0 {
+            for param in func.signature.params.clone() {
+                func.dfg.append_ebb_param(ebb, param.value_type);
+            }
+        } else {
+            for _ in 0..entropy.below(MAX_PARAMS + 1) {
+                func.dfg.append_ebb_param(ebb, entropy.choose(&PARAM_TYPES));
+            }
+        }
+        func.layout.append_ebb(ebb);
+        ebbs.push(ebb);
+    }
+
+    let candidates = supported_candidates(isa.name(), cpu_mode(isa));
+    let entry_params = func.dfg.ebb_params(ebbs[0]).to_vec();
+    let mut pos = FuncCursor::new(&mut func);
+    for (i, &ebb) in ebbs.iter().enumerate() {
+        pos.goto_bottom(ebb);
+        let mut values = ValuePool {
+            values: entry_params.clone(),
+        };
+        if i > 0 {
+            values
+                .values
+                .extend_from_slice(pos.func.dfg.ebb_params(ebb));
+        }
+
+        if !candidates.is_empty() {
+            for _ in 0..entropy.below(MAX_INSTS + 1) {
+                let candidate = entropy.choose(candidates);
+                let inst = insert_candidate(candidate, &mut pos, &mut values, entropy);
+                values
+                    .values
+                    .extend_from_slice(pos.func.dfg.inst_results(inst));
+            }
+        }
+
+        if i + 1 == ebbs.len() {
+            let returns: Vec<Type> = pos
+                .func
+                .signature
+                .returns
+                .iter()
+                .map(|param| param.value_type)
+                .collect();
+            let rvals: Vec<Value> = returns
+                .into_iter()
+                .map(|ty| values.get(&mut pos, entropy, ty))
+                .collect();
+            pos.ins().return_(&rvals);
+            continue;
+        }
+
+        // Branch forward to a random EBB, then jump to the next one.
+        if entropy.bool() {
+            let target = ebbs[i + 1 + entropy.below(ebbs.len() - i - 1)];
+            let cond_type = entropy.choose(&[types::B1, types::I32, types::I64]);
+            let cond = values.get(&mut pos, entropy, cond_type);
+            let args = values.args(&mut pos, entropy, target);
+            if entropy.bool() {
+                pos.ins().brz(cond, target, &args);
+            } else {
+                pos.ins().brnz(cond, target, &args);
+            }
+        }
+        let next = ebbs[i + 1];
+        let args = values.args(&mut pos, entropy, next);
+        pos.ins().jump(next, &args);
+    }
+
+    func
+}
+
+/// Check if all the instructions of `func` can be encoded by `isa` after legalization.
+///
+/// The functions which fail this check use instructions without encodings or legalizations for
+/// the settings of `isa`, which the compiler doesn't support.
+pub fn is_legalizable(func: &Function, isa: &TargetIsa) -> bool {
+    let mut func = func.clone();
+    let mut cfg = ControlFlowGraph::with_function(&func);
+    legalize_function(&mut func, &mut cfg, isa);
+    func.layout
+        .ebbs()
+        .flat_map(|ebb| func.layout.ebb_insts(ebb))
+        .all(|inst| func.encodings[inst].is_legal())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use isa;
+    use settings::{self, Configurable};
+    use std::str::FromStr;
+    use target_lexicon::Triple;
+    use verifier::verify_function;
+    use Context;
+
+    /// The number of functions generated for each ISA.
+    const NUM_SEEDS: u64 = 1000;
+
+    /// Get the deterministic pseudo-random input bytes of `seed`.
+    fn input(seed: u64) -> Vec<u8> {
+        // A xorshift generator, so the inputs don't depend on an external crate.
+        let mut state = seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1;
+        (0..seed % 64)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn verify_and_compile() {
+        let mut flag_builder = settings::builder();
+        flag_builder.enable("enable_verifier").unwrap();
+        let flags = settings::Flags::new(flag_builder);
+
+        for name in &["riscv32", "riscv64", "i686", "x86_64", "arm", "aarch64"] {
+            let mut isa_builder = match isa::lookup(Triple::from_str(name).unwrap()) {
+                Ok(isa_builder) => isa_builder,
+                Err(_) => continue,
+            };
+            // RISC-V has no registers for the float values without the F and D extensions.
+            if name.starts_with("riscv") {
+                for flag in &["supports_m", "supports_f", "supports_d"] {
+                    isa_builder.enable(flag).unwrap();
+                }
+            }
+            let isa = isa_builder.finish(flags.clone());
+
+            for seed in 0..NUM_SEEDS {
+                let func = random_function(&*isa, &mut Entropy::new(&input(seed)));
+                if let Err(errors) = verify_function(&func, &*isa) {
+                    panic!(
+                        "{} seed {}: {}\n{}",
+                        name,
+                        seed,
+                        errors,
+                        func.display(&*isa)
+                    );
+                }
+                if !is_legalizable(&func, &*isa) {
+                    continue;
+                }
+                let mut ctx = Context::for_function(func.clone());
+                if let Err(err) = ctx.compile(&*isa) {
+                    panic!("{} seed {}: {}\n{}", name, seed, err, func.display(&*isa));
+                }
+            }
+        }
+    }
+}
@@ -1,5 +1,6 @@
 //! Result and error types representing the outcome of compiling a function.
 
+use std::string::String;
 use verifier::VerifierError;
 
 /// A compilation error.
@@ -29,6 +30,10 @@ pub enum CodegenError {
     /// is exceeded, compilation fails.
     #[fail(display = "Code for function is too large")]
     CodeTooLarge,
+
+    /// The function uses a feature that the target ISA doesn't support with the current settings.
+    #[fail(display = "Unsupported feature: {}", _0)]
+    Unsupported(String),
 }
 
 /// A convenient alias for a `Result` that uses `CodegenError` as the error type.
@@ -40,6 +40,8 @@
 //!   function.
 //! - All return instructions must have return value operands matching the current
 //!   function signature.
+//! - Only atomic memory operations can have a memory ordering, and atomic loads and stores
+//!   can't have the orderings that only apply to the other direction.
 //!
 //! Global values
 //!
@@ -65,8 +67,8 @@ use ir;
 use ir::entities::AnyEntity;
 use ir::instructions::{BranchInfo, CallInfo, InstructionFormat, ResolvedConstraint};
 use ir::{
-    types, ArgumentLoc, Ebb, FuncRef, Function, GlobalValue, Inst, JumpTable, Opcode, SigRef,
-    StackSlot, StackSlotKind, Type, Value, ValueDef, ValueList, ValueLoc,
+    types, ArgumentLoc, AtomicOrdering, Ebb, FuncRef, Function, GlobalValue, Inst, JumpTable,
+    Opcode, SigRef, StackSlot, StackSlotKind, Type, Value, ValueDef, ValueList, ValueLoc,
 };
 use isa::TargetIsa;
 use iterators::IteratorExtras;
@@ -378,6 +380,7 @@ impl<'a> Verifier<'a> {
             | FloatCond { .. }
             | IntSelect { .. }
             | Load { .. }
+            | AtomicCas { .. }
             | Store { .. }
             | RegMove { .. }
             | CopySpecial { .. }
@@ -966,6 +969,40 @@ impl<'a> Verifier<'a> {
         Ok(())
     }
 
+    /// Check the memory ordering of memory operations.
+    fn verify_memory_ordering(&self, inst: Inst) -> VerifierResult<()> {
+        let (opcode, flags) = match self.func.dfg[inst] {
+            ir::InstructionData::Load { opcode, flags, .. }
+            | ir::InstructionData::LoadComplex { opcode, flags, .. }
+            | ir::InstructionData::Store { opcode, flags, .. }
+            | ir::InstructionData::StoreComplex { opcode, flags, .. }
+            | ir::InstructionData::AtomicCas { opcode, flags, .. } => (opcode, flags),
+            _ => return Ok(()),
+        };
+        let ordering = match flags.ordering() {
+            Some(ordering) => ordering,
+            None => return Ok(()),
+        };
+        match (opcode, ordering) {
+            (Opcode::AtomicLoad, AtomicOrdering::Release)
+            | (Opcode::AtomicLoad, AtomicOrdering::AcqRel)
+            | (Opcode::AtomicStore, AtomicOrdering::Acquire)
+            | (Opcode::AtomicStore, AtomicOrdering::AcqRel) => {
+                err!(inst, "{} can't have {} ordering", opcode, ordering)
+            }
+            (Opcode::AtomicLoad, _)
+            | (Opcode::AtomicStore, _)
+            | (Opcode::AtomicAdd, _)
+            | (Opcode::AtomicSub, _)
+            | (Opcode::AtomicAnd, _)
+            | (Opcode::AtomicOr, _)
+            | (Opcode::AtomicXor, _)
+            | (Opcode::AtomicXchg, _)
+            | (Opcode::AtomicCas, _) => Ok(()),
+            _ => err!(inst, "{} isn't atomic and can't have a memory ordering", opcode),
+        }
+    }
+
     fn cfg_integrity(&self, cfg: &ControlFlowGraph) -> VerifierResult<()> {
         let mut expected_succs = BTreeSet::<Ebb>::new();
         let mut got_succs = BTreeSet::<Ebb>::new();
@@ -1145,6 +1182,7 @@ impl<'a> Verifier<'a> {
                 self.ebb_integrity(ebb, inst)?;
                 self.instruction_integrity(inst)?;
                 self.typecheck(inst)?;
+                self.verify_memory_ordering(inst)?;
                 self.verify_encoding(inst)?;
             }
         }
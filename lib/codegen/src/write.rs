@@ -460,6 +460,9 @@ pub fn write_operands(
                 offset
             )
         }
+        AtomicCas { flags, args, .. } => {
+            write!(w, "{} {}, {}, {}", flags, args[0], args[1], args[2])
+        }
         RegMove { arg, src, dst, .. } => {
             if let Some(isa) = isa {
                 let regs = isa.register_info();
@@ -0,0 +1,96 @@
+//! Compile the instructions of the example extension of `cranelift-codegen-extensions`.
+//!
+//! These tests only run with the `example-extension` feature:
+//!
+//!     cargo test --features example-extension --test extension
+
+#![cfg(feature = "example-extension")]
+
+extern crate cranelift_codegen;
+extern crate target_lexicon;
+
+use cranelift_codegen::binemit::{Addend, CodeOffset, NullTrapSink, Reloc, RelocSink};
+use cranelift_codegen::cursor::{Cursor, FuncCursor};
+use cranelift_codegen::ir::{
+    types, AbiParam, ExternalName, Function, InstBuilder, JumpTable, Opcode, Signature,
+};
+use cranelift_codegen::isa;
+use cranelift_codegen::settings::{self, CallConv, Configurable};
+use cranelift_codegen::Context;
+use std::str::FromStr;
+use target_lexicon::Triple;
+
+struct PanicRelocSink;
+
+impl RelocSink for PanicRelocSink {
+    fn reloc_ebb(&mut self, _: CodeOffset, _: Reloc, _: CodeOffset) {
+        panic!("unexpected relocation");
+    }
+
+    fn reloc_external(&mut self, _: CodeOffset, _: Reloc, _: &ExternalName, _: Addend) {
+        panic!("unexpected relocation");
+    }
+
+    fn reloc_jt(&mut self, _: CodeOffset, _: Reloc, _: JumpTable) {
+        panic!("unexpected relocation");
+    }
+}
+
+/// Build `function %f(i32, i32, i32) -> i32` polling for a safepoint and
+/// adding its three arguments.
+fn example_function() -> Function {
+    let mut sig = Signature::new(CallConv::SystemV);
+    sig.params = vec![AbiParam::new(types::I32); 3];
+    sig.returns.push(AbiParam::new(types::I32));
+    let mut func = Function::with_name_signature(ExternalName::testcase("f"), sig);
+
+    let ebb = func.dfg.make_ebb();
+    let args = (0..3)
+        .map(|_| func.dfg.append_ebb_param(ebb, types::I32))
+        .collect::<Vec<_>>();
+    let mut pos = FuncCursor::new(&mut func);
+    pos.insert_ebb(ebb);
+    pos.ins().safepoint_poll();
+    let sum = pos.ins().iadd3(args[0], args[1], args[2]);
+    pos.ins().return_(&[sum]);
+    func
+}
+
+#[test]
+fn opcodes() {
+    assert_eq!(
+        Opcode::from_str("safepoint_poll"),
+        Ok(Opcode::SafepointPoll)
+    );
+    assert_eq!(Opcode::Iadd3.to_string(), "iadd3");
+    assert!(Opcode::SafepointPoll.other_side_effects());
+}
+
+#[test]
+fn compile_x86_64() {
+    let mut flag_builder = settings::builder();
+    flag_builder.enable("is_pic").unwrap();
+    let isa = isa::lookup(Triple::from_str("x86_64").unwrap())
+        .unwrap()
+        .finish(settings::Flags::new(flag_builder));
+
+    let mut ctx = Context::for_function(example_function());
+    let mut code = Vec::new();
+    ctx.compile_and_emit(&*isa, &mut code, &mut PanicRelocSink, &mut NullTrapSink {})
+        .unwrap();
+
+    // The three-way addition is legalized into two additions.
+    let opcodes = ctx
+        .func
+        .layout
+        .ebbs()
+        .flat_map(|ebb| ctx.func.layout.ebb_insts(ebb))
+        .map(|inst| ctx.func.dfg[inst].opcode())
+        .collect::<Vec<_>>();
+    assert!(!opcodes.contains(&Opcode::Iadd3));
+    assert_eq!(opcodes.iter().filter(|&&op| op == Opcode::Iadd).count(), 2);
+
+    // The safepoint poll is encoded as `pause`.
+    assert!(opcodes.contains(&Opcode::SafepointPoll));
+    assert!(code.windows(2).any(|bytes| bytes == [0xf3, 0x90]));
+}
@@ -80,6 +80,19 @@ impl FaerieBuilder {
             ir::LibCall::TruncF64 => "trunc".to_owned(),
             ir::LibCall::NearestF32 => "nearbyintf".to_owned(),
             ir::LibCall::NearestF64 => "nearbyint".to_owned(),
+            // The libgcc routines for 64-bit integers don't trap and don't reduce shift amounts
+            // modulo 64. The legalizer checks the operands before calling them.
+            ir::LibCall::UdivI64 => "__udivdi3".to_owned(),
+            ir::LibCall::SdivI64 => "__divdi3".to_owned(),
+            ir::LibCall::UremI64 => "__umoddi3".to_owned(),
+            ir::LibCall::SremI64 => "__moddi3".to_owned(),
+            ir::LibCall::IshlI64 => "__ashldi3".to_owned(),
+            ir::LibCall::UshrI64 => "__lshrdi3".to_owned(),
+            ir::LibCall::SshrI64 => "__ashrdi3".to_owned(),
+            ir::LibCall::FcvtToSintI64 => "__fixdfdi".to_owned(),
+            ir::LibCall::FcvtToUintI64 => "__fixunsdfdi".to_owned(),
+            ir::LibCall::FcvtFromSintF32 => "__floatdisf".to_owned(),
+            ir::LibCall::FcvtFromSintF64 => "__floatdidf".to_owned(),
         })
     }
 }
@@ -818,73 +818,135 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
         Operator::F32Le | Operator::F64Le => {
             translate_fcmp(FloatCC::LessThanOrEqual, builder, state)
         }
+        /****************************** Atomic instructions **********************************
+         * Only the full-width operators are supported. All accesses are sequentially consistent,
+         * and trap when they are misaligned.
+         ************************************************************************************/
+        Operator::I32AtomicLoad {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_load(offset, ir::Opcode::AtomicLoad, I32, builder, state, environ);
+        }
+        Operator::I64AtomicLoad {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_load(offset, ir::Opcode::AtomicLoad, I64, builder, state, environ);
+        }
+        Operator::I32AtomicStore {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64AtomicStore {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_store(offset, ir::Opcode::AtomicStore, builder, state, environ);
+        }
+        Operator::I32AtomicRmwAdd {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64AtomicRmwAdd {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_rmw(offset, ir::Opcode::AtomicAdd, builder, state, environ);
+        }
+        Operator::I32AtomicRmwSub {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64AtomicRmwSub {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_rmw(offset, ir::Opcode::AtomicSub, builder, state, environ);
+        }
+        Operator::I32AtomicRmwAnd {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64AtomicRmwAnd {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_rmw(offset, ir::Opcode::AtomicAnd, builder, state, environ);
+        }
+        Operator::I32AtomicRmwOr {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64AtomicRmwOr {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_rmw(offset, ir::Opcode::AtomicOr, builder, state, environ);
+        }
+        Operator::I32AtomicRmwXor {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64AtomicRmwXor {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_rmw(offset, ir::Opcode::AtomicXor, builder, state, environ);
+        }
+        Operator::I32AtomicRmwXchg {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64AtomicRmwXchg {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_rmw(offset, ir::Opcode::AtomicXchg, builder, state, environ);
+        }
+        Operator::I32AtomicRmwCmpxchg {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64AtomicRmwCmpxchg {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_cas(offset, builder, state, environ);
+        }
         Operator::Wake { .. }
         | Operator::I32Wait { .. }
         | Operator::I64Wait { .. }
-        | Operator::I32AtomicLoad { .. }
-        | Operator::I64AtomicLoad { .. }
         | Operator::I32AtomicLoad8U { .. }
         | Operator::I32AtomicLoad16U { .. }
         | Operator::I64AtomicLoad8U { .. }
         | Operator::I64AtomicLoad16U { .. }
         | Operator::I64AtomicLoad32U { .. }
-        | Operator::I32AtomicStore { .. }
-        | Operator::I64AtomicStore { .. }
         | Operator::I32AtomicStore8 { .. }
         | Operator::I32AtomicStore16 { .. }
         | Operator::I64AtomicStore8 { .. }
         | Operator::I64AtomicStore16 { .. }
         | Operator::I64AtomicStore32 { .. }
-        | Operator::I32AtomicRmwAdd { .. }
-        | Operator::I64AtomicRmwAdd { .. }
         | Operator::I32AtomicRmw8UAdd { .. }
         | Operator::I32AtomicRmw16UAdd { .. }
         | Operator::I64AtomicRmw8UAdd { .. }
         | Operator::I64AtomicRmw16UAdd { .. }
         | Operator::I64AtomicRmw32UAdd { .. }
-        | Operator::I32AtomicRmwSub { .. }
-        | Operator::I64AtomicRmwSub { .. }
         | Operator::I32AtomicRmw8USub { .. }
         | Operator::I32AtomicRmw16USub { .. }
         | Operator::I64AtomicRmw8USub { .. }
         | Operator::I64AtomicRmw16USub { .. }
         | Operator::I64AtomicRmw32USub { .. }
-        | Operator::I32AtomicRmwAnd { .. }
-        | Operator::I64AtomicRmwAnd { .. }
         | Operator::I32AtomicRmw8UAnd { .. }
         | Operator::I32AtomicRmw16UAnd { .. }
         | Operator::I64AtomicRmw8UAnd { .. }
         | Operator::I64AtomicRmw16UAnd { .. }
         | Operator::I64AtomicRmw32UAnd { .. }
-        | Operator::I32AtomicRmwOr { .. }
-        | Operator::I64AtomicRmwOr { .. }
         | Operator::I32AtomicRmw8UOr { .. }
         | Operator::I32AtomicRmw16UOr { .. }
         | Operator::I64AtomicRmw8UOr { .. }
         | Operator::I64AtomicRmw16UOr { .. }
         | Operator::I64AtomicRmw32UOr { .. }
-        | Operator::I32AtomicRmwXor { .. }
-        | Operator::I64AtomicRmwXor { .. }
         | Operator::I32AtomicRmw8UXor { .. }
         | Operator::I32AtomicRmw16UXor { .. }
         | Operator::I64AtomicRmw8UXor { .. }
         | Operator::I64AtomicRmw16UXor { .. }
         | Operator::I64AtomicRmw32UXor { .. }
-        | Operator::I32AtomicRmwXchg { .. }
-        | Operator::I64AtomicRmwXchg { .. }
         | Operator::I32AtomicRmw8UXchg { .. }
         | Operator::I32AtomicRmw16UXchg { .. }
         | Operator::I64AtomicRmw8UXchg { .. }
         | Operator::I64AtomicRmw16UXchg { .. }
         | Operator::I64AtomicRmw32UXchg { .. }
-        | Operator::I32AtomicRmwCmpxchg { .. }
-        | Operator::I64AtomicRmwCmpxchg { .. }
         | Operator::I32AtomicRmw8UCmpxchg { .. }
         | Operator::I32AtomicRmw16UCmpxchg { .. }
         | Operator::I64AtomicRmw8UCmpxchg { .. }
         | Operator::I64AtomicRmw16UCmpxchg { .. }
         | Operator::I64AtomicRmw32UCmpxchg { .. } => {
-            return Err(WasmError::Unsupported("proposed thread operators"));
+            return Err(WasmError::Unsupported(
+                "narrow atomics and wait/wake operators",
+            ));
         }
     };
     Ok(())
@@ -1012,6 +1074,28 @@ fn get_heap_addr(
     }
 }
 
+/// Trap if the effective address of an atomic access of type `ty` isn't a multiple of its size.
+///
+/// The `offset` is the one returned by `get_heap_addr`. It can differ from the Wasm offset by
+/// 2^31, which doesn't change the alignment.
+fn check_atomic_alignment(
+    addr32: ir::Value,
+    offset: i32,
+    ty: Type,
+    builder: &mut FunctionBuilder<Variable>,
+) {
+    // Only the low bits of the effective address matter, so the addition can wrap.
+    let addr = if offset == 0 {
+        addr32
+    } else {
+        builder.ins().iadd_imm(addr32, i64::from(offset))
+    };
+    let misaligned = builder.ins().band_imm(addr, i64::from(ty.bytes()) - 1);
+    builder
+        .ins()
+        .trapnz(misaligned, ir::TrapCode::HeapMisaligned);
+}
+
 /// Translate a load instruction.
 fn translate_load<FE: FuncEnvironment + ?Sized>(
     offset: u32,
@@ -1025,6 +1109,9 @@ fn translate_load<FE: FuncEnvironment + ?Sized>(
     // We don't yet support multiple linear memories.
     let heap = state.get_heap(builder.func, 0, environ);
     let (base, offset) = get_heap_addr(heap, addr32, offset, environ.native_pointer(), builder);
+    if opcode == ir::Opcode::AtomicLoad {
+        check_atomic_alignment(addr32, offset, result_ty, builder);
+    }
     // Note that we don't set `is_aligned` here, even if the load instruction's
     // alignment immediate says it's aligned, because WebAssembly's immediate
     // field is just a hint, while Cranelift's aligned flag needs a guarantee.
@@ -1049,6 +1136,9 @@ fn translate_store<FE: FuncEnvironment + ?Sized>(
     // We don't yet support multiple linear memories.
     let heap = state.get_heap(builder.func, 0, environ);
     let (base, offset) = get_heap_addr(heap, addr32, offset, environ.native_pointer(), builder);
+    if opcode == ir::Opcode::AtomicStore {
+        check_atomic_alignment(addr32, offset, val_ty, builder);
+    }
     // See the comments in `translate_load` about the flags.
     let flags = MemFlags::new();
     builder
@@ -1056,6 +1146,55 @@ fn translate_store<FE: FuncEnvironment + ?Sized>(
         .Store(opcode, val_ty, flags, offset.into(), val, base);
 }
 
+/// Translate an atomic read-modify-write instruction.
+fn translate_atomic_rmw<FE: FuncEnvironment + ?Sized>(
+    offset: u32,
+    opcode: ir::Opcode,
+    builder: &mut FunctionBuilder<Variable>,
+    state: &mut TranslationState,
+    environ: &mut FE,
+) {
+    let (addr32, val) = state.pop2();
+    let val_ty = builder.func.dfg.value_type(val);
+
+    // We don't yet support multiple linear memories.
+    let heap = state.get_heap(builder.func, 0, environ);
+    let (base, offset) = get_heap_addr(heap, addr32, offset, environ.native_pointer(), builder);
+    check_atomic_alignment(addr32, offset, val_ty, builder);
+    // See the comments in `translate_load` about the flags.
+    let flags = MemFlags::new();
+    let (rmw, dfg) = builder
+        .ins()
+        .Store(opcode, val_ty, flags, offset.into(), val, base);
+    state.push1(dfg.first_result(rmw));
+}
+
+/// Translate an atomic compare-and-exchange instruction.
+fn translate_atomic_cas<FE: FuncEnvironment + ?Sized>(
+    offset: u32,
+    builder: &mut FunctionBuilder<Variable>,
+    state: &mut TranslationState,
+    environ: &mut FE,
+) {
+    let (addr32, expected, replacement) = state.pop3();
+
+    // We don't yet support multiple linear memories.
+    let heap = state.get_heap(builder.func, 0, environ);
+    let (base, offset) = get_heap_addr(heap, addr32, offset, environ.native_pointer(), builder);
+    let ty = builder.func.dfg.value_type(expected);
+    check_atomic_alignment(addr32, offset, ty, builder);
+    // `atomic_cas` doesn't take an offset immediate.
+    let addr = if offset == 0 {
+        base
+    } else {
+        builder.ins().iadd_imm(base, i64::from(offset))
+    };
+    // See the comments in `translate_load` about the flags.
+    let flags = MemFlags::new();
+    let old = builder.ins().atomic_cas(flags, expected, replacement, addr);
+    state.push1(old);
+}
+
 fn translate_icmp(
     cc: IntCC,
     builder: &mut FunctionBuilder<Variable>,
@@ -65,6 +65,14 @@ cargo build
 banner "Rust unit tests"
 cargo test --all
 
+# Build the instructions of the example extension into cranelift-codegen.
+banner "Rust extension tests"
+(cd lib/codegen && cargo test --features example-extension --test extension)
+
+# Compile random functions for every ISA.
+banner "Rust random IR tests"
+(cd lib/codegen && cargo test --features random-ir --lib random_ir)
+
 # Make sure the documentation builds.
 banner "Rust documentation: $topdir/target/doc/cranelift/index.html"
 cargo doc